@@ -4,7 +4,14 @@ fn main() {
     // TODO: Improve java compilation in build script
     let mut echo_hello = Command::new("javac");
     echo_hello.args(["-source", "1.8", "-target", "1.8"]).arg("Main.java");
-    let output = echo_hello.output().unwrap();
+    // Without a JDK there is nothing to compile with, the VM can still run on its stub library
+    let output = match echo_hello.output() {
+        Ok(output) => output,
+        Err(error) => {
+            println!("cargo:warning=Main.java not compiled, javac could not be run: {}", error);
+            return;
+        }
+    };
     println!("status: {}", output.status);
     println!("stdout: {}", String::from_utf8_lossy(&output.stdout));
     println!("stderr: {}", String::from_utf8_lossy(&output.stderr));
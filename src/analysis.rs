@@ -0,0 +1,552 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap, VecDeque}, fmt::{self, Write}};
+
+use crate::{class_loader::{ClassFlags, ClassInfo, ConstantPool, ConstantPoolItem, MethodFlags}, class_path::ClassPath,
+    control_flow::escape, opcodes::*};
+
+/// A method by its class, name and descriptor, written like `a.b.C.brol(I)V`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct MethodId {
+    pub class: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl fmt::Display for MethodId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}{}", java_name(&self.class), self.name, self.descriptor)
+    }
+}
+
+/// A class or interface with its direct supertypes and the methods it declares.
+pub struct ClassNode {
+    pub super_class: Option<String>,
+    pub interfaces: Vec<String>,
+    pub is_interface: bool,
+    // The flags of the methods by name and descriptor
+    pub methods: BTreeMap<(String, String), MethodFlags>,
+}
+
+impl ClassNode {
+    fn new(class_info: &ClassInfo) -> ClassNode {
+        let constant_pool = &class_info.constant_pool;
+        let class_name = |index: u16| constant_pool.get_string(constant_pool.get_class_info(index)).to_string();
+        ClassNode {
+            super_class: (class_info.super_class != 0).then(|| class_name(class_info.super_class)),
+            interfaces: class_info.interfaces.iter().map(|index| class_name(*index)).collect(),
+            is_interface: class_info.access_flags.contains(ClassFlags::ACC_INTERFACE),
+            methods: class_info.methods.iter().map(|method| {
+                let key = (method.get_name(constant_pool).to_string(), constant_pool.get_string(method.descriptor_index).to_string());
+                (key, method.access_flags)
+            }).collect(),
+        }
+    }
+
+    fn supertypes(&self) -> impl Iterator<Item = &String> {
+        self.super_class.iter().chain(&self.interfaces)
+    }
+
+    /// Returns whether the class has a method that calls through its supertypes can end up in.
+    fn overrides(&self, name: &str, descriptor: &str) -> bool {
+        self.methods.get(&(name.to_string(), descriptor.to_string()))
+            .is_some_and(|flags| !flags.intersects(MethodFlags::ACC_STATIC | MethodFlags::ACC_PRIVATE))
+    }
+}
+
+/// The class hierarchy and the call graph of the classes on a classpath.
+/// Calls are resolved with class hierarchy analysis: a call of a virtual or interface method can go to the method it
+/// resolves to, and to every method of the classes on the classpath that overrides it. The methods that invokedynamic
+/// gets as method handles, like the ones that implement lambdas, count as called too.
+pub struct Analysis {
+    pub classes: BTreeMap<String, ClassNode>,
+    // The supertypes of the classes that aren't on the classpath but in the library
+    pub library_classes: BTreeMap<String, ClassNode>,
+    pub calls: BTreeMap<MethodId, BTreeSet<MethodId>>,
+    pub entry_points: BTreeSet<MethodId>,
+    // Classes that are in neither the classpath nor the library, with the classes that extend or implement them
+    pub missing_supertypes: BTreeMap<String, BTreeSet<String>>,
+    // Classes that are in neither the classpath nor the library, with the methods that call into them
+    pub missing_calls: BTreeMap<String, BTreeSet<MethodId>>,
+}
+
+/// A method that a method refers to: by an invoke instruction, or by a method handle with its reference kind.
+struct Reference {
+    opcode: u8,
+    owner: String,
+    name: String,
+    descriptor: String,
+}
+
+impl Analysis {
+    /// Analyzes classes, looking up the classes they use outside of them in the library, like the JDK.
+    /// The entry points are the main methods, the static initializers, the methods that the library can call because
+    /// they override one of its methods, and the methods of the classes or the methods named in `entries`, which are
+    /// written like `a.b.C` or `a.b.C.brol`.
+    pub fn build(class_infos: &[ClassInfo], library: &ClassPath, entries: &[String]) -> Result<Analysis, String> {
+        let mut analysis = Analysis {
+            classes: BTreeMap::new(),
+            library_classes: BTreeMap::new(),
+            calls: BTreeMap::new(),
+            entry_points: BTreeSet::new(),
+            missing_supertypes: BTreeMap::new(),
+            missing_calls: BTreeMap::new(),
+        };
+        let mut references = Vec::new();
+        for class_info in class_infos {
+            let constant_pool = &class_info.constant_pool;
+            let class_name = constant_pool.get_string(constant_pool.get_class_info(class_info.this_class)).to_string();
+            // The first class on the classpath wins, like when they are loaded
+            if analysis.classes.contains_key(&class_name) {
+                continue;
+            }
+            for method in &class_info.methods {
+                let caller = MethodId {
+                    class: class_name.clone(),
+                    name: method.get_name(constant_pool).to_string(),
+                    descriptor: constant_pool.get_string(method.descriptor_index).to_string(),
+                };
+                if let Some(code) = method.get_code() {
+                    references.push((caller, method_references(class_info, &code.code)));
+                }
+            }
+            analysis.classes.insert(class_name, ClassNode::new(class_info));
+        }
+
+        let mut unknown = BTreeMap::new();
+        let class_names: Vec<String> = analysis.classes.keys().cloned().collect();
+        for class_name in &class_names {
+            let supertypes: Vec<String> = analysis.classes[class_name].supertypes().cloned().collect();
+            for supertype in supertypes {
+                if !analysis.load(&supertype, library, &mut unknown) {
+                    analysis.missing_supertypes.entry(supertype).or_default().insert(class_name.clone());
+                }
+            }
+        }
+        // Every class on the classpath with the classes on the classpath that are subtypes of it, including itself
+        let mut subtypes: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for class_name in &class_names {
+            for supertype in analysis.ancestors(class_name) {
+                subtypes.entry(supertype).or_default().insert(class_name.clone());
+            }
+        }
+
+        for (caller, method_references) in references {
+            let mut callees = BTreeSet::new();
+            for reference in method_references {
+                // The methods of arrays, like clone, are in Object
+                if reference.owner.starts_with('[') {
+                    continue;
+                }
+                if !analysis.load(&reference.owner, library, &mut unknown) {
+                    analysis.missing_calls.entry(reference.owner).or_default().insert(caller.clone());
+                    continue;
+                }
+                if let Some(declaring_class) = analysis.resolve(&reference.owner, &reference.name, &reference.descriptor)
+                    && analysis.classes.contains_key(&declaring_class) {
+                    callees.insert(MethodId { class: declaring_class, name: reference.name.clone(), descriptor: reference.descriptor.clone() });
+                }
+                if matches!(reference.opcode, INVOKEVIRTUAL | INVOKEINTERFACE) {
+                    for subtype in subtypes.get(&reference.owner).into_iter().flatten() {
+                        if analysis.classes[subtype].overrides(&reference.name, &reference.descriptor) {
+                            callees.insert(MethodId { class: subtype.clone(), name: reference.name.clone(), descriptor: reference.descriptor.clone() });
+                        }
+                    }
+                }
+            }
+            analysis.calls.insert(caller, callees);
+        }
+
+        for class_name in &class_names {
+            // Classes with a missing supertype might override any of its methods
+            let ancestors = analysis.ancestors(class_name);
+            let library_supertypes: Vec<&ClassNode> = ancestors.iter().filter_map(|ancestor| analysis.library_classes.get(ancestor)).collect();
+            let has_missing_supertype = ancestors.iter().any(|ancestor| unknown.get(ancestor) == Some(&false));
+            for ((name, descriptor), flags) in &analysis.classes[class_name].methods {
+                let is_entry_point = name == "<clinit>"
+                    || name == "main" && descriptor == "([Ljava/lang/String;)V" && flags.contains(MethodFlags::ACC_PUBLIC | MethodFlags::ACC_STATIC)
+                    || name != "<init>" && analysis.classes[class_name].overrides(name, descriptor)
+                        && (has_missing_supertype || library_supertypes.iter().any(|supertype| supertype.overrides(name, descriptor)));
+                if is_entry_point {
+                    analysis.entry_points.insert(MethodId { class: class_name.clone(), name: name.clone(), descriptor: descriptor.clone() });
+                }
+            }
+        }
+        for entry in entries {
+            let internal_name = entry.replace('.', "/");
+            let (class_name, method_name) = match analysis.classes.contains_key(&internal_name) {
+                true => (internal_name, None),
+                false => match internal_name.rsplit_once('/') {
+                    Some((class_name, method_name)) if analysis.classes.contains_key(class_name) => (class_name.to_string(), Some(method_name)),
+                    other => return Err(format!("Entry point {} is not a class or method on the classpath", entry))
+                }
+            };
+            let methods: Vec<MethodId> = analysis.classes[&class_name].methods.keys()
+                .filter(|(name, _)| method_name.is_none_or(|method_name| method_name == name))
+                .map(|(name, descriptor)| MethodId { class: class_name.clone(), name: name.clone(), descriptor: descriptor.clone() })
+                .collect();
+            if methods.is_empty() {
+                return Err(format!("Entry point {} is not a class or method on the classpath", entry));
+            }
+            analysis.entry_points.extend(methods);
+        }
+        Ok(analysis)
+    }
+
+    /// Makes sure a class is known, from the classpath or by loading it and its supertypes from the library. Returns
+    /// false when it isn't in either. `unknown` remembers the classes that were looked up in the library.
+    fn load(&mut self, class_name: &str, library: &ClassPath, unknown: &mut BTreeMap<String, bool>) -> bool {
+        if self.classes.contains_key(class_name) || self.library_classes.contains_key(class_name) {
+            return true;
+        }
+        if let Some(found) = unknown.get(class_name) {
+            return *found;
+        }
+        let Some(Ok(class_info)) = library.load_class(class_name) else {
+            unknown.insert(class_name.to_string(), false);
+            return false;
+        };
+        let node = ClassNode::new(&class_info);
+        let supertypes: Vec<String> = node.supertypes().cloned().collect();
+        unknown.insert(class_name.to_string(), true);
+        self.library_classes.insert(class_name.to_string(), node);
+        for supertype in supertypes {
+            self.load(&supertype, library, unknown);
+        }
+        true
+    }
+
+    fn node(&self, class_name: &str) -> Option<&ClassNode> {
+        self.classes.get(class_name).or_else(|| self.library_classes.get(class_name))
+    }
+
+    /// Returns a class and all its known supertypes, the missing ones included.
+    pub fn ancestors(&self, class_name: &str) -> BTreeSet<String> {
+        let mut ancestors = BTreeSet::from([class_name.to_string()]);
+        let mut queue = VecDeque::from([class_name.to_string()]);
+        while let Some(current) = queue.pop_front() {
+            for supertype in self.node(&current).into_iter().flat_map(ClassNode::supertypes) {
+                if ancestors.insert(supertype.clone()) {
+                    queue.push_back(supertype.clone());
+                }
+            }
+        }
+        ancestors
+    }
+
+    /// Returns the class that declares the method that a reference resolves to: in the class or its superclasses
+    /// first, then in its superinterfaces.
+    fn resolve(&self, class_name: &str, name: &str, descriptor: &str) -> Option<String> {
+        let key = (name.to_string(), descriptor.to_string());
+        let mut current = Some(class_name.to_string());
+        while let Some(class) = current {
+            let node = self.node(&class)?;
+            if node.methods.contains_key(&key) {
+                return Some(class);
+            }
+            current = node.super_class.clone();
+        }
+        self.ancestors(class_name).into_iter()
+            .find(|ancestor| self.node(ancestor).is_some_and(|node| node.is_interface && node.methods.contains_key(&key)))
+    }
+
+    /// Returns the classes on the classpath that directly extend or implement each class.
+    pub fn subclasses(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut subclasses: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (class_name, node) in &self.classes {
+            for supertype in node.supertypes() {
+                subclasses.entry(supertype).or_default().insert(class_name);
+            }
+        }
+        subclasses
+    }
+
+    /// Returns the methods that the entry points can reach through calls.
+    pub fn reachable_methods(&self) -> BTreeSet<&MethodId> {
+        let mut reachable: BTreeSet<&MethodId> = self.entry_points.iter().collect();
+        let mut queue: VecDeque<&MethodId> = reachable.iter().copied().collect();
+        while let Some(method) = queue.pop_front() {
+            for callee in self.calls.get(method).into_iter().flatten() {
+                if reachable.insert(callee) {
+                    queue.push_back(callee);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Returns all the methods of the classes on the classpath.
+    pub fn methods(&self) -> Vec<MethodId> {
+        self.classes.iter().flat_map(|(class_name, node)| node.methods.keys().map(|(name, descriptor)| {
+            MethodId { class: class_name.clone(), name: name.clone(), descriptor: descriptor.clone() }
+        })).collect()
+    }
+
+    pub fn unreachable_methods(&self) -> Vec<MethodId> {
+        let reachable = self.reachable_methods();
+        self.methods().into_iter().filter(|method| !reachable.contains(method)).collect()
+    }
+
+    /// Returns the groups of methods that call each other in a cycle, which are the strongly connected components of
+    /// the call graph with more than one method, and the methods that call themselves.
+    pub fn cycles(&self) -> Vec<Vec<MethodId>> {
+        let methods = self.methods();
+        let numbers: HashMap<&MethodId, usize> = methods.iter().enumerate().map(|(number, method)| (method, number)).collect();
+        let successors: Vec<Vec<usize>> = methods.iter()
+            .map(|method| self.calls.get(method).into_iter().flatten().map(|callee| numbers[callee]).collect())
+            .collect();
+        // Tarjan's algorithm, with an explicit stack of the methods being visited and their next successor
+        let mut index = vec![usize::MAX; methods.len()];
+        let mut low_link = vec![0; methods.len()];
+        let mut on_stack = vec![false; methods.len()];
+        let mut stack = Vec::new();
+        let mut next_index = 0;
+        let mut cycles = Vec::new();
+        for root in 0..methods.len() {
+            if index[root] != usize::MAX {
+                continue;
+            }
+            let mut visits = vec![(root, 0)];
+            while let Some((node, successor)) = visits.pop() {
+                if successor == 0 {
+                    index[node] = next_index;
+                    low_link[node] = next_index;
+                    next_index += 1;
+                    stack.push(node);
+                    on_stack[node] = true;
+                }
+                if let Some(&next) = successors[node].get(successor) {
+                    visits.push((node, successor + 1));
+                    if index[next] == usize::MAX {
+                        visits.push((next, 0));
+                    } else if on_stack[next] {
+                        low_link[node] = low_link[node].min(index[next]);
+                    }
+                    continue;
+                }
+                if let Some(&(parent, _)) = visits.last() {
+                    low_link[parent] = low_link[parent].min(low_link[node]);
+                }
+                if low_link[node] == index[node] {
+                    let mut component = Vec::new();
+                    loop {
+                        let member = stack.pop().unwrap();
+                        on_stack[member] = false;
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    if component.len() > 1 || successors[node].contains(&node) {
+                        component.sort();
+                        cycles.push(component.into_iter().map(|member| methods[member].clone()).collect());
+                    }
+                }
+            }
+        }
+        cycles.sort();
+        cycles
+    }
+
+    /// Returns the missing classes with what refers to them: the classes that extend or implement them and the methods
+    /// that call into them.
+    pub fn missing_classes(&self) -> BTreeMap<&str, BTreeSet<String>> {
+        let mut missing: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+        for (class_name, subclasses) in &self.missing_supertypes {
+            missing.entry(class_name).or_default().extend(subclasses.iter().map(|subclass| java_name(subclass)));
+        }
+        for (class_name, callers) in &self.missing_calls {
+            missing.entry(class_name).or_default().extend(callers.iter().map(MethodId::to_string));
+        }
+        missing
+    }
+
+    /// Writes the hierarchy, the call graph and what was found in them as JSON.
+    pub fn to_json(&self) -> String {
+        let list = |items: &mut dyn Iterator<Item = String>| format!("[{}]", items.map(|item| json_string(&item)).collect::<Vec<_>>().join(", "));
+        let subclasses = self.subclasses();
+        let mut output = String::from("{\n  \"classes\": [");
+        for (number, (class_name, node)) in self.classes.iter().enumerate() {
+            output += if number == 0 { "\n" } else { ",\n" };
+            let super_class = node.super_class.as_ref().map_or(String::from("null"), |super_class| json_string(&java_name(super_class)));
+            write!(output, "    {{\"name\": {}, \"interface\": {}, \"super\": {}, \"interfaces\": {}, \"subclasses\": {}, \"methods\": {}}}",
+                json_string(&java_name(class_name)), node.is_interface, super_class,
+                list(&mut node.interfaces.iter().map(|interface| java_name(interface))),
+                list(&mut subclasses.get(class_name.as_str()).into_iter().flatten().map(|subclass| java_name(subclass))),
+                list(&mut node.methods.keys().map(|(name, descriptor)| format!("{}{}", name, descriptor)))).unwrap();
+        }
+        output += "\n  ],\n  \"calls\": [";
+        let calls: Vec<_> = self.calls.iter().filter(|(_, callees)| !callees.is_empty()).collect();
+        for (number, (caller, callees)) in calls.iter().enumerate() {
+            output += if number == 0 { "\n" } else { ",\n" };
+            write!(output, "    {{\"from\": {}, \"to\": {}}}", json_string(&caller.to_string()), list(&mut callees.iter().map(MethodId::to_string))).unwrap();
+        }
+        output += "\n  ],\n";
+        let mut method_list = |key: &str, methods: &mut dyn Iterator<Item = &MethodId>| {
+            write!(output, "  \"{}\": [", key).unwrap();
+            for (number, method) in methods.enumerate() {
+                output += if number == 0 { "\n    " } else { ",\n    " };
+                output += &json_string(&method.to_string());
+            }
+            output += "\n  ],\n";
+        };
+        method_list("entry_points", &mut self.entry_points.iter());
+        method_list("unreachable", &mut self.unreachable_methods().iter());
+        output += "  \"cycles\": [";
+        for (number, cycle) in self.cycles().iter().enumerate() {
+            output += if number == 0 { "\n" } else { ",\n" };
+            write!(output, "    {}", list(&mut cycle.iter().map(MethodId::to_string))).unwrap();
+        }
+        output += "\n  ],\n  \"missing\": [";
+        for (number, (class_name, referrers)) in self.missing_classes().iter().enumerate() {
+            output += if number == 0 { "\n" } else { ",\n" };
+            write!(output, "    {{\"class\": {}, \"referenced_by\": {}}}", json_string(&java_name(class_name)), list(&mut referrers.iter().cloned())).unwrap();
+        }
+        output += "\n  ]\n}\n";
+        output
+    }
+
+    /// Writes the call graph in the DOT format of Graphviz, with the methods in a box for each class. Entry points are
+    /// bold, unreachable methods are gray and dashed, and missing classes are red.
+    pub fn calls_to_dot(&self) -> String {
+        let unreachable: BTreeSet<MethodId> = self.unreachable_methods().into_iter().collect();
+        let methods = self.methods();
+        let numbers: HashMap<&MethodId, usize> = methods.iter().enumerate().map(|(number, method)| (method, number)).collect();
+        let mut output = String::from("digraph calls {\n    node [shape=box, fontname=\"monospace\"];\n");
+        for (class_number, class_name) in self.classes.keys().enumerate() {
+            writeln!(output, "    subgraph cluster_{} {{", class_number).unwrap();
+            writeln!(output, "        label=\"{}\";", escape(&java_name(class_name))).unwrap();
+            for method in methods.iter().filter(|method| method.class == *class_name) {
+                let style = if self.entry_points.contains(method) {
+                    ", style=bold"
+                } else if unreachable.contains(method) {
+                    ", style=dashed, color=gray, fontcolor=gray"
+                } else {
+                    ""
+                };
+                writeln!(output, "        M{} [label=\"{}{}\"{}];", numbers[method], escape(&method.name), escape(&method.descriptor), style).unwrap();
+            }
+            output += "    }\n";
+        }
+        let missing: Vec<&String> = self.missing_calls.keys().collect();
+        for (number, class_name) in missing.iter().enumerate() {
+            writeln!(output, "    X{} [label=\"{}\", color=red, fontcolor=red];", number, escape(&java_name(class_name))).unwrap();
+        }
+        for (caller, callees) in &self.calls {
+            for callee in callees {
+                writeln!(output, "    M{} -> M{};", numbers[caller], numbers[callee]).unwrap();
+            }
+        }
+        for (number, callers) in self.missing_calls.values().enumerate() {
+            for caller in callers {
+                writeln!(output, "    M{} -> X{} [color=red];", numbers[caller], number).unwrap();
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    /// Writes the class hierarchy in the DOT format of Graphviz, with an arrow from each class to its superclass and
+    /// a dashed one to each of its interfaces. Supertypes from the library are gray and missing ones are red.
+    pub fn hierarchy_to_dot(&self) -> String {
+        let mut names: BTreeSet<&str> = self.classes.keys().map(String::as_str).collect();
+        names.extend(self.classes.values().flat_map(ClassNode::supertypes).map(String::as_str));
+        let numbers: HashMap<&str, usize> = names.iter().enumerate().map(|(number, name)| (*name, number)).collect();
+        let mut output = String::from("digraph hierarchy {\n    node [shape=box, fontname=\"monospace\"];\n    edge [arrowhead=empty];\n");
+        for name in &names {
+            let mut attributes = Vec::new();
+            if self.node(name).is_some_and(|node| node.is_interface) {
+                attributes.push("style=rounded");
+            }
+            if self.library_classes.contains_key(*name) {
+                attributes.push("color=gray, fontcolor=gray");
+            } else if !self.classes.contains_key(*name) {
+                attributes.push("color=red, fontcolor=red");
+            }
+            let attributes: String = attributes.iter().map(|attribute| format!(", {}", attribute)).collect();
+            writeln!(output, "    C{} [label=\"{}\"{}];", numbers[name], escape(&java_name(name)), attributes).unwrap();
+        }
+        for (class_name, node) in &self.classes {
+            if let Some(super_class) = &node.super_class {
+                writeln!(output, "    C{} -> C{};", numbers[class_name.as_str()], numbers[super_class.as_str()]).unwrap();
+            }
+            for interface in &node.interfaces {
+                writeln!(output, "    C{} -> C{} [style=dashed];", numbers[class_name.as_str()], numbers[interface.as_str()]).unwrap();
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+}
+
+/// Returns the methods that code calls with invoke instructions, or gets as method handles for invokedynamic.
+fn method_references(class_info: &ClassInfo, code: &[u8]) -> Vec<Reference> {
+    let constant_pool = &class_info.constant_pool;
+    let mut references = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let Some(length) = instruction_length(code, pc) else {
+            break;
+        };
+        let index = || u16::from_be_bytes([code[pc + 1], code[pc + 2]]);
+        match code[pc] {
+            opcode @ (INVOKEVIRTUAL | INVOKESPECIAL | INVOKESTATIC | INVOKEINTERFACE) => {
+                if let Some((owner, name, descriptor)) = method_ref(constant_pool, index()) {
+                    references.push(Reference { opcode, owner, name, descriptor });
+                }
+            },
+            INVOKEDYNAMIC => {
+                let bootstrap_method = match constant_pool.constants.get(index() as usize) {
+                    Some(ConstantPoolItem::CPInvokeDynamicInfo { bootstrap_method_attr_index, .. }) => class_info.get_bootstrap_method(*bootstrap_method_attr_index),
+                    other => None
+                };
+                for argument in bootstrap_method.into_iter().flat_map(|bootstrap_method| &bootstrap_method.bootstrap_arguments) {
+                    if let Some(ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index }) = constant_pool.constants.get(*argument as usize)
+                        && let Some((owner, name, descriptor)) = method_ref(constant_pool, *reference_index) {
+                        // A handle behaves like the invoke instruction of its kind
+                        let opcode = match reference_kind {
+                            5 => INVOKEVIRTUAL,
+                            6 => INVOKESTATIC,
+                            9 => INVOKEINTERFACE,
+                            other => INVOKESPECIAL
+                        };
+                        references.push(Reference { opcode, owner, name, descriptor });
+                    }
+                }
+            },
+            other => {}
+        }
+        pc += length;
+    }
+    references
+}
+
+/// Returns the class, name and descriptor of a method reference in the constant pool.
+fn method_ref(constant_pool: &ConstantPool, index: u16) -> Option<(String, String, String)> {
+    match constant_pool.constants.get(index as usize)? {
+        ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } | ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } => {
+            let (name_index, descriptor_index) = constant_pool.get_name_and_type(*name_and_type_index);
+            let owner = constant_pool.get_string(constant_pool.get_class_info(*class_index));
+            Some((owner.to_string(), constant_pool.get_string(name_index).to_string(), constant_pool.get_string(descriptor_index).to_string()))
+        },
+        other => None
+    }
+}
+
+fn java_name(internal_name: &str) -> String {
+    internal_name.replace('/', ".")
+}
+
+/// Quotes text as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            c if (c as u32) < 0x20 => write!(quoted, "\\u{:04x}", c as u32).unwrap(),
+            c => quoted.push(c)
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,1442 @@
+use std::{collections::{BTreeSet, HashMap, HashSet}, fmt};
+
+use crate::{class_loader::{ATCode, ATLineNumberTable, ATLocalVariableTable, ATStackMapTable, AttributeInfo, ConstantPool, ConstantPoolItem, ExceptionTableEntry, LineNumberTableEntry, LocalVariableTableEnty, MethodFlags, StackMapFrame, VerificationTypeInfo}, constant_pool_builder::ConstantPoolBuilder, descriptor_parser::argument_descriptors, format_checker::{is_field_descriptor, is_method_descriptor}, opcodes::*};
+
+/// A position in the code for branches and exception handlers, made by `CodeBuilder::new_label`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Label(usize);
+
+/// An instruction for the CodeBuilder. Branches go to labels instead of offsets,
+/// and the assembler picks the short, wide or far encoding of an instruction.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Instruction {
+    // Places a label before the next instruction, it takes up no bytes
+    Label(Label),
+    // An instruction without operands, like iadd, aload_0 or return
+    Simple(u8),
+    // bipush or sipush with its value, or newarray with its type code
+    Immediate(u8, i32),
+    // A load, store or ret, which becomes iload_1 and the like for the first four locals, or wide above 255
+    Local(u8, u16),
+    // The index of the local and the increment, wide when they don't fit in a byte
+    Iinc(u16, i16),
+    // An instruction with a constant pool index, like getfield, invokevirtual or new. ldc becomes ldc_w above 255
+    Constant(u8, u16),
+    // The class and the number of dimensions
+    MultiANewArray(u16, u8),
+    // goto and jsr become goto_w and jsr_w when the label is too far away, conditional branches then jump around a goto_w
+    Jump(u8, Label),
+    TableSwitch { low: i32, default: Label, targets: Vec<Label> },
+    LookupSwitch { default: Label, pairs: Vec<(i32, Label)> },
+}
+
+/// An entry of the exception table. The range starts at `start` and ends before `end`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ExceptionHandler {
+    pub start: Label,
+    pub end: Label,
+    pub handler: Label,
+    // A class constant, or 0 to catch everything, as for finally
+    pub catch_type: u16,
+}
+
+/// An entry of the LocalVariableTable, for debuggers. The variable is in scope from `start` until before `end`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LocalVariable {
+    pub start: Label,
+    pub end: Label,
+    pub name: String,
+    pub descriptor: String,
+    pub index: u16,
+}
+
+/// Why code can't be assembled, with the instruction it is about when there is one: its index among the
+/// instructions and labels in the order they were added, and its pc.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AssemblyError {
+    pub instruction: Option<usize>,
+    pub pc: Option<usize>,
+    pub message: String,
+}
+
+impl AssemblyError {
+    fn at(instruction: Option<usize>, pc: Option<usize>, message: impl Into<String>) -> AssemblyError {
+        AssemblyError { instruction, pc, message: message.into() }
+    }
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.pc {
+            Some(pc) => write!(formatter, "{} at pc {}", self.message, pc),
+            None => write!(formatter, "{}", self.message)
+        }
+    }
+}
+
+// A full constant pool
+impl From<String> for AssemblyError {
+    fn from(message: String) -> AssemblyError {
+        AssemblyError::at(None, None, message)
+    }
+}
+
+/// A type on the operand stack or in a local variable, as the verifier tracks it.
+#[derive(Clone, PartialEq, Debug)]
+enum Type {
+    Top,
+    Int,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    // Created by the `new` at this instruction index, before its constructor was called. The pc of it in frames_at_targets
+    Uninitialized(usize),
+    // A class by its internal name, or an array by its descriptor
+    Reference(String),
+    ReturnAddress,
+}
+
+impl Type {
+    fn from_descriptor(descriptor: &str) -> Type {
+        match descriptor.as_bytes().first() {
+            Some(b'F') => Type::Float,
+            Some(b'J') => Type::Long,
+            Some(b'D') => Type::Double,
+            Some(b'L') if descriptor.ends_with(';') => Type::Reference(descriptor[1..descriptor.len() - 1].to_string()),
+            Some(b'[') => Type::Reference(descriptor.to_string()),
+            other => Type::Int
+        }
+    }
+
+    fn object() -> Type {
+        Type::Reference(String::from("java/lang/Object"))
+    }
+
+    fn is_category2(&self) -> bool {
+        matches!(self, Type::Long | Type::Double)
+    }
+
+    fn size(&self) -> usize {
+        if self.is_category2() { 2 } else { 1 }
+    }
+}
+
+/// The types at an instruction. Longs and doubles take up two locals, the second one holding Top.
+#[derive(Clone, PartialEq)]
+struct Frame {
+    locals: Vec<Type>,
+    stack: Vec<Type>,
+}
+
+impl Frame {
+    fn pop(&mut self) -> Result<Type, String> {
+        self.stack.pop().ok_or_else(|| String::from("Operand stack underflow"))
+    }
+
+    /// Pops one category 2 value or two category 1 values, the way pop2 and the dup2 instructions see the stack.
+    fn pop_two_words(&mut self) -> Result<Vec<Type>, String> {
+        let value = self.pop()?;
+        Ok(if value.is_category2() { vec![value] } else { vec![self.pop()?, value] })
+    }
+
+    fn push_all<'a>(&mut self, values: impl IntoIterator<Item = &'a Type>) {
+        self.stack.extend(values.into_iter().cloned());
+    }
+
+    fn store(&mut self, index: usize, value: Type) {
+        // Overwriting the second half of a long or double makes it unusable
+        if index > 0 && self.locals[index - 1].is_category2() {
+            self.locals[index - 1] = Type::Top;
+        }
+        if value.is_category2() {
+            self.locals[index + 1] = Type::Top;
+        }
+        self.locals[index] = value;
+    }
+
+    fn stack_size(&self) -> usize {
+        self.stack.iter().map(Type::size).sum()
+    }
+}
+
+/// Finds the superclass of a class by its internal name, None for Object or classes it doesn't know.
+pub type SuperclassLookup = Box<dyn Fn(&str) -> Option<String>>;
+
+/// Builds the Code attribute of a method from instructions.
+/// It lays out the code, computes max_stack and max_locals by following the types through it,
+/// and writes the StackMapTable frames that the type checking verifier needs.
+/// Code that can't be reached gets replaced by nops and an athrow, like javac and ASM do.
+pub struct CodeBuilder {
+    pub instructions: Vec<Instruction>,
+    pub handlers: Vec<ExceptionHandler>,
+    // The line in the source file that starts at a label
+    pub line_numbers: Vec<(Label, u16)>,
+    pub local_variables: Vec<LocalVariable>,
+    labels: usize,
+    superclass_of: Option<SuperclassLookup>,
+}
+
+impl Default for CodeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodeBuilder {
+    pub fn new() -> CodeBuilder {
+        CodeBuilder {
+            instructions: Vec::new(),
+            handlers: Vec::new(),
+            line_numbers: Vec::new(),
+            local_variables: Vec::new(),
+            labels: 0,
+            superclass_of: None,
+        }
+    }
+
+    /// Sets how to find the superclass of a class, by internal name. Where objects of different classes meet,
+    /// the frames then get their closest common superclass. Without it they get Object, which the verifier
+    /// rejects when the code goes on to use them as something more specific.
+    pub fn set_class_hierarchy(&mut self, superclass_of: impl Fn(&str) -> Option<String> + 'static) {
+        self.superclass_of = Some(Box::new(superclass_of));
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels += 1;
+        Label(self.labels - 1)
+    }
+
+    pub fn place_label(&mut self, label: Label) {
+        self.instructions.push(Instruction::Label(label));
+    }
+
+    pub fn add(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    pub fn add_handler(&mut self, start: Label, end: Label, handler: Label, catch_type: u16) {
+        self.handlers.push(ExceptionHandler { start, end, handler, catch_type });
+    }
+
+    pub fn add_line_number(&mut self, start: Label, line_number: u16) {
+        self.line_numbers.push((start, line_number));
+    }
+
+    pub fn add_local_variable(&mut self, start: Label, end: Label, name: &str, descriptor: &str, index: u16) {
+        self.local_variables.push(LocalVariable { start, end, name: name.to_string(), descriptor: descriptor.to_string(), index });
+    }
+
+    /// Decodes the instructions and exception table of existing code, with a label at every branch target.
+    /// Fails for code that doesn't decode, like an unknown opcode or a branch into the middle of an instruction.
+    pub fn from_code(code: &[u8], exceptions: &[ExceptionTableEntry]) -> Result<CodeBuilder, AssemblyError> {
+        let mut builder = CodeBuilder::new();
+        // The label at every target, with the pc of the first branch to it, None for the exception table
+        let mut labels: HashMap<i64, (Label, Option<usize>)> = HashMap::new();
+        let mut label_at = |builder: &mut CodeBuilder, from: Option<usize>, pc: i64| {
+            labels.entry(pc).or_insert_with(|| (builder.new_label(), from)).0
+        };
+        let read_u16 = |at: usize| u16::from_be_bytes([code[at], code[at + 1]]);
+        let read_i32 = |at: usize| i32::from_be_bytes(code[at..at + 4].try_into().unwrap());
+        let mut decoded = Vec::new();
+        let mut pc = 0;
+        while pc < code.len() {
+            let Some(length) = instruction_length(code, pc) else {
+                return Err(AssemblyError::at(None, Some(pc), format!("Bad instruction {:#04x}", code[pc])));
+            };
+            let opcode = code[pc];
+            let branch = |offset: i32| pc as i64 + offset as i64;
+            let instruction = match opcode {
+                BIPUSH => Instruction::Immediate(opcode, code[pc + 1] as i8 as i32),
+                SIPUSH => Instruction::Immediate(opcode, read_u16(pc + 1) as i16 as i32),
+                NEWARRAY => Instruction::Immediate(opcode, code[pc + 1] as i32),
+                LDC => Instruction::Constant(opcode, code[pc + 1] as u16),
+                ILOAD..=ALOAD | ISTORE..=ASTORE | RET => Instruction::Local(opcode, code[pc + 1] as u16),
+                IINC => Instruction::Iinc(code[pc + 1] as u16, code[pc + 2] as i8 as i16),
+                WIDE if code[pc + 1] == IINC => Instruction::Iinc(read_u16(pc + 2), read_u16(pc + 4) as i16),
+                WIDE => Instruction::Local(code[pc + 1], read_u16(pc + 2)),
+                IFEQ..=JSR | IFNULL | IFNONNULL => Instruction::Jump(opcode, label_at(&mut builder, Some(pc), branch(read_u16(pc + 1) as i16 as i32))),
+                GOTO_W | JSR_W => Instruction::Jump(opcode, label_at(&mut builder, Some(pc), branch(read_i32(pc + 1)))),
+                TABLESWITCH => {
+                    let start = pc + 4 - pc % 4;
+                    let default = label_at(&mut builder, Some(pc), branch(read_i32(start)));
+                    let (low, high) = (read_i32(start + 4), read_i32(start + 8));
+                    let targets = (0..(high as i64 - low as i64 + 1) as usize)
+                        .map(|entry| label_at(&mut builder, Some(pc), branch(read_i32(start + 12 + entry * 4))))
+                        .collect();
+                    Instruction::TableSwitch { low, default, targets }
+                },
+                LOOKUPSWITCH => {
+                    let start = pc + 4 - pc % 4;
+                    let default = label_at(&mut builder, Some(pc), branch(read_i32(start)));
+                    let pairs = (0..read_i32(start + 4) as usize)
+                        .map(|pair| (read_i32(start + 8 + pair * 8), label_at(&mut builder, Some(pc), branch(read_i32(start + 12 + pair * 8)))))
+                        .collect();
+                    Instruction::LookupSwitch { default, pairs }
+                },
+                LDC_W | LDC2_W | GETSTATIC..=INVOKEDYNAMIC | NEW | ANEWARRAY | CHECKCAST | INSTANCEOF => Instruction::Constant(opcode, read_u16(pc + 1)),
+                MULTIANEWARRAY => Instruction::MultiANewArray(read_u16(pc + 1), code[pc + 3]),
+                other => Instruction::Simple(opcode)
+            };
+            decoded.push((pc, instruction));
+            pc += length;
+        }
+        for entry in exceptions {
+            let start = label_at(&mut builder, None, entry.start_pc as i64);
+            let end = label_at(&mut builder, None, entry.end_pc as i64);
+            let handler = label_at(&mut builder, None, entry.handler_pc as i64);
+            builder.add_handler(start, end, handler, entry.catch_type);
+        }
+        let mut placed = HashSet::new();
+        for (pc, instruction) in decoded.into_iter().chain([(code.len(), Instruction::Simple(NOP))]) {
+            if let Some((label, _)) = labels.get(&(pc as i64)) {
+                builder.place_label(*label);
+                placed.insert(pc as i64);
+            }
+            if pc < code.len() {
+                builder.add(instruction);
+            }
+        }
+        let mut unplaced: Vec<(&i64, &(Label, Option<usize>))> = labels.iter().filter(|(target, _)| !placed.contains(target)).collect();
+        unplaced.sort_by_key(|(target, _)| **target);
+        if let Some((target, (_, from))) = unplaced.first() {
+            return Err(AssemblyError::at(None, *from, format!("Branch to {}, which is not the start of an instruction", target)));
+        }
+        Ok(builder)
+    }
+
+    /// Assembles the instructions into a Code attribute for a method of a class.
+    /// The names of the attributes and the classes in the frames get added to the constant pool.
+    /// Code with jsr or ret gets no StackMapTable, as those are only allowed in class files before version 51.
+    /// Fails for inconsistent code, like a missing label or operand stacks of different heights meeting, and
+    /// when the constant pool has no room for the names and classes.
+    pub fn build(self, constant_pool: &mut ConstantPoolBuilder, class_name: &str, access_flags: MethodFlags, name: &str, descriptor: &str) -> Result<ATCode, AssemblyError> {
+        let uses_subroutines = self.instructions.iter()
+            .any(|instruction| matches!(instruction, Instruction::Jump(JSR | JSR_W, _) | Instruction::Local(RET, _)));
+        let mut assembler = Assembler::new(self, constant_pool.constant_pool(), class_name)?;
+        assembler.layout()?;
+        let initial_frame = assembler.initial_frame(access_flags, name, descriptor);
+        assembler.infer_types(initial_frame.clone())?;
+        let dead_ranges = assembler.dead_ranges();
+        let code = assembler.emit(&dead_ranges)?;
+        let exceptions = assembler.exception_table(&dead_ranges);
+        let frames = if uses_subroutines { Vec::new() } else { assembler.frames_at_targets()? };
+        let (max_stack, max_locals) = (assembler.max_stack as u16, assembler.max_locals as u16);
+        let (line_numbers, local_variables) = (assembler.line_numbers(), assembler.local_variables());
+        let mut attributes = debug_attributes(line_numbers, local_variables, constant_pool)?;
+        if !frames.is_empty() {
+            let entries = stack_map_frames(frames, &initial_frame, constant_pool)?;
+            let name_index = constant_pool.add_utf8("StackMapTable")?;
+            attributes.push(AttributeInfo::StackMapTable(ATStackMapTable { name_index, entries, error: None }));
+        }
+        let name_index = constant_pool.add_utf8("Code")?;
+        Ok(ATCode { name_index, max_stack, max_locals, code, exceptions, attributes })
+    }
+
+    /// Assembles the instructions as they are, without following the types through them, so without frames and
+    /// with the max_stack and max_locals that are given. This is for class files before version 50,
+    /// and for code that is meant to fail verification.
+    pub fn build_unverified(self, constant_pool: &mut ConstantPoolBuilder, max_stack: u16, max_locals: u16) -> Result<ATCode, AssemblyError> {
+        let mut assembler = Assembler::new(self, constant_pool.constant_pool(), "")?;
+        assembler.layout()?;
+        let code = assembler.emit(&[])?;
+        let exceptions = assembler.exception_table(&[]);
+        let (line_numbers, local_variables) = (assembler.line_numbers(), assembler.local_variables());
+        let attributes = debug_attributes(line_numbers, local_variables, constant_pool)?;
+        let name_index = constant_pool.add_utf8("Code")?;
+        Ok(ATCode { name_index, max_stack, max_locals, code, exceptions, attributes })
+    }
+}
+
+/// The state of assembling one method.
+struct Assembler<'a> {
+    builder: CodeBuilder,
+    constant_pool: &'a ConstantPool,
+    class_name: &'a str,
+    // The instruction index of every label
+    label_indexes: HashMap<Label, usize>,
+    // The pc of every instruction, and of the end of the code as the last element
+    pcs: Vec<usize>,
+    // The branches that use the far encoding, by instruction index
+    far: Vec<bool>,
+    // The types at the start of every instruction that can be reached
+    frames: Vec<Option<Frame>>,
+    max_stack: usize,
+    max_locals: usize,
+}
+
+impl<'a> Assembler<'a> {
+    /// Fails when a label is placed twice or used without being placed.
+    fn new(builder: CodeBuilder, constant_pool: &'a ConstantPool, class_name: &'a str) -> Result<Assembler<'a>, AssemblyError> {
+        let mut label_indexes = HashMap::new();
+        for (index, instruction) in builder.instructions.iter().enumerate() {
+            if let Instruction::Label(label) = instruction
+                && label_indexes.insert(*label, index).is_some() {
+                return Err(AssemblyError::at(Some(index), None, format!("Label {:?} is placed twice", label)));
+            }
+        }
+        let unplaced = |label: &Label| !label_indexes.contains_key(label);
+        for (index, instruction) in builder.instructions.iter().enumerate() {
+            let labels: Vec<&Label> = match instruction {
+                Instruction::Jump(_, label) => vec![label],
+                Instruction::TableSwitch { default, targets, .. } => [default].into_iter().chain(targets).collect(),
+                Instruction::LookupSwitch { default, pairs } => [default].into_iter().chain(pairs.iter().map(|(_, label)| label)).collect(),
+                other => Vec::new()
+            };
+            if let Some(label) = labels.into_iter().find(|label| unplaced(label)) {
+                return Err(AssemblyError::at(Some(index), None, format!("Label {:?} is used but never placed", label)));
+            }
+        }
+        let other_labels = builder.handlers.iter().flat_map(|handler| [&handler.start, &handler.end, &handler.handler])
+            .chain(builder.line_numbers.iter().map(|(label, _)| label))
+            .chain(builder.local_variables.iter().flat_map(|variable| [&variable.start, &variable.end]));
+        if let Some(label) = other_labels.into_iter().find(|label| unplaced(label)) {
+            return Err(AssemblyError::at(None, None, format!("Label {:?} is used but never placed", label)));
+        }
+        let count = builder.instructions.len();
+        Ok(Assembler {
+            builder,
+            constant_pool,
+            class_name,
+            label_indexes,
+            pcs: Vec::new(),
+            far: vec![false; count],
+            frames: vec![None; count],
+            max_stack: 0,
+            max_locals: 0,
+        })
+    }
+
+    /// Every label that is used was checked to be placed when the assembler was made.
+    fn label_index(&self, label: Label) -> usize {
+        self.label_indexes[&label]
+    }
+
+    /// An error about the instruction at an index.
+    fn error_at(&self, index: usize, message: impl Into<String>) -> AssemblyError {
+        AssemblyError::at(Some(index), self.pcs.get(index).copied(), message)
+    }
+
+    fn label_pc(&self, label: Label) -> usize {
+        self.pcs[self.label_index(label)]
+    }
+
+    fn instruction_size(&self, index: usize, pc: usize) -> usize {
+        // The operands of the switches start at a multiple of 4
+        let padding = 3 - pc % 4;
+        match &self.builder.instructions[index] {
+            Instruction::Label(_) => 0,
+            Instruction::Simple(_) => 1,
+            Instruction::Immediate(opcode, _) => if *opcode == SIPUSH { 3 } else { 2 },
+            Instruction::Local(opcode, local) => match local {
+                0..=3 if *opcode != RET => 1,
+                0..=255 => 2,
+                other => 4
+            },
+            Instruction::Iinc(local, increment) => if *local <= 255 && i8::try_from(*increment).is_ok() { 3 } else { 6 },
+            Instruction::Constant(opcode, constant) => match *opcode {
+                LDC if *constant <= 255 => 2,
+                INVOKEINTERFACE | INVOKEDYNAMIC => 5,
+                other => 3
+            },
+            Instruction::MultiANewArray(..) => 4,
+            Instruction::Jump(GOTO_W | JSR_W, _) => 5,
+            Instruction::Jump(GOTO | JSR, _) => if self.far[index] { 5 } else { 3 },
+            // A conditional branch that is too far becomes the opposite branch over a goto_w
+            Instruction::Jump(..) => if self.far[index] { 8 } else { 3 },
+            Instruction::TableSwitch { targets, .. } => 1 + padding + 12 + 4 * targets.len(),
+            Instruction::LookupSwitch { pairs, .. } => 1 + padding + 8 + 8 * pairs.len(),
+        }
+    }
+
+    /// Computes the pcs, making branches far until all offsets fit. Making one far only moves code further apart.
+    fn layout(&mut self) -> Result<(), AssemblyError> {
+        loop {
+            self.pcs.clear();
+            let mut pc = 0;
+            for index in 0..self.builder.instructions.len() {
+                self.pcs.push(pc);
+                pc += self.instruction_size(index, pc);
+            }
+            self.pcs.push(pc);
+            if pc > u16::MAX as usize {
+                return Err(AssemblyError::at(None, None, format!("Code of {} bytes is too long, a method can have at most 65535 bytes", pc)));
+            }
+            let mut changed = false;
+            for index in 0..self.builder.instructions.len() {
+                if let Instruction::Jump(_, label) = self.builder.instructions[index]
+                    && !self.far[index]
+                    && i16::try_from(self.label_pc(label) as i64 - self.pcs[index] as i64).is_err() {
+                    self.far[index] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+
+    fn argument_types(descriptor: &str) -> Vec<Type> {
+        argument_descriptors(descriptor).iter().map(|argument| Type::from_descriptor(argument)).collect()
+    }
+
+    fn return_type(descriptor: &str) -> Option<Type> {
+        let return_type = &descriptor[descriptor.find(')').unwrap() + 1..];
+        (return_type != "V").then(|| Type::from_descriptor(return_type))
+    }
+
+    /// Finds the number of locals from the instructions that use them, and sets up the types at the start of the method.
+    fn initial_frame(&mut self, access_flags: MethodFlags, name: &str, descriptor: &str) -> Frame {
+        let mut arguments = Vec::new();
+        if !access_flags.contains(MethodFlags::ACC_STATIC) {
+            let is_constructor = name == "<init>" && self.class_name != "java/lang/Object";
+            arguments.push(if is_constructor { Type::UninitializedThis } else { Type::Reference(self.class_name.to_string()) });
+        }
+        arguments.extend(Self::argument_types(descriptor));
+        let mut max_locals: usize = arguments.iter().map(Type::size).sum();
+        for instruction in &self.builder.instructions {
+            let (index, size) = match instruction {
+                Instruction::Simple(opcode @ ILOAD_0..=ALOAD_3) => (((opcode - ILOAD_0) % 4) as usize, kind_size((opcode - ILOAD_0) / 4)),
+                Instruction::Simple(opcode @ ISTORE_0..=ASTORE_3) => (((opcode - ISTORE_0) % 4) as usize, kind_size((opcode - ISTORE_0) / 4)),
+                Instruction::Local(opcode @ ILOAD..=ALOAD, local) => (*local as usize, kind_size(opcode - ILOAD)),
+                Instruction::Local(opcode @ ISTORE..=ASTORE, local) => (*local as usize, kind_size(opcode - ISTORE)),
+                Instruction::Local(RET, local) | Instruction::Iinc(local, _) => (*local as usize, 1),
+                other => continue
+            };
+            max_locals = max_locals.max(index + size);
+        }
+        self.max_locals = max_locals;
+        let mut frame = Frame { locals: vec![Type::Top; max_locals], stack: Vec::new() };
+        let mut index = 0;
+        for argument in arguments {
+            let size = argument.size();
+            frame.store(index, argument);
+            index += size;
+        }
+        frame
+    }
+
+    fn class_name(&self, index: u16) -> Result<&'a str, String> {
+        match self.constant_pool.constants.get(index as usize) {
+            Some(ConstantPoolItem::CPClassInfo { name_index }) if index != 0 => Ok(self.constant_pool.get_string(*name_index)),
+            other => Err(format!("No class in constant pool at index {}", index))
+        }
+    }
+
+    /// Returns the class, name and descriptor of a field or method reference, or an invokedynamic without a class.
+    fn member(&self, index: u16) -> Result<(&'a str, &'a str, &'a str), String> {
+        let (class_name, name_and_type_index) = match self.constant_pool.constants.get(index as usize) {
+            Some(ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } |
+            ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } |
+            ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index }) => (self.class_name(*class_index)?, *name_and_type_index),
+            Some(ConstantPoolItem::CPInvokeDynamicInfo { name_and_type_index, .. }) => ("", *name_and_type_index),
+            other => return Err(format!("No field or method reference in constant pool at index {}", index))
+        };
+        let (name_index, descriptor_index) = self.constant_pool.get_name_and_type(name_and_type_index);
+        let descriptor = self.constant_pool.get_string(descriptor_index);
+        let is_field = matches!(self.constant_pool.constants[index as usize], ConstantPoolItem::CPFieldRef { .. });
+        if !(if is_field { is_field_descriptor(descriptor) } else { is_method_descriptor(descriptor) }) {
+            return Err(format!("Bad descriptor {} in constant pool at index {}", descriptor, index));
+        }
+        Ok((class_name, self.constant_pool.get_string(name_index), descriptor))
+    }
+
+    fn constant_type(&self, index: u16) -> Result<Type, String> {
+        Ok(match self.constant_pool.constants.get(index as usize) {
+            Some(ConstantPoolItem::CPIntegerInfo { .. }) => Type::Int,
+            Some(ConstantPoolItem::CPFloatInfo { .. }) => Type::Float,
+            Some(ConstantPoolItem::CPLongInfo { .. }) => Type::Long,
+            Some(ConstantPoolItem::CPDoubleInfo { .. }) => Type::Double,
+            Some(ConstantPoolItem::CPStringInfo { .. }) => Type::Reference(String::from("java/lang/String")),
+            Some(ConstantPoolItem::CPClassInfo { .. }) if index != 0 => Type::Reference(String::from("java/lang/Class")),
+            Some(ConstantPoolItem::CPMethodTypeInfo { .. }) => Type::Reference(String::from("java/lang/invoke/MethodType")),
+            Some(ConstantPoolItem::CPMethodHandleInfo { .. }) => Type::Reference(String::from("java/lang/invoke/MethodHandle")),
+            Some(ConstantPoolItem::CPDynamicInfo { name_and_type_index, .. }) => {
+                let (_, descriptor_index) = self.constant_pool.get_name_and_type(*name_and_type_index);
+                Type::from_descriptor(self.constant_pool.get_string(descriptor_index))
+            },
+            other => return Err(format!("No loadable constant in constant pool at index {}", index))
+        })
+    }
+
+    /// Applies an instruction to a frame. Returns the labels it can branch to,
+    /// and whether the next instruction can follow it.
+    fn execute(&self, index: usize, frame: &mut Frame) -> Result<(Vec<Label>, bool), String> {
+        match &self.builder.instructions[index] {
+            Instruction::Label(_) | Instruction::Iinc(..) => {},
+            Instruction::Simple(opcode) => return Ok((Vec::new(), self.execute_simple(*opcode, frame)?)),
+            Instruction::Immediate(NEWARRAY, type_code) => {
+                let component = match *type_code as u8 {
+                    T_BOOLEAN => "Z",
+                    T_CHAR => "C",
+                    T_FLOAT => "F",
+                    T_DOUBLE => "D",
+                    T_BYTE => "B",
+                    T_SHORT => "S",
+                    T_INT => "I",
+                    T_LONG => "J",
+                    other => return Err(format!("Illegal newarray type {}", other))
+                };
+                frame.pop()?;
+                frame.stack.push(Type::Reference(format!("[{}", component)));
+            },
+            Instruction::Immediate(..) => frame.stack.push(Type::Int),
+            Instruction::Local(opcode @ ILOAD..=ALOAD, local) => load(frame, *local as usize, opcode - ILOAD),
+            Instruction::Local(opcode @ ISTORE..=ASTORE, local) => {
+                let value = frame.pop()?;
+                frame.store(*local as usize, value);
+            },
+            Instruction::Local(RET, _) => return Ok((Vec::new(), false)),
+            Instruction::Local(opcode, _) => return Err(format!("{} doesn't use a local variable", opcode_name(*opcode))),
+            Instruction::Constant(opcode, constant) => self.execute_constant(index, *opcode, *constant, frame)?,
+            Instruction::MultiANewArray(class, dimensions) => {
+                for _dimension in 0..*dimensions {
+                    frame.pop()?;
+                }
+                frame.stack.push(Type::Reference(self.class_name(*class)?.to_string()));
+            },
+            Instruction::Jump(opcode, label) => {
+                match *opcode {
+                    IFEQ..=IFLE | IFNULL | IFNONNULL => {
+                        frame.pop()?;
+                    },
+                    IF_ICMPEQ..=IF_ACMPNE => {
+                        frame.pop()?;
+                        frame.pop()?;
+                    },
+                    GOTO | GOTO_W => return Ok((vec![*label], false)),
+                    // The subroutine gets the return address, and continues after the jsr when it returns
+                    JSR | JSR_W => {
+                        let mut subroutine_frame = frame.clone();
+                        subroutine_frame.stack.push(Type::ReturnAddress);
+                        *frame = subroutine_frame;
+                        return Ok((vec![*label], false));
+                    },
+                    other => return Err(format!("{} is not a branch", opcode_name(other)))
+                }
+                return Ok((vec![*label], true));
+            },
+            Instruction::TableSwitch { default, targets, .. } => {
+                frame.pop()?;
+                return Ok(([*default].into_iter().chain(targets.iter().copied()).collect(), false));
+            },
+            Instruction::LookupSwitch { default, pairs } => {
+                frame.pop()?;
+                return Ok(([*default].into_iter().chain(pairs.iter().map(|(_, label)| *label)).collect(), false));
+            },
+        }
+        Ok((Vec::new(), true))
+    }
+
+    fn execute_constant(&self, index: usize, opcode: u8, constant: u16, frame: &mut Frame) -> Result<(), String> {
+        match opcode {
+            LDC | LDC_W | LDC2_W => frame.stack.push(self.constant_type(constant)?),
+            GETSTATIC..=PUTFIELD => {
+                let (_, _, descriptor) = self.member(constant)?;
+                match opcode {
+                    GETSTATIC => frame.stack.push(Type::from_descriptor(descriptor)),
+                    PUTSTATIC => {
+                        frame.pop()?;
+                    },
+                    GETFIELD => {
+                        frame.pop()?;
+                        frame.stack.push(Type::from_descriptor(descriptor));
+                    },
+                    other => {
+                        frame.pop()?;
+                        frame.pop()?;
+                    }
+                }
+            },
+            INVOKEVIRTUAL..=INVOKEDYNAMIC => {
+                let (class_name, name, descriptor) = self.member(constant)?;
+                for _argument in argument_descriptors(descriptor) {
+                    frame.pop()?;
+                }
+                if opcode != INVOKESTATIC && opcode != INVOKEDYNAMIC {
+                    let receiver = frame.pop()?;
+                    if opcode == INVOKESPECIAL && name == "<init>" {
+                        let initialized = match &receiver {
+                            Type::Uninitialized(new_index) => match &self.builder.instructions[*new_index] {
+                                Instruction::Constant(NEW, class) => self.class_name(*class)?.to_string(),
+                                other => return Err(String::from("Uninitialized object not created by new"))
+                            },
+                            other => self.class_name.to_string()
+                        };
+                        for value in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+                            if *value == receiver {
+                                *value = Type::Reference(initialized.clone());
+                            }
+                        }
+                    }
+                }
+                if let Some(return_type) = Self::return_type(descriptor) {
+                    frame.stack.push(return_type);
+                }
+            },
+            NEW => {
+                let created = Type::Uninitialized(index);
+                for local in frame.locals.iter_mut().filter(|local| **local == created) {
+                    *local = Type::Top;
+                }
+                frame.stack.push(created);
+            },
+            ANEWARRAY => {
+                frame.pop()?;
+                let class_name = self.class_name(constant)?;
+                let array = if class_name.starts_with('[') { format!("[{}", class_name) } else { format!("[L{};", class_name) };
+                frame.stack.push(Type::Reference(array));
+            },
+            CHECKCAST => {
+                frame.pop()?;
+                frame.stack.push(Type::Reference(self.class_name(constant)?.to_string()));
+            },
+            INSTANCEOF => {
+                frame.pop()?;
+                frame.stack.push(Type::Int);
+            },
+            other => return Err(format!("{} doesn't use a constant", opcode_name(other)))
+        }
+        Ok(())
+    }
+
+    /// Applies an instruction without operands. Returns whether the next instruction can follow it.
+    fn execute_simple(&self, opcode: u8, frame: &mut Frame) -> Result<bool, String> {
+        match opcode {
+            NOP => {},
+            ACONST_NULL => frame.stack.push(Type::Null),
+            ICONST_M1..=ICONST_5 => frame.stack.push(Type::Int),
+            LCONST_0 | LCONST_1 => frame.stack.push(Type::Long),
+            FCONST_0..=FCONST_2 => frame.stack.push(Type::Float),
+            DCONST_0 | DCONST_1 => frame.stack.push(Type::Double),
+            ILOAD_0..=ALOAD_3 => load(frame, ((opcode - ILOAD_0) % 4) as usize, (opcode - ILOAD_0) / 4),
+            ISTORE_0..=ASTORE_3 => {
+                let value = frame.pop()?;
+                frame.store(((opcode - ISTORE_0) % 4) as usize, value);
+            },
+            IALOAD..=SALOAD => {
+                frame.pop()?;
+                let array = frame.pop()?;
+                frame.stack.push(match opcode {
+                    LALOAD => Type::Long,
+                    FALOAD => Type::Float,
+                    DALOAD => Type::Double,
+                    AALOAD => match array {
+                        Type::Reference(name) if name.starts_with('[') => Type::from_descriptor(&name[1..]),
+                        other => Type::Null
+                    },
+                    other => Type::Int
+                });
+            },
+            IASTORE..=SASTORE => {
+                frame.pop()?;
+                frame.pop()?;
+                frame.pop()?;
+            },
+            POP => {
+                frame.pop()?;
+            },
+            POP2 => {
+                frame.pop_two_words()?;
+            },
+            DUP => {
+                let value = frame.pop()?;
+                frame.push_all([&value, &value]);
+            },
+            DUP_X1 => {
+                let (value1, value2) = (frame.pop()?, frame.pop()?);
+                frame.push_all([&value1, &value2, &value1]);
+            },
+            DUP_X2 => {
+                let value1 = frame.pop()?;
+                let below = frame.pop_two_words()?;
+                frame.push_all([&value1].into_iter().chain(&below).chain([&value1]));
+            },
+            DUP2 => {
+                let top = frame.pop_two_words()?;
+                frame.push_all(top.iter().chain(&top));
+            },
+            DUP2_X1 => {
+                let top = frame.pop_two_words()?;
+                let below = frame.pop()?;
+                frame.push_all(top.iter().chain([&below]).chain(&top));
+            },
+            DUP2_X2 => {
+                let top = frame.pop_two_words()?;
+                let below = frame.pop_two_words()?;
+                frame.push_all(top.iter().chain(&below).chain(&top));
+            },
+            SWAP => {
+                let (value1, value2) = (frame.pop()?, frame.pop()?);
+                frame.push_all([&value1, &value2]);
+            },
+            // the result has the type of the left operand, which stays on the stack
+            IADD..=DREM | IAND..=LXOR | ISHL..=LUSHR => {
+                frame.pop()?;
+                let left = frame.pop()?;
+                frame.stack.push(left);
+            },
+            INEG..=DNEG => {
+                let value = frame.pop()?;
+                frame.stack.push(value);
+            },
+            I2L..=I2S => {
+                frame.pop()?;
+                frame.stack.push(match opcode {
+                    I2L | F2L | D2L => Type::Long,
+                    I2F | L2F | D2F => Type::Float,
+                    I2D | L2D | F2D => Type::Double,
+                    other => Type::Int
+                });
+            },
+            LCMP..=DCMPG => {
+                frame.pop()?;
+                frame.pop()?;
+                frame.stack.push(Type::Int);
+            },
+            IRETURN..=ARETURN | ATHROW => {
+                frame.pop()?;
+                return Ok(false);
+            },
+            RETURN => return Ok(false),
+            ARRAYLENGTH => {
+                frame.pop()?;
+                frame.stack.push(Type::Int);
+            },
+            MONITORENTER | MONITOREXIT => {
+                frame.pop()?;
+            },
+            other => return Err(format!("{} needs operands", opcode_name(other)))
+        }
+        Ok(true)
+    }
+
+    /// Merges the types that reach an instruction from two places. Arrays of references get their components merged.
+    fn merge_types(&self, first: &Type, second: &Type) -> Type {
+        match (first, second) {
+            (first, second) if first == second => first.clone(),
+            (Type::Null, Type::Reference(_)) => second.clone(),
+            (Type::Reference(_), Type::Null) => first.clone(),
+            (Type::Reference(first), Type::Reference(second)) => {
+                match (first.strip_prefix('['), second.strip_prefix('[')) {
+                    (Some(first), Some(second)) if first.starts_with(['L', '[']) && second.starts_with(['L', '[']) => {
+                        match self.merge_types(&Type::from_descriptor(first), &Type::from_descriptor(second)) {
+                            Type::Reference(component) if component.starts_with('[') => Type::Reference(format!("[{}", component)),
+                            Type::Reference(component) => Type::Reference(format!("[L{};", component)),
+                            other => Type::object()
+                        }
+                    },
+                    (None, None) => Type::Reference(self.common_superclass(first, second)),
+                    other => Type::object()
+                }
+            },
+            other => Type::Top
+        }
+    }
+
+    fn common_superclass(&self, first: &str, second: &str) -> String {
+        let Some(superclass_of) = &self.builder.superclass_of else {
+            return String::from("java/lang/Object");
+        };
+        let mut first_superclasses = vec![first.to_string()];
+        while let Some(superclass) = superclass_of(first_superclasses.last().unwrap()) {
+            first_superclasses.push(superclass);
+        }
+        let mut class = Some(second.to_string());
+        while let Some(name) = class {
+            if first_superclasses.contains(&name) {
+                return name;
+            }
+            class = superclass_of(&name);
+        }
+        String::from("java/lang/Object")
+    }
+
+    /// Merges the frame after the instruction at `from` into the frame of the instruction at `index`.
+    fn merge_into(&mut self, from: usize, index: usize, frame: &Frame, changed: &mut BTreeSet<usize>) -> Result<(), AssemblyError> {
+        if index >= self.frames.len() {
+            return Err(self.error_at(from, "Code falls off its end"));
+        }
+        let merged = match &self.frames[index] {
+            None => frame.clone(),
+            Some(existing) => {
+                if existing.stack.len() != frame.stack.len() {
+                    return Err(self.error_at(index, "Operand stacks of different heights meet"));
+                }
+                Frame {
+                    locals: existing.locals.iter().zip(&frame.locals).map(|(first, second)| self.merge_types(first, second)).collect(),
+                    stack: existing.stack.iter().zip(&frame.stack).map(|(first, second)| self.merge_types(first, second)).collect(),
+                }
+            }
+        };
+        if self.frames[index].as_ref() != Some(&merged) {
+            self.frames[index] = Some(merged);
+            changed.insert(index);
+        }
+        Ok(())
+    }
+
+    /// Follows the types through the code until they don't change anymore, like the verifier does for old class files.
+    fn infer_types(&mut self, initial_frame: Frame) -> Result<(), AssemblyError> {
+        if self.builder.instructions.is_empty() {
+            return Ok(());
+        }
+        // ret goes back to after any of the jsr instructions
+        let return_points: Vec<usize> = self.builder.instructions.iter().enumerate()
+            .filter(|(_, instruction)| matches!(instruction, Instruction::Jump(JSR | JSR_W, _)))
+            .map(|(index, _)| index + 1)
+            .collect();
+        let mut handlers: Vec<(usize, usize, usize, Type)> = Vec::new();
+        for handler in &self.builder.handlers {
+            let handler_index = self.label_index(handler.handler);
+            let catch_type = match handler.catch_type {
+                0 => String::from("java/lang/Throwable"),
+                index => self.class_name(index).map_err(|message| self.error_at(handler_index, message))?.to_string()
+            };
+            handlers.push((self.label_index(handler.start), self.label_index(handler.end), handler_index, Type::Reference(catch_type)));
+        }
+        let mut changed = BTreeSet::new();
+        self.merge_into(0, 0, &initial_frame, &mut changed)?;
+        while let Some(index) = changed.pop_first() {
+            let mut frame = self.frames[index].clone().unwrap();
+            let before = frame.locals.clone();
+            let (targets, continues) = self.execute(index, &mut frame).map_err(|message| self.error_at(index, message))?;
+            let stack_size = frame.stack_size();
+            self.max_stack = self.max_stack.max(stack_size);
+            for (start, end, handler, catch_type) in &handlers {
+                if (*start..*end).contains(&index) && !matches!(self.builder.instructions[index], Instruction::Label(_)) {
+                    for locals in [&before, &frame.locals] {
+                        let handler_frame = Frame { locals: locals.clone(), stack: vec![catch_type.clone()] };
+                        self.max_stack = self.max_stack.max(1);
+                        self.merge_into(index, *handler, &handler_frame, &mut changed)?;
+                    }
+                }
+            }
+            // jsr left its frame for the subroutine, ret passes its frame on to where the subroutine returns
+            if let Instruction::Local(RET, _) = self.builder.instructions[index] {
+                for return_point in &return_points {
+                    self.merge_into(index, *return_point, &frame, &mut changed)?;
+                }
+            }
+            for label in targets {
+                let target = self.label_index(label);
+                self.merge_into(index, target, &frame, &mut changed)?;
+            }
+            if continues {
+                self.merge_into(index, index + 1, &frame, &mut changed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the pc ranges of instructions that can't be reached. Labels alone don't make a range.
+    fn dead_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for (index, frame) in self.frames.iter().enumerate() {
+            let (start, end) = (self.pcs[index], self.pcs[index + 1]);
+            if frame.is_some() || start == end {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.1 == start => range.1 = end,
+                other => ranges.push((start, end))
+            }
+        }
+        ranges
+    }
+
+    fn emit(&self, dead_ranges: &[(usize, usize)]) -> Result<Vec<u8>, AssemblyError> {
+        let mut code = Vec::with_capacity(*self.pcs.last().unwrap());
+        let push_u16 = |code: &mut Vec<u8>, value: u16| code.extend(value.to_be_bytes());
+        let push_i32 = |code: &mut Vec<u8>, value: i32| code.extend(value.to_be_bytes());
+        for (index, instruction) in self.builder.instructions.iter().enumerate() {
+            let pc = self.pcs[index];
+            let offset = |label: &Label| self.label_pc(*label) as i32 - pc as i32;
+            match instruction {
+                Instruction::Label(_) => {},
+                Instruction::Simple(opcode) => code.push(*opcode),
+                Instruction::Immediate(SIPUSH, value) => {
+                    code.push(SIPUSH);
+                    push_u16(&mut code, *value as u16);
+                },
+                Instruction::Immediate(opcode, value) => code.extend([*opcode, *value as u8]),
+                Instruction::Local(opcode, local) => match local {
+                    0..=3 if *opcode != RET => {
+                        let short_form = if *opcode >= ISTORE { ISTORE_0 + (opcode - ISTORE) * 4 } else { ILOAD_0 + (opcode - ILOAD) * 4 };
+                        code.push(short_form + *local as u8);
+                    },
+                    0..=255 => code.extend([*opcode, *local as u8]),
+                    other => {
+                        code.extend([WIDE, *opcode]);
+                        push_u16(&mut code, *local);
+                    }
+                },
+                Instruction::Iinc(local, increment) => {
+                    if *local <= 255 && let Ok(increment) = i8::try_from(*increment) {
+                        code.extend([IINC, *local as u8, increment as u8]);
+                    } else {
+                        code.extend([WIDE, IINC]);
+                        push_u16(&mut code, *local);
+                        push_u16(&mut code, *increment as u16);
+                    }
+                },
+                Instruction::Constant(LDC, constant) if *constant <= 255 => code.extend([LDC, *constant as u8]),
+                Instruction::Constant(opcode, constant) => {
+                    code.push(if *opcode == LDC { LDC_W } else { *opcode });
+                    push_u16(&mut code, *constant);
+                    match *opcode {
+                        INVOKEINTERFACE => {
+                            let (_, _, descriptor) = self.member(*constant).map_err(|message| self.error_at(index, message))?;
+                            let slots: usize = Self::argument_types(descriptor).iter().map(Type::size).sum();
+                            code.extend([slots as u8 + 1, 0]);
+                        },
+                        INVOKEDYNAMIC => code.extend([0, 0]),
+                        other => {}
+                    }
+                },
+                Instruction::MultiANewArray(class, dimensions) => {
+                    code.push(MULTIANEWARRAY);
+                    push_u16(&mut code, *class);
+                    code.push(*dimensions);
+                },
+                Instruction::Jump(opcode @ (GOTO_W | JSR_W), label) => {
+                    code.push(*opcode);
+                    push_i32(&mut code, offset(label));
+                },
+                Instruction::Jump(opcode, label) if !self.far[index] => {
+                    code.push(*opcode);
+                    push_u16(&mut code, offset(label) as u16);
+                },
+                Instruction::Jump(opcode @ (GOTO | JSR), label) => {
+                    code.push(if *opcode == GOTO { GOTO_W } else { JSR_W });
+                    push_i32(&mut code, offset(label));
+                },
+                Instruction::Jump(opcode, label) => {
+                    code.push(opposite_branch(*opcode));
+                    push_u16(&mut code, 8);
+                    code.push(GOTO_W);
+                    push_i32(&mut code, offset(label) - 3);
+                },
+                Instruction::TableSwitch { low, default, targets } => {
+                    let high = i32::try_from(targets.len()).ok().filter(|count| *count > 0).and_then(|count| low.checked_add(count - 1))
+                        .ok_or_else(|| self.error_at(index, format!("tableswitch from {} can't have {} targets", low, targets.len())))?;
+                    code.push(TABLESWITCH);
+                    code.resize(code.len() + 3 - pc % 4, 0);
+                    push_i32(&mut code, offset(default));
+                    push_i32(&mut code, *low);
+                    push_i32(&mut code, high);
+                    for target in targets {
+                        push_i32(&mut code, offset(target));
+                    }
+                },
+                Instruction::LookupSwitch { default, pairs } => {
+                    code.push(LOOKUPSWITCH);
+                    code.resize(code.len() + 3 - pc % 4, 0);
+                    push_i32(&mut code, offset(default));
+                    push_i32(&mut code, pairs.len() as i32);
+                    for (key, target) in pairs {
+                        push_i32(&mut code, *key);
+                        push_i32(&mut code, offset(target));
+                    }
+                }
+            }
+        }
+        // Dead code can't be verified, so it becomes nops and an athrow, which only needs a frame with a Throwable
+        for (start, end) in dead_ranges.iter().copied() {
+            code[start..end - 1].fill(NOP);
+            code[end - 1] = ATHROW;
+        }
+        Ok(code)
+    }
+
+    /// Returns the exception table, leaving out the dead code from the ranges.
+    fn exception_table(&self, dead_ranges: &[(usize, usize)]) -> Vec<ExceptionTableEntry> {
+        let mut entries = Vec::new();
+        for handler in &self.builder.handlers {
+            let (mut start, end) = (self.label_pc(handler.start), self.label_pc(handler.end));
+            let handler_pc = self.label_pc(handler.handler);
+            let mut add = |start: usize, end: usize| if start < end {
+                entries.push(ExceptionTableEntry { start_pc: start as u16, end_pc: end as u16, handler_pc: handler_pc as u16, catch_type: handler.catch_type });
+            };
+            for (dead_start, dead_end) in dead_ranges {
+                if *dead_end > start && *dead_start < end {
+                    add(start, *dead_start);
+                    start = *dead_end;
+                }
+            }
+            add(start, end);
+        }
+        entries
+    }
+
+    /// Returns the frames for every branch target, exception handler and dead code range by pc.
+    /// Uninitialized types get the pc of their `new` instead of its instruction index.
+    fn frames_at_targets(&self) -> Result<Vec<(usize, Frame)>, AssemblyError> {
+        // The frame at a pc is the one of its first instruction, which all labels there flow into
+        let mut instruction_at = HashMap::new();
+        for (index, instruction) in self.builder.instructions.iter().enumerate().rev() {
+            if !matches!(instruction, Instruction::Label(_)) {
+                instruction_at.insert(self.pcs[index], index);
+            }
+        }
+        let mut targets = BTreeSet::new();
+        for (index, instruction) in self.builder.instructions.iter().enumerate() {
+            if self.frames[index].is_none() {
+                continue;
+            }
+            match instruction {
+                Instruction::Jump(opcode, label) => {
+                    targets.insert(self.label_pc(*label));
+                    // The opposite branch of a far conditional branch goes to the next instruction
+                    if self.far[index] && !matches!(*opcode, GOTO | JSR) {
+                        targets.insert(self.pcs[index + 1]);
+                    }
+                },
+                Instruction::TableSwitch { default, targets: switch_targets, .. } => {
+                    targets.extend([default].into_iter().chain(switch_targets).map(|label| self.label_pc(*label)));
+                },
+                Instruction::LookupSwitch { default, pairs } => {
+                    targets.extend([default].into_iter().chain(pairs.iter().map(|(_, label)| label)).map(|label| self.label_pc(*label)));
+                },
+                other => {}
+            }
+        }
+        let dead_ranges = self.dead_ranges();
+        targets.extend(self.exception_table(&dead_ranges).iter().map(|entry| entry.handler_pc as usize));
+        let dead_starts: BTreeSet<usize> = dead_ranges.iter().map(|(start, _)| *start).collect();
+        targets.extend(&dead_starts);
+
+        targets.into_iter().map(|pc| {
+            if dead_starts.contains(&pc) {
+                return Ok((pc, Frame { locals: Vec::new(), stack: vec![Type::Reference(String::from("java/lang/Throwable"))] }));
+            }
+            let Some(index) = instruction_at.get(&pc) else {
+                return Err(AssemblyError::at(None, Some(pc), "Branch to the end of the code"));
+            };
+            let mut frame = self.frames[*index].clone().unwrap();
+            for value in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+                if let Type::Uninitialized(new_index) = value {
+                    *new_index = self.pcs[*new_index];
+                }
+            }
+            Ok((pc, frame))
+        }).collect()
+    }
+
+    fn line_numbers(&self) -> Vec<LineNumberTableEntry> {
+        self.builder.line_numbers.iter()
+            .map(|(label, line_number)| LineNumberTableEntry { start_pc: self.label_pc(*label) as u16, line_number: *line_number })
+            .collect()
+    }
+
+    /// Returns the local variables with the pc where they start and their length.
+    fn local_variables(&self) -> Vec<(u16, u16, LocalVariable)> {
+        self.builder.local_variables.iter().map(|variable| {
+            let (start, end) = (self.label_pc(variable.start), self.label_pc(variable.end));
+            (start as u16, end.saturating_sub(start) as u16, variable.clone())
+        }).collect()
+    }
+}
+
+/// Makes the LineNumberTable and LocalVariableTable attributes, when there are entries for them.
+fn debug_attributes(line_numbers: Vec<LineNumberTableEntry>, local_variables: Vec<(u16, u16, LocalVariable)>, constant_pool: &mut ConstantPoolBuilder) -> Result<Vec<AttributeInfo>, String> {
+    let mut attributes = Vec::new();
+    if !line_numbers.is_empty() {
+        let name_index = constant_pool.add_utf8("LineNumberTable")?;
+        attributes.push(AttributeInfo::LineNumberTable(ATLineNumberTable { name_index, entries: line_numbers }));
+    }
+    if !local_variables.is_empty() {
+        let mut entries = Vec::new();
+        for (start_pc, length, variable) in local_variables {
+            entries.push(LocalVariableTableEnty {
+                start_pc,
+                length,
+                name_index: constant_pool.add_utf8(&variable.name)?,
+                descriptor_index: constant_pool.add_utf8(&variable.descriptor)?,
+                index: variable.index,
+            });
+        }
+        let name_index = constant_pool.add_utf8("LocalVariableTable")?;
+        attributes.push(AttributeInfo::LocalVariableTable(ATLocalVariableTable { name_index, entries }));
+    }
+    Ok(attributes)
+}
+
+/// Converts types to the entries of a StackMapTable frame, where longs and doubles take up one entry.
+fn verification_types(types: &[Type], constant_pool: &mut ConstantPoolBuilder) -> Result<Vec<VerificationTypeInfo>, String> {
+    let mut verification_types = Vec::new();
+    let mut slot = 0;
+    while slot < types.len() {
+        verification_types.push(match &types[slot] {
+            Type::Top | Type::ReturnAddress => VerificationTypeInfo::Top,
+            Type::Int => VerificationTypeInfo::Integer,
+            Type::Float => VerificationTypeInfo::Float,
+            Type::Long => VerificationTypeInfo::Long,
+            Type::Double => VerificationTypeInfo::Double,
+            Type::Null => VerificationTypeInfo::Null,
+            Type::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+            Type::Uninitialized(pc) => VerificationTypeInfo::Uninitialized(*pc as u16),
+            Type::Reference(name) => VerificationTypeInfo::Object(constant_pool.add_class(name)?),
+        });
+        slot += types[slot].size();
+    }
+    Ok(verification_types)
+}
+
+fn frame_types(frame: &Frame, constant_pool: &mut ConstantPoolBuilder) -> Result<(Vec<VerificationTypeInfo>, Vec<VerificationTypeInfo>), String> {
+    let used_locals = frame.locals.iter().rposition(|local| *local != Type::Top).map_or(0, |last| last + 1);
+    // Stack entries go one by one, the second half of a long or double isn't there
+    let mut stack = Vec::new();
+    for value in &frame.stack {
+        stack.extend(verification_types(std::slice::from_ref(value), constant_pool)?);
+    }
+    Ok((verification_types(&frame.locals[..used_locals], constant_pool)?, stack))
+}
+
+/// Writes each frame in the shortest form relative to the frame before it.
+fn stack_map_frames(frames: Vec<(usize, Frame)>, initial_frame: &Frame, constant_pool: &mut ConstantPoolBuilder) -> Result<Vec<StackMapFrame>, String> {
+    let (mut previous_locals, _) = frame_types(initial_frame, constant_pool)?;
+    let mut previous_pc = None;
+    let mut entries = Vec::new();
+    for (pc, frame) in frames {
+        let (locals, stack) = frame_types(&frame, constant_pool)?;
+        let offset_delta = match previous_pc {
+            None => pc as u16,
+            Some(previous_pc) => (pc - previous_pc - 1) as u16
+        };
+        let extra = locals.len() as isize - previous_locals.len() as isize;
+        let entry = if stack.is_empty() && locals == previous_locals {
+            StackMapFrame::Same { frame_type: if offset_delta < 64 { offset_delta as u8 } else { 251 }, offset_delta }
+        } else if stack.len() == 1 && locals == previous_locals {
+            let frame_type = if offset_delta < 64 { 64 + offset_delta as u8 } else { 247 };
+            StackMapFrame::SameLocals1StackItem { frame_type, offset_delta, stack: stack[0] }
+        } else if stack.is_empty() && (1..=3).contains(&extra) && locals.starts_with(&previous_locals) {
+            StackMapFrame::Append { offset_delta, locals: locals[previous_locals.len()..].to_vec() }
+        } else if stack.is_empty() && (-3..=-1).contains(&extra) && previous_locals.starts_with(&locals) {
+            StackMapFrame::Chop { frame_type: (251 + extra) as u8, offset_delta }
+        } else {
+            StackMapFrame::Full { offset_delta, locals: locals.clone(), stack }
+        };
+        entries.push(entry);
+        previous_locals = locals;
+        previous_pc = Some(pc);
+    }
+    Ok(entries)
+}
+
+/// The number of locals that the load and store instructions use, which come in the order int, long, float, double, reference.
+fn kind_size(kind: u8) -> usize {
+    if kind == 1 || kind == 3 { 2 } else { 1 }
+}
+
+fn load(frame: &mut Frame, index: usize, kind: u8) {
+    let value = match kind {
+        0 => Type::Int,
+        1 => Type::Long,
+        2 => Type::Float,
+        3 => Type::Double,
+        other => frame.locals[index].clone()
+    };
+    frame.stack.push(value);
+}
+
+/// Returns the branch with the opposite condition, like ifne for ifeq. They come in pairs.
+fn opposite_branch(opcode: u8) -> u8 {
+    match opcode {
+        IFNULL => IFNONNULL,
+        IFNONNULL => IFNULL,
+        other => IFEQ + ((opcode - IFEQ) ^ 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{class_loader::{ExceptionTableEntry, MethodFlags}, constant_pool_builder::ConstantPoolBuilder, opcodes::*};
+
+    use super::{AssemblyError, CodeBuilder, Instruction};
+
+    /// Assembles the instructions as the body of a static method `run()V`, with the labels placed where they are given.
+    fn assemble(instructions: Vec<Instruction>, constant_pool: &mut ConstantPoolBuilder) -> Result<Vec<u8>, AssemblyError> {
+        let mut builder = CodeBuilder::new();
+        for instruction in instructions {
+            builder.add(instruction);
+        }
+        builder.build(constant_pool, "Test", MethodFlags::ACC_STATIC, "run", "()V").map(|code| code.code)
+    }
+
+    fn error(instruction: Option<usize>, pc: Option<usize>, message: &str) -> AssemblyError {
+        AssemblyError { instruction, pc, message: message.to_string() }
+    }
+
+    #[test]
+    fn assembles_consistent_code() {
+        let mut builder = CodeBuilder::new();
+        let (loop_start, done) = (builder.new_label(), builder.new_label());
+        for instruction in [
+            Instruction::Simple(ICONST_3),
+            Instruction::Simple(ISTORE_0),
+            Instruction::Label(loop_start),
+            Instruction::Simple(ILOAD_0),
+            Instruction::Jump(IFEQ, done),
+            Instruction::Iinc(0, -1),
+            Instruction::Jump(GOTO, loop_start),
+            Instruction::Label(done),
+            Instruction::Simple(RETURN),
+        ] {
+            builder.add(instruction);
+        }
+        let code = builder.build(&mut ConstantPoolBuilder::new(), "Test", MethodFlags::ACC_STATIC, "run", "()V").unwrap();
+        assert_eq!(code.code, [ICONST_3, ISTORE_0, ILOAD_0, IFEQ, 0, 9, IINC, 0, 0xFF, GOTO, 0xFF, 0xF9, RETURN]);
+        assert_eq!((code.max_stack, code.max_locals), (1, 1));
+    }
+
+    #[test]
+    fn fails_for_code_that_does_not_decode() {
+        assert_eq!(CodeBuilder::from_code(&[NOP, 0xFF], &[]).err(), Some(error(None, Some(1), "Bad instruction 0xff")));
+        // bipush without its operand
+        assert_eq!(CodeBuilder::from_code(&[NOP, BIPUSH], &[]).err(), Some(error(None, Some(1), "Bad instruction 0x10")));
+        assert_eq!(CodeBuilder::from_code(&[GOTO, 0, 4, SIPUSH, 0, 1, RETURN], &[]).err(),
+            Some(error(None, Some(0), "Branch to 4, which is not the start of an instruction")));
+        assert_eq!(CodeBuilder::from_code(&[NOP, GOTO, 0xFF, 0xF0], &[]).err(),
+            Some(error(None, Some(1), "Branch to -15, which is not the start of an instruction")));
+        let handler = ExceptionTableEntry { start_pc: 0, end_pc: 2, handler_pc: 1, catch_type: 0 };
+        assert_eq!(CodeBuilder::from_code(&[SIPUSH, 0, 1, ATHROW], &[handler]).err(),
+            Some(error(None, None, "Branch to 1, which is not the start of an instruction")));
+        assert!(CodeBuilder::from_code(&[ICONST_0, IFEQ, 0, 4, RETURN, RETURN], &[]).is_ok());
+    }
+
+    #[test]
+    fn fails_for_labels_that_are_placed_twice_or_never() {
+        let mut builder = CodeBuilder::new();
+        let label = builder.new_label();
+        builder.add(Instruction::Label(label));
+        builder.add(Instruction::Simple(NOP));
+        builder.add(Instruction::Label(label));
+        builder.add(Instruction::Simple(RETURN));
+        let result = builder.build(&mut ConstantPoolBuilder::new(), "Test", MethodFlags::ACC_STATIC, "run", "()V");
+        assert_eq!(result.err(), Some(error(Some(2), None, "Label Label(0) is placed twice")));
+
+        let mut builder = CodeBuilder::new();
+        let label = builder.new_label();
+        builder.add(Instruction::Simple(NOP));
+        builder.add(Instruction::Jump(GOTO, label));
+        let result = builder.build(&mut ConstantPoolBuilder::new(), "Test", MethodFlags::ACC_STATIC, "run", "()V");
+        assert_eq!(result.err(), Some(error(Some(1), None, "Label Label(0) is used but never placed")));
+
+        let mut builder = CodeBuilder::new();
+        let (start, end) = (builder.new_label(), builder.new_label());
+        builder.add(Instruction::Label(start));
+        builder.add(Instruction::Simple(RETURN));
+        builder.add_handler(start, end, start, 0);
+        let result = builder.build_unverified(&mut ConstantPoolBuilder::new(), 0, 0);
+        assert_eq!(result.err(), Some(error(None, None, "Label Label(1) is used but never placed")));
+    }
+
+    #[test]
+    fn fails_for_code_that_is_too_long() {
+        let mut instructions = vec![Instruction::Simple(NOP); 65535];
+        instructions.push(Instruction::Simple(RETURN));
+        assert_eq!(assemble(instructions, &mut ConstantPoolBuilder::new()).err(),
+            Some(error(None, None, "Code of 65536 bytes is too long, a method can have at most 65535 bytes")));
+    }
+
+    #[test]
+    fn fails_for_operand_stack_underflow() {
+        let instructions = vec![Instruction::Simple(ICONST_1), Instruction::Simple(IADD), Instruction::Simple(RETURN)];
+        assert_eq!(assemble(instructions, &mut ConstantPoolBuilder::new()).err(), Some(error(Some(1), Some(1), "Operand stack underflow")));
+        let instructions = vec![Instruction::Simple(ICONST_1), Instruction::Simple(DUP2), Instruction::Simple(RETURN)];
+        assert_eq!(assemble(instructions, &mut ConstantPoolBuilder::new()).err(), Some(error(Some(1), Some(1), "Operand stack underflow")));
+    }
+
+    #[test]
+    fn fails_for_code_that_falls_off_its_end() {
+        let instructions = vec![Instruction::Simple(ICONST_1), Instruction::Simple(POP)];
+        assert_eq!(assemble(instructions, &mut ConstantPoolBuilder::new()).err(), Some(error(Some(1), Some(1), "Code falls off its end")));
+    }
+
+    #[test]
+    fn fails_when_operand_stacks_of_different_heights_meet() {
+        let mut builder = CodeBuilder::new();
+        let join = builder.new_label();
+        for instruction in [
+            Instruction::Simple(ICONST_0),
+            Instruction::Simple(ICONST_1),
+            Instruction::Jump(IFEQ, join),
+            Instruction::Simple(ICONST_2),
+            Instruction::Label(join),
+            Instruction::Simple(RETURN),
+        ] {
+            builder.add(instruction);
+        }
+        let result = builder.build(&mut ConstantPoolBuilder::new(), "Test", MethodFlags::ACC_STATIC, "run", "()V");
+        assert_eq!(result.err(), Some(error(Some(4), Some(6), "Operand stacks of different heights meet")));
+    }
+
+    #[test]
+    fn fails_for_constants_of_the_wrong_kind() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let class = constant_pool.add_class("Test").unwrap();
+        let name_and_type = constant_pool.add_name_and_type("run", "()V").unwrap();
+        let field = constant_pool.add_field_ref("Test", "field", "()V").unwrap();
+        let instructions = vec![Instruction::Constant(INVOKESTATIC, class), Instruction::Simple(RETURN)];
+        assert_eq!(assemble(instructions, &mut constant_pool).err(),
+            Some(error(Some(0), Some(0), &format!("No field or method reference in constant pool at index {}", class))));
+        let instructions = vec![Instruction::Simple(NOP), Instruction::Constant(LDC, name_and_type), Instruction::Simple(RETURN)];
+        assert_eq!(assemble(instructions, &mut constant_pool).err(),
+            Some(error(Some(1), Some(1), &format!("No loadable constant in constant pool at index {}", name_and_type))));
+        let instructions = vec![Instruction::Simple(ACONST_NULL), Instruction::Constant(CHECKCAST, 0), Instruction::Simple(RETURN)];
+        assert_eq!(assemble(instructions, &mut constant_pool).err(), Some(error(Some(1), Some(1), "No class in constant pool at index 0")));
+        let instructions = vec![Instruction::Constant(GETSTATIC, field), Instruction::Simple(RETURN)];
+        assert_eq!(assemble(instructions, &mut constant_pool).err(),
+            Some(error(Some(0), Some(0), &format!("Bad descriptor ()V in constant pool at index {}", field))));
+    }
+
+    #[test]
+    fn fails_for_instructions_with_the_wrong_operands() {
+        let cases = [
+            (Instruction::Immediate(NEWARRAY, 2), "Illegal newarray type 2"),
+            (Instruction::Local(IADD, 1), "iadd doesn't use a local variable"),
+            (Instruction::Constant(IADD, 1), "iadd doesn't use a constant"),
+            (Instruction::Simple(BIPUSH), "bipush needs operands"),
+        ];
+        for (instruction, message) in cases {
+            let instructions = vec![Instruction::Simple(ICONST_1), instruction, Instruction::Simple(RETURN)];
+            assert_eq!(assemble(instructions, &mut ConstantPoolBuilder::new()).err(), Some(error(Some(1), Some(1), message)));
+        }
+        let mut builder = CodeBuilder::new();
+        let label = builder.new_label();
+        for instruction in [Instruction::Label(label), Instruction::Jump(IADD, label), Instruction::Simple(RETURN)] {
+            builder.add(instruction);
+        }
+        let result = builder.build(&mut ConstantPoolBuilder::new(), "Test", MethodFlags::ACC_STATIC, "run", "()V");
+        assert_eq!(result.err(), Some(error(Some(1), Some(0), "iadd is not a branch")));
+    }
+
+    #[test]
+    fn fails_for_a_tableswitch_without_targets() {
+        let mut builder = CodeBuilder::new();
+        let label = builder.new_label();
+        for instruction in [
+            Instruction::Simple(ICONST_0),
+            Instruction::TableSwitch { low: 0, default: label, targets: Vec::new() },
+            Instruction::Label(label),
+            Instruction::Simple(RETURN),
+        ] {
+            builder.add(instruction);
+        }
+        let result = builder.build(&mut ConstantPoolBuilder::new(), "Test", MethodFlags::ACC_STATIC, "run", "()V");
+        assert_eq!(result.err(), Some(error(Some(1), Some(1), "tableswitch from 0 can't have 0 targets")));
+    }
+
+    #[test]
+    fn fails_when_the_constant_pool_is_full() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        for value in 1..65535 {
+            constant_pool.add_integer(value).unwrap();
+        }
+        assert_eq!(assemble(vec![Instruction::Simple(RETURN)], &mut constant_pool).err(),
+            Some(error(None, None, "Constant pool is full, it can have at most 65534 entries")));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{exceptions::{JvmResult, Throwable}, interpreter::Interpreter, object::{Object, ObjectRef, Value, VmData}, runtime_class::Class, strings::new_java_string, thread::JavaThread, thread_natives::{set_eetop, THREAD_STATUS_RUNNABLE}};
+
+// Thread.NORM_PRIORITY
+const NORM_PRIORITY: i32 = 5;
+
+pub fn invoke_static(thread: &JavaThread, class: &Class, name: &str, descriptor: &str, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let method = class.find_declared_method(name, descriptor)
+        .ok_or_else(|| Throwable::new("java/lang/NoSuchMethodError", format!("{}.{}{}", class.name.replace('/', "."), name, descriptor)))?;
+    Interpreter::invoke(thread, method, args)
+}
+
+/// Allocates an object and runs one of its constructors.
+pub fn construct(thread: &JavaThread, class_name: &str, descriptor: &str, args: Vec<Value>) -> JvmResult<ObjectRef> {
+    let class = thread.vm.load_class(class_name)?;
+    class.initialize(thread)?;
+    let object = Object::new_instance(class.clone());
+    let mut args = args;
+    args.insert(0, Value::Reference(Some(object.clone())));
+    let constructor = class.find_declared_method("<init>", descriptor)
+        .ok_or_else(|| Throwable::new("java/lang/NoSuchMethodError", format!("{}.<init>{}", class_name.replace('/', "."), descriptor)))?;
+    Interpreter::invoke(thread, constructor, args)?;
+    Ok(object)
+}
+
+fn initialize_class(thread: &JavaThread, class_name: &str) -> JvmResult<()> {
+    thread.vm.load_class(class_name)?.initialize(thread)
+}
+
+/// Creates the `java.lang.Thread` object of the main thread, in the `main` thread group.
+fn create_main_thread(thread: &JavaThread) -> JvmResult<()> {
+    let system_group = construct(thread, "java/lang/ThreadGroup", "()V", Vec::new())?;
+    let main_name = Value::Reference(Some(new_java_string(thread, "main")?));
+    let main_group = construct(thread, "java/lang/ThreadGroup", "(Ljava/lang/ThreadGroup;Ljava/lang/String;)V",
+        vec![Value::Reference(Some(system_group)), main_name.clone()])?;
+
+    // The constructor asks for the current thread and its priority, so those need to be there before it runs
+    let thread_class = thread.vm.load_class("java/lang/Thread")?;
+    thread_class.initialize(thread)?;
+    let thread_object = Object::new_instance(thread_class.clone());
+    {
+        let mut fields = thread_object.fields();
+        fields[thread_class.instance_field_slot("priority", "I").unwrap()] = Value::Int(NORM_PRIORITY);
+        fields[thread_class.instance_field_slot("threadStatus", "I").unwrap()] = Value::Int(THREAD_STATUS_RUNNABLE);
+    }
+    *thread_object.vm_data.lock().unwrap() = Some(VmData::Thread(thread.control.clone()));
+    set_eetop(&thread_object, true);
+    *thread.object.borrow_mut() = Some(thread_object.clone());
+    let constructor = thread_class.find_declared_method("<init>", "(Ljava/lang/ThreadGroup;Ljava/lang/String;)V").unwrap();
+    Interpreter::invoke(thread, constructor, vec![Value::Reference(Some(thread_object)), Value::Reference(Some(main_group)), main_name])?;
+    Ok(())
+}
+
+/// Sets the constants that the VM provides to Unsafe (JDK 9 and later).
+fn initialize_unsafe_constants(thread: &JavaThread) -> JvmResult<()> {
+    let class = match thread.vm.class_path.read_class("jdk/internal/misc/UnsafeConstants") {
+        Some(_) => thread.vm.load_class("jdk/internal/misc/UnsafeConstants")?,
+        None => return Ok(())
+    };
+    class.initialize(thread)?;
+    let constants = [
+        ("ADDRESS_SIZE0", Value::Int(size_of::<usize>() as i32)),
+        ("PAGE_SIZE", Value::Int(4096)),
+        ("BIG_ENDIAN", Value::Int(cfg!(target_endian = "big") as i32)),
+        ("UNALIGNED_ACCESS", Value::Int(1)),
+    ];
+    let mut static_values = class.static_values.lock().unwrap();
+    for (name, value) in constants {
+        if let Some(field) = class.fields.iter().find(|field| field.is_static() && field.name == name) {
+            static_values[field.slot] = value;
+        }
+    }
+    Ok(())
+}
+
+/// Gives the System of the stub library its standard streams, which write straight to a file descriptor,
+/// and the main thread its Thread object.
+fn initialize_stub_system(thread: &JavaThread) -> JvmResult<()> {
+    let system_class = thread.vm.load_class("java/lang/System")?;
+    system_class.initialize(thread)?;
+    let print_stream_class = thread.vm.load_class("java/io/PrintStream")?;
+    print_stream_class.initialize(thread)?;
+    for (setter, fd) in [("setOut0", 1), ("setErr0", 2)] {
+        let stream = Object::new_instance(print_stream_class.clone());
+        stream.fields()[print_stream_class.instance_field_slot("fd", "I").unwrap()] = Value::Int(fd);
+        invoke_static(thread, &system_class, setter, "(Ljava/io/PrintStream;)V", vec![Value::Reference(Some(stream))])?;
+    }
+
+    let thread_class = thread.vm.load_class("java/lang/Thread")?;
+    thread_class.initialize(thread)?;
+    let thread_object = Object::new_instance(thread_class.clone());
+    thread_object.fields()[thread_class.instance_field_slot("name", "Ljava/lang/String;").unwrap()] = Value::Reference(Some(new_java_string(thread, "main")?));
+    *thread_object.vm_data.lock().unwrap() = Some(VmData::Thread(thread.control.clone()));
+    *thread.object.borrow_mut() = Some(thread_object);
+    Ok(())
+}
+
+/// Keeps reflection on the native accessors. After a few calls, the JDK would otherwise generate accessor classes,
+/// which needs defineClass. JDK 9 and later only read the setting once the module system is up, which it never is here.
+fn disable_accessor_generation(thread: &JavaThread) -> JvmResult<()> {
+    let factory_name = ["jdk/internal/reflect/ReflectionFactory", "sun/reflect/ReflectionFactory"].into_iter()
+        .find(|name| thread.vm.class_path.read_class(name).is_some());
+    let factory = match factory_name {
+        Some(name) => thread.vm.load_class(name)?,
+        None => return Ok(())
+    };
+    // AccessibleObject hands the factory its access to java.lang.reflect when it gets initialized
+    initialize_class(thread, "java/lang/reflect/AccessibleObject")?;
+    factory.initialize(thread)?;
+    if let Some(field) = factory.fields.iter().find(|field| field.is_static() && field.name == "inflationThreshold") {
+        factory.static_values.lock().unwrap()[field.slot] = Value::Int(i32::MAX);
+    }
+    Ok(())
+}
+
+/// Brings up the class library the way HotSpot does before it loads the main class:
+/// the main thread gets its Thread object, and System sets up properties and the standard streams.
+pub fn initialize_system(thread: &JavaThread) -> JvmResult<()> {
+    if thread.vm.class_path.uses_stub_library() {
+        return initialize_stub_system(thread);
+    }
+    initialize_class(thread, "java/lang/String")?;
+    let system_class = thread.vm.load_class("java/lang/System")?;
+    system_class.initialize(thread)?;
+    initialize_class(thread, "java/lang/Class")?;
+    initialize_class(thread, "java/lang/ThreadGroup")?;
+    create_main_thread(thread)?;
+    initialize_unsafe_constants(thread)?;
+
+    // JDK 9 and later split the initialization in phases, JDK 8 does it all at once
+    if system_class.find_declared_method("initPhase1", "()V").is_some() {
+        invoke_static(thread, &system_class, "initPhase1", "()V", Vec::new())?;
+    } else {
+        invoke_static(thread, &system_class, "initializeSystemClass", "()V", Vec::new())?;
+    }
+    disable_accessor_generation(thread)?;
+    Ok(())
+}
+
+/// Runs the shutdown hooks, like the JDK does when the last non-daemon thread ends.
+pub fn shutdown(thread: &JavaThread) -> JvmResult<()> {
+    // The stub library has no shutdown hooks
+    if thread.vm.class_path.uses_stub_library() {
+        return Ok(());
+    }
+    let shutdown_class = thread.vm.load_class("java/lang/Shutdown")?;
+    shutdown_class.initialize(thread)?;
+    invoke_static(thread, &shutdown_class, "shutdown", "()V", Vec::new())?;
+    Ok(())
+}
@@ -1,45 +1,50 @@
 pub struct ByteArray {
     pub bytes: Vec<u8>,
-    current: usize
+    current: usize,
+    // What the bytes are, for the error when they end too early
+    name: &'static str
 }
 
 impl ByteArray {
     pub fn new(bytes: Vec<u8>, current: usize) -> Self {
-        Self { bytes, current }
+        Self { bytes, current, name: "class file" }
     }
-    pub fn read_u32(&mut self) -> u32 {
-        let buffer: [u8; 4] = [
-            self.bytes[self.current],
-            self.bytes[self.current+1],
-            self.bytes[self.current+2],
-            self.bytes[self.current+3],
-        ];
-        self.current += 4;
-        u32::from_be_bytes(buffer)
+
+    /// Reads the bytes of an attribute on their own, so that it can't be read past its length.
+    pub fn attribute(bytes: Vec<u8>, name: &'static str) -> Self {
+        Self { bytes, current: 0, name }
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, String> {
+        let buffer: [u8; 4] = self.read_bytes(4)?.try_into().unwrap();
+        Ok(u32::from_be_bytes(buffer))
     }
 
-    pub fn read_u16(&mut self) -> u16 {
-        let buffer: [u8; 2] = [
-            self.bytes[self.current],
-            self.bytes[self.current+1],
-        ];
-        self.current += 2;
-        u16::from_be_bytes(buffer)
-    }    
+    pub fn read_u16(&mut self) -> Result<u16, String> {
+        let buffer: [u8; 2] = self.read_bytes(2)?.try_into().unwrap();
+        Ok(u16::from_be_bytes(buffer))
+    }
 
-    pub fn read_u8(&mut self) -> u8 {
-        self.current += 1;
-        self.bytes[self.current-1]
+    pub fn read_u8(&mut self) -> Result<u8, String> {
+        Ok(self.read_bytes(1)?[0])
     }
 
-    pub fn read_bytes(&mut self, size: usize) -> &[u8] {
-        let bytes = &self.bytes[self.current..(self.current+size)];
-        self.current += size;
-        bytes
+    /// Fails when the data ends before the bytes.
+    pub fn read_bytes(&mut self, size: usize) -> Result<&[u8], String> {
+        let end = self.current.checked_add(size).filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("Truncated {}, it ends at byte {}", self.name, self.bytes.len()))?;
+        let bytes = &self.bytes[self.current..end];
+        self.current = end;
+        Ok(bytes)
     }
 
     pub fn len(&self) -> usize {
         self.bytes.len()
     }
 
-}
\ No newline at end of file
+    /// Whether everything has been read.
+    pub fn is_at_end(&self) -> bool {
+        self.current == self.bytes.len()
+    }
+
+}
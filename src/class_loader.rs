@@ -1,27 +1,30 @@
-use crate::{byte_array::{self, ByteArray}, descriptor_parser::parse_descriptor};
+use crate::{byte_array::{self, ByteArray}, descriptor_parser::{parse_descriptor, Descriptor}, format_checker::is_method_descriptor};
 
 use std::fs;
-use cesu8::from_cesu8;
+use cesu8::from_java_cesu8;
 use sha256::digest;
 use time::OffsetDateTime;
 use bitflags::bitflags;
 
-const MAGIC_NUMBER : u32 = 0xCAFEBABE;
-
-const CT_UTF8: u8            = 1;
-const CT_INTEGER: u8         = 3;
-const CT_FLOAT: u8           = 4;
-const CT_LONG: u8            = 5;
-const CT_DOUBLE: u8          = 6;
-const CT_CLASS: u8           = 7;
-const CT_STRING: u8          = 8;
-const CT_FIELDREF: u8        = 9;
-const CT_METHODREF: u8       = 10;
-const CT_INTERFACEMETHOD: u8 = 11;
-const CT_NAMEANDTYPE: u8     = 12;
-const CT_METHODHANDLE: u8    = 15;
-const CT_METHODTYPE: u8      = 16;
-const CT_INVOKEDYNAMIC: u8   = 18;
+pub const MAGIC_NUMBER : u32 = 0xCAFEBABE;
+
+pub const CT_UTF8: u8            = 1;
+pub const CT_INTEGER: u8         = 3;
+pub const CT_FLOAT: u8           = 4;
+pub const CT_LONG: u8            = 5;
+pub const CT_DOUBLE: u8          = 6;
+pub const CT_CLASS: u8           = 7;
+pub const CT_STRING: u8          = 8;
+pub const CT_FIELDREF: u8        = 9;
+pub const CT_METHODREF: u8       = 10;
+pub const CT_INTERFACEMETHOD: u8 = 11;
+pub const CT_NAMEANDTYPE: u8     = 12;
+pub const CT_METHODHANDLE: u8    = 15;
+pub const CT_METHODTYPE: u8      = 16;
+pub const CT_DYNAMIC: u8         = 17;
+pub const CT_INVOKEDYNAMIC: u8   = 18;
+pub const CT_MODULE: u8          = 19;
+pub const CT_PACKAGE: u8         = 20;
 
 bitflags! {
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -56,6 +59,21 @@ bitflags! {
     }
 }
 
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct FieldFlags: u16 {
+        const ACC_PUBLIC    = 0x0001;
+        const ACC_PRIVATE   = 0x0002;
+        const ACC_PROTECTED = 0x0004;
+        const ACC_STATIC    = 0x0008;
+        const ACC_FINAL     = 0x0010;
+        const ACC_VOLATILE  = 0x0040;
+        const ACC_TRANSIENT = 0x0080;
+        const ACC_SYNTHETIC = 0x1000;
+        const ACC_ENUM      = 0x4000;
+    }
+}
+
 impl MethodFlags {
     pub fn as_keyword(&self) -> &'static str {
         match *self {
@@ -76,6 +94,23 @@ impl MethodFlags {
     }
 }
 
+impl FieldFlags {
+    pub fn as_keyword(&self) -> &'static str {
+        match *self {
+            Self::ACC_PUBLIC => "public",
+            Self::ACC_PRIVATE => "private",
+            Self::ACC_PROTECTED => "protected",
+            Self::ACC_STATIC => "static",
+            Self::ACC_FINAL => "final",
+            Self::ACC_VOLATILE => "volatile",
+            Self::ACC_TRANSIENT => "transient",
+            Self::ACC_SYNTHETIC => "synthetic",
+            Self::ACC_ENUM => "enum",
+            other => "unknown"
+        }
+    }
+}
+
 impl ClassFlags {
     pub fn as_keyword(&self) -> &'static str {
         match *self {
@@ -112,18 +147,28 @@ pub struct ClassInfo {
     // static_fields_count: u16,
 }
 
+impl ClassInfo {
+    /// Returns an entry of the BootstrapMethods attribute, which invokedynamic instructions refer to.
+    pub fn get_bootstrap_method(&self, index: u16) -> Option<&BootstrapMethod> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::BootstrapMethods(bootstrap_methods) => bootstrap_methods.methods.get(index as usize),
+            other => None
+        })
+    }
+}
+
 pub struct MethodInfo {
     pub access_flags: MethodFlags,
     pub descriptor_index: u16,
     pub attributes: Vec<AttributeInfo>,
     pub return_type: String,
     pub args: Vec<String>,
-    name_index: u16,
+    pub name_index: u16,
 }
 
 impl MethodInfo {
-    pub fn get_name<'a>(self: &Self, constant_pool: &'a ConstantPool) -> &'a str {
-        return &constant_pool.get_string(self.name_index);
+    pub fn get_name<'a>(&self, constant_pool: &'a ConstantPool) -> &'a str {
+        constant_pool.get_string(self.name_index)
     }
 
     pub fn is_constructor(&self, constant_pool: &ConstantPool) -> bool {
@@ -142,6 +187,14 @@ impl MethodInfo {
         }
         code_attribute
     }
+
+    /// Returns the constant pool indexes of the checked exceptions the method declares to throw.
+    pub fn get_exceptions(&self) -> &[u16] {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::Exceptions(exceptions) => Some(exceptions.exception_index_table.as_slice()),
+            other => None
+        }).unwrap_or(&[])
+    }
 }
 
 pub struct ConstantPool {
@@ -149,17 +202,34 @@ pub struct ConstantPool {
 }
 
 impl ConstantPool {
+    /// The string of a Utf8 constant, None when the index doesn't point at one.
+    pub fn get_utf8(&self, index: u16) -> Option<&str> {
+        match self.constants.get(index as usize) {
+            Some(ConstantPoolItem::CPUTF8Info { utf8_string, .. }) => Some(utf8_string),
+            other => None
+        }
+    }
+
     pub fn get_string(self: &ConstantPool, index: u16) -> &str {
         // TODO: Check index?
         let item = &self.constants[(index) as usize];
         match item {
-            ConstantPoolItem::CPUTF8Info { utf8_string } => {
+            ConstantPoolItem::CPUTF8Info { utf8_string, .. } => {
                 utf8_string
             },
             other => panic!("No string found in constantpool at index: {}", index)
         }
     }
     
+    /// Returns the UTF-16 code units of a Utf8 constant, with any unpaired surrogates in it.
+    pub fn get_utf16(self: &ConstantPool, index: u16) -> Vec<u16> {
+        match &self.constants[index as usize] {
+            ConstantPoolItem::CPUTF8Info { bytes: Some(bytes), .. } => ClassLoader::decode_modified_utf8_to_utf16(bytes),
+            ConstantPoolItem::CPUTF8Info { utf8_string, .. } => utf8_string.encode_utf16().collect(),
+            other => panic!("No string found in constantpool at index: {}", index)
+        }
+    }
+
     pub fn get_class_info(self: &ConstantPool, index: u16) -> u16 {
         let item = &self.constants[(index) as usize];
         match item {
@@ -182,10 +252,12 @@ impl ConstantPool {
 }
 
 pub struct ATLineNumberTable{
+    pub name_index: u16,
     pub entries: Vec<LineNumberTableEntry>
 }
 
 pub struct ATCode{
+    pub name_index: u16,
     pub max_stack: u16,
     pub max_locals: u16,
     pub code: Vec<u8>,
@@ -193,27 +265,156 @@ pub struct ATCode{
     pub attributes: Vec<AttributeInfo>,
 }
 
+impl ATCode {
+    /// Returns the frames of the StackMapTable attribute, which is empty for code without branches.
+    pub fn get_stack_map_frames(&self) -> &[StackMapFrame] {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::StackMapTable(stack_map_table) => Some(stack_map_table.entries.as_slice()),
+            other => None
+        }).unwrap_or(&[])
+    }
+
+    /// Why the StackMapTable couldn't be read completely, if it couldn't.
+    pub fn get_stack_map_error(&self) -> Option<&str> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::StackMapTable(stack_map_table) => stack_map_table.error.as_deref(),
+            other => None
+        })
+    }
+
+    pub fn has_stack_map_table(&self) -> bool {
+        self.attributes.iter().any(|attribute| matches!(attribute, AttributeInfo::StackMapTable(_)))
+    }
+
+
+    /// Looks up the source line of an instruction in the LineNumberTable attributes, if there are any.
+    pub fn get_line_number(&self, pc: usize) -> Option<u16> {
+        self.attributes.iter()
+            .filter_map(|attribute| match attribute {
+                AttributeInfo::LineNumberTable(table) => Some(&table.entries),
+                other => None
+            })
+            .flatten()
+            .filter(|entry| entry.start_pc as usize <= pc)
+            .max_by_key(|entry| entry.start_pc)
+            .map(|entry| entry.line_number)
+    }
+}
+
 pub struct ATLocalVariableTable{
+    pub name_index: u16,
     pub entries: Vec<LocalVariableTableEnty>
 }
 
 pub struct ATSourceFile{
+    pub name_index: u16,
     pub source_file_index: u16
 }
 
+pub struct ATConstantValue{
+    pub name_index: u16,
+    pub constantvalue_index: u16
+}
+
+pub struct ATExceptions{
+    pub name_index: u16,
+    pub exception_index_table: Vec<u16>
+}
+
+pub struct BootstrapMethod {
+    pub bootstrap_method_ref: u16,
+    pub bootstrap_arguments: Vec<u16>
+}
+
+pub struct ATBootstrapMethods{
+    pub name_index: u16,
+    pub methods: Vec<BootstrapMethod>
+}
+
+/// The type of a local variable or operand stack entry in a stack map frame (JVMS §4.7.4).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VerificationTypeInfo {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    // Constant pool index of the class
+    Object(u16),
+    // Offset of the `new` instruction that created the object
+    Uninitialized(u16),
+}
+
+/// A frame of the StackMapTable attribute. The frame type is kept, as some frames can be written in two ways.
+pub enum StackMapFrame {
+    // Frame types 0-63, and 251 for same_frame_extended
+    Same { frame_type: u8, offset_delta: u16 },
+    // Frame types 64-127, and 247 for same_locals_1_stack_item_frame_extended
+    SameLocals1StackItem { frame_type: u8, offset_delta: u16, stack: VerificationTypeInfo },
+    // Frame types 248-250, which remove 251 - frame_type locals
+    Chop { frame_type: u8, offset_delta: u16 },
+    // Frame types 252-254
+    Append { offset_delta: u16, locals: Vec<VerificationTypeInfo> },
+    Full { offset_delta: u16, locals: Vec<VerificationTypeInfo>, stack: Vec<VerificationTypeInfo> },
+}
+
+impl StackMapFrame {
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::Same { offset_delta, .. } |
+            StackMapFrame::SameLocals1StackItem { offset_delta, .. } |
+            StackMapFrame::Chop { offset_delta, .. } |
+            StackMapFrame::Append { offset_delta, .. } |
+            StackMapFrame::Full { offset_delta, .. } => *offset_delta
+        }
+    }
+}
+
+pub struct ATStackMapTable{
+    pub name_index: u16,
+    pub entries: Vec<StackMapFrame>,
+    // Why the frames after the entries couldn't be read, which the verifier reports for the method
+    pub error: Option<String>
+}
+
+pub struct ATUnknown{
+    pub name_index: u16,
+    pub info: Vec<u8>
+}
+
 pub enum AttributeInfo {
     LineNumberTable(ATLineNumberTable),
     Code(ATCode),
     LocalVariableTable(ATLocalVariableTable),
-    SourceFile(ATSourceFile)
+    SourceFile(ATSourceFile),
+    ConstantValue(ATConstantValue),
+    Exceptions(ATExceptions),
+    BootstrapMethods(ATBootstrapMethods),
+    StackMapTable(ATStackMapTable),
+    Unknown(ATUnknown)
 }
 
 pub struct FieldInfo {
-    access_flags: u16,
-    name_index: u16,
-    desciptor_index: u16,
-    is_private: bool,
-    attributes: Vec<AttributeInfo>
+    pub access_flags: FieldFlags,
+    pub descriptor_index: u16,
+    pub attributes: Vec<AttributeInfo>,
+    pub name_index: u16,
+}
+
+impl FieldInfo {
+    pub fn get_name<'a>(&self, constant_pool: &'a ConstantPool) -> &'a str {
+        constant_pool.get_string(self.name_index)
+    }
+
+    /// Returns the index of the initial value for static fields that have a ConstantValue attribute.
+    pub fn get_constant_value(&self) -> Option<u16> {
+        self.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::ConstantValue(constant_value) => Some(constant_value.constantvalue_index),
+            other => None
+        })
+    }
 }
 
 
@@ -224,71 +425,145 @@ pub struct ClassLoader {
 pub enum ConstantPoolItem {
     CPMethodRef{class_index: u16, name_and_type_index: u16},
     CPClassInfo{name_index: u16},
-    CPUTF8Info{utf8_string: String},
+    // The bytes are only kept for strings with unpaired surrogates, which a String can't hold
+    CPUTF8Info{utf8_string: String, bytes: Option<Vec<u8>>},
     CPFieldRef{class_index: u16, name_and_type_index: u16},
     CPStringInfo{string_index: u16},
-    CPNameAndTypeInfo{name_index: u16, descriptor_index: u16}
+    CPNameAndTypeInfo{name_index: u16, descriptor_index: u16},
+    CPIntegerInfo{value: i32},
+    CPFloatInfo{value: f32},
+    CPLongInfo{value: i64},
+    CPDoubleInfo{value: f64},
+    CPInterfaceMethodRef{class_index: u16, name_and_type_index: u16},
+    CPMethodHandleInfo{reference_kind: u8, reference_index: u16},
+    CPMethodTypeInfo{descriptor_index: u16},
+    CPDynamicInfo{bootstrap_method_attr_index: u16, name_and_type_index: u16},
+    CPInvokeDynamicInfo{bootstrap_method_attr_index: u16, name_and_type_index: u16},
+    CPModuleInfo{name_index: u16},
+    CPPackageInfo{name_index: u16},
+    // Second slot taken up by a long or double constant
+    CPUnusable
 }
 
 struct AttributeParser {
 }
 
 pub struct ExceptionTableEntry {
-    start_pc: u16,
-    end_pc: u16,
-    handler_pc: u16,
-    catch_type: u16,
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    pub catch_type: u16,
 }
 
 pub struct LineNumberTableEntry {
-    start_pc: u16,
-    line_number: u16,
+    pub start_pc: u16,
+    pub line_number: u16,
 }
 
 pub struct LocalVariableTableEnty {
-    start_pc: u16,
-    length: u16,
-    name_index: u16,
-    descriptor_index: u16,
-    index: u16,
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub index: u16,
 }
 
 impl AttributeParser {
-    fn read_exception_table_entry(byte_array: &mut ByteArray) -> ExceptionTableEntry {
-        let start_pc = byte_array.read_u16();
-        let end_pc = byte_array.read_u16();
-        let handler_pc = byte_array.read_u16();
-        let catch_type = byte_array.read_u16();
+    fn read_exception_table_entry(byte_array: &mut ByteArray) -> Result<ExceptionTableEntry, String> {
+        let start_pc = byte_array.read_u16()?;
+        let end_pc = byte_array.read_u16()?;
+        let handler_pc = byte_array.read_u16()?;
+        let catch_type = byte_array.read_u16()?;
 
-        ExceptionTableEntry{start_pc, end_pc, handler_pc, catch_type}
+        Ok(ExceptionTableEntry{start_pc, end_pc, handler_pc, catch_type})
     }
-    fn read_exception_table(byte_array: &mut ByteArray) -> Vec<ExceptionTableEntry> {
-        let exception_table_length = byte_array.read_u16() as usize;
+    fn read_exception_table(byte_array: &mut ByteArray) -> Result<Vec<ExceptionTableEntry>, String> {
+        let exception_table_length = byte_array.read_u16()? as usize;
         let mut exception_table: Vec<ExceptionTableEntry> = Vec::with_capacity(exception_table_length);
         for _current_exception in 0..exception_table_length
         {
-            exception_table.push(Self::read_exception_table_entry(byte_array));
+            exception_table.push(Self::read_exception_table_entry(byte_array)?);
+        }
+        Ok(exception_table)
+    }
+
+    fn read_verification_type(byte_array: &mut ByteArray) -> Result<VerificationTypeInfo, String> {
+        Ok(match byte_array.read_u8()? {
+            0 => VerificationTypeInfo::Top,
+            1 => VerificationTypeInfo::Integer,
+            2 => VerificationTypeInfo::Float,
+            3 => VerificationTypeInfo::Double,
+            4 => VerificationTypeInfo::Long,
+            5 => VerificationTypeInfo::Null,
+            6 => VerificationTypeInfo::UninitializedThis,
+            7 => VerificationTypeInfo::Object(byte_array.read_u16()?),
+            8 => VerificationTypeInfo::Uninitialized(byte_array.read_u16()?),
+            other => return Err(format!("Unknown verification type {} in StackMapTable", other))
+        })
+    }
+
+    fn read_verification_types(byte_array: &mut ByteArray, count: usize) -> Result<Vec<VerificationTypeInfo>, String> {
+        (0..count).map(|_| Self::read_verification_type(byte_array)).collect()
+    }
+
+    fn read_stack_map_frame(byte_array: &mut ByteArray) -> Result<StackMapFrame, String> {
+        let frame_type = byte_array.read_u8()?;
+        Ok(match frame_type {
+            0..=63 => StackMapFrame::Same { frame_type, offset_delta: frame_type as u16 },
+            64..=127 => StackMapFrame::SameLocals1StackItem { frame_type, offset_delta: frame_type as u16 - 64, stack: Self::read_verification_type(byte_array)? },
+            247 => {
+                let offset_delta = byte_array.read_u16()?;
+                StackMapFrame::SameLocals1StackItem { frame_type, offset_delta, stack: Self::read_verification_type(byte_array)? }
+            },
+            248..=250 => StackMapFrame::Chop { frame_type, offset_delta: byte_array.read_u16()? },
+            251 => StackMapFrame::Same { frame_type, offset_delta: byte_array.read_u16()? },
+            252..=254 => {
+                let offset_delta = byte_array.read_u16()?;
+                StackMapFrame::Append { offset_delta, locals: Self::read_verification_types(byte_array, frame_type as usize - 251)? }
+            },
+            255 => {
+                let offset_delta = byte_array.read_u16()?;
+                let number_of_locals = byte_array.read_u16()? as usize;
+                let locals = Self::read_verification_types(byte_array, number_of_locals)?;
+                let number_of_stack_items = byte_array.read_u16()? as usize;
+                let stack = Self::read_verification_types(byte_array, number_of_stack_items)?;
+                StackMapFrame::Full { offset_delta, locals, stack }
+            },
+            other => return Err(format!("Unknown stack map frame type {}", other))
+        })
+    }
+
+    /// Reads the frames into entries until the end of the table, or the first one that is corrupt.
+    fn read_stack_map_frames(byte_array: &mut ByteArray, entries: &mut Vec<StackMapFrame>) -> Result<(), String> {
+        let number_of_entries = byte_array.read_u16()? as usize;
+        for _entry in 0..number_of_entries {
+            entries.push(Self::read_stack_map_frame(byte_array)?);
+        }
+        if !byte_array.is_at_end() {
+            return Err(String::from("StackMapTable is longer than its frames"));
         }
-        exception_table
+        Ok(())
     }
 
-    fn read_attributes(byte_array: &mut ByteArray, constant_pool: &ConstantPool) -> Vec<AttributeInfo> {
-        let count = byte_array.read_u16() as usize;
+    fn read_attributes(byte_array: &mut ByteArray, constant_pool: &ConstantPool) -> Result<Vec<AttributeInfo>, String> {
+        let count = byte_array.read_u16()? as usize;
         let mut vec: Vec<AttributeInfo> = Vec::with_capacity(count);
         for _current_attribute in 0..count {
-            let attribute_name_index = byte_array.read_u16();
-            let attribute_length = byte_array.read_u32();
-            let name = constant_pool.get_string(attribute_name_index);
+            let attribute_name_index = byte_array.read_u16()?;
+            let attribute_length = byte_array.read_u32()?;
+            let name = constant_pool.get_utf8(attribute_name_index)
+                .ok_or_else(|| format!("Attribute name index {} is not a Utf8 constant", attribute_name_index))?;
             match name {
                 "Code" => {
-                    let max_stack = byte_array.read_u16();
-                    let max_locals = byte_array.read_u16();
-                    let code_length = byte_array.read_u32() as usize;
-                    let code = byte_array.read_bytes(code_length);
+                    let max_stack = byte_array.read_u16()?;
+                    let max_locals = byte_array.read_u16()?;
+                    let code_length = byte_array.read_u32()? as usize;
+                    let code = byte_array.read_bytes(code_length)?;
                     let code_vec = code.to_vec().clone();
-                    let exceptions = Self::read_exception_table(byte_array);
-                    let attributes = Self::read_attributes(byte_array, constant_pool);
+                    let exceptions = Self::read_exception_table(byte_array)?;
+                    let attributes = Self::read_attributes(byte_array, constant_pool)?;
                     vec.push(AttributeInfo::Code(ATCode {
+                        name_index: attribute_name_index,
                         max_stack,
                         max_locals,
                         code: code_vec,
@@ -297,24 +572,24 @@ impl AttributeParser {
                     }));
                 },
                 "LineNumberTable" => {
-                    let line_number_table_length = byte_array.read_u16() as usize;
+                    let line_number_table_length = byte_array.read_u16()? as usize;
                     let mut entries = Vec::with_capacity(line_number_table_length);
                     for _line_number_table_entry in 0..line_number_table_length {
-                        let start_pc = byte_array.read_u16();
-                        let line_number = byte_array.read_u16();
+                        let start_pc = byte_array.read_u16()?;
+                        let line_number = byte_array.read_u16()?;
                         entries.push(LineNumberTableEntry{start_pc, line_number, });
                     }
-                    vec.push(AttributeInfo::LineNumberTable(ATLineNumberTable {entries}));
+                    vec.push(AttributeInfo::LineNumberTable(ATLineNumberTable {name_index: attribute_name_index, entries}));
                 }
                 "LocalVariableTable" => {
-                    let local_variable_table_length = byte_array.read_u16() as usize;
+                    let local_variable_table_length = byte_array.read_u16()? as usize;
                     let mut entries = Vec::with_capacity(local_variable_table_length);
                     for _local_variable_table_entry_index in 0..local_variable_table_length {
-                        let start_pc = byte_array.read_u16();
-                        let length = byte_array.read_u16();
-                        let name_index = byte_array.read_u16();
-                        let descriptor_index = byte_array.read_u16();
-                        let index = byte_array.read_u16();
+                        let start_pc = byte_array.read_u16()?;
+                        let length = byte_array.read_u16()?;
+                        let name_index = byte_array.read_u16()?;
+                        let descriptor_index = byte_array.read_u16()?;
+                        let index = byte_array.read_u16()?;
                         entries.push(LocalVariableTableEnty{
                             start_pc,
                             length,
@@ -323,116 +598,270 @@ impl AttributeParser {
                             index
                         })
                     }
-                    vec.push(AttributeInfo::LocalVariableTable(ATLocalVariableTable {entries}));
+                    vec.push(AttributeInfo::LocalVariableTable(ATLocalVariableTable {name_index: attribute_name_index, entries}));
                 },
                 "SourceFile" => {
-                    let source_file_index = byte_array.read_u16();
-                    vec.push(AttributeInfo::SourceFile(ATSourceFile {source_file_index}));
+                    let source_file_index = byte_array.read_u16()?;
+                    vec.push(AttributeInfo::SourceFile(ATSourceFile {name_index: attribute_name_index, source_file_index}));
+                },
+                "ConstantValue" => {
+                    let constantvalue_index = byte_array.read_u16()?;
+                    vec.push(AttributeInfo::ConstantValue(ATConstantValue {name_index: attribute_name_index, constantvalue_index}));
+                },
+                "Exceptions" => {
+                    let number_of_exceptions = byte_array.read_u16()? as usize;
+                    let exception_index_table = (0..number_of_exceptions).map(|_| byte_array.read_u16()).collect::<Result<_, _>>()?;
+                    vec.push(AttributeInfo::Exceptions(ATExceptions {name_index: attribute_name_index, exception_index_table}));
+                }
+                "BootstrapMethods" => {
+                    let num_bootstrap_methods = byte_array.read_u16()? as usize;
+                    let mut methods = Vec::with_capacity(num_bootstrap_methods);
+                    for _bootstrap_method in 0..num_bootstrap_methods {
+                        let bootstrap_method_ref = byte_array.read_u16()?;
+                        let num_bootstrap_arguments = byte_array.read_u16()? as usize;
+                        let bootstrap_arguments = (0..num_bootstrap_arguments).map(|_| byte_array.read_u16()).collect::<Result<_, _>>()?;
+                        methods.push(BootstrapMethod {bootstrap_method_ref, bootstrap_arguments});
+                    }
+                    vec.push(AttributeInfo::BootstrapMethods(ATBootstrapMethods {name_index: attribute_name_index, methods}));
+                }
+                "StackMapTable" => {
+                    let mut table = ByteArray::attribute(byte_array.read_bytes(attribute_length as usize)?.to_vec(), "StackMapTable");
+                    let mut entries = Vec::new();
+                    let error = Self::read_stack_map_frames(&mut table, &mut entries).err();
+                    vec.push(AttributeInfo::StackMapTable(ATStackMapTable {name_index: attribute_name_index, entries, error}));
+                }
+                other => {
+                    let info = byte_array.read_bytes(attribute_length as usize)?.to_vec();
+                    vec.push(AttributeInfo::Unknown(ATUnknown {name_index: attribute_name_index, info}));
                 }
-                other => panic!("Unknown attribute {} found", name)
             }
         }
-        vec
+        Ok(vec)
     }
 }
 
 
 impl ClassLoader {
-    fn read_constant_pool_item(tag: u8, byte_array: &mut ByteArray) -> ConstantPoolItem {
-        match tag {
+    fn decode_modified_utf8(bytes: &[u8]) -> String {
+        if let Ok(string) = from_java_cesu8(bytes) {
+            return string.to_string();
+        }
+        // Java strings can contain unpaired surrogates, which Rust strings can't hold, those get replaced
+        String::from_utf16_lossy(&Self::decode_modified_utf8_to_utf16(bytes))
+    }
+
+    /// Decodes modified UTF-8 to UTF-16 code units by hand, which keeps unpaired surrogates.
+    pub fn decode_modified_utf8_to_utf16(bytes: &[u8]) -> Vec<u16> {
+        let mut code_units: Vec<u16> = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let byte = bytes[i] as u16;
+            if byte & 0x80 == 0 {
+                code_units.push(byte);
+                i += 1;
+            } else if byte & 0xE0 == 0xC0 && i + 1 < bytes.len() {
+                code_units.push(((byte & 0x1F) << 6) | (bytes[i + 1] as u16 & 0x3F));
+                i += 2;
+            } else if i + 2 < bytes.len() {
+                code_units.push(((byte & 0x0F) << 12) | ((bytes[i + 1] as u16 & 0x3F) << 6) | (bytes[i + 2] as u16 & 0x3F));
+                i += 3;
+            } else {
+                break;
+            }
+        }
+        code_units
+    }
+
+    fn read_constant_pool_item(tag: u8, index: usize, byte_array: &mut ByteArray) -> Result<ConstantPoolItem, String> {
+        Ok(match tag {
             CT_METHODREF => {
-                let class_index = byte_array.read_u16();
-                let name_and_type_index = byte_array.read_u16();
+                let class_index = byte_array.read_u16()?;
+                let name_and_type_index = byte_array.read_u16()?;
                 ConstantPoolItem::CPMethodRef {
                     class_index,
                     name_and_type_index
                 }
             },
             CT_CLASS => {
-                let name_index = byte_array.read_u16();
+                let name_index = byte_array.read_u16()?;
                 ConstantPoolItem::CPClassInfo {
                     name_index
                 }
             },
             CT_FIELDREF => {
-                let class_index = byte_array.read_u16();
-                let name_and_type_index = byte_array.read_u16();
+                let class_index = byte_array.read_u16()?;
+                let name_and_type_index = byte_array.read_u16()?;
                 ConstantPoolItem::CPFieldRef {
                     class_index,
                     name_and_type_index
                 }
             },
             CT_STRING => {
-                let string_index = byte_array.read_u16();
+                let string_index = byte_array.read_u16()?;
                 ConstantPoolItem::CPStringInfo {
                     string_index
                 }
             },
             CT_UTF8 => {
-                let size = byte_array.read_u16() as usize;
-                let bytes: &[u8] = byte_array.read_bytes(size);
-                let cpitem = ConstantPoolItem::CPUTF8Info{
-                    utf8_string:  from_cesu8(bytes).unwrap().to_string()
-                };
-                cpitem
+                let size = byte_array.read_u16()? as usize;
+                let bytes: &[u8] = byte_array.read_bytes(size)?;
+                let utf8_string = Self::decode_modified_utf8(bytes);
+                let lossy = from_java_cesu8(bytes).is_err();
+                ConstantPoolItem::CPUTF8Info{
+                    utf8_string,
+                    bytes: lossy.then(|| bytes.to_vec())
+                }
             },
             CT_NAMEANDTYPE => {
-                let name_index = byte_array.read_u16();
-                let descriptor_index = byte_array.read_u16();
+                let name_index = byte_array.read_u16()?;
+                let descriptor_index = byte_array.read_u16()?;
                 ConstantPoolItem::CPNameAndTypeInfo {
                     name_index,
                     descriptor_index
                 }
             },
-            other => panic!("Unknown tag {} read in Class", tag)
-        }
+            CT_INTEGER => {
+                ConstantPoolItem::CPIntegerInfo {
+                    value: byte_array.read_u32()? as i32
+                }
+            },
+            CT_FLOAT => {
+                ConstantPoolItem::CPFloatInfo {
+                    value: f32::from_bits(byte_array.read_u32()?)
+                }
+            },
+            CT_LONG => {
+                let high_bytes = byte_array.read_u32()? as u64;
+                let low_bytes = byte_array.read_u32()? as u64;
+                ConstantPoolItem::CPLongInfo {
+                    value: ((high_bytes << 32) | low_bytes) as i64
+                }
+            },
+            CT_DOUBLE => {
+                let high_bytes = byte_array.read_u32()? as u64;
+                let low_bytes = byte_array.read_u32()? as u64;
+                ConstantPoolItem::CPDoubleInfo {
+                    value: f64::from_bits((high_bytes << 32) | low_bytes)
+                }
+            },
+            CT_INTERFACEMETHOD => {
+                let class_index = byte_array.read_u16()?;
+                let name_and_type_index = byte_array.read_u16()?;
+                ConstantPoolItem::CPInterfaceMethodRef {
+                    class_index,
+                    name_and_type_index
+                }
+            },
+            CT_METHODHANDLE => {
+                let reference_kind = byte_array.read_u8()?;
+                let reference_index = byte_array.read_u16()?;
+                ConstantPoolItem::CPMethodHandleInfo {
+                    reference_kind,
+                    reference_index
+                }
+            },
+            CT_METHODTYPE => {
+                let descriptor_index = byte_array.read_u16()?;
+                ConstantPoolItem::CPMethodTypeInfo {
+                    descriptor_index
+                }
+            },
+            CT_DYNAMIC => {
+                let bootstrap_method_attr_index = byte_array.read_u16()?;
+                let name_and_type_index = byte_array.read_u16()?;
+                ConstantPoolItem::CPDynamicInfo {
+                    bootstrap_method_attr_index,
+                    name_and_type_index
+                }
+            },
+            CT_INVOKEDYNAMIC => {
+                let bootstrap_method_attr_index = byte_array.read_u16()?;
+                let name_and_type_index = byte_array.read_u16()?;
+                ConstantPoolItem::CPInvokeDynamicInfo {
+                    bootstrap_method_attr_index,
+                    name_and_type_index
+                }
+            },
+            CT_MODULE => {
+                let name_index = byte_array.read_u16()?;
+                ConstantPoolItem::CPModuleInfo {
+                    name_index
+                }
+            },
+            CT_PACKAGE => {
+                let name_index = byte_array.read_u16()?;
+                ConstantPoolItem::CPPackageInfo {
+                    name_index
+                }
+            },
+            other => return Err(format!("Unknown constant pool tag {} at index {}", tag, index))
+        })
     }
 
-    fn read_constant_pool(byte_array: &mut ByteArray) -> ConstantPool {
-        let cp_count: usize = byte_array.read_u16() as usize;
+    fn read_constant_pool(byte_array: &mut ByteArray) -> Result<ConstantPool, String> {
+        let cp_count: usize = byte_array.read_u16()? as usize;
 
         let mut constant_pool: ConstantPool = ConstantPool { constants: Vec::with_capacity(cp_count) };
 
         // Add filler
         constant_pool.constants.push(ConstantPoolItem::CPClassInfo { name_index: 0 });
 
-        for _current_index in 0..(cp_count-1) {
-            let tag = byte_array.read_u8();
-            constant_pool.constants.push(Self::read_constant_pool_item(tag, byte_array));
+        while constant_pool.constants.len() < cp_count {
+            let tag = byte_array.read_u8()?;
+            let item = Self::read_constant_pool_item(tag, constant_pool.constants.len(), byte_array)?;
+            constant_pool.constants.push(item);
+            // Long and double constants take up two entries
+            if tag == CT_LONG || tag == CT_DOUBLE {
+                constant_pool.constants.push(ConstantPoolItem::CPUnusable);
+            }
         }
 
-        constant_pool
-
+        Ok(constant_pool)
     }
 
-    fn read_interfaces(byte_array: &mut ByteArray) -> Vec<u16> {
-        let count = byte_array.read_u16() as usize;
+    fn read_interfaces(byte_array: &mut ByteArray) -> Result<Vec<u16>, String> {
+        let count = byte_array.read_u16()? as usize;
         let mut vec: Vec<u16> = Vec::with_capacity(count);
         for _current_interface in 0..count {
-            vec.push(byte_array.read_u16());
+            vec.push(byte_array.read_u16()?);
         }
-        vec
+        Ok(vec)
     }
 
-    fn read_fields(byte_array: &mut ByteArray) -> Vec<FieldInfo> {
-        let count = byte_array.read_u16() as usize;
+    fn read_fields(byte_array: &mut ByteArray, constant_pool: &ConstantPool) -> Result<Vec<FieldInfo>, String> {
+        let count = byte_array.read_u16()? as usize;
         let mut vec: Vec<FieldInfo> = Vec::with_capacity(count);
-        // TODO: Implement
-        vec
+        for _current_field in 0..count {
+            let access_flags = byte_array.read_u16()?;
+            let name_index = byte_array.read_u16()?;
+            let descriptor_index = byte_array.read_u16()?;
+            let attributes = AttributeParser::read_attributes(byte_array, constant_pool)?;
+            vec.push(FieldInfo {
+                access_flags: FieldFlags::from_bits_retain(access_flags),
+                descriptor_index,
+                attributes,
+                name_index
+            });
+        }
+        Ok(vec)
     }
 
-    fn read_methods(byte_array: &mut ByteArray, constant_pool: &ConstantPool) -> Vec<MethodInfo> {
-        let count = byte_array.read_u16() as usize;
+    fn read_methods(byte_array: &mut ByteArray, constant_pool: &ConstantPool) -> Result<Vec<MethodInfo>, String> {
+        let count = byte_array.read_u16()? as usize;
         let mut vec: Vec<MethodInfo> = Vec::with_capacity(count);
         for _current_method in 0..count {
-            let access_flags = byte_array.read_u16();
-            let name_index = byte_array.read_u16();
-            let descriptor_index = byte_array.read_u16();
-            let attributes = AttributeParser::read_attributes(byte_array, constant_pool);
-            let descriptor = parse_descriptor(constant_pool.get_string(descriptor_index).to_string());
+            let access_flags = byte_array.read_u16()?;
+            let name_index = byte_array.read_u16()?;
+            let descriptor_index = byte_array.read_u16()?;
+            let attributes = AttributeParser::read_attributes(byte_array, constant_pool)?;
+            // A bad descriptor is left for the format check to report, until then the method takes no arguments
+            let descriptor = match constant_pool.get_utf8(descriptor_index) {
+                Some(descriptor) if is_method_descriptor(descriptor) => parse_descriptor(descriptor.to_string()),
+                other => Descriptor { args: Vec::new(), return_type: String::from("V") }
+            };
 
             vec.push(MethodInfo{
-                access_flags: MethodFlags::from_bits_truncate(access_flags),
+                access_flags: MethodFlags::from_bits_retain(access_flags),
                 descriptor_index,
                 attributes,
                 return_type: descriptor.return_type,
@@ -440,46 +869,63 @@ impl ClassLoader {
                 name_index
             });
         }
-        vec
-    } 
+        Ok(vec)
+    }
 
     
-    pub fn load_class(path: &str) -> ClassInfo {
-        let mut byte_array = ByteArray::new (
-            fs::read(path).unwrap(),
-            0
-        );
-        let metadata = fs::metadata(path).unwrap();
-        let modified: OffsetDateTime = metadata.modified().unwrap().into();
-        //println!("{}", modified_string.unwrap());
-        let result = byte_array.read_u32();
-        assert_eq!(result, MAGIC_NUMBER);
-        let minor_version = byte_array.read_u16();
-        let major_version = byte_array.read_u16();
-        let constant_pool = Self::read_constant_pool(&mut byte_array);
-        let access_flags = byte_array.read_u16();
-        let this_class = byte_array.read_u16();
-        let super_class = byte_array.read_u16();
-        let interfaces = Self::read_interfaces(&mut byte_array);
-        let fields = Self::read_fields(&mut byte_array);
-        let methods = Self::read_methods(&mut byte_array, &constant_pool);
-        let attributes = AttributeParser::read_attributes(&mut byte_array, &constant_pool);
-        ClassInfo {
-            constant_pool: constant_pool,
+    pub fn load_class(path: &str) -> Result<ClassInfo, String> {
+        let bytes = fs::read(path).map_err(|error| error.to_string())?;
+        let modified: OffsetDateTime = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|error| error.to_string())?
+            .into();
+        Self::load_class_from_bytes(bytes, path, modified)
+    }
+
+    /// Parses a class that was already read into memory, for example from a jar or jimage file.
+    /// The path is only used for reporting where the class came from.
+    /// Fails when the structure of the class file can't be read, everything else is left to the format check.
+    pub fn load_class_from_bytes(bytes: Vec<u8>, path: &str, modified: OffsetDateTime) -> Result<ClassInfo, String> {
+        let mut byte_array = ByteArray::new(bytes, 0);
+        let magic = byte_array.read_u32()?;
+        if magic != MAGIC_NUMBER {
+            return Err(format!("Bad magic number {:#X}", magic));
+        }
+        let minor_version = byte_array.read_u16()?;
+        let major_version = byte_array.read_u16()?;
+        let constant_pool = Self::read_constant_pool(&mut byte_array)?;
+        let access_flags = byte_array.read_u16()?;
+        let this_class = byte_array.read_u16()?;
+        let super_class = byte_array.read_u16()?;
+        let interfaces = Self::read_interfaces(&mut byte_array)?;
+        let fields = Self::read_fields(&mut byte_array, &constant_pool)?;
+        let methods = Self::read_methods(&mut byte_array, &constant_pool)?;
+        let attributes = AttributeParser::read_attributes(&mut byte_array, &constant_pool)?;
+        if !byte_array.is_at_end() {
+            return Err(String::from("Extra bytes at the end of the class file"));
+        }
+        let source_file = attributes.iter()
+            .find_map(|attribute| match attribute {
+                AttributeInfo::SourceFile(ATSourceFile { source_file_index, .. }) => constant_pool.get_utf8(*source_file_index).map(String::from),
+                other => None
+            })
+            .unwrap_or_default();
+        Ok(ClassInfo {
+            constant_pool,
             file_path: String::from(path),
             size: byte_array.len(),
             last_modified: modified,
             minor_version,
             major_version,
-            access_flags: ClassFlags::from_bits_truncate(access_flags),
+            access_flags: ClassFlags::from_bits_retain(access_flags),
             this_class,
             super_class,
             interfaces,
             fields,
             methods,
             attributes,
-            source_file: String::from("TODO"),
+            source_file,
             hash: digest(byte_array.bytes),
-        }
+        })
     }
 }
\ No newline at end of file
@@ -0,0 +1,150 @@
+use std::{env, fs, path::{Path, PathBuf}};
+
+use time::OffsetDateTime;
+
+use crate::{class_loader::{ClassInfo, ClassLoader}, jar_file::JarFile, jimage::JImage, stub_library::{self, STUB_LIBRARY_PATH}};
+
+pub enum ClassPathEntry {
+    Directory(String),
+    Jar(JarFile),
+    JImage(JImage),
+    // The classes built into the VM, used in place of a JDK
+    StubLibrary
+}
+
+impl ClassPathEntry {
+    /// Opens a classpath entry, picking the kind of entry based on what the path points at.
+    pub fn open(path: &str) -> Option<ClassPathEntry> {
+        let metadata = fs::metadata(path).ok()?;
+        if metadata.is_dir() {
+            Some(ClassPathEntry::Directory(String::from(path)))
+        } else if path.ends_with("modules") {
+            JImage::open(path).map(ClassPathEntry::JImage)
+        } else {
+            JarFile::open(path).map(ClassPathEntry::Jar)
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        match self {
+            ClassPathEntry::Directory(path) => path,
+            ClassPathEntry::Jar(jar_file) => &jar_file.path,
+            ClassPathEntry::JImage(jimage) => &jimage.path,
+            ClassPathEntry::StubLibrary => STUB_LIBRARY_PATH
+        }
+    }
+
+    /// Reads the bytes of a class by its internal name, together with the path it was found at.
+    /// The error tells why a class in a jar or jimage can't be read.
+    pub fn read_class(&self, class_name: &str) -> Option<Result<(Vec<u8>, String), String>> {
+        match self {
+            ClassPathEntry::Directory(directory) => {
+                let path = Path::new(directory).join(format!("{}.class", class_name));
+                let bytes = fs::read(&path).ok()?;
+                Some(Ok((bytes, path.to_string_lossy().to_string())))
+            },
+            ClassPathEntry::Jar(jar_file) => jar_file.read_class(class_name),
+            ClassPathEntry::JImage(jimage) => jimage.read_class(class_name),
+            ClassPathEntry::StubLibrary => {
+                stub_library::read_class(class_name).map(|bytes| Ok((bytes, format!("{}/{}.class", STUB_LIBRARY_PATH, class_name))))
+            }
+        }
+    }
+}
+
+/// An ordered list of places to look for classes.
+/// The boot classpath comes from a JDK, either its `lib/modules` jimage or the `rt.jar` of JDK 8.
+#[derive(Default)]
+pub struct ClassPath {
+    pub entries: Vec<ClassPathEntry>,
+    // The number of entries at the start that make up the boot classpath
+    pub boot_entries: usize,
+}
+
+impl ClassPath {
+    /// Adds every entry of a classpath string, separated like the `-cp` option of `java`.
+    /// Entries that do not exist are ignored, just like `java` does, but a corrupt jar is an error.
+    pub fn add_paths(&mut self, paths: &str) -> Result<(), String> {
+        let separator = if cfg!(windows) { ';' } else { ':' };
+        for path in paths.split(separator).filter(|path| !path.is_empty()) {
+            match ClassPathEntry::open(path) {
+                Some(entry) => self.entries.push(entry),
+                None if fs::metadata(path).is_ok() => return Err(format!("could not open {}", path)),
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Looks for the JDK to boot from: `JAVA_HOME` if set, otherwise the `java` found on the `PATH`.
+    pub fn find_java_home() -> Option<PathBuf> {
+        if let Some(java_home) = env::var_os("JAVA_HOME") {
+            return Some(PathBuf::from(java_home));
+        }
+        let path = env::var_os("PATH")?;
+        env::split_paths(&path)
+            .map(|directory| directory.join("java"))
+            .find(|java| java.is_file())
+            .and_then(|java| fs::canonicalize(java).ok())
+            // The binary lives in $JAVA_HOME/bin/java (or $JAVA_HOME/jre/bin/java on JDK 8)
+            .and_then(|java| java.parent()?.parent().map(Path::to_path_buf))
+    }
+
+    /// Creates the boot classpath of a JDK.
+    /// Uses `lib/modules` when present, falling back to the `rt.jar` of JDK 8.
+    pub fn boot(java_home: &Path) -> Result<ClassPath, String> {
+        let candidates = [
+            java_home.join("lib").join("modules"),
+            java_home.join("jre").join("lib").join("rt.jar"),
+            java_home.join("lib").join("rt.jar"),
+        ];
+        let path = candidates.iter()
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| format!("no lib/modules or rt.jar found in {}", java_home.display()))?
+            .to_string_lossy();
+        let entry = ClassPathEntry::open(&path).ok_or_else(|| format!("could not open {}", path))?;
+        Ok(ClassPath { entries: vec![entry], boot_entries: 1 })
+    }
+
+    /// Creates a boot classpath with only the stub library.
+    pub fn stub_library() -> ClassPath {
+        ClassPath { entries: vec![ClassPathEntry::StubLibrary], boot_entries: 1 }
+    }
+
+    pub fn uses_stub_library(&self) -> bool {
+        self.entries.iter().any(|entry| matches!(entry, ClassPathEntry::StubLibrary))
+    }
+
+    pub fn read_class(&self, class_name: &str) -> Option<Result<(Vec<u8>, String), String>> {
+        self.entries.iter().find_map(|entry| entry.read_class(class_name))
+    }
+
+    /// Finds and parses a class by its internal name, for example `java/lang/Object`.
+    /// The error tells why a class that was found isn't a well formed class file.
+    pub fn load_class(&self, class_name: &str) -> Option<Result<ClassInfo, String>> {
+        self.find_class(class_name).map(|found| found.map(|(class_info, _)| class_info))
+    }
+
+    /// Finds and parses a class, also telling whether it comes from the boot classpath.
+    pub fn find_class(&self, class_name: &str) -> Option<Result<(ClassInfo, bool), String>> {
+        for (index, entry) in self.entries.iter().enumerate() {
+            if let Some(found) = entry.read_class(class_name) {
+                let (bytes, path) = match found {
+                    Ok(found) => found,
+                    Err(message) => return Some(Err(message))
+                };
+                let metadata_path = match entry {
+                    ClassPathEntry::Directory(_) => &path,
+                    other => other.path()
+                };
+                let modified: OffsetDateTime = fs::metadata(metadata_path)
+                    .and_then(|metadata| metadata.modified())
+                    .map(OffsetDateTime::from)
+                    .unwrap_or_else(|_| OffsetDateTime::now_utc());
+                let from_boot_class_path = index < self.boot_entries;
+                return Some(ClassLoader::load_class_from_bytes(bytes, &path, modified).map(|class_info| (class_info, from_boot_class_path)));
+            }
+        }
+        None
+    }
+}
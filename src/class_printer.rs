@@ -2,7 +2,7 @@ use std::{fs, path::Path};
 
 use time::format_description;
 
-use crate::class_loader::{ATSourceFile, AttributeInfo, ClassInfo, ConstantPool, ConstantPoolItem, MethodFlags, MethodInfo};
+use crate::{class_loader::{ATSourceFile, AttributeInfo, ClassInfo, ConstantPool, ConstantPoolItem, MethodFlags, MethodInfo}, descriptor_parser::argument_descriptors};
 
 pub struct ClassPrinter {
 }
@@ -13,7 +13,7 @@ impl ClassPrinter {
         for (pos, cp_item) in constant_pool.constants.iter().skip(1).enumerate() {
             let number_formatted = format!("#{}", pos+1);
             let output = match cp_item {
-                ConstantPoolItem::CPUTF8Info{utf8_string} => {
+                ConstantPoolItem::CPUTF8Info{utf8_string, ..} => {
                     format!("{:15} {}", "Utf8", utf8_string.clone())
                 },
                 ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } => {
@@ -58,14 +58,15 @@ impl ClassPrinter {
             other => "unknown"
         }
     }
-    fn get_as_external_type(type_str: &str) -> String {
+    /// Returns the Java type of a descriptor, like `java.lang.String[]` for `[Ljava/lang/String;`.
+    pub fn get_as_external_type(type_str: &str) -> String {
         let mut buffer = String::new();
         let mut arr_count = 0;
         let mut in_class = false;
 
         for c in type_str.chars() {
             match c {
-                'L' => {
+                'L' if !in_class => {
                     in_class = true;
                 },
                 ';' => {
@@ -94,26 +95,40 @@ impl ClassPrinter {
         buffer
     }
 
-    fn print_method(method: &MethodInfo, constant_pool: &ConstantPool, class_name: &str) {
+    /// Returns the Java-style header of a method, like `public int brol(int, double[])`. The types are written with
+    /// `type_name`, and the parameters get the given names after their types when there are some.
+    pub fn method_header(method: &MethodInfo, constant_pool: &ConstantPool, class_name: &str, type_name: &dyn Fn(&str) -> String,
+        parameter_names: &[String]) -> String {
         let is_constructor = method.is_constructor(constant_pool);
         let name = if is_constructor {class_name} else {method.get_name(constant_pool)};
+        let descriptor = constant_pool.get_string(method.descriptor_index);
         let mut args = String::from("");
-        for (number, arg) in method.args.iter().enumerate() {
+        for (number, arg) in argument_descriptors(descriptor).iter().enumerate() {
             if number != 0 {args += ", "};
-            args+= &Self::get_as_external_type(&arg);
+            args += &type_name(arg);
+            if let Some(parameter_name) = parameter_names.get(number) {
+                args += " ";
+                args += parameter_name;
+            }
         }
-        let mut return_type = if is_constructor {String::from("")} else {Self::get_as_external_type(&method.return_type)};
-        if return_type.len() > 0 { return_type += " "}
-        let mut flags = String::from("");
+        let mut return_type = if is_constructor {String::from("")} else {type_name(&descriptor[descriptor.find(')').unwrap() + 1..])};
+        if !return_type.is_empty() { return_type += " "}
         let mut keywords = String::from("");
         for (name, bitflags) in method.access_flags.iter_names() {
-            flags += name;
-            flags += ", ";
             keywords += bitflags.as_keyword();
             keywords += " ";
         }
-        let flags_cut = &flags[0..flags.len()-2];
-        println!("  {}{}{}({}):", keywords, return_type, name, args);
+        format!("{}{}{}({})", keywords, return_type, name, args)
+    }
+
+    fn print_method(method: &MethodInfo, constant_pool: &ConstantPool, class_name: &str) {
+        let mut flags = String::from("");
+        for (name, bitflags) in method.access_flags.iter_names() {
+            flags += name;
+            flags += ", ";
+        }
+        let flags_cut = flags.strip_suffix(", ").unwrap_or(&flags);
+        println!("  {}:", Self::method_header(method, constant_pool, class_name, &Self::get_as_external_type, &[]));
         println!("    descriptor: {}", constant_pool.get_string(method.descriptor_index));
         println!("    flags: ({:#06x}) {}", method.access_flags.bits(), flags_cut);
 
@@ -123,7 +138,7 @@ impl ClassPrinter {
                 println!("    Code:");
                 let mut args_size = method.args.len();
                 if !method.access_flags.contains(MethodFlags::ACC_STATIC) {
-                    args_size = args_size + 1;
+                    args_size += 1;
                 }
                 println!("      stack={}, locals={}, args_size={}", code_attribute.max_stack, code_attribute.max_locals, args_size);
             },
@@ -140,15 +155,18 @@ impl ClassPrinter {
     }
 
     pub fn print_class(class_info: &ClassInfo) {
-        let absolute_path = fs::canonicalize(Path::new(&class_info.file_path)).unwrap();
-        println!("Classfile {}", absolute_path.display());
+        // Classes read from a jar or jimage have no path on disk
+        match fs::canonicalize(Path::new(&class_info.file_path)) {
+            Ok(absolute_path) => println!("Classfile {}", absolute_path.display()),
+            Err(_) => println!("Classfile {}", class_info.file_path)
+        }
         let format_description = format_description::parse("[day] [month repr:short] [year]").unwrap();
         let modified_string = class_info.last_modified.format(&format_description);
         println!("  Last modified {}; size {} bytes", modified_string.unwrap(), class_info.size);
         println!("  SHA-256 checksum {}", class_info.hash);
         for att in &class_info.attributes {
             match att {
-                AttributeInfo::SourceFile(ATSourceFile { source_file_index }) => {
+                AttributeInfo::SourceFile(ATSourceFile { source_file_index, .. }) => {
                     let source_file = class_info.constant_pool.get_string(*source_file_index);
                     println!("  Compiled from \"{}\"", source_file);
                 },
@@ -163,7 +181,7 @@ impl ClassPrinter {
         let mut flags = String::from("");
         for (name, bitflags) in class_info.access_flags.iter_names() {
             let keyword = bitflags.as_keyword();
-            if keyword.len() > 0 {
+            if !keyword.is_empty() {
                 keywords += keyword;
                 keywords += " ";
             }
@@ -171,7 +189,7 @@ impl ClassPrinter {
             flags += ", ";
         }
 
-        let flags = &flags[0..(flags.len()-2)];
+        let flags = flags.strip_suffix(", ").unwrap_or(&flags);
 
         println!("{}class {}", keywords, class_name);
         println!("  minor version: {}", class_info.minor_version);
@@ -0,0 +1,385 @@
+use std::borrow::Cow;
+
+use cesu8::to_java_cesu8;
+
+use crate::class_loader::{
+    AttributeInfo, ClassInfo, ConstantPool, ConstantPoolItem, StackMapFrame, VerificationTypeInfo, MAGIC_NUMBER,
+    CT_CLASS, CT_DOUBLE, CT_DYNAMIC, CT_FIELDREF, CT_FLOAT, CT_INTEGER, CT_INTERFACEMETHOD, CT_INVOKEDYNAMIC, CT_LONG,
+    CT_METHODHANDLE, CT_METHODREF, CT_METHODTYPE, CT_MODULE, CT_NAMEANDTYPE, CT_PACKAGE, CT_STRING, CT_UTF8
+};
+
+/// Writes a ClassInfo back into the class file format, so classes can be patched and not only read.
+/// A class that was parsed and not changed is written back byte for byte.
+pub struct ClassWriter {
+}
+
+impl ClassWriter {
+    /// Fails when an attribute doesn't name itself with a Utf8 constant of its name.
+    pub fn write_class(class_info: &ClassInfo) -> Result<Vec<u8>, String> {
+        let constant_pool = &class_info.constant_pool;
+        let mut bytes = Vec::new();
+        push_u32(&mut bytes, MAGIC_NUMBER);
+        push_u16(&mut bytes, class_info.minor_version);
+        push_u16(&mut bytes, class_info.major_version);
+        Self::write_constant_pool(&mut bytes, constant_pool);
+        push_u16(&mut bytes, class_info.access_flags.bits());
+        push_u16(&mut bytes, class_info.this_class);
+        push_u16(&mut bytes, class_info.super_class);
+        push_u16(&mut bytes, class_info.interfaces.len() as u16);
+        for interface in &class_info.interfaces {
+            push_u16(&mut bytes, *interface);
+        }
+        push_u16(&mut bytes, class_info.fields.len() as u16);
+        for field in &class_info.fields {
+            push_u16(&mut bytes, field.access_flags.bits());
+            push_u16(&mut bytes, field.name_index);
+            push_u16(&mut bytes, field.descriptor_index);
+            Self::write_attributes(&mut bytes, &field.attributes, constant_pool)?;
+        }
+        push_u16(&mut bytes, class_info.methods.len() as u16);
+        for method in &class_info.methods {
+            push_u16(&mut bytes, method.access_flags.bits());
+            push_u16(&mut bytes, method.name_index);
+            push_u16(&mut bytes, method.descriptor_index);
+            Self::write_attributes(&mut bytes, &method.attributes, constant_pool)?;
+        }
+        Self::write_attributes(&mut bytes, &class_info.attributes, constant_pool)?;
+        Ok(bytes)
+    }
+
+    pub fn write_constant_pool(bytes: &mut Vec<u8>, constant_pool: &ConstantPool) {
+        push_u16(bytes, constant_pool.constants.len() as u16);
+        // Skip the filler at index 0
+        for item in constant_pool.constants.iter().skip(1) {
+            Self::write_constant(bytes, item);
+        }
+    }
+
+    /// Writes one constant pool entry with its tag.
+    pub fn write_constant(bytes: &mut Vec<u8>, item: &ConstantPoolItem) {
+        match item {
+            ConstantPoolItem::CPUTF8Info { utf8_string, bytes: original } => {
+                let encoded = match original {
+                    Some(original) => Cow::Borrowed(original.as_slice()),
+                    None => to_java_cesu8(utf8_string)
+                };
+                bytes.push(CT_UTF8);
+                push_u16(bytes, encoded.len() as u16);
+                bytes.extend_from_slice(&encoded);
+            },
+            ConstantPoolItem::CPIntegerInfo { value } => {
+                bytes.push(CT_INTEGER);
+                push_u32(bytes, *value as u32);
+            },
+            ConstantPoolItem::CPFloatInfo { value } => {
+                bytes.push(CT_FLOAT);
+                push_u32(bytes, value.to_bits());
+            },
+            ConstantPoolItem::CPLongInfo { value } => {
+                bytes.push(CT_LONG);
+                bytes.extend(value.to_be_bytes());
+            },
+            ConstantPoolItem::CPDoubleInfo { value } => {
+                bytes.push(CT_DOUBLE);
+                bytes.extend(value.to_bits().to_be_bytes());
+            },
+            ConstantPoolItem::CPClassInfo { name_index } => {
+                bytes.push(CT_CLASS);
+                push_u16(bytes, *name_index);
+            },
+            ConstantPoolItem::CPStringInfo { string_index } => {
+                bytes.push(CT_STRING);
+                push_u16(bytes, *string_index);
+            },
+            ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } => {
+                bytes.push(CT_FIELDREF);
+                push_u16(bytes, *class_index);
+                push_u16(bytes, *name_and_type_index);
+            },
+            ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } => {
+                bytes.push(CT_METHODREF);
+                push_u16(bytes, *class_index);
+                push_u16(bytes, *name_and_type_index);
+            },
+            ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } => {
+                bytes.push(CT_INTERFACEMETHOD);
+                push_u16(bytes, *class_index);
+                push_u16(bytes, *name_and_type_index);
+            },
+            ConstantPoolItem::CPNameAndTypeInfo { name_index, descriptor_index } => {
+                bytes.push(CT_NAMEANDTYPE);
+                push_u16(bytes, *name_index);
+                push_u16(bytes, *descriptor_index);
+            },
+            ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index } => {
+                bytes.push(CT_METHODHANDLE);
+                bytes.push(*reference_kind);
+                push_u16(bytes, *reference_index);
+            },
+            ConstantPoolItem::CPMethodTypeInfo { descriptor_index } => {
+                bytes.push(CT_METHODTYPE);
+                push_u16(bytes, *descriptor_index);
+            },
+            ConstantPoolItem::CPDynamicInfo { bootstrap_method_attr_index, name_and_type_index } => {
+                bytes.push(CT_DYNAMIC);
+                push_u16(bytes, *bootstrap_method_attr_index);
+                push_u16(bytes, *name_and_type_index);
+            },
+            ConstantPoolItem::CPInvokeDynamicInfo { bootstrap_method_attr_index, name_and_type_index } => {
+                bytes.push(CT_INVOKEDYNAMIC);
+                push_u16(bytes, *bootstrap_method_attr_index);
+                push_u16(bytes, *name_and_type_index);
+            },
+            ConstantPoolItem::CPModuleInfo { name_index } => {
+                bytes.push(CT_MODULE);
+                push_u16(bytes, *name_index);
+            },
+            ConstantPoolItem::CPPackageInfo { name_index } => {
+                bytes.push(CT_PACKAGE);
+                push_u16(bytes, *name_index);
+            },
+            // The second slot of a long or double is not written
+            ConstantPoolItem::CPUnusable => {}
+        }
+    }
+
+    /// Checks the index of the name of an attribute, which is kept as it was parsed, as a class can have the name
+    /// more than once. Unknown attributes can have any name.
+    fn attribute_name_index(constant_pool: &ConstantPool, name_index: u16, name: Option<&str>) -> Result<u16, String> {
+        match constant_pool.constants.get(name_index as usize) {
+            Some(ConstantPoolItem::CPUTF8Info { utf8_string, .. }) if name.is_none_or(|name| name == utf8_string) => Ok(name_index),
+            other => Err(format!("The name index {} of the {} attribute is not a Utf8 constant with its name", name_index, name.unwrap_or("unknown")))
+        }
+    }
+
+    fn write_attributes(bytes: &mut Vec<u8>, attributes: &[AttributeInfo], constant_pool: &ConstantPool) -> Result<(), String> {
+        push_u16(bytes, attributes.len() as u16);
+        for attribute in attributes {
+            Self::write_attribute(bytes, attribute, constant_pool)?;
+        }
+        Ok(())
+    }
+
+    fn write_attribute(bytes: &mut Vec<u8>, attribute: &AttributeInfo, constant_pool: &ConstantPool) -> Result<(), String> {
+        let mut info = Vec::new();
+        let name_index = match attribute {
+            AttributeInfo::Code(code) => {
+                push_u16(&mut info, code.max_stack);
+                push_u16(&mut info, code.max_locals);
+                push_u32(&mut info, code.code.len() as u32);
+                info.extend_from_slice(&code.code);
+                push_u16(&mut info, code.exceptions.len() as u16);
+                for entry in &code.exceptions {
+                    push_u16(&mut info, entry.start_pc);
+                    push_u16(&mut info, entry.end_pc);
+                    push_u16(&mut info, entry.handler_pc);
+                    push_u16(&mut info, entry.catch_type);
+                }
+                Self::write_attributes(&mut info, &code.attributes, constant_pool)?;
+                Self::attribute_name_index(constant_pool, code.name_index, Some("Code"))?
+            },
+            AttributeInfo::LineNumberTable(table) => {
+                push_u16(&mut info, table.entries.len() as u16);
+                for entry in &table.entries {
+                    push_u16(&mut info, entry.start_pc);
+                    push_u16(&mut info, entry.line_number);
+                }
+                Self::attribute_name_index(constant_pool, table.name_index, Some("LineNumberTable"))?
+            },
+            AttributeInfo::LocalVariableTable(table) => {
+                push_u16(&mut info, table.entries.len() as u16);
+                for entry in &table.entries {
+                    push_u16(&mut info, entry.start_pc);
+                    push_u16(&mut info, entry.length);
+                    push_u16(&mut info, entry.name_index);
+                    push_u16(&mut info, entry.descriptor_index);
+                    push_u16(&mut info, entry.index);
+                }
+                Self::attribute_name_index(constant_pool, table.name_index, Some("LocalVariableTable"))?
+            },
+            AttributeInfo::SourceFile(source_file) => {
+                push_u16(&mut info, source_file.source_file_index);
+                Self::attribute_name_index(constant_pool, source_file.name_index, Some("SourceFile"))?
+            },
+            AttributeInfo::ConstantValue(constant_value) => {
+                push_u16(&mut info, constant_value.constantvalue_index);
+                Self::attribute_name_index(constant_pool, constant_value.name_index, Some("ConstantValue"))?
+            },
+            AttributeInfo::Exceptions(exceptions) => {
+                push_u16(&mut info, exceptions.exception_index_table.len() as u16);
+                for index in &exceptions.exception_index_table {
+                    push_u16(&mut info, *index);
+                }
+                Self::attribute_name_index(constant_pool, exceptions.name_index, Some("Exceptions"))?
+            },
+            AttributeInfo::BootstrapMethods(bootstrap_methods) => {
+                push_u16(&mut info, bootstrap_methods.methods.len() as u16);
+                for method in &bootstrap_methods.methods {
+                    push_u16(&mut info, method.bootstrap_method_ref);
+                    push_u16(&mut info, method.bootstrap_arguments.len() as u16);
+                    for argument in &method.bootstrap_arguments {
+                        push_u16(&mut info, *argument);
+                    }
+                }
+                Self::attribute_name_index(constant_pool, bootstrap_methods.name_index, Some("BootstrapMethods"))?
+            },
+            AttributeInfo::StackMapTable(stack_map_table) => {
+                if let Some(error) = &stack_map_table.error {
+                    return Err(format!("The StackMapTable can't be written: {}", error));
+                }
+                push_u16(&mut info, stack_map_table.entries.len() as u16);
+                for frame in &stack_map_table.entries {
+                    Self::write_stack_map_frame(&mut info, frame);
+                }
+                Self::attribute_name_index(constant_pool, stack_map_table.name_index, Some("StackMapTable"))?
+            },
+            AttributeInfo::Unknown(unknown) => {
+                info.extend_from_slice(&unknown.info);
+                Self::attribute_name_index(constant_pool, unknown.name_index, None)?
+            }
+        };
+        push_u16(bytes, name_index);
+        push_u32(bytes, info.len() as u32);
+        bytes.extend(info);
+        Ok(())
+    }
+
+    fn write_stack_map_frame(bytes: &mut Vec<u8>, frame: &StackMapFrame) {
+        match frame {
+            StackMapFrame::Same { frame_type, offset_delta } => {
+                bytes.push(*frame_type);
+                if *frame_type >= 64 {
+                    push_u16(bytes, *offset_delta);
+                }
+            },
+            StackMapFrame::SameLocals1StackItem { frame_type, offset_delta, stack } => {
+                bytes.push(*frame_type);
+                if *frame_type >= 128 {
+                    push_u16(bytes, *offset_delta);
+                }
+                Self::write_verification_type(bytes, stack);
+            },
+            StackMapFrame::Chop { frame_type, offset_delta } => {
+                bytes.push(*frame_type);
+                push_u16(bytes, *offset_delta);
+            },
+            StackMapFrame::Append { offset_delta, locals } => {
+                bytes.push(251 + locals.len() as u8);
+                push_u16(bytes, *offset_delta);
+                for local in locals {
+                    Self::write_verification_type(bytes, local);
+                }
+            },
+            StackMapFrame::Full { offset_delta, locals, stack } => {
+                bytes.push(255);
+                push_u16(bytes, *offset_delta);
+                push_u16(bytes, locals.len() as u16);
+                for local in locals {
+                    Self::write_verification_type(bytes, local);
+                }
+                push_u16(bytes, stack.len() as u16);
+                for item in stack {
+                    Self::write_verification_type(bytes, item);
+                }
+            }
+        }
+    }
+
+    fn write_verification_type(bytes: &mut Vec<u8>, verification_type: &VerificationTypeInfo) {
+        match verification_type {
+            VerificationTypeInfo::Top => bytes.push(0),
+            VerificationTypeInfo::Integer => bytes.push(1),
+            VerificationTypeInfo::Float => bytes.push(2),
+            VerificationTypeInfo::Double => bytes.push(3),
+            VerificationTypeInfo::Long => bytes.push(4),
+            VerificationTypeInfo::Null => bytes.push(5),
+            VerificationTypeInfo::UninitializedThis => bytes.push(6),
+            VerificationTypeInfo::Object(class_index) => {
+                bytes.push(7);
+                push_u16(bytes, *class_index);
+            },
+            VerificationTypeInfo::Uninitialized(offset) => {
+                bytes.push(8);
+                push_u16(bytes, *offset);
+            }
+        }
+    }
+}
+
+fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend(value.to_be_bytes());
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend(value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use time::OffsetDateTime;
+
+    use crate::class_loader::{AttributeInfo, ClassLoader, ConstantPoolItem};
+
+    use super::ClassWriter;
+
+    /// Reads the class files of the tests, and checks that writing them back gives the same bytes.
+    /// The ones that are corrupted on purpose can't be read or written, they are skipped.
+    #[test]
+    fn rewrites_class_files_unchanged() {
+        let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let mut directories = vec![tests.clone()];
+        let mut count = 0;
+        while let Some(directory) = directories.pop() {
+            for entry in fs::read_dir(&directory).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    directories.push(path);
+                } else if path.extension().is_some_and(|extension| extension == "class") {
+                    let Ok(class_info) = ClassLoader::load_class(&path.to_string_lossy()) else {
+                        continue;
+                    };
+                    let has_stack_map_error = class_info.methods.iter()
+                        .any(|method| method.get_code().is_some_and(|code| code.get_stack_map_error().is_some()));
+                    if has_stack_map_error {
+                        continue;
+                    }
+                    let bytes = ClassWriter::write_class(&class_info).unwrap();
+                    assert!(bytes == fs::read(&path).unwrap(), "{} is written back differently", path.display());
+                    count += 1;
+                }
+            }
+        }
+        assert!(count > 0);
+    }
+
+    /// Points the SourceFile attribute at a second constant with its name, which has to be kept.
+    #[test]
+    fn keeps_attribute_name_index() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/Numerics.class");
+        let mut class_info = ClassLoader::load_class(&path.to_string_lossy()).unwrap();
+        let name_index = class_info.constant_pool.constants.len() as u16;
+        class_info.constant_pool.constants.push(ConstantPoolItem::CPUTF8Info { utf8_string: "SourceFile".to_string(), bytes: None });
+        for attribute in &mut class_info.attributes {
+            if let AttributeInfo::SourceFile(source_file) = attribute {
+                source_file.name_index = name_index;
+            }
+        }
+        let bytes = ClassWriter::write_class(&class_info).unwrap();
+        let rewritten = ClassLoader::load_class_from_bytes(bytes, "Numerics.class", OffsetDateTime::now_utc()).unwrap();
+        assert!(rewritten.attributes.iter().any(|attribute| matches!(attribute, AttributeInfo::SourceFile(source_file) if source_file.name_index == name_index)));
+    }
+
+    #[test]
+    fn rejects_attribute_name_index_of_another_constant() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/Numerics.class");
+        let mut class_info = ClassLoader::load_class(&path.to_string_lossy()).unwrap();
+        let source_file_index = class_info.constant_pool.constants.iter()
+            .position(|item| matches!(item, ConstantPoolItem::CPUTF8Info { utf8_string, .. } if utf8_string == "SourceFile"))
+            .unwrap();
+        class_info.constant_pool.constants[source_file_index] = ConstantPoolItem::CPUTF8Info { utf8_string: "Renamed".to_string(), bytes: None };
+        assert_eq!(ClassWriter::write_class(&class_info).unwrap_err(),
+            format!("The name index {} of the SourceFile attribute is not a Utf8 constant with its name", source_file_index));
+    }
+}
@@ -0,0 +1,328 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt};
+
+use crate::class_loader::{ClassFlags, ClassInfo, ConstantPool, ConstantPoolItem, FieldFlags, MethodFlags};
+
+/// A difference in the API of two versions of a library, with whether it breaks binaries that were linked against
+/// the old version under the binary compatibility rules of JLS chapter 13.
+pub struct Change {
+    pub breaking: bool,
+    pub description: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}: {}", if self.breaking { "breaking" } else { "compatible" }, self.description)
+    }
+}
+
+/// The access of a class member, from the least to the most accessible.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Access {
+    Private,
+    Package,
+    Protected,
+    Public,
+}
+
+impl Access {
+    // Fields and methods have the same bits for their access
+    fn of(bits: u16) -> Access {
+        if bits & FieldFlags::ACC_PUBLIC.bits() != 0 {
+            Access::Public
+        } else if bits & FieldFlags::ACC_PROTECTED.bits() != 0 {
+            Access::Protected
+        } else if bits & FieldFlags::ACC_PRIVATE.bits() != 0 {
+            Access::Private
+        } else {
+            Access::Package
+        }
+    }
+
+    fn keyword(&self) -> &'static str {
+        match self {
+            Access::Private => "private",
+            Access::Package => "package private",
+            Access::Protected => "protected",
+            Access::Public => "public",
+        }
+    }
+}
+
+/// What the API of a class is made of: its flags, supertypes, fields with their constant value, and methods.
+struct ClassVersion {
+    flags: ClassFlags,
+    supertypes: Vec<String>,
+    fields: BTreeMap<(String, String), (FieldFlags, Option<String>)>,
+    methods: BTreeMap<(String, String), MethodFlags>,
+}
+
+impl ClassVersion {
+    fn new(class_info: &ClassInfo) -> ClassVersion {
+        let constant_pool = &class_info.constant_pool;
+        let class_name = |index: u16| constant_pool.get_string(constant_pool.get_class_info(index)).to_string();
+        let descriptor = |index: u16| constant_pool.get_string(index).to_string();
+        ClassVersion {
+            flags: class_info.access_flags,
+            supertypes: (class_info.super_class != 0).then_some(class_info.super_class).into_iter()
+                .chain(class_info.interfaces.iter().copied())
+                .map(class_name)
+                .collect(),
+            fields: class_info.fields.iter().map(|field| {
+                let constant = field.get_constant_value().and_then(|index| constant_text(constant_pool, index));
+                ((field.get_name(constant_pool).to_string(), descriptor(field.descriptor_index)), (field.access_flags, constant))
+            }).collect(),
+            methods: class_info.methods.iter()
+                .map(|method| ((method.get_name(constant_pool).to_string(), descriptor(method.descriptor_index)), method.access_flags))
+                .collect(),
+        }
+    }
+
+    fn is_public(&self) -> bool {
+        self.flags.contains(ClassFlags::ACC_PUBLIC)
+    }
+
+    fn is_interface(&self) -> bool {
+        self.flags.contains(ClassFlags::ACC_INTERFACE)
+    }
+
+    /// Returns whether code in other packages can use a member with these flags: a public one, or a protected one
+    /// from a subclass.
+    fn exports(&self, bits: u16) -> bool {
+        self.is_public() && match Access::of(bits) {
+            Access::Public => true,
+            Access::Protected => !self.flags.contains(ClassFlags::ACC_FINAL),
+            other => false
+        }
+    }
+}
+
+/// The classes of one version, by their internal name. The first one of a name wins, like on a classpath.
+type Version = BTreeMap<String, ClassVersion>;
+
+fn version(class_infos: &[ClassInfo]) -> Version {
+    let mut classes = BTreeMap::new();
+    for class_info in class_infos {
+        let constant_pool = &class_info.constant_pool;
+        let name = constant_pool.get_string(constant_pool.get_class_info(class_info.this_class)).to_string();
+        classes.entry(name).or_insert_with(|| ClassVersion::new(class_info));
+    }
+    classes
+}
+
+/// Returns the superclasses and superinterfaces of a class, as far as they are in the same version. Object is left
+/// out, since every class has it.
+fn ancestors(classes: &Version, class_name: &str) -> BTreeSet<String> {
+    let mut ancestors = BTreeSet::new();
+    let mut pending = vec![class_name.to_string()];
+    while let Some(current) = pending.pop() {
+        for supertype in classes.get(&current).into_iter().flat_map(|class| &class.supertypes) {
+            if supertype != "java/lang/Object" && ancestors.insert(supertype.clone()) {
+                pending.push(supertype.clone());
+            }
+        }
+    }
+    ancestors
+}
+
+/// Compares the public API of two versions of a library: the public classes, and their public fields and methods
+/// and the protected ones that subclasses in other packages can use. Private and package private classes and members
+/// can only be used by the library itself, so their changes aren't reported.
+pub fn compare(old: &[ClassInfo], new: &[ClassInfo]) -> Vec<Change> {
+    let (old, new) = (version(old), version(new));
+    let mut changes = Vec::new();
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for name in names {
+        let java_name = name.replace('/', ".");
+        match (old.get(name), new.get(name)) {
+            (Some(old_class), None) if old_class.is_public() => {
+                changes.push(breaking(format!("removed class {} (JLS §13.3)", java_name)));
+            },
+            (None, Some(new_class)) if new_class.is_public() => {
+                changes.push(compatible(format!("added class {}", java_name)));
+            },
+            (Some(old_class), Some(new_class)) if old_class.is_public() || new_class.is_public() => {
+                compare_classes(&mut changes, &old, &new, name, &java_name);
+            },
+            other => {}
+        }
+    }
+    changes
+}
+
+fn breaking(description: String) -> Change {
+    Change { breaking: true, description }
+}
+
+fn compatible(description: String) -> Change {
+    Change { breaking: false, description }
+}
+
+fn compare_classes(changes: &mut Vec<Change>, old: &Version, new: &Version, name: &str, java_name: &str) {
+    let (old_class, new_class) = (&old[name], &new[name]);
+    let kind = if old_class.is_interface() { "interface" } else { "class" };
+    // Changes to a class that other packages couldn't use don't break them
+    let change = |is_breaking: bool, description: String| Change { breaking: is_breaking && old_class.is_public(), description };
+    let flag_changed = |flag: ClassFlags| (old_class.flags.contains(flag), new_class.flags.contains(flag));
+
+    if old_class.is_interface() != new_class.is_interface() {
+        let (new_kind, section) = if new_class.is_interface() { ("an interface", "13.4.1") } else { ("a class", "13.5.1") };
+        changes.push(change(true, format!("changed {} {} into {} (JLS §{})", kind, java_name, new_kind, section)));
+    }
+    let section = if old_class.is_interface() { "13.5.1" } else { "13.4.3" };
+    match flag_changed(ClassFlags::ACC_PUBLIC) {
+        (true, false) => changes.push(change(true, format!("made {} {} package private (JLS §{})", kind, java_name, section))),
+        (false, true) => changes.push(compatible(format!("made {} {} public", kind, java_name))),
+        other => {}
+    }
+    if !old_class.is_interface() && !new_class.is_interface() {
+        match flag_changed(ClassFlags::ACC_ABSTRACT) {
+            (false, true) => changes.push(change(true, format!("made class {} abstract (JLS §13.4.1)", java_name))),
+            (true, false) => changes.push(compatible(format!("made class {} no longer abstract", java_name))),
+            other => {}
+        }
+        match flag_changed(ClassFlags::ACC_FINAL) {
+            (false, true) => changes.push(change(true, format!("made class {} final (JLS §13.4.2)", java_name))),
+            (true, false) => changes.push(compatible(format!("made class {} no longer final", java_name))),
+            other => {}
+        }
+    }
+    let (old_ancestors, new_ancestors) = (ancestors(old, name), ancestors(new, name));
+    let section = if old_class.is_interface() { "13.5.2" } else { "13.4.4" };
+    for removed in old_ancestors.difference(&new_ancestors) {
+        changes.push(change(true, format!("removed supertype {} from {} {} (JLS §{})", removed.replace('/', "."), kind, java_name, section)));
+    }
+    for added in new_ancestors.difference(&old_ancestors) {
+        changes.push(compatible(format!("added supertype {} to {} {}", added.replace('/', "."), kind, java_name)));
+    }
+
+    let keys: BTreeSet<&(String, String)> = old_class.fields.keys().chain(new_class.fields.keys()).collect();
+    for key in keys {
+        let (field_name, descriptor) = key;
+        let field = format!("field {}.{}:{}", java_name, field_name, descriptor);
+        let old_field = old_class.fields.get(key).filter(|(flags, _)| old_class.exports(flags.bits()) && !flags.contains(FieldFlags::ACC_SYNTHETIC));
+        let new_field = new_class.fields.get(key).filter(|(flags, _)| new_class.exports(flags.bits()) && !flags.contains(FieldFlags::ACC_SYNTHETIC));
+        match (old_field, new_field) {
+            (Some((old_flags, _)), None) => match new_class.fields.get(key) {
+                Some((new_flags, _)) => changes.extend(decreased_access(&field, old_flags.bits(), new_flags.bits())),
+                None if inherits_field(new, name, key) => changes.push(compatible(format!("moved {} to a supertype", field))),
+                None => changes.push(breaking(format!("removed {} (JLS §13.4.8)", field)))
+            },
+            (None, Some((new_flags, _))) => match old_class.fields.get(key) {
+                Some((old_flags, _)) => changes.extend(increased_access(old_class, &field, old_flags.bits(), new_flags.bits())),
+                None => changes.push(compatible(format!("added {}", field)))
+            },
+            (Some((old_flags, old_constant)), Some((new_flags, new_constant))) => {
+                let flag_changed = |flag: FieldFlags| (old_flags.contains(flag), new_flags.contains(flag));
+                changes.extend(decreased_access(&field, old_flags.bits(), new_flags.bits()));
+                changes.extend(increased_access(old_class, &field, old_flags.bits(), new_flags.bits()));
+                match flag_changed(FieldFlags::ACC_FINAL) {
+                    (false, true) => changes.push(breaking(format!("made {} final (JLS §13.4.9)", field))),
+                    (true, false) => changes.push(compatible(format!("made {} no longer final", field))),
+                    other => {}
+                }
+                match flag_changed(FieldFlags::ACC_STATIC) {
+                    (false, true) => changes.push(breaking(format!("made {} static (JLS §13.4.10)", field))),
+                    (true, false) => changes.push(breaking(format!("made {} no longer static (JLS §13.4.10)", field))),
+                    other => {}
+                }
+                // The value of a constant is copied into the code that uses it, which keeps the old value
+                if old_constant.is_some() && old_constant != new_constant {
+                    let new_value = new_constant.as_deref().unwrap_or("no constant");
+                    changes.push(compatible(format!("changed the value of {} from {} to {}, code compiled against the old value keeps it (JLS §13.4.9)",
+                        field, old_constant.as_deref().unwrap(), new_value)));
+                }
+            },
+            other => {}
+        }
+    }
+
+    let keys: BTreeSet<&(String, String)> = old_class.methods.keys().chain(new_class.methods.keys()).collect();
+    for key in keys {
+        let (method_name, descriptor) = key;
+        let kind = if method_name == "<init>" { "constructor" } else { "method" };
+        let method = format!("{} {}.{}{}", kind, java_name, method_name, descriptor);
+        let is_api = |class: &ClassVersion, flags: &&MethodFlags| class.exports(flags.bits()) && method_name != "<clinit>"
+            && !flags.intersects(MethodFlags::ACC_SYNTHETIC | MethodFlags::ACC_BRIDGE);
+        let old_method = old_class.methods.get(key).filter(|flags| is_api(old_class, flags));
+        let new_method = new_class.methods.get(key).filter(|flags| is_api(new_class, flags));
+        match (old_method, new_method) {
+            (Some(old_flags), None) => match new_class.methods.get(key) {
+                Some(new_flags) => changes.extend(decreased_access(&method, old_flags.bits(), new_flags.bits())),
+                None if method_name != "<init>" && inherits_method(new, name, key) => {
+                    changes.push(compatible(format!("moved {} to a supertype", method)));
+                },
+                None => {
+                    let section = if old_class.is_interface() { "13.5.3" } else { "13.4.12" };
+                    changes.push(breaking(format!("removed {} (JLS §{})", method, section)));
+                }
+            },
+            (None, Some(new_flags)) => match old_class.methods.get(key) {
+                Some(old_flags) => changes.extend(increased_access(old_class, &method, old_flags.bits(), new_flags.bits())),
+                None => changes.push(compatible(format!("added {}", method)))
+            },
+            (Some(old_flags), Some(new_flags)) => {
+                let flag_changed = |flag: MethodFlags| (old_flags.contains(flag), new_flags.contains(flag));
+                changes.extend(decreased_access(&method, old_flags.bits(), new_flags.bits()));
+                changes.extend(increased_access(old_class, &method, old_flags.bits(), new_flags.bits()));
+                match flag_changed(MethodFlags::ACC_ABSTRACT) {
+                    (false, true) => changes.push(breaking(format!("made {} abstract (JLS §13.4.16)", method))),
+                    (true, false) => changes.push(compatible(format!("made {} no longer abstract", method))),
+                    other => {}
+                }
+                // Static methods can't be overridden, so making them final doesn't break subclasses
+                match flag_changed(MethodFlags::ACC_FINAL) {
+                    (false, true) if !new_flags.contains(MethodFlags::ACC_STATIC) => {
+                        changes.push(breaking(format!("made {} final (JLS §13.4.17)", method)));
+                    },
+                    (false, true) => changes.push(compatible(format!("made {} final", method))),
+                    (true, false) => changes.push(compatible(format!("made {} no longer final", method))),
+                    other => {}
+                }
+                match flag_changed(MethodFlags::ACC_STATIC) {
+                    (false, true) => changes.push(breaking(format!("made {} static (JLS §13.4.19)", method))),
+                    (true, false) => changes.push(breaking(format!("made {} no longer static (JLS §13.4.19)", method))),
+                    other => {}
+                }
+            },
+            other => {}
+        }
+    }
+}
+
+/// Returns the change of a member that became less accessible. A member that is only no longer part of the API
+/// because its class isn't public anymore or became final has no change of its own.
+fn decreased_access(member: &str, old_bits: u16, new_bits: u16) -> Option<Change> {
+    let (old_access, new_access) = (Access::of(old_bits), Access::of(new_bits));
+    (new_access < old_access).then(|| breaking(format!("made {} {} instead of {} (JLS §13.4.7)", member, new_access.keyword(), old_access.keyword())))
+}
+
+/// Returns the change of a member that became more accessible, unless it only became part of the API because its
+/// class did.
+fn increased_access(old_class: &ClassVersion, member: &str, old_bits: u16, new_bits: u16) -> Option<Change> {
+    let (old_access, new_access) = (Access::of(old_bits), Access::of(new_bits));
+    (new_access > old_access && old_class.is_public()).then(|| compatible(format!("made {} {}", member, new_access.keyword())))
+}
+
+/// Returns whether a class of the new version still gets a field from one of its supertypes.
+fn inherits_field(new: &Version, class_name: &str, key: &(String, String)) -> bool {
+    ancestors(new, class_name).iter().filter_map(|ancestor| new.get(ancestor))
+        .any(|ancestor| ancestor.fields.get(key).is_some_and(|(flags, _)| ancestor.exports(flags.bits())))
+}
+
+/// Returns whether a class of the new version still gets a method from one of its supertypes.
+fn inherits_method(new: &Version, class_name: &str, key: &(String, String)) -> bool {
+    ancestors(new, class_name).iter().filter_map(|ancestor| new.get(ancestor))
+        .any(|ancestor| ancestor.methods.get(key).is_some_and(|flags| ancestor.exports(flags.bits()) && !flags.contains(MethodFlags::ACC_STATIC)))
+}
+
+/// Returns the value of a ConstantValue attribute as text, to compare it between versions.
+fn constant_text(constant_pool: &ConstantPool, index: u16) -> Option<String> {
+    match constant_pool.constants.get(index as usize)? {
+        ConstantPoolItem::CPIntegerInfo { value } => Some(value.to_string()),
+        ConstantPoolItem::CPLongInfo { value } => Some(format!("{}L", value)),
+        ConstantPoolItem::CPFloatInfo { value } => Some(format!("{:?}f", value)),
+        ConstantPoolItem::CPDoubleInfo { value } => Some(format!("{:?}", value)),
+        ConstantPoolItem::CPStringInfo { string_index } => Some(format!("{:?}", constant_pool.get_string(*string_index))),
+        other => None
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use cesu8::to_java_cesu8;
+
+use crate::{class_loader::{ConstantPool, ConstantPoolItem}, class_writer::ClassWriter};
+
+// Indexes are u16 and the count written before the entries includes the unused index 0
+const MAX_CONSTANT_POOL_COUNT: usize = 65535;
+
+/// Builds a constant pool for a generated or patched class. Adding an entry that is already in the pool
+/// returns the index of the existing one, so classes don't get duplicate constants.
+pub struct ConstantPoolBuilder {
+    constant_pool: ConstantPool,
+    // Entries by their encoding in the class file, which tells apart floats like 0.0 and -0.0
+    indexes: HashMap<Vec<u8>, u16>
+}
+
+impl Default for ConstantPoolBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> ConstantPoolBuilder {
+        // Index 0 is not used, the parser puts the same filler there
+        let constants = vec![ConstantPoolItem::CPClassInfo { name_index: 0 }];
+        ConstantPoolBuilder { constant_pool: ConstantPool { constants }, indexes: HashMap::new() }
+    }
+
+    /// Continues with the constant pool of a class that was parsed, to add what a patch needs.
+    /// The existing entries keep their indexes.
+    pub fn from_constant_pool(constant_pool: ConstantPool) -> ConstantPoolBuilder {
+        let mut indexes = HashMap::new();
+        for (index, item) in constant_pool.constants.iter().enumerate().skip(1) {
+            if !matches!(item, ConstantPoolItem::CPUnusable) {
+                indexes.entry(Self::key(item)).or_insert(index as u16);
+            }
+        }
+        ConstantPoolBuilder { constant_pool, indexes }
+    }
+
+    /// The number of entries, counting index 0 and the second slots of longs and doubles, as in the class file.
+    pub fn count(&self) -> usize {
+        self.constant_pool.constants.len()
+    }
+
+    pub fn constant_pool(&self) -> &ConstantPool {
+        &self.constant_pool
+    }
+
+    pub fn build(self) -> ConstantPool {
+        self.constant_pool
+    }
+
+    fn key(item: &ConstantPoolItem) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ClassWriter::write_constant(&mut bytes, item);
+        bytes
+    }
+
+    /// Returns the index of an entry, adding it when it isn't in the pool yet.
+    /// Fails when the pool is full, as a class can't have more than 65535 entries.
+    pub fn add(&mut self, item: ConstantPoolItem) -> Result<u16, String> {
+        let key = Self::key(&item);
+        if let Some(index) = self.indexes.get(&key) {
+            return Ok(*index);
+        }
+        // Longs and doubles take up two entries
+        let size = if matches!(item, ConstantPoolItem::CPLongInfo { .. } | ConstantPoolItem::CPDoubleInfo { .. }) { 2 } else { 1 };
+        if self.count() + size > MAX_CONSTANT_POOL_COUNT {
+            return Err(format!("Constant pool is full, it can have at most {} entries", MAX_CONSTANT_POOL_COUNT - 1));
+        }
+        let index = self.count() as u16;
+        self.constant_pool.constants.push(item);
+        if size == 2 {
+            self.constant_pool.constants.push(ConstantPoolItem::CPUnusable);
+        }
+        self.indexes.insert(key, index);
+        Ok(index)
+    }
+
+    /// Fails for strings longer than 65535 bytes in modified UTF-8, which the class file format can't hold.
+    pub fn add_utf8(&mut self, text: &str) -> Result<u16, String> {
+        let length = to_java_cesu8(text).len();
+        if length > u16::MAX as usize {
+            return Err(format!("String constant of {} bytes is too long for the constant pool", length));
+        }
+        self.add(ConstantPoolItem::CPUTF8Info { utf8_string: text.to_string(), bytes: None })
+    }
+
+    /// Adds a string given as UTF-16 code units, which can be a Java string with unpaired surrogates.
+    pub fn add_utf16(&mut self, code_units: &[u16]) -> Result<u16, String> {
+        if let Ok(text) = String::from_utf16(code_units) {
+            return self.add_utf8(&text);
+        }
+        // Modified UTF-8 encodes every code unit on its own, surrogates too
+        let mut bytes = Vec::new();
+        for unit in code_units.iter().map(|unit| *unit as u32) {
+            match unit {
+                0x01..=0x7F => bytes.push(unit as u8),
+                0x00..=0x7FF => bytes.extend([0xC0 | (unit >> 6) as u8, 0x80 | (unit & 0x3F) as u8]),
+                other => bytes.extend([0xE0 | (unit >> 12) as u8, 0x80 | ((unit >> 6) & 0x3F) as u8, 0x80 | (unit & 0x3F) as u8])
+            }
+        }
+        if bytes.len() > u16::MAX as usize {
+            return Err(format!("String constant of {} bytes is too long for the constant pool", bytes.len()));
+        }
+        self.add(ConstantPoolItem::CPUTF8Info { utf8_string: String::from_utf16_lossy(code_units), bytes: Some(bytes) })
+    }
+
+    pub fn add_integer(&mut self, value: i32) -> Result<u16, String> {
+        self.add(ConstantPoolItem::CPIntegerInfo { value })
+    }
+
+    pub fn add_float(&mut self, value: f32) -> Result<u16, String> {
+        self.add(ConstantPoolItem::CPFloatInfo { value })
+    }
+
+    pub fn add_long(&mut self, value: i64) -> Result<u16, String> {
+        self.add(ConstantPoolItem::CPLongInfo { value })
+    }
+
+    pub fn add_double(&mut self, value: f64) -> Result<u16, String> {
+        self.add(ConstantPoolItem::CPDoubleInfo { value })
+    }
+
+    /// Adds a class by its internal name, like java/lang/Object, or by its descriptor for array classes.
+    pub fn add_class(&mut self, name: &str) -> Result<u16, String> {
+        let name_index = self.add_utf8(name)?;
+        self.add(ConstantPoolItem::CPClassInfo { name_index })
+    }
+
+    pub fn add_string(&mut self, text: &str) -> Result<u16, String> {
+        let string_index = self.add_utf8(text)?;
+        self.add(ConstantPoolItem::CPStringInfo { string_index })
+    }
+
+    pub fn add_name_and_type(&mut self, name: &str, descriptor: &str) -> Result<u16, String> {
+        let name_index = self.add_utf8(name)?;
+        let descriptor_index = self.add_utf8(descriptor)?;
+        self.add(ConstantPoolItem::CPNameAndTypeInfo { name_index, descriptor_index })
+    }
+
+    pub fn add_field_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, String> {
+        let class_index = self.add_class(class)?;
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.add(ConstantPoolItem::CPFieldRef { class_index, name_and_type_index })
+    }
+
+    pub fn add_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, String> {
+        let class_index = self.add_class(class)?;
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.add(ConstantPoolItem::CPMethodRef { class_index, name_and_type_index })
+    }
+
+    pub fn add_interface_method_ref(&mut self, class: &str, name: &str, descriptor: &str) -> Result<u16, String> {
+        let class_index = self.add_class(class)?;
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.add(ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index })
+    }
+
+    /// Adds a method handle for a field or method reference that was already added.
+    pub fn add_method_handle(&mut self, reference_kind: u8, reference_index: u16) -> Result<u16, String> {
+        self.add(ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index })
+    }
+
+    pub fn add_method_type(&mut self, descriptor: &str) -> Result<u16, String> {
+        let descriptor_index = self.add_utf8(descriptor)?;
+        self.add(ConstantPoolItem::CPMethodTypeInfo { descriptor_index })
+    }
+
+    /// Adds a dynamic constant, whose bootstrap method is an index into the BootstrapMethods attribute.
+    pub fn add_dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<u16, String> {
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.add(ConstantPoolItem::CPDynamicInfo { bootstrap_method_attr_index, name_and_type_index })
+    }
+
+    pub fn add_invoke_dynamic(&mut self, bootstrap_method_attr_index: u16, name: &str, descriptor: &str) -> Result<u16, String> {
+        let name_and_type_index = self.add_name_and_type(name, descriptor)?;
+        self.add(ConstantPoolItem::CPInvokeDynamicInfo { bootstrap_method_attr_index, name_and_type_index })
+    }
+
+    pub fn add_module(&mut self, name: &str) -> Result<u16, String> {
+        let name_index = self.add_utf8(name)?;
+        self.add(ConstantPoolItem::CPModuleInfo { name_index })
+    }
+
+    pub fn add_package(&mut self, name: &str) -> Result<u16, String> {
+        let name_index = self.add_utf8(name)?;
+        self.add(ConstantPoolItem::CPPackageInfo { name_index })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::class_loader::ConstantPoolItem;
+
+    use super::{ConstantPoolBuilder, MAX_CONSTANT_POOL_COUNT};
+
+    #[test]
+    fn longs_and_doubles_take_two_entries() {
+        let mut builder = ConstantPoolBuilder::new();
+        assert_eq!(builder.add_integer(1), Ok(1));
+        assert_eq!(builder.add_long(2), Ok(2));
+        assert_eq!(builder.add_double(3.0), Ok(4));
+        assert_eq!(builder.add_float(4.0), Ok(6));
+        assert_eq!(builder.count(), 7);
+        assert!(matches!(builder.constant_pool().constants[3], ConstantPoolItem::CPUnusable));
+        assert!(matches!(builder.constant_pool().constants[5], ConstantPoolItem::CPUnusable));
+    }
+
+    #[test]
+    fn adds_an_entry_once() {
+        let mut builder = ConstantPoolBuilder::new();
+        let method_ref = builder.add_method_ref("java/lang/Object", "<init>", "()V").unwrap();
+        assert_eq!(builder.add_method_ref("java/lang/Object", "<init>", "()V"), Ok(method_ref));
+        assert_eq!(builder.add_utf8("java/lang/Object"), Ok(1));
+        assert_eq!(builder.add_class("java/lang/Object"), Ok(2));
+        assert_eq!(builder.add_long(7), builder.add_long(7));
+        // 0.0 and -0.0 are equal, but different constants
+        assert_ne!(builder.add_double(0.0), builder.add_double(-0.0));
+        let count = builder.count();
+        assert_eq!(builder.add_string("java/lang/Object"), Ok(count as u16));
+        assert_eq!(builder.count(), count + 1);
+    }
+
+    #[test]
+    fn fails_when_the_pool_is_full() {
+        let mut builder = ConstantPoolBuilder::new();
+        for value in 1..MAX_CONSTANT_POOL_COUNT - 1 {
+            builder.add_integer(value as i32).unwrap();
+        }
+        assert_eq!(builder.count(), MAX_CONSTANT_POOL_COUNT - 1);
+        // There is room for one more entry, not for a long
+        assert_eq!(builder.add_long(0), Err("Constant pool is full, it can have at most 65534 entries".to_string()));
+        assert_eq!(builder.add_integer(0), Ok(65534));
+        assert_eq!(builder.add_integer(-1), Err("Constant pool is full, it can have at most 65534 entries".to_string()));
+        // Entries that are already there can still be found
+        assert_eq!(builder.add_integer(1), Ok(1));
+    }
+
+    #[test]
+    fn fails_for_strings_too_long_for_utf8_constants() {
+        let mut builder = ConstantPoolBuilder::new();
+        assert_eq!(builder.add_utf8(&"a".repeat(65535)), Ok(1));
+        assert_eq!(builder.add_utf8(&"a".repeat(65536)), Err("String constant of 65536 bytes is too long for the constant pool".to_string()));
+        // A NUL character takes two bytes in modified UTF-8
+        assert_eq!(builder.add_utf8(&"\0".repeat(32768)), Err("String constant of 65536 bytes is too long for the constant pool".to_string()));
+        let surrogates = vec![0xD800; 21846];
+        assert_eq!(builder.add_utf16(&surrogates), Err("String constant of 65538 bytes is too long for the constant pool".to_string()));
+        assert_eq!(builder.count(), 2);
+    }
+}
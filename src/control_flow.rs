@@ -0,0 +1,476 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Write};
+
+use crate::{class_loader::{ATCode, ClassInfo}, disassembler::Disassembler, opcodes::*};
+
+/// How control gets from one basic block to another.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EdgeKind {
+    /// To the next instruction. A jsr counts as falling through to the instruction after it, where its subroutine
+    /// returns to, so a ret has no successors.
+    FallThrough,
+    /// A goto, a taken branch, a case of a switch or the call of a subroutine
+    Jump,
+    /// To an exception handler, with the index of its entry in the exception table
+    Exception(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions that is only entered at its first instruction and only left after its last one.
+/// Blocks also start and end at the bounds of the ranges of the exception table, so a block is either completely
+/// covered by an exception handler or not at all.
+#[derive(Debug)]
+pub struct BasicBlock {
+    pub start: usize,
+    // The pc after the last instruction
+    pub end: usize,
+    // The pcs of the instructions
+    pub instructions: Vec<usize>,
+    pub successors: Vec<Edge>,
+    pub predecessors: Vec<Edge>,
+}
+
+/// A natural loop: the blocks that can reach a back edge to the header without passing through the header.
+/// Loops with the same header are merged into one.
+#[derive(Debug)]
+pub struct Loop {
+    pub header: usize,
+    // The blocks of the loop, including the header
+    pub blocks: BTreeSet<usize>,
+}
+
+/// The dominator tree of a control flow graph, which counts exception edges like the other edges.
+pub struct Dominators {
+    // The immediate dominator of each block, the entry block has itself and unreachable blocks have None
+    immediate: Vec<Option<usize>>,
+}
+
+impl Dominators {
+    /// Returns the immediate dominator of a block, which is None for the entry block and unreachable blocks.
+    pub fn immediate_dominator(&self, block: usize) -> Option<usize> {
+        self.immediate[block].filter(|dominator| *dominator != block)
+    }
+
+    /// Returns whether every path from the entry to `block` goes through `dominator`. A block dominates itself.
+    pub fn dominates(&self, dominator: usize, block: usize) -> bool {
+        let mut current = block;
+        loop {
+            if current == dominator {
+                return true;
+            }
+            match self.immediate[current] {
+                Some(next) if next != current => current = next,
+                other => return false
+            }
+        }
+    }
+}
+
+/// The control flow graph of the code of a method, made of basic blocks. The entry block is the first one, and the
+/// blocks are in the order of their pcs.
+#[derive(Debug)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl ControlFlowGraph {
+    /// Splits code into basic blocks and connects them. Returns an error for code that doesn't decode, like an
+    /// unknown opcode or a branch into the middle of an instruction.
+    pub fn build(code: &ATCode) -> Result<ControlFlowGraph, String> {
+        let bytes = &code.code;
+        let mut pcs = Vec::new();
+        let mut pc = 0;
+        while pc < bytes.len() {
+            pcs.push(pc);
+            pc += instruction_length(bytes, pc).ok_or_else(|| format!("Bad instruction {:#04x} at pc {}", bytes[pc], pc))?;
+        }
+        let is_instruction = |pc: usize| pcs.binary_search(&pc).is_ok();
+
+        let mut leaders = BTreeSet::from([0]);
+        for &pc in &pcs {
+            let targets = branch_targets(bytes, pc);
+            for &target in &targets {
+                if target < 0 || !is_instruction(target as usize) {
+                    return Err(format!("Illegal target of jump or branch: {} at pc {}", target, pc));
+                }
+                leaders.insert(target as usize);
+            }
+            if !targets.is_empty() || !falls_through(bytes[pc]) {
+                leaders.insert(pc + instruction_length(bytes, pc).unwrap());
+            }
+        }
+        for entry in &code.exceptions {
+            let (start, end, handler) = (entry.start_pc as usize, entry.end_pc as usize, entry.handler_pc as usize);
+            if start >= end || !is_instruction(start) || !(is_instruction(end) || end == bytes.len()) {
+                return Err(format!("Illegal exception table range from {} to {}", start, end));
+            }
+            if !is_instruction(handler) {
+                return Err(format!("Illegal exception handler pc {}", handler));
+            }
+            leaders.extend([start, end, handler]);
+        }
+
+        let mut blocks: Vec<BasicBlock> = Vec::new();
+        for &pc in &pcs {
+            if leaders.contains(&pc) {
+                blocks.push(BasicBlock { start: pc, end: pc, instructions: Vec::new(), successors: Vec::new(), predecessors: Vec::new() });
+            }
+            let block = blocks.last_mut().unwrap();
+            block.instructions.push(pc);
+            block.end = pc + instruction_length(bytes, pc).unwrap();
+        }
+        let mut graph = ControlFlowGraph { blocks };
+
+        let mut edges = Vec::new();
+        for (from, block) in graph.blocks.iter().enumerate() {
+            let last = *block.instructions.last().unwrap();
+            for target in branch_targets(bytes, last) {
+                edges.push(Edge { from, to: graph.block_at(target as usize).unwrap(), kind: EdgeKind::Jump });
+            }
+            if falls_through(bytes[last]) && block.end < bytes.len() {
+                edges.push(Edge { from, to: from + 1, kind: EdgeKind::FallThrough });
+            }
+            for (index, entry) in code.exceptions.iter().enumerate() {
+                if (entry.start_pc as usize..entry.end_pc as usize).contains(&block.start) {
+                    edges.push(Edge { from, to: graph.block_at(entry.handler_pc as usize).unwrap(), kind: EdgeKind::Exception(index) });
+                }
+            }
+        }
+        for edge in edges {
+            // The cases of a switch can share a target
+            if !graph.blocks[edge.from].successors.contains(&edge) {
+                graph.blocks[edge.from].successors.push(edge);
+                graph.blocks[edge.to].predecessors.push(edge);
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Returns the index of the block with the instruction at a pc.
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        let index = self.blocks.partition_point(|block| block.start <= pc).checked_sub(1)?;
+        (pc < self.blocks[index].end).then_some(index)
+    }
+
+    /// Returns the blocks that can be reached from the entry, in reverse postorder.
+    pub fn reverse_postorder(&self) -> Vec<usize> {
+        let mut order = Vec::new();
+        if self.blocks.is_empty() {
+            return order;
+        }
+        let mut visited = vec![false; self.blocks.len()];
+        // Blocks with the index of the next successor to visit
+        let mut stack = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            match self.blocks[block].successors.get(next) {
+                Some(edge) => {
+                    stack.push((block, next + 1));
+                    if !visited[edge.to] {
+                        visited[edge.to] = true;
+                        stack.push((edge.to, 0));
+                    }
+                },
+                None => order.push(block)
+            }
+        }
+        order.reverse();
+        order
+    }
+
+    /// Computes the dominators with the algorithm of Cooper, Harvey and Kennedy.
+    pub fn dominators(&self) -> Dominators {
+        let order = self.reverse_postorder();
+        let mut position = vec![usize::MAX; self.blocks.len()];
+        for (index, block) in order.iter().enumerate() {
+            position[*block] = index;
+        }
+        let mut immediate = vec![None; self.blocks.len()];
+        if let Some(entry) = order.first() {
+            immediate[*entry] = Some(*entry);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in order.iter().skip(1) {
+                let mut dominator: Option<usize> = None;
+                for predecessor in self.blocks[block].predecessors.iter().map(|edge| edge.from) {
+                    if immediate[predecessor].is_none() {
+                        continue;
+                    }
+                    dominator = Some(match dominator {
+                        None => predecessor,
+                        Some(mut other) => {
+                            // Walks up from both blocks until they meet
+                            let mut current = predecessor;
+                            while current != other {
+                                while position[current] > position[other] {
+                                    current = immediate[current].unwrap();
+                                }
+                                while position[other] > position[current] {
+                                    other = immediate[other].unwrap();
+                                }
+                            }
+                            current
+                        }
+                    });
+                }
+                if immediate[block] != dominator {
+                    immediate[block] = dominator;
+                    changed = true;
+                }
+            }
+        }
+        Dominators { immediate }
+    }
+
+    /// Finds the natural loops, ordered by the pc of their header. Loops that are entered in more than one place,
+    /// which javac doesn't generate, have no back edge to a dominating header and aren't found.
+    pub fn loops(&self) -> Vec<Loop> {
+        let dominators = self.dominators();
+        let mut loops: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+        for edge in self.blocks.iter().flat_map(|block| &block.successors) {
+            if !dominators.dominates(edge.to, edge.from) {
+                continue;
+            }
+            let header = edge.to;
+            let blocks = loops.entry(header).or_insert_with(|| BTreeSet::from([header]));
+            let mut stack = vec![edge.from];
+            while let Some(block) = stack.pop() {
+                if blocks.insert(block) {
+                    let predecessors = self.blocks[block].predecessors.iter().map(|edge| edge.from);
+                    stack.extend(predecessors.filter(|predecessor| dominators.dominates(header, *predecessor)));
+                }
+            }
+        }
+        loops.into_iter().map(|(header, blocks)| Loop { header, blocks }).collect()
+    }
+
+    /// Writes the graph in the DOT format of Graphviz, with the instructions of each block. Branches name the block
+    /// they go to, exception edges are dashed and labeled with the class they catch, and loop headers are bold.
+    pub fn to_dot(&self, class_info: &ClassInfo, code: &ATCode, name: &str) -> String {
+        let constant_pool = &class_info.constant_pool;
+        let loop_headers: BTreeSet<usize> = self.loops().iter().map(|found| found.header).collect();
+        let mut output = String::new();
+        writeln!(output, "digraph \"{}\" {{", escape(name)).unwrap();
+        writeln!(output, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = format!("B{}\\l", index);
+            for &pc in &block.instructions {
+                let instruction = Disassembler::instruction_text(class_info, &code.code, pc, &mut |target| match self.block_at(target) {
+                    Some(target) => format!("B{}", target),
+                    None => target.to_string()
+                });
+                // The cases of switches are on lines of their own
+                let lines: Vec<String> = instruction.lines().map(|line| escape(line.trim())).collect();
+                write!(label, "{}: {}\\l", pc, lines.join("\\l    ")).unwrap();
+            }
+            let style = if loop_headers.contains(&index) { ", style=bold" } else { "" };
+            writeln!(output, "    B{} [label=\"{}\"{}];", index, label, style).unwrap();
+        }
+        for edge in self.blocks.iter().flat_map(|block| &block.successors) {
+            let attributes = match edge.kind {
+                EdgeKind::FallThrough | EdgeKind::Jump => String::new(),
+                EdgeKind::Exception(index) => {
+                    let catch_type = code.exceptions[index].catch_type;
+                    let caught = if catch_type == 0 { "any" } else { constant_pool.get_string(constant_pool.get_class_info(catch_type)) };
+                    format!(" [style=dashed, label=\"{}\"]", escape(caught))
+                }
+            };
+            writeln!(output, "    B{} -> B{}{};", edge.from, edge.to, attributes).unwrap();
+        }
+        output.push_str("}\n");
+        output
+    }
+}
+
+/// Returns the pcs that the instruction at a pc branches to, which can be outside the code for bad code.
+fn branch_targets(code: &[u8], pc: usize) -> Vec<i64> {
+    let read_u16 = |at: usize| u16::from_be_bytes([code[at], code[at + 1]]);
+    let read_i32 = |at: usize| i32::from_be_bytes(code[at..at + 4].try_into().unwrap());
+    let branch = |offset: i32| pc as i64 + offset as i64;
+    match code[pc] {
+        IFEQ..=JSR | IFNULL | IFNONNULL => vec![branch(read_u16(pc + 1) as i16 as i32)],
+        GOTO_W | JSR_W => vec![branch(read_i32(pc + 1))],
+        TABLESWITCH => {
+            let start = pc + 1 + (3 - pc % 4);
+            let count = (read_i32(start + 8) as i64 - read_i32(start + 4) as i64 + 1) as usize;
+            let cases = (0..count).map(|entry| branch(read_i32(start + 12 + entry * 4)));
+            cases.chain([branch(read_i32(start))]).collect()
+        },
+        LOOKUPSWITCH => {
+            let start = pc + 1 + (3 - pc % 4);
+            let cases = (0..read_i32(start + 4) as usize).map(|pair| branch(read_i32(start + 12 + pair * 8)));
+            cases.chain([branch(read_i32(start))]).collect()
+        },
+        other => Vec::new()
+    }
+}
+
+/// Returns whether the next instruction can run after an instruction.
+fn falls_through(opcode: u8) -> bool {
+    !matches!(opcode, GOTO | GOTO_W | TABLESWITCH | LOOKUPSWITCH | IRETURN..=RETURN | ATHROW | RET)
+}
+
+/// Escapes text for a quoted string in DOT.
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::{assembler::{CodeBuilder, Instruction}, constant_pool_builder::ConstantPoolBuilder, opcodes::*};
+
+    use super::{ControlFlowGraph, EdgeKind};
+
+    /// Assembles the code without verifying it and builds its graph.
+    fn build(builder: CodeBuilder, constant_pool: &mut ConstantPoolBuilder) -> ControlFlowGraph {
+        let code = builder.build_unverified(constant_pool, 2, 2).unwrap();
+        ControlFlowGraph::build(&code).unwrap()
+    }
+
+    fn add_all(builder: &mut CodeBuilder, instructions: Vec<Instruction>) {
+        for instruction in instructions {
+            builder.add(instruction);
+        }
+    }
+
+    fn immediate_dominators(graph: &ControlFlowGraph) -> Vec<Option<usize>> {
+        let dominators = graph.dominators();
+        (0..graph.blocks.len()).map(|block| dominators.immediate_dominator(block)).collect()
+    }
+
+    fn loops(graph: &ControlFlowGraph) -> Vec<(usize, BTreeSet<usize>)> {
+        graph.loops().into_iter().map(|found| (found.header, found.blocks)).collect()
+    }
+
+    #[test]
+    fn nested_loops() {
+        let mut builder = CodeBuilder::new();
+        let (outer, inner, inner_done, done) = (builder.new_label(), builder.new_label(), builder.new_label(), builder.new_label());
+        add_all(&mut builder, vec![
+            // 0: i = 0
+            Instruction::Simple(ICONST_0),
+            Instruction::Local(ISTORE, 0),
+            // 1: while (i < 10)
+            Instruction::Label(outer),
+            Instruction::Local(ILOAD, 0),
+            Instruction::Immediate(BIPUSH, 10),
+            Instruction::Jump(IF_ICMPGE, done),
+            // 2: j = 0
+            Instruction::Simple(ICONST_0),
+            Instruction::Local(ISTORE, 1),
+            // 3: while (j < 10)
+            Instruction::Label(inner),
+            Instruction::Local(ILOAD, 1),
+            Instruction::Immediate(BIPUSH, 10),
+            Instruction::Jump(IF_ICMPGE, inner_done),
+            // 4: j++
+            Instruction::Iinc(1, 1),
+            Instruction::Jump(GOTO, inner),
+            // 5: i++
+            Instruction::Label(inner_done),
+            Instruction::Iinc(0, 1),
+            Instruction::Jump(GOTO, outer),
+            // 6
+            Instruction::Label(done),
+            Instruction::Simple(RETURN),
+        ]);
+        let graph = build(builder, &mut ConstantPoolBuilder::new());
+        assert_eq!(immediate_dominators(&graph), [None, Some(0), Some(1), Some(2), Some(3), Some(3), Some(1)]);
+        assert_eq!(loops(&graph), [(1, BTreeSet::from([1, 2, 3, 4, 5])), (3, BTreeSet::from([3, 4]))]);
+    }
+
+    #[test]
+    fn loop_with_continue_and_break() {
+        let mut builder = CodeBuilder::new();
+        let (head, next, done) = (builder.new_label(), builder.new_label(), builder.new_label());
+        add_all(&mut builder, vec![
+            // 0: i = 0
+            Instruction::Simple(ICONST_0),
+            Instruction::Local(ISTORE, 0),
+            // 1: while (i < 10)
+            Instruction::Label(head),
+            Instruction::Local(ILOAD, 0),
+            Instruction::Immediate(BIPUSH, 10),
+            Instruction::Jump(IF_ICMPGE, done),
+            // 2: if (i % 2 == 0) continue
+            Instruction::Local(ILOAD, 0),
+            Instruction::Simple(ICONST_2),
+            Instruction::Simple(IREM),
+            Instruction::Jump(IFEQ, next),
+            // 3: if (i == 7) break
+            Instruction::Local(ILOAD, 0),
+            Instruction::Immediate(BIPUSH, 7),
+            Instruction::Jump(IF_ICMPEQ, done),
+            // 4: j = i
+            Instruction::Local(ILOAD, 0),
+            Instruction::Local(ISTORE, 1),
+            // 5: i++
+            Instruction::Label(next),
+            Instruction::Iinc(0, 1),
+            Instruction::Jump(GOTO, head),
+            // 6
+            Instruction::Label(done),
+            Instruction::Simple(RETURN),
+        ]);
+        let graph = build(builder, &mut ConstantPoolBuilder::new());
+        // The increment is reached both from the continue and from the end of the body
+        assert_eq!(immediate_dominators(&graph), [None, Some(0), Some(1), Some(2), Some(3), Some(2), Some(1)]);
+        // The break leaves the loop, so the block after it is not a member
+        assert_eq!(loops(&graph), [(1, BTreeSet::from([1, 2, 3, 4, 5]))]);
+    }
+
+    #[test]
+    fn try_catch() {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let exception = constant_pool.add_class("java/lang/Exception").unwrap();
+        let mut builder = CodeBuilder::new();
+        let (try_start, skip, try_end, handler, after) =
+            (builder.new_label(), builder.new_label(), builder.new_label(), builder.new_label(), builder.new_label());
+        add_all(&mut builder, vec![
+            // 0: i = 1
+            Instruction::Simple(ICONST_1),
+            Instruction::Local(ISTORE, 0),
+            // 1: try { if (i != 0)
+            Instruction::Label(try_start),
+            Instruction::Local(ILOAD, 0),
+            Instruction::Jump(IFEQ, skip),
+            // 2: i = 0
+            Instruction::Simple(ICONST_0),
+            Instruction::Local(ISTORE, 0),
+            // 3: j = i }
+            Instruction::Label(skip),
+            Instruction::Local(ILOAD, 0),
+            Instruction::Local(ISTORE, 1),
+            // 4
+            Instruction::Label(try_end),
+            Instruction::Jump(GOTO, after),
+            // 5: catch (Exception e) { i = -1 }
+            Instruction::Label(handler),
+            Instruction::Local(ASTORE, 1),
+            Instruction::Simple(ICONST_M1),
+            Instruction::Local(ISTORE, 0),
+            // 6
+            Instruction::Label(after),
+            Instruction::Simple(RETURN),
+        ]);
+        builder.add_handler(try_start, try_end, handler, exception);
+        let graph = build(builder, &mut constant_pool);
+        let handlers: Vec<usize> = graph.blocks[5].predecessors.iter()
+            .filter(|edge| edge.kind == EdgeKind::Exception(0))
+            .map(|edge| edge.from)
+            .collect();
+        assert_eq!(handlers, [1, 2, 3]);
+        // The handler can be entered from every block of the try, so only the first one dominates it
+        assert_eq!(immediate_dominators(&graph), [None, Some(0), Some(1), Some(1), Some(3), Some(1), Some(1)]);
+        assert!(loops(&graph).is_empty());
+    }
+}
@@ -0,0 +1,2427 @@
+use std::{cell::RefCell, collections::{BTreeMap, BTreeSet, HashMap, HashSet}, fmt::Write};
+
+use crate::{class_loader::{ATCode, AttributeInfo, ClassFlags, ClassInfo, ClassLoader, ConstantPool, ConstantPoolItem, FieldInfo, MethodFlags, MethodInfo},
+    class_printer::ClassPrinter, control_flow::ControlFlowGraph, descriptor_parser::argument_descriptors, disassembler::quote, opcodes::*};
+
+/// Writes the names of classes, without their package for the classes of java.lang and of the package of the class.
+/// The classes of other packages are imported, unless a class of another package already took their simple name.
+struct TypeNames {
+    package: String,
+    // The outer class and simple name of nested classes by internal name, from the InnerClasses attribute
+    nested_classes: HashMap<String, (String, String)>,
+    // The qualified names by simple name, of the classes that are written without their package
+    simple_names: RefCell<BTreeMap<String, String>>,
+}
+
+impl TypeNames {
+    /// Returns the Java name of a class from its internal name.
+    fn class_name(&self, internal_name: &str) -> String {
+        if internal_name.starts_with('[') {
+            return self.java_type(internal_name);
+        }
+        if let Some((outer_class, simple_name)) = self.nested_classes.get(internal_name) && outer_class != internal_name {
+            return format!("{}.{}", self.class_name(outer_class), simple_name);
+        }
+        let name = internal_name.replace('/', ".");
+        let (package, simple_name) = name.rsplit_once('.').unwrap_or(("", &name));
+        let mut simple_names = self.simple_names.borrow_mut();
+        match simple_names.get(simple_name) {
+            Some(qualified_name) if *qualified_name == name => simple_name.to_string(),
+            Some(other) => name.clone(),
+            None => {
+                simple_names.insert(simple_name.to_string(), name.clone());
+                simple_name.to_string()
+            }
+        }
+    }
+
+    /// Returns the Java type of a field descriptor, like `int[]` for `[I`.
+    fn java_type(&self, descriptor: &str) -> String {
+        let dimensions = descriptor.bytes().take_while(|byte| *byte == b'[').count();
+        let element = &descriptor[dimensions..];
+        let element_type = match element.strip_prefix('L').and_then(|name| name.strip_suffix(';')) {
+            Some(internal_name) => self.class_name(internal_name),
+            None => ClassPrinter::get_as_external_type(element)
+        };
+        element_type + &"[]".repeat(dimensions)
+    }
+
+    /// Returns the classes to import.
+    fn imports(&self) -> Vec<String> {
+        self.simple_names.borrow().values()
+            .filter(|name| {
+                let package = name.rsplit_once('.').map_or("", |(package, _)| package);
+                package != "java.lang" && package != self.package && !package.is_empty()
+            })
+            .cloned()
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Returns the kind of local variable slot that holds a value of a descriptor, like the prefix of its load instruction.
+fn slot_kind(descriptor: &str) -> char {
+    match descriptor.as_bytes().first() {
+        Some(b'J') => 'l',
+        Some(b'F') => 'f',
+        Some(b'D') => 'd',
+        Some(b'L' | b'[') => 'a',
+        other => 'i'
+    }
+}
+
+fn is_wide(type_name: &str) -> bool {
+    type_name == "long" || type_name == "double"
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Expr {
+    // A constant as Java source, with its type
+    Literal(String, String),
+    Variable(usize),
+    // The exception that a handler catches
+    Caught,
+    // A class, for static members
+    Class(String),
+    Super,
+    // An object made by `new` at a pc, whose constructor hasn't run yet
+    Uninitialized(usize, String),
+    // Object or class, name and type
+    Field(Box<Expr>, String, String),
+    ArrayElement(Box<Expr>, Box<Expr>, String),
+    ArrayLength(Box<Expr>),
+    // Receiver, name, arguments and return type
+    Call(Box<Expr>, String, Vec<Expr>, String),
+    New(String, Vec<Expr>),
+    // Element type, the lengths that are given, and the dimensions after those
+    NewArray(String, Vec<Expr>, usize),
+    // Element type and elements, like `new int[] {1, 2}`
+    ArrayInitializer(String, Vec<Expr>),
+    // Operator, operands and type
+    Binary(&'static str, Box<Expr>, Box<Expr>, String),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Cast(String, Box<Expr>),
+    // The value, the class and the variable of a pattern, like `o instanceof String s`
+    InstanceOf(Box<Expr>, String, Option<usize>),
+    // A variable that is incremented after it is read, like `dp++`
+    PostIncrement(usize, i32),
+    // The result of lcmp, fcmpl, fcmpg, dcmpl or dcmpg, until a branch compares it with 0
+    Compare(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    // An assignment that is used as a value, like `(n = tab.length) == 0`
+    Assign(Box<Expr>, Box<Expr>),
+    // A method reference on a class or an object, with the method name and the type of the functional interface
+    MethodReference(Box<Expr>, String, String),
+    // A lambda that calls the method that implements it, with its parameter names and type
+    Lambda(Vec<String>, Box<Expr>, String),
+    // Another invokedynamic: name, arguments and type
+    Dynamic(String, Vec<Expr>, String),
+}
+
+impl Expr {
+    /// Returns the operands in the order they are evaluated.
+    fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Field(object, ..) | Expr::ArrayLength(object) | Expr::Not(object) | Expr::Negate(object) | Expr::Cast(_, object)
+                | Expr::InstanceOf(object, ..) => vec![object],
+            Expr::ArrayElement(array, index, _) => vec![array, index],
+            Expr::Call(receiver, _, arguments, _) => [&**receiver].into_iter().chain(arguments).collect(),
+            Expr::New(_, arguments) | Expr::NewArray(_, arguments, _) | Expr::ArrayInitializer(_, arguments) | Expr::Dynamic(_, arguments, _) => {
+                arguments.iter().collect()
+            },
+            Expr::MethodReference(object, ..) | Expr::Lambda(_, object, _) => vec![object],
+            Expr::Binary(_, left, right, _) | Expr::Compare(_, left, right) | Expr::Assign(left, right) => vec![left, right],
+            Expr::Conditional(condition, yes, no) => vec![condition, yes, no],
+            other => Vec::new()
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Field(object, ..) | Expr::ArrayLength(object) | Expr::Not(object) | Expr::Negate(object) | Expr::Cast(_, object)
+                | Expr::InstanceOf(object, ..) => vec![object],
+            Expr::ArrayElement(array, index, _) => vec![array, index],
+            Expr::Call(receiver, _, arguments, _) => [&mut **receiver].into_iter().chain(arguments).collect(),
+            Expr::New(_, arguments) | Expr::NewArray(_, arguments, _) | Expr::ArrayInitializer(_, arguments) | Expr::Dynamic(_, arguments, _) => {
+                arguments.iter_mut().collect()
+            },
+            Expr::MethodReference(object, ..) | Expr::Lambda(_, object, _) => vec![object],
+            Expr::Binary(_, left, right, _) | Expr::Compare(_, left, right) | Expr::Assign(left, right) => vec![left, right],
+            Expr::Conditional(condition, yes, no) => vec![condition, yes, no],
+            other => Vec::new()
+        }
+    }
+
+    /// Returns whether evaluating the expression can't have side effects or see those of others, so it can be moved.
+    /// Fields, array elements and calls can.
+    fn is_pure(&self) -> bool {
+        match self {
+            Expr::Field(..) | Expr::ArrayElement(..) | Expr::ArrayLength(_) | Expr::Call(..) | Expr::New(..) | Expr::NewArray(..)
+                | Expr::ArrayInitializer(..) | Expr::MethodReference(..) | Expr::Lambda(..) | Expr::Dynamic(..) | Expr::Assign(..) | Expr::PostIncrement(..)
+                | Expr::InstanceOf(_, _, Some(_)) => false,
+            other => other.children().iter().all(|child| child.is_pure())
+        }
+    }
+
+    fn reads(&self, variable: usize) -> bool {
+        matches!(self, Expr::Variable(id) | Expr::PostIncrement(id, _) | Expr::InstanceOf(_, _, Some(id)) if *id == variable)
+            || self.children().iter().any(|child| child.reads(variable))
+    }
+
+    /// Returns whether `dup` can copy the expression, instead of storing it in a temporary variable first.
+    fn is_simple(&self) -> bool {
+        matches!(self, Expr::Literal(..) | Expr::Variable(_) | Expr::Class(_) | Expr::Uninitialized(..))
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary(operator, ..) => match *operator {
+                "*" | "/" | "%" => 13,
+                "+" | "-" => 12,
+                "<<" | ">>" | ">>>" => 11,
+                "<" | ">" | "<=" | ">=" => 10,
+                "==" | "!=" => 9,
+                "&" => 8,
+                "^" => 7,
+                "|" => 6,
+                "&&" => 5,
+                other => 4
+            },
+            Expr::InstanceOf(..) => 10,
+            Expr::Not(_) | Expr::Negate(_) | Expr::Cast(..) | Expr::NewArray(..) => 14,
+            Expr::Literal(text, _) if text.starts_with('-') => 14,
+            Expr::Conditional(..) | Expr::Lambda(..) => 3,
+            Expr::Assign(..) => 2,
+            other => 16
+        }
+    }
+
+    /// Returns the opposite of a condition.
+    fn negate(self) -> Expr {
+        let opposite = |operator: &str| match operator {
+            "==" => Some("!="),
+            "!=" => Some("=="),
+            "<" => Some(">="),
+            ">=" => Some("<"),
+            ">" => Some("<="),
+            "<=" => Some(">"),
+            other => None
+        };
+        match self {
+            Expr::Binary(operator, left, right, type_name) => match (operator, opposite(operator)) {
+                (_, Some(opposite)) => Expr::Binary(opposite, left, right, type_name),
+                ("&&", _) => Expr::Binary("||", Box::new(left.negate()), Box::new(right.negate()), type_name),
+                ("||", _) => Expr::Binary("&&", Box::new(left.negate()), Box::new(right.negate()), type_name),
+                other => Expr::Not(Box::new(Expr::Binary(operator, left, right, type_name)))
+            },
+            Expr::Not(condition) => *condition,
+            Expr::Literal(text, type_name) if text == "true" => Expr::Literal(String::from("false"), type_name),
+            Expr::Literal(text, type_name) if text == "false" => Expr::Literal(String::from("true"), type_name),
+            other => Expr::Not(Box::new(other))
+        }
+    }
+}
+
+fn literal(text: impl ToString, type_name: &str) -> Expr {
+    Expr::Literal(text.to_string(), type_name.to_string())
+}
+
+fn boolean_condition(operator: &'static str, left: Expr, right: Expr) -> Expr {
+    Expr::Binary(operator, Box::new(left), Box::new(right), String::from("boolean"))
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum LoopKind {
+    Forever,
+    While(Expr),
+    DoWhile(Expr),
+    // The initialization, the condition and the update
+    For(Box<Stmt>, Expr, Box<Stmt>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Catch {
+    types: Vec<String>,
+    variable: usize,
+    body: Vec<Stmt>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Stmt {
+    Expression(Expr),
+    Assign(Expr, Expr),
+    // A local variable declared where it is first assigned
+    Declare(usize, Expr),
+    Increment(usize, i32),
+    Return(Option<Expr>),
+    Throw(Expr),
+    // monitorenter or monitorexit, which synchronized blocks compile to
+    Monitor(&'static str, Expr),
+    Jsr(usize),
+    Ret(usize),
+    // The start of a block, which gets a label if a goto jumps there
+    Label(usize),
+    Goto(usize),
+    Break(Option<String>),
+    Continue(Option<String>),
+    If(Expr, Vec<Stmt>, Vec<Stmt>),
+    Loop(Option<String>, LoopKind, Vec<Stmt>),
+    // The cases are the keys of each body, None for default
+    Switch(Option<String>, Expr, Vec<(Vec<Option<i32>>, Vec<Stmt>)>),
+    Try(Vec<Stmt>, Vec<Catch>),
+    Comment(String),
+}
+
+impl Stmt {
+    fn bodies(&self) -> Vec<&Vec<Stmt>> {
+        match self {
+            Stmt::If(_, then, otherwise) => vec![then, otherwise],
+            Stmt::Loop(_, _, body) => vec![body],
+            Stmt::Switch(_, _, cases) => cases.iter().map(|(_, body)| body).collect(),
+            Stmt::Try(body, catches) => [body].into_iter().chain(catches.iter().map(|catch| &catch.body)).collect(),
+            other => Vec::new()
+        }
+    }
+
+    fn bodies_mut(&mut self) -> Vec<&mut Vec<Stmt>> {
+        match self {
+            Stmt::If(_, then, otherwise) => vec![then, otherwise],
+            Stmt::Loop(_, _, body) => vec![body],
+            Stmt::Switch(_, _, cases) => cases.iter_mut().map(|(_, body)| body).collect(),
+            Stmt::Try(body, catches) => [body].into_iter().chain(catches.iter_mut().map(|catch| &mut catch.body)).collect(),
+            other => Vec::new()
+        }
+    }
+
+    /// Returns the expressions of the statement itself, not of the statements in its bodies, in the order they are
+    /// evaluated.
+    fn expressions(&self) -> Vec<&Expr> {
+        match self {
+            Stmt::Loop(_, LoopKind::For(initialization, condition, update), _) => {
+                initialization.expressions().into_iter().chain([condition]).chain(update.expressions()).collect()
+            },
+            Stmt::Expression(expr) | Stmt::Return(Some(expr)) | Stmt::Throw(expr) | Stmt::Monitor(_, expr) | Stmt::If(expr, ..)
+                | Stmt::Loop(_, LoopKind::While(expr) | LoopKind::DoWhile(expr), _) | Stmt::Switch(_, expr, _) => vec![expr],
+            Stmt::Assign(target, value) => vec![target, value],
+            Stmt::Declare(_, value) => vec![value],
+            other => Vec::new()
+        }
+    }
+
+    fn expressions_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Stmt::Loop(_, LoopKind::For(initialization, condition, update), _) => {
+                initialization.expressions_mut().into_iter().chain([condition]).chain(update.expressions_mut()).collect()
+            },
+            Stmt::Expression(expr) | Stmt::Return(Some(expr)) | Stmt::Throw(expr) | Stmt::Monitor(_, expr) | Stmt::If(expr, ..)
+                | Stmt::Loop(_, LoopKind::While(expr) | LoopKind::DoWhile(expr), _) | Stmt::Switch(_, expr, _) => vec![expr],
+            Stmt::Assign(target, value) => vec![target, value],
+            Stmt::Declare(_, value) => vec![value],
+            other => Vec::new()
+        }
+    }
+}
+
+/// How a block of code is left.
+#[derive(Clone, Debug)]
+enum Exit {
+    // By a return, a throw or a ret
+    None,
+    Goto(usize),
+    // Condition, the block when it holds and the one when it doesn't
+    If(Expr, usize, usize),
+    // Value, the cases and the default
+    Switch(Expr, Vec<(i32, usize)>, usize),
+}
+
+struct Node {
+    statements: Vec<Stmt>,
+    exit: Exit,
+    // Blocks that were merged into a condition of the block before them are no longer live
+    live: bool,
+}
+
+struct Variable {
+    name: String,
+    type_name: String,
+    // Parameters and catch variables don't get a declaration at the start of the method
+    declared: bool,
+    temporary: bool,
+}
+
+/// A loop or switch that break and continue can leave.
+struct Context {
+    label: String,
+    is_loop: bool,
+    continue_target: usize,
+    break_target: usize,
+    label_used: bool,
+}
+
+/// Rebuilds Java-like source from class files, for classes that come without their source. The expressions are
+/// rebuilt from the operand stack, and if, loops, switches and try blocks from the control flow graph. Code that
+/// doesn't fit those structures gets labels and gotos, and the values that stay on the operand stack between
+/// statements get temporary variables named after their position on the stack.
+pub struct Decompiler<'a> {
+    class_info: &'a ClassInfo,
+    constant_pool: &'a ConstantPool,
+    names: &'a TypeNames,
+    output: String,
+}
+
+impl<'a> Decompiler<'a> {
+    pub fn decompile(class_info: &ClassInfo) -> String {
+        let constant_pool = &class_info.constant_pool;
+        let this_class = constant_pool.get_string(constant_pool.get_class_info(class_info.this_class));
+        let package = this_class.rsplit_once('/').map_or("", |(package, _)| package).replace('/', ".");
+        let mut nested_classes = HashMap::new();
+        for attribute in &class_info.attributes {
+            if let AttributeInfo::Unknown(unknown) = attribute && constant_pool.get_string(unknown.name_index) == "InnerClasses" {
+                let info = &unknown.info;
+                let read_u16 = |at: usize| u16::from_be_bytes([info[at], info[at + 1]]);
+                let count = if info.len() >= 2 { read_u16(0) as usize } else { 0 };
+                for at in (0..count).map(|entry| 2 + 8 * entry).filter(|at| at + 8 <= info.len()) {
+                    // Local and anonymous classes have no outer class or no name
+                    let (inner_class, outer_class, name) = (read_u16(at), read_u16(at + 2), read_u16(at + 4));
+                    if outer_class != 0 && name != 0 {
+                        let inner_class = constant_pool.get_string(constant_pool.get_class_info(inner_class)).to_string();
+                        let outer_class = constant_pool.get_string(constant_pool.get_class_info(outer_class)).to_string();
+                        nested_classes.insert(inner_class, (outer_class, constant_pool.get_string(name).to_string()));
+                    }
+                }
+            }
+        }
+        let names = TypeNames { package, nested_classes, simple_names: RefCell::new(BTreeMap::new()) };
+        // The class itself takes its simple name first
+        names.class_name(this_class);
+        let mut decompiler = Decompiler { class_info, constant_pool, names: &names, output: String::new() };
+        decompiler.write_class();
+        let mut header = String::new();
+        if !names.package.is_empty() {
+            header += &format!("package {};\n\n", names.package);
+        }
+        let imports = names.imports();
+        for import in &imports {
+            header += &format!("import {};\n", import);
+        }
+        if !imports.is_empty() {
+            header += "\n";
+        }
+        header + &decompiler.output
+    }
+
+    fn line(&mut self, indent: usize, line: &str) {
+        self.output.push_str(&"    ".repeat(indent));
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn this_class(&self) -> &'a str {
+        self.constant_pool.get_string(self.constant_pool.get_class_info(self.class_info.this_class))
+    }
+
+    fn write_class(&mut self) {
+        let this_class = self.this_class();
+        let access_flags = self.class_info.access_flags;
+        let is_interface = access_flags.contains(ClassFlags::ACC_INTERFACE);
+        let mut header = String::new();
+        for (_, flag) in access_flags.iter_names() {
+            if !(flag.as_keyword().is_empty() || is_interface && flag == ClassFlags::ACC_ABSTRACT) {
+                header += flag.as_keyword();
+                header += " ";
+            }
+        }
+        header += match access_flags {
+            flags if flags.contains(ClassFlags::ACC_ANNOTATION) => "@interface ",
+            flags if flags.contains(ClassFlags::ACC_INTERFACE) => "interface ",
+            flags if flags.contains(ClassFlags::ACC_ENUM) => "enum ",
+            other => "class "
+        };
+        header += self.names.class_name(this_class).rsplit('.').next().unwrap();
+        if self.class_info.super_class != 0 {
+            let super_class = self.constant_pool.get_string(self.constant_pool.get_class_info(self.class_info.super_class));
+            if super_class != "java/lang/Object" && super_class != "java/lang/Enum" && super_class != "java/lang/Record" {
+                header += &format!(" extends {}", self.names.class_name(super_class));
+            }
+        }
+        let interfaces: Vec<String> = self.class_info.interfaces.iter()
+            .map(|interface| self.names.class_name(self.constant_pool.get_string(self.constant_pool.get_class_info(*interface))))
+            .collect();
+        if !interfaces.is_empty() {
+            header += if is_interface { " extends " } else { " implements " };
+            header += &interfaces.join(", ");
+        }
+        self.line(0, &format!("{} {{", header));
+        for field in &self.class_info.fields {
+            self.write_field(field);
+        }
+        for method in &self.class_info.methods {
+            self.line(0, "");
+            self.write_method(method);
+        }
+        self.line(0, "}");
+    }
+
+    fn write_field(&mut self, field: &FieldInfo) {
+        let mut keywords = String::new();
+        for (_, flag) in field.access_flags.iter_names() {
+            keywords += flag.as_keyword();
+            keywords += " ";
+        }
+        let type_name = self.names.java_type(self.constant_pool.get_string(field.descriptor_index));
+        let mut line = format!("{}{} {}", java_modifiers(&keywords), type_name, field.get_name(self.constant_pool));
+        if let Some(index) = field.get_constant_value() {
+            line += &format!(" = {}", constant_text(self.names, self.class_info, index, &type_name));
+        }
+        self.line(1, &format!("{};", line));
+    }
+
+    fn write_method(&mut self, method: &MethodInfo) {
+        let name = method.get_name(self.constant_pool);
+        let simple_name = self.this_class().rsplit(['/', '$']).next().unwrap();
+        let Some(code) = method.get_code() else {
+            let descriptor = self.constant_pool.get_string(method.descriptor_index);
+            let parameter_names: Vec<String> = (0..argument_descriptors(descriptor).len()).map(|number| format!("arg{}", number)).collect();
+            let header = ClassPrinter::method_header(method, self.constant_pool, simple_name, &|descriptor| self.names.java_type(descriptor), &parameter_names);
+            self.line(1, &format!("{};", java_modifiers(&header)));
+            return;
+        };
+        let mut decompiler = MethodDecompiler::new(self.class_info, self.names, method, code);
+        let parameter_names: Vec<String> = decompiler.parameters.iter().map(|id| decompiler.variables[*id].name.clone()).collect();
+        if name == "<clinit>" {
+            self.line(1, "static {");
+        } else {
+            let header = ClassPrinter::method_header(method, self.constant_pool, simple_name, &|descriptor| self.names.java_type(descriptor), &parameter_names);
+            self.line(1, &format!("{} {{", java_modifiers(&header)));
+        }
+        match decompiler.decompile() {
+            Ok(body) => self.output += &body,
+            Err(message) => self.line(2, &format!("// The code can't be decompiled: {}", message))
+        }
+        self.line(1, "}");
+    }
+}
+
+/// The join target of a stack value that is an object whose constructor isn't called yet.
+const UNINITIALIZED: usize = usize::MAX - 1;
+
+/// Moves the keywords of access flags that Java source doesn't have, at the start of a declaration, into a comment.
+fn java_modifiers(declaration: &str) -> String {
+    let mut modifiers = Vec::new();
+    let mut others = Vec::new();
+    let mut rest = declaration;
+    while let Some((word, after)) = rest.split_once(' ') {
+        match word {
+            "public" | "private" | "protected" | "static" | "final" | "synchronized" | "native" | "abstract" | "volatile" | "transient" => modifiers.push(word),
+            "strict" => modifiers.push("strictfp"),
+            "bridge" | "varargs" | "synthetic" | "enum" => others.push(word),
+            other => break
+        }
+        rest = after;
+    }
+    let comment = if others.is_empty() { String::new() } else { format!("/* {} */ ", others.join(" ")) };
+    let modifiers = modifiers.iter().map(|modifier| format!("{} ", modifier)).collect::<String>();
+    format!("{}{}{}", comment, modifiers, rest)
+}
+
+/// Writes a loadable constant as Java source, using `type_name` for ints that are booleans or chars.
+fn constant_text(names: &TypeNames, class_info: &ClassInfo, index: u16, type_name: &str) -> String {
+    constant(names, class_info, index, type_name).map_or_else(|| format!("/* constant #{} */", index), |(text, _)| text)
+}
+
+/// Returns a loadable constant as Java source with its type.
+fn constant(names: &TypeNames, class_info: &ClassInfo, index: u16, type_name: &str) -> Option<(String, String)> {
+    let constant_pool = &class_info.constant_pool;
+    let float = |value: f64, text: String, class: &str| match value {
+        value if value.is_nan() => format!("{}.NaN", class),
+        value if value == f64::INFINITY => format!("{}.POSITIVE_INFINITY", class),
+        value if value == f64::NEG_INFINITY => format!("{}.NEGATIVE_INFINITY", class),
+        value => text
+    };
+    Some(match constant_pool.constants.get(index as usize)? {
+        ConstantPoolItem::CPIntegerInfo { value } => (int_text(*value, type_name), String::from(if type_name == "boolean" || type_name == "char" { type_name } else { "int" })),
+        ConstantPoolItem::CPLongInfo { value } => (format!("{}L", value), String::from("long")),
+        ConstantPoolItem::CPFloatInfo { value } => (float(*value as f64, format!("{:?}f", value), "Float"), String::from("float")),
+        ConstantPoolItem::CPDoubleInfo { value } => (float(*value, format!("{:?}", value), "Double"), String::from("double")),
+        ConstantPoolItem::CPStringInfo { string_index } => {
+            let code_units = match &constant_pool.constants[*string_index as usize] {
+                ConstantPoolItem::CPUTF8Info { bytes: Some(bytes), .. } => ClassLoader::decode_modified_utf8_to_utf16(bytes),
+                ConstantPoolItem::CPUTF8Info { utf8_string, .. } => utf8_string.encode_utf16().collect(),
+                other => Vec::new()
+            };
+            (quote(&code_units), String::from("String"))
+        },
+        ConstantPoolItem::CPClassInfo { name_index } => (format!("{}.class", names.class_name(constant_pool.get_string(*name_index))), String::from("Class")),
+        ConstantPoolItem::CPMethodTypeInfo { descriptor_index } => (format!("/* method type */ {}", quote(&constant_pool.get_string(*descriptor_index).encode_utf16().collect::<Vec<u16>>())), String::from("java.lang.invoke.MethodType")),
+        ConstantPoolItem::CPMethodHandleInfo { reference_index, .. } => {
+            let (owner, name, _) = member(constant_pool, *reference_index)?;
+            (format!("{}::{}", names.class_name(&owner), name), String::from("java.lang.invoke.MethodHandle"))
+        },
+        ConstantPoolItem::CPDynamicInfo { name_and_type_index, .. } => {
+            let (name_index, descriptor_index) = constant_pool.get_name_and_type(*name_and_type_index);
+            let descriptor = constant_pool.get_string(descriptor_index);
+            (format!("/* dynamic constant */ {}", constant_pool.get_string(name_index)), names.java_type(descriptor))
+        },
+        other => return None
+    })
+}
+
+/// Writes an int for a type, as true or false for booleans and as a character literal for printable chars.
+fn int_text(value: i32, type_name: &str) -> String {
+    match (type_name, value) {
+        ("boolean", 0) => String::from("false"),
+        ("boolean", 1) => String::from("true"),
+        ("char", 0x20..=0x7E) => match value as u8 as char {
+            '\'' => String::from("'\\''"),
+            '\\' => String::from("'\\\\'"),
+            character => format!("'{}'", character)
+        },
+        other => value.to_string()
+    }
+}
+
+/// Returns the class, name and descriptor of a field or method reference.
+fn member(constant_pool: &ConstantPool, index: u16) -> Option<(String, String, String)> {
+    match constant_pool.constants.get(index as usize)? {
+        ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } | ConstantPoolItem::CPMethodRef { class_index, name_and_type_index }
+            | ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } => {
+            let (name_index, descriptor_index) = constant_pool.get_name_and_type(*name_and_type_index);
+            let owner = constant_pool.get_string(constant_pool.get_class_info(*class_index));
+            Some((owner.to_string(), constant_pool.get_string(name_index).to_string(), constant_pool.get_string(descriptor_index).to_string()))
+        },
+        other => None
+    }
+}
+
+struct MethodDecompiler<'a> {
+    class_info: &'a ClassInfo,
+    constant_pool: &'a ConstantPool,
+    names: &'a TypeNames,
+    method: &'a MethodInfo,
+    code: &'a ATCode,
+    graph: Option<ControlFlowGraph>,
+    variables: Vec<Variable>,
+    // The variables of the parameters, starting with this
+    parameters: Vec<usize>,
+    // The variables by the slot and kind of their parameter
+    parameter_slots: HashMap<u16, (usize, char)>,
+    // The entries of the LocalVariableTable: slot, start and end pc, name and descriptor
+    local_variables: Vec<(u16, usize, usize, String, String)>,
+    named_variables: HashMap<(u16, String, String), usize>,
+    unnamed_variables: HashMap<(u16, char), usize>,
+    nodes: Vec<Node>,
+    // The exception table entries that became try blocks
+    handled_exceptions: HashSet<usize>,
+    contexts: Vec<Context>,
+    goto_targets: BTreeSet<usize>,
+}
+
+impl<'a> MethodDecompiler<'a> {
+    fn new(class_info: &'a ClassInfo, names: &'a TypeNames, method: &'a MethodInfo, code: &'a ATCode) -> MethodDecompiler<'a> {
+        let constant_pool = &class_info.constant_pool;
+        let mut local_variables = Vec::new();
+        for attribute in &code.attributes {
+            if let AttributeInfo::LocalVariableTable(table) = attribute {
+                for entry in &table.entries {
+                    let (start, end) = (entry.start_pc as usize, entry.start_pc as usize + entry.length as usize);
+                    let name = constant_pool.get_string(entry.name_index).to_string();
+                    local_variables.push((entry.index, start, end, name, constant_pool.get_string(entry.descriptor_index).to_string()));
+                }
+            }
+        }
+        let mut decompiler = MethodDecompiler {
+            class_info,
+            constant_pool,
+            names,
+            method,
+            code,
+            graph: None,
+            variables: Vec::new(),
+            parameters: Vec::new(),
+            parameter_slots: HashMap::new(),
+            local_variables,
+            named_variables: HashMap::new(),
+            unnamed_variables: HashMap::new(),
+            nodes: Vec::new(),
+            handled_exceptions: HashSet::new(),
+            contexts: Vec::new(),
+            goto_targets: BTreeSet::new(),
+        };
+        let descriptor = constant_pool.get_string(method.descriptor_index);
+        let mut slot = 0;
+        if !method.access_flags.contains(MethodFlags::ACC_STATIC) {
+            let this = match decompiler.named_variable(0, 0, 'a') {
+                Some(this) => this,
+                None => decompiler.add_variable("this", &decompiler.names.class_name(decompiler.this_class()))
+            };
+            decompiler.parameter_slots.insert(0, (this, 'a'));
+            decompiler.variables[this].declared = true;
+            slot = 1;
+        }
+        for (number, argument) in argument_descriptors(descriptor).iter().enumerate() {
+            let kind = slot_kind(argument);
+            let id = match decompiler.named_variable(slot, 0, kind) {
+                Some(id) => id,
+                None => decompiler.add_variable(&format!("arg{}", number), &decompiler.names.java_type(argument))
+            };
+            decompiler.variables[id].declared = true;
+            decompiler.parameters.push(id);
+            decompiler.parameter_slots.insert(slot, (id, kind));
+            slot += if kind == 'l' || kind == 'd' { 2 } else { 1 };
+        }
+        decompiler
+    }
+
+    fn this_class(&self) -> &'a str {
+        self.constant_pool.get_string(self.constant_pool.get_class_info(self.class_info.this_class))
+    }
+
+    fn super_class(&self) -> &'a str {
+        if self.class_info.super_class == 0 {
+            return "java/lang/Object";
+        }
+        self.constant_pool.get_string(self.constant_pool.get_class_info(self.class_info.super_class))
+    }
+
+    /// Adds a variable, renaming it when another variable already has its name.
+    fn add_variable(&mut self, name: &str, type_name: &str) -> usize {
+        let mut unique_name = name.to_string();
+        let mut number = 2;
+        while self.variables.iter().any(|variable| variable.name == unique_name) {
+            unique_name = format!("{}_{}", name, number);
+            number += 1;
+        }
+        self.variables.push(Variable { name: unique_name, type_name: type_name.to_string(), declared: false, temporary: false });
+        self.variables.len() - 1
+    }
+
+    /// Returns the variable of the LocalVariableTable for a slot at a pc.
+    fn named_variable(&mut self, slot: u16, pc: usize, kind: char) -> Option<usize> {
+        let (_, _, _, name, descriptor) = self.local_variables.iter()
+            .find(|(index, start, end, _, descriptor)| *index == slot && (*start..*end).contains(&pc) && slot_kind(descriptor) == kind)?
+            .clone();
+        let key = (slot, name.clone(), descriptor.clone());
+        if let Some(id) = self.named_variables.get(&key) {
+            return Some(*id);
+        }
+        let id = self.add_variable(&name, &self.names.java_type(&descriptor));
+        self.named_variables.insert(key, id);
+        Some(id)
+    }
+
+    /// Returns the variable in a slot at a pc, which is named after its slot without a LocalVariableTable.
+    fn variable(&mut self, slot: u16, pc: usize, kind: char) -> usize {
+        if let Some(id) = self.named_variable(slot, pc, kind) {
+            return id;
+        }
+        if let Some((id, parameter_kind)) = self.parameter_slots.get(&slot) && *parameter_kind == kind {
+            return *id;
+        }
+        if let Some(id) = self.unnamed_variables.get(&(slot, kind)) {
+            return *id;
+        }
+        let (prefix, type_name) = match kind {
+            'l' => ("l", "long"),
+            'f' => ("f", "float"),
+            'd' => ("d", "double"),
+            'a' => ("a", "Object"),
+            other => ("i", "int")
+        };
+        let id = self.add_variable(&format!("{}{}", prefix, slot), type_name);
+        self.unnamed_variables.insert((slot, kind), id);
+        id
+    }
+
+    /// Returns a new temporary variable for a position on the operand stack.
+    fn temporary(&mut self, position: usize, type_name: &str) -> usize {
+        let id = self.add_variable(&format!("stack{}", position), type_name);
+        self.variables[id].temporary = true;
+        id
+    }
+
+    fn type_of(&self, expr: &Expr) -> String {
+        match expr {
+            Expr::Literal(_, type_name) | Expr::Field(_, _, type_name) | Expr::ArrayElement(_, _, type_name) | Expr::Call(_, _, _, type_name)
+                | Expr::Binary(_, _, _, type_name) | Expr::Cast(type_name, _) | Expr::Dynamic(_, _, type_name) | Expr::Class(type_name)
+                | Expr::Uninitialized(_, type_name) | Expr::New(type_name, _) => type_name.clone(),
+            Expr::Variable(id) => self.variables[*id].type_name.clone(),
+            Expr::Caught => String::from("Throwable"),
+            Expr::Super => self.names.class_name(self.super_class()),
+            Expr::NewArray(element_type, lengths, dimensions) => element_type.clone() + &"[]".repeat(lengths.len() + dimensions),
+            Expr::ArrayInitializer(element_type, _) => format!("{}[]", element_type),
+            Expr::ArrayLength(_) | Expr::Compare(..) => String::from("int"),
+            Expr::Not(_) | Expr::InstanceOf(..) => String::from("boolean"),
+            Expr::PostIncrement(id, _) => self.variables[*id].type_name.clone(),
+            Expr::Negate(operand) => self.type_of(operand),
+            Expr::Conditional(_, yes, _) => self.type_of(yes),
+            Expr::Assign(target, _) => self.type_of(target),
+            Expr::MethodReference(_, _, type_name) | Expr::Lambda(_, _, type_name) => type_name.clone()
+        }
+    }
+
+    /// Makes ints that are booleans or chars look like them.
+    fn coerce(&mut self, expr: Expr, type_name: &str) -> Expr {
+        match expr {
+            // Chars are widened explicitly, since methods like StringBuilder.append take them differently
+            expr if matches!(type_name, "int" | "long" | "float" | "double") && self.type_of(&expr) == "char" => Expr::Cast(type_name.to_string(), Box::new(expr)),
+            // A temporary variable that holds 1 or 0 becomes a condition where a boolean is expected
+            Expr::Variable(id) if type_name == "boolean" && self.variables[id].temporary => {
+                self.variables[id].type_name = type_name.to_string();
+                expr
+            },
+            Expr::Literal(text, literal_type) if literal_type == "int" && (type_name == "boolean" || type_name == "char") => match text.parse() {
+                Ok(value) => Expr::Literal(int_text(value, type_name), type_name.to_string()),
+                Err(_) => Expr::Literal(text, literal_type)
+            },
+            other => other
+        }
+    }
+
+    fn decompile(&mut self) -> Result<String, String> {
+        let graph = ControlFlowGraph::build(self.code)?;
+        let block_count = graph.blocks.len();
+        self.graph = Some(graph);
+        self.simulate()?;
+        self.merge_conditions();
+        let mut statements = Vec::new();
+        self.region(0, block_count, None, None, &mut statements);
+        self.clean_up(&mut statements);
+
+        let mut output = String::new();
+        let mut used = BTreeSet::new();
+        collect_variables(&statements, &mut used);
+        used.retain(|id| !self.variables[*id].declared);
+        // The variables that can't be declared where they are first assigned are declared at the start
+        let mut declared = BTreeSet::new();
+        declare_variables(&mut statements, &used, &mut declared);
+        for id in used.difference(&declared) {
+            let variable = &self.variables[*id];
+            writeln!(output, "        {} {};", variable.type_name, variable.name).unwrap();
+        }
+        self.write_statements(&mut output, &statements, 2);
+        Ok(output)
+    }
+
+    fn graph(&self) -> &ControlFlowGraph {
+        self.graph.as_ref().unwrap()
+    }
+
+    fn block_at(&self, pc: usize) -> usize {
+        self.graph().block_at(pc).unwrap()
+    }
+
+    /// Runs the instructions of each block on a stack of expressions, which turns them into statements.
+    fn simulate(&mut self) -> Result<(), String> {
+        let block_count = self.graph().blocks.len();
+        let mut order = self.graph().reverse_postorder();
+        let reachable: HashSet<usize> = order.iter().copied().collect();
+        order.extend((0..block_count).filter(|block| !reachable.contains(block)));
+        let mut stacks: Vec<Option<Vec<Expr>>> = vec![None; block_count];
+        for entry in &self.code.exceptions {
+            stacks[self.block_at(entry.handler_pc as usize)] = Some(vec![Expr::Caught]);
+        }
+        let mut nodes: Vec<Option<Node>> = (0..block_count).map(|_| None).collect();
+        for block in order {
+            let stack = stacks[block].clone().unwrap_or_default();
+            let (node, successors, stack) = self.simulate_block(block, stack, &mut stacks)?;
+            for successor in successors {
+                if stacks[successor].is_none() && nodes[successor].is_none() {
+                    stacks[successor] = Some(stack.clone());
+                }
+            }
+            nodes[block] = Some(node);
+        }
+        self.nodes = nodes.into_iter().map(Option::unwrap).collect();
+        Ok(())
+    }
+
+    /// Stores the expressions on the stack that aren't pure or that read `written` in temporary variables, so the
+    /// statement that comes next doesn't change the order they are evaluated in.
+    fn before_statement(&mut self, stack: &mut [Expr], statements: &mut Vec<Stmt>, written: Option<usize>) {
+        for position in 0..stack.len() {
+            if !stack[position].is_pure() || written.is_some_and(|variable| stack[position].reads(variable)) {
+                self.spill(stack, statements, position);
+            }
+        }
+    }
+
+    fn spill(&mut self, stack: &mut [Expr], statements: &mut Vec<Stmt>, position: usize) {
+        let type_name = self.type_of(&stack[position]);
+        let temporary = self.temporary(position, &type_name);
+        let value = std::mem::replace(&mut stack[position], Expr::Variable(temporary));
+        statements.push(Stmt::Assign(Expr::Variable(temporary), value));
+    }
+
+    /// Pushes a statement, after the expressions that were evaluated before it.
+    fn statement(&mut self, stack: &mut [Expr], statements: &mut Vec<Stmt>, written: Option<usize>, statement: Stmt) {
+        self.before_statement(stack, statements, written);
+        statements.push(statement);
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn simulate_block(&mut self, block: usize, mut stack: Vec<Expr>, stacks: &mut [Option<Vec<Expr>>]) -> Result<(Node, Vec<usize>, Vec<Expr>), String> {
+        let code = &self.code.code;
+        let instructions = self.graph().blocks[block].instructions.clone();
+        let end = self.graph().blocks[block].end;
+        let mut statements = Vec::new();
+        let mut exit = if end < code.len() { Exit::Goto(block + 1) } else { Exit::None };
+        let read_u16 = |at: usize| u16::from_be_bytes([code[at], code[at + 1]]);
+        let read_i32 = |at: usize| i32::from_be_bytes(code[at..at + 4].try_into().unwrap());
+        macro_rules! pop {
+            () => {
+                stack.pop().ok_or_else(|| format!("The operand stack is empty at pc {}", instructions.last().unwrap()))?
+            };
+        }
+        for &pc in &instructions {
+            let next_pc = pc + instruction_length(code, pc).unwrap();
+            let (opcode, wide) = if code[pc] == WIDE { (code[pc + 1], true) } else { (code[pc], false) };
+            let slot = if wide { read_u16(pc + 2) } else if pc + 1 < code.len() { code[pc + 1] as u16 } else { 0 };
+            let branch = |offset: i32| (pc as i64 + offset as i64) as usize;
+            match opcode {
+                NOP => {},
+                ACONST_NULL => stack.push(literal("null", "Object")),
+                ICONST_M1..=ICONST_5 => stack.push(literal(opcode as i32 - ICONST_0 as i32, "int")),
+                LCONST_0 | LCONST_1 => stack.push(literal(format!("{}L", opcode - LCONST_0), "long")),
+                FCONST_0..=FCONST_2 => stack.push(literal(format!("{}.0f", opcode - FCONST_0), "float")),
+                DCONST_0 | DCONST_1 => stack.push(literal(format!("{}.0", opcode - DCONST_0), "double")),
+                BIPUSH => stack.push(literal(code[pc + 1] as i8, "int")),
+                SIPUSH => stack.push(literal(read_u16(pc + 1) as i16, "int")),
+                LDC | LDC_W | LDC2_W => {
+                    let index = if opcode == LDC { code[pc + 1] as u16 } else { read_u16(pc + 1) };
+                    let (text, type_name) = constant(self.names, self.class_info, index, "int").unwrap_or_else(|| (format!("/* constant #{} */", index), String::from("Object")));
+                    stack.push(Expr::Literal(text, type_name));
+                },
+                ILOAD..=ALOAD | ILOAD_0..=ALOAD_3 => {
+                    let (kind, slot) = if opcode <= ALOAD { (opcode - ILOAD, slot) } else { ((opcode - ILOAD_0) / 4, ((opcode - ILOAD_0) % 4) as u16) };
+                    let variable = self.variable(slot, pc, b"ilfda"[kind as usize] as char);
+                    stack.push(Expr::Variable(variable));
+                },
+                IALOAD..=SALOAD => {
+                    let index = pop!();
+                    let array = pop!();
+                    let array_type = self.type_of(&array);
+                    let element_type = match opcode {
+                        IALOAD => "int",
+                        LALOAD => "long",
+                        FALOAD => "float",
+                        DALOAD => "double",
+                        AALOAD => array_type.strip_suffix("[]").unwrap_or("Object"),
+                        BALOAD if array_type == "boolean[]" => "boolean",
+                        BALOAD => "byte",
+                        CALOAD => "char",
+                        other => "short"
+                    }.to_string();
+                    stack.push(Expr::ArrayElement(Box::new(array), Box::new(index), element_type));
+                },
+                ISTORE..=ASTORE | ISTORE_0..=ASTORE_3 => {
+                    let (kind, slot) = if opcode <= ASTORE { (opcode - ISTORE, slot) } else { ((opcode - ISTORE_0) / 4, ((opcode - ISTORE_0) % 4) as u16) };
+                    let value = pop!();
+                    let before = self.variables.len();
+                    let mut variable = self.variable(slot, next_pc, b"ilfda"[kind as usize] as char);
+                    // A caught exception gets a variable of its own, as the handler can reuse a slot of the try block
+                    if value == Expr::Caught && variable < before && self.unnamed_variables.get(&(slot, 'a')) == Some(&variable) {
+                        variable = self.add_variable(&format!("a{}", slot), "Throwable");
+                        self.unnamed_variables.insert((slot, 'a'), variable);
+                    }
+                    // A variable without a name gets the type of the first value stored in it, and a new variable when
+                    // the slot is reused for a value of another type
+                    let value_type = self.type_of(&value);
+                    if kind == 4 && value != Expr::Caught && value_type != "Object" && self.unnamed_variables.get(&(slot, 'a')) == Some(&variable) {
+                        if variable < before && self.variables[variable].type_name != value_type {
+                            variable = self.add_variable(&format!("a{}", slot), &value_type);
+                            self.unnamed_variables.insert((slot, 'a'), variable);
+                        }
+                        self.variables[variable].type_name = value_type;
+                    }
+                    let type_name = self.variables[variable].type_name.clone();
+                    let value = self.coerce(value, &type_name);
+                    // A value that dup copied into a temporary variable just before is stored in the variable instead,
+                    // and the copy left on the stack reads that
+                    if let Expr::Variable(temporary) = value && self.variables[temporary].temporary && stack.contains(&value)
+                        && let Some(Stmt::Assign(Expr::Variable(target), _)) = statements.last() && *target == temporary {
+                        let Some(Stmt::Assign(_, copied)) = statements.pop() else {
+                            panic!("The statement changed");
+                        };
+                        self.before_statement(&mut stack, &mut statements, Some(variable));
+                        for expr in stack.iter_mut().filter(|expr| **expr == value) {
+                            *expr = Expr::Variable(variable);
+                        }
+                        statements.push(Stmt::Assign(Expr::Variable(variable), copied));
+                        continue;
+                    }
+                    self.statement(&mut stack, &mut statements, Some(variable), Stmt::Assign(Expr::Variable(variable), value));
+                },
+                IASTORE..=SASTORE => {
+                    let value = pop!();
+                    let index = pop!();
+                    let array = pop!();
+                    let element_type = self.type_of(&array).strip_suffix("[]").unwrap_or("Object").to_string();
+                    let value = self.coerce(value, &element_type);
+                    let target = Expr::ArrayElement(Box::new(array), Box::new(index), element_type);
+                    self.statement(&mut stack, &mut statements, None, Stmt::Assign(target, value));
+                },
+                POP | POP2 => {
+                    let mut count = if opcode == POP { 1 } else { 2 };
+                    while count > 0 {
+                        let value = pop!();
+                        count -= if is_wide(&self.type_of(&value)) { 2 } else { 1 };
+                        match value {
+                            Expr::Assign(target, value) => self.statement(&mut stack, &mut statements, None, Stmt::Assign(*target, *value)),
+                            value if !value.is_pure() => self.statement(&mut stack, &mut statements, None, Stmt::Expression(value)),
+                            other => {}
+                        }
+                    }
+                },
+                DUP => self.duplicate(&mut stack, &mut statements, 1, 0)?,
+                DUP_X1 => self.duplicate(&mut stack, &mut statements, 1, 1)?,
+                DUP_X2 => self.duplicate(&mut stack, &mut statements, 1, 2)?,
+                DUP2 => self.duplicate(&mut stack, &mut statements, 2, 0)?,
+                DUP2_X1 => self.duplicate(&mut stack, &mut statements, 2, 1)?,
+                DUP2_X2 => self.duplicate(&mut stack, &mut statements, 2, 2)?,
+                SWAP => {
+                    let top = pop!();
+                    let below = pop!();
+                    if !top.is_pure() && !below.is_pure() {
+                        // The one below was evaluated first
+                        stack.push(below);
+                        let position = stack.len() - 1;
+                        self.spill(&mut stack, &mut statements, position);
+                        let below = stack.pop().unwrap();
+                        stack.extend([top, below]);
+                    } else {
+                        stack.extend([top, below]);
+                    }
+                },
+                INEG..=DNEG => {
+                    let value = pop!();
+                    stack.push(Expr::Negate(Box::new(value)));
+                },
+                IADD..=DREM => {
+                    let right = pop!();
+                    let left = pop!();
+                    let operator = ["+", "-", "*", "/", "%"][((opcode - IADD) / 4) as usize];
+                    let type_name = ["int", "long", "float", "double"][((opcode - IADD) % 4) as usize];
+                    stack.push(Expr::Binary(operator, Box::new(left), Box::new(right), type_name.to_string()));
+                },
+                ISHL..=LXOR => {
+                    let right = pop!();
+                    let left = pop!();
+                    let operator = ["<<", "<<", ">>", ">>", ">>>", ">>>", "&", "&", "|", "|", "^", "^"][(opcode - ISHL) as usize];
+                    let type_name = match (opcode - ISHL) % 2 {
+                        // The logical operators also work on booleans, which are ints in class files
+                        0 if opcode >= IAND && self.type_of(&left) == "boolean" => "boolean",
+                        0 => "int",
+                        other => "long"
+                    };
+                    stack.push(Expr::Binary(operator, Box::new(left), Box::new(right), type_name.to_string()));
+                },
+                IINC => {
+                    let variable = self.variable(slot, pc, 'i');
+                    let increment = if wide { read_u16(pc + 4) as i16 as i32 } else { code[pc + 2] as i8 as i32 };
+                    // A variable that was just loaded and is incremented before it is used, like `dst[dp++]`
+                    let readers: Vec<usize> = (0..stack.len()).filter(|position| stack[*position].reads(variable)).collect();
+                    if let [position] = readers[..] && stack[position] == Expr::Variable(variable) && (increment == 1 || increment == -1) {
+                        stack[position] = Expr::PostIncrement(variable, increment);
+                        continue;
+                    }
+                    self.statement(&mut stack, &mut statements, Some(variable), Stmt::Increment(variable, increment));
+                },
+                I2L..=I2S => {
+                    let value = pop!();
+                    let type_name = match opcode {
+                        I2L | F2L | D2L => "long",
+                        I2F | L2F | D2F => "float",
+                        I2D | L2D | F2D => "double",
+                        I2B => "byte",
+                        I2C => "char",
+                        I2S => "short",
+                        other => "int"
+                    };
+                    stack.push(Expr::Cast(type_name.to_string(), Box::new(value)));
+                },
+                LCMP..=DCMPG => {
+                    let right = pop!();
+                    let left = pop!();
+                    let name = ["lcmp", "fcmpl", "fcmpg", "dcmpl", "dcmpg"][(opcode - LCMP) as usize];
+                    stack.push(Expr::Compare(name, Box::new(left), Box::new(right)));
+                },
+                IFEQ..=IFLE | IFNULL | IFNONNULL => {
+                    let value = pop!();
+                    let operator = match opcode {
+                        IFEQ | IFNULL => "==",
+                        IFNE | IFNONNULL => "!=",
+                        IFLT => "<",
+                        IFGE => ">=",
+                        IFGT => ">",
+                        other => "<="
+                    };
+                    let condition = match value {
+                        Expr::Compare(_, left, right) => boolean_condition(operator, *left, *right),
+                        value if opcode == IFNULL || opcode == IFNONNULL => boolean_condition(operator, value, literal("null", "Object")),
+                        value if self.type_of(&value) == "boolean" && operator == "!=" => value,
+                        value if self.type_of(&value) == "boolean" && operator == "==" => value.negate(),
+                        value => boolean_condition(operator, value, literal(0, "int"))
+                    };
+                    exit = Exit::If(condition, self.block_at(branch(read_u16(pc + 1) as i16 as i32)), self.block_at(next_pc));
+                },
+                IF_ICMPEQ..=IF_ACMPNE => {
+                    let right = pop!();
+                    let left = pop!();
+                    let operator = ["==", "!=", "<", ">=", ">", "<=", "==", "!="][(opcode - IF_ICMPEQ) as usize];
+                    let left_type = self.type_of(&left);
+                    let right = self.coerce(right, &left_type);
+                    exit = Exit::If(boolean_condition(operator, left, right), self.block_at(branch(read_u16(pc + 1) as i16 as i32)), self.block_at(next_pc));
+                },
+                GOTO | GOTO_W => {
+                    let offset = if opcode == GOTO { read_u16(pc + 1) as i16 as i32 } else { read_i32(pc + 1) };
+                    exit = Exit::Goto(self.block_at(branch(offset)));
+                },
+                JSR | JSR_W => {
+                    let offset = if opcode == JSR { read_u16(pc + 1) as i16 as i32 } else { read_i32(pc + 1) };
+                    let subroutine = self.block_at(branch(offset));
+                    self.statement(&mut stack, &mut statements, None, Stmt::Jsr(subroutine));
+                    if stacks[subroutine].is_none() {
+                        stacks[subroutine] = Some(vec![literal("returnAddress", "returnAddress")]);
+                    }
+                },
+                RET => {
+                    let variable = self.variable(slot, pc, 'a');
+                    statements.push(Stmt::Ret(variable));
+                    exit = Exit::None;
+                },
+                TABLESWITCH | LOOKUPSWITCH => {
+                    let value = pop!();
+                    let start = pc + 4 - pc % 4;
+                    let default = self.block_at(branch(read_i32(start)));
+                    let mut cases = Vec::new();
+                    if opcode == TABLESWITCH {
+                        let (low, high) = (read_i32(start + 4), read_i32(start + 8));
+                        for (entry, key) in (low..=high).enumerate() {
+                            let target = self.block_at(branch(read_i32(start + 12 + entry * 4)));
+                            if target != default {
+                                cases.push((key, target));
+                            }
+                        }
+                    } else {
+                        for pair in 0..read_i32(start + 4) as usize {
+                            cases.push((read_i32(start + 8 + pair * 8), self.block_at(branch(read_i32(start + 12 + pair * 8)))));
+                        }
+                    }
+                    exit = Exit::Switch(value, cases, default);
+                },
+                IRETURN..=ARETURN => {
+                    let value = pop!();
+                    let descriptor = self.constant_pool.get_string(self.method.descriptor_index);
+                    let value = self.coerce(value, &self.names.java_type(&descriptor[descriptor.find(')').unwrap() + 1..]));
+                    statements.push(Stmt::Return(Some(value)));
+                    exit = Exit::None;
+                },
+                RETURN => {
+                    statements.push(Stmt::Return(None));
+                    exit = Exit::None;
+                },
+                GETSTATIC | GETFIELD => {
+                    let (owner, name, descriptor) = member(self.constant_pool, read_u16(pc + 1)).ok_or("Bad field reference")?;
+                    let object = if opcode == GETSTATIC { Expr::Class(self.names.class_name(&owner)) } else { pop!() };
+                    stack.push(Expr::Field(Box::new(object), name, self.names.java_type(&descriptor)));
+                },
+                PUTSTATIC | PUTFIELD => {
+                    let (owner, name, descriptor) = member(self.constant_pool, read_u16(pc + 1)).ok_or("Bad field reference")?;
+                    let type_name = self.names.java_type(&descriptor);
+                    let value = self.coerce(pop!(), &type_name);
+                    let object = if opcode == PUTSTATIC { Expr::Class(self.names.class_name(&owner)) } else { pop!() };
+                    let target = Expr::Field(Box::new(object), name, type_name);
+                    // A value that dup copied just before stays on the stack as the assignment, like `(this.size = a.length) != 0`
+                    if let Expr::Variable(temporary) = value && self.variables[temporary].temporary
+                        && stack.iter().filter(|expr| **expr == value).count() == 1
+                        && let Some(Stmt::Assign(Expr::Variable(last), _)) = statements.last() && *last == temporary {
+                        let Some(Stmt::Assign(_, copied)) = statements.pop() else {
+                            panic!("The statement changed");
+                        };
+                        let position = stack.iter().position(|expr| *expr == value).unwrap();
+                        stack[position] = Expr::Assign(Box::new(target), Box::new(copied));
+                        continue;
+                    }
+                    self.statement(&mut stack, &mut statements, None, Stmt::Assign(target, value));
+                },
+                INVOKEVIRTUAL..=INVOKEINTERFACE => {
+                    let (owner, name, descriptor) = member(self.constant_pool, read_u16(pc + 1)).ok_or("Bad method reference")?;
+                    let argument_types = argument_descriptors(&descriptor);
+                    let mut arguments = Vec::new();
+                    for argument_type in argument_types.iter().rev() {
+                        arguments.push(self.coerce(pop!(), &self.names.java_type(argument_type)));
+                    }
+                    arguments.reverse();
+                    let return_type = self.names.java_type(&descriptor[descriptor.find(')').unwrap() + 1..]);
+                    let receiver = if opcode == INVOKESTATIC { Expr::Class(self.names.class_name(&owner)) } else { pop!() };
+                    if name == "<init>" {
+                        self.constructor(&mut stack, &mut statements, receiver, &owner, arguments);
+                        continue;
+                    }
+                    let receiver = match receiver {
+                        // invokespecial of a method that isn't private calls the method of a superclass
+                        Expr::Variable(id) if opcode == INVOKESPECIAL && owner != self.this_class() && self.variables[id].name == "this" => Expr::Super,
+                        other => other
+                    };
+                    let call = Expr::Call(Box::new(receiver), name, arguments, return_type.clone());
+                    if return_type == "void" {
+                        self.statement(&mut stack, &mut statements, None, Stmt::Expression(call));
+                    } else {
+                        stack.push(call);
+                    }
+                },
+                INVOKEDYNAMIC => {
+                    let expr = self.invoke_dynamic(read_u16(pc + 1), &mut stack)?;
+                    if self.type_of(&expr) == "void" {
+                        self.statement(&mut stack, &mut statements, None, Stmt::Expression(expr));
+                    } else {
+                        stack.push(expr);
+                    }
+                },
+                NEW => {
+                    let class = self.names.class_name(self.constant_pool.get_string(self.constant_pool.get_class_info(read_u16(pc + 1))));
+                    stack.push(Expr::Uninitialized(pc, class));
+                },
+                NEWARRAY => {
+                    let length = pop!();
+                    let element_type = crate::disassembler::array_type_name(code[pc + 1]).unwrap_or("int").to_string();
+                    stack.push(Expr::NewArray(element_type, vec![length], 0));
+                },
+                ANEWARRAY | MULTIANEWARRAY => {
+                    let class = self.constant_pool.get_string(self.constant_pool.get_class_info(read_u16(pc + 1)));
+                    // The class of anewarray is the element type, the one of multianewarray the array type
+                    let descriptor = if opcode == ANEWARRAY {
+                        if class.starts_with('[') { format!("[{}", class) } else { format!("[L{};", class) }
+                    } else {
+                        class.to_string()
+                    };
+                    let count = if opcode == ANEWARRAY { 1 } else { code[pc + 3] as usize };
+                    let mut lengths = Vec::new();
+                    for _ in 0..count {
+                        lengths.push(pop!());
+                    }
+                    lengths.reverse();
+                    let dimensions = descriptor.bytes().take_while(|byte| *byte == b'[').count();
+                    let element_type = self.names.java_type(&descriptor[dimensions..]);
+                    stack.push(Expr::NewArray(element_type, lengths, dimensions.saturating_sub(count)));
+                },
+                ARRAYLENGTH => {
+                    let array = pop!();
+                    stack.push(Expr::ArrayLength(Box::new(array)));
+                },
+                ATHROW => {
+                    let exception = pop!();
+                    statements.push(Stmt::Throw(exception));
+                    exit = Exit::None;
+                },
+                CHECKCAST | INSTANCEOF => {
+                    let value = pop!();
+                    let type_name = self.names.class_name(self.constant_pool.get_string(self.constant_pool.get_class_info(read_u16(pc + 1))));
+                    stack.push(if opcode == CHECKCAST { Expr::Cast(type_name, Box::new(value)) } else { Expr::InstanceOf(Box::new(value), type_name, None) });
+                },
+                MONITORENTER | MONITOREXIT => {
+                    let object = pop!();
+                    let name = if opcode == MONITORENTER { "monitorenter" } else { "monitorexit" };
+                    self.statement(&mut stack, &mut statements, None, Stmt::Monitor(name, object));
+                },
+                other => return Err(format!("Unknown opcode {:#04x} at pc {}", other, pc))
+            }
+        }
+
+        // The values left on the stack go into the temporary variables that the next blocks start with
+        let mut successors = match &exit {
+            Exit::None => Vec::new(),
+            Exit::Goto(target) => vec![*target],
+            Exit::If(_, taken, next) => vec![*taken, *next],
+            Exit::Switch(_, cases, default) => cases.iter().map(|(_, target)| *target).chain([*default]).collect()
+        };
+        if let Some(Stmt::Jsr(subroutine)) = statements.last() {
+            successors.push(*subroutine);
+        }
+        if !stack.is_empty() && !successors.is_empty() {
+            // Objects that aren't initialized yet stay on the stack for the constructor call after the join
+            let targets: Vec<usize> = match successors.iter().find_map(|successor| stacks[*successor].as_ref()) {
+                Some(next_stack) if next_stack.len() == stack.len() => next_stack.iter().zip(&stack).map(|(expr, value)| match expr {
+                    Expr::Variable(id) => *id,
+                    Expr::Uninitialized(..) if expr == value => UNINITIALIZED,
+                    other => usize::MAX
+                }).collect(),
+                other => Vec::new()
+            };
+            let targets = if targets.len() == stack.len() && !targets.contains(&usize::MAX) {
+                targets
+            } else {
+                let mut targets = Vec::new();
+                for position in 0..stack.len() {
+                    // A temporary variable that is already there can stay in place
+                    let target = match &stack[position] {
+                        Expr::Uninitialized(..) => UNINITIALIZED,
+                        Expr::Variable(id) if self.variables[*id].temporary && stack.iter().filter(|expr| expr.reads(*id)).count() == 1 => *id,
+                        other => {
+                            let type_name = self.type_of(other);
+                            self.temporary(position, &type_name)
+                        }
+                    };
+                    targets.push(target);
+                }
+                targets
+            };
+            // Values that read a variable that another value goes into are copied first
+            let conflict = stack.iter().enumerate().any(|(position, expr)| {
+                targets.iter().enumerate().any(|(other, target)| other != position && expr.reads(*target))
+            });
+            for position in 0..stack.len() {
+                if conflict && (!stack[position].is_simple() || stack[position].reads(targets[position])) {
+                    let type_name = self.type_of(&stack[position]);
+                    let fresh = self.temporary(position, &type_name);
+                    let value = std::mem::replace(&mut stack[position], Expr::Variable(fresh));
+                    statements.push(Stmt::Assign(Expr::Variable(fresh), value));
+                }
+            }
+            for (position, value) in stack.iter_mut().enumerate() {
+                let target = targets[position];
+                if target != UNINITIALIZED && *value != Expr::Variable(target) {
+                    // Values of different classes make the variable an Object
+                    let type_name = self.type_of(value);
+                    let is_class = |type_name: &str| type_name.ends_with("[]") || type_name.starts_with(char::is_uppercase) || type_name.contains('.');
+                    if self.variables[target].type_name != type_name && is_class(&type_name) && is_class(&self.variables[target].type_name)
+                        && !matches!(value, Expr::Literal(text, _) if text == "null") {
+                        self.variables[target].type_name = String::from("Object");
+                    }
+                    let value = std::mem::replace(value, Expr::Variable(target));
+                    statements.push(Stmt::Assign(Expr::Variable(target), value));
+                }
+            }
+        }
+        let node = Node { statements, exit, live: true };
+        Ok((node, successors, stack))
+    }
+
+    /// Runs dup and its variants, which copy the top `count` words below the `skip` words under them.
+    fn duplicate(&mut self, stack: &mut Vec<Expr>, statements: &mut Vec<Stmt>, count: usize, skip: usize) -> Result<(), String> {
+        let entries = |stack: &[Expr], this: &Self, from: usize, words: usize| -> Option<usize> {
+            let mut covered = 0;
+            let mut entries = 0;
+            while covered < words {
+                let expr = stack.get(stack.len().checked_sub(from + entries + 1)?)?;
+                covered += if is_wide(&this.type_of(expr)) { 2 } else { 1 };
+                entries += 1;
+            }
+            Some(entries)
+        };
+        let copied = entries(stack, self, 0, count).ok_or("The operand stack is empty at a dup")?;
+        let skipped = entries(stack, self, copied, skip).ok_or("The operand stack is empty at a dup")?;
+        let top = stack.len() - copied;
+        for position in top..stack.len() {
+            if !stack[position].is_simple() {
+                self.before_statement(&mut stack[..position], statements, None);
+                self.spill(stack, statements, position);
+            }
+        }
+        let copies: Vec<Expr> = stack[top..].to_vec();
+        let at = top - skipped;
+        stack.splice(at..at, copies);
+        Ok(())
+    }
+
+    /// Runs the constructor call of invokespecial, which turns the uninitialized object into a `new` expression.
+    fn constructor(&mut self, stack: &mut [Expr], statements: &mut Vec<Stmt>, receiver: Expr, owner: &str, arguments: Vec<Expr>) {
+        match receiver {
+            Expr::Uninitialized(pc, class) => {
+                let new = Expr::New(class, arguments);
+                let mut used = false;
+                for expr in stack.iter_mut() {
+                    if matches!(expr, Expr::Uninitialized(other, _) if *other == pc) {
+                        *expr = new.clone();
+                        used = true;
+                    }
+                }
+                if !used {
+                    self.statement(stack, statements, None, Stmt::Expression(new));
+                }
+            },
+            receiver => {
+                let receiver = if owner == self.this_class() { receiver } else { Expr::Super };
+                let call = Expr::Call(Box::new(receiver), String::from("<init>"), arguments, String::from("void"));
+                self.statement(stack, statements, None, Stmt::Expression(call));
+            }
+        }
+    }
+
+    /// Rebuilds string concatenation and lambdas from invokedynamic, and writes other calls as they are.
+    fn invoke_dynamic(&mut self, index: u16, stack: &mut Vec<Expr>) -> Result<Expr, String> {
+        let ConstantPoolItem::CPInvokeDynamicInfo { bootstrap_method_attr_index, name_and_type_index } = &self.constant_pool.constants[index as usize] else {
+            return Err(String::from("Bad invokedynamic reference"));
+        };
+        let (name_index, descriptor_index) = self.constant_pool.get_name_and_type(*name_and_type_index);
+        let name = self.constant_pool.get_string(name_index);
+        let descriptor = self.constant_pool.get_string(descriptor_index);
+        let argument_types = argument_descriptors(descriptor);
+        let mut arguments = Vec::new();
+        for _ in &argument_types {
+            arguments.push(stack.pop().ok_or("The operand stack is empty at invokedynamic")?);
+        }
+        arguments.reverse();
+        let return_type = self.names.java_type(&descriptor[descriptor.find(')').unwrap() + 1..]);
+        let bootstrap_method = self.class_info.get_bootstrap_method(*bootstrap_method_attr_index);
+        let bootstrap = bootstrap_method.and_then(|method| match &self.constant_pool.constants[method.bootstrap_method_ref as usize] {
+            ConstantPoolItem::CPMethodHandleInfo { reference_index, .. } => member(self.constant_pool, *reference_index),
+            other => None
+        });
+        let bootstrap_arguments = bootstrap_method.map_or(&[][..], |method| &method.bootstrap_arguments[..]);
+        match bootstrap {
+            Some((owner, bootstrap_name, _)) if owner == "java/lang/invoke/StringConcatFactory" => {
+                let mut parts: Vec<Expr> = Vec::new();
+                let mut remaining = arguments.into_iter();
+                let recipe = match bootstrap_arguments.first().map(|index| &self.constant_pool.constants[*index as usize]) {
+                    Some(ConstantPoolItem::CPStringInfo { string_index }) if bootstrap_name == "makeConcatWithConstants" => self.constant_pool.get_string(*string_index).to_string(),
+                    other => "\u{1}".repeat(argument_types.len())
+                };
+                let mut constants = bootstrap_arguments.iter().skip(1);
+                let mut text: Vec<u16> = Vec::new();
+                for character in recipe.chars() {
+                    let part = match character {
+                        '\u{1}' => remaining.next(),
+                        '\u{2}' => constants.next().and_then(|index| constant(self.names, self.class_info, *index, "int")).map(|(text, type_name)| Expr::Literal(text, type_name)),
+                        character => {
+                            let mut buffer = [0; 2];
+                            text.extend_from_slice(character.encode_utf16(&mut buffer));
+                            continue;
+                        }
+                    };
+                    if !text.is_empty() {
+                        parts.push(literal(quote(&std::mem::take(&mut text)), "String"));
+                    }
+                    parts.extend(part);
+                }
+                if !text.is_empty() {
+                    parts.push(literal(quote(&text), "String"));
+                }
+                // One of the first two operands has to be a String for + to concatenate
+                if parts.iter().take(2).all(|part| self.type_of(part) != "String") {
+                    parts.insert(0, literal("\"\"", "String"));
+                }
+                let mut parts = parts.into_iter();
+                let first = parts.next().unwrap_or_else(|| literal("\"\"", "String"));
+                Ok(parts.fold(first, |left, right| Expr::Binary("+", Box::new(left), Box::new(right), String::from("String"))))
+            },
+            Some((owner, _, _)) if owner == "java/lang/invoke/LambdaMetafactory" => {
+                let implementation = bootstrap_arguments.get(1).and_then(|index| match &self.constant_pool.constants[*index as usize] {
+                    ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index } => Some((*reference_kind, member(self.constant_pool, *reference_index)?)),
+                    other => None
+                });
+                // The first bootstrap argument is the erased type of the method of the functional interface
+                let parameter_count = match bootstrap_arguments.first().map(|index| &self.constant_pool.constants[*index as usize]) {
+                    Some(ConstantPoolItem::CPMethodTypeInfo { descriptor_index }) => argument_descriptors(self.constant_pool.get_string(*descriptor_index)).len(),
+                    other => 0
+                };
+                let synthetic = |method_name: &str| method_name.starts_with("lambda$");
+                match implementation {
+                    Some((kind, (owner, method_name, _))) if arguments.is_empty() && !synthetic(&method_name) => {
+                        let method_name = if method_name == "<init>" { String::from("new") } else { method_name };
+                        Ok(Expr::MethodReference(Box::new(Expr::Class(self.names.class_name(&owner))), method_name, return_type))
+                    },
+                    // A method reference bound to an object, like System.out::println
+                    Some((kind, (_, method_name, _))) if (kind == 5 || kind == 9) && arguments.len() == 1 && !synthetic(&method_name) => {
+                        Ok(Expr::MethodReference(Box::new(arguments.pop().unwrap()), method_name, return_type))
+                    },
+                    // Otherwise the lambda calls the method with the values it captures and then its parameters
+                    Some((kind, (owner, method_name, method_descriptor))) => {
+                        let parameters: Vec<String> = (0..parameter_count).map(|number| format!("p{}", number)).collect();
+                        let mut values = arguments;
+                        let parameter_types = argument_descriptors(&method_descriptor);
+                        let captured = values.len();
+                        for (number, parameter) in parameters.iter().enumerate() {
+                            // Instance methods take the first value as the object
+                            let type_index = (captured + number).checked_sub(if kind == 6 || kind == 8 { 0 } else { 1 });
+                            let type_name = type_index.and_then(|index| parameter_types.get(index)).map_or(String::from("Object"), |type_name| self.names.java_type(type_name));
+                            values.push(literal(parameter, &type_name));
+                        }
+                        let method_return_type = self.names.java_type(&method_descriptor[method_descriptor.find(')').unwrap() + 1..]);
+                        let body = match kind {
+                            8 => Expr::New(self.names.class_name(&owner), values),
+                            6 => Expr::Call(Box::new(Expr::Class(self.names.class_name(&owner))), method_name, values, method_return_type),
+                            other if values.is_empty() => return Ok(Expr::Dynamic(name.to_string(), Vec::new(), return_type)),
+                            other => {
+                                let receiver = values.remove(0);
+                                Expr::Call(Box::new(receiver), method_name, values, method_return_type)
+                            }
+                        };
+                        Ok(Expr::Lambda(parameters, Box::new(body), return_type))
+                    },
+                    None => Ok(Expr::Dynamic(name.to_string(), arguments, return_type))
+                }
+            },
+            other => Ok(Expr::Dynamic(name.to_string(), arguments, return_type))
+        }
+    }
+
+    /// Merges the blocks of the conditions of `&&` and `||` into the block before them.
+    fn merge_conditions(&mut self) {
+        loop {
+            let mut predecessors = vec![0; self.nodes.len()];
+            for node in self.nodes.iter().filter(|node| node.live) {
+                for target in exit_targets(&node.exit) {
+                    predecessors[target] += 1;
+                }
+            }
+            for entry in &self.code.exceptions {
+                predecessors[self.graph().block_at(entry.handler_pc as usize).unwrap()] += 1;
+            }
+            self.bind_patterns(&predecessors);
+            let mut merged = false;
+            for first in 0..self.nodes.len() {
+                let Exit::If(_, first_taken, second) = &self.nodes[first].exit else {
+                    continue;
+                };
+                let (first_taken, second) = (*first_taken, *second);
+                let next = self.next_live(first);
+                let second_node = &self.nodes[second];
+                if !self.nodes[first].live || second != next || second == first_taken || predecessors[second] != 1 {
+                    continue;
+                }
+                let Exit::If(second_condition, second_taken, second_next) = &second_node.exit else {
+                    continue;
+                };
+                let Some(second_condition) = fold_assignments(&second_node.statements, second_condition) else {
+                    continue;
+                };
+                let (second_taken, second_next) = (*second_taken, *second_next);
+                let Exit::If(first_condition, ..) = std::mem::replace(&mut self.nodes[first].exit, Exit::None) else {
+                    panic!("The exit changed");
+                };
+                let boolean = String::from("boolean");
+                self.nodes[first].exit = if second_taken == first_taken {
+                    Exit::If(Expr::Binary("||", Box::new(first_condition), Box::new(second_condition), boolean), first_taken, second_next)
+                } else if second_next == first_taken {
+                    Exit::If(Expr::Binary("&&", Box::new(first_condition.negate()), Box::new(second_condition), boolean), second_taken, second_next)
+                } else {
+                    self.nodes[first].exit = Exit::If(first_condition, first_taken, second);
+                    continue;
+                };
+                self.nodes[second].live = false;
+                self.nodes[second].statements.clear();
+                merged = true;
+                break;
+            }
+            if !merged {
+                break;
+            }
+        }
+    }
+
+    /// Turns a cast right after a passed instanceof into the variable of a pattern, like `o instanceof String s`.
+    fn bind_patterns(&mut self, predecessors: &[usize]) {
+        for block in 0..self.nodes.len() {
+            let Exit::If(Expr::Not(test), _, next) = &self.nodes[block].exit else {
+                continue;
+            };
+            let Expr::InstanceOf(value, class, None) = &**test else {
+                continue;
+            };
+            let next = *next;
+            if !self.nodes[block].live || predecessors[next] != 1 {
+                continue;
+            }
+            let variable = match self.nodes[next].statements.first() {
+                Some(Stmt::Assign(Expr::Variable(variable), Expr::Cast(cast, cast_value))) if cast == class && cast_value == value => *variable,
+                other => continue
+            };
+            self.nodes[next].statements.remove(0);
+            self.variables[variable].declared = true;
+            if let Exit::If(Expr::Not(test), ..) = &mut self.nodes[block].exit && let Expr::InstanceOf(_, _, binding) = &mut **test {
+                *binding = Some(variable);
+            }
+        }
+    }
+
+    fn next_live(&self, block: usize) -> usize {
+        (block + 1..self.nodes.len()).find(|next| self.nodes[*next].live).unwrap_or(self.nodes.len())
+    }
+
+    fn previous_live(&self, block: usize) -> Option<usize> {
+        (0..block).rev().find(|previous| self.nodes[*previous].live)
+    }
+
+    /// Returns the statement that jumps to a block: a break or continue of a loop or switch around it, or a goto.
+    fn jump(&mut self, target: usize) -> Stmt {
+        if let Some(jump) = self.context_jump(target) {
+            return jump;
+        }
+        // Blocks that only jump on go where they go, like the end of a loop body that jumps to the header
+        let mut next = target;
+        let mut seen = HashSet::new();
+        while next < self.nodes.len() && self.nodes[next].statements.is_empty() && seen.insert(next)
+            && let Exit::Goto(after) = self.nodes[next].exit {
+            next = after;
+        }
+        if let Some(jump) = self.context_jump(next) {
+            return jump;
+        }
+        self.goto_targets.insert(target);
+        Stmt::Goto(target)
+    }
+
+    fn context_jump(&mut self, target: usize) -> Option<Stmt> {
+        let innermost_loop = self.contexts.iter().rposition(|context| context.is_loop);
+        let innermost = self.contexts.len().checked_sub(1);
+        for index in (0..self.contexts.len()).rev() {
+            let context = &mut self.contexts[index];
+            if context.break_target == target {
+                if Some(index) == innermost {
+                    return Some(Stmt::Break(None));
+                }
+                context.label_used = true;
+                return Some(Stmt::Break(Some(context.label.clone())));
+            }
+            if context.is_loop && context.continue_target == target {
+                if Some(index) == innermost_loop {
+                    return Some(Stmt::Continue(None));
+                }
+                context.label_used = true;
+                return Some(Stmt::Continue(Some(context.label.clone())));
+            }
+        }
+        None
+    }
+
+    /// Jumps to a block after the last one of a region, unless that is where the code after the region goes anyway.
+    fn leave(&mut self, target: usize, end: usize, exit: Option<usize>, statements: &mut Vec<Stmt>) {
+        if !(target == end && exit == Some(end)) && exit != Some(target) {
+            let jump = self.jump(target);
+            statements.push(jump);
+        }
+    }
+
+    /// Turns the blocks from `start` up to `end` into statements. `exit` is the block that the code after the region
+    /// goes to, so jumps there are left out. `loop_header` is a loop that the region is the body of.
+    fn region(&mut self, start: usize, end: usize, exit: Option<usize>, loop_header: Option<usize>, statements: &mut Vec<Stmt>) {
+        let mut block = start;
+        while block < end {
+            if !self.nodes[block].live {
+                block += 1;
+                continue;
+            }
+            let try_block = self.try_at(block, end, exit);
+            let loop_end = if loop_header == Some(block) { None } else { self.loop_at(block, end) };
+            match (try_block, loop_end) {
+                (Some((try_end, handlers, follow)), loop_end) if loop_end.is_none_or(|loop_end| loop_end <= handlers.last().unwrap().0) => {
+                    block = self.try_statement(block, try_end, handlers, follow, statements);
+                    continue;
+                },
+                (_, Some(loop_end)) => {
+                    block = self.loop_statement(block, loop_end, statements);
+                    continue;
+                },
+                other => {}
+            }
+            statements.push(Stmt::Label(block));
+            statements.append(&mut self.nodes[block].statements);
+            let next = self.next_live(block);
+            match self.nodes[block].exit.clone() {
+                Exit::None => block = next,
+                Exit::Goto(target) => {
+                    if !(target == next && next < end) {
+                        self.leave(target, end, exit, statements);
+                    }
+                    block = next;
+                },
+                Exit::If(condition, taken, fall) => {
+                    let (condition, taken, fall) = if fall == next { (condition, taken, fall) } else if taken == next {
+                        (condition.negate(), fall, taken)
+                    } else {
+                        let jump = self.jump(taken);
+                        statements.push(Stmt::If(condition, vec![jump], Vec::new()));
+                        self.leave(fall, end, exit, statements);
+                        block = next;
+                        continue;
+                    };
+                    // A jump to where the code after the region goes skips the rest of the region
+                    if exit == Some(taken) && taken >= end && fall < end {
+                        let mut then = Vec::new();
+                        self.region(fall, end, exit, None, &mut then);
+                        statements.push(Stmt::If(condition.negate(), then, Vec::new()));
+                        block = end;
+                        continue;
+                    }
+                    let within = |target: usize| target > fall && (target < end || target == end && exit == Some(end));
+                    if !within(taken) || self.is_context_target(taken) {
+                        let jump = self.jump(taken);
+                        statements.push(Stmt::If(condition, vec![jump], Vec::new()));
+                        if next >= end {
+                            self.leave(fall, end, exit, statements);
+                        }
+                        block = next;
+                        continue;
+                    }
+                    // An if with an else ends its then part with a goto past the else part
+                    let last = self.previous_live(taken).unwrap();
+                    let else_end = match &self.nodes[last].exit {
+                        Exit::Goto(target) if last != block && *target > taken && (within(*target) || exit == Some(*target))
+                            && !self.is_context_target(*target) => Some(*target),
+                        other => None
+                    };
+                    let mut then = Vec::new();
+                    match else_end {
+                        Some(else_end) => {
+                            self.region(fall, taken, Some(else_end), None, &mut then);
+                            // The else part goes up to the end of the region when its code goes where the region's goes
+                            let else_region_end = else_end.min(end);
+                            let mut otherwise = Vec::new();
+                            self.region(taken, else_region_end, Some(else_end), None, &mut otherwise);
+                            statements.push(Stmt::If(condition.negate(), then, otherwise));
+                            block = else_region_end;
+                        },
+                        None => {
+                            self.region(fall, taken, Some(taken), None, &mut then);
+                            statements.push(Stmt::If(condition.negate(), then, Vec::new()));
+                            block = taken;
+                        }
+                    }
+                },
+                Exit::Switch(value, cases, default) => block = self.switch_statement(block, value, cases, default, end, exit, statements)
+            }
+        }
+    }
+
+    /// Returns whether a block is where a break or continue of a loop or switch around the code goes.
+    fn is_context_target(&self, block: usize) -> bool {
+        self.contexts.iter().any(|context| context.break_target == block || context.is_loop && context.continue_target == block)
+    }
+
+    /// Returns the end of the loop with its header at a block, if the loop fits in the region.
+    fn loop_at(&self, block: usize, end: usize) -> Option<usize> {
+        let loops = self.graph().loops();
+        let found = loops.iter().find(|found| found.header == block)?;
+        let last = *found.blocks.last().unwrap();
+        let loop_end = self.next_live(last);
+        (*found.blocks.first().unwrap() == block && loop_end <= end).then_some(loop_end)
+    }
+
+    fn loop_statement(&mut self, header: usize, loop_end: usize, statements: &mut Vec<Stmt>) -> usize {
+        self.contexts.push(Context { label: format!("loop{}", header), is_loop: true, continue_target: header, break_target: loop_end, label_used: false });
+        let mut body = Vec::new();
+        self.region(header, loop_end, Some(header), Some(header), &mut body);
+        let context = self.contexts.pop().unwrap();
+        statements.push(Stmt::Loop(context.label_used.then_some(context.label), LoopKind::Forever, body));
+        loop_end
+    }
+
+    /// Finds the outermost try block that starts at a block and fits in the region. Returns the end of the block,
+    /// its handlers with the entries of the exception table that they handle, and where the code after it goes.
+    #[allow(clippy::type_complexity)]
+    fn try_at(&mut self, block: usize, end: usize, exit: Option<usize>) -> Option<(usize, Vec<(usize, Vec<usize>)>, usize)> {
+        let start_pc = self.graph().blocks[block].start;
+        let code_length = self.code.code.len();
+        let block_count = self.nodes.len();
+        let end_of = |graph: &ControlFlowGraph, pc: usize| if pc >= code_length { block_count } else { graph.block_at(pc).unwrap() };
+        loop {
+            let entries: Vec<usize> = (0..self.code.exceptions.len())
+                .filter(|index| !self.handled_exceptions.contains(index) && self.code.exceptions[*index].start_pc as usize == start_pc)
+                .collect();
+            let end_pc = entries.iter().map(|index| self.code.exceptions[*index].end_pc).max()?;
+            let group: Vec<usize> = entries.into_iter().filter(|index| self.code.exceptions[*index].end_pc == end_pc).collect();
+            self.handled_exceptions.extend(&group);
+            let try_end = end_of(self.graph(), end_pc as usize);
+            let mut handlers: Vec<(usize, Vec<usize>)> = Vec::new();
+            for index in &group {
+                let handler = self.block_at(self.code.exceptions[*index].handler_pc as usize);
+                match handlers.iter_mut().find(|(other, _)| *other == handler) {
+                    Some((_, entries)) => entries.push(*index),
+                    None => handlers.push((handler, vec![*index]))
+                }
+            }
+            handlers.sort();
+            // The handlers have to come after the try block, in the region
+            if handlers.iter().any(|(handler, _)| *handler < try_end || *handler >= end) {
+                continue;
+            }
+            // The goto past the handlers comes right after the protected code, so the try block goes up to the first handler
+            let try_end = handlers[0].0;
+            // The code after the try statement is where the try block and the handlers jump past the last handler
+            let last_handler = handlers.last().unwrap().0;
+            let mut follow = None;
+            for region_end in [try_end].into_iter().chain(handlers.iter().skip(1).map(|(handler, _)| *handler)) {
+                if let Some(last) = self.previous_live(region_end) && let Exit::Goto(target) = self.nodes[last].exit
+                    && target > last_handler && (target < end || target == end && exit == Some(end)) {
+                    follow = Some(follow.map_or(target, |follow: usize| follow.min(target)));
+                }
+            }
+            return Some((try_end, handlers, follow.unwrap_or(end)));
+        }
+    }
+
+    fn try_statement(&mut self, block: usize, try_end: usize, handlers: Vec<(usize, Vec<usize>)>, follow: usize, statements: &mut Vec<Stmt>) -> usize {
+        let mut body = Vec::new();
+        self.region(block, try_end, Some(follow), None, &mut body);
+        let mut catches = Vec::new();
+        for (number, (handler, entries)) in handlers.iter().enumerate() {
+            let handler_end = handlers.get(number + 1).map_or(follow, |(next, _)| *next);
+            let mut handler_body = Vec::new();
+            self.region(*handler, handler_end, Some(follow), None, &mut handler_body);
+            // The handler usually starts by storing the exception in its variable
+            let first = handler_body.iter().position(|statement| !matches!(statement, Stmt::Label(_)));
+            let variable = match first.map(|first| (first, &handler_body[first])) {
+                Some((first, Stmt::Assign(Expr::Variable(id), Expr::Caught))) => {
+                    let id = *id;
+                    handler_body.remove(first);
+                    id
+                },
+                // Else the exception is used where it is on the stack, like in the handlers of finally blocks
+                other => {
+                    let id = self.add_variable("exception", "Throwable");
+                    replace_caught(&mut handler_body, id);
+                    id
+                }
+            };
+            self.variables[variable].declared = true;
+            let mut types: Vec<String> = Vec::new();
+            for index in entries {
+                let catch_type = self.code.exceptions[*index].catch_type;
+                let type_name = if catch_type == 0 { String::from("Throwable") } else {
+                    self.names.class_name(self.constant_pool.get_string(self.constant_pool.get_class_info(catch_type)))
+                };
+                if !types.contains(&type_name) {
+                    types.push(type_name);
+                }
+            }
+            if types.len() == 1 {
+                self.variables[variable].type_name = types[0].clone();
+            }
+            catches.push(Catch { types, variable, body: handler_body });
+        }
+        statements.push(Stmt::Try(body, catches));
+        follow
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn switch_statement(&mut self, block: usize, value: Expr, cases: Vec<(i32, usize)>, default: usize, end: usize, exit: Option<usize>,
+        statements: &mut Vec<Stmt>) -> usize {
+        let targets: BTreeSet<usize> = cases.iter().map(|(_, target)| *target).chain([default]).collect();
+        let first = *targets.first().unwrap();
+        let last = *targets.last().unwrap();
+        let within = |target: usize| target < end || target == end && exit == Some(end);
+        // The end of the switch is where the breaks of its cases go
+        let mut follow = (first..end.min(self.nodes.len()))
+            .filter(|block| self.nodes[*block].live)
+            .filter_map(|block| match &self.nodes[block].exit {
+                Exit::Goto(target) if *target > last && within(*target) && !self.is_context_target(*target) => Some(*target),
+                other => None
+            })
+            .min();
+        if follow.is_none() && default == last && cases.iter().all(|(_, target)| *target < default) {
+            follow = Some(default);
+        }
+        let follow = follow.unwrap_or(end);
+        if first <= block || targets.iter().any(|target| *target > follow) {
+            // Not a switch that fits in the region, so every case jumps
+            let mut bodies = Vec::new();
+            for (key, target) in cases {
+                let jump = self.jump(target);
+                bodies.push((vec![Some(key)], vec![jump]));
+            }
+            let jump = self.jump(default);
+            bodies.push((vec![None], vec![jump]));
+            statements.push(Stmt::Switch(None, value, bodies));
+            return self.next_live(block);
+        }
+        self.contexts.push(Context { label: format!("switch{}", block), is_loop: false, continue_target: usize::MAX, break_target: follow, label_used: false });
+        let starts: Vec<usize> = targets.iter().copied().filter(|target| *target != follow).collect();
+        let mut bodies = Vec::new();
+        for (number, start) in starts.iter().enumerate() {
+            let mut keys: Vec<Option<i32>> = cases.iter().filter(|(_, target)| target == start).map(|(key, _)| Some(*key)).collect();
+            if default == *start {
+                keys.push(None);
+            }
+            let case_end = starts.get(number + 1).copied().unwrap_or(follow);
+            let mut body = Vec::new();
+            self.region(*start, case_end, Some(case_end), None, &mut body);
+            bodies.push((keys, body));
+        }
+        let context = self.contexts.pop().unwrap();
+        statements.push(Stmt::Switch(context.label_used.then_some(context.label), value, bodies));
+        follow
+    }
+
+    /// Tidies up the structured statements: removes labels that no goto uses, rebuilds conditional expressions and
+    /// while loops, and puts the temporary variables that are used once back into the expression that uses them.
+    fn clean_up(&mut self, statements: &mut Vec<Stmt>) {
+        let goto_targets = self.goto_targets.clone();
+        retain_recursively(statements, &|statement| !matches!(statement, Stmt::Label(block) if !goto_targets.contains(block)));
+        self.rebuild_conditionals(statements);
+        loop {
+            let mut counts = HashMap::new();
+            count_variables(statements, &mut counts);
+            let rebuilt = self.rebuild_array_initializers(statements, &counts);
+            if !self.inline_temporaries(statements, &counts) && !rebuilt {
+                break;
+            }
+            self.rebuild_conditionals(statements);
+        }
+        self.rebuild_loops(statements);
+        // The return at the end of a void method and the call of the constructor of Object are implicit
+        if matches!(statements.last(), Some(Stmt::Return(None))) {
+            statements.pop();
+        }
+        if let Some(position) = statements.iter().position(|statement| !matches!(statement, Stmt::Label(_)))
+            && let Stmt::Expression(Expr::Call(receiver, name, arguments, _)) = &statements[position]
+            && **receiver == Expr::Super && name == "<init>" && arguments.is_empty() {
+            statements.remove(position);
+        }
+    }
+
+    /// Turns an if that stores one value or another in the same temporary variable into a conditional expression.
+    fn rebuild_conditionals(&self, statements: &mut [Stmt]) {
+        for statement in statements.iter_mut() {
+            for body in statement.bodies_mut() {
+                self.rebuild_conditionals(body);
+            }
+            // An if with only an else is turned around, and an if with only another if joins its condition
+            if let Stmt::If(condition, then, otherwise) = statement && then.is_empty() && !otherwise.is_empty() {
+                *condition = std::mem::replace(condition, Expr::Super).negate();
+                std::mem::swap(then, otherwise);
+            }
+            if let Stmt::If(condition, then, otherwise) = statement && otherwise.is_empty()
+                && let [Stmt::If(inner_condition, inner_then, inner_otherwise)] = then.as_mut_slice() && inner_otherwise.is_empty() {
+                let inner_then = std::mem::take(inner_then);
+                let inner_condition = std::mem::replace(inner_condition, Expr::Super);
+                *condition = boolean_condition("&&", std::mem::replace(condition, Expr::Super), inner_condition);
+                *then = inner_then;
+            }
+            let Stmt::If(condition, then, otherwise) = statement else {
+                continue;
+            };
+            let (Some(Stmt::Assign(Expr::Variable(first), yes)), Some(Stmt::Assign(Expr::Variable(second), no))) = (then.first(), otherwise.first()) else {
+                continue;
+            };
+            if then.len() != 1 || otherwise.len() != 1 || first != second || !self.variables[*first].temporary {
+                continue;
+            }
+            // Booleans are ints in class files, so a boolean condition stores 1 or 0
+            let boolean_value = |expr: &Expr| match expr {
+                Expr::Literal(text, _) if self.variables[*first].type_name == "boolean" && (text == "1" || text == "0") => {
+                    literal(if text == "1" { "true" } else { "false" }, "boolean")
+                },
+                other => other.clone()
+            };
+            let value = match (boolean_value(yes), boolean_value(no)) {
+                (Expr::Literal(yes, _), Expr::Literal(no, _)) if yes == "true" && no == "false" => condition.clone(),
+                (Expr::Literal(yes, _), Expr::Literal(no, _)) if yes == "false" && no == "true" => condition.clone().negate(),
+                (yes, no) => Expr::Conditional(Box::new(condition.clone()), Box::new(yes), Box::new(no))
+            };
+            *statement = Stmt::Assign(Expr::Variable(*first), value);
+        }
+    }
+
+    /// Puts temporary variables that are stored and used once, in the statement right after, back into that statement.
+    fn inline_temporaries(&self, statements: &mut Vec<Stmt>, counts: &HashMap<usize, (usize, usize)>) -> bool {
+        let mut changed = false;
+        for statement in statements.iter_mut() {
+            for body in statement.bodies_mut() {
+                changed |= self.inline_temporaries(body, counts);
+            }
+        }
+        let mut index = 0;
+        while index + 1 < statements.len() {
+            let Stmt::Assign(Expr::Variable(id), value) = &statements[index] else {
+                index += 1;
+                continue;
+            };
+            let (id, value) = (*id, value.clone());
+            if !self.variables[id].temporary || counts.get(&id) != Some(&(1, 1)) {
+                index += 1;
+                continue;
+            }
+            if substitute_in_statement(&mut statements[index + 1], id, &value) {
+                statements.remove(index);
+                changed = true;
+            } else {
+                index += 1;
+            }
+        }
+        changed
+    }
+
+    /// Turns a temporary array that gets all its elements stored right after it is made into an array initializer.
+    fn rebuild_array_initializers(&self, statements: &mut Vec<Stmt>, counts: &HashMap<usize, (usize, usize)>) -> bool {
+        let mut changed = false;
+        for statement in statements.iter_mut() {
+            for body in statement.bodies_mut() {
+                changed |= self.rebuild_array_initializers(body, counts);
+            }
+        }
+        for index in 0.. {
+            let Some(Stmt::Assign(Expr::Variable(id), Expr::NewArray(element_type, lengths, 0))) = statements.get(index) else {
+                if index >= statements.len() {
+                    break;
+                }
+                continue;
+            };
+            let (id, element_type) = (*id, element_type.clone());
+            let Ok(length) = (match lengths.as_slice() {
+                [Expr::Literal(text, _)] => text.parse::<usize>(),
+                other => continue
+            }) else {
+                continue;
+            };
+            if !self.variables[id].temporary || length == 0 || counts.get(&id).is_none_or(|(_, reads)| *reads != length + 1) {
+                continue;
+            }
+            let mut elements = Vec::new();
+            for (position, statement) in statements[index + 1..].iter().take(length).enumerate() {
+                match statement {
+                    Stmt::Assign(Expr::ArrayElement(array, element_index, _), value) if **array == Expr::Variable(id)
+                        && **element_index == literal(position, "int") && !value.reads(id) => elements.push(value.clone()),
+                    other => break
+                }
+            }
+            if elements.len() == length {
+                statements.drain(index + 1..index + 1 + length);
+                statements[index] = Stmt::Assign(Expr::Variable(id), Expr::ArrayInitializer(element_type, elements));
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Turns `while (true)` loops that start with a condition that breaks out into while loops, and the ones that end
+    /// with one into do while loops. While loops that update the variable they test at the end, after it is set right
+    /// before them, become for loops.
+    fn rebuild_loops(&self, statements: &mut Vec<Stmt>) {
+        for statement in statements.iter_mut() {
+            for body in statement.bodies_mut() {
+                self.rebuild_loops(body);
+            }
+            let Stmt::Loop(_, kind, body) = statement else {
+                continue;
+            };
+            if *kind != LoopKind::Forever {
+                continue;
+            }
+            while matches!(body.last(), Some(Stmt::Continue(None))) {
+                body.pop();
+            }
+            if let Some(Stmt::If(condition, then, otherwise)) = body.first() && then == &[Stmt::Break(None)] && otherwise.is_empty() {
+                *kind = LoopKind::While(condition.clone().negate());
+                body.remove(0);
+                continue;
+            }
+            let length = body.len();
+            if length >= 2 && body[length - 1] == Stmt::Break(None)
+                && let Stmt::If(condition, then, otherwise) = &body[length - 2] && then == &[Stmt::Continue(None)] && otherwise.is_empty() {
+                *kind = LoopKind::DoWhile(condition.clone());
+                body.truncate(length - 2);
+            } else if let Some(Stmt::If(condition, then, otherwise)) = body.last() && then == &[Stmt::Break(None)] && otherwise.is_empty() {
+                *kind = LoopKind::DoWhile(condition.clone().negate());
+                body.pop();
+            }
+        }
+        let mut index = 1;
+        while index < statements.len() {
+            if let Stmt::Assign(Expr::Variable(variable), _) = &statements[index - 1]
+                && let Stmt::Loop(label, LoopKind::While(condition), body) = &statements[index]
+                && condition.reads(*variable) && !continues(body, label.as_deref(), true)
+                && let Some(Stmt::Increment(updated, _) | Stmt::Assign(Expr::Variable(updated), _)) = body.last() && updated == variable {
+                let initialization = statements.remove(index - 1);
+                let Stmt::Loop(_, kind, body) = &mut statements[index - 1] else {
+                    panic!("The loop moved");
+                };
+                let LoopKind::While(condition) = std::mem::replace(kind, LoopKind::Forever) else {
+                    panic!("The loop changed");
+                };
+                let update = body.pop().unwrap();
+                *kind = LoopKind::For(Box::new(initialization), condition, Box::new(update));
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    fn expression(&self, expr: &Expr) -> String {
+        let operand = |expr: &Expr, precedence: u8| {
+            let text = self.expression(expr);
+            if expr.precedence() < precedence { format!("({})", text) } else { text }
+        };
+        let arguments = |arguments: &[Expr]| arguments.iter().map(|argument| self.expression(argument)).collect::<Vec<_>>().join(", ");
+        match expr {
+            Expr::Literal(text, _) => text.clone(),
+            Expr::Variable(id) => self.variables[*id].name.clone(),
+            Expr::Caught => String::from("exception"),
+            Expr::Class(name) => name.clone(),
+            Expr::Super => String::from("super"),
+            Expr::Uninitialized(_, class) => format!("/* uninitialized */ new {}", class),
+            // Static final fields can only be assigned with their simple name
+            Expr::Field(object, name, _) if **object == Expr::Class(self.names.class_name(self.this_class())) => name.clone(),
+            Expr::Field(object, name, _) => format!("{}.{}", operand(object, 16), name),
+            Expr::ArrayElement(array, index, _) => format!("{}[{}]", operand(array, 16), self.expression(index)),
+            Expr::ArrayLength(array) => format!("{}.length", operand(array, 16)),
+            Expr::Call(receiver, name, arguments_list, _) if name == "<init>" => {
+                let keyword = if **receiver == Expr::Super { "super" } else { "this" };
+                format!("{}({})", keyword, arguments(arguments_list))
+            },
+            Expr::Call(receiver, name, arguments_list, _) => format!("{}.{}({})", operand(receiver, 16), name, arguments(arguments_list)),
+            Expr::New(class, arguments_list) => format!("new {}({})", class, arguments(arguments_list)),
+            Expr::NewArray(element_type, lengths, dimensions) => {
+                let lengths: String = lengths.iter().map(|length| format!("[{}]", self.expression(length))).collect();
+                format!("new {}{}{}", element_type, lengths, "[]".repeat(*dimensions))
+            },
+            Expr::Binary(operator, left, right, _) => {
+                let precedence = expr.precedence();
+                format!("{} {} {}", operand(left, precedence), operator, operand(right, precedence + 1))
+            },
+            Expr::Not(operand_expr) => format!("!{}", operand(operand_expr, 14)),
+            Expr::Negate(operand_expr) => format!("-{}", operand(operand_expr, 15)),
+            Expr::Cast(type_name, operand_expr) => format!("({}) {}", type_name, operand(operand_expr, 14)),
+            Expr::InstanceOf(operand_expr, type_name, None) => format!("{} instanceof {}", operand(operand_expr, 11), type_name),
+            Expr::InstanceOf(operand_expr, type_name, Some(id)) => format!("{} instanceof {} {}", operand(operand_expr, 11), type_name, self.variables[*id].name),
+            Expr::PostIncrement(id, 1) => format!("{}++", self.variables[*id].name),
+            Expr::PostIncrement(id, _) => format!("{}--", self.variables[*id].name),
+            Expr::Compare(name, left, right) => format!("{}({}, {})", name, self.expression(left), self.expression(right)),
+            Expr::Conditional(condition, yes, no) => format!("{} ? {} : {}", operand(condition, 4), operand(yes, 4), operand(no, 3)),
+            Expr::Assign(target, value) => format!("{} = {}", self.expression(target), operand(value, 2)),
+            Expr::MethodReference(object, name, _) => format!("{}::{}", operand(object, 16), name),
+            Expr::Lambda(parameters, body, _) => format!("({}) -> {}", parameters.join(", "), self.expression(body)),
+            Expr::ArrayInitializer(element_type, elements) => format!("new {}[] {{{}}}", element_type, arguments(elements)),
+            Expr::Dynamic(name, arguments_list, _) => format!("/* invokedynamic */ {}({})", name, arguments(arguments_list)),
+        }
+    }
+
+    /// Returns an expression, assignment or increment statement without its semicolon.
+    fn simple_statement(&self, statement: &Stmt) -> String {
+        match statement {
+            Stmt::Expression(expr) => self.expression(expr),
+            Stmt::Assign(target, value) => {
+                let target_text = self.expression(target);
+                let compound = match value {
+                    Expr::Binary(operator, left, right, _) if **left == *target && !matches!(*operator, "&&" | "||" | "==" | "!=" | "<" | ">" | "<=" | ">=") => Some((operator, right)),
+                    other => None
+                };
+                match compound {
+                    Some((operator, right)) if matches!(**right, Expr::Literal(ref text, _) if text == "1") && (*operator == "+" || *operator == "-") => {
+                        format!("{}{}{}", target_text, operator, operator)
+                    },
+                    Some((operator, right)) => format!("{} {}= {}", target_text, operator, self.expression(right)),
+                    None => match value {
+                        // Booleans are ints in class files
+                        Expr::Literal(text, type_name) if type_name == "int" && self.type_of(target) == "boolean" => {
+                            format!("{} = {}", target_text, int_text(text.parse().unwrap_or(1), "boolean"))
+                        },
+                        value => format!("{} = {}", target_text, self.expression(value))
+                    }
+                }
+            },
+            Stmt::Declare(id, value) => {
+                let assignment = self.simple_statement(&Stmt::Assign(Expr::Variable(*id), value.clone()));
+                format!("{} {}", self.variables[*id].type_name, assignment)
+            },
+            Stmt::Increment(id, 1) => format!("{}++", self.variables[*id].name),
+            Stmt::Increment(id, -1) => format!("{}--", self.variables[*id].name),
+            Stmt::Increment(id, increment) if *increment < 0 => format!("{} -= {}", self.variables[*id].name, -(*increment as i64)),
+            Stmt::Increment(id, increment) => format!("{} += {}", self.variables[*id].name, increment),
+            other => panic!("{:?} isn't a simple statement", other)
+        }
+    }
+
+    fn write_statements(&self, output: &mut String, statements: &[Stmt], indent: usize) {
+        let padding = "    ".repeat(indent);
+        for statement in statements {
+            let mut line = |text: &str| writeln!(output, "{}{}", padding, text).unwrap();
+            match statement {
+                Stmt::Expression(_) | Stmt::Assign(..) | Stmt::Declare(..) | Stmt::Increment(..) => line(&format!("{};", self.simple_statement(statement))),
+                Stmt::Return(None) => line("return;"),
+                Stmt::Return(Some(value)) => line(&format!("return {};", self.expression(value))),
+                Stmt::Throw(exception) => line(&format!("throw {};", self.expression(exception))),
+                Stmt::Monitor(name, object) => line(&format!("{}({});", name, self.expression(object))),
+                Stmt::Jsr(block) => line(&format!("jsr B{};", block)),
+                Stmt::Ret(id) => line(&format!("ret {};", self.variables[*id].name)),
+                Stmt::Label(block) => writeln!(output, "{}B{}:", "    ".repeat(indent - 1), block).unwrap(),
+                Stmt::Goto(block) => line(&format!("goto B{};", block)),
+                Stmt::Break(None) => line("break;"),
+                Stmt::Break(Some(label)) => line(&format!("break {};", label)),
+                Stmt::Continue(None) => line("continue;"),
+                Stmt::Continue(Some(label)) => line(&format!("continue {};", label)),
+                Stmt::Comment(text) => line(&format!("// {}", text)),
+                Stmt::If(condition, then, otherwise) => {
+                    line(&format!("if ({}) {{", self.expression(condition)));
+                    self.write_statements(output, then, indent + 1);
+                    let mut otherwise = otherwise;
+                    // Else if chains stay flat
+                    while let [Stmt::If(condition, then, next)] = otherwise.as_slice() {
+                        writeln!(output, "{}}} else if ({}) {{", padding, self.expression(condition)).unwrap();
+                        self.write_statements(output, then, indent + 1);
+                        otherwise = next;
+                    }
+                    if !otherwise.is_empty() {
+                        writeln!(output, "{}}} else {{", padding).unwrap();
+                        self.write_statements(output, otherwise, indent + 1);
+                    }
+                    writeln!(output, "{}}}", padding).unwrap();
+                },
+                Stmt::Loop(label, kind, body) => {
+                    let label = label.as_ref().map_or(String::new(), |label| format!("{}: ", label));
+                    match kind {
+                        LoopKind::Forever => line(&format!("{}while (true) {{", label)),
+                        LoopKind::While(condition) => line(&format!("{}while ({}) {{", label, self.expression(condition))),
+                        LoopKind::DoWhile(_) => line(&format!("{}do {{", label)),
+                        LoopKind::For(initialization, condition, update) => line(&format!("{}for ({}; {}; {}) {{", label,
+                            self.simple_statement(initialization), self.expression(condition), self.simple_statement(update)))
+                    }
+                    self.write_statements(output, body, indent + 1);
+                    match kind {
+                        LoopKind::DoWhile(condition) => writeln!(output, "{}}} while ({});", padding, self.expression(condition)).unwrap(),
+                        other => writeln!(output, "{}}}", padding).unwrap()
+                    }
+                },
+                Stmt::Switch(label, value, cases) => {
+                    let label = label.as_ref().map_or(String::new(), |label| format!("{}: ", label));
+                    line(&format!("{}switch ({}) {{", label, self.expression(value)));
+                    let value_type = self.type_of(value);
+                    for (keys, body) in cases {
+                        for key in keys {
+                            match key {
+                                Some(key) => writeln!(output, "{}    case {}:", padding, int_text(*key, &value_type)).unwrap(),
+                                None => writeln!(output, "{}    default:", padding).unwrap()
+                            }
+                        }
+                        self.write_statements(output, body, indent + 2);
+                    }
+                    writeln!(output, "{}}}", padding).unwrap();
+                },
+                Stmt::Try(body, catches) => {
+                    line("try {");
+                    self.write_statements(output, body, indent + 1);
+                    for catch in catches {
+                        writeln!(output, "{}}} catch ({} {}) {{", padding, catch.types.join(" | "), self.variables[catch.variable].name).unwrap();
+                        self.write_statements(output, &catch.body, indent + 1);
+                    }
+                    writeln!(output, "{}}}", padding).unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Moves the assignments of a block into its condition, like `(n = tab.length) == 0`, when they all fit there.
+fn fold_assignments(statements: &[Stmt], condition: &Expr) -> Option<Expr> {
+    let mut condition = condition.clone();
+    for statement in statements.iter().rev() {
+        let Stmt::Assign(target @ Expr::Variable(variable), value) = statement else {
+            return None;
+        };
+        let assignment = Expr::Assign(Box::new(target.clone()), Box::new(value.clone()));
+        if substitute(&mut condition, *variable, &assignment, &mut false, false) != Some(true) {
+            return None;
+        }
+    }
+    Some(condition)
+}
+
+/// Returns whether statements continue the loop with the label, or the innermost loop when they aren't in another one.
+fn continues(statements: &[Stmt], label: Option<&str>, innermost: bool) -> bool {
+    statements.iter().any(|statement| match statement {
+        Stmt::Continue(None) => innermost,
+        Stmt::Continue(Some(target)) => Some(target.as_str()) == label,
+        Stmt::Loop(_, _, body) => continues(body, label, false),
+        other => other.bodies().iter().any(|body| continues(body, label, innermost))
+    })
+}
+
+fn exit_targets(exit: &Exit) -> Vec<usize> {
+    match exit {
+        Exit::None => Vec::new(),
+        Exit::Goto(target) => vec![*target],
+        Exit::If(_, taken, next) => vec![*taken, *next],
+        Exit::Switch(_, cases, default) => cases.iter().map(|(_, target)| *target).chain([*default]).collect()
+    }
+}
+
+fn retain_recursively(statements: &mut Vec<Stmt>, keep: &dyn Fn(&Stmt) -> bool) {
+    statements.retain(keep);
+    for statement in statements.iter_mut() {
+        for body in statement.bodies_mut() {
+            retain_recursively(body, keep);
+        }
+    }
+}
+
+fn collect_variables(statements: &[Stmt], used: &mut BTreeSet<usize>) {
+    for statement in statements {
+        collect_statement_variables(statement, used);
+        for body in statement.bodies() {
+            collect_variables(body, used);
+        }
+    }
+}
+
+/// Replaces the exception that a handler finds on the stack with its variable.
+fn replace_caught(statements: &mut [Stmt], id: usize) {
+    fn replace(expr: &mut Expr, id: usize) {
+        if *expr == Expr::Caught {
+            *expr = Expr::Variable(id);
+        }
+        for child in expr.children_mut() {
+            replace(child, id);
+        }
+    }
+    for statement in statements {
+        for expr in statement.expressions_mut() {
+            replace(expr, id);
+        }
+        for body in statement.bodies_mut() {
+            replace_caught(body, id);
+        }
+    }
+}
+
+/// Collects the variables of a statement itself, not those of the statements in its bodies.
+fn collect_statement_variables(statement: &Stmt, used: &mut BTreeSet<usize>) {
+    fn collect_expression(expr: &Expr, used: &mut BTreeSet<usize>) {
+        if let Expr::Variable(id) | Expr::PostIncrement(id, _) | Expr::InstanceOf(_, _, Some(id)) = expr {
+            used.insert(*id);
+        }
+        for child in expr.children() {
+            collect_expression(child, used);
+        }
+    }
+    for expr in statement.expressions() {
+        collect_expression(expr, used);
+    }
+    if let Stmt::Increment(id, _) | Stmt::Ret(id) | Stmt::Declare(id, _) = statement {
+        used.insert(*id);
+    }
+}
+
+/// Declares variables where they are first assigned, when that assignment comes before their other uses in the
+/// statements that hold all of them, and in the initialization of a for loop that is the only one to use them. The
+/// variables are declared for all of their uses, `declared` gets the ones that were.
+fn declare_variables(statements: &mut [Stmt], variables: &BTreeSet<usize>, declared: &mut BTreeSet<usize>) {
+    // The variables of each statement itself, and of each of its bodies
+    let mut uses = Vec::new();
+    for statement in statements.iter() {
+        let mut own = BTreeSet::new();
+        collect_statement_variables(statement, &mut own);
+        let bodies: Vec<BTreeSet<usize>> = statement.bodies().into_iter().map(|body| {
+            let mut used = BTreeSet::new();
+            collect_variables(body, &mut used);
+            used
+        }).collect();
+        uses.push((own, bodies));
+    }
+    let uses_variable = |index: usize, id: usize| uses[index].0.contains(&id) || uses[index].1.iter().any(|body| body.contains(&id));
+    let mut inner = vec![BTreeSet::new(); statements.len()];
+    for &id in variables {
+        let users: Vec<usize> = (0..statements.len()).filter(|index| uses_variable(*index, id)).collect();
+        let Some(&first) = users.first() else {
+            continue;
+        };
+        match &mut statements[first] {
+            Stmt::Assign(Expr::Variable(target), value) if *target == id && !value.reads(id) => {
+                statements[first] = Stmt::Declare(id, value.clone());
+                declared.insert(id);
+            },
+            Stmt::Loop(_, LoopKind::For(initialization, ..), _) if users.len() == 1
+                && matches!(&**initialization, Stmt::Assign(Expr::Variable(target), value) if *target == id && !value.reads(id)) => {
+                let Stmt::Assign(_, value) = &**initialization else {
+                    panic!("The initialization changed");
+                };
+                **initialization = Stmt::Declare(id, value.clone());
+                declared.insert(id);
+            },
+            // Used in a single body of a single statement, it can be declared there
+            other if users.len() == 1 && !uses[first].0.contains(&id) && uses[first].1.iter().filter(|body| body.contains(&id)).count() == 1 => {
+                inner[first].insert(id);
+            },
+            other => {}
+        }
+    }
+    for (index, statement) in statements.iter_mut().enumerate() {
+        for (body, used) in statement.bodies_mut().into_iter().zip(&uses[index].1) {
+            let body_variables = inner[index].intersection(used).copied().collect();
+            declare_variables(body, &body_variables, declared);
+        }
+    }
+}
+
+/// Counts how many times each variable is stored and read.
+fn count_variables(statements: &[Stmt], counts: &mut HashMap<usize, (usize, usize)>) {
+    fn count_reads(expr: &Expr, counts: &mut HashMap<usize, (usize, usize)>) {
+        match expr {
+            Expr::Variable(id) => counts.entry(*id).or_default().1 += 1,
+            Expr::PostIncrement(id, _) | Expr::InstanceOf(_, _, Some(id)) => {
+                let count = counts.entry(*id).or_default();
+                count.0 += 1;
+                count.1 += 1;
+            },
+            other => {}
+        }
+        for child in expr.children() {
+            count_reads(child, counts);
+        }
+    }
+    for statement in statements {
+        match statement {
+            Stmt::Assign(Expr::Variable(id), value) => {
+                counts.entry(*id).or_default().0 += 1;
+                count_reads(value, counts);
+            },
+            Stmt::Increment(id, _) => {
+                let count = counts.entry(*id).or_default();
+                count.0 += 1;
+                count.1 += 1;
+            },
+            other => {
+                for expr in other.expressions() {
+                    count_reads(expr, counts);
+                }
+            }
+        }
+        for body in statement.bodies() {
+            count_variables(body, counts);
+        }
+    }
+}
+
+/// Replaces the read of a variable in the expressions that a statement evaluates first, when moving the value there
+/// doesn't change the order of side effects. Returns whether it was replaced.
+fn substitute_in_statement(statement: &mut Stmt, variable: usize, value: &Expr) -> bool {
+    let mut blocked = false;
+    let expressions: Vec<&mut Expr> = match statement {
+        Stmt::Expression(expr) | Stmt::Return(Some(expr)) | Stmt::Throw(expr) | Stmt::Monitor(_, expr) | Stmt::If(expr, ..)
+            | Stmt::Switch(_, expr, _) => vec![expr],
+        Stmt::Assign(Expr::Variable(_), value) => vec![value],
+        Stmt::Assign(target, value) => target.children_mut().into_iter().chain([value]).collect(),
+        other => return false
+    };
+    for expr in expressions {
+        if let Some(replaced) = substitute(expr, variable, value, &mut blocked, false) {
+            return replaced;
+        }
+    }
+    false
+}
+
+/// Looks for the read of a variable in the order of evaluation. Returns None when it isn't there, or whether it
+/// could be replaced: an impure value can't move past other impure expressions or into code that might not run.
+fn substitute(expr: &mut Expr, variable: usize, value: &Expr, blocked: &mut bool, conditional: bool) -> Option<bool> {
+    if *expr == Expr::Variable(variable) {
+        if !value.is_pure() && (*blocked || conditional) {
+            return Some(false);
+        }
+        *expr = value.clone();
+        return Some(true);
+    }
+    let short_circuit = matches!(expr, Expr::Binary("&&" | "||", ..) | Expr::Conditional(..));
+    // The body of a lambda runs later
+    let deferred = matches!(expr, Expr::Lambda(..));
+    for (index, child) in expr.children_mut().into_iter().enumerate() {
+        if let Some(replaced) = substitute(child, variable, value, blocked, conditional || deferred || short_circuit && index > 0) {
+            return Some(replaced);
+        }
+        if !child.is_pure() {
+            *blocked = true;
+        }
+    }
+    None
+}
@@ -0,0 +1,286 @@
+use std::{collections::{BTreeMap, BTreeSet}, fmt::Write, path::Path};
+
+use crate::{class_loader::{AttributeInfo, ClassInfo, ConstantPoolItem}, class_path::{ClassPath, ClassPathEntry},
+    control_flow::escape, descriptor_parser::class_names};
+
+/// What a class on the classpath refers to in other classes.
+pub struct ClassDependencies {
+    pub module: String,
+    // The members of each class it refers to, like `field out:Ljava/io/PrintStream;` and `method println(I)V`
+    pub references: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// The dependencies of the classes on a classpath, like jdeps gives them. They come from the classes, fields and
+/// methods in the constant pool, and from the classes in the descriptors of fields, methods and invokedynamic call
+/// sites. A classpath entry with a module-info.class is the module it declares, other entries are an unnamed module
+/// named after their file. The classes outside of the classpath are in the module of the library they are found in.
+pub struct Dependencies {
+    pub classes: BTreeMap<String, ClassDependencies>,
+    // The module of every class on the classpath or referred to, or None for classes that weren't found
+    pub modules: BTreeMap<String, Option<String>>,
+}
+
+impl Dependencies {
+    /// Collects the dependencies of the classes from each classpath entry, given by its path.
+    pub fn build(entries: &[(String, Vec<ClassInfo>)], library: &ClassPath) -> Dependencies {
+        let mut classes = BTreeMap::new();
+        let mut modules = BTreeMap::new();
+        for (path, class_infos) in entries {
+            let module = class_infos.iter().find_map(module_name).unwrap_or_else(|| file_name(path));
+            for class_info in class_infos {
+                let name = class_name(class_info);
+                if name == "module-info" {
+                    continue;
+                }
+                modules.insert(name.to_string(), Some(module.clone()));
+                classes.insert(name.to_string(), ClassDependencies { module: module.clone(), references: references(class_info) });
+            }
+        }
+        let referenced: BTreeSet<String> = classes.values().flat_map(|class| class.references.keys().cloned()).collect();
+        for class_name in referenced {
+            modules.entry(class_name).or_insert_with_key(|class_name| library_module(library, class_name));
+        }
+        Dependencies { classes, modules }
+    }
+
+    /// Writes the dependencies as text: for each package the packages it depends on, followed by the classes in it
+    /// with the classes, fields and methods they refer to. With modules, the packages are grouped by module and each
+    /// module starts with the modules it depends on.
+    pub fn to_text(&self, with_modules: bool) -> String {
+        let mut output = String::new();
+        if !with_modules {
+            for package in self.packages(None) {
+                self.write_package(&mut output, None, package, "");
+            }
+            return output;
+        }
+        let modules: BTreeSet<&str> = self.classes.values().map(|class| class.module.as_str()).collect();
+        for module in modules {
+            writeln!(output, "module {}", module).unwrap();
+            let required: BTreeSet<&str> = self.classes.values()
+                .filter(|class| class.module == module)
+                .flat_map(|class| class.references.keys())
+                .filter_map(|class_name| self.modules[class_name].as_deref())
+                .filter(|required| *required != module)
+                .collect();
+            for required in required {
+                writeln!(output, "   -> {}", required).unwrap();
+            }
+            for package in self.packages(Some(module)) {
+                self.write_package(&mut output, Some(module), package, "   ");
+            }
+        }
+        output
+    }
+
+    /// Writes the dependencies between packages in the DOT format of Graphviz. Packages from the library are gray,
+    /// and packages of classes that weren't found are red. With modules, the packages are clustered by module.
+    pub fn to_dot(&self, with_modules: bool) -> String {
+        let package_modules = self.package_modules();
+        let numbers: BTreeMap<&str, usize> = package_modules.keys().enumerate().map(|(number, package)| (*package, number)).collect();
+        let classpath_packages = self.packages(None);
+        let node = |package: &str| {
+            let attributes = match package_modules[package] {
+                None => ", color=red, fontcolor=red",
+                Some(other) if !classpath_packages.contains(package) => ", color=gray, fontcolor=gray",
+                Some(other) => ""
+            };
+            format!("P{} [label=\"{}\"{}];", numbers[package], escape(&package_name(package)), attributes)
+        };
+        let mut output = String::from("digraph dependencies {\n    node [shape=box, fontname=\"monospace\"];\n");
+        if with_modules {
+            let mut clusters: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+            for (package, module) in &package_modules {
+                match module {
+                    Some(module) => clusters.entry(module).or_default().push(package),
+                    None => writeln!(output, "    {}", node(package)).unwrap()
+                }
+            }
+            for (cluster_number, (module, packages)) in clusters.iter().enumerate() {
+                writeln!(output, "    subgraph cluster_{} {{", cluster_number).unwrap();
+                writeln!(output, "        label=\"{}\";", escape(module)).unwrap();
+                for package in packages {
+                    writeln!(output, "        {}", node(package)).unwrap();
+                }
+                output += "    }\n";
+            }
+        } else {
+            for package in package_modules.keys() {
+                writeln!(output, "    {}", node(package)).unwrap();
+            }
+        }
+        for (package, dependencies) in self.package_dependencies(None) {
+            for dependency in dependencies {
+                writeln!(output, "    P{} -> P{};", numbers[package], numbers[dependency]).unwrap();
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    /// Returns the packages with classes on the classpath, only those of a module if one is given.
+    fn packages(&self, module: Option<&str>) -> BTreeSet<&str> {
+        self.classes.iter()
+            .filter(|(_, class)| module.is_none_or(|module| class.module == module))
+            .map(|(class_name, _)| package(class_name))
+            .collect()
+    }
+
+    /// Returns the other packages that the packages on the classpath depend on, only for the classes of a module if
+    /// one is given.
+    fn package_dependencies(&self, module: Option<&str>) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut dependencies: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (class_name, class) in &self.classes {
+            if module.is_some_and(|module| class.module != module) {
+                continue;
+            }
+            let from = package(class_name);
+            let to = dependencies.entry(from).or_default();
+            to.extend(class.references.keys().map(|referenced| package(referenced)).filter(|to| *to != from));
+        }
+        dependencies
+    }
+
+    /// Returns the module of every package, which is None when none of its classes were found.
+    fn package_modules(&self) -> BTreeMap<&str, Option<&str>> {
+        let mut package_modules: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+        for (class_name, module) in &self.modules {
+            let package_module = package_modules.entry(package(class_name)).or_default();
+            if package_module.is_none() {
+                *package_module = module.as_deref();
+            }
+        }
+        package_modules
+    }
+
+    /// Writes a package with the packages it depends on, followed by its classes with what they refer to.
+    fn write_package(&self, output: &mut String, module: Option<&str>, name: &str, indent: &str) {
+        let package_modules = self.package_modules();
+        // Other modules are only shown when the packages are grouped by module
+        let annotation = |package: &str| match (package_modules[package], module) {
+            (None, _) => " (not found)".to_string(),
+            (Some(other_module), Some(module)) if other_module != module => format!(" ({})", other_module),
+            other => String::new()
+        };
+        writeln!(output, "{}package {}", indent, package_name(name)).unwrap();
+        for dependency in self.package_dependencies(module).remove(name).unwrap_or_default() {
+            writeln!(output, "{}   -> {}{}", indent, package_name(dependency), annotation(dependency)).unwrap();
+        }
+        let classes = self.classes.iter().filter(|(class_name, class)| {
+            package(class_name) == name && module.is_none_or(|module| class.module == module)
+        });
+        for (class_name, class) in classes {
+            writeln!(output, "{}   {}", indent, java_name(class_name)).unwrap();
+            for (referenced, members) in &class.references {
+                let not_found = if self.modules[referenced].is_none() { " (not found)" } else { "" };
+                writeln!(output, "{}      -> {}{}", indent, java_name(referenced), not_found).unwrap();
+                for member in members {
+                    writeln!(output, "{}         {}", indent, member).unwrap();
+                }
+            }
+        }
+        output.push('\n');
+    }
+}
+
+/// Returns the classes, fields and methods that a class refers to, by the classes they are in.
+fn references(class_info: &ClassInfo) -> BTreeMap<String, BTreeSet<String>> {
+    let constant_pool = &class_info.constant_pool;
+    let mut references: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut add_classes = |references: &mut BTreeMap<String, BTreeSet<String>>, descriptor: &str| {
+        for name in class_names(descriptor) {
+            references.entry(name.to_string()).or_default();
+        }
+    };
+    for field in &class_info.fields {
+        add_classes(&mut references, constant_pool.get_string(field.descriptor_index));
+    }
+    for method in &class_info.methods {
+        add_classes(&mut references, constant_pool.get_string(method.descriptor_index));
+    }
+    // The first entry is a placeholder
+    for item in &constant_pool.constants[1..] {
+        match item {
+            ConstantPoolItem::CPClassInfo { name_index } => {
+                let name = constant_pool.get_string(*name_index);
+                // Array classes like [Ljava/lang/String; depend on their element class
+                if name.starts_with('[') {
+                    add_classes(&mut references, name);
+                } else {
+                    references.entry(name.to_string()).or_default();
+                }
+            },
+            ConstantPoolItem::CPFieldRef { class_index, name_and_type_index }
+            | ConstantPoolItem::CPMethodRef { class_index, name_and_type_index }
+            | ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } => {
+                let owner = constant_pool.get_string(constant_pool.get_class_info(*class_index));
+                let (name_index, descriptor_index) = constant_pool.get_name_and_type(*name_and_type_index);
+                let (name, descriptor) = (constant_pool.get_string(name_index), constant_pool.get_string(descriptor_index));
+                // Methods of arrays, like clone, belong to no class
+                if !owner.starts_with('[') {
+                    let member = match item {
+                        ConstantPoolItem::CPFieldRef { .. } => format!("field {}:{}", name, descriptor),
+                        other => format!("method {}{}", name, descriptor)
+                    };
+                    references.entry(owner.to_string()).or_default().insert(member);
+                }
+            },
+            // These cover the descriptors of member references and invokedynamic call sites
+            ConstantPoolItem::CPNameAndTypeInfo { descriptor_index, .. } | ConstantPoolItem::CPMethodTypeInfo { descriptor_index } => {
+                add_classes(&mut references, constant_pool.get_string(*descriptor_index));
+            },
+            other => {}
+        }
+    }
+    references.remove(class_name(class_info));
+    references
+}
+
+/// Returns the module that a module-info.class declares.
+fn module_name(class_info: &ClassInfo) -> Option<String> {
+    let constant_pool = &class_info.constant_pool;
+    if class_name(class_info) != "module-info" {
+        return None;
+    }
+    class_info.attributes.iter().find_map(|attribute| match attribute {
+        AttributeInfo::Unknown(unknown) if constant_pool.get_string(unknown.name_index) == "Module" && unknown.info.len() >= 2 => {
+            match &constant_pool.constants[u16::from_be_bytes([unknown.info[0], unknown.info[1]]) as usize] {
+                ConstantPoolItem::CPModuleInfo { name_index } => Some(constant_pool.get_string(*name_index).to_string()),
+                other => None
+            }
+        },
+        other => None
+    })
+}
+
+/// Returns the module of a class in the library: the module it is in for a jimage, and otherwise the file name of
+/// the entry it was found in. Returns None when the library doesn't have it.
+fn library_module(library: &ClassPath, class_name: &str) -> Option<String> {
+    let entry = library.entries.iter().find(|entry| entry.read_class(class_name).is_some())?;
+    match entry {
+        ClassPathEntry::JImage(jimage) => Some(jimage.package_to_module(package(class_name)).unwrap_or_else(|| file_name(&jimage.path))),
+        other => Some(file_name(other.path()))
+    }
+}
+
+fn class_name(class_info: &ClassInfo) -> &str {
+    let constant_pool = &class_info.constant_pool;
+    constant_pool.get_string(constant_pool.get_class_info(class_info.this_class))
+}
+
+/// Returns the package of a class by its internal name, like `java/lang` for `java/lang/String`.
+fn package(class_name: &str) -> &str {
+    class_name.rfind('/').map_or("", |index| &class_name[..index])
+}
+
+fn package_name(package: &str) -> String {
+    if package.is_empty() { "<unnamed>".to_string() } else { java_name(package) }
+}
+
+fn java_name(internal_name: &str) -> String {
+    internal_name.replace('/', ".")
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name().map_or(path.to_string(), |name| name.to_string_lossy().to_string())
+}
@@ -20,9 +20,9 @@ fn get_types(descriptor: &str) -> Vec<String> {
         //println!("{}",c);
         match c {
             'L' => {
-                let location = (&descriptor[i..]).find(';').unwrap();
+                let location = descriptor[i..].find(';').unwrap();
                 let class = &descriptor[(i+1)..(i+location)];
-                let token = format!("L{}{};", class, std::iter::repeat("[").take(array_count).collect::<String>());
+                let token = format!("L{}{};", class, "[".repeat(array_count));
                 args.push(token);
                 array_count = 0;
                 i += class.len() + 2;
@@ -32,7 +32,7 @@ fn get_types(descriptor: &str) -> Vec<String> {
                 i += 1;
             },
             _ => {
-                let token = format!("{}{}", c, std::iter::repeat("[").take(array_count).collect::<String>());
+                let token = format!("{}{}", c, "[".repeat(array_count));
                 args.push(token);
                 array_count = 0;
                 i += 1;
@@ -46,7 +46,44 @@ fn get_types(descriptor: &str) -> Vec<String> {
 pub fn parse_descriptor(descriptor: String) -> Descriptor {
     let (args_part, return_part) = split_descriptor(&descriptor);
     let args = get_types(args_part);
-    let return_type = String::from(get_types(return_part).get(0).unwrap());
+    let return_type = String::from(get_types(return_part).first().unwrap());
     //println!("{} {} {}", descriptor, args_part, return_part);
-    Descriptor{args: args, return_type: return_type}
-}
\ No newline at end of file
+    Descriptor{args, return_type}
+}
+/// Splits the arguments of a method descriptor into field descriptors,
+/// like `I`, `Ljava/lang/String;` and `[J` for `(ILjava/lang/String;[J)V`.
+pub fn argument_descriptors(descriptor: &str) -> Vec<&str> {
+    let (args_part, _) = split_descriptor(descriptor);
+    let mut args = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < args_part.len() {
+        match args_part.as_bytes()[i] {
+            b'[' => {
+                i += 1;
+                continue;
+            },
+            b'L' => i += args_part[i..].find(';').unwrap() + 1,
+            _ => i += 1
+        }
+        args.push(&args_part[start..i]);
+        start = i;
+    }
+    args
+}
+
+/// Returns the classes that a field or method descriptor mentions, like `java/lang/String` and `java/util/List`
+/// for `([Ljava/lang/String;I)Ljava/util/List;`.
+pub fn class_names(descriptor: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut i = 0;
+    while i < descriptor.len() {
+        if descriptor.as_bytes()[i] == b'L' {
+            let end = i + descriptor[i..].find(';').unwrap();
+            names.push(&descriptor[(i+1)..end]);
+            i = end;
+        }
+        i += 1;
+    }
+    names
+}
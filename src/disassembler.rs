@@ -0,0 +1,650 @@
+use std::{collections::{BTreeSet, HashMap}, fmt::Write};
+
+use crate::{class_loader::{ATCode, ATUnknown, AttributeInfo, ClassInfo, ClassLoader, ConstantPool, ConstantPoolItem, FieldInfo, MethodInfo}, opcodes::*};
+
+// The access flags by their keyword in the assembly format. Some bits mean different things for classes, fields and methods
+pub const CLASS_FLAGS: [(&str, u16); 9] = [
+    ("public", 0x0001), ("final", 0x0010), ("super", 0x0020), ("interface", 0x0200), ("abstract", 0x0400),
+    ("synthetic", 0x1000), ("annotation", 0x2000), ("enum", 0x4000), ("module", 0x8000),
+];
+pub const FIELD_FLAGS: [(&str, u16); 9] = [
+    ("public", 0x0001), ("private", 0x0002), ("protected", 0x0004), ("static", 0x0008), ("final", 0x0010),
+    ("volatile", 0x0040), ("transient", 0x0080), ("synthetic", 0x1000), ("enum", 0x4000),
+];
+pub const METHOD_FLAGS: [(&str, u16); 12] = [
+    ("public", 0x0001), ("private", 0x0002), ("protected", 0x0004), ("static", 0x0008), ("final", 0x0010),
+    ("synchronized", 0x0020), ("bridge", 0x0040), ("varargs", 0x0080), ("native", 0x0100), ("abstract", 0x0400),
+    ("strict", 0x0800), ("synthetic", 0x1000),
+];
+pub const INNER_CLASS_FLAGS: [(&str, u16); 10] = [
+    ("public", 0x0001), ("private", 0x0002), ("protected", 0x0004), ("static", 0x0008), ("final", 0x0010),
+    ("interface", 0x0200), ("abstract", 0x0400), ("synthetic", 0x1000), ("annotation", 0x2000), ("enum", 0x4000),
+];
+
+// The kinds of method handles by their reference_kind, named after the instruction they behave like
+pub const REFERENCE_KINDS: [&str; 10] = [
+    "", "getfield", "getstatic", "putfield", "putstatic", "invokevirtual", "invokestatic", "invokespecial",
+    "newinvokespecial", "invokeinterface",
+];
+
+/// Returns the name of a newarray type code, like int for T_INT.
+pub fn array_type_name(type_code: u8) -> Option<&'static str> {
+    Some(match type_code {
+        T_BOOLEAN => "boolean",
+        T_CHAR => "char",
+        T_FLOAT => "float",
+        T_DOUBLE => "double",
+        T_BYTE => "byte",
+        T_SHORT => "short",
+        T_INT => "int",
+        T_LONG => "long",
+        other => return None
+    })
+}
+
+/// Writes a class in a text format based on the one of Jasmin, which `JasminParser` reads back.
+/// Branch targets get labels numbered in the order of their pcs. The StackMapTable isn't written, as the assembler
+/// computes it. Of the attributes that the class loader keeps as bytes, the ones made of class and string references
+/// like InnerClasses or Signature become directives, the others like annotations get a comment instead.
+pub struct Disassembler<'a> {
+    class_info: &'a ClassInfo,
+    constant_pool: &'a ConstantPool,
+    output: String,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn disassemble(class_info: &ClassInfo) -> String {
+        let mut disassembler = Disassembler { class_info, constant_pool: &class_info.constant_pool, output: String::new() };
+        disassembler.write_class();
+        disassembler.output
+    }
+
+    /// Returns the text of the instruction at a pc, using `label` for the names of the pcs it branches to.
+    pub fn instruction_text(class_info: &ClassInfo, code: &[u8], pc: usize, label: &mut impl FnMut(usize) -> String) -> String {
+        let disassembler = Disassembler { class_info, constant_pool: &class_info.constant_pool, output: String::new() };
+        disassembler.instruction(code, pc, label).trim_start().to_string()
+    }
+
+    fn line(&mut self, line: &str) {
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn class_name(&self, index: u16) -> &'a str {
+        self.constant_pool.get_string(self.constant_pool.get_class_info(index))
+    }
+
+    /// Writes the keywords of the flags that are set, and the bits that have no keyword as a number.
+    fn flags(keywords: &[(&str, u16)], bits: u16) -> String {
+        let mut words = Vec::new();
+        let mut left = bits;
+        for (keyword, flag) in keywords {
+            if bits & flag != 0 {
+                words.push(keyword.to_string());
+                left &= !flag;
+            }
+        }
+        if left != 0 {
+            words.push(format!("{:#06x}", left));
+        }
+        words.iter().map(|word| format!("{} ", word)).collect()
+    }
+
+    /// Writes an attribute that the class loader keeps as bytes as directives, or as its bytes when it has no directive.
+    fn unknown_attribute(&mut self, indent: &str, attribute: &AttributeInfo) {
+        if let AttributeInfo::Unknown(unknown) = attribute {
+            let lines = self.attribute_directives(attribute).unwrap_or_else(|| vec![self.raw_attribute(".attribute", unknown)]);
+            for line in lines {
+                self.line(&format!("{}{}", indent, line));
+            }
+        }
+    }
+
+    fn attribute_directives(&self, attribute: &AttributeInfo) -> Option<Vec<String>> {
+        let AttributeInfo::Unknown(unknown) = attribute else {
+            return None;
+        };
+        let info = &unknown.info;
+        let read_u16 = |at: usize| u16::from_be_bytes([info[at], info[at + 1]]);
+        let count = if info.len() >= 2 { read_u16(0) as usize } else { 0 };
+        let name = self.constant_pool.get_string(unknown.name_index);
+        Some(match name {
+            "Signature" if info.len() == 2 => vec![format!(".signature {}", quote(&self.utf16(read_u16(0))))],
+            "NestHost" if info.len() == 2 => vec![format!(".nesthost {}", self.class_name(read_u16(0)))],
+            "NestMembers" | "PermittedSubclasses" if info.len() == 2 + 2 * count => {
+                let directive = if name == "NestMembers" { ".nestmember" } else { ".permittedsubclass" };
+                (0..count).map(|entry| format!("{} {}", directive, self.class_name(read_u16(2 + 2 * entry)))).collect()
+            },
+            "InnerClasses" if info.len() == 2 + 8 * count => (0..count).map(|entry| {
+                let at = 2 + 8 * entry;
+                let flags = Self::flags(&INNER_CLASS_FLAGS, read_u16(at + 6));
+                let mut line = format!(".inner {}{}", flags, self.class_name(read_u16(at)));
+                if read_u16(at + 2) != 0 {
+                    line += &format!(" outer {}", self.class_name(read_u16(at + 2)));
+                }
+                if read_u16(at + 4) != 0 {
+                    line += &format!(" name {}", self.constant_pool.get_string(read_u16(at + 4)));
+                }
+                line
+            }).collect(),
+            "EnclosingMethod" if info.len() == 4 => {
+                let mut line = format!(".enclosing {}", self.class_name(read_u16(0)));
+                if read_u16(2) != 0 {
+                    let (name, descriptor) = self.name_and_type(read_u16(2));
+                    line += &format!(" {}{}", name, descriptor);
+                }
+                vec![line]
+            },
+            "Deprecated" | "Synthetic" if info.is_empty() => vec![format!(".{}", name.to_lowercase())],
+            other => return None
+        })
+    }
+
+    /// Writes an attribute as its bytes in hex, with the constants it refers to in braces, so the assembler can add them
+    /// to its own constant pool. The constants can only be found in the attributes that `constant_references` knows,
+    /// the others keep their bytes as they are.
+    fn raw_attribute(&self, directive: &str, unknown: &ATUnknown) -> String {
+        let name = self.constant_pool.get_string(unknown.name_index);
+        let info = &unknown.info;
+        let mut line = format!("{} {}", directive, quote_if_needed(name));
+        let mut hex = String::new();
+        let mut at = 0;
+        for reference in constant_references(self.constant_pool, name, info).unwrap_or_default() {
+            let index = u16::from_be_bytes([info[reference], info[reference + 1]]);
+            let constant = match self.constant_pool.constants.get(index as usize) {
+                Some(ConstantPoolItem::CPUTF8Info { .. }) => format!("{{utf8 {}}}", quote(&self.utf16(index))),
+                Some(ConstantPoolItem::CPIntegerInfo { .. } | ConstantPoolItem::CPLongInfo { .. } | ConstantPoolItem::CPFloatInfo { .. }
+                    | ConstantPoolItem::CPDoubleInfo { .. } | ConstantPoolItem::CPClassInfo { .. }) => format!("{{{}}}", self.constant(index)),
+                other => continue
+            };
+            info[at..reference].iter().for_each(|byte| write!(hex, "{:02x}", byte).unwrap());
+            if !hex.is_empty() {
+                line += &format!(" {}", std::mem::take(&mut hex));
+            }
+            line += &format!(" {}", constant);
+            at = reference + 2;
+        }
+        info[at..].iter().for_each(|byte| write!(hex, "{:02x}", byte).unwrap());
+        if !hex.is_empty() {
+            line += &format!(" {}", hex);
+        }
+        line
+    }
+
+    fn write_class(&mut self) {
+        let class_info = self.class_info;
+        self.line(&format!(".bytecode {}.{}", class_info.major_version, class_info.minor_version));
+        for attribute in &class_info.attributes {
+            if let AttributeInfo::SourceFile(source_file) = attribute {
+                let source_file = quote_if_needed(self.constant_pool.get_string(source_file.source_file_index));
+                self.line(&format!(".source {}", source_file));
+            }
+        }
+        let flags = Self::flags(&CLASS_FLAGS, class_info.access_flags.bits());
+        self.line(&format!(".class {}{}", flags, self.class_name(class_info.this_class)));
+        if class_info.super_class != 0 {
+            self.line(&format!(".super {}", self.class_name(class_info.super_class)));
+        }
+        for interface in &class_info.interfaces {
+            self.line(&format!(".implements {}", self.class_name(*interface)));
+        }
+        for attribute in &class_info.attributes {
+            self.unknown_attribute("", attribute);
+        }
+        for field in &class_info.fields {
+            self.line("");
+            self.write_field(field);
+        }
+        for method in &class_info.methods {
+            self.line("");
+            self.write_method(method);
+        }
+    }
+
+    fn write_field(&mut self, field: &FieldInfo) {
+        let flags = Self::flags(&FIELD_FLAGS, field.access_flags.bits());
+        let mut line = format!(".field {}{} {}", flags, field.get_name(self.constant_pool), self.constant_pool.get_string(field.descriptor_index));
+        if let Some(value) = field.get_constant_value() {
+            line += &format!(" = {}", self.constant(value));
+        }
+        self.line(&line);
+        for attribute in &field.attributes {
+            self.unknown_attribute("    ", attribute);
+        }
+        // Directives of a field go in a block
+        if field.attributes.iter().any(|attribute| matches!(attribute, AttributeInfo::Unknown(_))) {
+            self.line(".end field");
+        }
+    }
+
+    fn write_method(&mut self, method: &MethodInfo) {
+        let flags = Self::flags(&METHOD_FLAGS, method.access_flags.bits());
+        let name = method.get_name(self.constant_pool);
+        self.line(&format!(".method {}{}{}", flags, name, self.constant_pool.get_string(method.descriptor_index)));
+        for attribute in &method.attributes {
+            match attribute {
+                AttributeInfo::Exceptions(exceptions) => {
+                    for exception in &exceptions.exception_index_table {
+                        self.line(&format!("    .throws {}", self.class_name(*exception)));
+                    }
+                },
+                AttributeInfo::Code(code) => self.write_code(code),
+                other => self.unknown_attribute("    ", other)
+            }
+        }
+        self.line(".end method");
+    }
+
+    fn write_code(&mut self, code: &ATCode) {
+        self.line(&format!("    .limit stack {}", code.max_stack));
+        self.line(&format!("    .limit locals {}", code.max_locals));
+        // The pcs where an instruction starts, and the end of the code
+        let mut pcs = BTreeSet::new();
+        let mut pc = 0;
+        while pc < code.code.len() {
+            pcs.insert(pc);
+            match instruction_length(&code.code, pc) {
+                Some(length) => pc += length,
+                None => {
+                    self.line(&format!("    ; bad instruction {:#04x} at pc {}, the code can't be disassembled", code.code[pc], pc));
+                    return;
+                }
+            }
+        }
+        pcs.insert(code.code.len());
+        // Labels are numbered in the order of their pcs, so they stay the same when the assembler picks other encodings
+        let mut targets = BTreeSet::new();
+        for entry in &code.exceptions {
+            targets.extend([entry.start_pc as usize, entry.end_pc as usize, entry.handler_pc as usize]);
+        }
+        let mut line_numbers = Vec::new();
+        for attribute in &code.attributes {
+            match attribute {
+                AttributeInfo::LineNumberTable(table) => line_numbers.extend(table.entries.iter().map(|entry| (entry.start_pc as usize, entry.line_number))),
+                AttributeInfo::LocalVariableTable(table) => {
+                    let ranges = table.entries.iter().map(|entry| [entry.start_pc as usize, entry.start_pc as usize + entry.length as usize]);
+                    targets.extend(ranges.filter(|range| range.iter().all(|pc| pcs.contains(pc))).flatten());
+                },
+                other => {}
+            }
+        }
+        for pc in pcs.iter().copied().filter(|pc| *pc < code.code.len()) {
+            self.instruction(&code.code, pc, &mut |target| {
+                targets.insert(target);
+                String::new()
+            });
+        }
+        let names: HashMap<usize, String> = targets.iter().enumerate().map(|(number, pc)| (*pc, format!("L{}", number))).collect();
+        let mut label = |pc: usize| names[&pc].clone();
+
+        for entry in &code.exceptions {
+            let catch_type = if entry.catch_type == 0 { "all" } else { self.class_name(entry.catch_type) };
+            let (start, end, handler) = (label(entry.start_pc as usize), label(entry.end_pc as usize), label(entry.handler_pc as usize));
+            self.line(&format!("    .catch {} from {} to {} using {}", catch_type, start, end, handler));
+        }
+        for attribute in &code.attributes {
+            match attribute {
+                AttributeInfo::LineNumberTable(_) | AttributeInfo::StackMapTable(_) => {},
+                AttributeInfo::LocalVariableTable(table) => {
+                    for entry in &table.entries {
+                        let (start, end) = (entry.start_pc as usize, entry.start_pc as usize + entry.length as usize);
+                        let name = self.constant_pool.get_string(entry.name_index);
+                        let descriptor = self.constant_pool.get_string(entry.descriptor_index);
+                        if pcs.contains(&start) && pcs.contains(&end) {
+                            self.line(&format!("    .var {} is {} {} from {} to {}", entry.index, name, descriptor, label(start), label(end)));
+                        } else {
+                            self.line(&format!("    ; skipped local variable {} that isn't at the start of an instruction", name));
+                        }
+                    }
+                },
+                AttributeInfo::Unknown(unknown) => {
+                    let line = self.raw_attribute(".codeattribute", unknown);
+                    self.line(&format!("    {}", line));
+                },
+                other => {}
+            }
+        }
+        for pc in pcs.iter().copied() {
+            if let Some(name) = names.get(&pc) {
+                self.line(&format!("{}:", name));
+            }
+            if pc == code.code.len() {
+                break;
+            }
+            for (_, line_number) in line_numbers.iter().filter(|(start, _)| *start == pc) {
+                self.line(&format!("    .line {}", line_number));
+            }
+            let instruction = self.instruction(&code.code, pc, &mut label);
+            self.line(&instruction);
+        }
+    }
+
+    /// Returns the line of the instruction at a pc, using `label` for the names of the pcs it branches to.
+    fn instruction(&self, code: &[u8], pc: usize, label: &mut impl FnMut(usize) -> String) -> String {
+        let read_u16 = |at: usize| u16::from_be_bytes([code[at], code[at + 1]]);
+        let read_i32 = |at: usize| i32::from_be_bytes(code[at..at + 4].try_into().unwrap());
+        let mut branch = |offset: i32| label((pc as i64 + offset as i64) as usize);
+        let opcode = code[pc];
+        let name = opcode_name(opcode);
+        let operands = match opcode {
+            BIPUSH => (code[pc + 1] as i8).to_string(),
+            SIPUSH => (read_u16(pc + 1) as i16).to_string(),
+            NEWARRAY => array_type_name(code[pc + 1]).map_or_else(|| code[pc + 1].to_string(), String::from),
+            LDC => self.constant(code[pc + 1] as u16),
+            // The assembler uses ldc_w by itself for constants above 255
+            LDC_W => return format!("    ldc {}", self.constant(read_u16(pc + 1))),
+            LDC2_W => self.constant(read_u16(pc + 1)),
+            ILOAD..=ALOAD | ISTORE..=ASTORE | RET => code[pc + 1].to_string(),
+            IINC => format!("{} {}", code[pc + 1], code[pc + 2] as i8),
+            // The assembler uses wide by itself for locals above 255 and increments that don't fit in a byte
+            WIDE if code[pc + 1] == IINC => return format!("    iinc {} {}", read_u16(pc + 2), read_u16(pc + 4) as i16),
+            WIDE => return format!("    {} {}", opcode_name(code[pc + 1]), read_u16(pc + 2)),
+            IFEQ..=JSR | IFNULL | IFNONNULL => branch(read_u16(pc + 1) as i16 as i32),
+            GOTO_W | JSR_W => branch(read_i32(pc + 1)),
+            TABLESWITCH => {
+                let start = pc + 4 - pc % 4;
+                let (low, high) = (read_i32(start + 4), read_i32(start + 8));
+                let mut lines = format!("{} {}", low, high);
+                for entry in 0..(high as i64 - low as i64 + 1) as usize {
+                    lines += &format!("\n        {}", branch(read_i32(start + 12 + entry * 4)));
+                }
+                lines + &format!("\n        default : {}", branch(read_i32(start)))
+            },
+            LOOKUPSWITCH => {
+                let start = pc + 4 - pc % 4;
+                let mut lines = String::new();
+                for pair in 0..read_i32(start + 4) as usize {
+                    let key = read_i32(start + 8 + pair * 8);
+                    lines += &format!("\n        {} : {}", key, branch(read_i32(start + 12 + pair * 8)));
+                }
+                lines + &format!("\n        default : {}", branch(read_i32(start)))
+            },
+            GETSTATIC..=PUTFIELD => self.member(read_u16(pc + 1), opcode),
+            INVOKEVIRTUAL..=INVOKESTATIC => self.member(read_u16(pc + 1), opcode),
+            INVOKEINTERFACE => format!("{} {}", self.member(read_u16(pc + 1), opcode), code[pc + 3]),
+            INVOKEDYNAMIC => match &self.constant_pool.constants[read_u16(pc + 1) as usize] {
+                ConstantPoolItem::CPInvokeDynamicInfo { bootstrap_method_attr_index, name_and_type_index } => {
+                    let (name, descriptor) = self.name_and_type(*name_and_type_index);
+                    format!("{}{} {}", name, descriptor, self.bootstrap_method(*bootstrap_method_attr_index))
+                },
+                other => format!("#{}", read_u16(pc + 1))
+            },
+            NEW | ANEWARRAY | CHECKCAST | INSTANCEOF => self.class_name(read_u16(pc + 1)).to_string(),
+            MULTIANEWARRAY => format!("{} {}", self.class_name(read_u16(pc + 1)), code[pc + 3]),
+            other => return format!("    {}", name)
+        };
+        if operands.starts_with('\n') { format!("    {}{}", name, operands) } else { format!("    {} {}", name, operands) }
+    }
+
+    fn name_and_type(&self, index: u16) -> (&'a str, &'a str) {
+        let (name_index, descriptor_index) = self.constant_pool.get_name_and_type(index);
+        (self.constant_pool.get_string(name_index), self.constant_pool.get_string(descriptor_index))
+    }
+
+    /// Writes a field as `class/name descriptor` and a method as `class/name(arguments)return`. A method reference of
+    /// an interface gets the `interface` keyword, except after invokeinterface, which always takes one.
+    fn member(&self, index: u16, opcode: u8) -> String {
+        match &self.constant_pool.constants[index as usize] {
+            ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } => {
+                let (name, descriptor) = self.name_and_type(*name_and_type_index);
+                format!("{}/{} {}", self.class_name(*class_index), name, descriptor)
+            },
+            ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } => {
+                let (name, descriptor) = self.name_and_type(*name_and_type_index);
+                format!("{}/{}{}", self.class_name(*class_index), name, descriptor)
+            },
+            ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } => {
+                let (name, descriptor) = self.name_and_type(*name_and_type_index);
+                let keyword = if opcode == INVOKEINTERFACE { "" } else { "interface " };
+                format!("{}{}/{}{}", keyword, self.class_name(*class_index), name, descriptor)
+            },
+            other => format!("#{}", index)
+        }
+    }
+
+    fn bootstrap_method(&self, index: u16) -> String {
+        let Some(bootstrap_method) = self.class_info.get_bootstrap_method(index) else {
+            return format!("{{ #{} }}", index);
+        };
+        let mut text = format!("{{ {}", self.method_handle(bootstrap_method.bootstrap_method_ref));
+        for argument in &bootstrap_method.bootstrap_arguments {
+            text += " ";
+            text += &self.constant(*argument);
+        }
+        text + " }"
+    }
+
+    fn method_handle(&self, index: u16) -> String {
+        match &self.constant_pool.constants[index as usize] {
+            ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index } => {
+                let kind = REFERENCE_KINDS.get(*reference_kind as usize).copied().unwrap_or("unknown");
+                let opcode = if kind == "invokeinterface" { INVOKEINTERFACE } else { INVOKESTATIC };
+                format!("{} {}", kind, self.member(*reference_index, opcode))
+            },
+            other => format!("#{}", index)
+        }
+    }
+
+    /// Writes a loadable constant. Longs end in L and floats in f, ints and doubles are plain numbers.
+    fn constant(&self, index: u16) -> String {
+        match &self.constant_pool.constants[index as usize] {
+            ConstantPoolItem::CPIntegerInfo { value } => value.to_string(),
+            ConstantPoolItem::CPLongInfo { value } => format!("{}L", value),
+            ConstantPoolItem::CPFloatInfo { value } if value.is_nan() && value.to_bits() != f32::NAN.to_bits() => format!("NaN<{:#010x}>f", value.to_bits()),
+            ConstantPoolItem::CPFloatInfo { value } => format!("{}f", float_text(format!("{:?}", value))),
+            ConstantPoolItem::CPDoubleInfo { value } if value.is_nan() && value.to_bits() != f64::NAN.to_bits() => format!("NaN<{:#018x}>", value.to_bits()),
+            ConstantPoolItem::CPDoubleInfo { value } => float_text(format!("{:?}", value)),
+            ConstantPoolItem::CPStringInfo { string_index } => quote(&self.utf16(*string_index)),
+            ConstantPoolItem::CPClassInfo { name_index } => format!("class {}", self.constant_pool.get_string(*name_index)),
+            ConstantPoolItem::CPMethodTypeInfo { descriptor_index } => format!("methodtype {}", self.constant_pool.get_string(*descriptor_index)),
+            ConstantPoolItem::CPMethodHandleInfo { .. } => format!("methodhandle {}", self.method_handle(index)),
+            ConstantPoolItem::CPDynamicInfo { bootstrap_method_attr_index, name_and_type_index } => {
+                let (name, descriptor) = self.name_and_type(*name_and_type_index);
+                format!("dynamic {} {} {}", name, descriptor, self.bootstrap_method(*bootstrap_method_attr_index))
+            },
+            other => format!("#{}", index)
+        }
+    }
+
+    /// Returns a string constant as UTF-16 code units, keeping the unpaired surrogates that a String can't hold.
+    fn utf16(&self, index: u16) -> Vec<u16> {
+        match &self.constant_pool.constants[index as usize] {
+            ConstantPoolItem::CPUTF8Info { bytes: Some(bytes), .. } => ClassLoader::decode_modified_utf8_to_utf16(bytes),
+            ConstantPoolItem::CPUTF8Info { utf8_string, .. } => utf8_string.encode_utf16().collect(),
+            other => Vec::new()
+        }
+    }
+}
+
+/// Returns where the constant pool indexes are in the bytes of an attribute, or None when its format isn't known or
+/// the bytes don't follow it.
+fn constant_references(constant_pool: &ConstantPool, name: &str, info: &[u8]) -> Option<Vec<usize>> {
+    let mut reader = AttributeReader { constant_pool, info, at: 0, references: Vec::new() };
+    reader.attribute(name)?;
+    (reader.at == info.len()).then_some(reader.references)
+}
+
+/// Walks the bytes of an attribute, keeping where the constant pool indexes are.
+struct AttributeReader<'a> {
+    constant_pool: &'a ConstantPool,
+    info: &'a [u8],
+    at: usize,
+    references: Vec<usize>,
+}
+
+impl AttributeReader<'_> {
+    fn skip(&mut self, length: usize) -> Option<()> {
+        self.at = self.at.checked_add(length).filter(|end| *end <= self.info.len())?;
+        Some(())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        let value = *self.info.get(self.at)?;
+        self.at += 1;
+        Some(value)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let value = u16::from_be_bytes([*self.info.get(self.at)?, *self.info.get(self.at + 1)?]);
+        self.at += 2;
+        Some(value)
+    }
+
+    /// Reads a constant pool index. Index 0 means there is no constant.
+    fn reference(&mut self) -> Option<()> {
+        if self.u16()? != 0 {
+            self.references.push(self.at - 2);
+        }
+        Some(())
+    }
+
+    fn attribute(&mut self, name: &str) -> Option<()> {
+        match name {
+            "Signature" | "SourceFile" | "ModuleMainClass" => self.reference()?,
+            "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => self.annotations()?,
+            "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                for _ in 0..self.u8()? {
+                    self.annotations()?;
+                }
+            },
+            "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+                for _ in 0..self.u16()? {
+                    self.type_annotation()?;
+                }
+            },
+            "AnnotationDefault" => self.element_value()?,
+            "LocalVariableTypeTable" => {
+                for _ in 0..self.u16()? {
+                    // The range of pcs, then the name, the signature and the local variable index
+                    self.skip(4)?;
+                    self.reference()?;
+                    self.reference()?;
+                    self.skip(2)?;
+                }
+            },
+            "MethodParameters" => {
+                for _ in 0..self.u8()? {
+                    self.reference()?;
+                    self.skip(2)?;
+                }
+            },
+            "Record" => {
+                for _ in 0..self.u16()? {
+                    self.reference()?;
+                    self.reference()?;
+                    for _ in 0..self.u16()? {
+                        self.nested_attribute()?;
+                    }
+                }
+            },
+            other => return None
+        }
+        Some(())
+    }
+
+    /// Reads an attribute inside this one, like those of the components of a record. One whose format isn't known keeps
+    /// its bytes.
+    fn nested_attribute(&mut self) -> Option<()> {
+        let name_index = self.u16()?;
+        self.references.push(self.at - 2);
+        let name = match self.constant_pool.constants.get(name_index as usize)? {
+            ConstantPoolItem::CPUTF8Info { utf8_string, .. } => utf8_string.as_str(),
+            other => return None
+        };
+        let length = self.u16()? as usize * 0x10000 + self.u16()? as usize;
+        let (start, references) = (self.at, self.references.len());
+        let end = start.checked_add(length).filter(|end| *end <= self.info.len())?;
+        if self.attribute(name).is_none() || self.at != end {
+            self.at = end;
+            self.references.truncate(references);
+        }
+        Some(())
+    }
+
+    fn annotations(&mut self) -> Option<()> {
+        for _ in 0..self.u16()? {
+            self.annotation()?;
+        }
+        Some(())
+    }
+
+    fn annotation(&mut self) -> Option<()> {
+        self.reference()?;
+        for _ in 0..self.u16()? {
+            self.reference()?;
+            self.element_value()?;
+        }
+        Some(())
+    }
+
+    fn element_value(&mut self) -> Option<()> {
+        match self.u8()? {
+            b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' | b'c' => self.reference()?,
+            b'e' => {
+                self.reference()?;
+                self.reference()?;
+            },
+            b'@' => self.annotation()?,
+            b'[' => {
+                for _ in 0..self.u16()? {
+                    self.element_value()?;
+                }
+            },
+            other => return None
+        }
+        Some(())
+    }
+
+    /// Reads a type annotation, whose target is kept as bytes as it has pcs and indexes of other tables.
+    fn type_annotation(&mut self) -> Option<()> {
+        match self.u8()? {
+            0x00 | 0x01 | 0x16 => self.skip(1)?,
+            0x10..=0x12 | 0x17 | 0x42..=0x46 => self.skip(2)?,
+            0x13..=0x15 => {},
+            0x40 | 0x41 => {
+                let length = self.u16()? as usize;
+                self.skip(6 * length)?;
+            },
+            0x47..=0x4B => self.skip(3)?,
+            other => return None
+        }
+        let path_length = self.u8()? as usize;
+        self.skip(2 * path_length)?;
+        self.annotation()
+    }
+}
+
+/// Writes infinities the way Java does. Other values keep the shortest text that reads back the same, as Debug writes them.
+fn float_text(text: String) -> String {
+    match text.as_str() {
+        "inf" => String::from("Infinity"),
+        "-inf" => String::from("-Infinity"),
+        other => text
+    }
+}
+
+/// Puts a string in double quotes, escaping quotes, backslashes, control characters and unpaired surrogates.
+pub fn quote(code_units: &[u16]) -> String {
+    let mut text = String::from("\"");
+    for result in char::decode_utf16(code_units.iter().copied()) {
+        match result {
+            Ok('"') => text += "\\\"",
+            Ok('\\') => text += "\\\\",
+            Ok('\n') => text += "\\n",
+            Ok('\t') => text += "\\t",
+            Ok('\r') => text += "\\r",
+            Ok(char) if char.is_control() => write!(text, "\\u{:04x}", char as u32).unwrap(),
+            Ok(char) => text.push(char),
+            Err(error) => write!(text, "\\u{:04x}", error.unpaired_surrogate()).unwrap()
+        }
+    }
+    text + "\""
+}
+
+fn quote_if_needed(text: &str) -> String {
+    if text.is_empty() || text.starts_with(['"', ';']) || text.chars().any(|char| char.is_whitespace() || char.is_control()) {
+        quote(&text.encode_utf16().collect::<Vec<u16>>())
+    } else {
+        text.to_string()
+    }
+}
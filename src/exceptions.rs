@@ -0,0 +1,195 @@
+use std::{fmt, sync::Arc};
+
+use crate::{interpreter::Interpreter, object::{Object, ObjectRef, Value, VmData}, runtime_class::Method, strings::{new_java_string, to_rust_string}, thread::JavaThread, vm::Vm};
+
+/// Something thrown by Java code or by the VM itself.
+pub enum Throwable {
+    Object(ObjectRef),
+    // Exception raised by the VM, only turned into an object once Java code gets to see it
+    Pending{class_name: String, message: Option<String>, cause: Option<Box<Throwable>>}
+}
+
+pub type JvmResult<T> = Result<T, Throwable>;
+
+impl Throwable {
+    pub fn new(class_name: &str, message: impl Into<String>) -> Throwable {
+        Throwable::Pending { class_name: String::from(class_name), message: Some(message.into()), cause: None }
+    }
+
+    pub fn without_message(class_name: &str) -> Throwable {
+        Throwable::Pending { class_name: String::from(class_name), message: None, cause: None }
+    }
+
+    pub fn caused_by(class_name: &str, cause: Throwable) -> Throwable {
+        Throwable::Pending { class_name: String::from(class_name), message: None, cause: Some(Box::new(cause)) }
+    }
+
+    pub fn class_name(&self) -> &str {
+        match self {
+            Throwable::Object(object) => &object.class.name,
+            Throwable::Pending { class_name, .. } => class_name
+        }
+    }
+
+    /// Whether this is a `java.lang.Error`, as opposed to an exception.
+    pub fn is_error(&self, vm: &Vm) -> bool {
+        match self {
+            Throwable::Object(object) => object.class.is_subclass_of("java/lang/Error"),
+            Throwable::Pending { class_name, .. } => {
+                vm.load_class(class_name).is_ok_and(|class| class.is_subclass_of("java/lang/Error"))
+            }
+        }
+    }
+
+    /// Creates the object for an exception raised by the VM, by running its constructor.
+    /// The stack trace gets filled in with the current stack of the thread.
+    pub fn into_object(self, thread: &JavaThread) -> JvmResult<ObjectRef> {
+        let (class_name, message, cause) = match self {
+            Throwable::Object(object) => return Ok(object),
+            Throwable::Pending { class_name, message, cause } => (class_name, message, cause)
+        };
+        let in_stack_reserve = thread.in_stack_reserve.get();
+        if class_name == "java/lang/StackOverflowError" {
+            thread.in_stack_reserve.set(true);
+        }
+        let object = Self::create(thread, &class_name, message, cause);
+        thread.in_stack_reserve.set(in_stack_reserve);
+        object
+    }
+
+    fn create(thread: &JavaThread, class_name: &str, message: Option<String>, cause: Option<Box<Throwable>>) -> JvmResult<ObjectRef> {
+        let cause = match cause {
+            Some(cause) => Some(cause.into_object(thread)?),
+            None => None
+        };
+        let class = thread.vm.load_class(class_name)?;
+        class.initialize(thread)?;
+        let object = Object::new_instance(class.clone());
+        let this = Value::Reference(Some(object.clone()));
+        let constructor = |descriptor: &str| class.find_declared_method("<init>", descriptor).cloned();
+        let message_constructor = message.as_ref().and(constructor("(Ljava/lang/String;)V"));
+        let cause_constructor = cause.as_ref().and(constructor("(Ljava/lang/Throwable;)V"));
+        let mut cause = cause;
+        if let (Some(message), Some(constructor)) = (message, message_constructor) {
+            let message = new_java_string(thread, &message)?;
+            Interpreter::invoke(thread, &constructor, vec![this.clone(), Value::Reference(Some(message))])?;
+        } else if let (Some(cause), Some(constructor)) = (cause.take(), cause_constructor) {
+            Interpreter::invoke(thread, &constructor, vec![this.clone(), Value::Reference(Some(cause))])?;
+        } else {
+            let constructor = constructor("()V")
+                .ok_or_else(|| Throwable::new("java/lang/NoSuchMethodError", format!("{}.<init>()V", class_name.replace('/', "."))))?;
+            Interpreter::invoke(thread, &constructor, vec![this.clone()])?;
+        }
+        if let Some(cause) = cause {
+            Interpreter::invoke_virtual_method(thread, &object, "initCause", "(Ljava/lang/Throwable;)Ljava/lang/Throwable;", vec![Value::Reference(Some(cause))])?;
+        }
+        Ok(object)
+    }
+}
+
+/// A frame of a stack trace: a method and the pc of the instruction it was running.
+#[derive(Clone)]
+pub struct BacktraceEntry {
+    pub method: Arc<Method>,
+    pub pc: usize
+}
+
+impl BacktraceEntry {
+    pub fn line_number(&self) -> Option<u16> {
+        self.method.code(&self.method.class()).and_then(|code| code.get_line_number(self.pc))
+    }
+}
+
+impl fmt::Display for BacktraceEntry {
+    /// Formats the entry like StackTraceElement.toString does, for example `Main.main(Main.java:5)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let class = self.method.class();
+        let source_file = &class.info.as_ref().unwrap().source_file;
+        let line_number = self.line_number();
+        let location = match (self.method.is_native(), source_file.is_empty(), line_number) {
+            (true, _, _) => String::from("Native Method"),
+            (false, true, _) => String::from("Unknown Source"),
+            (false, false, Some(line_number)) => format!("{}:{}", source_file, line_number),
+            (false, false, None) => source_file.clone()
+        };
+        write!(f, "{}.{}({})", class.name.replace('/', "."), self.method.name, location)
+    }
+}
+
+/// Records the stack of the thread in a throwable, leaving out the frames that are creating the throwable itself,
+/// and those of hidden classes.
+pub fn fill_in_stack_trace(thread: &JavaThread, throwable: &ObjectRef) {
+    let is_own_frame = |method: &Method, name: &str| method.name == name && throwable.class.is_subclass_of(&method.class_name);
+    let backtrace = thread.stack.borrow().iter().rev()
+        .skip_while(|frame| is_own_frame(&frame.method, "fillInStackTrace"))
+        .skip_while(|frame| is_own_frame(&frame.method, "<init>"))
+        .filter(|frame| !thread.vm.is_hidden_class(&frame.method.class_name))
+        .map(|frame| BacktraceEntry { method: frame.method.clone(), pc: frame.pc.get() })
+        .collect::<Vec<_>>();
+    // JDK 9 and later size the array of StackTraceElements by this field
+    if let Some(slot) = throwable.class.instance_field_slot("depth", "I") {
+        throwable.fields()[slot] = Value::Int(backtrace.len() as i32);
+    }
+    *throwable.vm_data.lock().unwrap() = Some(VmData::Backtrace(backtrace));
+}
+
+pub fn get_backtrace(throwable: &ObjectRef) -> Vec<BacktraceEntry> {
+    match &*throwable.vm_data.lock().unwrap() {
+        Some(VmData::Backtrace(backtrace)) => backtrace.clone(),
+        other => Vec::new()
+    }
+}
+
+/// Calls the getCause method of a throwable, which subclasses like InvocationTargetException override, falling back
+/// to its cause field if that fails.
+fn get_cause(thread: &JavaThread, throwable: &ObjectRef) -> Option<ObjectRef> {
+    match Interpreter::invoke_virtual_method(thread, throwable, "getCause", "()Ljava/lang/Throwable;", Vec::new()) {
+        Ok(Some(Value::Reference(cause))) => return cause,
+        other => {}
+    }
+    let slot = throwable.class.instance_field_slot("cause", "Ljava/lang/Throwable;")?;
+    let cause = throwable.fields()[slot].as_reference().cloned()?;
+    // A throwable whose cause has not been set yet points to itself
+    if Arc::ptr_eq(&cause, throwable) { None } else { Some(cause) }
+}
+
+/// Calls the toString method of a throwable, falling back to its class name if that fails.
+fn describe(thread: &JavaThread, throwable: &ObjectRef) -> String {
+    match Interpreter::invoke_virtual_method(thread, throwable, "toString", "()Ljava/lang/String;", Vec::new()) {
+        Ok(Some(Value::Reference(Some(string)))) => to_rust_string(&string),
+        other => throwable.class.name.replace('/', ".")
+    }
+}
+
+/// Formats a throwable and its causes the way Throwable.printStackTrace does.
+pub fn format_stack_trace(thread: &JavaThread, throwable: &ObjectRef) -> String {
+    let mut backtrace = get_backtrace(throwable);
+    let mut output = format!("{}\n", describe(thread, throwable));
+    for entry in &backtrace {
+        output += &format!("\tat {}\n", entry);
+    }
+    let mut seen = vec![throwable.clone()];
+    while let Some(cause) = get_cause(thread, seen.last().unwrap()) {
+        if seen.iter().any(|throwable| Arc::ptr_eq(throwable, &cause)) {
+            output += &format!("Caused by: [CIRCULAR REFERENCE: {}]\n", describe(thread, &cause));
+            break;
+        }
+        let enclosing = backtrace;
+        backtrace = get_backtrace(&cause);
+        // Frames at the bottom that are the same as those of the enclosing trace are only counted,
+        // frames are compared like StackTraceElement.equals does, by their class, method and line
+        let in_common = backtrace.iter().rev()
+            .zip(enclosing.iter().rev())
+            .take_while(|(entry, enclosing_entry)| entry.to_string() == enclosing_entry.to_string())
+            .count();
+        output += &format!("Caused by: {}\n", describe(thread, &cause));
+        for entry in &backtrace[..backtrace.len() - in_common] {
+            output += &format!("\tat {}\n", entry);
+        }
+        if in_common > 0 {
+            output += &format!("\t... {} more\n", in_common);
+        }
+        seen.push(cause);
+    }
+    output
+}
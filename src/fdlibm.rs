@@ -0,0 +1,507 @@
+// Ports of the fdlibm functions that StrictMath has to give the exact results of, where the C library rounds
+// differently. They follow the C code of fdlibm 5.3, which compares the upper 32 bits of doubles against its thresholds.
+// The constants are written as fdlibm has them, so that they can be compared.
+#![allow(clippy::excessive_precision, clippy::approx_constant)]
+
+const LN2_HI: f64 = 6.93147180369123816490e-01;
+const LN2_LO: f64 = 1.90821492927058770002e-10;
+const INV_LN2: f64 = 1.44269504088896338700e+00;
+const O_THRESHOLD: f64 = 7.09782712893383973096e+02;
+const U_THRESHOLD: f64 = -7.45133219101941108420e+02;
+const TWO_M1000: f64 = 9.33263618503218878990e-302;
+// The largest |x| that sinh and cosh don't overflow for
+const OVERFLOW_THRESHOLD: u64 = 0x408633ce8fb9f87d;
+
+/// The upper 32 bits of a double without the sign.
+fn high_word(x: f64) -> u32 {
+    (x.to_bits() >> 32) as u32 & 0x7fffffff
+}
+
+/// Multiplies a double by 2^k by adding k to its exponent, which has to stay in range.
+fn add_to_exponent(y: f64, k: i32) -> f64 {
+    f64::from_bits((y.to_bits() as i64 + ((k as i64) << 52)) as u64)
+}
+
+/// Builds a double from its upper 32 bits, with the lower ones 0.
+fn from_high_word(high: u32) -> f64 {
+    f64::from_bits((high as u64) << 32)
+}
+
+/// e_exp.c: reduces x to r = x - k ln2, and approximates exp(r) with a rational function.
+pub fn exp(x: f64) -> f64 {
+    const P1: f64 = 1.66666666666666019037e-01;
+    const P2: f64 = -2.77777777770155933842e-03;
+    const P3: f64 = 6.61375632143793436117e-05;
+    const P4: f64 = -1.65339022054652515390e-06;
+    const P5: f64 = 4.13813679705723846039e-08;
+
+    let hx = high_word(x);
+    let negative = x.is_sign_negative();
+    if hx >= 0x40862e42 {
+        if x.is_nan() {
+            return x + x;
+        }
+        if x.is_infinite() {
+            return if negative { 0.0 } else { x };
+        }
+        if x > O_THRESHOLD {
+            return f64::INFINITY;
+        }
+        if x < U_THRESHOLD {
+            return 0.0;
+        }
+    }
+    let mut x = x;
+    let (mut hi, mut lo, mut k) = (0.0, 0.0, 0);
+    // |x| > ln2 / 2
+    if hx > 0x3fd62e42 {
+        // |x| < 3 ln2 / 2
+        if hx < 0x3ff0a2b2 {
+            (hi, lo, k) = match negative {
+                false => (x - LN2_HI, LN2_LO, 1),
+                true => (x + LN2_HI, -LN2_LO, -1)
+            };
+        } else {
+            k = (INV_LN2 * x + if negative { -0.5 } else { 0.5 }) as i32;
+            let t = k as f64;
+            hi = x - t * LN2_HI;
+            lo = t * LN2_LO;
+        }
+        x = hi - lo;
+    } else if hx < 0x3e300000 {
+        // |x| < 2^-28
+        return 1.0 + x;
+    }
+    let t = x * x;
+    let c = x - t * (P1 + t * (P2 + t * (P3 + t * (P4 + t * P5))));
+    if k == 0 {
+        return 1.0 - ((x * c) / (c - 2.0) - x);
+    }
+    let y = 1.0 - ((lo - (x * c) / (2.0 - c)) - hi);
+    match k >= -1021 {
+        true => add_to_exponent(y, k),
+        false => add_to_exponent(y, k + 1000) * TWO_M1000
+    }
+}
+
+/// s_expm1.c: exp(x) - 1, which stays accurate for x near 0.
+pub fn expm1(x: f64) -> f64 {
+    const Q1: f64 = -3.33333333333331316428e-02;
+    const Q2: f64 = 1.58730158725481460165e-03;
+    const Q3: f64 = -7.93650757867487942473e-05;
+    const Q4: f64 = 4.00821782732936239552e-06;
+    const Q5: f64 = -2.01099218183624371326e-07;
+
+    let hx = high_word(x);
+    let negative = x.is_sign_negative();
+    // |x| >= 56 ln2
+    if hx >= 0x4043687a {
+        if hx >= 0x40862e42 {
+            if x.is_nan() {
+                return x + x;
+            }
+            if x.is_infinite() {
+                return if negative { -1.0 } else { x };
+            }
+            if x > O_THRESHOLD {
+                return f64::INFINITY;
+            }
+        }
+        if negative {
+            return -1.0;
+        }
+    }
+    let mut x = x;
+    let (c, k) = if hx > 0x3fd62e42 {
+        let (hi, lo, k) = if hx < 0x3ff0a2b2 {
+            match negative {
+                false => (x - LN2_HI, LN2_LO, 1),
+                true => (x + LN2_HI, -LN2_LO, -1)
+            }
+        } else {
+            let k = (INV_LN2 * x + if negative { -0.5 } else { 0.5 }) as i32;
+            let t = k as f64;
+            (x - t * LN2_HI, t * LN2_LO, k)
+        };
+        x = hi - lo;
+        ((hi - x) - lo, k)
+    } else if hx < 0x3c900000 {
+        // |x| < 2^-54
+        return x;
+    } else {
+        (0.0, 0)
+    };
+    let hfx = 0.5 * x;
+    let hxs = x * hfx;
+    let r1 = 1.0 + hxs * (Q1 + hxs * (Q2 + hxs * (Q3 + hxs * (Q4 + hxs * Q5))));
+    let t = 3.0 - r1 * hfx;
+    let mut e = hxs * ((r1 - t) / (6.0 - x * t));
+    if k == 0 {
+        return x - (x * e - hxs);
+    }
+    e = x * (e - c) - c;
+    e -= hxs;
+    if k == -1 {
+        return 0.5 * (x - e) - 0.5;
+    }
+    if k == 1 {
+        return match x < -0.25 {
+            true => -2.0 * (e - (x + 0.5)),
+            false => 1.0 + 2.0 * (x - e)
+        };
+    }
+    if k <= -2 || k > 56 {
+        let y = 1.0 - (e - x);
+        return add_to_exponent(y, k) - 1.0;
+    }
+    if k < 20 {
+        // 1 - 2^-k
+        let t = from_high_word(0x3ff00000 - (0x200000 >> k));
+        add_to_exponent(t - (e - x), k)
+    } else {
+        // 2^-k
+        let t = from_high_word(((0x3ff - k) as u32) << 20);
+        add_to_exponent(x - (e + t) + 1.0, k)
+    }
+}
+
+/// e_sinh.c
+pub fn sinh(x: f64) -> f64 {
+    let ix = high_word(x);
+    if ix >= 0x7ff00000 {
+        return x + x;
+    }
+    let h = if x.is_sign_negative() { -0.5 } else { 0.5 };
+    let absolute = x.abs();
+    // |x| < 22
+    if ix < 0x40360000 {
+        // |x| < 2^-28
+        if ix < 0x3e300000 {
+            return x;
+        }
+        let t = expm1(absolute);
+        if ix < 0x3ff00000 {
+            return h * (2.0 * t - t * t / (t + 1.0));
+        }
+        return h * (t + t / (t + 1.0));
+    }
+    // |x| < log(Double.MAX_VALUE)
+    if ix < 0x40862e42 {
+        return h * exp(absolute);
+    }
+    if absolute.to_bits() <= OVERFLOW_THRESHOLD {
+        let w = exp(0.5 * absolute);
+        let t = h * w;
+        return t * w;
+    }
+    // Overflows to infinity with the sign of x
+    x * 1.0e307
+}
+
+/// e_cosh.c
+pub fn cosh(x: f64) -> f64 {
+    let ix = high_word(x);
+    if ix >= 0x7ff00000 {
+        return x * x;
+    }
+    let absolute = x.abs();
+    // |x| < ln2 / 2
+    if ix < 0x3fd62e43 {
+        let t = expm1(absolute);
+        let w = 1.0 + t;
+        // |x| < 2^-55
+        if ix < 0x3c800000 {
+            return w;
+        }
+        return 1.0 + (t * t) / (w + w);
+    }
+    // |x| < 22
+    if ix < 0x40360000 {
+        let t = exp(absolute);
+        return 0.5 * t + 0.5 / t;
+    }
+    if ix < 0x40862e42 {
+        return 0.5 * exp(absolute);
+    }
+    if absolute.to_bits() <= OVERFLOW_THRESHOLD {
+        let w = exp(0.5 * absolute);
+        let t = 0.5 * w;
+        return t * w;
+    }
+    f64::INFINITY
+}
+
+/// s_tanh.c
+pub fn tanh(x: f64) -> f64 {
+    let ix = high_word(x);
+    if ix >= 0x7ff00000 {
+        return if x.is_nan() { x } else { x.signum() };
+    }
+    // |x| < 22
+    let z = if ix < 0x40360000 {
+        // |x| < 2^-55
+        if ix < 0x3c800000 {
+            return x * (1.0 + x);
+        }
+        if ix >= 0x3ff00000 {
+            let t = expm1(2.0 * x.abs());
+            1.0 - 2.0 / (t + 2.0)
+        } else {
+            let t = expm1(-2.0 * x.abs());
+            -t / (t + 2.0)
+        }
+    } else {
+        1.0
+    };
+    if x.is_sign_negative() { -z } else { z }
+}
+
+/// e_remainder.c: the remainder of IEEE 754, where the quotient is rounded to the nearest integer instead of
+/// truncated like `%` does. The remainder of dividing by 2p is exact, and gets p taken off at most twice.
+pub fn ieee_remainder(x: f64, p: f64) -> f64 {
+    if x.is_nan() || p.is_nan() || x.is_infinite() || p == 0.0 {
+        return f64::NAN;
+    }
+    if p.is_infinite() {
+        return x;
+    }
+    let negative = x.is_sign_negative();
+    let p = p.abs();
+    let mut x = match p <= f64::MAX / 2.0 {
+        true => (x % (p + p)).abs(),
+        false => x.abs()
+    };
+    if x == p {
+        return if negative { -0.0 } else { 0.0 };
+    }
+    // Halving p would lose a bit for the smallest numbers, doubling x can't overflow there
+    if p < f64::MIN_POSITIVE * 2.0 {
+        if x + x > p {
+            x -= p;
+            if x + x >= p {
+                x -= p;
+            }
+        }
+    } else {
+        let half = 0.5 * p;
+        if x > half {
+            x -= p;
+            if x >= half {
+                x -= p;
+            }
+        }
+    }
+    if negative { -x } else { x }
+}
+
+/// s_atan.c: reduces |x| to one of four intervals around atan(0.5), atan(1), atan(1.5) and atan(inf),
+/// and approximates the rest with a polynomial.
+pub fn atan(x: f64) -> f64 {
+    const ATAN_HI: [f64; 4] = [4.63647609000806093515e-01, 7.85398163397448278999e-01, 9.82793723247329054082e-01, 1.57079632679489655800e+00];
+    const ATAN_LO: [f64; 4] = [2.26987774529616870924e-17, 3.06161699786838301793e-17, 1.39033110312309984516e-17, 6.12323399573676603587e-17];
+    const AT: [f64; 11] = [
+        3.33333333333329318027e-01, -1.99999999998764832476e-01, 1.42857142725034663711e-01, -1.11111104054623557880e-01,
+        9.09088713343650656196e-02, -7.69187620504482999495e-02, 6.66107313738753120669e-02, -5.83357013379057348645e-02,
+        4.97687799461593236017e-02, -3.65315727442169155270e-02, 1.62858201153657823623e-02
+    ];
+
+    let ix = high_word(x);
+    let negative = x.is_sign_negative();
+    // |x| >= 2^66
+    if ix >= 0x44100000 {
+        if x.is_nan() {
+            return x + x;
+        }
+        return if negative { -ATAN_HI[3] - ATAN_LO[3] } else { ATAN_HI[3] + ATAN_LO[3] };
+    }
+    let (x, id) = if ix < 0x3fdc0000 {
+        // |x| < 2^-29
+        if ix < 0x3e200000 {
+            return x;
+        }
+        (x, None)
+    } else {
+        let x = x.abs();
+        if ix < 0x3ff30000 {
+            if ix < 0x3fe60000 {
+                ((2.0 * x - 1.0) / (2.0 + x), Some(0))
+            } else {
+                ((x - 1.0) / (x + 1.0), Some(1))
+            }
+        } else if ix < 0x40038000 {
+            ((x - 1.5) / (1.0 + 1.5 * x), Some(2))
+        } else {
+            (-1.0 / x, Some(3))
+        }
+    };
+    let z = x * x;
+    let w = z * z;
+    // The odd and even terms of the polynomial
+    let s1 = z * (AT[0] + w * (AT[2] + w * (AT[4] + w * (AT[6] + w * (AT[8] + w * AT[10])))));
+    let s2 = w * (AT[1] + w * (AT[3] + w * (AT[5] + w * (AT[7] + w * AT[9]))));
+    match id {
+        None => x - x * (s1 + s2),
+        Some(id) => {
+            let z = ATAN_HI[id] - ((x * (s1 + s2) - ATAN_LO[id]) - x);
+            if negative { -z } else { z }
+        }
+    }
+}
+
+/// e_atan2.c: the angle of the point (x, y), from atan(|y/x|) put in the quadrant of the point.
+pub fn atan2(y: f64, x: f64) -> f64 {
+    const PI_O_4: f64 = 7.8539816339744827900e-01;
+    const PI_O_2: f64 = 1.5707963267948965580e+00;
+    const PI: f64 = 3.1415926535897931160e+00;
+    const PI_LO: f64 = 1.2246467991473531772e-16;
+
+    if x.is_nan() || y.is_nan() {
+        return x + y;
+    }
+    if x == 1.0 {
+        return atan(y);
+    }
+    let (x_negative, y_negative) = (x.is_sign_negative(), y.is_sign_negative());
+    if y == 0.0 {
+        return match x_negative {
+            false => y,
+            true => if y_negative { -PI } else { PI }
+        };
+    }
+    if x == 0.0 {
+        return if y_negative { -PI_O_2 } else { PI_O_2 };
+    }
+    if x.is_infinite() {
+        let z = match (y.is_infinite(), x_negative) {
+            (true, false) => PI_O_4,
+            (true, true) => 3.0 * PI_O_4,
+            (false, false) => 0.0,
+            (false, true) => PI
+        };
+        return if y_negative { -z } else { z };
+    }
+    if y.is_infinite() {
+        return if y_negative { -PI_O_2 } else { PI_O_2 };
+    }
+    let k = (high_word(y) as i32 - high_word(x) as i32) >> 20;
+    let z = if k > 60 {
+        // |y / x| > 2^60
+        PI_O_2 + 0.5 * PI_LO
+    } else if x_negative && k < -60 {
+        0.0
+    } else {
+        atan((y / x).abs())
+    };
+    match (x_negative, y_negative) {
+        (false, false) => z,
+        (false, true) => -z,
+        (true, false) => PI - (z - PI_LO),
+        (true, true) => (z - PI_LO) - PI
+    }
+}
+
+/// e_log.c: splits x into 2^k (1 + f), and approximates log(1 + f) with a polynomial in s = f / (2 + f).
+pub fn log(x: f64) -> f64 {
+    const TWO54: f64 = 1.80143985094819840000e+16;
+    const LG1: f64 = 6.666666666666735130e-01;
+    const LG2: f64 = 3.999999999940941908e-01;
+    const LG3: f64 = 2.857142874366239149e-01;
+    const LG4: f64 = 2.222219843214978396e-01;
+    const LG5: f64 = 1.818357216161805012e-01;
+    const LG6: f64 = 1.531383769920937332e-01;
+    const LG7: f64 = 1.479819860511658591e-01;
+
+    let mut x = x;
+    let mut hx = (x.to_bits() >> 32) as i32;
+    let mut k = 0;
+    // x < 2^-1022
+    if hx < 0x00100000 {
+        if x == 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        if hx < 0 {
+            return f64::NAN;
+        }
+        // Scales a subnormal number up
+        k -= 54;
+        x *= TWO54;
+        hx = (x.to_bits() >> 32) as i32;
+    }
+    if hx >= 0x7ff00000 {
+        return x + x;
+    }
+    k += (hx >> 20) - 1023;
+    hx &= 0x000fffff;
+    // Normalizes x or x / 2 to [sqrt(2) / 2, sqrt(2)]
+    let i = (hx + 0x95f64) & 0x100000;
+    x = f64::from_bits(((hx | (i ^ 0x3ff00000)) as u64) << 32 | (x.to_bits() & 0xffffffff));
+    k += i >> 20;
+    let f = x - 1.0;
+    let dk = k as f64;
+    // |f| < 2^-20
+    if (0x000fffff & (2 + hx)) < 3 {
+        if f == 0.0 {
+            return match k {
+                0 => 0.0,
+                k => dk * LN2_HI + dk * LN2_LO
+            };
+        }
+        let r = f * f * (0.5 - 0.33333333333333333 * f);
+        return match k {
+            0 => f - r,
+            k => dk * LN2_HI - ((r - dk * LN2_LO) - f)
+        };
+    }
+    let s = f / (2.0 + f);
+    let z = s * s;
+    let w = z * z;
+    let t1 = w * (LG2 + w * (LG4 + w * LG6));
+    let t2 = z * (LG1 + w * (LG3 + w * (LG5 + w * LG7)));
+    let r = t2 + t1;
+    if ((hx - 0x6147a) | (0x6b851 - hx)) > 0 {
+        let hfsq = 0.5 * f * f;
+        match k {
+            0 => f - (hfsq - s * (hfsq + r)),
+            k => dk * LN2_HI - ((hfsq - (s * (hfsq + r) + dk * LN2_LO)) - f)
+        }
+    } else {
+        match k {
+            0 => f - s * (f - r),
+            k => dk * LN2_HI - ((s * (f - r) - dk * LN2_LO) - f)
+        }
+    }
+}
+
+/// e_log10.c: log10(2^n x) = n log10(2) + log10(x), with log10(x) from log(x).
+pub fn log10(x: f64) -> f64 {
+    const TWO54: f64 = 1.80143985094819840000e+16;
+    const IVLN10: f64 = 4.34294481903251816668e-01;
+    const LOG10_2HI: f64 = 3.01029995663611771306e-01;
+    const LOG10_2LO: f64 = 3.69423907715893078616e-13;
+
+    let mut x = x;
+    let mut hx = (x.to_bits() >> 32) as i32;
+    let mut k = 0;
+    if hx < 0x00100000 {
+        if x == 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        if hx < 0 {
+            return f64::NAN;
+        }
+        k -= 54;
+        x *= TWO54;
+        hx = (x.to_bits() >> 32) as i32;
+    }
+    if hx >= 0x7ff00000 {
+        return x + x;
+    }
+    k += (hx >> 20) - 1023;
+    // Keeps the exponent of x at 0, or -1 when k is negative
+    let i = ((k as u32) & 0x80000000) >> 31;
+    hx = (hx & 0x000fffff) | ((0x3ff - i as i32) << 20);
+    let y = (k + i as i32) as f64;
+    x = f64::from_bits((hx as u64) << 32 | (x.to_bits() & 0xffffffff));
+    let z = y * LOG10_2LO + IVLN10 * log(x);
+    z + y * LOG10_2HI
+}
@@ -0,0 +1,521 @@
+use std::{collections::HashSet, fmt};
+
+use crate::class_loader::{AttributeInfo, ClassFlags, ClassInfo, ConstantPoolItem, FieldFlags, FieldInfo, MethodFlags, MethodInfo};
+
+/// A problem found by the format check, with the part of the class file it is about.
+pub struct Diagnostic {
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "{}: {}", self.location, self.message)
+    }
+}
+
+/// Checks that a parsed class file is well formed (JVMS §4.8), before it gets linked: constant pool entries
+/// point at the right kind of entry, names and descriptors are valid, flags are combined in a legal way, there are
+/// no duplicate fields or methods, and methods have code exactly when they are neither abstract nor native.
+pub fn check_class(info: &ClassInfo) -> Vec<Diagnostic> {
+    let mut checker = FormatChecker { info, diagnostics: Vec::new() };
+    checker.check_constant_pool();
+    checker.check_class();
+    let mut fields = HashSet::new();
+    for field in &info.fields {
+        checker.check_field(field, &mut fields);
+    }
+    let mut methods = HashSet::new();
+    for method in &info.methods {
+        checker.check_method(method, &mut methods);
+    }
+    checker.check_attributes("class", &info.attributes);
+    checker.diagnostics
+}
+
+/// The kinds of names JVMS §4.2 allows.
+#[derive(Clone, Copy, PartialEq)]
+enum NameKind {
+    // Like `java/lang/String`
+    Class,
+    Field,
+    // Like a field name, but without `<` and `>` unless it is `<init>` or `<clinit>`
+    Method,
+}
+
+struct FormatChecker<'a> {
+    info: &'a ClassInfo,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> FormatChecker<'a> {
+    fn report(&mut self, location: impl Into<String>, message: impl Into<String>) {
+        self.diagnostics.push(Diagnostic { location: location.into(), message: message.into() });
+    }
+
+    fn constant(&self, index: u16) -> Option<&'a ConstantPoolItem> {
+        // The first entry is only there to make indexes start at 1
+        match index {
+            0 => None,
+            index => self.info.constant_pool.constants.get(index as usize)
+        }
+    }
+
+    /// Returns the string of a Utf8 entry, reporting it when the index points at something else.
+    fn utf8(&mut self, location: &str, index: u16) -> Option<&'a str> {
+        match self.constant(index) {
+            Some(ConstantPoolItem::CPUTF8Info { utf8_string, .. }) => Some(utf8_string),
+            other => {
+                self.report(location, format!("Index {} is not a Utf8 constant", index));
+                None
+            }
+        }
+    }
+
+    /// Returns the name of a Class entry, reporting it when the index points at something else.
+    fn class_name(&mut self, location: &str, index: u16) -> Option<&'a str> {
+        match self.constant(index) {
+            Some(ConstantPoolItem::CPClassInfo { name_index }) => self.info.constant_pool.constants.get(*name_index as usize)
+                .and_then(|name| match name {
+                    ConstantPoolItem::CPUTF8Info { utf8_string, .. } => Some(utf8_string.as_str()),
+                    other => None
+                }),
+            other => {
+                self.report(location, format!("Index {} is not a Class constant", index));
+                None
+            }
+        }
+    }
+
+    fn name_and_type(&mut self, location: &str, index: u16) -> Option<(&'a str, &'a str)> {
+        match self.constant(index) {
+            Some(ConstantPoolItem::CPNameAndTypeInfo { name_index, descriptor_index }) => {
+                let name = self.utf8(location, *name_index)?;
+                let descriptor = self.utf8(location, *descriptor_index)?;
+                Some((name, descriptor))
+            },
+            other => {
+                self.report(location, format!("Index {} is not a NameAndType constant", index));
+                None
+            }
+        }
+    }
+
+    fn bootstrap_method_count(&self) -> usize {
+        self.info.attributes.iter().find_map(|attribute| match attribute {
+            AttributeInfo::BootstrapMethods(bootstrap_methods) => Some(bootstrap_methods.methods.len()),
+            other => None
+        }).unwrap_or(0)
+    }
+
+    fn check_constant_pool(&mut self) {
+        let major_version = self.info.major_version;
+        for (index, constant) in self.info.constant_pool.constants.iter().enumerate().skip(1) {
+            let location = format!("constant pool #{}", index);
+            let location = location.as_str();
+            let required_version = match constant {
+                ConstantPoolItem::CPMethodHandleInfo { .. } | ConstantPoolItem::CPMethodTypeInfo { .. } | ConstantPoolItem::CPInvokeDynamicInfo { .. } => 51,
+                ConstantPoolItem::CPModuleInfo { .. } | ConstantPoolItem::CPPackageInfo { .. } => 53,
+                ConstantPoolItem::CPDynamicInfo { .. } => 55,
+                other => 45
+            };
+            if major_version < required_version {
+                self.report(location, format!("Constant not allowed before class file version {}", required_version));
+            }
+            match constant {
+                ConstantPoolItem::CPClassInfo { name_index } => {
+                    if let Some(name) = self.utf8(location, *name_index) {
+                        let valid = if name.starts_with('[') { is_field_descriptor(name) } else { is_valid_name(name, NameKind::Class) };
+                        if !valid {
+                            self.report(location, format!("Illegal class name \"{}\"", name));
+                        }
+                    }
+                },
+                ConstantPoolItem::CPStringInfo { string_index: index } | ConstantPoolItem::CPModuleInfo { name_index: index } |
+                ConstantPoolItem::CPPackageInfo { name_index: index } => {
+                    self.utf8(location, *index);
+                },
+                ConstantPoolItem::CPNameAndTypeInfo { name_index, descriptor_index } => {
+                    self.utf8(location, *name_index);
+                    self.utf8(location, *descriptor_index);
+                },
+                ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } => {
+                    self.class_name(location, *class_index);
+                    if let Some((name, descriptor)) = self.name_and_type(location, *name_and_type_index) {
+                        self.check_name(location, name, NameKind::Field);
+                        self.check_field_descriptor(location, descriptor);
+                    }
+                },
+                ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } |
+                ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } => {
+                    self.class_name(location, *class_index);
+                    if let Some((name, descriptor)) = self.name_and_type(location, *name_and_type_index) {
+                        self.check_name(location, name, NameKind::Method);
+                        self.check_method_descriptor(location, name, descriptor);
+                        if name == "<clinit>" {
+                            self.report(location, "Illegal reference to the class initializer <clinit>");
+                        }
+                    }
+                },
+                ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index } => {
+                    self.check_method_handle(location, *reference_kind, *reference_index);
+                },
+                ConstantPoolItem::CPMethodTypeInfo { descriptor_index } => {
+                    if let Some(descriptor) = self.utf8(location, *descriptor_index) && !is_method_descriptor(descriptor) {
+                        self.report(location, format!("Illegal method descriptor \"{}\"", descriptor));
+                    }
+                },
+                ConstantPoolItem::CPDynamicInfo { bootstrap_method_attr_index, name_and_type_index } |
+                ConstantPoolItem::CPInvokeDynamicInfo { bootstrap_method_attr_index, name_and_type_index } => {
+                    if *bootstrap_method_attr_index as usize >= self.bootstrap_method_count() {
+                        self.report(location, format!("Bootstrap method {} does not exist", bootstrap_method_attr_index));
+                    }
+                    if let Some((name, descriptor)) = self.name_and_type(location, *name_and_type_index) {
+                        self.check_name(location, name, NameKind::Field);
+                        if matches!(constant, ConstantPoolItem::CPDynamicInfo { .. }) {
+                            self.check_field_descriptor(location, descriptor);
+                        } else if !is_method_descriptor(descriptor) {
+                            self.report(location, format!("Illegal method descriptor \"{}\"", descriptor));
+                        }
+                    }
+                },
+                other => {}
+            }
+        }
+    }
+
+    /// Checks the member a method handle refers to, which depends on its kind (JVMS §4.4.8).
+    fn check_method_handle(&mut self, location: &str, reference_kind: u8, reference_index: u16) {
+        let reference = self.constant(reference_index);
+        let (matches, name_and_type_index) = match (reference_kind, reference) {
+            // getField, getStatic, putField, putStatic
+            (1..=4, Some(ConstantPoolItem::CPFieldRef { name_and_type_index, .. })) => (true, *name_and_type_index),
+            // invokeVirtual, newInvokeSpecial
+            (5 | 8, Some(ConstantPoolItem::CPMethodRef { name_and_type_index, .. })) => (true, *name_and_type_index),
+            // invokeStatic, invokeSpecial, which can call interface methods since version 52
+            (6 | 7, Some(ConstantPoolItem::CPMethodRef { name_and_type_index, .. })) => (true, *name_and_type_index),
+            (6 | 7, Some(ConstantPoolItem::CPInterfaceMethodRef { name_and_type_index, .. })) => (self.info.major_version >= 52, *name_and_type_index),
+            // invokeInterface
+            (9, Some(ConstantPoolItem::CPInterfaceMethodRef { name_and_type_index, .. })) => (true, *name_and_type_index),
+            (1..=9, other) => (false, 0),
+            other => {
+                self.report(location, format!("Illegal method handle kind {}", reference_kind));
+                return;
+            }
+        };
+        if !matches {
+            self.report(location, format!("Method handle of kind {} refers to the wrong kind of constant at index {}", reference_kind, reference_index));
+            return;
+        }
+        if let Some((name, _)) = self.name_and_type(location, name_and_type_index) {
+            if reference_kind == 8 && name != "<init>" {
+                self.report(location, "Method handle of kind newInvokeSpecial must refer to <init>");
+            } else if reference_kind != 8 && name.starts_with('<') {
+                self.report(location, format!("Method handle of kind {} can't refer to {}", reference_kind, name));
+            }
+        }
+    }
+
+    fn check_name(&mut self, location: &str, name: &str, kind: NameKind) {
+        if !is_valid_name(name, kind) {
+            let what = match kind {
+                NameKind::Class => "class name",
+                NameKind::Field => "field name",
+                NameKind::Method => "method name"
+            };
+            self.report(location, format!("Illegal {} \"{}\"", what, name));
+        }
+    }
+
+    fn check_field_descriptor(&mut self, location: &str, descriptor: &str) {
+        if !is_field_descriptor(descriptor) {
+            self.report(location, format!("Illegal field descriptor \"{}\"", descriptor));
+        }
+    }
+
+    fn check_method_descriptor(&mut self, location: &str, name: &str, descriptor: &str) {
+        if !is_method_descriptor(descriptor) {
+            self.report(location, format!("Illegal method descriptor \"{}\"", descriptor));
+        } else if name.starts_with('<') && !descriptor.ends_with(")V") {
+            self.report(location, format!("Method {} must return void", name));
+        }
+    }
+
+    fn check_class(&mut self) {
+        let flags = self.info.access_flags;
+        let this_class = self.class_name("class", self.info.this_class);
+        if flags.contains(ClassFlags::ACC_MODULE) {
+            if flags != ClassFlags::ACC_MODULE || self.info.super_class != 0 || !self.info.interfaces.is_empty() {
+                self.report("class", "A module-info class can't have other flags, a superclass or interfaces");
+            }
+            return;
+        }
+        if flags.contains(ClassFlags::ACC_INTERFACE) {
+            if !flags.contains(ClassFlags::ACC_ABSTRACT) {
+                self.report("class", "An interface must be abstract");
+            }
+            if flags.intersects(ClassFlags::ACC_FINAL | ClassFlags::ACC_ENUM) {
+                self.report("class", "An interface can't be final or an enum");
+            }
+        } else if flags.contains(ClassFlags::ACC_ANNOTATION) {
+            self.report("class", "An annotation must be an interface");
+        }
+        if flags.contains(ClassFlags::ACC_FINAL | ClassFlags::ACC_ABSTRACT) {
+            self.report("class", "A class can't be both final and abstract");
+        }
+        if let Some(this_class) = this_class && this_class.starts_with('[') {
+            self.report("class", format!("Illegal class name \"{}\"", this_class));
+        }
+        match self.info.super_class {
+            0 if this_class != Some("java/lang/Object") => self.report("class", "Only java.lang.Object can have no superclass"),
+            0 => {},
+            index => {
+                let super_class = self.class_name("class", index);
+                if flags.contains(ClassFlags::ACC_INTERFACE) && super_class.is_some_and(|super_class| super_class != "java/lang/Object") {
+                    self.report("class", "The superclass of an interface must be java.lang.Object");
+                }
+                if super_class.is_some_and(|super_class| super_class.starts_with('[')) {
+                    self.report("class", "The superclass can't be an array");
+                }
+            }
+        }
+        let mut interfaces = HashSet::new();
+        for index in &self.info.interfaces {
+            if let Some(interface) = self.class_name("class", *index) && !interfaces.insert(interface) {
+                self.report("class", format!("Duplicate interface \"{}\"", interface));
+            }
+        }
+    }
+
+    fn check_field(&mut self, field: &'a FieldInfo, fields: &mut HashSet<(&'a str, &'a str)>) {
+        let name = self.utf8("field", field.name_index);
+        let descriptor = self.utf8("field", field.descriptor_index);
+        let location = format!("field {} {}", name.unwrap_or("?"), descriptor.unwrap_or("?"));
+        let location = location.as_str();
+        if let Some(name) = name {
+            self.check_name(location, name, NameKind::Field);
+        }
+        if let Some(descriptor) = descriptor {
+            self.check_field_descriptor(location, descriptor);
+        }
+        if let (Some(name), Some(descriptor)) = (name, descriptor) && !fields.insert((name, descriptor)) {
+            self.report(location, "Duplicate field");
+        }
+
+        let flags = field.access_flags;
+        let access = flags & (FieldFlags::ACC_PUBLIC | FieldFlags::ACC_PRIVATE | FieldFlags::ACC_PROTECTED);
+        if access.bits().count_ones() > 1 {
+            self.report(location, "A field can only be one of public, private and protected");
+        }
+        if flags.contains(FieldFlags::ACC_FINAL | FieldFlags::ACC_VOLATILE) {
+            self.report(location, "A field can't be both final and volatile");
+        }
+        if self.info.access_flags.contains(ClassFlags::ACC_INTERFACE)
+            && (flags & !FieldFlags::ACC_SYNTHETIC) != FieldFlags::ACC_PUBLIC | FieldFlags::ACC_STATIC | FieldFlags::ACC_FINAL {
+            self.report(location, "A field of an interface must be public, static and final");
+        }
+        if let (Some(index), Some(descriptor)) = (field.get_constant_value(), descriptor) {
+            let matches = match (self.constant(index), descriptor) {
+                (Some(ConstantPoolItem::CPIntegerInfo { .. }), "I" | "S" | "C" | "B" | "Z") => true,
+                (Some(ConstantPoolItem::CPLongInfo { .. }), "J") => true,
+                (Some(ConstantPoolItem::CPFloatInfo { .. }), "F") => true,
+                (Some(ConstantPoolItem::CPDoubleInfo { .. }), "D") => true,
+                (Some(ConstantPoolItem::CPStringInfo { .. }), "Ljava/lang/String;") => true,
+                other => false
+            };
+            if !matches {
+                self.report(location, format!("ConstantValue at index {} does not match the type of the field", index));
+            }
+        }
+        self.check_attributes(location, &field.attributes);
+    }
+
+    fn check_method(&mut self, method: &'a MethodInfo, methods: &mut HashSet<(&'a str, &'a str)>) {
+        let name = self.utf8("method", method.name_index);
+        let descriptor = self.utf8("method", method.descriptor_index);
+        let location = format!("method {}{}", name.unwrap_or("?"), descriptor.unwrap_or("?"));
+        let location = location.as_str();
+        let (name, descriptor) = match (name, descriptor) {
+            (Some(name), Some(descriptor)) => (name, descriptor),
+            other => return
+        };
+        self.check_name(location, name, NameKind::Method);
+        self.check_method_descriptor(location, name, descriptor);
+        if !methods.insert((name, descriptor)) {
+            self.report(location, "Duplicate method");
+        }
+        let flags = method.access_flags;
+        if is_method_descriptor(descriptor) {
+            let receiver = if flags.contains(MethodFlags::ACC_STATIC) { 0 } else { 1 };
+            if parameter_slots(descriptor) + receiver > 255 {
+                self.report(location, "A method can't have more than 255 parameter slots");
+            }
+        }
+
+        let is_interface = self.info.access_flags.contains(ClassFlags::ACC_INTERFACE);
+        let access = flags & (MethodFlags::ACC_PUBLIC | MethodFlags::ACC_PRIVATE | MethodFlags::ACC_PROTECTED);
+        if access.bits().count_ones() > 1 {
+            self.report(location, "A method can only be one of public, private and protected");
+        }
+        if name == "<clinit>" {
+            if descriptor != "()V" {
+                self.report(location, "The class initializer must have descriptor ()V");
+            }
+            if self.info.major_version >= 51 && !flags.contains(MethodFlags::ACC_STATIC) {
+                self.report(location, "The class initializer must be static");
+            }
+        } else if name == "<init>" {
+            if is_interface {
+                self.report(location, "An interface can't have a constructor");
+            }
+            let allowed = MethodFlags::ACC_PUBLIC | MethodFlags::ACC_PRIVATE | MethodFlags::ACC_PROTECTED | MethodFlags::ACC_VARARGS
+                | MethodFlags::ACC_STRICT | MethodFlags::ACC_SYNTHETIC;
+            if !allowed.contains(flags) {
+                self.report(location, "A constructor can only be public, private, protected, varargs, strict or synthetic");
+            }
+        } else if is_interface {
+            if self.info.major_version < 52 && !flags.contains(MethodFlags::ACC_PUBLIC | MethodFlags::ACC_ABSTRACT) {
+                self.report(location, "A method of an interface must be public and abstract before class file version 52");
+            }
+            if flags.intersects(MethodFlags::ACC_PROTECTED | MethodFlags::ACC_FINAL | MethodFlags::ACC_SYNCHRONIZED | MethodFlags::ACC_NATIVE) {
+                self.report(location, "A method of an interface can't be protected, final, synchronized or native");
+            }
+            if access != MethodFlags::ACC_PUBLIC && access != MethodFlags::ACC_PRIVATE {
+                self.report(location, "A method of an interface must be either public or private");
+            }
+        }
+        if flags.contains(MethodFlags::ACC_ABSTRACT) {
+            let mut illegal = MethodFlags::ACC_PRIVATE | MethodFlags::ACC_STATIC | MethodFlags::ACC_FINAL
+                | MethodFlags::ACC_SYNCHRONIZED | MethodFlags::ACC_NATIVE;
+            // Strict became the default in Java 17, which made the flag meaningless
+            if self.info.major_version < 61 {
+                illegal |= MethodFlags::ACC_STRICT;
+            }
+            if flags.intersects(illegal) {
+                self.report(location, "An abstract method can't be private, static, final, synchronized, native or strict");
+            }
+        }
+
+        let code_count = method.attributes.iter().filter(|attribute| matches!(attribute, AttributeInfo::Code(_))).count();
+        let needs_code = !flags.intersects(MethodFlags::ACC_ABSTRACT | MethodFlags::ACC_NATIVE);
+        if needs_code && code_count == 0 {
+            self.report(location, "Missing Code attribute in a method that is neither abstract nor native");
+        } else if !needs_code && code_count > 0 {
+            self.report(location, "An abstract or native method can't have a Code attribute");
+        }
+        if let Some(code) = method.get_code() {
+            if code.code.is_empty() || code.code.len() >= 65536 {
+                self.report(location, format!("Illegal code length {}", code.code.len()));
+            }
+            for entry in &code.exceptions {
+                if entry.catch_type != 0 {
+                    self.class_name(location, entry.catch_type);
+                }
+            }
+            self.check_attributes(location, &code.attributes);
+        }
+        for index in method.get_exceptions() {
+            self.class_name(location, *index);
+        }
+        self.check_attributes(location, &method.attributes);
+    }
+
+    /// Checks that attributes which may only appear once do, and what they point at.
+    fn check_attributes(&mut self, location: &str, attributes: &[AttributeInfo]) {
+        let mut seen = HashSet::new();
+        for attribute in attributes {
+            let name = match attribute {
+                AttributeInfo::Code(_) => "Code",
+                AttributeInfo::SourceFile(_) => "SourceFile",
+                AttributeInfo::ConstantValue(_) => "ConstantValue",
+                AttributeInfo::Exceptions(_) => "Exceptions",
+                AttributeInfo::BootstrapMethods(_) => "BootstrapMethods",
+                AttributeInfo::StackMapTable(_) => "StackMapTable",
+                other => continue
+            };
+            if !seen.insert(name) {
+                self.report(location, format!("Duplicate {} attribute", name));
+            }
+            match attribute {
+                AttributeInfo::SourceFile(source_file) => {
+                    self.utf8(location, source_file.source_file_index);
+                },
+                AttributeInfo::BootstrapMethods(bootstrap_methods) => {
+                    for bootstrap_method in &bootstrap_methods.methods {
+                        if !matches!(self.constant(bootstrap_method.bootstrap_method_ref), Some(ConstantPoolItem::CPMethodHandleInfo { .. })) {
+                            self.report(location, format!("Bootstrap method at index {} is not a MethodHandle constant", bootstrap_method.bootstrap_method_ref));
+                        }
+                        for argument in &bootstrap_method.bootstrap_arguments {
+                            let loadable = matches!(self.constant(*argument), Some(
+                                ConstantPoolItem::CPIntegerInfo { .. } | ConstantPoolItem::CPFloatInfo { .. } | ConstantPoolItem::CPLongInfo { .. } |
+                                ConstantPoolItem::CPDoubleInfo { .. } | ConstantPoolItem::CPClassInfo { .. } | ConstantPoolItem::CPStringInfo { .. } |
+                                ConstantPoolItem::CPMethodHandleInfo { .. } | ConstantPoolItem::CPMethodTypeInfo { .. } | ConstantPoolItem::CPDynamicInfo { .. }));
+                            if !loadable {
+                                self.report(location, format!("Bootstrap argument at index {} is not a loadable constant", argument));
+                            }
+                        }
+                    }
+                },
+                other => {}
+            }
+        }
+    }
+}
+
+/// Checks a name in its internal form (JVMS §4.2).
+fn is_valid_name(name: &str, kind: NameKind) -> bool {
+    if kind == NameKind::Class {
+        return name.split('/').all(|part| is_valid_name(part, NameKind::Field));
+    }
+    if kind == NameKind::Method && (name == "<init>" || name == "<clinit>") {
+        return true;
+    }
+    let illegal: &[char] = if kind == NameKind::Method { &['.', ';', '[', '/', '<', '>'] } else { &['.', ';', '[', '/'] };
+    !name.is_empty() && !name.contains(illegal)
+}
+
+/// Returns the rest of a descriptor after the field type it starts with, if it starts with a valid one.
+fn skip_field_type(descriptor: &str) -> Option<&str> {
+    let dimensions = descriptor.bytes().take_while(|byte| *byte == b'[').count();
+    if dimensions > 255 {
+        return None;
+    }
+    let rest = &descriptor[dimensions..];
+    match rest.as_bytes().first()? {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' => Some(&rest[1..]),
+        b'L' => {
+            let end = rest.find(';')?;
+            is_valid_name(&rest[1..end], NameKind::Class).then(|| &rest[end + 1..])
+        },
+        other => None
+    }
+}
+
+pub fn is_field_descriptor(descriptor: &str) -> bool {
+    skip_field_type(descriptor) == Some("")
+}
+
+pub fn is_method_descriptor(descriptor: &str) -> bool {
+    let mut rest = match descriptor.strip_prefix('(') {
+        Some(rest) => rest,
+        None => return false
+    };
+    while !rest.starts_with(')') {
+        rest = match skip_field_type(rest) {
+            Some(rest) => rest,
+            None => return false
+        };
+    }
+    rest == ")V" || is_field_descriptor(&rest[1..])
+}
+
+/// The number of local variables the parameters of a method take up, where longs and doubles take two.
+fn parameter_slots(descriptor: &str) -> usize {
+    let mut rest = &descriptor[1..];
+    let mut slots = 0;
+    while !rest.starts_with(')') {
+        slots += if rest.starts_with('J') || rest.starts_with('D') { 2 } else { 1 };
+        rest = skip_field_type(rest).unwrap();
+    }
+    slots
+}
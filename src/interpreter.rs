@@ -0,0 +1,1034 @@
+use std::sync::Arc;
+
+use crate::{class_loader::{ClassFlags, ConstantPoolItem, ExceptionTableEntry}, exceptions::{JvmResult, Throwable}, descriptor_parser::argument_descriptors, invokedynamic::resolve_call_site, method_handles::{invoke_method_handle, invoke_var_handle, is_signature_polymorphic, resolve_object_constant}, monitor::{enter_monitor, exit_monitor}, object::{ArrayData, Object, ObjectRef, Value}, opcodes::*, resolution::{resolve_any_method, resolve_class, resolve_field, resolve_interface_method, resolve_method, member_ref_descriptor, resolve_string, ResolvedField}, runtime_class::{Class, Method}, thread::JavaThread};
+
+// Deeper calls raise a StackOverflowError, before the native stack runs out
+const MAX_STACK_DEPTH: usize = 4096;
+// Frames beyond that for creating the StackOverflowError, whose class may still have to be initialized
+const STACK_RESERVE: usize = 512;
+
+struct Frame<'a> {
+    class: &'a Arc<Class>,
+    code: &'a [u8],
+    exception_table: &'a [ExceptionTableEntry],
+    locals: Vec<Value>,
+    stack: Vec<Value>,
+    pc: usize,
+    // Start of the instruction being executed
+    opcode_pc: usize,
+}
+
+impl Frame<'_> {
+    fn read_u8(&mut self) -> u8 {
+        self.pc += 1;
+        self.code[self.pc - 1]
+    }
+
+    fn read_i8(&mut self) -> i8 {
+        self.read_u8() as i8
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        self.pc += 2;
+        u16::from_be_bytes([self.code[self.pc - 2], self.code[self.pc - 1]])
+    }
+
+    fn read_i16(&mut self) -> i16 {
+        self.read_u16() as i16
+    }
+
+    fn read_i32(&mut self) -> i32 {
+        self.pc += 4;
+        i32::from_be_bytes(self.code[self.pc - 4..self.pc].try_into().unwrap())
+    }
+
+    fn push(&mut self, value: Value) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().unwrap()
+    }
+
+    fn pop_int(&mut self) -> i32 {
+        self.pop().as_int()
+    }
+
+    fn pop_long(&mut self) -> i64 {
+        self.pop().as_long()
+    }
+
+    fn pop_float(&mut self) -> f32 {
+        self.pop().as_float()
+    }
+
+    fn pop_double(&mut self) -> f64 {
+        self.pop().as_double()
+    }
+
+    fn pop_reference(&mut self) -> Option<ObjectRef> {
+        self.pop().as_reference().cloned()
+    }
+
+    fn load(&mut self, index: usize) {
+        self.stack.push(self.locals[index].clone());
+    }
+
+    fn store(&mut self, index: usize) {
+        let value = self.pop();
+        if value.is_category2() {
+            self.locals[index + 1] = Value::Top;
+        }
+        self.locals[index] = value;
+    }
+
+    /// Returns how many stack entries make up the given number of words, counting from the top.
+    fn entries_for_words(&self, words: usize, below: usize) -> usize {
+        let mut counted_words = 0;
+        let mut entries = 0;
+        while counted_words < words {
+            let value = &self.stack[self.stack.len() - 1 - below - entries];
+            counted_words += if value.is_category2() { 2 } else { 1 };
+            entries += 1;
+        }
+        entries
+    }
+
+    /// Implements the dup family: copies the top `words` words, and inserts them `skipped_words` deeper.
+    fn dup_words(&mut self, words: usize, skipped_words: usize) {
+        let copied = self.entries_for_words(words, 0);
+        let skipped = self.entries_for_words(skipped_words, copied);
+        let top = self.stack.len();
+        let values: Vec<Value> = self.stack[top - copied..].to_vec();
+        let position = top - copied - skipped;
+        self.stack.splice(position..position, values);
+    }
+
+    fn branch(&mut self, opcode_pc: usize, condition: bool) {
+        let offset = self.read_i16();
+        if condition {
+            self.pc = (opcode_pc as isize + offset as isize) as usize;
+        }
+    }
+
+    /// Skips the padding that aligns the operands of the switch instructions on 4 bytes.
+    fn align(&mut self) {
+        self.pc = (self.pc + 3) & !3;
+    }
+}
+
+fn null_pointer() -> Throwable {
+    Throwable::without_message("java/lang/NullPointerException")
+}
+
+fn check_index(array: &ArrayData, index: i32) -> JvmResult<usize> {
+    if index < 0 || index as usize >= array.len() {
+        return Err(Throwable::new("java/lang/ArrayIndexOutOfBoundsException",
+            format!("Index {} out of bounds for length {}", index, array.len())));
+    }
+    Ok(index as usize)
+}
+
+fn divide_by_zero() -> Throwable {
+    Throwable::new("java/lang/ArithmeticException", "/ by zero")
+}
+
+/// Compares floating point values for fcmpl, fcmpg, dcmpl and dcmpg. NaN gives `nan_result`.
+fn compare_floats<T: PartialOrd>(value1: T, value2: T, nan_result: i32) -> i32 {
+    if value1 > value2 {
+        1
+    } else if value1 == value2 {
+        0
+    } else if value1 < value2 {
+        -1
+    } else {
+        nan_result
+    }
+}
+
+fn array_class_name(component_name: &str) -> String {
+    if component_name.starts_with('[') {
+        format!("[{}", component_name)
+    } else {
+        format!("[L{};", component_name)
+    }
+}
+
+pub struct Interpreter {
+}
+
+impl Interpreter {
+    /// Runs a method. The arguments include the receiver for instance methods,
+    /// with one value per argument (longs and doubles are not split in two).
+    pub fn invoke(thread: &JavaThread, method: &Arc<Method>, args: Vec<Value>) -> JvmResult<Option<Value>> {
+        if method.is_abstract() {
+            return Err(Throwable::new("java/lang/AbstractMethodError", format!("{}.{}{}", method.class_name.replace('/', "."), method.name, method.descriptor)));
+        }
+        let max_depth = if thread.in_stack_reserve.get() { MAX_STACK_DEPTH + STACK_RESERVE } else { MAX_STACK_DEPTH };
+        if thread.stack.borrow().len() >= max_depth {
+            return Err(Throwable::without_message("java/lang/StackOverflowError"));
+        }
+        if !method.is_synchronized() {
+            return Self::run(thread, method, args);
+        }
+        // Synchronized methods hold the monitor of the receiver, or of the class for static methods
+        let lock = match method.is_static() {
+            true => method.class().mirror(thread)?,
+            false => args[0].as_reference().cloned().unwrap()
+        };
+        enter_monitor(&lock);
+        let result = Self::run(thread, method, args);
+        exit_monitor(&lock).and(result)
+    }
+
+    fn run(thread: &JavaThread, method: &Arc<Method>, args: Vec<Value>) -> JvmResult<Option<Value>> {
+        if method.is_native() {
+            thread.push_frame(method.clone());
+            let result = thread.vm.natives.invoke(thread, method, args);
+            thread.pop_frame();
+            return result;
+        }
+        let class = method.class();
+        let code = method.code(&class).unwrap();
+
+        let mut locals = Vec::with_capacity(code.max_locals as usize);
+        for arg in args {
+            let is_category2 = arg.is_category2();
+            locals.push(arg);
+            if is_category2 {
+                locals.push(Value::Top);
+            }
+        }
+        locals.resize(code.max_locals as usize, Value::Top);
+
+        let mut frame = Frame {
+            class: &class,
+            code: &code.code,
+            exception_table: &code.exceptions,
+            locals,
+            stack: Vec::with_capacity(code.max_stack as usize),
+            pc: 0,
+            opcode_pc: 0
+        };
+        thread.push_frame(method.clone());
+        let result = Self::execute(thread, &mut frame);
+        thread.pop_frame();
+        result
+    }
+
+    /// Calls an instance method from Rust code, selecting the implementation from the class of the receiver.
+    pub fn invoke_virtual_method(thread: &JavaThread, receiver: &ObjectRef, name: &str, descriptor: &str, mut args: Vec<Value>) -> JvmResult<Option<Value>> {
+        let method = receiver.class.vtable_index(name, descriptor)
+            .and_then(|index| receiver.class.vtable[index].clone())
+            .ok_or_else(|| Throwable::new("java/lang/NoSuchMethodError", format!("{}.{}{}", receiver.class.name.replace('/', "."), name, descriptor)))?;
+        args.insert(0, Value::Reference(Some(receiver.clone())));
+        Self::invoke(thread, &method, args)
+    }
+
+    /// Pops the arguments of a method off the operand stack and runs it, pushing the result.
+    fn invoke_from_frame(thread: &JavaThread, frame: &mut Frame, method: &Arc<Method>, args_count: usize) -> JvmResult<()> {
+        let args = frame.stack.split_off(frame.stack.len() - args_count);
+        if let Some(result) = Self::invoke(thread, method, args)? {
+            frame.push(result);
+        }
+        Ok(())
+    }
+
+    fn receiver(frame: &Frame, method: &Method) -> JvmResult<ObjectRef> {
+        frame.stack[frame.stack.len() - method.arg_count - 1].as_reference().cloned().ok_or_else(null_pointer)
+    }
+
+    /// Picks the implementation from a vtable slot, failing for abstract methods and default method conflicts.
+    fn select_from_vtable(receiver_class: &Class, resolved: &Method, vtable_index: Option<usize>) -> JvmResult<Arc<Method>> {
+        let description = || format!("{}.{}{}", receiver_class.name.replace('/', "."), resolved.name, resolved.descriptor);
+        let selected = vtable_index.and_then(|index| receiver_class.vtable[index].clone());
+        match (vtable_index, selected) {
+            (Some(_), Some(method)) if !method.is_abstract() => Ok(method),
+            (Some(_), None) => Err(Throwable::new("java/lang/IncompatibleClassChangeError", format!("Conflicting default methods: {}", description()))),
+            other => Err(Throwable::new("java/lang/AbstractMethodError", description()))
+        }
+    }
+
+    fn invoke_static(thread: &JavaThread, frame: &mut Frame, index: u16) -> JvmResult<()> {
+        let method = resolve_any_method(thread, frame.class, index)?;
+        if !method.is_static() {
+            return Err(Throwable::new("java/lang/IncompatibleClassChangeError", format!("Expected static method '{}.{}{}'", method.class_name.replace('/', "."), method.name, method.descriptor)));
+        }
+        if is_signature_polymorphic(&method) {
+            return Self::invoke_signature_polymorphic(thread, frame, &method, member_ref_descriptor(frame.class, index));
+        }
+        method.class().initialize(thread)?;
+        Self::invoke_from_frame(thread, frame, &method, method.arg_count)
+    }
+
+    fn invoke_special(thread: &JavaThread, frame: &mut Frame, index: u16) -> JvmResult<()> {
+        let resolved = resolve_any_method(thread, frame.class, index)?;
+        if resolved.is_static() {
+            return Err(Throwable::new("java/lang/IncompatibleClassChangeError", format!("Expecting non-static method '{}.{}{}'", resolved.class_name.replace('/', "."), resolved.name, resolved.descriptor)));
+        }
+        Self::receiver(frame, &resolved)?;
+        let current = frame.class;
+        // Calls to superclass methods start looking from the direct superclass (JVMS §6.5 invokespecial)
+        let resolved_class = resolved.class();
+        let selected = if resolved.name != "<init>"
+            && !resolved_class.is_interface()
+            && resolved_class.name != current.name
+            && current.is_subclass_of(&resolved_class.name)
+            && current.access_flags.contains(ClassFlags::ACC_SUPER) {
+            current.super_class.as_ref()
+                .and_then(|super_class| super_class.find_method_in_superclasses(&resolved.name, &resolved.descriptor))
+                .unwrap_or(resolved)
+        } else {
+            resolved
+        };
+        Self::invoke_from_frame(thread, frame, &selected, selected.arg_count + 1)
+    }
+
+    fn invoke_virtual(thread: &JavaThread, frame: &mut Frame, index: u16) -> JvmResult<()> {
+        let resolved = resolve_method(thread, frame.class, index)?;
+        if resolved.is_static() {
+            return Err(Throwable::new("java/lang/IncompatibleClassChangeError", format!("Expecting non-static method '{}.{}{}'", resolved.class_name.replace('/', "."), resolved.name, resolved.descriptor)));
+        }
+        if is_signature_polymorphic(&resolved) {
+            return Self::invoke_signature_polymorphic(thread, frame, &resolved, member_ref_descriptor(frame.class, index));
+        }
+        let receiver = Self::receiver(frame, &resolved)?;
+        let selected = if resolved.is_private() {
+            resolved
+        } else {
+            // Methods found in an interface have a slot in the implementing class, but it could be any slot
+            let vtable_index = match resolved.vtable_index {
+                Some(vtable_index) => Some(vtable_index),
+                None => receiver.class.vtable_index(&resolved.name, &resolved.descriptor)
+            };
+            Self::select_from_vtable(&receiver.class, &resolved, vtable_index)?
+        };
+        Self::invoke_from_frame(thread, frame, &selected, selected.arg_count + 1)
+    }
+
+    /// Calls a method like `MethodHandle.invokeExact` or `VarHandle.compareAndSet`, which takes the arguments
+    /// of the descriptor at the call site. The static ones, like `MethodHandle.linkToStatic`, have no receiver.
+    fn invoke_signature_polymorphic(thread: &JavaThread, frame: &mut Frame, resolved: &Method, descriptor: &str) -> JvmResult<()> {
+        let args_count = argument_descriptors(descriptor).len() + !resolved.is_static() as usize;
+        let args = frame.stack.split_off(frame.stack.len() - args_count);
+        if !resolved.is_static() && args[0].as_reference().is_none() {
+            return Err(null_pointer());
+        }
+        let result = match resolved.class_name.as_str() {
+            "java/lang/invoke/VarHandle" => invoke_var_handle(thread, &resolved.name, descriptor, args)?,
+            other => invoke_method_handle(thread, &resolved.name, descriptor, args)?
+        };
+        if let Some(result) = result {
+            frame.push(result);
+        }
+        Ok(())
+    }
+
+    fn invoke_interface(thread: &JavaThread, frame: &mut Frame, index: u16) -> JvmResult<()> {
+        let resolved = resolve_interface_method(thread, frame.class, index)?;
+        if resolved.is_static() {
+            return Err(Throwable::new("java/lang/IncompatibleClassChangeError", format!("Expecting non-static method '{}.{}{}'", resolved.class_name.replace('/', "."), resolved.name, resolved.descriptor)));
+        }
+        let receiver = Self::receiver(frame, &resolved)?;
+        let selected = if resolved.is_private() {
+            resolved
+        } else if resolved.vtable_index.is_some() {
+            // Public methods of java.lang.Object can be called through interfaces too
+            Self::select_from_vtable(&receiver.class, &resolved, resolved.vtable_index)?
+        } else {
+            let vtable_index = receiver.class.itable_slot(&resolved.class_name, resolved.index)
+                .ok_or_else(|| Throwable::new("java/lang/IncompatibleClassChangeError",
+                    format!("Class {} does not implement the requested interface {}", receiver.class.name.replace('/', "."), resolved.class_name.replace('/', "."))))?;
+            Self::select_from_vtable(&receiver.class, &resolved, vtable_index)?
+        };
+        Self::invoke_from_frame(thread, frame, &selected, selected.arg_count + 1)
+    }
+
+    fn invoke_dynamic(thread: &JavaThread, frame: &mut Frame, index: u16) -> JvmResult<()> {
+        let call_site = resolve_call_site(thread, frame.class, index)?;
+        let args = frame.stack.split_off(frame.stack.len() - call_site.arg_count());
+        if let Some(result) = call_site.invoke(thread, args)? {
+            frame.push(result);
+        }
+        Ok(())
+    }
+
+    fn resolve_static_field(thread: &JavaThread, frame: &Frame, index: u16) -> JvmResult<ResolvedField> {
+        let resolved = resolve_field(thread, frame.class, index)?;
+        let field = resolved.field();
+        if !field.is_static() {
+            return Err(Throwable::new("java/lang/IncompatibleClassChangeError", format!("Expected static field {}.{}", field.class_name.replace('/', "."), field.name)));
+        }
+        resolved.class.initialize(thread)?;
+        Ok(resolved)
+    }
+
+    fn resolve_instance_field(thread: &JavaThread, frame: &Frame, index: u16) -> JvmResult<ResolvedField> {
+        let resolved = resolve_field(thread, frame.class, index)?;
+        let field = resolved.field();
+        if field.is_static() {
+            return Err(Throwable::new("java/lang/IncompatibleClassChangeError", format!("Expected non-static field {}.{}", field.class_name.replace('/', "."), field.name)));
+        }
+        Ok(resolved)
+    }
+
+    /// Final fields can only be assigned by the class that declares them (JVMS §6.5 putfield and putstatic).
+    fn check_final_field_write(frame: &Frame, resolved: &ResolvedField) -> JvmResult<()> {
+        let field = resolved.field();
+        if field.is_final() && field.class_name != frame.class.name {
+            return Err(Throwable::new("java/lang/IllegalAccessError",
+                format!("Update to final field {}.{} attempted from a different class ({})", field.class_name.replace('/', "."), field.name, frame.class.name.replace('/', "."))));
+        }
+        Ok(())
+    }
+
+    fn new_array(thread: &JavaThread, class_name: &str, length: i32) -> JvmResult<ObjectRef> {
+        if length < 0 {
+            return Err(Throwable::new("java/lang/NegativeArraySizeException", length.to_string()));
+        }
+        let class = thread.vm.load_class(class_name)?;
+        Ok(Object::new_array(class, ArrayData::new(&class_name[1..], length as usize)))
+    }
+
+    fn new_multi_array(thread: &JavaThread, class_name: &str, lengths: &[i32]) -> JvmResult<ObjectRef> {
+        let array = Self::new_array(thread, class_name, lengths[0])?;
+        if lengths.len() > 1 {
+            let mut data = array.array();
+            for index in 0..data.len() {
+                let element = Self::new_multi_array(thread, &class_name[1..], &lengths[1..])?;
+                data.set(index, Value::Reference(Some(element)));
+            }
+        }
+        Ok(array)
+    }
+
+    fn load_constant(thread: &JavaThread, frame: &mut Frame, index: u16) -> JvmResult<()> {
+        let constant_pool = &frame.class.info.as_ref().unwrap().constant_pool;
+        let value = match &constant_pool.constants[index as usize] {
+            ConstantPoolItem::CPIntegerInfo { value } => Value::Int(*value),
+            ConstantPoolItem::CPFloatInfo { value } => Value::Float(*value),
+            ConstantPoolItem::CPLongInfo { value } => Value::Long(*value),
+            ConstantPoolItem::CPDoubleInfo { value } => Value::Double(*value),
+            ConstantPoolItem::CPStringInfo { .. } => Value::Reference(Some(resolve_string(thread, frame.class, index)?)),
+            ConstantPoolItem::CPClassInfo { .. } => Value::Reference(Some(resolve_class(thread, frame.class, index)?.mirror(thread)?)),
+            ConstantPoolItem::CPMethodTypeInfo { .. } |
+            ConstantPoolItem::CPMethodHandleInfo { .. } |
+            ConstantPoolItem::CPDynamicInfo { .. } => resolve_object_constant(thread, frame.class, index)?,
+            other => panic!("Unsupported constant type at index {} for ldc in {}", index, frame.class.name)
+        };
+        frame.push(value);
+        Ok(())
+    }
+
+    /// Returns the handler for an exception thrown at the current instruction, searching the exception table in order.
+    fn find_handler(thread: &JavaThread, frame: &Frame, exception: &ObjectRef) -> JvmResult<Option<usize>> {
+        for entry in frame.exception_table {
+            if frame.opcode_pc < entry.start_pc as usize || frame.opcode_pc >= entry.end_pc as usize {
+                continue;
+            }
+            // Handlers without a catch type are used for finally blocks, and catch everything
+            if entry.catch_type == 0 || exception.class.is_assignable_to(&*resolve_class(thread, frame.class, entry.catch_type)?) {
+                return Ok(Some(entry.handler_pc as usize));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs the code of a frame, and jumps to the matching handler when an exception gets thrown.
+    /// Exceptions without a handler in this method are passed on to the caller.
+    fn execute(thread: &JavaThread, frame: &mut Frame) -> JvmResult<Option<Value>> {
+        loop {
+            match Self::execute_instructions(thread, frame) {
+                Err(throwable) => {
+                    let exception = throwable.into_object(thread)?;
+                    match Self::find_handler(thread, frame, &exception)? {
+                        Some(handler_pc) => {
+                            frame.stack.clear();
+                            frame.push(Value::Reference(Some(exception)));
+                            frame.pc = handler_pc;
+                        },
+                        None => return Err(Throwable::Object(exception))
+                    }
+                },
+                result => return result
+            }
+        }
+    }
+
+    fn execute_instructions(thread: &JavaThread, frame: &mut Frame) -> JvmResult<Option<Value>> {
+        loop {
+            let opcode_pc = frame.pc;
+            frame.opcode_pc = opcode_pc;
+            thread.set_pc(opcode_pc);
+            let opcode = frame.read_u8();
+            match opcode {
+                NOP => {},
+                ACONST_NULL => frame.push(Value::null()),
+                ICONST_M1..=ICONST_5 => frame.push(Value::Int(opcode as i32 - ICONST_0 as i32)),
+                LCONST_0 | LCONST_1 => frame.push(Value::Long((opcode - LCONST_0) as i64)),
+                FCONST_0..=FCONST_2 => frame.push(Value::Float((opcode - FCONST_0) as f32)),
+                DCONST_0 | DCONST_1 => frame.push(Value::Double((opcode - DCONST_0) as f64)),
+                BIPUSH => {
+                    let value = frame.read_i8();
+                    frame.push(Value::Int(value as i32));
+                },
+                SIPUSH => {
+                    let value = frame.read_i16();
+                    frame.push(Value::Int(value as i32));
+                },
+                LDC => {
+                    let index = frame.read_u8() as u16;
+                    Self::load_constant(thread, frame, index)?;
+                },
+                LDC_W | LDC2_W => {
+                    let index = frame.read_u16();
+                    Self::load_constant(thread, frame, index)?;
+                },
+
+                ILOAD | LLOAD | FLOAD | DLOAD | ALOAD => {
+                    let index = frame.read_u8() as usize;
+                    frame.load(index);
+                },
+                ILOAD_0..=ILOAD_3 => frame.load((opcode - ILOAD_0) as usize),
+                LLOAD_0..=LLOAD_3 => frame.load((opcode - LLOAD_0) as usize),
+                FLOAD_0..=FLOAD_3 => frame.load((opcode - FLOAD_0) as usize),
+                DLOAD_0..=DLOAD_3 => frame.load((opcode - DLOAD_0) as usize),
+                ALOAD_0..=ALOAD_3 => frame.load((opcode - ALOAD_0) as usize),
+                ISTORE | LSTORE | FSTORE | DSTORE | ASTORE => {
+                    let index = frame.read_u8() as usize;
+                    frame.store(index);
+                },
+                ISTORE_0..=ISTORE_3 => frame.store((opcode - ISTORE_0) as usize),
+                LSTORE_0..=LSTORE_3 => frame.store((opcode - LSTORE_0) as usize),
+                FSTORE_0..=FSTORE_3 => frame.store((opcode - FSTORE_0) as usize),
+                DSTORE_0..=DSTORE_3 => frame.store((opcode - DSTORE_0) as usize),
+                ASTORE_0..=ASTORE_3 => frame.store((opcode - ASTORE_0) as usize),
+
+                IALOAD | LALOAD | FALOAD | DALOAD | AALOAD | BALOAD | CALOAD | SALOAD => {
+                    let index = frame.pop_int();
+                    let array = frame.pop_reference().ok_or_else(null_pointer)?;
+                    let data = array.array();
+                    let value = data.get(check_index(&data, index)?);
+                    frame.push(value);
+                },
+                IASTORE | LASTORE | FASTORE | DASTORE | AASTORE | BASTORE | CASTORE | SASTORE => {
+                    let value = frame.pop();
+                    let index = frame.pop_int();
+                    let array = frame.pop_reference().ok_or_else(null_pointer)?;
+                    let mut data = array.array();
+                    let index = check_index(&data, index)?;
+                    // Arrays are covariant, so the element has to be checked against the actual component type
+                    match (opcode, &value) {
+                        (AASTORE, Value::Reference(Some(element))) if !element.class.is_assignable_to(array.class.component.as_ref().unwrap()) => {
+                            return Err(Throwable::new("java/lang/ArrayStoreException", element.class.name.replace('/', ".")));
+                        },
+                        other => {}
+                    }
+                    data.set(index, value);
+                },
+
+                POP => { frame.pop(); },
+                POP2 => {
+                    if !frame.pop().is_category2() {
+                        frame.pop();
+                    }
+                },
+                DUP => frame.dup_words(1, 0),
+                DUP_X1 => frame.dup_words(1, 1),
+                DUP_X2 => frame.dup_words(1, 2),
+                DUP2 => frame.dup_words(2, 0),
+                DUP2_X1 => frame.dup_words(2, 1),
+                DUP2_X2 => frame.dup_words(2, 2),
+                SWAP => {
+                    let length = frame.stack.len();
+                    frame.stack.swap(length - 1, length - 2);
+                },
+
+                IADD => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value1.wrapping_add(value2)));
+                },
+                LADD => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    frame.push(Value::Long(value1.wrapping_add(value2)));
+                },
+                FADD => {
+                    let (value2, value1) = (frame.pop_float(), frame.pop_float());
+                    frame.push(Value::Float(value1 + value2));
+                },
+                DADD => {
+                    let (value2, value1) = (frame.pop_double(), frame.pop_double());
+                    frame.push(Value::Double(value1 + value2));
+                },
+                ISUB => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value1.wrapping_sub(value2)));
+                },
+                LSUB => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    frame.push(Value::Long(value1.wrapping_sub(value2)));
+                },
+                FSUB => {
+                    let (value2, value1) = (frame.pop_float(), frame.pop_float());
+                    frame.push(Value::Float(value1 - value2));
+                },
+                DSUB => {
+                    let (value2, value1) = (frame.pop_double(), frame.pop_double());
+                    frame.push(Value::Double(value1 - value2));
+                },
+                IMUL => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value1.wrapping_mul(value2)));
+                },
+                LMUL => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    frame.push(Value::Long(value1.wrapping_mul(value2)));
+                },
+                FMUL => {
+                    let (value2, value1) = (frame.pop_float(), frame.pop_float());
+                    frame.push(Value::Float(value1 * value2));
+                },
+                DMUL => {
+                    let (value2, value1) = (frame.pop_double(), frame.pop_double());
+                    frame.push(Value::Double(value1 * value2));
+                },
+                IDIV => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    if value2 == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    frame.push(Value::Int(value1.wrapping_div(value2)));
+                },
+                LDIV => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    if value2 == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    frame.push(Value::Long(value1.wrapping_div(value2)));
+                },
+                FDIV => {
+                    let (value2, value1) = (frame.pop_float(), frame.pop_float());
+                    frame.push(Value::Float(value1 / value2));
+                },
+                DDIV => {
+                    let (value2, value1) = (frame.pop_double(), frame.pop_double());
+                    frame.push(Value::Double(value1 / value2));
+                },
+                IREM => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    if value2 == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    frame.push(Value::Int(value1.wrapping_rem(value2)));
+                },
+                LREM => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    if value2 == 0 {
+                        return Err(divide_by_zero());
+                    }
+                    frame.push(Value::Long(value1.wrapping_rem(value2)));
+                },
+                FREM => {
+                    let (value2, value1) = (frame.pop_float(), frame.pop_float());
+                    frame.push(Value::Float(value1 % value2));
+                },
+                DREM => {
+                    let (value2, value1) = (frame.pop_double(), frame.pop_double());
+                    frame.push(Value::Double(value1 % value2));
+                },
+                INEG => {
+                    let value = frame.pop_int();
+                    frame.push(Value::Int(value.wrapping_neg()));
+                },
+                LNEG => {
+                    let value = frame.pop_long();
+                    frame.push(Value::Long(value.wrapping_neg()));
+                },
+                FNEG => {
+                    let value = frame.pop_float();
+                    frame.push(Value::Float(-value));
+                },
+                DNEG => {
+                    let value = frame.pop_double();
+                    frame.push(Value::Double(-value));
+                },
+                ISHL => {
+                    let (shift, value) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value.wrapping_shl(shift as u32)));
+                },
+                LSHL => {
+                    let (shift, value) = (frame.pop_int(), frame.pop_long());
+                    frame.push(Value::Long(value.wrapping_shl(shift as u32)));
+                },
+                ISHR => {
+                    let (shift, value) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value.wrapping_shr(shift as u32)));
+                },
+                LSHR => {
+                    let (shift, value) = (frame.pop_int(), frame.pop_long());
+                    frame.push(Value::Long(value.wrapping_shr(shift as u32)));
+                },
+                IUSHR => {
+                    let (shift, value) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int((value as u32).wrapping_shr(shift as u32) as i32));
+                },
+                LUSHR => {
+                    let (shift, value) = (frame.pop_int(), frame.pop_long());
+                    frame.push(Value::Long((value as u64).wrapping_shr(shift as u32) as i64));
+                },
+                IAND => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value1 & value2));
+                },
+                LAND => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    frame.push(Value::Long(value1 & value2));
+                },
+                IOR => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value1 | value2));
+                },
+                LOR => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    frame.push(Value::Long(value1 | value2));
+                },
+                IXOR => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    frame.push(Value::Int(value1 ^ value2));
+                },
+                LXOR => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    frame.push(Value::Long(value1 ^ value2));
+                },
+                IINC => {
+                    let index = frame.read_u8() as usize;
+                    let increment = frame.read_i8() as i32;
+                    let value = frame.locals[index].as_int();
+                    frame.locals[index] = Value::Int(value.wrapping_add(increment));
+                },
+
+                I2L => {
+                    let value = frame.pop_int();
+                    frame.push(Value::Long(value as i64));
+                },
+                I2F => {
+                    let value = frame.pop_int();
+                    frame.push(Value::Float(value as f32));
+                },
+                I2D => {
+                    let value = frame.pop_int();
+                    frame.push(Value::Double(value as f64));
+                },
+                L2I => {
+                    let value = frame.pop_long();
+                    frame.push(Value::Int(value as i32));
+                },
+                L2F => {
+                    let value = frame.pop_long();
+                    frame.push(Value::Float(value as f32));
+                },
+                L2D => {
+                    let value = frame.pop_long();
+                    frame.push(Value::Double(value as f64));
+                },
+                // Float to integer casts in Rust saturate and turn NaN into 0, just like Java
+                F2I => {
+                    let value = frame.pop_float();
+                    frame.push(Value::Int(value as i32));
+                },
+                F2L => {
+                    let value = frame.pop_float();
+                    frame.push(Value::Long(value as i64));
+                },
+                F2D => {
+                    let value = frame.pop_float();
+                    frame.push(Value::Double(value as f64));
+                },
+                D2I => {
+                    let value = frame.pop_double();
+                    frame.push(Value::Int(value as i32));
+                },
+                D2L => {
+                    let value = frame.pop_double();
+                    frame.push(Value::Long(value as i64));
+                },
+                D2F => {
+                    let value = frame.pop_double();
+                    frame.push(Value::Float(value as f32));
+                },
+                I2B => {
+                    let value = frame.pop_int();
+                    frame.push(Value::Int(value as i8 as i32));
+                },
+                I2C => {
+                    let value = frame.pop_int();
+                    frame.push(Value::Int(value as u16 as i32));
+                },
+                I2S => {
+                    let value = frame.pop_int();
+                    frame.push(Value::Int(value as i16 as i32));
+                },
+
+                LCMP => {
+                    let (value2, value1) = (frame.pop_long(), frame.pop_long());
+                    frame.push(Value::Int(value1.cmp(&value2) as i32));
+                },
+                FCMPL | FCMPG => {
+                    let (value2, value1) = (frame.pop_float(), frame.pop_float());
+                    let nan_result = if opcode == FCMPG { 1 } else { -1 };
+                    frame.push(Value::Int(compare_floats(value1, value2, nan_result)));
+                },
+                DCMPL | DCMPG => {
+                    let (value2, value1) = (frame.pop_double(), frame.pop_double());
+                    let nan_result = if opcode == DCMPG { 1 } else { -1 };
+                    frame.push(Value::Int(compare_floats(value1, value2, nan_result)));
+                },
+                IFEQ..=IFLE => {
+                    let value = frame.pop_int();
+                    let condition = match opcode {
+                        IFEQ => value == 0,
+                        IFNE => value != 0,
+                        IFLT => value < 0,
+                        IFGE => value >= 0,
+                        IFGT => value > 0,
+                        _ => value <= 0
+                    };
+                    frame.branch(opcode_pc, condition);
+                },
+                IF_ICMPEQ..=IF_ICMPLE => {
+                    let (value2, value1) = (frame.pop_int(), frame.pop_int());
+                    let condition = match opcode {
+                        IF_ICMPEQ => value1 == value2,
+                        IF_ICMPNE => value1 != value2,
+                        IF_ICMPLT => value1 < value2,
+                        IF_ICMPGE => value1 >= value2,
+                        IF_ICMPGT => value1 > value2,
+                        _ => value1 <= value2
+                    };
+                    frame.branch(opcode_pc, condition);
+                },
+                IF_ACMPEQ | IF_ACMPNE => {
+                    let (value2, value1) = (frame.pop_reference(), frame.pop_reference());
+                    let same = match (&value1, &value2) {
+                        (Some(object1), Some(object2)) => Arc::ptr_eq(object1, object2),
+                        (None, None) => true,
+                        other => false
+                    };
+                    frame.branch(opcode_pc, same == (opcode == IF_ACMPEQ));
+                },
+                IFNULL | IFNONNULL => {
+                    let value = frame.pop_reference();
+                    frame.branch(opcode_pc, value.is_none() == (opcode == IFNULL));
+                },
+                GOTO => frame.branch(opcode_pc, true),
+                GOTO_W => {
+                    let offset = frame.read_i32();
+                    frame.pc = (opcode_pc as isize + offset as isize) as usize;
+                },
+                JSR => {
+                    frame.push(Value::ReturnAddress(frame.pc + 2));
+                    frame.branch(opcode_pc, true);
+                },
+                JSR_W => {
+                    let offset = frame.read_i32();
+                    frame.push(Value::ReturnAddress(frame.pc));
+                    frame.pc = (opcode_pc as isize + offset as isize) as usize;
+                },
+                RET => {
+                    let index = frame.read_u8() as usize;
+                    match frame.locals[index] {
+                        Value::ReturnAddress(address) => frame.pc = address,
+                        ref other => panic!("Expected returnAddress, found {:?}", other)
+                    }
+                },
+                TABLESWITCH => {
+                    let index = frame.pop_int();
+                    frame.align();
+                    let default = frame.read_i32();
+                    let low = frame.read_i32();
+                    let high = frame.read_i32();
+                    let offset = if index < low || index > high {
+                        default
+                    } else {
+                        frame.pc += (index - low) as usize * 4;
+                        frame.read_i32()
+                    };
+                    frame.pc = (opcode_pc as isize + offset as isize) as usize;
+                },
+                LOOKUPSWITCH => {
+                    let key = frame.pop_int();
+                    frame.align();
+                    let default = frame.read_i32();
+                    let pairs_count = frame.read_i32();
+                    let mut offset = default;
+                    for _pair in 0..pairs_count {
+                        let pair_key = frame.read_i32();
+                        let pair_offset = frame.read_i32();
+                        if pair_key == key {
+                            offset = pair_offset;
+                            break;
+                        }
+                    }
+                    frame.pc = (opcode_pc as isize + offset as isize) as usize;
+                },
+                IRETURN | LRETURN | FRETURN | DRETURN | ARETURN => return Ok(Some(frame.pop())),
+                RETURN => return Ok(None),
+
+                GETSTATIC => {
+                    let index = frame.read_u16();
+                    let resolved = Self::resolve_static_field(thread, frame, index)?;
+                    let value = resolved.class.static_values.lock().unwrap()[resolved.field().slot].clone();
+                    frame.push(value);
+                },
+                PUTSTATIC => {
+                    let index = frame.read_u16();
+                    let resolved = Self::resolve_static_field(thread, frame, index)?;
+                    Self::check_final_field_write(frame, &resolved)?;
+                    let value = frame.pop();
+                    resolved.class.static_values.lock().unwrap()[resolved.field().slot] = value;
+                },
+                GETFIELD => {
+                    let index = frame.read_u16();
+                    let resolved = Self::resolve_instance_field(thread, frame, index)?;
+                    let object = frame.pop_reference().ok_or_else(null_pointer)?;
+                    let value = object.fields()[resolved.field().slot].clone();
+                    frame.push(value);
+                },
+                PUTFIELD => {
+                    let index = frame.read_u16();
+                    let resolved = Self::resolve_instance_field(thread, frame, index)?;
+                    Self::check_final_field_write(frame, &resolved)?;
+                    let value = frame.pop();
+                    let object = frame.pop_reference().ok_or_else(null_pointer)?;
+                    object.fields()[resolved.field().slot] = value;
+                },
+
+                INVOKEVIRTUAL => {
+                    let index = frame.read_u16();
+                    Self::invoke_virtual(thread, frame, index)?;
+                },
+                INVOKESPECIAL => {
+                    let index = frame.read_u16();
+                    Self::invoke_special(thread, frame, index)?;
+                },
+                INVOKESTATIC => {
+                    let index = frame.read_u16();
+                    Self::invoke_static(thread, frame, index)?;
+                },
+                INVOKEINTERFACE => {
+                    let index = frame.read_u16();
+                    // The count and the zero byte are only there for historical reasons
+                    frame.pc += 2;
+                    Self::invoke_interface(thread, frame, index)?;
+                },
+                INVOKEDYNAMIC => {
+                    let index = frame.read_u16();
+                    // Followed by two zero bytes
+                    frame.pc += 2;
+                    Self::invoke_dynamic(thread, frame, index)?;
+                },
+
+                NEW => {
+                    let index = frame.read_u16();
+                    let class = resolve_class(thread, frame.class, index)?;
+                    if class.is_interface() || class.access_flags.contains(ClassFlags::ACC_ABSTRACT) {
+                        return Err(Throwable::new("java/lang/InstantiationError", class.name.replace('/', ".")));
+                    }
+                    class.initialize(thread)?;
+                    frame.push(Value::Reference(Some(Object::new_instance(class))));
+                },
+                NEWARRAY => {
+                    let array_type = frame.read_u8();
+                    let length = frame.pop_int();
+                    let class_name = match array_type {
+                        T_BOOLEAN => "[Z",
+                        T_CHAR => "[C",
+                        T_FLOAT => "[F",
+                        T_DOUBLE => "[D",
+                        T_BYTE => "[B",
+                        T_SHORT => "[S",
+                        T_INT => "[I",
+                        T_LONG => "[J",
+                        other => panic!("Invalid array type {} for newarray", array_type)
+                    };
+                    let array = Self::new_array(thread, class_name, length)?;
+                    frame.push(Value::Reference(Some(array)));
+                },
+                ANEWARRAY => {
+                    let index = frame.read_u16();
+                    let length = frame.pop_int();
+                    let component = resolve_class(thread, frame.class, index)?;
+                    let array = Self::new_array(thread, &array_class_name(&component.name), length)?;
+                    frame.push(Value::Reference(Some(array)));
+                },
+                MULTIANEWARRAY => {
+                    let index = frame.read_u16();
+                    let dimensions = frame.read_u8() as usize;
+                    let lengths: Vec<i32> = frame.stack.split_off(frame.stack.len() - dimensions)
+                        .iter()
+                        .map(Value::as_int)
+                        .collect();
+                    if let Some(length) = lengths.iter().find(|length| **length < 0) {
+                        return Err(Throwable::new("java/lang/NegativeArraySizeException", length.to_string()));
+                    }
+                    let class = resolve_class(thread, frame.class, index)?;
+                    let array = Self::new_multi_array(thread, &class.name, &lengths)?;
+                    frame.push(Value::Reference(Some(array)));
+                },
+                CHECKCAST => {
+                    let index = frame.read_u16();
+                    if let Some(object) = frame.stack.last().unwrap().as_reference() {
+                        let class = resolve_class(thread, frame.class, index)?;
+                        if !object.class.is_assignable_to(&class) {
+                            return Err(Throwable::new("java/lang/ClassCastException",
+                                format!("class {} cannot be cast to class {}", object.class.name.replace('/', "."), class.name.replace('/', "."))));
+                        }
+                    }
+                },
+                INSTANCEOF => {
+                    let index = frame.read_u16();
+                    let result = match frame.pop_reference() {
+                        Some(object) => {
+                            let class = resolve_class(thread, frame.class, index)?;
+                            object.class.is_assignable_to(&class)
+                        },
+                        None => false
+                    };
+                    frame.push(Value::Int(result as i32));
+                },
+                MONITORENTER => enter_monitor(&frame.pop_reference().ok_or_else(null_pointer)?),
+                MONITOREXIT => exit_monitor(&frame.pop_reference().ok_or_else(null_pointer)?)?,
+                ATHROW => {
+                    let exception = frame.pop_reference().ok_or_else(null_pointer)?;
+                    return Err(Throwable::Object(exception));
+                },
+                ARRAYLENGTH => {
+                    let array = frame.pop_reference().ok_or_else(null_pointer)?;
+                    let length = array.array().len();
+                    frame.push(Value::Int(length as i32));
+                },
+
+                WIDE => {
+                    let opcode = frame.read_u8();
+                    let index = frame.read_u16() as usize;
+                    match opcode {
+                        ILOAD | LLOAD | FLOAD | DLOAD | ALOAD => frame.load(index),
+                        ISTORE | LSTORE | FSTORE | DSTORE | ASTORE => frame.store(index),
+                        IINC => {
+                            let increment = frame.read_i16() as i32;
+                            let value = frame.locals[index].as_int();
+                            frame.locals[index] = Value::Int(value.wrapping_add(increment));
+                        },
+                        RET => match frame.locals[index] {
+                            Value::ReturnAddress(address) => frame.pc = address,
+                            ref other => panic!("Expected returnAddress, found {:?}", other)
+                        },
+                        other => panic!("Invalid opcode {} after wide", opcode_name(opcode))
+                    }
+                },
+
+                other => panic!("Unsupported opcode {} at pc {} in {}", opcode_name(opcode), opcode_pc, frame.class.name)
+            }
+        }
+    }
+}
@@ -0,0 +1,483 @@
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+
+use time::OffsetDateTime;
+
+use crate::{class_loader::{ClassFlags, ClassLoader, ConstantPool, ConstantPoolItem, FieldFlags, MethodFlags}, descriptor_parser::argument_descriptors, exceptions::{JvmResult, Throwable}, interpreter::Interpreter, natives::reference_arg, object::{Object, ObjectRef, Value}, reflection::{box_value, widen, WRAPPER_CLASSES}, resolution::{resolve_any_method, resolve_field, ResolvedEntry, ResolvedField}, runtime_class::{Class, Method}, strings::{new_java_string_from_utf16, to_utf16}, stub_library::StubClass, thread::JavaThread};
+
+// Kinds of method handles (JVMS §5.4.3.5)
+const REF_GET_FIELD: u8          = 1;
+const REF_INVOKE_VIRTUAL: u8     = 5;
+const REF_INVOKE_STATIC: u8      = 6;
+const REF_INVOKE_SPECIAL: u8     = 7;
+const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+const REF_INVOKE_INTERFACE: u8   = 9;
+
+// Flags of LambdaMetafactory.altMetafactory
+const FLAG_SERIALIZABLE: i32 = 1;
+const FLAG_MARKERS: i32      = 2;
+const FLAG_BRIDGES: i32      = 4;
+
+// Where the recipe of StringConcatFactory.makeConcatWithConstants takes an argument, or the next constant
+const TAG_ARGUMENT: char = '\u{1}';
+const TAG_CONSTANT: char = '\u{2}';
+
+// Number in the name of the next lambda class, like `Main$$Lambda$1`
+static LAMBDA_NUMBER: AtomicU64 = AtomicU64::new(1);
+
+/// A linked invokedynamic call site. Instead of running bootstrap methods, the VM knows the ones that javac
+/// uses, and does what the call sites they return would do.
+pub enum CallSite {
+    /// LambdaMetafactory: creates an instance of a lambda class, which holds the captured arguments.
+    /// Lambdas that capture nothing always give the same instance.
+    Lambda { class: Arc<Class>, instance: Option<ObjectRef> },
+    /// StringConcatFactory: concatenates the arguments as String.valueOf converts them.
+    Concat { recipe: Vec<ConcatPart>, argument_types: Vec<String> },
+    /// ObjectMethods: the toString, hashCode or equals method of a record, from its components.
+    RecordMethod { name: String, record: Arc<Class>, component_names: Vec<String>, components: Vec<ResolvedField> }
+}
+
+pub enum ConcatPart {
+    Text(Vec<u16>),
+    Argument
+}
+
+/// What the functional interface method of a lambda class calls.
+pub struct Lambda {
+    kind: u8,
+    target: Arc<Method>,
+    // The functional interface method type for this lambda, like `(Ljava/lang/String;)Ljava/lang/Integer;`
+    instantiated_type: String
+}
+
+impl Lambda {
+    /// The parameters of the target, including the receiver of instance methods.
+    fn target_parameter_types(&self) -> Vec<String> {
+        let mut types = Vec::new();
+        if matches!(self.kind, REF_INVOKE_VIRTUAL | REF_INVOKE_SPECIAL | REF_INVOKE_INTERFACE) {
+            types.push(format!("L{};", self.target.class_name));
+        }
+        types.extend(argument_descriptors(&self.target.descriptor).into_iter().map(String::from));
+        types
+    }
+
+    fn target_return_type(&self) -> String {
+        match self.kind {
+            REF_NEW_INVOKE_SPECIAL => format!("L{};", self.target.class_name),
+            other => self.target.return_descriptor().to_string()
+        }
+    }
+
+    fn invoke_target(&self, thread: &JavaThread, mut args: Vec<Value>) -> JvmResult<Option<Value>> {
+        match self.kind {
+            REF_INVOKE_STATIC => {
+                self.target.class().initialize(thread)?;
+                Interpreter::invoke(thread, &self.target, args)
+            },
+            REF_INVOKE_VIRTUAL | REF_INVOKE_INTERFACE if !self.target.is_private() => {
+                let receiver = reference_arg(&args, 0)?;
+                let selected = receiver.class.vtable_index(&self.target.name, &self.target.descriptor)
+                    .and_then(|index| receiver.class.vtable[index].clone())
+                    .unwrap_or_else(|| self.target.clone());
+                Interpreter::invoke(thread, &selected, args)
+            },
+            REF_NEW_INVOKE_SPECIAL => {
+                let class = self.target.class();
+                class.initialize(thread)?;
+                let object = Object::new_instance(class);
+                args.insert(0, Value::Reference(Some(object.clone())));
+                Interpreter::invoke(thread, &self.target, args)?;
+                Ok(Some(Value::Reference(Some(object))))
+            },
+            other => {
+                reference_arg(&args, 0)?;
+                Interpreter::invoke(thread, &self.target, args)
+            }
+        }
+    }
+}
+
+impl CallSite {
+    /// The number of values the call site takes off the operand stack.
+    pub fn arg_count(&self) -> usize {
+        match self {
+            CallSite::Lambda { class, .. } => class.fields.len(),
+            CallSite::Concat { argument_types, .. } => argument_types.len(),
+            CallSite::RecordMethod { name, .. } if name == "equals" => 2,
+            CallSite::RecordMethod { .. } => 1
+        }
+    }
+
+    pub fn invoke(&self, thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+        match self {
+            CallSite::Lambda { instance: Some(instance), .. } => Ok(Some(Value::Reference(Some(instance.clone())))),
+            CallSite::Lambda { class, instance: None } => {
+                let object = Object::new_instance(class.clone());
+                object.fields().clone_from_slice(&args);
+                Ok(Some(Value::Reference(Some(object))))
+            },
+            CallSite::Concat { recipe, argument_types } => {
+                let mut chars = Vec::new();
+                let mut arguments = args.iter().zip(argument_types);
+                for part in recipe {
+                    match part {
+                        ConcatPart::Text(text) => chars.extend(text),
+                        ConcatPart::Argument => {
+                            let (value, descriptor) = arguments.next().unwrap();
+                            chars.extend(string_value_of(thread, value, descriptor)?);
+                        }
+                    }
+                }
+                Ok(Some(Value::Reference(Some(new_java_string_from_utf16(thread, &chars)?))))
+            },
+            CallSite::RecordMethod { name, record, component_names, components } => {
+                let this = reference_arg(&args, 0)?;
+                let values: Vec<(Value, &str)> = components.iter()
+                    .map(|component| (this.fields()[component.field().slot].clone(), component.field().descriptor.as_str()))
+                    .collect();
+                match name.as_str() {
+                    "toString" => record_to_string(thread, record, component_names, &values),
+                    "hashCode" => record_hash_code(thread, &values),
+                    other => record_equals(thread, record, components, &values, args[1].as_reference())
+                }
+            }
+        }
+    }
+}
+
+fn is_reference(descriptor: &str) -> bool {
+    matches!(descriptor.as_bytes()[0], b'L' | b'[')
+}
+
+fn return_descriptor(descriptor: &str) -> &str {
+    &descriptor[descriptor.find(')').unwrap() + 1..]
+}
+
+/// Returns the class, name and descriptor of a method or field reference, without resolving it.
+fn member_ref(constant_pool: &ConstantPool, index: u16) -> (&str, &str, &str) {
+    let (class_index, name_and_type_index) = match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } |
+        ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } |
+        ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } => (*class_index, *name_and_type_index),
+        other => panic!("No member reference found in constantpool at index: {}", index)
+    };
+    let (name_index, descriptor_index) = constant_pool.get_name_and_type(name_and_type_index);
+    (constant_pool.get_string(constant_pool.get_class_info(class_index)), constant_pool.get_string(name_index), constant_pool.get_string(descriptor_index))
+}
+
+fn method_handle(constant_pool: &ConstantPool, index: u16) -> (u8, u16) {
+    match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index } => (*reference_kind, *reference_index),
+        other => panic!("No method handle found in constantpool at index: {}", index)
+    }
+}
+
+fn method_type(constant_pool: &ConstantPool, index: u16) -> &str {
+    match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPMethodTypeInfo { descriptor_index } => constant_pool.get_string(*descriptor_index),
+        other => panic!("No method type found in constantpool at index: {}", index)
+    }
+}
+
+/// Returns the text of a static argument that is a String or a number, as a recipe constant.
+fn constant_text(constant_pool: &ConstantPool, index: u16) -> String {
+    match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPStringInfo { string_index } => constant_pool.get_string(*string_index).to_string(),
+        ConstantPoolItem::CPIntegerInfo { value } => value.to_string(),
+        ConstantPoolItem::CPLongInfo { value } => value.to_string(),
+        other => panic!("Unsupported constant at index {} for a string concatenation", index)
+    }
+}
+
+fn int_constant(constant_pool: &ConstantPool, index: u16) -> i32 {
+    match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPIntegerInfo { value } => *value,
+        other => panic!("No integer found in constantpool at index: {}", index)
+    }
+}
+
+/// Links the invokedynamic call site of a `CONSTANT_InvokeDynamic` entry (JVMS §5.4.3.6).
+pub fn resolve_call_site(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<Arc<CallSite>> {
+    if let Some(ResolvedEntry::CallSite(call_site)) = current.cp_cache[index as usize].get() {
+        return Ok(call_site.clone());
+    }
+    let info = current.info.as_ref().unwrap();
+    let constant_pool = &info.constant_pool;
+    let (bootstrap_method_attr_index, name_and_type_index) = match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPInvokeDynamicInfo { bootstrap_method_attr_index, name_and_type_index } => (*bootstrap_method_attr_index, *name_and_type_index),
+        other => panic!("No invokedynamic entry found in constantpool at index: {}", index)
+    };
+    let (name_index, descriptor_index) = constant_pool.get_name_and_type(name_and_type_index);
+    let name = constant_pool.get_string(name_index);
+    let descriptor = constant_pool.get_string(descriptor_index);
+    let bootstrap_method = info.get_bootstrap_method(bootstrap_method_attr_index)
+        .ok_or_else(|| Throwable::new("java/lang/BootstrapMethodError", format!("Missing bootstrap method {} in {}", bootstrap_method_attr_index, current.name.replace('/', "."))))?;
+    let arguments = &bootstrap_method.bootstrap_arguments;
+    let (_, reference_index) = method_handle(constant_pool, bootstrap_method.bootstrap_method_ref);
+    let (bootstrap_class, bootstrap_name, _) = member_ref(constant_pool, reference_index);
+
+    let call_site = match (bootstrap_class, bootstrap_name) {
+        ("java/lang/invoke/LambdaMetafactory", "metafactory" | "altMetafactory") => link_lambda(thread, current, name, descriptor, arguments)?,
+        ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") => {
+            let recipe = constant_text(constant_pool, arguments[0]);
+            let mut constants = arguments[1..].iter().map(|argument| constant_text(constant_pool, *argument));
+            let mut parts = Vec::new();
+            let mut text = String::new();
+            for c in recipe.chars() {
+                match c {
+                    TAG_ARGUMENT => {
+                        parts.push(ConcatPart::Text(text.encode_utf16().collect()));
+                        parts.push(ConcatPart::Argument);
+                        text.clear();
+                    },
+                    TAG_CONSTANT => text += &constants.next().unwrap(),
+                    other => text.push(c)
+                }
+            }
+            parts.push(ConcatPart::Text(text.encode_utf16().collect()));
+            CallSite::Concat { recipe: parts, argument_types: argument_descriptors(descriptor).into_iter().map(String::from).collect() }
+        },
+        ("java/lang/invoke/StringConcatFactory", "makeConcat") => {
+            let argument_types: Vec<String> = argument_descriptors(descriptor).into_iter().map(String::from).collect();
+            let recipe = argument_types.iter().map(|_| ConcatPart::Argument).collect();
+            CallSite::Concat { recipe, argument_types }
+        },
+        ("java/lang/runtime/ObjectMethods", "bootstrap") => {
+            let record = thread.vm.load_class(constant_pool.get_string(constant_pool.get_class_info(arguments[0])))?;
+            let names = constant_text(constant_pool, arguments[1]);
+            let mut components = Vec::with_capacity(arguments.len() - 2);
+            for argument in &arguments[2..] {
+                let (kind, reference_index) = method_handle(constant_pool, *argument);
+                if kind != REF_GET_FIELD {
+                    return Err(Throwable::new("java/lang/BootstrapMethodError", format!("Record component accessor of kind {} is not a getter", kind)));
+                }
+                components.push(resolve_field(thread, current, reference_index)?);
+            }
+            let component_names = names.split(';').filter(|name| !name.is_empty()).map(String::from).collect();
+            CallSite::RecordMethod { name: name.to_string(), record, component_names, components }
+        },
+        other => return Err(Throwable::new("java/lang/BootstrapMethodError",
+            format!("Unsupported bootstrap method {}.{}", bootstrap_class.replace('/', "."), bootstrap_name)))
+    };
+    match current.cp_cache[index as usize].get_or_init(|| ResolvedEntry::CallSite(Arc::new(call_site))) {
+        ResolvedEntry::CallSite(call_site) => Ok(call_site.clone()),
+        other => panic!("Constant pool entry {} of {} resolved to something else than a call site", index, current.name)
+    }
+}
+
+/// Does what LambdaMetafactory does: makes a class that implements the functional interface, whose method
+/// calls the implementation method with the captured arguments and its own arguments.
+fn link_lambda(thread: &JavaThread, current: &Class, name: &str, descriptor: &str, arguments: &[u16]) -> JvmResult<CallSite> {
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    let interface_descriptor = return_descriptor(descriptor);
+    let mut interfaces = vec![&interface_descriptor[1..interface_descriptor.len() - 1]];
+    let mut method_types = vec![method_type(constant_pool, arguments[0])];
+    let (kind, reference_index) = method_handle(constant_pool, arguments[1]);
+    let instantiated_type = method_type(constant_pool, arguments[2]).to_string();
+
+    // altMetafactory adds flags, and then the marker interfaces and bridge method types they ask for
+    let flags = arguments.get(3).map_or(0, |argument| int_constant(constant_pool, *argument));
+    let mut rest = arguments.iter().skip(4);
+    if flags & FLAG_MARKERS != 0 {
+        let count = int_constant(constant_pool, *rest.next().unwrap());
+        for _ in 0..count {
+            interfaces.push(constant_pool.get_string(constant_pool.get_class_info(*rest.next().unwrap())));
+        }
+    }
+    if flags & FLAG_SERIALIZABLE != 0 && !interfaces.contains(&"java/io/Serializable") {
+        interfaces.push("java/io/Serializable");
+    }
+    if flags & FLAG_BRIDGES != 0 {
+        let count = int_constant(constant_pool, *rest.next().unwrap());
+        for _ in 0..count {
+            let bridge = method_type(constant_pool, *rest.next().unwrap());
+            if !method_types.contains(&bridge) {
+                method_types.push(bridge);
+            }
+        }
+    }
+
+    let target = match kind {
+        REF_INVOKE_VIRTUAL | REF_INVOKE_STATIC | REF_INVOKE_SPECIAL | REF_NEW_INVOKE_SPECIAL | REF_INVOKE_INTERFACE => {
+            resolve_any_method(thread, current, reference_index)?
+        },
+        other => return Err(Throwable::new("java/lang/BootstrapMethodError", format!("Unsupported lambda implementation of kind {}", kind)))
+    };
+
+    let class_name = format!("{}$$Lambda${}", current.name, LAMBDA_NUMBER.fetch_add(1, Ordering::Relaxed));
+    let mut lambda_class = StubClass::new(ClassFlags::ACC_FINAL | ClassFlags::ACC_SUPER | ClassFlags::ACC_SYNTHETIC, Some("java/lang/Object"), &interfaces);
+    for (index, captured) in argument_descriptors(descriptor).into_iter().enumerate() {
+        lambda_class = lambda_class.field(FieldFlags::ACC_PRIVATE | FieldFlags::ACC_FINAL, &format!("arg${}", index + 1), captured);
+    }
+    let methods: Vec<(&str, &str)> = method_types.iter().map(|method_type| (name, *method_type)).collect();
+    lambda_class = lambda_class.methods(MethodFlags::ACC_PUBLIC | MethodFlags::ACC_NATIVE, &methods);
+    let path = format!("{}.class", class_name);
+    let class_file = lambda_class.to_class_file(&class_name).map_err(|message| Throwable::new("java/lang/BootstrapMethodError", message))?;
+    let class_info = ClassLoader::load_class_from_bytes(class_file, &path, OffsetDateTime::now_utc())
+        .map_err(|message| Throwable::new("java/lang/BootstrapMethodError", message))?;
+
+    for method_type in &method_types {
+        thread.vm.natives.register(&class_name, name, method_type, invoke_lambda);
+    }
+    thread.vm.add_lambda(&class_name, Arc::new(Lambda { kind, target, instantiated_type }));
+    let class = thread.vm.define_class(class_info)?;
+    class.initialize(thread)?;
+    let instance = match class.fields.is_empty() {
+        true => Some(Object::new_instance(class.clone())),
+        false => None
+    };
+    Ok(CallSite::Lambda { class, instance })
+}
+
+/// Converts a value between the types of the functional interface method and the implementation method,
+/// with the casts, boxing, unboxing and widening that LambdaMetafactory allows.
+pub fn convert(thread: &JavaThread, value: Value, from: &str, to: &str) -> JvmResult<Value> {
+    if from == to {
+        return Ok(value);
+    }
+    match (is_reference(from), is_reference(to)) {
+        (false, false) => Ok(widen(value, from, to).unwrap()),
+        (false, true) => box_value(thread, value, from),
+        (true, false) => {
+            let object = value.as_reference().ok_or_else(|| Throwable::without_message("java/lang/NullPointerException"))?;
+            let cannot_cast = || Throwable::new("java/lang/ClassCastException",
+                format!("class {} cannot be cast to class {}", object.class.name.replace('/', "."), WRAPPER_CLASSES.iter()
+                    .find(|(primitive, _)| *primitive == to)
+                    .map_or(to, |(_, wrapper_name)| wrapper_name).replace('/', ".")));
+            let (primitive, _) = WRAPPER_CLASSES.iter().find(|(_, wrapper_name)| *wrapper_name == object.class.name).ok_or_else(cannot_cast)?;
+            let value = object.fields()[object.class.instance_field_slot("value", primitive).unwrap()].clone();
+            widen(value, primitive, to).ok_or_else(cannot_cast)
+        },
+        (true, true) => {
+            if let Some(object) = value.as_reference() {
+                let class = thread.vm.class_for_descriptor(to)?;
+                if !object.class.is_assignable_to(&class) {
+                    return Err(Throwable::new("java/lang/ClassCastException",
+                        format!("class {} cannot be cast to class {}", object.class.name.replace('/', "."), class.name.replace('/', "."))));
+                }
+            }
+            Ok(value)
+        }
+    }
+}
+
+/// The native behind the methods of lambda classes: calls the implementation method of the lambda,
+/// with the captured arguments in front of the given ones.
+fn invoke_lambda(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = reference_arg(&args, 0)?;
+    let lambda = thread.vm.find_lambda(&this.class.name).unwrap();
+    let descriptor = thread.stack.borrow().last().unwrap().method.descriptor.clone();
+    let mut target_args = this.fields().clone();
+    let target_types = lambda.target_parameter_types();
+    let instantiated_types = argument_descriptors(&lambda.instantiated_type);
+    for (index, (value, from)) in args.into_iter().skip(1).zip(argument_descriptors(&descriptor)).enumerate() {
+        let instantiated = instantiated_types[index];
+        let value = convert(thread, value, from, instantiated)?;
+        target_args.push(convert(thread, value, instantiated, &target_types[target_args.len()])?);
+    }
+    let result = lambda.invoke_target(thread, target_args)?;
+    match (result, return_descriptor(&descriptor)) {
+        (_, "V") => Ok(None),
+        (Some(value), to) => {
+            let instantiated = return_descriptor(&lambda.instantiated_type);
+            let value = convert(thread, value, &lambda.target_return_type(), instantiated)?;
+            Ok(Some(convert(thread, value, instantiated, to)?))
+        },
+        (None, to) => Ok(Some(Value::default_for(to)))
+    }
+}
+
+/// Converts a value to text the way String.valueOf does, calling toString on objects.
+fn string_value_of(thread: &JavaThread, value: &Value, descriptor: &str) -> JvmResult<Vec<u16>> {
+    let parameter = match descriptor {
+        "B" | "S" => "I",
+        descriptor if is_reference(descriptor) => match value.as_reference() {
+            Some(string) if string.class.name == "java/lang/String" => return Ok(to_utf16(string)),
+            other => "Ljava/lang/Object;"
+        },
+        descriptor => descriptor
+    };
+    let string_class = thread.vm.load_class("java/lang/String")?;
+    string_class.initialize(thread)?;
+    let value_of = string_class.find_declared_method("valueOf", &format!("({})Ljava/lang/String;", parameter)).unwrap();
+    match Interpreter::invoke(thread, value_of, vec![value.clone()])? {
+        Some(Value::Reference(Some(string))) => Ok(to_utf16(&string)),
+        other => Ok("null".encode_utf16().collect())
+    }
+}
+
+/// Formats a record like `Point[x=1, y=2]`.
+fn record_to_string(thread: &JavaThread, record: &Class, component_names: &[String], values: &[(Value, &str)]) -> JvmResult<Option<Value>> {
+    let mut chars: Vec<u16> = format!("{}[", record.simple_name()).encode_utf16().collect();
+    for (index, (name, (value, descriptor))) in component_names.iter().zip(values).enumerate() {
+        if index > 0 {
+            chars.extend(", ".encode_utf16());
+        }
+        chars.extend(format!("{}=", name).encode_utf16());
+        chars.extend(string_value_of(thread, value, descriptor)?);
+    }
+    chars.push(']' as u16);
+    Ok(Some(Value::Reference(Some(new_java_string_from_utf16(thread, &chars)?))))
+}
+
+/// The bits of a float or double that Float.compare and Double.compare go by, with a single NaN.
+fn canonical_bits(value: &Value) -> i64 {
+    match value {
+        Value::Float(value) if value.is_nan() => f32::NAN.to_bits() as i64,
+        Value::Float(value) => value.to_bits() as i64,
+        Value::Double(value) if value.is_nan() => f64::NAN.to_bits() as i64,
+        Value::Double(value) => value.to_bits() as i64,
+        other => panic!("Expected a float or double, found {:?}", value)
+    }
+}
+
+/// Combines the hash codes of the components like ObjectMethods does, `31 * result + hash` for each one.
+fn record_hash_code(thread: &JavaThread, values: &[(Value, &str)]) -> JvmResult<Option<Value>> {
+    let mut result: i32 = 0;
+    for (value, descriptor) in values {
+        let hash = match *descriptor {
+            "Z" => if value.as_int() != 0 { 1231 } else { 1237 },
+            "J" => (value.as_long() ^ ((value.as_long() as u64) >> 32) as i64) as i32,
+            "F" => canonical_bits(value) as i32,
+            "D" => {
+                let bits = canonical_bits(value);
+                (bits ^ ((bits as u64) >> 32) as i64) as i32
+            },
+            descriptor if is_reference(descriptor) => match value.as_reference() {
+                Some(object) => Interpreter::invoke_virtual_method(thread, object, "hashCode", "()I", Vec::new())?.unwrap().as_int(),
+                None => 0
+            },
+            other => value.as_int()
+        };
+        result = result.wrapping_mul(31).wrapping_add(hash);
+    }
+    Ok(Some(Value::Int(result)))
+}
+
+/// Records are equal when they have the same class and their components are equal, compared with `==`
+/// for primitives, except floats and doubles which are compared like Float.compare does, and with equals for objects.
+fn record_equals(thread: &JavaThread, record: &Class, components: &[ResolvedField], values: &[(Value, &str)], other: Option<&ObjectRef>) -> JvmResult<Option<Value>> {
+    let other = match other {
+        Some(other) if other.class.name == record.name => other,
+        other => return Ok(Some(Value::Int(0)))
+    };
+    for (component, (value, descriptor)) in components.iter().zip(values) {
+        let other_value = other.fields()[component.field().slot].clone();
+        let equal = match *descriptor {
+            "J" => value.as_long() == other_value.as_long(),
+            "F" | "D" => canonical_bits(value) == canonical_bits(&other_value),
+            descriptor if is_reference(descriptor) => match (value.as_reference(), other_value.as_reference()) {
+                (None, None) => true,
+                (Some(object), Some(other_object)) if Arc::ptr_eq(object, other_object) => true,
+                (Some(object), other_object) => {
+                    Interpreter::invoke_virtual_method(thread, object, "equals", "(Ljava/lang/Object;)Z", vec![Value::Reference(other_object.cloned())])?.unwrap().as_int() != 0
+                },
+                (None, Some(_)) => false
+            },
+            other => value.as_int() == other_value.as_int()
+        };
+        if !equal {
+            return Ok(Some(Value::Int(0)));
+        }
+    }
+    Ok(Some(Value::Int(1)))
+}
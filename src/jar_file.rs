@@ -0,0 +1,231 @@
+use std::{collections::HashMap, fs::File, io::{Read, Seek, SeekFrom}, sync::Mutex};
+
+use miniz_oxide::inflate::decompress_to_vec;
+
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x06054b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32        = 0x02014b50;
+const LOCAL_HEADER_SIGNATURE: u32             = 0x04034b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: usize    = 22;
+const LOCAL_HEADER_SIZE: usize                = 30;
+
+const METHOD_STORED: u16   = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+struct JarEntry {
+    method: u16,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    local_header_offset: u64,
+}
+
+/// Reader for jar (zip) files, used for classpath entries and the `rt.jar` of JDK 8.
+///
+/// Only the central directory is kept in memory, entries are read from the file on demand.
+pub struct JarFile {
+    pub path: String,
+    entries: HashMap<String, JarEntry>,
+    file: Mutex<File>,
+}
+
+/// Reads a little-endian number, or None past the end of the bytes.
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().unwrap()))
+}
+
+impl JarFile {
+    /// Reads the central directory of a jar. Returns None when the file isn't a zip file or its directory is corrupt.
+    pub fn open(path: &str) -> Option<JarFile> {
+        let mut file = File::open(path).ok()?;
+        let file_size = file.metadata().ok()?.len() as usize;
+        if file_size < END_OF_CENTRAL_DIRECTORY_SIZE {
+            return None;
+        }
+
+        // The end of central directory record is followed by a comment of at most 64 KiB
+        let tail_size = file_size.min(END_OF_CENTRAL_DIRECTORY_SIZE + 0xFFFF);
+        let mut tail = vec![0u8; tail_size];
+        file.seek(SeekFrom::Start((file_size - tail_size) as u64)).ok()?;
+        file.read_exact(&mut tail).ok()?;
+        let end_offset = (0..=tail_size - END_OF_CENTRAL_DIRECTORY_SIZE).rev()
+            .find(|offset| read_u32(&tail, *offset) == Some(END_OF_CENTRAL_DIRECTORY_SIGNATURE))?;
+        let entry_count = read_u16(&tail, end_offset + 10)? as usize;
+        let directory_size = read_u32(&tail, end_offset + 12)? as usize;
+        let directory_offset = read_u32(&tail, end_offset + 16)? as u64;
+        if directory_size > file_size {
+            return None;
+        }
+
+        let mut directory = vec![0u8; directory_size];
+        file.seek(SeekFrom::Start(directory_offset)).ok()?;
+        file.read_exact(&mut directory).ok()?;
+
+        let mut entries = HashMap::with_capacity(entry_count);
+        let mut offset = 0;
+        for _current_entry in 0..entry_count {
+            if read_u32(&directory, offset)? != CENTRAL_DIRECTORY_SIGNATURE {
+                return None;
+            }
+            let method = read_u16(&directory, offset + 10)?;
+            let compressed_size = read_u32(&directory, offset + 20)? as usize;
+            let uncompressed_size = read_u32(&directory, offset + 24)? as usize;
+            let name_length = read_u16(&directory, offset + 28)? as usize;
+            let extra_length = read_u16(&directory, offset + 30)? as usize;
+            let comment_length = read_u16(&directory, offset + 32)? as usize;
+            let local_header_offset = read_u32(&directory, offset + 42)? as u64;
+            let name = String::from_utf8_lossy(directory.get(offset + 46..offset + 46 + name_length)?).to_string();
+            if compressed_size > file_size {
+                return None;
+            }
+            entries.insert(name, JarEntry {
+                method,
+                compressed_size,
+                uncompressed_size,
+                local_header_offset
+            });
+            offset += 46 + name_length + extra_length + comment_length;
+        }
+
+        Some(JarFile {
+            path: String::from(path),
+            entries,
+            file: Mutex::new(file)
+        })
+    }
+
+    /// Returns the names of all entries, in no particular order.
+    pub fn entry_names(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    /// Reads the bytes of an entry, or None when there is no such entry.
+    /// The error tells what is wrong with an entry that can't be read.
+    pub fn read_entry(&self, name: &str) -> Option<Result<Vec<u8>, String>> {
+        let entry = self.entries.get(name)?;
+        Some(self.read_entry_data(entry).map_err(|message| format!("{} for {} in {}", message, name, self.path)))
+    }
+
+    fn read_entry_data(&self, entry: &JarEntry) -> Result<Vec<u8>, String> {
+        let mut compressed = vec![0u8; entry.compressed_size];
+        {
+            let mut file = self.file.lock().unwrap();
+            let mut local_header = [0u8; LOCAL_HEADER_SIZE];
+            file.seek(SeekFrom::Start(entry.local_header_offset)).map_err(|error| error.to_string())?;
+            file.read_exact(&mut local_header).map_err(|_| "Truncated local header")?;
+            if read_u32(&local_header, 0) != Some(LOCAL_HEADER_SIGNATURE) {
+                return Err(String::from("Corrupt local header"));
+            }
+            // The name and extra field lengths of the local header can differ from the central directory
+            let name_length = read_u16(&local_header, 26).unwrap() as i64;
+            let extra_length = read_u16(&local_header, 28).unwrap() as i64;
+            file.seek(SeekFrom::Current(name_length + extra_length)).map_err(|error| error.to_string())?;
+            file.read_exact(&mut compressed).map_err(|_| "Truncated data")?;
+        }
+        let bytes = match entry.method {
+            METHOD_STORED => compressed,
+            METHOD_DEFLATED => decompress_to_vec(&compressed).map_err(|error| format!("Corrupt deflated data: {}", error))?,
+            other => return Err(format!("Unsupported compression method {}", other))
+        };
+        if bytes.len() != entry.uncompressed_size {
+            return Err(format!("Got {} bytes instead of {}", bytes.len(), entry.uncompressed_size));
+        }
+        Ok(bytes)
+    }
+
+    /// Reads the bytes of a class by its internal name, for example `java/lang/Object`.
+    /// Returns the bytes together with the `jar:` path of the class.
+    pub fn read_class(&self, class_name: &str) -> Option<Result<(Vec<u8>, String), String>> {
+        let name = format!("{}.class", class_name);
+        let bytes = self.read_entry(&name)?;
+        Some(bytes.map(|bytes| (bytes, format!("jar:file:{}!/{}", self.path, name))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::{Path, PathBuf}, process};
+
+    use super::JarFile;
+
+    fn jar_path() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/jar/Classes.jar")
+    }
+
+    /// Writes a copy of the test jar with some bytes changed, and returns its path.
+    /// Opens a corrupted copy of the test jar, removing the copy again once it is open.
+    fn open_corrupt_jar(name: &str, corrupt: impl Fn(&mut Vec<u8>)) -> (Option<JarFile>, String) {
+        let mut bytes = fs::read(jar_path()).unwrap();
+        corrupt(&mut bytes);
+        let path = env::temp_dir().join(format!("vigur-{}-{}.jar", name, process::id()));
+        fs::write(&path, bytes).unwrap();
+        let path = path.to_string_lossy().to_string();
+        let jar_file = JarFile::open(&path);
+        fs::remove_file(&path).unwrap();
+        (jar_file, path)
+    }
+
+    fn find(bytes: &[u8], pattern: &[u8], start: usize) -> usize {
+        start + bytes[start..].windows(pattern.len()).position(|window| window == pattern).unwrap()
+    }
+
+    /// The central directory entry of Numerics.class, which comes second.
+    fn numerics_directory_entry(bytes: &[u8]) -> usize {
+        let first = find(bytes, b"PK\x01\x02", 0);
+        find(bytes, b"PK\x01\x02", first + 4)
+    }
+
+    /// Slots.class is stored and Numerics.class deflated.
+    #[test]
+    fn reads_stored_and_deflated_classes() {
+        let path = jar_path().to_string_lossy().to_string();
+        let jar_file = JarFile::open(&path).unwrap();
+        for class_name in ["Slots", "Numerics"] {
+            let (bytes, class_path) = jar_file.read_class(class_name).unwrap().unwrap();
+            let expected = fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join(format!("tests/conformance/{}.class", class_name))).unwrap();
+            assert!(bytes == expected, "{} differs from its class file", class_name);
+            assert_eq!(class_path, format!("jar:file:{}!/{}.class", path, class_name));
+        }
+        assert!(jar_file.read_class("Missing").is_none());
+    }
+
+    #[test]
+    fn corrupt_central_directory_is_not_opened() {
+        let (jar_file, _) = open_corrupt_jar("directory", |bytes| {
+            let offset = numerics_directory_entry(bytes);
+            bytes[offset] = 0;
+        });
+        assert!(jar_file.is_none());
+        let (jar_file, _) = open_corrupt_jar("truncated", |bytes| bytes.truncate(bytes.len() - 30));
+        assert!(jar_file.is_none());
+    }
+
+    #[test]
+    fn corrupt_entries_are_errors() {
+        let (jar_file, path) = open_corrupt_jar("local-header", |bytes| bytes[0] = 0);
+        assert_eq!(jar_file.unwrap().read_class("Slots").unwrap().err().unwrap(), format!("Corrupt local header for Slots.class in {}", path));
+
+        // The compression method is at offset 10 of the central directory entry
+        let (jar_file, path) = open_corrupt_jar("method", |bytes| {
+            let offset = numerics_directory_entry(bytes);
+            bytes[offset + 10] = 9;
+        });
+        assert_eq!(jar_file.unwrap().read_class("Numerics").unwrap().err().unwrap(), format!("Unsupported compression method 9 for Numerics.class in {}", path));
+
+        // A block type of 3 is invalid in deflated data
+        let (jar_file, path) = open_corrupt_jar("deflated", |bytes| {
+            let offset = find(bytes, b"Numerics.class", 0) + "Numerics.class".len();
+            bytes[offset] |= 0x06;
+        });
+        assert_eq!(jar_file.unwrap().read_class("Numerics").unwrap().err().unwrap(), format!("Corrupt deflated data: Invalid input data for Numerics.class in {}", path));
+
+        // The uncompressed size is at offset 24 of the central directory entry
+        let (jar_file, path) = open_corrupt_jar("size", |bytes| {
+            let offset = numerics_directory_entry(bytes);
+            bytes[offset + 24] ^= 1;
+        });
+        assert_eq!(jar_file.unwrap().read_class("Numerics").unwrap().err().unwrap(), format!("Got 6343 bytes instead of 6342 for Numerics.class in {}", path));
+    }
+}
@@ -0,0 +1,945 @@
+use std::{collections::{HashMap, HashSet}, rc::Rc, str::FromStr};
+
+use time::OffsetDateTime;
+
+use crate::{assembler::{AssemblyError, CodeBuilder, Instruction, Label}, class_loader::{ATBootstrapMethods, ATConstantValue, ATExceptions, ATSourceFile, ATUnknown, AttributeInfo, BootstrapMethod, ClassFlags, ClassInfo, ConstantPoolItem, FieldFlags, FieldInfo, MethodFlags, MethodInfo}, class_writer::ClassWriter, constant_pool_builder::ConstantPoolBuilder, disassembler::{array_type_name, CLASS_FLAGS, FIELD_FLAGS, INNER_CLASS_FLAGS, METHOD_FLAGS, REFERENCE_KINDS}, opcodes::*};
+
+// The version of Java 8, for files without .bytecode
+const DEFAULT_MAJOR_VERSION: u16 = 52;
+
+/// Looks up the superclass of a class by name, shared by the files that are assembled together.
+pub type SharedSuperclassLookup = Rc<dyn Fn(&str) -> Option<String>>;
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Word(String),
+    // A string in double quotes, as UTF-16 so it can hold unpaired surrogates
+    Text(Vec<u16>),
+    Open,
+    Close,
+}
+
+struct Line {
+    number: usize,
+    tokens: Vec<Token>,
+}
+
+#[derive(Clone, Copy)]
+enum NumberType {
+    Int,
+    Long,
+    Float,
+    Double,
+}
+
+/// The labels of the method that is being parsed, by name.
+#[derive(Default)]
+struct Labels {
+    labels: HashMap<String, Label>,
+    placed: HashSet<String>,
+    // The line where each label was first used, to report the ones that never get placed
+    used: Vec<(String, usize)>,
+}
+
+/// Reads the text format that `Disassembler` writes, which is based on the one of Jasmin, and assembles it
+/// into a class file. The assembler picks the encoding of the instructions, like wide or goto_w, and computes the
+/// StackMapTable. Code that has to fail verification can use a class file version before 50 and .limit both
+/// stack and locals, then it is written as it is.
+pub struct JasminParser {
+    path: String,
+    lines: Vec<Line>,
+    // The line that is being parsed, and the next token on it
+    line: usize,
+    token: usize,
+    constant_pool: ConstantPoolBuilder,
+    bootstrap_methods: Vec<BootstrapMethod>,
+    superclass_of: SharedSuperclassLookup,
+}
+
+impl JasminParser {
+    /// Assembles the text of a .j file, and returns the name of the class and its class file. The superclasses that
+    /// the frames need are looked up with `superclass_of`, except the one of the class itself.
+    pub fn assemble(text: &str, path: &str, superclass_of: SharedSuperclassLookup) -> Result<(String, Vec<u8>), String> {
+        let mut lines = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let tokens = tokenize(line).map_err(|message| format!("{}:{}: {}", path, index + 1, message))?;
+            if !tokens.is_empty() {
+                lines.push(Line { number: index + 1, tokens });
+            }
+        }
+        let mut parser = JasminParser {
+            path: path.to_string(),
+            lines,
+            line: 0,
+            token: 0,
+            constant_pool: ConstantPoolBuilder::new(),
+            bootstrap_methods: Vec::new(),
+            superclass_of,
+        };
+        let class_info = parser.class()?;
+        let class_name = class_info.constant_pool.get_string(class_info.constant_pool.get_class_info(class_info.this_class)).to_string();
+        let bytes = ClassWriter::write_class(&class_info).map_err(|message| format!("{}: {}", path, message))?;
+        Ok((class_name, bytes))
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(self.located(message))
+    }
+
+    /// Prefixes a message with the file and the line that is being parsed.
+    fn located(&self, message: &str) -> String {
+        format!("{}:{}: {}", self.path, self.line_number(), message)
+    }
+
+    /// The number in the file of the line that is being parsed.
+    fn line_number(&self) -> usize {
+        self.lines.get(self.line).map_or(0, |line| line.number)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.lines.get(self.line).and_then(|line| line.tokens.get(self.token))
+    }
+
+    fn next(&mut self, what: &str) -> Result<Token, String> {
+        match self.peek().cloned() {
+            Some(token) => {
+                self.token += 1;
+                Ok(token)
+            },
+            None => self.error(&format!("expected {}", what))
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, String> {
+        match self.next(what)? {
+            Token::Word(word) => Ok(word),
+            other => self.error(&format!("expected {}", what))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.word(keyword)? != keyword {
+            return self.error(&format!("expected {}", keyword));
+        }
+        Ok(())
+    }
+
+    fn parse_word<T: FromStr>(&mut self, what: &str) -> Result<T, String> {
+        let word = self.word(what)?;
+        match word.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => self.error(&format!("expected {}, not {}", what, word))
+        }
+    }
+
+    /// The words left on the line.
+    fn rest(&mut self) -> Result<Vec<String>, String> {
+        let mut words = Vec::new();
+        while self.peek().is_some() {
+            words.push(self.word("a word")?);
+        }
+        Ok(words)
+    }
+
+    fn end_of_line(&self) -> Result<(), String> {
+        match self.peek() {
+            Some(Token::Word(word)) => self.error(&format!("unexpected {}", word)),
+            Some(other) => self.error("unexpected text at the end of the line"),
+            None => Ok(())
+        }
+    }
+
+    /// Moves to the next line, returning false at the end of the file.
+    fn next_line(&mut self) -> bool {
+        if self.line < self.lines.len() {
+            self.line += 1;
+        }
+        self.token = 0;
+        self.line < self.lines.len()
+    }
+
+    fn flags(&self, words: &[String], keywords: &[(&str, u16)]) -> Result<u16, String> {
+        let mut flags = 0;
+        for word in words {
+            flags |= match keywords.iter().find(|(keyword, _)| keyword == word) {
+                Some((_, flag)) => *flag,
+                None => match word.strip_prefix("0x").and_then(|bits| u16::from_str_radix(bits, 16).ok()) {
+                    Some(bits) => bits,
+                    None => return self.error(&format!("unknown access flag {}", word))
+                }
+            };
+        }
+        Ok(flags)
+    }
+
+    /// Makes an attribute that the class loader keeps as bytes.
+    fn unknown_attribute(&mut self, name: &str, info: Vec<u8>) -> Result<AttributeInfo, String> {
+        let name_index = self.constant_pool.add_utf8(name).map_err(|message| self.located(&message))?;
+        Ok(AttributeInfo::Unknown(ATUnknown { name_index, info }))
+    }
+
+    /// Parses the directives that classes, fields and methods all have, returning None for other directives.
+    fn member_attribute(&mut self, directive: &str) -> Result<Option<AttributeInfo>, String> {
+        Ok(Some(match directive {
+            ".signature" => {
+                let signature = match self.next("a signature")? {
+                    Token::Word(word) => self.constant_pool.add_utf8(&word).map_err(|message| self.located(&message))?,
+                    Token::Text(text) => self.constant_pool.add_utf16(&text).map_err(|message| self.located(&message))?,
+                    other => return self.error("expected a signature")
+                };
+                self.unknown_attribute("Signature", signature.to_be_bytes().to_vec())?
+            },
+            ".deprecated" => self.unknown_attribute("Deprecated", Vec::new())?,
+            ".synthetic" => self.unknown_attribute("Synthetic", Vec::new())?,
+            ".attribute" => self.raw_attribute()?,
+            other => return Ok(None)
+        }))
+    }
+
+    /// Parses `<name> <item>...` of `.attribute` and `.codeattribute` into an attribute with those bytes. An item is
+    /// bytes in hex, or the index of a constant in braces: `{utf8 <text>}`, or a constant as `ldc` takes it.
+    fn raw_attribute(&mut self) -> Result<AttributeInfo, String> {
+        let name = match self.next("an attribute name")? {
+            Token::Word(word) => word,
+            Token::Text(text) => String::from_utf16(&text).map_err(|_| self.located("bad attribute name"))?,
+            other => return self.error("expected an attribute name")
+        };
+        let mut info = Vec::new();
+        while let Some(token) = self.peek().cloned() {
+            let index = match token {
+                Token::Word(word) => {
+                    let bytes = (word.len() % 2 == 0).then(|| (0..word.len()).step_by(2)
+                        .map(|at| word.get(at..at + 2).and_then(|digits| u8::from_str_radix(digits, 16).ok()))
+                        .collect::<Option<Vec<u8>>>()).flatten();
+                    match bytes {
+                        Some(bytes) => info.extend(bytes),
+                        None => return self.error(&format!("expected bytes in hex, not {}", word))
+                    }
+                    self.token += 1;
+                    continue;
+                },
+                Token::Open => {
+                    self.token += 1;
+                    if matches!(self.peek(), Some(Token::Word(word)) if word == "utf8") {
+                        self.token += 1;
+                        match self.next("a text")? {
+                            Token::Word(word) => self.constant_pool.add_utf8(&word).map_err(|message| self.located(&message))?,
+                            Token::Text(text) => self.constant_pool.add_utf16(&text).map_err(|message| self.located(&message))?,
+                            other => return self.error("expected a text")
+                        }
+                    } else {
+                        self.constant(NumberType::Int, NumberType::Double)?
+                    }
+                },
+                other => return self.error("expected bytes in hex or a constant in braces")
+            };
+            if self.next("}")? != Token::Close {
+                return self.error("expected }");
+            }
+            info.extend(index.to_be_bytes());
+        }
+        self.unknown_attribute(&name, info)
+    }
+
+    /// Parses `.inner <flags> <class> [outer <class>] [name <simple name>]` into an entry of the InnerClasses attribute.
+    fn inner_class(&mut self) -> Result<[u16; 4], String> {
+        let mut words = self.rest()?;
+        let mut outer_class = 0;
+        let mut inner_name = 0;
+        while words.len() >= 3 && matches!(words[words.len() - 2].as_str(), "outer" | "name") {
+            let value = words.pop().unwrap();
+            match words.pop().unwrap().as_str() {
+                "outer" => outer_class = self.constant_pool.add_class(&value).map_err(|message| self.located(&message))?,
+                other => inner_name = self.constant_pool.add_utf8(&value).map_err(|message| self.located(&message))?
+            }
+        }
+        let Some(inner_class) = words.pop() else {
+            return self.error("expected an inner class name");
+        };
+        let flags = self.flags(&words, &INNER_CLASS_FLAGS)?;
+        Ok([self.constant_pool.add_class(&inner_class).map_err(|message| self.located(&message))?, outer_class, inner_name, flags])
+    }
+
+    /// Adds an entry to an attribute that is a u16 count followed by entries, like InnerClasses. The attribute is
+    /// made where the directive of its first entry is, so the attributes keep the order of the text.
+    fn add_entry(&mut self, attributes: &mut Vec<AttributeInfo>, name: &str, entry: &[u16]) -> Result<(), String> {
+        let name_index = self.constant_pool.add_utf8(name).map_err(|message| self.located(&message))?;
+        let position = attributes.iter().position(|attribute| matches!(attribute, AttributeInfo::Unknown(unknown) if unknown.name_index == name_index));
+        let position = position.unwrap_or_else(|| {
+            attributes.push(AttributeInfo::Unknown(ATUnknown { name_index, info: vec![0, 0] }));
+            attributes.len() - 1
+        });
+        let AttributeInfo::Unknown(unknown) = &mut attributes[position] else { panic!("{} is not an unknown attribute", name) };
+        let count = u16::from_be_bytes([unknown.info[0], unknown.info[1]]) + 1;
+        unknown.info[..2].copy_from_slice(&count.to_be_bytes());
+        unknown.info.extend(entry.iter().flat_map(|value| value.to_be_bytes()));
+        Ok(())
+    }
+
+    fn class(&mut self) -> Result<ClassInfo, String> {
+        let (mut major_version, mut minor_version) = (DEFAULT_MAJOR_VERSION, 0);
+        let mut class_attributes = Vec::new();
+        let mut header: Option<(u16, String)> = None;
+        let mut super_class = None;
+        let mut interfaces = Vec::new();
+        let mut source_file = None;
+        let (mut fields, mut methods) = (Vec::new(), Vec::new());
+        self.line = 0;
+        while self.line < self.lines.len() {
+            let directive = self.word("a directive")?;
+            match directive.as_str() {
+                ".bytecode" => {
+                    let version = self.word("a version")?;
+                    let (major, minor) = version.split_once('.').unwrap_or((&version, "0"));
+                    match (major.parse(), minor.parse()) {
+                        (Ok(major), Ok(minor)) => (major_version, minor_version) = (major, minor),
+                        other => return self.error(&format!("bad version {}", version))
+                    }
+                },
+                ".source" => {
+                    source_file = Some(match self.next("a file name")? {
+                        Token::Word(word) => self.constant_pool.add_utf8(&word).map_err(|message| self.located(&message))?,
+                        Token::Text(text) => self.constant_pool.add_utf16(&text).map_err(|message| self.located(&message))?,
+                        other => return self.error("expected a file name")
+                    });
+                },
+                ".class" | ".interface" => {
+                    let mut words = self.rest()?;
+                    let Some(name) = words.pop() else {
+                        return self.error("expected a class name");
+                    };
+                    let mut flags = self.flags(&words, &CLASS_FLAGS)?;
+                    if directive == ".interface" {
+                        flags |= ClassFlags::ACC_INTERFACE.bits();
+                    }
+                    self.constant_pool.add_class(&name).map_err(|message| self.located(&message))?;
+                    header = Some((flags, name));
+                },
+                ".super" => super_class = Some(self.word("a class name")?),
+                ".implements" => {
+                    let interface = self.word("an interface name")?;
+                    interfaces.push(self.constant_pool.add_class(&interface).map_err(|message| self.located(&message))?);
+                },
+                ".nesthost" => {
+                    let host = self.word("a class name")?;
+                    let host = self.constant_pool.add_class(&host).map_err(|message| self.located(&message))?;
+                    class_attributes.push(self.unknown_attribute("NestHost", host.to_be_bytes().to_vec())?);
+                },
+                ".nestmember" | ".permittedsubclass" => {
+                    let class = self.word("a class name")?;
+                    let class = self.constant_pool.add_class(&class).map_err(|message| self.located(&message))?;
+                    let name = if directive == ".nestmember" { "NestMembers" } else { "PermittedSubclasses" };
+                    self.add_entry(&mut class_attributes, name, &[class])?;
+                },
+                ".inner" => {
+                    let entry = self.inner_class()?;
+                    self.add_entry(&mut class_attributes, "InnerClasses", &entry)?;
+                },
+                ".enclosing" => {
+                    let class = self.word("a class name")?;
+                    let class_index = self.constant_pool.add_class(&class).map_err(|message| self.located(&message))?;
+                    let method_index = match self.peek() {
+                        Some(_) => {
+                            let signature = self.word("a method name and descriptor")?;
+                            let Some(descriptor_start) = signature.find('(') else {
+                                return self.error(&format!("expected a method descriptor after {}", signature));
+                            };
+                            let (name, descriptor) = signature.split_at(descriptor_start);
+                            self.constant_pool.add_name_and_type(name, descriptor).map_err(|message| self.located(&message))?
+                        },
+                        None => 0
+                    };
+                    let info = [class_index, method_index].iter().flat_map(|index| index.to_be_bytes()).collect();
+                    class_attributes.push(self.unknown_attribute("EnclosingMethod", info)?);
+                },
+                ".field" | ".method" => {
+                    let Some((_, class_name)) = &header else {
+                        return self.error(&format!("{} before .class", directive));
+                    };
+                    let class_name = class_name.clone();
+                    if directive == ".field" {
+                        fields.push(self.field()?);
+                    } else {
+                        methods.push(self.method(&class_name, super_class.clone(), major_version)?);
+                    }
+                },
+                other => match self.member_attribute(other)? {
+                    Some(attribute) => class_attributes.push(attribute),
+                    None => return self.error(&format!("unknown directive {}", other))
+                }
+            }
+            self.end_of_line()?;
+            self.next_line();
+        }
+        let Some((access_flags, class_name)) = header else {
+            return self.error("no .class directive");
+        };
+        let this_class = self.constant_pool.add_class(&class_name).map_err(|message| self.located(&message))?;
+        let super_class = match super_class {
+            Some(super_class) => self.constant_pool.add_class(&super_class).map_err(|message| self.located(&message))?,
+            None => 0
+        };
+        let mut attributes = Vec::new();
+        if let Some(source_file_index) = source_file {
+            let name_index = self.constant_pool.add_utf8("SourceFile").map_err(|message| self.located(&message))?;
+            attributes.push(AttributeInfo::SourceFile(ATSourceFile { name_index, source_file_index }));
+        }
+        attributes.extend(class_attributes);
+        if !self.bootstrap_methods.is_empty() {
+            let name_index = self.constant_pool.add_utf8("BootstrapMethods").map_err(|message| self.located(&message))?;
+            attributes.push(AttributeInfo::BootstrapMethods(ATBootstrapMethods { name_index, methods: std::mem::take(&mut self.bootstrap_methods) }));
+        }
+        let constant_pool = std::mem::take(&mut self.constant_pool).build();
+        Ok(ClassInfo {
+            constant_pool,
+            file_path: self.path.clone(),
+            size: 0,
+            last_modified: OffsetDateTime::now_utc(),
+            minor_version,
+            major_version,
+            access_flags: ClassFlags::from_bits_retain(access_flags),
+            this_class,
+            super_class,
+            interfaces,
+            fields,
+            methods,
+            attributes,
+            source_file: String::new(),
+            hash: String::new(),
+        })
+    }
+
+    /// Parses `.field <flags> <name> <descriptor> [= <value>]`.
+    fn field(&mut self) -> Result<FieldInfo, String> {
+        let mut words = Vec::new();
+        while let Some(Token::Word(word)) = self.peek() && word != "=" {
+            words.push(self.word("a word")?);
+        }
+        if words.len() < 2 {
+            return self.error("expected a field name and descriptor");
+        }
+        let descriptor = words.pop().unwrap();
+        let name = words.pop().unwrap();
+        let access_flags = FieldFlags::from_bits_retain(self.flags(&words, &FIELD_FLAGS)?);
+        let name_index = self.constant_pool.add_utf8(&name).map_err(|message| self.located(&message))?;
+        let descriptor_index = self.constant_pool.add_utf8(&descriptor).map_err(|message| self.located(&message))?;
+        let mut attributes = Vec::new();
+        if self.peek().is_some() {
+            self.keyword("=")?;
+            let (integer, decimal) = match descriptor.as_str() {
+                "J" => (NumberType::Long, NumberType::Double),
+                "F" => (NumberType::Float, NumberType::Float),
+                "D" => (NumberType::Double, NumberType::Double),
+                other => (NumberType::Int, NumberType::Float)
+            };
+            let constantvalue_index = self.constant(integer, decimal)?;
+            let name_index = self.constant_pool.add_utf8("ConstantValue").map_err(|message| self.located(&message))?;
+            attributes.push(AttributeInfo::ConstantValue(ATConstantValue { name_index, constantvalue_index }));
+        }
+        // Other attributes of the field come in a block until .end field
+        let starts_block = self.lines.get(self.line + 1)
+            .is_some_and(|line| matches!(line.tokens.first(), Some(Token::Word(word)) if [".signature", ".deprecated", ".synthetic", ".attribute"].contains(&word.as_str())));
+        if starts_block {
+            self.end_of_line()?;
+            loop {
+                if !self.next_line() {
+                    return self.error("expected .end field");
+                }
+                let directive = self.word("a directive")?;
+                if directive == ".end" {
+                    self.keyword("field")?;
+                    break;
+                }
+                match self.member_attribute(&directive)? {
+                    Some(attribute) => attributes.push(attribute),
+                    None => return self.error(&format!("unknown directive {} in a field", directive))
+                }
+                self.end_of_line()?;
+            }
+        }
+        Ok(FieldInfo { access_flags, descriptor_index, attributes, name_index })
+    }
+
+    /// Parses a method from `.method <flags> <name><descriptor>` until `.end method`.
+    fn method(&mut self, class_name: &str, super_class: Option<String>, major_version: u16) -> Result<MethodInfo, String> {
+        let mut words = self.rest()?;
+        let Some(signature) = words.pop() else {
+            return self.error("expected a method name and descriptor");
+        };
+        let Some(descriptor_start) = signature.find('(') else {
+            return self.error(&format!("expected a method descriptor after {}", signature));
+        };
+        let (name, descriptor) = signature.split_at(descriptor_start);
+        let access_flags = MethodFlags::from_bits_retain(self.flags(&words, &METHOD_FLAGS)?);
+        let name_index = self.constant_pool.add_utf8(name).map_err(|message| self.located(&message))?;
+        let descriptor_index = self.constant_pool.add_utf8(descriptor).map_err(|message| self.located(&message))?;
+        let method_line = self.line_number();
+
+        let mut builder = CodeBuilder::new();
+        let superclass_of = self.superclass_of.clone();
+        let this_class = class_name.to_string();
+        builder.set_class_hierarchy(move |name| if name == this_class { super_class.clone() } else { superclass_of(name) });
+        let mut labels = Labels::default();
+        let (mut max_stack, mut max_locals) = (None, None);
+        let mut exceptions = Vec::new();
+        let mut method_attributes = Vec::new();
+        let mut code_attributes = Vec::new();
+        let mut has_code = false;
+        // The line of each instruction and label in the builder, to report where assembly errors are
+        let mut instruction_lines = Vec::new();
+        loop {
+            if !self.next_line() {
+                return self.error("expected .end method");
+            }
+            let first = self.word("a directive, label or instruction")?;
+            match first.as_str() {
+                ".end" => {
+                    self.keyword("method")?;
+                    break;
+                },
+                ".throws" => {
+                    let exception = self.word("a class name")?;
+                    exceptions.push(self.constant_pool.add_class(&exception).map_err(|message| self.located(&message))?);
+                },
+                ".limit" => {
+                    let what = self.word("stack or locals")?;
+                    let value = self.parse_word("a number")?;
+                    match what.as_str() {
+                        "stack" => max_stack = Some(value),
+                        "locals" => max_locals = Some(value),
+                        other => return self.error(&format!("expected stack or locals, not {}", other))
+                    }
+                    has_code = true;
+                },
+                ".catch" => {
+                    let catch_type = match self.word("a class name")?.as_str() {
+                        "all" => 0,
+                        class => self.constant_pool.add_class(class).map_err(|message| self.located(&message))?
+                    };
+                    self.keyword("from")?;
+                    let start = self.label(&mut builder, &mut labels)?;
+                    self.keyword("to")?;
+                    let end = self.label(&mut builder, &mut labels)?;
+                    self.keyword("using")?;
+                    let handler = self.label(&mut builder, &mut labels)?;
+                    builder.add_handler(start, end, handler, catch_type);
+                    has_code = true;
+                },
+                ".var" => {
+                    let index = self.parse_word("a local variable index")?;
+                    self.keyword("is")?;
+                    let name = self.word("a name")?;
+                    let descriptor = self.word("a descriptor")?;
+                    self.keyword("from")?;
+                    let start = self.label(&mut builder, &mut labels)?;
+                    self.keyword("to")?;
+                    let end = self.label(&mut builder, &mut labels)?;
+                    builder.add_local_variable(start, end, &name, &descriptor, index);
+                    has_code = true;
+                },
+                ".codeattribute" => {
+                    code_attributes.push(self.raw_attribute()?);
+                    has_code = true;
+                },
+                ".line" => {
+                    let line_number = self.parse_word("a line number")?;
+                    let label = builder.new_label();
+                    builder.place_label(label);
+                    builder.add_line_number(label, line_number);
+                    has_code = true;
+                },
+                label if label.ends_with(':') && !label.starts_with('.') => {
+                    let name = &label[..label.len() - 1];
+                    if !labels.placed.insert(name.to_string()) {
+                        return self.error(&format!("label {} is placed twice", name));
+                    }
+                    let label = *labels.labels.entry(name.to_string()).or_insert_with(|| builder.new_label());
+                    builder.place_label(label);
+                    if self.peek().is_some() {
+                        let mnemonic = self.word("an instruction")?;
+                        self.instruction(&mnemonic, &mut builder, &mut labels)?;
+                    }
+                    has_code = true;
+                },
+                directive if directive.starts_with('.') => match self.member_attribute(directive)? {
+                    Some(attribute) => method_attributes.push(attribute),
+                    None => return self.error(&format!("unknown directive {} in a method", directive))
+                },
+                mnemonic => {
+                    self.instruction(mnemonic, &mut builder, &mut labels)?;
+                    has_code = true;
+                }
+            }
+            self.end_of_line()?;
+            instruction_lines.resize(builder.instructions.len(), self.line_number());
+        }
+        for (name, line_number) in &labels.used {
+            if !labels.placed.contains(name) {
+                return Err(format!("{}:{}: label {} is used but never placed", self.path, line_number, name));
+            }
+        }
+
+        // Errors about the whole code are reported at the start of the method
+        let located = |error: AssemblyError| {
+            let line_number = error.instruction.map_or(method_line, |index| instruction_lines[index]);
+            format!("{}:{}: {}", self.path, line_number, error)
+        };
+        let mut attributes = Vec::new();
+        if has_code {
+            let mut code = match (max_stack, max_locals) {
+                (Some(max_stack), Some(max_locals)) if major_version < 50 => builder.build_unverified(&mut self.constant_pool, max_stack, max_locals)
+                    .map_err(located)?,
+                other => {
+                    let mut code = builder.build(&mut self.constant_pool, class_name, access_flags, name, descriptor)
+                        .map_err(located)?;
+                    code.max_stack = max_stack.unwrap_or(code.max_stack);
+                    code.max_locals = max_locals.unwrap_or(code.max_locals);
+                    // Class files before version 50 have no frames, they get their types inferred
+                    if major_version < 50 {
+                        code.attributes.retain(|attribute| !matches!(attribute, AttributeInfo::StackMapTable(_)));
+                    }
+                    code
+                }
+            };
+            code.attributes.extend(code_attributes);
+            attributes.push(AttributeInfo::Code(code));
+        }
+        if !exceptions.is_empty() {
+            let name_index = self.constant_pool.add_utf8("Exceptions").map_err(|message| self.located(&message))?;
+            attributes.push(AttributeInfo::Exceptions(ATExceptions { name_index, exception_index_table: exceptions }));
+        }
+        attributes.extend(method_attributes);
+        // The class writer doesn't need the parsed descriptor
+        Ok(MethodInfo { access_flags, descriptor_index, attributes, return_type: String::new(), args: Vec::new(), name_index })
+    }
+
+    fn label(&mut self, builder: &mut CodeBuilder, labels: &mut Labels) -> Result<Label, String> {
+        let name = self.word("a label")?;
+        self.label_named(&name, builder, labels)
+    }
+
+    fn label_named(&self, name: &str, builder: &mut CodeBuilder, labels: &mut Labels) -> Result<Label, String> {
+        if let Some(label) = labels.labels.get(name) {
+            return Ok(*label);
+        }
+        let label = builder.new_label();
+        labels.labels.insert(name.to_string(), label);
+        labels.used.push((name.to_string(), self.lines[self.line].number));
+        Ok(label)
+    }
+
+    fn instruction(&mut self, mnemonic: &str, builder: &mut CodeBuilder, labels: &mut Labels) -> Result<(), String> {
+        // The assembler decides when an instruction needs wide
+        if mnemonic == "wide" {
+            let mnemonic = self.word("an instruction")?;
+            return self.instruction(&mnemonic, builder, labels);
+        }
+        let Some(opcode) = (0..=u8::MAX).find(|opcode| opcode_name(*opcode) == mnemonic && *opcode != WIDE) else {
+            return self.error(&format!("unknown instruction {}", mnemonic));
+        };
+        let instruction = match opcode {
+            BIPUSH => {
+                let value: i8 = self.parse_word("a byte")?;
+                Instruction::Immediate(opcode, value as i32)
+            },
+            SIPUSH => {
+                let value: i16 = self.parse_word("a short")?;
+                Instruction::Immediate(opcode, value as i32)
+            },
+            NEWARRAY => {
+                let name = self.word("an array type")?;
+                match (T_BOOLEAN..=T_LONG).find(|type_code| array_type_name(*type_code) == Some(&name)) {
+                    Some(type_code) => Instruction::Immediate(opcode, type_code as i32),
+                    None => return self.error(&format!("unknown array type {}", name))
+                }
+            },
+            LDC | LDC_W => Instruction::Constant(opcode, self.constant(NumberType::Int, NumberType::Float)?),
+            LDC2_W => Instruction::Constant(opcode, self.constant(NumberType::Long, NumberType::Double)?),
+            ILOAD..=ALOAD | ISTORE..=ASTORE | RET => Instruction::Local(opcode, self.parse_word("a local variable index")?),
+            IINC => {
+                let local = self.parse_word("a local variable index")?;
+                Instruction::Iinc(local, self.parse_word("an increment")?)
+            },
+            IFEQ..=JSR | IFNULL | IFNONNULL | GOTO_W | JSR_W => Instruction::Jump(opcode, self.label(builder, labels)?),
+            TABLESWITCH => self.table_switch(builder, labels)?,
+            LOOKUPSWITCH => self.lookup_switch(builder, labels)?,
+            GETSTATIC..=PUTFIELD => {
+                let member = self.word("a field")?;
+                let descriptor = self.word("a field descriptor")?;
+                let Some((class, name)) = member.rsplit_once('/') else {
+                    return self.error(&format!("expected a class and field name, not {}", member));
+                };
+                Instruction::Constant(opcode, self.constant_pool.add_field_ref(class, name, &descriptor).map_err(|message| self.located(&message))?)
+            },
+            INVOKEVIRTUAL..=INVOKESTATIC => {
+                let interface = self.peek() == Some(&Token::Word(String::from("interface")));
+                if interface {
+                    self.token += 1;
+                }
+                Instruction::Constant(opcode, self.method_ref(interface)?)
+            },
+            INVOKEINTERFACE => {
+                let index = self.method_ref(true)?;
+                // The count of argument slots is computed, Jasmin files have it after the method
+                if self.peek().is_some() {
+                    let _count: u8 = self.parse_word("the count of arguments")?;
+                }
+                Instruction::Constant(opcode, index)
+            },
+            INVOKEDYNAMIC => {
+                let signature = self.word("a name and method descriptor")?;
+                let Some(descriptor_start) = signature.find('(') else {
+                    return self.error(&format!("expected a method descriptor after {}", signature));
+                };
+                let (name, descriptor) = signature.split_at(descriptor_start);
+                let bootstrap_method_attr_index = self.bootstrap_method()?;
+                Instruction::Constant(opcode, self.constant_pool.add_invoke_dynamic(bootstrap_method_attr_index, name, descriptor).map_err(|message| self.located(&message))?)
+            },
+            NEW | ANEWARRAY | CHECKCAST | INSTANCEOF => {
+                let class = self.word("a class name")?;
+                Instruction::Constant(opcode, self.constant_pool.add_class(&class).map_err(|message| self.located(&message))?)
+            },
+            MULTIANEWARRAY => {
+                let class = self.word("an array class")?;
+                let class_index = self.constant_pool.add_class(&class).map_err(|message| self.located(&message))?;
+                Instruction::MultiANewArray(class_index, self.parse_word("the number of dimensions")?)
+            },
+            other => Instruction::Simple(opcode)
+        };
+        builder.add(instruction);
+        Ok(())
+    }
+
+    /// Parses `tableswitch <low> [<high>]`, followed by lines with the labels and a line `default : <label>`.
+    fn table_switch(&mut self, builder: &mut CodeBuilder, labels: &mut Labels) -> Result<Instruction, String> {
+        let low = self.parse_word("the lowest key")?;
+        let high: Option<i32> = if self.peek().is_some() { Some(self.parse_word("the highest key")?) } else { None };
+        let mut targets = Vec::new();
+        loop {
+            self.end_of_line()?;
+            if !self.next_line() {
+                return self.error("expected the default of the tableswitch");
+            }
+            while let Some(Token::Word(word)) = self.peek().cloned() {
+                if word.starts_with("default") {
+                    let default = self.default_label(builder, labels)?;
+                    if high.is_some_and(|high| high as i64 - low as i64 + 1 != targets.len() as i64) {
+                        return self.error("the number of labels doesn't match the keys of the tableswitch");
+                    }
+                    return Ok(Instruction::TableSwitch { low, default, targets });
+                }
+                self.token += 1;
+                targets.push(self.label_named(&word, builder, labels)?);
+            }
+        }
+    }
+
+    /// Parses `lookupswitch`, followed by lines `<key> : <label>` and a line `default : <label>`.
+    fn lookup_switch(&mut self, builder: &mut CodeBuilder, labels: &mut Labels) -> Result<Instruction, String> {
+        let mut pairs = Vec::new();
+        loop {
+            self.end_of_line()?;
+            if !self.next_line() {
+                return self.error("expected the default of the lookupswitch");
+            }
+            if let Some(Token::Word(word)) = self.peek() && word.starts_with("default") {
+                let default = self.default_label(builder, labels)?;
+                return Ok(Instruction::LookupSwitch { default, pairs });
+            }
+            let key = self.word("a key")?;
+            let key = match key.strip_suffix(':') {
+                Some(key) => key.to_string(),
+                None => {
+                    self.keyword(":")?;
+                    key
+                }
+            };
+            let Ok(key) = key.parse() else {
+                return self.error(&format!("expected a key, not {}", key));
+            };
+            pairs.push((key, self.label(builder, labels)?));
+        }
+    }
+
+    fn default_label(&mut self, builder: &mut CodeBuilder, labels: &mut Labels) -> Result<Label, String> {
+        if self.word("default")? == "default" {
+            self.keyword(":")?;
+        }
+        self.label(builder, labels)
+    }
+
+    /// Parses a method as `class/name(arguments)return`.
+    fn method_ref(&mut self, interface: bool) -> Result<u16, String> {
+        let signature = self.word("a method")?;
+        let Some((class, name, descriptor)) = signature.find('(').and_then(|descriptor_start| {
+            let (member, descriptor) = signature.split_at(descriptor_start);
+            member.rsplit_once('/').map(|(class, name)| (class, name, descriptor))
+        }) else {
+            return self.error(&format!("expected a class, method name and descriptor, not {}", signature));
+        };
+        Ok(if interface {
+            self.constant_pool.add_interface_method_ref(class, name, descriptor).map_err(|message| self.located(&message))?
+        } else {
+            self.constant_pool.add_method_ref(class, name, descriptor).map_err(|message| self.located(&message))?
+        })
+    }
+
+    /// Parses `<kind> [interface] <member>`, where the kind is named after the instruction that the handle behaves like.
+    fn method_handle(&mut self) -> Result<u16, String> {
+        let kind = self.word("a method handle kind")?;
+        let Some(reference_kind) = REFERENCE_KINDS.iter().skip(1).position(|name| *name == kind).map(|index| index as u8 + 1) else {
+            return self.error(&format!("unknown method handle kind {}", kind));
+        };
+        let reference_index = match kind.as_str() {
+            "getfield" | "getstatic" | "putfield" | "putstatic" => {
+                let member = self.word("a field")?;
+                let descriptor = self.word("a field descriptor")?;
+                let Some((class, name)) = member.rsplit_once('/') else {
+                    return self.error(&format!("expected a class and field name, not {}", member));
+                };
+                self.constant_pool.add_field_ref(class, name, &descriptor).map_err(|message| self.located(&message))?
+            },
+            "invokeinterface" => self.method_ref(true)?,
+            other => {
+                let interface = self.peek() == Some(&Token::Word(String::from("interface")));
+                if interface {
+                    self.token += 1;
+                }
+                self.method_ref(interface)?
+            }
+        };
+        self.constant_pool.add_method_handle(reference_kind, reference_index).map_err(|message| self.located(&message))
+    }
+
+    /// Parses `{ <method handle> <arguments>... }` and returns its index in the BootstrapMethods attribute.
+    fn bootstrap_method(&mut self) -> Result<u16, String> {
+        if self.next("a bootstrap method in braces")? != Token::Open {
+            return self.error("expected a bootstrap method in braces");
+        }
+        let bootstrap_method_ref = self.method_handle()?;
+        let mut bootstrap_arguments = Vec::new();
+        while self.peek() != Some(&Token::Close) {
+            bootstrap_arguments.push(self.constant(NumberType::Int, NumberType::Double)?);
+        }
+        self.token += 1;
+        let existing = self.bootstrap_methods.iter().position(|method| {
+            method.bootstrap_method_ref == bootstrap_method_ref && method.bootstrap_arguments == bootstrap_arguments
+        });
+        Ok(match existing {
+            Some(index) => index as u16,
+            None => {
+                self.bootstrap_methods.push(BootstrapMethod { bootstrap_method_ref, bootstrap_arguments });
+                self.bootstrap_methods.len() as u16 - 1
+            }
+        })
+    }
+
+    /// Parses a loadable constant and adds it to the constant pool. Numbers without L, f or d at the end
+    /// get the given type for integers or for decimals.
+    fn constant(&mut self, integer: NumberType, decimal: NumberType) -> Result<u16, String> {
+        let word = match self.next("a constant")? {
+            Token::Text(text) => {
+                let string_index = self.constant_pool.add_utf16(&text).map_err(|message| self.located(&message))?;
+                return self.constant_pool.add(ConstantPoolItem::CPStringInfo { string_index }).map_err(|message| self.located(&message));
+            },
+            Token::Word(word) => word,
+            other => return self.error("expected a constant")
+        };
+        match word.as_str() {
+            "class" => {
+                let class = self.word("a class name")?;
+                self.constant_pool.add_class(&class).map_err(|message| self.located(&message))
+            },
+            "methodtype" => {
+                let descriptor = self.word("a method descriptor")?;
+                self.constant_pool.add_method_type(&descriptor).map_err(|message| self.located(&message))
+            },
+            "methodhandle" => self.method_handle(),
+            "dynamic" => {
+                let name = self.word("a name")?;
+                let descriptor = self.word("a descriptor")?;
+                let bootstrap_method_attr_index = self.bootstrap_method()?;
+                self.constant_pool.add_dynamic(bootstrap_method_attr_index, &name, &descriptor).map_err(|message| self.located(&message))
+            },
+            number => match parse_number(number, integer, decimal) {
+                Some(item) => self.constant_pool.add(item).map_err(|message| self.located(&message)),
+                None => self.error(&format!("bad constant {}", number))
+            }
+        }
+    }
+}
+
+fn parse_number(text: &str, integer: NumberType, decimal: NumberType) -> Option<ConstantPoolItem> {
+    let (number, suffix) = match text.chars().last()? {
+        'L' | 'l' => (&text[..text.len() - 1], Some(NumberType::Long)),
+        'f' | 'F' => (&text[..text.len() - 1], Some(NumberType::Float)),
+        'd' | 'D' => (&text[..text.len() - 1], Some(NumberType::Double)),
+        other => (text, None)
+    };
+    let number_type = suffix.unwrap_or(if number.parse::<i64>().is_ok() { integer } else { decimal });
+    // A NaN other than the one Java uses has its bits written out, like NaN<0x7fc00001>
+    let nan_bits = number.strip_prefix("NaN<0x").and_then(|bits| bits.strip_suffix('>'));
+    Some(match number_type {
+        NumberType::Int => ConstantPoolItem::CPIntegerInfo { value: number.parse().ok()? },
+        NumberType::Long => ConstantPoolItem::CPLongInfo { value: number.parse().ok()? },
+        NumberType::Float => ConstantPoolItem::CPFloatInfo { value: match nan_bits {
+            Some(bits) => f32::from_bits(u32::from_str_radix(bits, 16).ok()?),
+            None => number.parse().ok()?
+        } },
+        NumberType::Double => ConstantPoolItem::CPDoubleInfo { value: match nan_bits {
+            Some(bits) => f64::from_bits(u64::from_str_radix(bits, 16).ok()?),
+            None => number.parse().ok()?
+        } },
+    })
+}
+
+/// Splits a line into words, strings in double quotes and braces. A comment starts with a ; at the start of a word,
+/// as descriptors have them at the end.
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            char if char.is_whitespace() => {},
+            ';' => break,
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut text = Vec::new();
+                loop {
+                    let char = chars.next().ok_or("unterminated string")?;
+                    let escaped = match char {
+                        '"' => break,
+                        '\\' => match chars.next().ok_or("unterminated string")? {
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            'b' => '\u{8}',
+                            'f' => '\u{c}',
+                            'u' => {
+                                let digits: String = (0..4).filter_map(|_| chars.next()).collect();
+                                let unit = u16::from_str_radix(&digits, 16).map_err(|_| format!("bad escape \\u{}", digits))?;
+                                // Kept as a code unit, it can be half of a surrogate pair
+                                text.push(unit);
+                                continue;
+                            },
+                            other => other
+                        },
+                        other => other
+                    };
+                    text.extend(escaped.encode_utf16(&mut [0; 2]).iter());
+                }
+                tokens.push(Token::Text(text));
+            },
+            char => {
+                let mut word = String::from(char);
+                while let Some(char) = chars.peek() && !char.is_whitespace() && !matches!(char, '{' | '}') {
+                    word.push(*char);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
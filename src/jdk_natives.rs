@@ -0,0 +1,383 @@
+use std::{env, fs, io::{self, Write}, path::{Path, PathBuf}, sync::Arc, thread};
+
+use crate::{boot::construct, exceptions::{get_backtrace, BacktraceEntry, JvmResult, Throwable}, interpreter::Interpreter, natives::{reference_arg, NativeRegistry}, object::{ArrayData, Object, ObjectRef, Value}, runtime_class::Class, strings::{new_java_string, to_rust_string}, thread::JavaThread};
+
+/// Registers the natives of the JDK outside of java.lang, that System initialization depends on.
+pub fn register(registry: &NativeRegistry) {
+    registry.register("java/security/AccessController", "getStackAccessControlContext", "()Ljava/security/AccessControlContext;", return_null);
+    registry.register("java/security/AccessController", "getInheritedAccessControlContext", "()Ljava/security/AccessControlContext;", return_null);
+
+    // There is no class data sharing archive
+    registry.register("jdk/internal/misc/CDS", "isDumpingClassList0", "()Z", return_false);
+    registry.register("jdk/internal/misc/CDS", "isDumpingArchive0", "()Z", return_false);
+    registry.register("jdk/internal/misc/CDS", "isSharingEnabled0", "()Z", return_false);
+    registry.register("jdk/internal/misc/CDS", "initializeFromArchive", "(Ljava/lang/Class;)V", return_nothing);
+    registry.register("jdk/internal/misc/CDS", "getRandomSeedForDumping", "()J", return_zero_long);
+    registry.register("jdk/internal/misc/VM", "initialize", "()V", return_nothing);
+    registry.register("jdk/internal/misc/VM", "initializeFromArchive", "(Ljava/lang/Class;)V", return_nothing);
+
+    registry.register("jdk/internal/reflect/Reflection", "getCallerClass", "()Ljava/lang/Class;", reflection_get_caller_class);
+    registry.register("sun/reflect/Reflection", "getCallerClass", "()Ljava/lang/Class;", reflection_get_caller_class);
+    registry.register("jdk/internal/reflect/Reflection", "getClassAccessFlags", "(Ljava/lang/Class;)I", reflection_get_class_access_flags);
+    registry.register("sun/reflect/Reflection", "getClassAccessFlags", "(Ljava/lang/Class;)I", reflection_get_class_access_flags);
+
+    // All classes share one loader, which is also the boot loader
+    registry.register("jdk/internal/loader/BootLoader", "setBootLoaderUnnamedModule0", "(Ljava/lang/Module;)V", boot_loader_set_boot_loader_unnamed_module);
+    registry.register("java/lang/ClassLoader", "findBootstrapClass", "(Ljava/lang/String;)Ljava/lang/Class;", class_loader_find_bootstrap_class);
+
+    registry.register("java/lang/System", "initProperties", "(Ljava/util/Properties;)Ljava/util/Properties;", system_init_properties);
+    registry.register("java/lang/StringUTF16", "isBigEndian", "()Z", string_utf16_is_big_endian);
+
+    // JDK 9 and later fill the elements in one go, JDK 8 asks for them one by one
+    registry.register("java/lang/StackTraceElement", "initStackTraceElements", "([Ljava/lang/StackTraceElement;Ljava/lang/Throwable;)V", stack_trace_element_init_stack_trace_elements);
+    registry.register("java/lang/Throwable", "getStackTraceDepth", "()I", throwable_get_stack_trace_depth);
+    registry.register("java/lang/Throwable", "getStackTraceElement", "(I)Ljava/lang/StackTraceElement;", throwable_get_stack_trace_element);
+
+    // There is no garbage collector, so no reference ever gets cleared and enqueued
+    registry.register("java/lang/ref/Reference", "waitForReferencePendingList", "()V", reference_wait_for_reference_pending_list);
+    registry.register("java/lang/ref/Reference", "hasReferencePendingList", "()Z", return_false);
+    registry.register("java/lang/ref/Reference", "getAndClearReferencePendingList", "()Ljava/lang/ref/Reference;", return_null);
+    // There is no garbage collector, so references keep their referent until they get cleared
+    for class_name in ["java/lang/ref/Reference", "java/lang/ref/PhantomReference"] {
+        registry.register(class_name, "refersTo0", "(Ljava/lang/Object;)Z", reference_refers_to);
+    }
+    registry.register("java/lang/ref/Reference", "clear0", "()V", reference_clear);
+
+    registry.register("jdk/internal/util/SystemProps$Raw", "vmProperties", "()[Ljava/lang/String;", system_props_vm_properties);
+    registry.register("jdk/internal/util/SystemProps$Raw", "platformProperties", "()[Ljava/lang/String;", system_props_platform_properties);
+
+    registry.register("jdk/internal/misc/Signal", "findSignal0", "(Ljava/lang/String;)I", signal_find_signal);
+    registry.register("jdk/internal/misc/Signal", "handle0", "(IJ)J", return_zero_long);
+    registry.register("sun/misc/Signal", "findSignal", "(Ljava/lang/String;)I", signal_find_signal);
+    registry.register("sun/misc/Signal", "handle0", "(IJ)J", return_zero_long);
+
+    registry.register("java/io/FileDescriptor", "getHandle", "(I)J", file_descriptor_get_handle);
+    registry.register("java/io/FileDescriptor", "getAppend", "(I)Z", return_false);
+    registry.register("java/io/FileOutputStream", "writeBytes", "([BIIZ)V", file_output_stream_write_bytes);
+    registry.register("java/io/FileOutputStream", "write", "(IZ)V", file_output_stream_write);
+    registry.register("java/io/UnixFileSystem", "canonicalize0", "(Ljava/lang/String;)Ljava/lang/String;", unix_file_system_canonicalize);
+    registry.register("java/io/UnixFileSystem", "getBooleanAttributes0", "(Ljava/io/File;)I", unix_file_system_get_boolean_attributes);
+    registry.register("java/io/UnixFileSystem", "getLength", "(Ljava/io/File;)J", unix_file_system_get_length);
+}
+
+fn boot_loader_set_boot_loader_unnamed_module(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    thread.vm.set_unnamed_module(reference_arg(&args, 0)?);
+    Ok(None)
+}
+
+/// Returns the class with a binary name, or null when there is none.
+fn class_loader_find_bootstrap_class(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let name = to_rust_string(&reference_arg(&args, 0)?);
+    match thread.vm.load_class(&name.replace('.', "/")) {
+        Ok(class) => Ok(Some(Value::Reference(Some(class.mirror(thread)?)))),
+        Err(Throwable::Pending { class_name, .. }) if class_name == "java/lang/NoClassDefFoundError" => Ok(Some(Value::null())),
+        Err(other) => Err(other)
+    }
+}
+
+fn return_null(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::null()))
+}
+
+fn return_false(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(0)))
+}
+
+fn return_zero_long(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Long(0)))
+}
+
+fn return_nothing(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(None)
+}
+
+fn string_utf16_is_big_endian(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(cfg!(target_endian = "big") as i32)))
+}
+
+/// The line number StackTraceElement has for a frame, which is -2 for native methods and -1 when unknown.
+fn stack_trace_line_number(entry: &BacktraceEntry) -> i32 {
+    match (entry.method.is_native(), entry.line_number()) {
+        (true, _) => -2,
+        (false, Some(line_number)) => line_number as i32,
+        (false, None) => -1
+    }
+}
+
+fn optional_string(thread: &JavaThread, string: &str) -> JvmResult<Value> {
+    match string.is_empty() {
+        true => Ok(Value::null()),
+        false => Ok(Value::Reference(Some(new_java_string(thread, string)?)))
+    }
+}
+
+fn stack_trace_element_init_stack_trace_elements(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let elements = match &*reference_arg(&args, 0)?.array() {
+        ArrayData::Reference(elements) => elements.clone(),
+        other => panic!("Stack trace elements are not an object array")
+    };
+    let backtrace = get_backtrace(&reference_arg(&args, 1)?);
+    for (element, entry) in elements.iter().zip(&backtrace) {
+        let element = element.as_ref().ok_or_else(|| Throwable::without_message("java/lang/NullPointerException"))?;
+        let class = entry.method.class();
+        let values = [
+            ("declaringClassObject", "Ljava/lang/Class;", Value::Reference(Some(class.mirror(thread)?))),
+            ("declaringClass", "Ljava/lang/String;", Value::Reference(Some(new_java_string(thread, &class.name.replace('/', "."))?))),
+            ("methodName", "Ljava/lang/String;", Value::Reference(Some(new_java_string(thread, &entry.method.name)?))),
+            ("fileName", "Ljava/lang/String;", optional_string(thread, &class.info.as_ref().unwrap().source_file)?),
+            ("lineNumber", "I", Value::Int(stack_trace_line_number(entry))),
+        ];
+        let mut fields = element.fields();
+        for (name, descriptor, value) in values {
+            fields[element.class.instance_field_slot(name, descriptor).unwrap()] = value;
+        }
+    }
+    Ok(None)
+}
+
+fn throwable_get_stack_trace_depth(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(get_backtrace(&reference_arg(&args, 0)?).len() as i32)))
+}
+
+fn throwable_get_stack_trace_element(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let backtrace = get_backtrace(&reference_arg(&args, 0)?);
+    let entry = backtrace.get(args[1].as_int() as usize)
+        .ok_or_else(|| Throwable::without_message("java/lang/IndexOutOfBoundsException"))?;
+    let class = entry.method.class();
+    let element = construct(thread, "java/lang/StackTraceElement", "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;I)V", vec![
+        Value::Reference(Some(new_java_string(thread, &class.name.replace('/', "."))?)),
+        Value::Reference(Some(new_java_string(thread, &entry.method.name)?)),
+        optional_string(thread, &class.info.as_ref().unwrap().source_file)?,
+        Value::Int(stack_trace_line_number(entry)),
+    ])?;
+    Ok(Some(Value::Reference(Some(element))))
+}
+
+/// The reference handler thread waits here for references the garbage collector cleared, which never happens.
+fn reference_wait_for_reference_pending_list(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    loop {
+        thread::park();
+    }
+}
+
+/// Returns the class of the method that called the method asking for its caller.
+/// The top frames are the native itself and the method that calls it.
+fn referent_slot(reference: &ObjectRef) -> usize {
+    reference.class.instance_field_slot("referent", "Ljava/lang/Object;").unwrap()
+}
+
+fn reference_refers_to(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let reference = reference_arg(&args, 0)?;
+    let referent = reference.fields()[referent_slot(&reference)].clone();
+    let refers_to = match (referent.as_reference(), args[1].as_reference()) {
+        (Some(referent), Some(object)) => Arc::ptr_eq(referent, object),
+        (referent, object) => referent.is_none() && object.is_none()
+    };
+    Ok(Some(Value::Int(refers_to as i32)))
+}
+
+fn reference_clear(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let reference = reference_arg(&args, 0)?;
+    reference.fields()[referent_slot(&reference)] = Value::null();
+    Ok(None)
+}
+
+fn reflection_get_caller_class(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let caller = thread.stack.borrow().iter().rev()
+        .nth(2)
+        .map(|frame| frame.method.class());
+    match caller {
+        Some(class) => Ok(Some(Value::Reference(Some(class.mirror(thread)?)))),
+        None => Ok(Some(Value::null()))
+    }
+}
+
+fn reflection_get_class_access_flags(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(args[0].as_reference().unwrap());
+    Ok(Some(Value::Int(class.access_flags.bits() as i32)))
+}
+
+fn new_string_array(thread: &JavaThread, strings: Vec<Option<ObjectRef>>) -> JvmResult<ObjectRef> {
+    let class = thread.vm.load_class("[Ljava/lang/String;")?;
+    Ok(Object::new_array(class, ArrayData::Reference(strings)))
+}
+
+/// Returns the properties set by the VM, as an array of keys and values.
+fn system_props_vm_properties(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mut strings = Vec::with_capacity(thread.vm.system_properties.len() * 2);
+    for (key, value) in &thread.vm.system_properties {
+        strings.push(Some(new_java_string(thread, key)?));
+        strings.push(Some(new_java_string(thread, value)?));
+    }
+    Ok(Some(Value::Reference(Some(new_string_array(thread, strings)?))))
+}
+
+// The platform properties that JDK 8 expects the VM to set
+const PLATFORM_PROPERTY_NAMES: [&str; 15] = [
+    "file.encoding", "sun.jnu.encoding", "file.separator", "path.separator", "line.separator", "java.io.tmpdir",
+    "os.name", "os.arch", "os.version", "sun.arch.data.model", "sun.cpu.endian", "sun.io.unicode.encoding",
+    "user.dir", "user.home", "user.name"
+];
+
+fn platform_property(name: &str) -> Option<String> {
+    let value = match name {
+        "file.encoding" | "sun.jnu.encoding" => String::from("UTF-8"),
+        "file.separator" => String::from("/"),
+        "path.separator" => String::from(":"),
+        "line.separator" => String::from("\n"),
+        "java.io.tmpdir" => env::temp_dir().to_string_lossy().into_owned(),
+        "os.name" => match env::consts::OS {
+            "linux" => String::from("Linux"),
+            "macos" => String::from("Mac OS X"),
+            other => String::from(other)
+        },
+        "os.arch" => match env::consts::ARCH {
+            "x86_64" => String::from("amd64"),
+            other => String::from(other)
+        },
+        "os.version" => String::from("unknown"),
+        "sun.arch.data.model" => (usize::BITS).to_string(),
+        "sun.cpu.endian" => String::from(if cfg!(target_endian = "big") { "big" } else { "little" }),
+        "sun.io.unicode.encoding" => String::from(if cfg!(target_endian = "big") { "UnicodeBig" } else { "UnicodeLittle" }),
+        "user.dir" => env::current_dir().ok()?.to_string_lossy().into_owned(),
+        "user.home" => env::var("HOME").ok()?,
+        "user.name" => env::var("USER").unwrap_or_else(|_| String::from("?")),
+        "display.language" | "format.language" => String::from("en"),
+        other => return None
+    };
+    Some(value)
+}
+
+/// Fills in the system properties on JDK 8, where System asks for them with a Properties object.
+fn system_init_properties(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let properties = args[0].as_reference().unwrap();
+    let platform_properties = PLATFORM_PROPERTY_NAMES.iter()
+        .filter_map(|name| Some((name.to_string(), platform_property(name)?)));
+    for (key, value) in platform_properties.chain(thread.vm.system_properties.iter().cloned()) {
+        let key = Value::Reference(Some(new_java_string(thread, &key)?));
+        let value = Value::Reference(Some(new_java_string(thread, &value)?));
+        Interpreter::invoke_virtual_method(thread, properties, "setProperty", "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/Object;", vec![key, value])?;
+    }
+    Ok(Some(args[0].clone()))
+}
+
+/// Returns the platform properties, at the positions given by the `_<name>_NDX` constants of SystemProps.Raw.
+fn system_props_platform_properties(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let raw_class = thread.vm.load_class("jdk/internal/util/SystemProps$Raw")?;
+    let static_values = raw_class.static_values.lock().unwrap().clone();
+    let static_int = |name: &str| raw_class.fields.iter()
+        .find(|field| field.is_static() && field.name == name)
+        .map(|field| static_values[field.slot].as_int() as usize);
+    let mut strings = vec![None; static_int("FIXED_LENGTH").unwrap()];
+    for field in raw_class.fields.iter().filter(|field| field.is_static() && field.name.ends_with("_NDX")) {
+        // For example _java_io_tmpdir_NDX is the index of java.io.tmpdir
+        let name = field.name.trim_start_matches('_').trim_end_matches("_NDX").replace('_', ".");
+        if let Some(value) = platform_property(&name) {
+            strings[static_values[field.slot].as_int() as usize] = Some(new_java_string(thread, &value)?);
+        }
+    }
+    Ok(Some(Value::Reference(Some(new_string_array(thread, strings)?))))
+}
+
+/// Returns the number of a signal by its name, like `INT`. Handlers are never installed, so signals keep their default action.
+fn signal_find_signal(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let number = match to_rust_string(args[0].as_reference().unwrap()).as_str() {
+        "HUP" => 1,
+        "INT" => 2,
+        "QUIT" => 3,
+        "KILL" => 9,
+        "USR1" => 10,
+        "USR2" => 12,
+        "PIPE" => 13,
+        "TERM" => 15,
+        other => -1
+    };
+    Ok(Some(Value::Int(number)))
+}
+
+/// File descriptors are used directly, there are no Windows handles.
+fn file_descriptor_get_handle(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Long(-1)))
+}
+
+/// Returns the `fd` of the FileDescriptor of a stream.
+fn stream_fd(stream: &ObjectRef) -> i32 {
+    let descriptor_slot = stream.class.instance_field_slot("fd", "Ljava/io/FileDescriptor;").unwrap();
+    let descriptor = stream.fields()[descriptor_slot].as_reference().cloned().unwrap();
+    let fd_slot = descriptor.class.instance_field_slot("fd", "I").unwrap();
+    descriptor.fields()[fd_slot].as_int()
+}
+
+pub fn write_to_fd(fd: i32, bytes: &[u8]) -> JvmResult<()> {
+    let result = match fd {
+        1 => io::stdout().write_all(bytes).and_then(|_| io::stdout().flush()),
+        2 => io::stderr().write_all(bytes),
+        // TODO: Files
+        other => return Err(Throwable::new("java/io/IOException", format!("Writing to file descriptor {} is not supported", fd)))
+    };
+    result.map_err(|error| Throwable::new("java/io/IOException", error.to_string()))
+}
+
+fn file_output_stream_write_bytes(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let stream = args[0].as_reference().unwrap();
+    let array = args[1].as_reference().cloned().ok_or_else(|| Throwable::without_message("java/lang/NullPointerException"))?;
+    let (offset, length) = (args[2].as_int(), args[3].as_int());
+    let bytes: Vec<u8> = match &*array.array() {
+        ArrayData::Byte(bytes) => {
+            if offset < 0 || length < 0 || offset as usize + length as usize > bytes.len() {
+                return Err(Throwable::without_message("java/lang/IndexOutOfBoundsException"));
+            }
+            bytes[offset as usize..(offset + length) as usize].iter().map(|byte| *byte as u8).collect()
+        },
+        other => panic!("FileOutputStream.writeBytes needs a byte array")
+    };
+    write_to_fd(stream_fd(stream), &bytes)?;
+    Ok(None)
+}
+
+fn file_output_stream_write(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let stream = args[0].as_reference().unwrap();
+    write_to_fd(stream_fd(stream), &[args[1].as_int() as u8])?;
+    Ok(None)
+}
+
+/// Resolves the links of the part of a path that exists, like realpath does, and appends the rest of it.
+fn canonicalize(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => canonicalize(parent).join(name),
+        other => path.to_path_buf()
+    }
+}
+
+fn unix_file_system_canonicalize(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let path = to_rust_string(&reference_arg(&args, 1)?);
+    let canonical = canonicalize(Path::new(&path));
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &canonical.to_string_lossy())?))))
+}
+
+/// Returns the `path` of a File.
+fn file_path(file: &ObjectRef) -> String {
+    let slot = file.class.instance_field_slot("path", "Ljava/lang/String;").unwrap();
+    let path = file.fields()[slot].as_reference().cloned().unwrap();
+    to_rust_string(&path)
+}
+
+/// Returns the BA_EXISTS, BA_REGULAR and BA_DIRECTORY flags of java.io.FileSystem that apply to a file.
+fn unix_file_system_get_boolean_attributes(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let attributes = match fs::metadata(file_path(&reference_arg(&args, 1)?)) {
+        Ok(metadata) if metadata.is_dir() => 0x01 | 0x04,
+        Ok(metadata) if metadata.is_file() => 0x01 | 0x02,
+        Ok(metadata) => 0x01,
+        Err(error) => 0
+    };
+    Ok(Some(Value::Int(attributes)))
+}
+
+/// Returns the length of a file, or 0 when it doesn't exist.
+fn unix_file_system_get_length(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let length = fs::metadata(file_path(&reference_arg(&args, 1)?)).map_or(0, |metadata| metadata.len());
+    Ok(Some(Value::Long(length as i64)))
+}
@@ -0,0 +1,259 @@
+use std::{collections::HashMap, fs::File, io::{Read, Seek, SeekFrom}, sync::Mutex};
+
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+const IMAGE_MAGIC: u32 = 0xCAFEDADA;
+const COMPRESSED_MAGIC: u32 = 0xCAFEFAFA;
+const HASH_MULTIPLIER: u32 = 0x01000193;
+const HEADER_SIZE: usize = 7 * 4;
+const COMPRESSED_HEADER_SIZE: usize = 29;
+
+const ATTRIBUTE_END: usize          = 0;
+const ATTRIBUTE_MODULE: usize       = 1;
+const ATTRIBUTE_PARENT: usize       = 2;
+const ATTRIBUTE_BASE: usize         = 3;
+const ATTRIBUTE_EXTENSION: usize    = 4;
+const ATTRIBUTE_OFFSET: usize       = 5;
+const ATTRIBUTE_COMPRESSED: usize   = 6;
+const ATTRIBUTE_UNCOMPRESSED: usize = 7;
+const ATTRIBUTE_COUNT: usize        = 8;
+
+/// Reader for the jimage container format used for `$JAVA_HOME/lib/modules` since JDK 9.
+///
+/// The header and index are kept in memory, resources are read from the file on demand.
+pub struct JImage {
+    pub path: String,
+    big_endian: bool,
+    table_length: u32,
+    redirect: Vec<i32>,
+    offsets: Vec<u32>,
+    locations: Vec<u8>,
+    strings: Vec<u8>,
+    index_size: u64,
+    file: Mutex<File>,
+    package_modules: Mutex<HashMap<String, Option<String>>>,
+}
+
+struct ImageLocation {
+    attributes: [u64; ATTRIBUTE_COUNT]
+}
+
+impl ImageLocation {
+    fn get(&self, kind: usize) -> u64 {
+        self.attributes[kind]
+    }
+}
+
+impl JImage {
+    /// Reads the header and index of an image. Returns None when the file isn't a jimage of a version that is known.
+    pub fn open(path: &str) -> Option<JImage> {
+        let mut file = File::open(path).ok()?;
+        let mut header = [0u8; HEADER_SIZE];
+        file.read_exact(&mut header).ok()?;
+
+        // The image is written in the byte order of the platform that produced it
+        let big_endian = match u32::from_le_bytes(header[0..4].try_into().unwrap()) {
+            IMAGE_MAGIC => false,
+            other if u32::from_be_bytes(header[0..4].try_into().unwrap()) == IMAGE_MAGIC => true,
+            other => return None
+        };
+        let read_header = |index: usize| -> u32 {
+            let bytes: [u8; 4] = header[index * 4..(index + 1) * 4].try_into().unwrap();
+            if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+        };
+        let version = read_header(1);
+        let major_version = version >> 16;
+        if major_version != 1 {
+            return None;
+        }
+        let table_length = read_header(4);
+        let locations_size = read_header(5) as usize;
+        let strings_size = read_header(6) as usize;
+
+        let table_size = table_length as usize * 4;
+        let file_size = file.metadata().ok()?.len() as usize;
+        if table_size * 2 + locations_size + strings_size > file_size {
+            return None;
+        }
+        let mut index = vec![0u8; table_size * 2 + locations_size + strings_size];
+        file.read_exact(&mut index).ok()?;
+
+        let mut image = JImage {
+            path: String::from(path),
+            big_endian,
+            table_length,
+            redirect: Vec::with_capacity(table_length as usize),
+            offsets: Vec::with_capacity(table_length as usize),
+            locations: index[table_size * 2..table_size * 2 + locations_size].to_vec(),
+            strings: index[table_size * 2 + locations_size..].to_vec(),
+            index_size: (HEADER_SIZE + index.len()) as u64,
+            file: Mutex::new(file),
+            package_modules: Mutex::new(HashMap::new()),
+        };
+        for entry in 0..table_length as usize {
+            let redirect = image.read_u32(&index[entry * 4..]) as i32;
+            let offset = image.read_u32(&index[table_size + entry * 4..]);
+            image.redirect.push(redirect);
+            image.offsets.push(offset);
+        }
+        Some(image)
+    }
+
+    fn read_u32(&self, bytes: &[u8]) -> u32 {
+        let buffer: [u8; 4] = bytes[0..4].try_into().unwrap();
+        if self.big_endian { u32::from_be_bytes(buffer) } else { u32::from_le_bytes(buffer) }
+    }
+
+    fn hash_code(name: &str, seed: u32) -> u32 {
+        let mut hash = seed;
+        for byte in name.as_bytes() {
+            hash = hash.wrapping_mul(HASH_MULTIPLIER) ^ (*byte as u32);
+        }
+        hash & 0x7FFFFFFF
+    }
+
+    fn get_string(&self, offset: u64) -> &str {
+        let start = offset as usize;
+        let length = self.strings[start..].iter().position(|byte| *byte == 0).unwrap();
+        std::str::from_utf8(&self.strings[start..start + length]).unwrap()
+    }
+
+    fn read_location(&self, offset: usize) -> ImageLocation {
+        let mut attributes = [0u64; ATTRIBUTE_COUNT];
+        let mut position = offset;
+        while position < self.locations.len() {
+            let data = self.locations[position] as usize;
+            let kind = data >> 3;
+            if kind == ATTRIBUTE_END {
+                break;
+            }
+            let length = (data & 0x7) + 1;
+            let mut value: u64 = 0;
+            for byte in &self.locations[position + 1..position + 1 + length] {
+                value = (value << 8) | (*byte as u64);
+            }
+            attributes[kind] = value;
+            position += length + 1;
+        }
+        ImageLocation { attributes }
+    }
+
+    /// Rebuilds the full name of a location, in the form `/module/parent/base.extension`.
+    fn location_name(&self, location: &ImageLocation) -> String {
+        let mut name = String::new();
+        let module = self.get_string(location.get(ATTRIBUTE_MODULE));
+        if !module.is_empty() {
+            name += "/";
+            name += module;
+            name += "/";
+        }
+        let parent = self.get_string(location.get(ATTRIBUTE_PARENT));
+        if !parent.is_empty() {
+            name += parent;
+            name += "/";
+        }
+        name += self.get_string(location.get(ATTRIBUTE_BASE));
+        let extension = self.get_string(location.get(ATTRIBUTE_EXTENSION));
+        if !extension.is_empty() {
+            name += ".";
+            name += extension;
+        }
+        name
+    }
+
+    fn find_location(&self, name: &str) -> Option<ImageLocation> {
+        if self.table_length == 0 {
+            return None;
+        }
+        let mut index = self.redirect[(Self::hash_code(name, HASH_MULTIPLIER) % self.table_length) as usize];
+        if index < 0 {
+            // Negative values point at the location directly
+            index = -index - 1;
+        } else if index > 0 {
+            // Positive values are the seed for a second hash
+            index = (Self::hash_code(name, index as u32) % self.table_length) as i32;
+        } else {
+            return None;
+        }
+        let location = self.read_location(self.offsets[index as usize] as usize);
+        // The hash table only gives a candidate, so the name still needs to be verified
+        if self.location_name(&location) == name {
+            Some(location)
+        } else {
+            None
+        }
+    }
+
+    /// Reads the content of a location. The error tells what is wrong with content that can't be read.
+    fn read_content(&self, location: &ImageLocation) -> Result<Vec<u8>, String> {
+        let compressed_size = location.get(ATTRIBUTE_COMPRESSED) as usize;
+        let uncompressed_size = location.get(ATTRIBUTE_UNCOMPRESSED) as usize;
+        let size = if compressed_size != 0 { compressed_size } else { uncompressed_size };
+        let mut bytes = vec![0u8; size];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(self.index_size + location.get(ATTRIBUTE_OFFSET))).map_err(|error| error.to_string())?;
+            file.read_exact(&mut bytes).map_err(|_| "Truncated resource")?;
+        }
+        if compressed_size != 0 {
+            bytes = self.decompress(bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    /// Undoes the compression applied by `jlink --compress`.
+    /// Resources can be compressed several times, each layer having its own header.
+    fn decompress(&self, mut bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        while bytes.len() >= COMPRESSED_HEADER_SIZE && self.read_u32(&bytes) == COMPRESSED_MAGIC {
+            let read_u64 = |offset: usize| -> u64 {
+                let buffer: [u8; 8] = bytes[offset..offset + 8].try_into().unwrap();
+                if self.big_endian { u64::from_be_bytes(buffer) } else { u64::from_le_bytes(buffer) }
+            };
+            let compressed_size = read_u64(4) as usize;
+            let decompressor = self.get_string(self.read_u32(&bytes[20..]) as u64);
+            let payload = bytes.get(COMPRESSED_HEADER_SIZE..COMPRESSED_HEADER_SIZE.saturating_add(compressed_size)).ok_or("Truncated compressed resource")?;
+            bytes = match decompressor {
+                "zip" => decompress_to_vec_zlib(payload).map_err(|error| format!("Corrupt compressed resource: {}", error))?,
+                other => return Err(format!("Unsupported jimage decompressor {}", other))
+            };
+        }
+        Ok(bytes)
+    }
+
+    /// Returns the name of the module that contains the given package, like `java/lang`.
+    pub fn package_to_module(&self, package: &str) -> Option<String> {
+        let mut package_modules = self.package_modules.lock().unwrap();
+        if let Some(module) = package_modules.get(package) {
+            return module.clone();
+        }
+        let name = format!("/packages/{}", package.replace('/', "."));
+        // The content is a list of (is_empty, module name offset) pairs
+        let module = self.find_location(&name).and_then(|location| {
+            let content = self.read_content(&location).ok()?;
+            content.chunks_exact(8)
+                .find(|entry| self.read_u32(entry) == 0)
+                .map(|entry| self.get_string(self.read_u32(&entry[4..]) as u64).to_string())
+        });
+        package_modules.insert(package.to_string(), module.clone());
+        module
+    }
+
+    /// Reads a resource by its full name, for example `/java.base/java/lang/Object.class`.
+    pub fn read_resource(&self, name: &str) -> Option<Result<Vec<u8>, String>> {
+        let location = self.find_location(name)?;
+        Some(self.read_content(&location).map_err(|message| format!("{} for {} in {}", message, name, self.path)))
+    }
+
+    /// Reads the bytes of a class by its internal name, for example `java/lang/Object`.
+    /// Returns the bytes together with the `jrt:` path of the class.
+    pub fn read_class(&self, class_name: &str) -> Option<Result<(Vec<u8>, String), String>> {
+        let package = match class_name.rfind('/') {
+            Some(index) => &class_name[..index],
+            None => ""
+        };
+        let module = self.package_to_module(package)?;
+        let name = format!("/{}/{}.class", module, class_name);
+        let bytes = self.read_resource(&name)?;
+        Some(bytes.map(|bytes| (bytes, format!("jrt:{}", name))))
+    }
+}
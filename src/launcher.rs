@@ -0,0 +1,107 @@
+use std::{sync::Arc, thread};
+
+use crate::{boot::{initialize_system, shutdown}, class_path::ClassPath, exceptions::{format_stack_trace, JvmResult, Throwable}, interpreter::Interpreter, object::{ArrayData, Object, Value}, strings::new_java_string, thread::{JavaThread, THREAD_STACK_SIZE}, verifier::Verification, vm::Vm};
+
+/// The class library that programs run on.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClassLibrary {
+    // The class library of an installed JDK
+    Jdk,
+    // The tiny library built into the VM, enough for simple programs
+    Stub
+}
+
+/// Starts a VM and runs the main method of a class, like the `java` command does.
+/// Without a class library given, the JDK is used when there is one, and the stub library otherwise.
+/// Returns the exit status of the process.
+pub fn launch(class_path: &str, main_class: &str, args: Vec<String>, library: Option<ClassLibrary>, verification: Verification) -> i32 {
+    let java_home = match library {
+        Some(ClassLibrary::Stub) => None,
+        other => ClassPath::find_java_home()
+    };
+    let mut full_class_path = match (&java_home, library) {
+        (Some(java_home), _) => match ClassPath::boot(java_home) {
+            Ok(boot_class_path) => boot_class_path,
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                return 1;
+            }
+        },
+        (None, Some(ClassLibrary::Jdk)) => {
+            eprintln!("Error: no JDK found, set JAVA_HOME");
+            return 1;
+        },
+        (None, other) => ClassPath::stub_library()
+    };
+    if let Err(message) = full_class_path.add_paths(class_path) {
+        eprintln!("Error: {}", message);
+        return 1;
+    }
+    let mut system_properties = vec![
+        (String::from("java.class.path"), String::from(class_path)),
+        (String::from("java.library.path"), String::new()),
+        (String::from("java.vm.specification.name"), String::from("Java Virtual Machine Specification")),
+        (String::from("java.vm.specification.vendor"), String::from("Oracle Corporation")),
+        (String::from("java.vm.name"), String::from("Vigur")),
+        (String::from("java.vm.vendor"), String::from("Vigur")),
+        (String::from("java.vm.version"), String::from(env!("CARGO_PKG_VERSION"))),
+        (String::from("java.vm.info"), String::from("interpreted mode")),
+        (String::from("sun.java.launcher"), String::from("SUN_STANDARD")),
+    ];
+    if let Some(java_home) = java_home {
+        system_properties.push((String::from("java.home"), java_home.to_string_lossy().into_owned()));
+        system_properties.push((String::from("sun.boot.library.path"), java_home.join("lib").to_string_lossy().into_owned()));
+    }
+    let vm = Vm::new(full_class_path, system_properties, verification);
+
+    let main_class = main_class.replace('.', "/");
+    let main_thread = thread::Builder::new()
+        .name(String::from("main"))
+        .stack_size(THREAD_STACK_SIZE)
+        .spawn(move || {
+            let thread = JavaThread::new(vm);
+            let status = match run_main(&thread, &main_class, args) {
+                Ok(()) => 0,
+                Err(throwable) => {
+                    report_uncaught(&thread, "main", throwable);
+                    1
+                }
+            };
+            thread.vm.wait_for_threads();
+            // Runs the shutdown hooks, a failure there does not change the exit status
+            let _ = shutdown(&thread);
+            status
+        })
+        .unwrap();
+    main_thread.join().unwrap()
+}
+
+fn run_main(thread: &JavaThread, main_class: &str, args: Vec<String>) -> JvmResult<()> {
+    initialize_system(thread)?;
+    let class = thread.vm.load_class(main_class)?;
+    let main_method = class.find_declared_method("main", "([Ljava/lang/String;)V")
+        .filter(|method| method.is_static())
+        .cloned()
+        .ok_or_else(|| Throwable::new("java/lang/NoSuchMethodError", format!("{}.main([Ljava/lang/String;)V", main_class.replace('/', "."))))?;
+    class.initialize(thread)?;
+    let string_array_class = thread.vm.load_class("[Ljava/lang/String;")?;
+    let mut strings = Vec::with_capacity(args.len());
+    for arg in &args {
+        strings.push(Some(new_java_string(thread, arg)?));
+    }
+    let main_args = Object::new_array(string_array_class, ArrayData::Reference(strings));
+    Interpreter::invoke(thread, &main_method, vec![Value::Reference(Some(main_args))])?;
+    Ok(())
+}
+
+/// Prints an exception that ended a thread, for threads without an uncaught exception handler.
+pub fn report_uncaught(thread: &JavaThread, thread_name: &str, throwable: Throwable) {
+    match throwable.into_object(thread) {
+        Ok(exception) => eprint!("Exception in thread \"{}\" {}", thread_name, format_stack_trace(thread, &exception)),
+        // Not even the exception could be created, so there is no stack trace
+        Err(Throwable::Pending { class_name, message: Some(message), .. }) => {
+            eprintln!("Exception in thread \"{}\" {}: {}", thread_name, class_name.replace('/', "."), message);
+        },
+        Err(other) => eprintln!("Exception in thread \"{}\" {}", thread_name, other.class_name().replace('/', "."))
+    }
+}
@@ -2,12 +2,526 @@ mod class_loader;
 mod byte_array;
 mod descriptor_parser;
 mod class_printer;
+mod class_path;
+mod jar_file;
+mod jimage;
+mod opcodes;
+mod object;
+mod monitor;
+mod exceptions;
+mod runtime_class;
+mod resolution;
+mod interpreter;
+mod natives;
+mod fdlibm;
+mod jdk_natives;
+mod strings;
+mod thread;
+mod vm;
+mod launcher;
+mod boot;
+mod stub_library;
+mod stub_natives;
+mod reflection;
+mod thread_natives;
+mod invokedynamic;
+mod method_handles;
+mod verifier;
+mod format_checker;
+mod class_writer;
+mod constant_pool_builder;
+mod assembler;
+mod disassembler;
+mod jasmin_parser;
+mod control_flow;
+mod decompiler;
+mod analysis;
+mod compatibility;
+mod dependencies;
 
-use class_loader::ClassLoader;
+use std::{env, fs, path::Path, process, rc::Rc};
+
+use analysis::Analysis;
+use class_loader::{ClassInfo, ClassLoader};
+use class_path::ClassPath;
+use dependencies::Dependencies;
+use jar_file::JarFile;
+use launcher::ClassLibrary;
+use time::OffsetDateTime;
+use verifier::Verification;
 
 use class_printer::ClassPrinter;
+use control_flow::ControlFlowGraph;
+use decompiler::Decompiler;
+use disassembler::Disassembler;
+use jasmin_parser::JasminParser;
+
+fn print_usage() {
+    eprintln!("Usage: Vigur [<file.class> | <class name>]");
+    eprintln!("       Vigur run [-cp <classpath>] [--library jdk|stub] [-Xverify:none|remote|all] <main class> [args...]");
+    eprintln!("       Vigur check <file.class | directory | file.jar | class name>...");
+    eprintln!("       Vigur disasm <file.class | class name>");
+    eprintln!("       Vigur asm [-d <directory>] <file.j>...");
+    eprintln!("       Vigur cfg <file.class | class name> [<method name>[<descriptor>]]");
+    eprintln!("       Vigur decompile <file.class | class name>");
+    eprintln!("       Vigur analyze [--library jdk|stub] [--dot calls|hierarchy] [--entry <class>[.<method name>]]... <classpath>");
+    eprintln!("       Vigur diff <old.jar | directory | file.class> <new.jar | directory | file.class>");
+    eprintln!("       Vigur deps [--library jdk|stub] [--modules] [--dot] <classpath>");
+}
+
+/// The classpath of the tools: a directory, followed by the JDK if there is one.
+fn tool_class_path(directory: &str) -> ClassPath {
+    let mut class_path = ClassPath::default();
+    // A directory always opens
+    let _ = class_path.add_paths(directory);
+    if let Some(java_home) = ClassPath::find_java_home() {
+        class_path.entries.extend(ClassPath::boot(&java_home).into_iter().flat_map(|boot| boot.entries));
+    }
+    class_path
+}
+
+/// Loads a class file by its path, or a class by its name from the current directory or the JDK.
+/// The error tells why the class file that was found is malformed.
+fn find_class_info(target: &str) -> Option<Result<ClassInfo, String>> {
+    if target.ends_with(".class") {
+        return Some(load_class_file(target));
+    }
+    let class_info = tool_class_path(".").load_class(&target.replace('.', "/"))?;
+    Some(class_info.map_err(|message| format!("{}: {}", target, message)))
+}
+
+/// Like find_class_info, but prints why when there is no class.
+fn load_target(target: &str) -> Option<ClassInfo> {
+    match find_class_info(target) {
+        Some(Ok(class_info)) => Some(class_info),
+        Some(Err(message)) => {
+            eprintln!("Error: {}", message);
+            None
+        },
+        None => {
+            eprintln!("Error: class {} not found", target);
+            None
+        }
+    }
+}
+
+fn load_class_file(path: &str) -> Result<ClassInfo, String> {
+    ClassLoader::load_class(path).map_err(|message| format!("{}: {}", path, message))
+}
+
+/// Returns the paths of the class files in a directory and its subdirectories, sorted.
+fn class_files(directory: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(directory).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            files.extend(class_files(&path));
+        } else if path.extension().is_some_and(|extension| extension == "class") {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Reads all the classes of a directory and its subdirectories, or of a jar.
+/// Each class file that is malformed gives an error naming it.
+fn read_classes(path: &str) -> Result<Vec<Result<ClassInfo, String>>, String> {
+    if Path::new(path).is_dir() {
+        return Ok(class_files(Path::new(path)).iter().map(|file| load_class_file(file)).collect());
+    }
+    let jar_file = JarFile::open(path).ok_or_else(|| format!("could not open {}", path))?;
+    let mut names: Vec<&String> = jar_file.entry_names().filter(|name| name.ends_with(".class")).collect();
+    names.sort();
+    Ok(names.into_iter().map(|name| {
+        let (bytes, path) = jar_file.read_class(name.trim_end_matches(".class")).unwrap()?;
+        ClassLoader::load_class_from_bytes(bytes, &path, OffsetDateTime::now_utc())
+            .map_err(|message| format!("{}: {}", path, message))
+    }).collect())
+}
+
+/// Loads all the classes of a directory and its subdirectories, or of a jar.
+/// Fails on the first class file that is malformed.
+fn load_classes(path: &str) -> Result<Vec<ClassInfo>, String> {
+    read_classes(path)?.into_iter().collect()
+}
+
+/// Runs the format check on classes and prints what it finds, like a lint.
+/// Returns 1 when there were problems.
+fn check(targets: &[String]) -> i32 {
+    if targets.is_empty() {
+        print_usage();
+        return 1;
+    }
+    let mut classes = 0;
+    let mut problems = 0;
+    // A class file that can't even be parsed is one problem
+    let mut check_class = |class_info: Result<ClassInfo, String>| {
+        classes += 1;
+        match class_info {
+            Ok(class_info) => for diagnostic in format_checker::check_class(&class_info) {
+                println!("{}: {}", class_info.file_path, diagnostic);
+                problems += 1;
+            },
+            Err(message) => {
+                println!("{}", message);
+                problems += 1;
+            }
+        }
+    };
+    for target in targets {
+        if Path::new(target).is_dir() || target.ends_with(".jar") {
+            match read_classes(target) {
+                Ok(class_infos) => class_infos.into_iter().for_each(&mut check_class),
+                Err(message) => {
+                    eprintln!("Error: {}", message);
+                    return 1;
+                }
+            }
+        } else {
+            match find_class_info(target) {
+                Some(class_info) => check_class(class_info),
+                None => {
+                    eprintln!("Error: class {} not found", target);
+                    return 1;
+                }
+            }
+        }
+    }
+    println!("{} classes checked, {} problems found", classes, problems);
+    if problems > 0 { 1 } else { 0 }
+}
+
+fn disassemble(targets: &[String]) -> i32 {
+    let [target] = targets else {
+        print_usage();
+        return 1;
+    };
+    match load_target(target) {
+        Some(class_info) => {
+            print!("{}", Disassembler::disassemble(&class_info));
+            0
+        },
+        None => 1
+    }
+}
+
+fn decompile(targets: &[String]) -> i32 {
+    let [target] = targets else {
+        print_usage();
+        return 1;
+    };
+    match load_target(target) {
+        Some(class_info) => {
+            print!("{}", Decompiler::decompile(&class_info));
+            0
+        },
+        None => 1
+    }
+}
+
+/// Assembles .j files into class files, in the directories of their packages.
+fn assemble(args: &[String]) -> i32 {
+    let (directory, files) = match args {
+        [option, directory, files @ ..] if option == "-d" => (directory.as_str(), files),
+        files => (".", files)
+    };
+    if files.is_empty() {
+        print_usage();
+        return 1;
+    }
+    // The frames need the superclasses of the classes that meet in the code
+    let class_path = tool_class_path(directory);
+    let superclass_of = Rc::new(move |name: &str| {
+        let class_info = class_path.load_class(name)?.ok()?;
+        (class_info.super_class != 0).then(|| class_info.constant_pool.get_string(class_info.constant_pool.get_class_info(class_info.super_class)).to_string())
+    });
+    for file in files {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("Error: could not read {}: {}", file, error);
+                return 1;
+            }
+        };
+        let (class_name, bytes) = match JasminParser::assemble(&text, file, superclass_of.clone()) {
+            Ok(class) => class,
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                return 1;
+            }
+        };
+        let path = Path::new(directory).join(format!("{}.class", class_name));
+        if let Err(error) = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::write(&path, bytes)) {
+            eprintln!("Error: could not write {}: {}", path.display(), error);
+            return 1;
+        }
+    }
+    0
+}
+
+/// Prints the control flow graphs of the methods of a class with the given name, or of all methods, in DOT.
+fn control_flow_graphs(args: &[String]) -> i32 {
+    let (target, method_name) = match args {
+        [target] => (target, None),
+        [target, method_name] => (target, Some(method_name.as_str())),
+        other => {
+            print_usage();
+            return 1;
+        }
+    };
+    let Some(class_info) = load_target(target) else {
+        return 1;
+    };
+    let constant_pool = &class_info.constant_pool;
+    let class_name = constant_pool.get_string(constant_pool.get_class_info(class_info.this_class));
+    let mut found = false;
+    for method in &class_info.methods {
+        let name = method.get_name(constant_pool);
+        let descriptor = constant_pool.get_string(method.descriptor_index);
+        let signature = format!("{}{}", name, descriptor);
+        if method_name.is_some_and(|method_name| method_name != name && method_name != signature) {
+            continue;
+        }
+        let Some(code) = method.get_code() else {
+            continue;
+        };
+        found = true;
+        match ControlFlowGraph::build(code) {
+            Ok(graph) => print!("{}", graph.to_dot(&class_info, code, &format!("{}.{}", class_name, signature))),
+            Err(message) => {
+                eprintln!("Error: {}.{}: {}", class_name, signature, message);
+                return 1;
+            }
+        }
+    }
+    if let Some(method_name) = method_name && !found {
+        eprintln!("Error: no method {} with code in {}", method_name, class_name);
+        return 1;
+    }
+    0
+}
+
+/// Returns the library that the classes outside of a classpath are looked up in: the JDK, or the stub library
+/// without one, unless `--library` picked one of them.
+fn library_class_path(library: Option<&str>) -> Result<ClassPath, String> {
+    let jdk = match library {
+        Some("stub") => None,
+        other => ClassPath::find_java_home().and_then(|java_home| ClassPath::boot(&java_home).ok())
+    };
+    match (jdk, library) {
+        (Some(jdk), _) => Ok(jdk),
+        (None, Some("jdk")) => Err(String::from("no JDK found, set JAVA_HOME")),
+        (None, other) => Ok(ClassPath::stub_library())
+    }
+}
+
+/// Builds the class hierarchy and the call graph of the classes on a classpath, and prints them with the unreachable
+/// methods, the cycles of calls and the missing classes as JSON, or one of the graphs as DOT.
+fn analyze(args: &[String]) -> i32 {
+    let mut dot = None;
+    let mut entries = Vec::new();
+    let mut library = None;
+    let mut index = 0;
+    while index + 1 < args.len() && args[index].starts_with('-') {
+        match args[index].as_str() {
+            "--library" if matches!(args[index + 1].as_str(), "jdk" | "stub") => library = Some(args[index + 1].as_str()),
+            "--dot" if matches!(args[index + 1].as_str(), "calls" | "hierarchy") => dot = Some(args[index + 1].as_str()),
+            "--entry" => entries.push(args[index + 1].clone()),
+            other => {
+                print_usage();
+                return 1;
+            }
+        }
+        index += 2;
+    }
+    let [class_path] = &args[index..] else {
+        print_usage();
+        return 1;
+    };
+    let separator = if cfg!(windows) { ';' } else { ':' };
+    let mut class_infos = Vec::new();
+    for path in class_path.split(separator).filter(|path| !path.is_empty()) {
+        match load_classes(path) {
+            Ok(loaded) => class_infos.extend(loaded),
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                return 1;
+            }
+        }
+    }
+    let library = match library_class_path(library) {
+        Ok(library) => library,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            return 1;
+        }
+    };
+    let analysis = match Analysis::build(&class_infos, &library, &entries) {
+        Ok(analysis) => analysis,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            return 1;
+        }
+    };
+    match dot {
+        Some("calls") => print!("{}", analysis.calls_to_dot()),
+        Some(other) => print!("{}", analysis.hierarchy_to_dot()),
+        None => print!("{}", analysis.to_json())
+    }
+    0
+}
+
+/// Compares the API of two versions of a library, and prints the changes with whether they break binary
+/// compatibility. Returns 1 when one does, so that it can stop a build.
+fn diff(args: &[String]) -> i32 {
+    let [old, new] = args else {
+        print_usage();
+        return 1;
+    };
+    let load = |path: &str| match path.ends_with(".class") {
+        true => load_class_file(path).map(|class_info| vec![class_info]),
+        false => load_classes(path)
+    };
+    let (old_classes, new_classes) = match (load(old), load(new)) {
+        (Ok(old_classes), Ok(new_classes)) => (old_classes, new_classes),
+        (Err(message), _) | (_, Err(message)) => {
+            eprintln!("Error: {}", message);
+            return 1;
+        }
+    };
+    let changes = compatibility::compare(&old_classes, &new_classes);
+    for change in &changes {
+        println!("{}", change);
+    }
+    let breaking = changes.iter().filter(|change| change.breaking).count();
+    println!("{} changes, {} breaking", changes.len(), breaking);
+    if breaking > 0 { 1 } else { 0 }
+}
+
+/// Prints the classes, methods and fields that the classes on a classpath refer to, grouped by package and
+/// optionally by module, or the dependencies between their packages as DOT.
+fn deps(args: &[String]) -> i32 {
+    let mut library = None;
+    let mut with_modules = false;
+    let mut dot = false;
+    let mut index = 0;
+    while index + 1 < args.len() && args[index].starts_with('-') {
+        match args[index].as_str() {
+            "--library" if matches!(args[index + 1].as_str(), "jdk" | "stub") => {
+                library = Some(args[index + 1].as_str());
+                index += 1;
+            },
+            "--modules" => with_modules = true,
+            "--dot" => dot = true,
+            other => {
+                print_usage();
+                return 1;
+            }
+        }
+        index += 1;
+    }
+    let [class_path] = &args[index..] else {
+        print_usage();
+        return 1;
+    };
+    let separator = if cfg!(windows) { ';' } else { ':' };
+    let mut entries = Vec::new();
+    for path in class_path.split(separator).filter(|path| !path.is_empty()) {
+        match load_classes(path) {
+            Ok(loaded) => entries.push((path.to_string(), loaded)),
+            Err(message) => {
+                eprintln!("Error: {}", message);
+                return 1;
+            }
+        }
+    }
+    let library = match library_class_path(library) {
+        Ok(library) => library,
+        Err(message) => {
+            eprintln!("Error: {}", message);
+            return 1;
+        }
+    };
+    let dependencies = Dependencies::build(&entries, &library);
+    if dot {
+        print!("{}", dependencies.to_dot(with_modules));
+    } else {
+        print!("{}", dependencies.to_text(with_modules));
+    }
+    0
+}
+
+fn run(args: &[String]) -> i32 {
+    let mut class_path = String::from(".");
+    let mut library = None;
+    let mut verification = Verification::Remote;
+    let mut index = 0;
+    while index < args.len() && args[index].starts_with('-') {
+        match args[index].as_str() {
+            "-cp" | "-classpath" | "--class-path" if index + 1 < args.len() => {
+                class_path = args[index + 1].clone();
+                index += 2;
+            },
+            "--library" if index + 1 < args.len() => {
+                library = match args[index + 1].as_str() {
+                    "jdk" => Some(ClassLibrary::Jdk),
+                    "stub" => Some(ClassLibrary::Stub),
+                    other => {
+                        eprintln!("Unknown class library {}, expected jdk or stub", other);
+                        return 1;
+                    }
+                };
+                index += 2;
+            },
+            "-Xverify:none" => {
+                verification = Verification::None;
+                index += 1;
+            },
+            "-Xverify:remote" => {
+                verification = Verification::Remote;
+                index += 1;
+            },
+            "-Xverify:all" => {
+                verification = Verification::All;
+                index += 1;
+            },
+            other => {
+                eprintln!("Unknown option {}", other);
+                print_usage();
+                return 1;
+            }
+        }
+    }
+    match args.get(index) {
+        Some(main_class) => launcher::launch(&class_path, main_class, args[index + 1..].to_vec(), library, verification),
+        None => {
+            print_usage();
+            1
+        }
+    }
+}
 
 fn main() {
-    let class_info = ClassLoader::load_class("./Main.class");
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("run") => process::exit(run(&args[2..])),
+        Some("check") => process::exit(check(&args[2..])),
+        Some("disasm") => process::exit(disassemble(&args[2..])),
+        Some("asm") => process::exit(assemble(&args[2..])),
+        Some("cfg") => process::exit(control_flow_graphs(&args[2..])),
+        Some("decompile") => process::exit(decompile(&args[2..])),
+        Some("analyze") => process::exit(analyze(&args[2..])),
+        Some("diff") => process::exit(diff(&args[2..])),
+        Some("deps") => process::exit(deps(&args[2..])),
+        other => {}
+    }
+    let class_info = match args.get(1) {
+        Some(target) => load_target(target),
+        None => load_class_file("./Main.class").inspect_err(|message| eprintln!("Error: {}", message)).ok()
+    };
+    let Some(class_info) = class_info else {
+        process::exit(1);
+    };
     ClassPrinter::print_class(&class_info);
-}
\ No newline at end of file
+}
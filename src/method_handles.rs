@@ -0,0 +1,550 @@
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+
+use time::OffsetDateTime;
+
+use crate::{boot::invoke_static, class_loader::{ClassLoader, ConstantPoolItem, MethodFlags}, descriptor_parser::argument_descriptors, exceptions::{JvmResult, Throwable}, interpreter::Interpreter, invokedynamic::convert, natives::{access_unsafe, reference_arg, same_value, NativeRegistry}, object::{ArrayData, Object, ObjectRef, Value}, reflection::{box_value, new_class_array, reflected_member, STATIC_FIELD_OFFSET}, resolution::{find_method, resolve_class, resolve_string, ResolvedEntry}, runtime_class::{Class, Method}, strings::{new_java_string, to_rust_string}, thread::JavaThread};
+
+const METHOD_HANDLE_NATIVES: &str = "java/lang/invoke/MethodHandleNatives";
+
+// Flag of ClassLoader.defineClass0 for Lookup.defineHiddenClass
+const HIDDEN_CLASS: i32 = 0x2;
+
+static HIDDEN_CLASS_NUMBER: AtomicU64 = AtomicU64::new(0x800000000);
+
+// Flags of MemberName, next to the access flags in the low 16 bits
+const MN_IS_METHOD: i32             = 0x00010000;
+const MN_IS_CONSTRUCTOR: i32        = 0x00020000;
+const MN_IS_FIELD: i32              = 0x00040000;
+const MN_TRUSTED_FINAL: i32         = 0x00200000;
+const MN_REFERENCE_KIND_SHIFT: i32  = 24;
+const MN_REFERENCE_KIND_MASK: i32   = 0x0F;
+
+// Kinds of method handles (JVMS §5.4.3.5)
+const REF_GET_FIELD: i32          = 1;
+const REF_GET_STATIC: i32         = 2;
+const REF_PUT_FIELD: i32          = 3;
+const REF_PUT_STATIC: i32         = 4;
+const REF_INVOKE_VIRTUAL: i32     = 5;
+const REF_INVOKE_STATIC: i32      = 6;
+const REF_NEW_INVOKE_SPECIAL: i32 = 8;
+const REF_INVOKE_INTERFACE: i32   = 9;
+
+/// Registers the natives that MethodHandles.Lookup needs to make method handles and VarHandles.
+pub fn register(registry: &NativeRegistry) {
+    registry.register(METHOD_HANDLE_NATIVES, "resolve", "(Ljava/lang/invoke/MemberName;Ljava/lang/Class;IZ)Ljava/lang/invoke/MemberName;", method_handle_natives_resolve);
+    registry.register(METHOD_HANDLE_NATIVES, "init", "(Ljava/lang/invoke/MemberName;Ljava/lang/Object;)V", method_handle_natives_init);
+    registry.register(METHOD_HANDLE_NATIVES, "objectFieldOffset", "(Ljava/lang/invoke/MemberName;)J", method_handle_natives_field_offset);
+    registry.register(METHOD_HANDLE_NATIVES, "staticFieldOffset", "(Ljava/lang/invoke/MemberName;)J", method_handle_natives_field_offset);
+    registry.register(METHOD_HANDLE_NATIVES, "staticFieldBase", "(Ljava/lang/invoke/MemberName;)Ljava/lang/Object;", method_handle_natives_static_field_base);
+    // The LambdaForms and BoundMethodHandle species that the JDK doesn't have pregenerated are compiled to classes
+    registry.register("java/lang/ClassLoader", "defineClass1", "(Ljava/lang/ClassLoader;Ljava/lang/String;[BIILjava/security/ProtectionDomain;Ljava/lang/String;)Ljava/lang/Class;", class_loader_define_class1);
+    registry.register("java/lang/ClassLoader", "defineClass0", "(Ljava/lang/ClassLoader;Ljava/lang/Class;Ljava/lang/String;[BIILjava/security/ProtectionDomain;ZILjava/lang/Object;)Ljava/lang/Class;", class_loader_define_class0);
+}
+
+/// Methods of MethodHandle and VarHandle that are native and only take an Object[] of varargs, like invokeExact
+/// and compareAndSet, can be called with any descriptor (JVMS §2.9.3).
+pub fn is_signature_polymorphic(method: &Method) -> bool {
+    matches!(method.class_name.as_str(), "java/lang/invoke/MethodHandle" | "java/lang/invoke/VarHandle")
+        && method.is_native()
+        && method.access_flags.contains(MethodFlags::ACC_VARARGS)
+        && method.descriptor.starts_with("([Ljava/lang/Object;)")
+}
+
+fn member_value(member: &ObjectRef, name: &str, descriptor: &str) -> Value {
+    member.fields()[member.class.instance_field_slot(name, descriptor).unwrap()].clone()
+}
+
+fn set_member_value(member: &ObjectRef, name: &str, descriptor: &str, value: Value) {
+    member.fields()[member.class.instance_field_slot(name, descriptor).unwrap()] = value;
+}
+
+/// Returns the method descriptor of a `java.lang.invoke.MethodType`, like `(I)Ljava/lang/String;`.
+fn method_type_descriptor(method_type: &ObjectRef) -> String {
+    let return_type = Class::from_mirror(member_value(method_type, "rtype", "Ljava/lang/Class;").as_reference().unwrap());
+    let parameter_types = member_value(method_type, "ptypes", "[Ljava/lang/Class;").as_reference().cloned().unwrap();
+    let parameter_types = parameter_types.array();
+    let parameters: String = (0..parameter_types.len())
+        .map(|index| Class::from_mirror(parameter_types.get(index).as_reference().unwrap()).descriptor())
+        .collect();
+    format!("({}){}", parameters, return_type.descriptor())
+}
+
+/// Returns the class, name and descriptor of a MemberName. Its type is a Class for fields and a MethodType
+/// for methods, or the descriptor as a String.
+fn member_signature(member: &ObjectRef) -> (Arc<Class>, String, String) {
+    let class = Class::from_mirror(member_value(member, "clazz", "Ljava/lang/Class;").as_reference().unwrap());
+    let name = to_rust_string(member_value(member, "name", "Ljava/lang/String;").as_reference().unwrap());
+    let member_type = member_value(member, "type", "Ljava/lang/Object;").as_reference().cloned().unwrap();
+    let descriptor = match member_type.class.name.as_str() {
+        "java/lang/Class" => Class::from_mirror(&member_type).descriptor(),
+        "java/lang/invoke/MethodType" => method_type_descriptor(&member_type),
+        other => to_rust_string(&member_type)
+    };
+    (class, name, descriptor)
+}
+
+/// Finds the field that a MemberName stands for, by its class, name and type.
+fn member_field(member: &ObjectRef) -> JvmResult<Option<(Arc<Class>, usize)>> {
+    let (class, name, descriptor) = member_signature(member);
+    Ok(class.find_field(&name, &descriptor))
+}
+
+/// Finds the method or constructor that a resolved MemberName stands for.
+fn member_method(member: &ObjectRef) -> JvmResult<Arc<Method>> {
+    let (class, name, descriptor) = member_signature(member);
+    // Signature polymorphic methods, like the linkTo methods the LambdaForms call, take any descriptor
+    let signature_polymorphic = || class.methods.iter()
+        .find(|method| method.name == name && is_signature_polymorphic(method))
+        .cloned();
+    find_method(&class, &name, &descriptor)
+        .or_else(signature_polymorphic)
+        .ok_or_else(|| Throwable::new("java/lang/NoSuchMethodError", format!("{}.{}{}", class.name.replace('/', "."), name, descriptor)))
+}
+
+/// Resolves a MemberName, filling in the class that declares the member and its flags.
+fn method_handle_natives_resolve(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let member = reference_arg(&args, 0)?;
+    let speculative = args[3].as_int() != 0;
+    let flags = member_value(&member, "flags", "I").as_int();
+    if flags & (MN_IS_METHOD | MN_IS_CONSTRUCTOR) != 0 {
+        return resolve_method_member(thread, member, flags, speculative);
+    }
+    if flags & MN_IS_FIELD == 0 {
+        return Err(Throwable::new("java/lang/InternalError", format!("Can't resolve a MemberName with flags {:#X}", flags)));
+    }
+    let (class, index) = match member_field(&member)? {
+        Some(field) => field,
+        None if speculative => return Ok(Some(Value::null())),
+        None => return Err(Throwable::new("java/lang/NoSuchFieldError", to_rust_string(member_value(&member, "name", "Ljava/lang/String;").as_reference().unwrap())))
+    };
+    let field = &class.fields[index];
+    let is_setter = matches!((flags >> MN_REFERENCE_KIND_SHIFT) & MN_REFERENCE_KIND_MASK, REF_PUT_FIELD | REF_PUT_STATIC);
+    let reference_kind = match (field.is_static(), is_setter) {
+        (false, false) => REF_GET_FIELD,
+        (true, false) => REF_GET_STATIC,
+        (false, true) => REF_PUT_FIELD,
+        (true, true) => REF_PUT_STATIC
+    };
+    let mut flags = field.access_flags.bits() as i32 | MN_IS_FIELD | (reference_kind << MN_REFERENCE_KIND_SHIFT);
+    // Static final fields can't be changed, not even through reflection
+    if field.is_static() && field.is_final() {
+        flags |= MN_TRUSTED_FINAL;
+    }
+    set_member_value(&member, "clazz", "Ljava/lang/Class;", Value::Reference(Some(class.mirror(thread)?)));
+    set_member_value(&member, "flags", "I", Value::Int(flags));
+    Ok(Some(args[0].clone()))
+}
+
+fn resolve_method_member(thread: &JavaThread, member: ObjectRef, flags: i32, speculative: bool) -> JvmResult<Option<Value>> {
+    let method = match member_method(&member) {
+        Ok(method) => method,
+        Err(_) if speculative => return Ok(Some(Value::null())),
+        Err(throwable) => return Err(throwable)
+    };
+    let kind_flag = if method.name == "<init>" { MN_IS_CONSTRUCTOR } else { MN_IS_METHOD };
+    let reference_kind = match method.is_static() {
+        true => REF_INVOKE_STATIC,
+        false => (flags >> MN_REFERENCE_KIND_SHIFT) & MN_REFERENCE_KIND_MASK
+    };
+    let flags = method.access_flags.bits() as i32 | kind_flag | (reference_kind << MN_REFERENCE_KIND_SHIFT);
+    set_member_value(&member, "clazz", "Ljava/lang/Class;", Value::Reference(Some(method.class().mirror(thread)?)));
+    set_member_value(&member, "flags", "I", Value::Int(flags));
+    Ok(Some(Value::Reference(Some(member))))
+}
+
+/// Fills in a MemberName from a `java.lang.reflect` Method, Constructor or Field, for `Lookup.unreflect` and the
+/// functions of LambdaForms.
+fn method_handle_natives_init(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let member = reference_arg(&args, 0)?;
+    let reflected = reference_arg(&args, 1)?;
+    let (class, slot) = reflected_member(&reflected);
+    let (name, member_type, flags) = match reflected.class.name.as_str() {
+        "java/lang/reflect/Field" => {
+            let field = &class.fields[slot];
+            let reference_kind = if field.is_static() { REF_GET_STATIC } else { REF_GET_FIELD };
+            let flags = field.access_flags.bits() as i32 | MN_IS_FIELD | (reference_kind << MN_REFERENCE_KIND_SHIFT);
+            (&field.name, thread.vm.class_for_descriptor(&field.descriptor)?.mirror(thread)?, flags)
+        },
+        other => {
+            let method = &class.methods[slot];
+            let (kind_flag, reference_kind) = if method.name == "<init>" {
+                (MN_IS_CONSTRUCTOR, REF_NEW_INVOKE_SPECIAL)
+            } else if method.is_static() {
+                (MN_IS_METHOD, REF_INVOKE_STATIC)
+            } else if class.is_interface() {
+                (MN_IS_METHOD, REF_INVOKE_INTERFACE)
+            } else {
+                (MN_IS_METHOD, REF_INVOKE_VIRTUAL)
+            };
+            let flags = method.access_flags.bits() as i32 | kind_flag | (reference_kind << MN_REFERENCE_KIND_SHIFT);
+            (&method.name, new_method_type(thread, &method.descriptor)?, flags)
+        }
+    };
+    set_member_value(&member, "clazz", "Ljava/lang/Class;", Value::Reference(Some(class.mirror(thread)?)));
+    set_member_value(&member, "name", "Ljava/lang/String;", Value::Reference(Some(thread.vm.intern_string(new_java_string(thread, name)?))));
+    set_member_value(&member, "type", "Ljava/lang/Object;", Value::Reference(Some(member_type)));
+    set_member_value(&member, "flags", "I", Value::Int(flags));
+    Ok(None)
+}
+
+/// The same offsets as Unsafe gives out, see `access_unsafe`.
+fn method_handle_natives_field_offset(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (class, index) = member_field(&reference_arg(&args, 0)?)?.unwrap();
+    let field = &class.fields[index];
+    let offset = if field.is_static() { STATIC_FIELD_OFFSET | field.slot as i64 } else { field.slot as i64 };
+    Ok(Some(Value::Long(offset)))
+}
+
+fn method_handle_natives_static_field_base(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (class, _) = member_field(&reference_arg(&args, 0)?)?.unwrap();
+    Ok(Some(Value::Reference(Some(class.mirror(thread)?))))
+}
+
+/// Resolves a `CONSTANT_MethodType`, `CONSTANT_MethodHandle` or `CONSTANT_Dynamic` entry (JVMS §5.4.3.5, §5.4.3.6),
+/// with the Java code of MethodHandleNatives that HotSpot calls for them.
+pub fn resolve_object_constant(thread: &JavaThread, current: &Arc<Class>, index: u16) -> JvmResult<Value> {
+    if let Some(ResolvedEntry::Constant(value)) = current.cp_cache[index as usize].get() {
+        return Ok(value.clone());
+    }
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    let value = match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPMethodTypeInfo { descriptor_index } => Value::Reference(Some(new_method_type(thread, constant_pool.get_string(*descriptor_index))?)),
+        ConstantPoolItem::CPMethodHandleInfo { reference_kind, reference_index } => link_method_handle_constant(thread, current, *reference_kind, *reference_index)?,
+        ConstantPoolItem::CPDynamicInfo { bootstrap_method_attr_index, name_and_type_index } =>
+            link_dynamic_constant(thread, current, index, *bootstrap_method_attr_index, *name_and_type_index)?,
+        other => panic!("No method handle, method type or dynamic constant found in constantpool at index: {}", index)
+    };
+    match current.cp_cache[index as usize].get_or_init(|| ResolvedEntry::Constant(value)) {
+        ResolvedEntry::Constant(value) => Ok(value.clone()),
+        other => panic!("Constant pool entry {} of {} resolved to something else than a constant", index, current.name)
+    }
+}
+
+fn call_method_handle_natives(thread: &JavaThread, name: &str, descriptor: &str, args: Vec<Value>) -> JvmResult<Value> {
+    let natives = thread.vm.load_class(METHOD_HANDLE_NATIVES)?;
+    natives.initialize(thread)?;
+    Ok(invoke_static(thread, &natives, name, descriptor, args)?.unwrap())
+}
+
+/// Makes the `java.lang.invoke.MethodType` of a method descriptor.
+fn new_method_type(thread: &JavaThread, descriptor: &str) -> JvmResult<ObjectRef> {
+    let return_type = thread.vm.class_for_descriptor(return_descriptor(descriptor))?.mirror(thread)?;
+    let mut parameter_types = Vec::new();
+    for parameter in argument_descriptors(descriptor) {
+        parameter_types.push(thread.vm.class_for_descriptor(parameter)?);
+    }
+    let parameter_types = new_class_array(thread, &parameter_types)?;
+    let method_type = call_method_handle_natives(thread, "findMethodHandleType", "(Ljava/lang/Class;[Ljava/lang/Class;)Ljava/lang/invoke/MethodType;",
+        vec![Value::Reference(Some(return_type)), Value::Reference(Some(parameter_types))])?;
+    Ok(method_type.as_reference().cloned().unwrap())
+}
+
+fn link_method_handle_constant(thread: &JavaThread, current: &Arc<Class>, reference_kind: u8, reference_index: u16) -> JvmResult<Value> {
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    let (class_index, name_and_type_index) = match &constant_pool.constants[reference_index as usize] {
+        ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } |
+        ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } |
+        ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } => (*class_index, *name_and_type_index),
+        other => panic!("No member reference found in constantpool at index: {}", reference_index)
+    };
+    let class = resolve_class(thread, current, class_index)?;
+    let (name_index, descriptor_index) = constant_pool.get_name_and_type(name_and_type_index);
+    let descriptor = constant_pool.get_string(descriptor_index);
+    // Fields have their class as type, methods a MethodType
+    let member_type = match reference_kind as i32 {
+        REF_GET_FIELD..=REF_PUT_STATIC => thread.vm.class_for_descriptor(descriptor)?.mirror(thread)?,
+        other => new_method_type(thread, descriptor)?
+    };
+    let args = vec![
+        Value::Reference(Some(current.mirror(thread)?)),
+        Value::Int(reference_kind as i32),
+        Value::Reference(Some(class.mirror(thread)?)),
+        Value::Reference(Some(new_java_string(thread, constant_pool.get_string(name_index))?)),
+        Value::Reference(Some(member_type))
+    ];
+    call_method_handle_natives(thread, "linkMethodHandleConstant",
+        "(Ljava/lang/Class;ILjava/lang/Class;Ljava/lang/String;Ljava/lang/Object;)Ljava/lang/invoke/MethodHandle;", args)
+}
+
+/// Runs the bootstrap method of a dynamic constant, with its static arguments boxed in an Object[].
+fn link_dynamic_constant(thread: &JavaThread, current: &Arc<Class>, index: u16, bootstrap_method_attr_index: u16, name_and_type_index: u16) -> JvmResult<Value> {
+    let info = current.info.as_ref().unwrap();
+    let constant_pool = &info.constant_pool;
+    let bootstrap_method = info.get_bootstrap_method(bootstrap_method_attr_index)
+        .ok_or_else(|| Throwable::new("java/lang/BootstrapMethodError", format!("Missing bootstrap method {} in {}", bootstrap_method_attr_index, current.name.replace('/', "."))))?;
+    let handle = resolve_object_constant(thread, current, bootstrap_method.bootstrap_method_ref)?;
+    let mut arguments = Vec::with_capacity(bootstrap_method.bootstrap_arguments.len());
+    for argument in &bootstrap_method.bootstrap_arguments {
+        arguments.push(static_argument(thread, current, *argument)?.as_reference().cloned());
+    }
+    let arguments = Object::new_array(thread.vm.load_class("[Ljava/lang/Object;")?, ArrayData::Reference(arguments));
+    let (name_index, descriptor_index) = constant_pool.get_name_and_type(name_and_type_index);
+    let descriptor = constant_pool.get_string(descriptor_index);
+    let args = vec![
+        Value::Reference(Some(current.mirror(thread)?)),
+        Value::Int(index as i32),
+        handle,
+        Value::Reference(Some(new_java_string(thread, constant_pool.get_string(name_index))?)),
+        Value::Reference(Some(thread.vm.class_for_descriptor(descriptor)?.mirror(thread)?)),
+        Value::Reference(Some(arguments))
+    ];
+    let value = call_method_handle_natives(thread, "linkDynamicConstant",
+        "(Ljava/lang/Object;ILjava/lang/Object;Ljava/lang/Object;Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;", args)?;
+    convert(thread, value, "Ljava/lang/Object;", descriptor)
+}
+
+/// Returns a static argument of a bootstrap method as an object, boxing the numbers.
+fn static_argument(thread: &JavaThread, current: &Arc<Class>, index: u16) -> JvmResult<Value> {
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPIntegerInfo { value } => box_value(thread, Value::Int(*value), "I"),
+        ConstantPoolItem::CPFloatInfo { value } => box_value(thread, Value::Float(*value), "F"),
+        ConstantPoolItem::CPLongInfo { value } => box_value(thread, Value::Long(*value), "J"),
+        ConstantPoolItem::CPDoubleInfo { value } => box_value(thread, Value::Double(*value), "D"),
+        ConstantPoolItem::CPStringInfo { .. } => Ok(Value::Reference(Some(resolve_string(thread, current, index)?))),
+        ConstantPoolItem::CPClassInfo { .. } => Ok(Value::Reference(Some(resolve_class(thread, current, index)?.mirror(thread)?))),
+        other => {
+            let value = resolve_object_constant(thread, current, index)?;
+            // Dynamic constants of primitive types are boxed again
+            match &constant_pool.constants[index as usize] {
+                ConstantPoolItem::CPDynamicInfo { name_and_type_index, .. } =>
+                    box_value(thread, value, constant_pool.get_string(constant_pool.get_name_and_type(*name_and_type_index).1)),
+                other => Ok(value)
+            }
+        }
+    }
+}
+
+/// Runs a signature polymorphic method of MethodHandle. `invokeExact` and `invoke` call `invokeBasic`, which runs
+/// the compiled LambdaForm of the handle, and that ends in one of the `linkTo` methods, which call the member
+/// given as their last argument.
+pub fn invoke_method_handle(thread: &JavaThread, name: &str, descriptor: &str, mut args: Vec<Value>) -> JvmResult<Option<Value>> {
+    match name {
+        "invokeExact" | "invoke" => {
+            let handle = reference_arg(&args, 0)?;
+            let method_type = member_value(&handle, "type", "Ljava/lang/invoke/MethodType;").as_reference().cloned().unwrap();
+            let handle_descriptor = method_type_descriptor(&method_type);
+            if handle_descriptor == descriptor {
+                return invoke_basic(thread, args);
+            }
+            let call_site_type = new_method_type(thread, descriptor)?;
+            if name == "invokeExact" {
+                let expected = Interpreter::invoke_virtual_method(thread, &method_type, "toString", "()Ljava/lang/String;", Vec::new())?.unwrap();
+                let found = Interpreter::invoke_virtual_method(thread, &call_site_type, "toString", "()Ljava/lang/String;", Vec::new())?.unwrap();
+                return Err(Throwable::new("java/lang/invoke/WrongMethodTypeException",
+                    format!("expected {} but found {}", to_rust_string(expected.as_reference().unwrap()), to_rust_string(found.as_reference().unwrap()))));
+            }
+            // invoke adapts the handle to the type at the call site first, with the conversions of asType
+            args[0] = Interpreter::invoke_virtual_method(thread, &handle, "asType", "(Ljava/lang/invoke/MethodType;)Ljava/lang/invoke/MethodHandle;",
+                vec![Value::Reference(Some(call_site_type))])?.unwrap();
+            invoke_basic(thread, args)
+        },
+        "invokeBasic" => invoke_basic(thread, args),
+        "linkToStatic" | "linkToSpecial" | "linkToVirtual" | "linkToInterface" => {
+            let member = args.pop().and_then(|member| member.as_reference().cloned()).unwrap();
+            let method = member_method(&member)?;
+            let selected = match name {
+                "linkToStatic" => {
+                    method.class().initialize(thread)?;
+                    method
+                },
+                "linkToSpecial" => {
+                    reference_arg(&args, 0)?;
+                    method
+                },
+                other => {
+                    let receiver = reference_arg(&args, 0)?;
+                    receiver.class.vtable_index(&method.name, &method.descriptor)
+                        .and_then(|index| receiver.class.vtable[index].clone())
+                        .unwrap_or(method)
+                }
+            };
+            Interpreter::invoke(thread, &selected, args)
+        },
+        other => Err(Throwable::new("java/lang/UnsupportedOperationException", format!("MethodHandle.{} is not supported", name)))
+    }
+}
+
+/// Calls the method that the LambdaForm of a handle was compiled to, with the handle as its first argument.
+fn invoke_basic(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let handle = reference_arg(&args, 0)?;
+    let form = member_value(&handle, "form", "Ljava/lang/invoke/LambdaForm;").as_reference().cloned().unwrap();
+    let entry = member_value(&form, "vmentry", "Ljava/lang/invoke/MemberName;").as_reference().cloned()
+        .ok_or_else(|| Throwable::new("java/lang/InternalError", "The LambdaForm of the method handle is not compiled"))?;
+    Interpreter::invoke(thread, &member_method(&entry)?, args)
+}
+
+fn return_descriptor(descriptor: &str) -> &str {
+    &descriptor[descriptor.find(')').unwrap() + 1..]
+}
+
+/// Defines a class from the bytes of a class file, like the species of BoundMethodHandle that the JDK generates.
+fn class_loader_define_class1(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = define_class(thread, &args[1..], false)?;
+    Ok(Some(Value::Reference(Some(class.mirror(thread)?))))
+}
+
+/// Defines a class for a Lookup. Hidden classes get a name of their own, made unique by a number after a slash like
+/// HotSpot does, and the class data that `MethodHandles.classData` returns.
+fn class_loader_define_class0(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = define_class(thread, &args[2..], args[8].as_int() & HIDDEN_CLASS != 0)?;
+    let mirror = class.mirror(thread)?;
+    mirror.fields()[mirror.class.instance_field_slot("classData", "Ljava/lang/Object;").unwrap()] = args[9].clone();
+    if args[7].as_int() != 0 {
+        class.initialize(thread)?;
+    }
+    Ok(Some(Value::Reference(Some(mirror))))
+}
+
+/// Defines a class from the name, byte[], offset and length at the start of the arguments.
+fn define_class(thread: &JavaThread, args: &[Value], hidden: bool) -> JvmResult<Arc<Class>> {
+    let name = to_rust_string(&reference_arg(args, 0)?).replace('.', "/");
+    let bytes = match &*reference_arg(args, 1)?.array() {
+        ArrayData::Byte(bytes) => {
+            let (offset, length) = (args[2].as_int() as usize, args[3].as_int() as usize);
+            bytes[offset..offset + length].iter().map(|byte| *byte as u8).collect()
+        },
+        other => panic!("The bytes of a class are not a byte[]")
+    };
+    let mut class_info = ClassLoader::load_class_from_bytes(bytes, &format!("{}.class", name), OffsetDateTime::now_utc())
+        .map_err(|message| Throwable::new("java/lang/ClassFormatError", format!("{} in class file {}", message, name)))?;
+    if hidden {
+        let name_index = class_info.constant_pool.get_class_info(class_info.this_class);
+        let hidden_name = format!("{}/0x{:016x}", name, HIDDEN_CLASS_NUMBER.fetch_add(1, Ordering::Relaxed));
+        class_info.constant_pool.constants[name_index as usize] = ConstantPoolItem::CPUTF8Info { utf8_string: hidden_name, bytes: None };
+    }
+    thread.vm.define_class(class_info)
+}
+
+/// Returns the descriptor of the variables a VarHandle class accesses, like `I` for `VarHandleInts$Array`.
+fn variable_type(handle_class: &str) -> Option<&'static str> {
+    let type_name = handle_class.strip_prefix("java/lang/invoke/VarHandle")?.split('$').next()?;
+    let descriptor = match type_name {
+        "Booleans" => "Z",
+        "Bytes" => "B",
+        "Shorts" => "S",
+        "Chars" => "C",
+        "Ints" => "I",
+        "Longs" => "J",
+        "Floats" => "F",
+        "Doubles" => "D",
+        "References" => "Ljava/lang/Object;",
+        other => return None
+    };
+    Some(descriptor)
+}
+
+/// Keeps a byte, short or char in its range after arithmetic on its int value.
+fn narrow(value: i32, variable_type: &str) -> i32 {
+    match variable_type {
+        "B" => value as i8 as i32,
+        "S" => value as i16 as i32,
+        "C" => value as u16 as i32,
+        other => value
+    }
+}
+
+fn add(value: &Value, delta: &Value, variable_type: &str) -> Value {
+    match (value, delta) {
+        (Value::Int(value), Value::Int(delta)) => Value::Int(narrow(value.wrapping_add(*delta), variable_type)),
+        (Value::Long(value), Value::Long(delta)) => Value::Long(value.wrapping_add(*delta)),
+        (Value::Float(value), Value::Float(delta)) => Value::Float(value + delta),
+        (Value::Double(value), Value::Double(delta)) => Value::Double(value + delta),
+        other => panic!("Can't add {:?} to {:?}", delta, value)
+    }
+}
+
+fn bitwise(value: &Value, mask: &Value, operation: &str) -> Value {
+    let apply = |value: i64, mask: i64| match operation {
+        "Or" => value | mask,
+        "And" => value & mask,
+        other => value ^ mask
+    };
+    match (value, mask) {
+        (Value::Int(value), Value::Int(mask)) => Value::Int(apply(*value as i64, *mask as i64) as i32),
+        (Value::Long(value), Value::Long(mask)) => Value::Long(apply(*value, *mask)),
+        other => panic!("Can't combine {:?} with {:?}", value, mask)
+    }
+}
+
+/// Runs an access mode method of a VarHandle, like get or compareAndSet, on the variable it points at.
+/// The VarHandles of fields and array elements are supported, which are the ones the JDK uses itself.
+/// The arguments are converted to the type of the variable first, like `VarHandle.invoke` would.
+pub fn invoke_var_handle(thread: &JavaThread, name: &str, descriptor: &str, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let handle = reference_arg(&args, 0)?;
+    let unsupported = || Throwable::new("java/lang/UnsupportedOperationException", format!("{}.{} is not supported", handle.class.name.replace('/', "."), name));
+    let variable_type = variable_type(&handle.class.name).ok_or_else(unsupported)?;
+    let kind = handle.class.name.rsplit('$').next().unwrap();
+    let argument_types = argument_descriptors(descriptor);
+    let (object, offset, coordinates) = if kind.starts_with("FieldInstance") {
+        (reference_arg(&args, 1)?, member_value(&handle, "fieldOffset", "J").as_long(), 2)
+    } else if kind.starts_with("FieldStatic") {
+        (member_value(&handle, "base", "Ljava/lang/Object;").as_reference().cloned().unwrap(), member_value(&handle, "fieldOffset", "J").as_long(), 1)
+    } else if kind == "Array" {
+        let array = reference_arg(&args, 1)?;
+        let (index, length) = (args[2].as_int(), array.array().len());
+        if index < 0 || index as usize >= length {
+            return Err(Throwable::new("java/lang/ArrayIndexOutOfBoundsException", format!("Index {} out of bounds for length {}", index, length)));
+        }
+        let offset = member_value(&handle, "abase", "I").as_int() as i64 + ((index as i64) << member_value(&handle, "ashift", "I").as_int());
+        (array, offset, 3)
+    } else {
+        return Err(unsupported());
+    };
+    let mut values = Vec::with_capacity(args.len() - coordinates);
+    for (value, from) in args.iter().skip(coordinates).zip(&argument_types[coordinates - 1..]) {
+        values.push(convert(thread, value.clone(), from, variable_type)?);
+    }
+    let is_read = matches!(name, "get" | "getVolatile" | "getAcquire" | "getOpaque");
+    if kind.ends_with("ReadOnly") && !is_read {
+        return Err(unsupported());
+    }
+
+    // The compareAndSet modes return whether they swapped, the others the value of the variable
+    let result_type = if name.contains("CompareAndSet") || name == "compareAndSet" { "Z" } else { variable_type };
+    let result = match name {
+        name if is_read => Some(access_unsafe(&object, offset, |variable| variable.clone())),
+        "set" | "setVolatile" | "setRelease" | "setOpaque" => {
+            access_unsafe(&object, offset, |variable| *variable = values[0].clone());
+            None
+        },
+        "compareAndSet" | "weakCompareAndSet" | "weakCompareAndSetPlain" | "weakCompareAndSetAcquire" | "weakCompareAndSetRelease" => {
+            let swapped = access_unsafe(&object, offset, |variable| {
+                let swapped = same_value(variable, &values[0]);
+                if swapped {
+                    *variable = values[1].clone();
+                }
+                swapped
+            });
+            Some(Value::Int(swapped as i32))
+        },
+        "compareAndExchange" | "compareAndExchangeAcquire" | "compareAndExchangeRelease" => {
+            Some(access_unsafe(&object, offset, |variable| {
+                let witness = variable.clone();
+                if same_value(&witness, &values[0]) {
+                    *variable = values[1].clone();
+                }
+                witness
+            }))
+        },
+        name if name.starts_with("getAnd") => {
+            let operation = name.trim_start_matches("getAnd").trim_end_matches("Acquire").trim_end_matches("Release");
+            Some(access_unsafe(&object, offset, |variable| {
+                let previous = variable.clone();
+                *variable = match operation {
+                    "Set" => values[0].clone(),
+                    "Add" => add(&previous, &values[0], variable_type),
+                    operation => bitwise(&previous, &values[0], operation.trim_start_matches("Bitwise"))
+                };
+                previous
+            }))
+        },
+        other => return Err(unsupported())
+    };
+    let return_type = return_descriptor(descriptor);
+    match (result, return_type) {
+        (_, "V") => Ok(None),
+        (Some(value), return_type) => Ok(Some(convert(thread, value, result_type, return_type)?)),
+        (None, return_type) => Ok(Some(Value::default_for(return_type)))
+    }
+}
@@ -0,0 +1,155 @@
+use std::{collections::VecDeque, sync::{Condvar, Mutex}, thread::{self, ThreadId}, time::{Duration, Instant}};
+
+use crate::{exceptions::{JvmResult, Throwable}, object::ObjectRef};
+
+/// The monitor of an object (JVMS §2.11.10), used by synchronized code and Object.wait/notify.
+/// It is reentrant: its owner can enter it again, and has to exit it as many times.
+pub struct Monitor {
+    state: Mutex<MonitorState>,
+    // Signalled when the monitor becomes free
+    released: Condvar,
+    // Signalled when threads in the wait set get notified or interrupted
+    notified: Condvar
+}
+
+struct MonitorState {
+    owner: Option<ThreadId>,
+    count: usize,
+    // A ticket for every waiting thread, in the order they started waiting. Notifying takes tickets out.
+    wait_set: VecDeque<u64>,
+    next_ticket: u64
+}
+
+/// How a wait in the wait set of a monitor ended.
+#[derive(PartialEq)]
+pub enum WaitOutcome {
+    Notified,
+    TimedOut,
+    Interrupted
+}
+
+impl Monitor {
+    pub const fn new() -> Monitor {
+        Monitor {
+            state: Mutex::new(MonitorState { owner: None, count: 0, wait_set: VecDeque::new(), next_ticket: 0 }),
+            released: Condvar::new(),
+            notified: Condvar::new()
+        }
+    }
+
+    /// Blocks until the current thread owns the monitor.
+    pub fn enter(&self) {
+        let current = thread::current().id();
+        let mut state = self.state.lock().unwrap();
+        while state.owner.is_some_and(|owner| owner != current) {
+            state = self.released.wait(state).unwrap();
+        }
+        state.owner = Some(current);
+        state.count += 1;
+    }
+
+    /// Exits the monitor once. Returns false when the current thread does not own it.
+    pub fn exit(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.owner != Some(thread::current().id()) {
+            return false;
+        }
+        state.count -= 1;
+        if state.count == 0 {
+            state.owner = None;
+            self.released.notify_one();
+        }
+        true
+    }
+
+    pub fn is_owned_by_current_thread(&self) -> bool {
+        self.state.lock().unwrap().owner == Some(thread::current().id())
+    }
+
+    /// Releases the monitor completely and waits in its wait set, until another thread notifies it,
+    /// the timeout passes or `is_interrupted` says so. The monitor is owned again when this returns.
+    /// Returns None when the current thread does not own the monitor.
+    pub fn wait(&self, timeout: Option<Duration>, is_interrupted: impl Fn() -> bool) -> Option<WaitOutcome> {
+        let current = thread::current().id();
+        let mut state = self.state.lock().unwrap();
+        if state.owner != Some(current) {
+            return None;
+        }
+        let count = state.count;
+        state.owner = None;
+        state.count = 0;
+        self.released.notify_one();
+
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.wait_set.push_back(ticket);
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let outcome = loop {
+            if !state.wait_set.contains(&ticket) {
+                break WaitOutcome::Notified;
+            }
+            if is_interrupted() {
+                break WaitOutcome::Interrupted;
+            }
+            match deadline {
+                Some(deadline) if Instant::now() >= deadline => break WaitOutcome::TimedOut,
+                Some(deadline) => state = self.notified.wait_timeout(state, deadline - Instant::now()).unwrap().0,
+                None => state = self.notified.wait(state).unwrap()
+            }
+        };
+        state.wait_set.retain(|waiting| *waiting != ticket);
+
+        while state.owner.is_some() {
+            state = self.released.wait(state).unwrap();
+        }
+        state.owner = Some(current);
+        state.count = count;
+        Some(outcome)
+    }
+
+    /// Wakes up the thread that has been waiting the longest. Returns false when the current thread does not own the monitor.
+    pub fn notify(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.owner != Some(thread::current().id()) {
+            return false;
+        }
+        if state.wait_set.pop_front().is_some() {
+            self.notified.notify_all();
+        }
+        true
+    }
+
+    /// Wakes up all waiting threads. Returns false when the current thread does not own the monitor.
+    pub fn notify_all(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.owner != Some(thread::current().id()) {
+            return false;
+        }
+        state.wait_set.clear();
+        self.notified.notify_all();
+        true
+    }
+
+    /// Wakes up the waiting threads without notifying them, so an interrupted one sees that it got interrupted.
+    pub fn wake_waiters(&self) {
+        let _state = self.state.lock().unwrap();
+        self.notified.notify_all();
+    }
+}
+
+pub fn illegal_monitor_state() -> Throwable {
+    Throwable::new("java/lang/IllegalMonitorStateException", "current thread is not owner")
+}
+
+/// Enters the monitor of an object, for monitorenter and synchronized methods.
+pub fn enter_monitor(object: &ObjectRef) {
+    object.monitor.enter();
+}
+
+/// Exits the monitor of an object, which fails when the current thread does not own it.
+pub fn exit_monitor(object: &ObjectRef) -> JvmResult<()> {
+    match object.monitor.exit() {
+        true => Ok(()),
+        false => Err(illegal_monitor_state())
+    }
+}
@@ -0,0 +1,495 @@
+use std::{collections::HashMap, process, sync::{Arc, RwLock}, thread, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{class_loader::ClassFlags, fdlibm, jdk_natives, method_handles, reflection::{self, STATIC_FIELD_OFFSET}, stub_natives, thread_natives, exceptions::{fill_in_stack_trace, JvmResult, Throwable}, object::{ArrayData, Object, ObjectRef, Value}, runtime_class::{Class, Method}, strings::{new_java_string, to_rust_string}, thread::JavaThread};
+
+/// The Rust implementation of a native method. It gets one value per argument, including the receiver.
+pub type NativeMethod = fn(&JavaThread, Vec<Value>) -> JvmResult<Option<Value>>;
+
+const JDK_UNSAFE: &str = "jdk/internal/misc/Unsafe";
+const SUN_UNSAFE: &str = "sun/misc/Unsafe";
+
+// Types that Unsafe has a get and put method for, with the name used on JDK 9+ and on JDK 8
+const UNSAFE_TYPES: [(&str, &str, &str); 9] = [
+    ("Int",       "Int",     "I"),
+    ("Long",      "Long",    "J"),
+    ("Reference", "Object",  "Ljava/lang/Object;"),
+    ("Boolean",   "Boolean", "Z"),
+    ("Byte",      "Byte",    "B"),
+    ("Short",     "Short",   "S"),
+    ("Char",      "Char",    "C"),
+    ("Float",     "Float",   "F"),
+    ("Double",    "Double",  "D"),
+];
+
+/// Native methods implemented by the VM, by class name, method name and descriptor.
+pub struct NativeRegistry {
+    methods: RwLock<HashMap<(String, String, String), NativeMethod>>,
+}
+
+impl NativeRegistry {
+    /// Creates a registry with the natives the JDK needs to run.
+    pub fn new() -> NativeRegistry {
+        let registry = NativeRegistry { methods: RwLock::new(HashMap::new()) };
+        registry.register("java/lang/Object", "hashCode", "()I", object_hash_code);
+        registry.register("java/lang/Object", "getClass", "()Ljava/lang/Class;", object_get_class);
+        registry.register("java/lang/Object", "clone", "()Ljava/lang/Object;", object_clone);
+
+        registry.register("java/lang/System", "arraycopy", "(Ljava/lang/Object;ILjava/lang/Object;II)V", system_arraycopy);
+        registry.register("java/lang/System", "currentTimeMillis", "()J", system_current_time_millis);
+        registry.register("java/lang/System", "nanoTime", "()J", system_nano_time);
+        registry.register("java/lang/System", "setIn0", "(Ljava/io/InputStream;)V", system_set_in);
+        registry.register("java/lang/System", "setOut0", "(Ljava/io/PrintStream;)V", system_set_out);
+        registry.register("java/lang/System", "setErr0", "(Ljava/io/PrintStream;)V", system_set_err);
+        registry.register("java/lang/System", "identityHashCode", "(Ljava/lang/Object;)I", system_identity_hash_code);
+
+        registry.register("java/lang/Shutdown", "beforeHalt", "()V", no_op);
+        registry.register("java/lang/Shutdown", "halt0", "(I)V", shutdown_halt);
+
+        registry.register("java/lang/Runtime", "availableProcessors", "()I", runtime_available_processors);
+        registry.register("java/lang/Runtime", "freeMemory", "()J", runtime_memory);
+        registry.register("java/lang/Runtime", "totalMemory", "()J", runtime_memory);
+        registry.register("java/lang/Runtime", "maxMemory", "()J", runtime_memory);
+        registry.register("java/lang/Runtime", "gc", "()V", no_op);
+
+        registry.register("java/lang/Class", "getPrimitiveClass", "(Ljava/lang/String;)Ljava/lang/Class;", class_get_primitive_class);
+        registry.register("java/lang/Class", "desiredAssertionStatus0", "(Ljava/lang/Class;)Z", class_desired_assertion_status);
+        registry.register("java/lang/Class", "forName0", "(Ljava/lang/String;ZLjava/lang/ClassLoader;Ljava/lang/Class;)Ljava/lang/Class;", class_for_name);
+        registry.register("java/lang/Class", "isPrimitive", "()Z", class_is_primitive);
+        registry.register("java/lang/Class", "isArray", "()Z", class_is_array);
+        registry.register("java/lang/Class", "isInterface", "()Z", class_is_interface);
+        registry.register("java/lang/Class", "initClassName", "()Ljava/lang/String;", class_init_class_name);
+        registry.register("java/lang/Class", "getName0", "()Ljava/lang/String;", class_init_class_name);
+
+        registry.register("java/lang/Throwable", "fillInStackTrace", "(I)Ljava/lang/Throwable;", throwable_fill_in_stack_trace);
+        registry.register("java/lang/NullPointerException", "getExtendedNPEMessage", "()Ljava/lang/String;", null_pointer_exception_get_extended_npe_message);
+        registry.register("java/lang/String", "intern", "()Ljava/lang/String;", string_intern);
+
+        registry.register("java/lang/Float", "floatToRawIntBits", "(F)I", float_to_raw_int_bits);
+        registry.register("java/lang/Float", "intBitsToFloat", "(I)F", int_bits_to_float);
+        registry.register("java/lang/Double", "doubleToRawLongBits", "(D)J", double_to_raw_long_bits);
+        registry.register("java/lang/Double", "longBitsToDouble", "(J)D", long_bits_to_double);
+
+        // Math calls these for the functions that the JVM doesn't have intrinsics for
+        for name in ["sin", "cos", "tan", "asin", "acos", "atan", "exp", "log", "log10", "sqrt", "cbrt", "sinh", "cosh", "tanh", "expm1", "log1p"] {
+            registry.register("java/lang/StrictMath", name, "(D)D", strict_math);
+        }
+        for name in ["IEEEremainder", "atan2", "pow", "hypot"] {
+            registry.register("java/lang/StrictMath", name, "(DD)D", strict_math);
+        }
+
+        registry.register_unsafe();
+        jdk_natives::register(&registry);
+        stub_natives::register(&registry);
+        reflection::register(&registry);
+        thread_natives::register(&registry);
+        method_handles::register(&registry);
+        registry
+    }
+
+    pub fn register(&self, class_name: &str, name: &str, descriptor: &str, native: NativeMethod) {
+        let key = (class_name.to_string(), name.to_string(), descriptor.to_string());
+        self.methods.write().unwrap().insert(key, native);
+    }
+
+    /// Registers the Unsafe natives, under their JDK 9+ and JDK 8 names.
+    fn register_unsafe(&self) {
+        for unsafe_class in [JDK_UNSAFE, SUN_UNSAFE] {
+            let suffix = if unsafe_class == JDK_UNSAFE { "0" } else { "" };
+            self.register(unsafe_class, &format!("arrayBaseOffset{}", suffix), "(Ljava/lang/Class;)I", unsafe_array_base_offset);
+            self.register(unsafe_class, &format!("arrayIndexScale{}", suffix), "(Ljava/lang/Class;)I", unsafe_array_index_scale);
+            self.register(unsafe_class, &format!("addressSize{}", suffix), "()I", unsafe_address_size);
+            self.register(unsafe_class, "pageSize", "()I", unsafe_page_size);
+            for fence in ["loadFence", "storeFence", "fullFence"] {
+                self.register(unsafe_class, fence, "()V", no_op);
+            }
+        }
+        self.register(JDK_UNSAFE, "allocateUninitializedArray0", "(Ljava/lang/Class;I)Ljava/lang/Object;", unsafe_allocate_uninitialized_array);
+        self.register(JDK_UNSAFE, "allocateInstance", "(Ljava/lang/Class;)Ljava/lang/Object;", unsafe_allocate_instance);
+        self.register(SUN_UNSAFE, "allocateInstance", "(Ljava/lang/Class;)Ljava/lang/Object;", unsafe_allocate_instance);
+        self.register(JDK_UNSAFE, "objectFieldOffset1", "(Ljava/lang/Class;Ljava/lang/String;)J", unsafe_object_field_offset);
+
+        for (jdk_name, sun_name, descriptor) in UNSAFE_TYPES {
+            for (unsafe_class, type_name) in [(JDK_UNSAFE, jdk_name), (SUN_UNSAFE, sun_name)] {
+                for access in ["", "Volatile", "Acquire", "Opaque"] {
+                    self.register(unsafe_class, &format!("get{}{}", type_name, access), &format!("(Ljava/lang/Object;J){}", descriptor), unsafe_get);
+                }
+                for access in ["", "Volatile", "Release", "Opaque"] {
+                    self.register(unsafe_class, &format!("put{}{}", type_name, access), &format!("(Ljava/lang/Object;J{})V", descriptor), unsafe_put);
+                }
+            }
+            self.register(JDK_UNSAFE, &format!("compareAndSet{}", jdk_name), &format!("(Ljava/lang/Object;J{}{})Z", descriptor, descriptor), unsafe_compare_and_set);
+            self.register(SUN_UNSAFE, &format!("compareAndSwap{}", sun_name), &format!("(Ljava/lang/Object;J{}{})Z", descriptor, descriptor), unsafe_compare_and_set);
+            self.register(SUN_UNSAFE, &format!("putOrdered{}", sun_name), &format!("(Ljava/lang/Object;J{})V", descriptor), unsafe_put);
+        }
+    }
+
+    pub fn find(&self, method: &Method) -> Option<NativeMethod> {
+        let key = (method.class_name.clone(), method.name.clone(), method.descriptor.clone());
+        if let Some(native) = self.methods.read().unwrap().get(&key) {
+            return Some(*native);
+        }
+        // Many classes link their natives from a static initializer, there is nothing to do for that here
+        match (method.name.as_str(), method.descriptor.as_str()) {
+            ("registerNatives" | "initIDs", "()V") => Some(no_op),
+            other => None
+        }
+    }
+
+    /// Runs a native method, or throws UnsatisfiedLinkError when there is no implementation for it.
+    pub fn invoke(&self, thread: &JavaThread, method: &Method, args: Vec<Value>) -> JvmResult<Option<Value>> {
+        match self.find(method) {
+            Some(native) => native(thread, args),
+            None => Err(Throwable::new("java/lang/UnsatisfiedLinkError",
+                format!("{}.{}{}", method.class_name.replace('/', "."), method.name, method.descriptor)))
+        }
+    }
+}
+
+pub fn reference_arg(args: &[Value], index: usize) -> JvmResult<ObjectRef> {
+    args[index].as_reference().cloned().ok_or_else(|| Throwable::without_message("java/lang/NullPointerException"))
+}
+
+fn no_op(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(None)
+}
+
+fn object_hash_code(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(reference_arg(&args, 0)?.identity_hash())))
+}
+
+fn object_get_class(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mirror = reference_arg(&args, 0)?.class.mirror(thread)?;
+    Ok(Some(Value::Reference(Some(mirror))))
+}
+
+fn object_clone(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let object = reference_arg(&args, 0)?;
+    if !object.is_array() && !object.class.is_subclass_of("java/lang/Cloneable") {
+        return Err(Throwable::new("java/lang/CloneNotSupportedException", object.class.name.replace('/', ".")));
+    }
+    Ok(Some(Value::Reference(Some(object.shallow_clone()))))
+}
+
+/// Implements System.arraycopy, with the checks from its documentation.
+fn system_arraycopy(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let source = reference_arg(&args, 0)?;
+    let source_position = args[1].as_int();
+    let destination = reference_arg(&args, 2)?;
+    let destination_position = args[3].as_int();
+    let length = args[4].as_int();
+    if !source.is_array() || !destination.is_array() {
+        let not_an_array = if source.is_array() { "destination" } else { "source" };
+        return Err(Throwable::new("java/lang/ArrayStoreException", format!("arraycopy: {} type {} is not an array", not_an_array,
+            if source.is_array() { &destination.class.name } else { &source.class.name }.replace('/', "."))));
+    }
+    let is_primitive = |class: &Class| !matches!(class.name.as_bytes()[1], b'L' | b'[');
+    if (is_primitive(&source.class) || is_primitive(&destination.class)) && source.class.name != destination.class.name {
+        return Err(Throwable::new("java/lang/ArrayStoreException", format!("arraycopy: type mismatch: can not copy {} into {}", source.class.name, destination.class.name)));
+    }
+    let check_range = |array: &ObjectRef, position: i32, which: &str| {
+        let array_length = array.array().len() as i64;
+        if position < 0 || length < 0 || position as i64 + length as i64 > array_length {
+            return Err(Throwable::new("java/lang/ArrayIndexOutOfBoundsException",
+                format!("arraycopy: last {} index {} out of bounds for length {}", which, position as i64 + length as i64, array_length)));
+        }
+        Ok(())
+    };
+    check_range(&source, source_position, "source")?;
+    check_range(&destination, destination_position, "destination")?;
+    let (source_position, destination_position, length) = (source_position as usize, destination_position as usize, length as usize);
+    if Arc::ptr_eq(&source, &destination) {
+        source.array().copy_within(source_position, destination_position, length);
+        return Ok(None);
+    }
+    let source_data = source.array();
+    let mut destination_data = destination.array();
+    // Reference arrays of different types need a check of every element that gets stored
+    let component = destination.class.component.as_ref().unwrap();
+    if !is_primitive(&destination.class) && !source.class.is_assignable_to(&destination.class) {
+        for index in 0..length {
+            let element = source_data.get(source_position + index);
+            if let Some(object) = element.as_reference().filter(|object| !object.class.is_assignable_to(component)) {
+                return Err(Throwable::new("java/lang/ArrayStoreException",
+                    format!("arraycopy: element type mismatch: can not cast one of the elements of {}[] to the type of the destination array, {}", source.class.component.as_ref().unwrap().name.replace('/', "."), component.name.replace('/', "."))));
+            }
+            destination_data.set(destination_position + index, element);
+        }
+        return Ok(None);
+    }
+    source_data.copy_to(source_position, &mut destination_data, destination_position, length);
+    Ok(None)
+}
+
+fn system_current_time_millis(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+    Ok(Some(Value::Long(millis as i64)))
+}
+
+fn system_nano_time(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Long(thread.vm.start_time.elapsed().as_nanos() as i64)))
+}
+
+fn system_identity_hash_code(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let hash = args[0].as_reference().map(|object| object.identity_hash()).unwrap_or(0);
+    Ok(Some(Value::Int(hash)))
+}
+
+/// Sets one of the standard streams, which are final fields that only the VM can change.
+fn set_system_stream(thread: &JavaThread, name: &str, stream: Value) -> JvmResult<Option<Value>> {
+    let system_class = thread.vm.load_class("java/lang/System")?;
+    let field = system_class.fields.iter().find(|field| field.is_static() && field.name == name).unwrap();
+    system_class.static_values.lock().unwrap()[field.slot] = stream;
+    Ok(None)
+}
+
+fn system_set_in(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    set_system_stream(thread, "in", args[0].clone())
+}
+
+fn system_set_out(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    set_system_stream(thread, "out", args[0].clone())
+}
+
+fn system_set_err(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    set_system_stream(thread, "err", args[0].clone())
+}
+
+fn shutdown_halt(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    process::exit(args[0].as_int());
+}
+
+fn runtime_available_processors(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let processors = thread::available_parallelism().map(|count| count.get()).unwrap_or(1);
+    Ok(Some(Value::Int(processors as i32)))
+}
+
+/// There is no garbage collected heap, so the memory figures are only there to keep code that reads them working.
+fn runtime_memory(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Long(256 * 1024 * 1024)))
+}
+
+fn class_get_primitive_class(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let name = to_rust_string(&reference_arg(&args, 0)?);
+    let mirror = thread.vm.primitive_class(&name).mirror(thread)?;
+    Ok(Some(Value::Reference(Some(mirror))))
+}
+
+fn class_desired_assertion_status(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(0)))
+}
+
+/// Loads a class by its binary name, like `java.lang.String` or `[I`, and initializes it if asked to.
+pub fn class_for_name(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let name = to_rust_string(&reference_arg(&args, 0)?);
+    let initialize = args[1].as_int() != 0;
+    if name.contains('/') {
+        return Err(Throwable::new("java/lang/ClassNotFoundException", name));
+    }
+    let class = match thread.vm.load_class(&name.replace('.', "/")) {
+        Ok(class) => class,
+        Err(Throwable::Pending { class_name, .. }) if class_name == "java/lang/NoClassDefFoundError" => {
+            return Err(Throwable::new("java/lang/ClassNotFoundException", name));
+        },
+        Err(other) => return Err(other)
+    };
+    if initialize {
+        class.initialize(thread)?;
+    }
+    Ok(Some(Value::Reference(Some(class.mirror(thread)?))))
+}
+
+fn class_is_primitive(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    Ok(Some(Value::Int(class.is_primitive() as i32)))
+}
+
+fn class_is_array(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    Ok(Some(Value::Int(class.is_array() as i32)))
+}
+
+fn class_is_interface(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    Ok(Some(Value::Int(class.is_interface() as i32)))
+}
+
+/// Returns the name of a class as Class.getName does, which caches it in the `name` field (JDK 9 and later).
+fn class_init_class_name(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mirror = reference_arg(&args, 0)?;
+    let class = Class::from_mirror(&mirror);
+    let name = new_java_string(thread, &class.name.replace('/', "."))?;
+    if let Some(slot) = mirror.class.instance_field_slot("name", "Ljava/lang/String;") {
+        mirror.fields()[slot] = Value::Reference(Some(name.clone()));
+    }
+    Ok(Some(Value::Reference(Some(name))))
+}
+
+fn throwable_fill_in_stack_trace(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    fill_in_stack_trace(thread, &reference_arg(&args, 0)?);
+    Ok(Some(args[0].clone()))
+}
+
+/// Returns no helpful message (JEP 358) for a NullPointerException, so getMessage returns null like it does on HotSpot
+/// with -XX:-ShowCodeDetailsInExceptionMessages.
+fn null_pointer_exception_get_extended_npe_message(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::null()))
+}
+
+fn string_intern(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let string = reference_arg(&args, 0)?;
+    Ok(Some(Value::Reference(Some(thread.vm.intern_string(string)))))
+}
+
+fn float_to_raw_int_bits(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(args[0].as_float().to_bits() as i32)))
+}
+
+fn int_bits_to_float(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Float(f32::from_bits(args[0].as_int() as u32))))
+}
+
+fn double_to_raw_long_bits(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Long(args[0].as_double().to_bits() as i64)))
+}
+
+fn long_bits_to_double(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Double(f64::from_bits(args[0].as_long() as u64))))
+}
+
+/// The functions of StrictMath. Java specifies the results of the fdlibm algorithms, the ones that the C library rounds
+/// differently too often are ported in fdlibm.rs. The trigonometric functions come from the C library, which rounds the
+/// last bit differently for a few percent of the arguments.
+fn strict_math(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let name = thread.stack.borrow().last().unwrap().method.name.clone();
+    let value = args[0].as_double();
+    let second = args.get(1).map(Value::as_double).unwrap_or(0.0);
+    let result = match name.as_str() {
+        "sin" => value.sin(),
+        "cos" => value.cos(),
+        "tan" => value.tan(),
+        "asin" => value.asin(),
+        "acos" => value.acos(),
+        "atan" => fdlibm::atan(value),
+        "exp" => fdlibm::exp(value),
+        "log" => fdlibm::log(value),
+        "log10" => fdlibm::log10(value),
+        "sqrt" => value.sqrt(),
+        "cbrt" => value.cbrt(),
+        "sinh" => fdlibm::sinh(value),
+        "cosh" => fdlibm::cosh(value),
+        "tanh" => fdlibm::tanh(value),
+        "expm1" => fdlibm::expm1(value),
+        "log1p" => value.ln_1p(),
+        "IEEEremainder" => fdlibm::ieee_remainder(value, second),
+        "atan2" => fdlibm::atan2(value, second),
+        "pow" => value.powf(second),
+        "hypot" => value.hypot(second),
+        other => panic!("Unknown StrictMath function {}", other)
+    };
+    Ok(Some(Value::Double(result)))
+}
+
+// Objects have no memory layout, so Unsafe offsets are field slots, or element indexes for arrays.
+// Arrays start at offset 0 and every element takes 1.
+
+fn unsafe_array_base_offset(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(0)))
+}
+
+fn unsafe_array_index_scale(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(1)))
+}
+
+fn unsafe_address_size(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(size_of::<usize>() as i32)))
+}
+
+fn unsafe_page_size(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(4096)))
+}
+
+fn unsafe_allocate_uninitialized_array(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let component = Class::from_mirror(&reference_arg(&args, 1)?);
+    let length = args[2].as_int();
+    if length < 0 {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", length.to_string()));
+    }
+    let class = thread.vm.load_class(&format!("[{}", component.descriptor()))?;
+    let data = ArrayData::new(&class.name[1..], length as usize);
+    Ok(Some(Value::Reference(Some(Object::new_array(class, data)))))
+}
+
+fn unsafe_allocate_instance(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 1)?);
+    if class.is_interface() || class.is_array() || class.is_primitive() || class.access_flags.contains(ClassFlags::ACC_ABSTRACT) {
+        return Err(Throwable::new("java/lang/InstantiationException", class.name.replace('/', ".")));
+    }
+    class.initialize(thread)?;
+    Ok(Some(Value::Reference(Some(Object::new_instance(class)))))
+}
+
+fn unsafe_object_field_offset(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 1)?);
+    let name = to_rust_string(&reference_arg(&args, 2)?);
+    let field = class.fields.iter()
+        .find(|field| !field.is_static() && field.name == name)
+        .ok_or_else(|| Throwable::new("java/lang/InternalError", name))?;
+    Ok(Some(Value::Long(field.slot as i64)))
+}
+
+/// Runs a function on the variable at an Unsafe offset: an array element, a static field of the class whose
+/// mirror is the object, or an instance field. The variable stays locked while the function runs.
+pub fn access_unsafe<R>(object: &ObjectRef, offset: i64, access: impl FnOnce(&mut Value) -> R) -> R {
+    if object.is_array() {
+        let mut data = object.array();
+        let mut value = data.get(offset as usize);
+        let result = access(&mut value);
+        data.set(offset as usize, value);
+        result
+    } else if offset & STATIC_FIELD_OFFSET != 0 {
+        let class = Class::from_mirror(object);
+        let mut static_values = class.static_values.lock().unwrap();
+        access(&mut static_values[(offset & !STATIC_FIELD_OFFSET) as usize])
+    } else {
+        access(&mut object.fields()[offset as usize])
+    }
+}
+
+fn unsafe_get(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let object = reference_arg(&args, 1)?;
+    let value = access_unsafe(&object, args[2].as_long(), |value| value.clone());
+    Ok(Some(value))
+}
+
+fn unsafe_put(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let object = reference_arg(&args, 1)?;
+    let value = args[3].clone();
+    access_unsafe(&object, args[2].as_long(), |variable| *variable = value);
+    Ok(None)
+}
+
+pub fn same_value(value1: &Value, value2: &Value) -> bool {
+    match (value1, value2) {
+        (Value::Int(value1), Value::Int(value2)) => value1 == value2,
+        (Value::Long(value1), Value::Long(value2)) => value1 == value2,
+        (Value::Float(value1), Value::Float(value2)) => value1.to_bits() == value2.to_bits(),
+        (Value::Double(value1), Value::Double(value2)) => value1.to_bits() == value2.to_bits(),
+        (Value::Reference(Some(object1)), Value::Reference(Some(object2))) => Arc::ptr_eq(object1, object2),
+        (Value::Reference(None), Value::Reference(None)) => true,
+        other => false
+    }
+}
+
+/// The field or element stays locked between the compare and the set, which makes the update atomic.
+fn unsafe_compare_and_set(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let object = reference_arg(&args, 1)?;
+    let (expected, value) = (&args[3], args[4].clone());
+    let swapped = access_unsafe(&object, args[2].as_long(), |variable| {
+        let swapped = same_value(variable, expected);
+        if swapped {
+            *variable = value;
+        }
+        swapped
+    });
+    Ok(Some(Value::Int(swapped as i32)))
+}
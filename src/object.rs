@@ -0,0 +1,280 @@
+use std::{fmt, sync::{Arc, Mutex, MutexGuard}};
+
+use crate::{exceptions::BacktraceEntry, monitor::Monitor, runtime_class::Class, thread::ThreadControl};
+
+pub type ObjectRef = Arc<Object>;
+
+/// A value as it lives in a local variable, on the operand stack or in a field.
+#[derive(Clone)]
+pub enum Value {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Reference(Option<ObjectRef>),
+    ReturnAddress(usize),
+    // Unusable local variable slot, like the second half of a long or double
+    Top
+}
+
+impl Value {
+    pub fn null() -> Value {
+        Value::Reference(None)
+    }
+
+    /// Returns the zero value of a field or array element type, given as a descriptor like `I`.
+    pub fn default_for(descriptor: &str) -> Value {
+        match descriptor.as_bytes()[0] {
+            b'J' => Value::Long(0),
+            b'F' => Value::Float(0.0),
+            b'D' => Value::Double(0.0),
+            b'L' | b'[' => Value::Reference(None),
+            _ => Value::Int(0)
+        }
+    }
+
+    pub fn as_int(&self) -> i32 {
+        match self {
+            Value::Int(value) => *value,
+            other => panic!("Expected int, found {:?}", other)
+        }
+    }
+
+    pub fn as_long(&self) -> i64 {
+        match self {
+            Value::Long(value) => *value,
+            other => panic!("Expected long, found {:?}", other)
+        }
+    }
+
+    pub fn as_float(&self) -> f32 {
+        match self {
+            Value::Float(value) => *value,
+            other => panic!("Expected float, found {:?}", other)
+        }
+    }
+
+    pub fn as_double(&self) -> f64 {
+        match self {
+            Value::Double(value) => *value,
+            other => panic!("Expected double, found {:?}", other)
+        }
+    }
+
+    pub fn as_reference(&self) -> Option<&ObjectRef> {
+        match self {
+            Value::Reference(reference) => reference.as_ref(),
+            other => panic!("Expected reference, found {:?}", other)
+        }
+    }
+
+    /// Long and double values take up two local variable slots, and count as two for `pop2` and the like.
+    pub fn is_category2(&self) -> bool {
+        matches!(self, Value::Long(_) | Value::Double(_))
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "int {}", value),
+            Value::Long(value) => write!(f, "long {}", value),
+            Value::Float(value) => write!(f, "float {}", value),
+            Value::Double(value) => write!(f, "double {}", value),
+            Value::Reference(None) => write!(f, "null"),
+            Value::Reference(Some(object)) => write!(f, "{}@{:x}", object.class.name, object.identity_hash()),
+            Value::ReturnAddress(address) => write!(f, "returnAddress {}", address),
+            Value::Top => write!(f, "top")
+        }
+    }
+}
+
+pub enum ArrayData {
+    Boolean(Vec<i8>),
+    Byte(Vec<i8>),
+    Char(Vec<u16>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    Reference(Vec<Option<ObjectRef>>)
+}
+
+impl ArrayData {
+    /// Creates an array filled with zero values, for a component type given as a descriptor like `I`.
+    pub fn new(component_descriptor: &str, length: usize) -> ArrayData {
+        match component_descriptor.as_bytes()[0] {
+            b'Z' => ArrayData::Boolean(vec![0; length]),
+            b'B' => ArrayData::Byte(vec![0; length]),
+            b'C' => ArrayData::Char(vec![0; length]),
+            b'S' => ArrayData::Short(vec![0; length]),
+            b'I' => ArrayData::Int(vec![0; length]),
+            b'J' => ArrayData::Long(vec![0; length]),
+            b'F' => ArrayData::Float(vec![0.0; length]),
+            b'D' => ArrayData::Double(vec![0.0; length]),
+            _ => ArrayData::Reference(vec![None; length])
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ArrayData::Boolean(array) => array.len(),
+            ArrayData::Byte(array) => array.len(),
+            ArrayData::Char(array) => array.len(),
+            ArrayData::Short(array) => array.len(),
+            ArrayData::Int(array) => array.len(),
+            ArrayData::Long(array) => array.len(),
+            ArrayData::Float(array) => array.len(),
+            ArrayData::Double(array) => array.len(),
+            ArrayData::Reference(array) => array.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads an element as it would be pushed on the operand stack.
+    pub fn get(&self, index: usize) -> Value {
+        match self {
+            ArrayData::Boolean(array) => Value::Int(array[index] as i32),
+            ArrayData::Byte(array) => Value::Int(array[index] as i32),
+            ArrayData::Char(array) => Value::Int(array[index] as i32),
+            ArrayData::Short(array) => Value::Int(array[index] as i32),
+            ArrayData::Int(array) => Value::Int(array[index]),
+            ArrayData::Long(array) => Value::Long(array[index]),
+            ArrayData::Float(array) => Value::Float(array[index]),
+            ArrayData::Double(array) => Value::Double(array[index]),
+            ArrayData::Reference(array) => Value::Reference(array[index].clone())
+        }
+    }
+
+    /// Stores a value from the operand stack, narrowing ints to the element type.
+    pub fn set(&mut self, index: usize, value: Value) {
+        match self {
+            ArrayData::Boolean(array) => array[index] = (value.as_int() & 1) as i8,
+            ArrayData::Byte(array) => array[index] = value.as_int() as i8,
+            ArrayData::Char(array) => array[index] = value.as_int() as u16,
+            ArrayData::Short(array) => array[index] = value.as_int() as i16,
+            ArrayData::Int(array) => array[index] = value.as_int(),
+            ArrayData::Long(array) => array[index] = value.as_long(),
+            ArrayData::Float(array) => array[index] = value.as_float(),
+            ArrayData::Double(array) => array[index] = value.as_double(),
+            ArrayData::Reference(array) => array[index] = value.as_reference().cloned()
+        }
+    }
+}
+
+impl ArrayData {
+    /// Copies elements to another array of the same type, which must not be this one.
+    pub fn copy_to(&self, source_position: usize, destination: &mut ArrayData, destination_position: usize, length: usize) {
+        let source_range = source_position..source_position + length;
+        let destination_range = destination_position..destination_position + length;
+        match (self, destination) {
+            (ArrayData::Boolean(source), ArrayData::Boolean(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Byte(source), ArrayData::Byte(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Char(source), ArrayData::Char(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Short(source), ArrayData::Short(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Int(source), ArrayData::Int(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Long(source), ArrayData::Long(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Float(source), ArrayData::Float(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Double(source), ArrayData::Double(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            (ArrayData::Reference(source), ArrayData::Reference(destination)) => destination[destination_range].clone_from_slice(&source[source_range]),
+            other => panic!("Copy between arrays of different types")
+        }
+    }
+
+    /// Copies elements within this array, the ranges can overlap.
+    pub fn copy_within(&mut self, source_position: usize, destination_position: usize, length: usize) {
+        let copy = self.slice(source_position, length);
+        copy.copy_to(0, self, destination_position, length);
+    }
+
+    /// Returns a copy of part of the array.
+    pub fn slice(&self, position: usize, length: usize) -> ArrayData {
+        match self {
+            ArrayData::Boolean(array) => ArrayData::Boolean(array[position..position + length].to_vec()),
+            ArrayData::Byte(array) => ArrayData::Byte(array[position..position + length].to_vec()),
+            ArrayData::Char(array) => ArrayData::Char(array[position..position + length].to_vec()),
+            ArrayData::Short(array) => ArrayData::Short(array[position..position + length].to_vec()),
+            ArrayData::Int(array) => ArrayData::Int(array[position..position + length].to_vec()),
+            ArrayData::Long(array) => ArrayData::Long(array[position..position + length].to_vec()),
+            ArrayData::Float(array) => ArrayData::Float(array[position..position + length].to_vec()),
+            ArrayData::Double(array) => ArrayData::Double(array[position..position + length].to_vec()),
+            ArrayData::Reference(array) => ArrayData::Reference(array[position..position + length].to_vec())
+        }
+    }
+}
+
+pub enum ObjectKind {
+    Instance(Mutex<Vec<Value>>),
+    Array(Mutex<ArrayData>)
+}
+
+/// Data the VM keeps for some objects, that has no field in the Java class.
+pub enum VmData {
+    // Where a throwable was created, filled in by Throwable.fillInStackTrace
+    Backtrace(Vec<BacktraceEntry>),
+    // The class a java.lang.Class object stands for
+    Class(Arc<Class>),
+    // The running thread of a java.lang.Thread object, from the moment it got started
+    Thread(Arc<ThreadControl>)
+}
+
+/// An object on the heap.
+/// There is no garbage collector, objects live as long as something references them.
+pub struct Object {
+    pub class: Arc<Class>,
+    pub kind: ObjectKind,
+    pub vm_data: Mutex<Option<VmData>>,
+    pub monitor: Monitor
+}
+
+impl Object {
+    /// Allocates an object with all its fields set to their zero value.
+    pub fn new_instance(class: Arc<Class>) -> ObjectRef {
+        let fields = class.instance_field_defaults.clone();
+        Arc::new(Object { class, kind: ObjectKind::Instance(Mutex::new(fields)), vm_data: Mutex::new(None), monitor: Monitor::new() })
+    }
+
+    pub fn new_array(class: Arc<Class>, data: ArrayData) -> ObjectRef {
+        Arc::new(Object { class, kind: ObjectKind::Array(Mutex::new(data)), vm_data: Mutex::new(None), monitor: Monitor::new() })
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.kind, ObjectKind::Array(_))
+    }
+
+    /// Creates a shallow copy of the object, as Object.clone does.
+    pub fn shallow_clone(&self) -> ObjectRef {
+        let kind = match &self.kind {
+            ObjectKind::Instance(fields) => ObjectKind::Instance(Mutex::new(fields.lock().unwrap().clone())),
+            ObjectKind::Array(data) => {
+                let data = data.lock().unwrap();
+                ObjectKind::Array(Mutex::new(data.slice(0, data.len())))
+            }
+        };
+        Arc::new(Object { class: self.class.clone(), kind, vm_data: Mutex::new(None), monitor: Monitor::new() })
+    }
+
+    pub fn fields(&self) -> MutexGuard<'_, Vec<Value>> {
+        match &self.kind {
+            ObjectKind::Instance(fields) => fields.lock().unwrap(),
+            ObjectKind::Array(_) => panic!("Array {} has no fields", self.class.name)
+        }
+    }
+
+    pub fn array(&self) -> MutexGuard<'_, ArrayData> {
+        match &self.kind {
+            ObjectKind::Array(data) => data.lock().unwrap(),
+            ObjectKind::Instance(_) => panic!("Object of class {} is not an array", self.class.name)
+        }
+    }
+
+    /// The identity hash code is derived from the address of the object, which never moves.
+    pub fn identity_hash(&self) -> i32 {
+        let address = self as *const Object as usize;
+        ((address >> 3) ^ (address >> 35)) as i32 & 0x7FFFFFFF
+    }
+}
@@ -0,0 +1,497 @@
+use std::sync::Arc;
+
+use crate::{boot::construct, class_loader::{AttributeInfo, ClassFlags}, descriptor_parser::argument_descriptors, exceptions::{JvmResult, Throwable}, interpreter::Interpreter, natives::{reference_arg, NativeRegistry}, object::{ArrayData, Object, ObjectRef, Value}, runtime_class::{Class, Method}, strings::{new_java_string, to_rust_string}, thread::JavaThread};
+
+const JDK_UNSAFE: &str = "jdk/internal/misc/Unsafe";
+const SUN_UNSAFE: &str = "sun/misc/Unsafe";
+
+/// Offsets that Unsafe gets for static fields have this bit set, to tell them apart from the instance fields
+/// of the Class object that is their base.
+pub const STATIC_FIELD_OFFSET: i64 = 1 << 32;
+
+// The wrapper class of every primitive type, which reflection boxes values in
+pub const WRAPPER_CLASSES: [(&str, &str); 8] = [
+    ("Z", "java/lang/Boolean"),
+    ("B", "java/lang/Byte"),
+    ("C", "java/lang/Character"),
+    ("S", "java/lang/Short"),
+    ("I", "java/lang/Integer"),
+    ("J", "java/lang/Long"),
+    ("F", "java/lang/Float"),
+    ("D", "java/lang/Double"),
+];
+
+/// Registers the natives behind java.lang.Class and java.lang.reflect, under their JDK 9+ and JDK 8 names.
+pub fn register(registry: &NativeRegistry) {
+    registry.register("java/lang/Class", "getSuperclass", "()Ljava/lang/Class;", class_get_superclass);
+    registry.register("java/lang/Class", "getInterfaces0", "()[Ljava/lang/Class;", class_get_interfaces);
+    registry.register("java/lang/Class", "getInterfaces", "()[Ljava/lang/Class;", class_get_interfaces);
+    registry.register("java/lang/Class", "getComponentType", "()Ljava/lang/Class;", class_get_component_type);
+    registry.register("java/lang/Class", "getModifiers", "()I", class_get_modifiers);
+    registry.register("java/lang/Class", "isInstance", "(Ljava/lang/Object;)Z", class_is_instance);
+    registry.register("java/lang/Class", "isAssignableFrom", "(Ljava/lang/Class;)Z", class_is_assignable_from);
+    registry.register("java/lang/Class", "isHidden", "()Z", class_is_hidden);
+    registry.register("java/lang/Class", "isRecord0", "()Z", class_is_record);
+    registry.register("java/lang/Class", "getRecordComponents0", "()[Ljava/lang/reflect/RecordComponent;", class_get_record_components);
+    registry.register("java/lang/Class", "getNestHost0", "()Ljava/lang/Class;", return_receiver);
+    // There is no InnerClasses, EnclosingMethod or Signature information, nor annotations
+    for (name, descriptor) in [
+        ("getDeclaringClass0", "()Ljava/lang/Class;"),
+        ("getEnclosingMethod0", "()[Ljava/lang/Object;"),
+        ("getSimpleBinaryName0", "()Ljava/lang/String;"),
+        ("getGenericSignature0", "()Ljava/lang/String;"),
+        ("getRawAnnotations", "()[B"),
+        ("getRawTypeAnnotations", "()[B"),
+        ("getConstantPool", "()Ljdk/internal/reflect/ConstantPool;"),
+        ("getConstantPool", "()Lsun/reflect/ConstantPool;"),
+        ("getProtectionDomain0", "()Ljava/security/ProtectionDomain;"),
+        ("getSigners", "()[Ljava/lang/Object;"),
+    ] {
+        registry.register("java/lang/Class", name, descriptor, return_null);
+    }
+    registry.register("java/lang/Class", "getDeclaredMethods0", "(Z)[Ljava/lang/reflect/Method;", class_get_declared_methods);
+    registry.register("java/lang/Class", "getDeclaredConstructors0", "(Z)[Ljava/lang/reflect/Constructor;", class_get_declared_constructors);
+    registry.register("java/lang/Class", "getDeclaredFields0", "(Z)[Ljava/lang/reflect/Field;", class_get_declared_fields);
+
+    for package in ["jdk/internal/reflect", "sun/reflect"] {
+        registry.register(&format!("{}/NativeMethodAccessorImpl", package), "invoke0",
+            "(Ljava/lang/reflect/Method;Ljava/lang/Object;[Ljava/lang/Object;)Ljava/lang/Object;", method_invoke);
+        registry.register(&format!("{}/NativeConstructorAccessorImpl", package), "newInstance0",
+            "(Ljava/lang/reflect/Constructor;[Ljava/lang/Object;)Ljava/lang/Object;", constructor_new_instance);
+    }
+    registry.register("java/lang/reflect/Array", "newArray", "(Ljava/lang/Class;I)Ljava/lang/Object;", array_new_array);
+    registry.register("java/lang/reflect/Array", "getLength", "(Ljava/lang/Object;)I", array_get_length);
+
+    for (unsafe_class, suffix) in [(JDK_UNSAFE, "0"), (SUN_UNSAFE, "")] {
+        registry.register(unsafe_class, &format!("objectFieldOffset{}", suffix), "(Ljava/lang/reflect/Field;)J", unsafe_field_offset);
+        registry.register(unsafe_class, &format!("staticFieldOffset{}", suffix), "(Ljava/lang/reflect/Field;)J", unsafe_field_offset);
+        registry.register(unsafe_class, &format!("staticFieldBase{}", suffix), "(Ljava/lang/reflect/Field;)Ljava/lang/Object;", unsafe_static_field_base);
+        registry.register(unsafe_class, &format!("ensureClassInitialized{}", suffix), "(Ljava/lang/Class;)V", unsafe_ensure_class_initialized);
+        registry.register(unsafe_class, &format!("shouldBeInitialized{}", suffix), "(Ljava/lang/Class;)Z", unsafe_should_be_initialized);
+    }
+}
+
+fn return_null(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::null()))
+}
+
+fn return_false(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(0)))
+}
+
+fn return_receiver(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(args[0].clone()))
+}
+
+fn mirror_value(thread: &JavaThread, class: Option<&Arc<Class>>) -> JvmResult<Option<Value>> {
+    let mirror = match class {
+        Some(class) => Some(class.mirror(thread)?),
+        None => None
+    };
+    Ok(Some(Value::Reference(mirror)))
+}
+
+/// Creates a `Class[]` with the mirrors of some classes.
+pub fn new_class_array(thread: &JavaThread, classes: &[Arc<Class>]) -> JvmResult<ObjectRef> {
+    let mut mirrors = Vec::with_capacity(classes.len());
+    for class in classes {
+        mirrors.push(Some(class.mirror(thread)?));
+    }
+    Ok(Object::new_array(thread.vm.load_class("[Ljava/lang/Class;")?, ArrayData::Reference(mirrors)))
+}
+
+/// Loads the classes of some field descriptors, like the parameter types of a method.
+fn classes_for_descriptors(thread: &JavaThread, descriptors: &[&str]) -> JvmResult<Vec<Arc<Class>>> {
+    descriptors.iter().map(|descriptor| thread.vm.class_for_descriptor(descriptor)).collect()
+}
+
+fn class_get_superclass(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    // Interfaces have Object as their superclass in the class file, but not for Java code
+    let super_class = class.super_class.as_ref().filter(|_| !class.is_interface());
+    mirror_value(thread, super_class)
+}
+
+fn class_get_interfaces(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    Ok(Some(Value::Reference(Some(new_class_array(thread, &class.interfaces)?))))
+}
+
+fn class_get_component_type(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    mirror_value(thread, class.component.as_ref())
+}
+
+fn class_get_modifiers(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    Ok(Some(Value::Int(class.modifiers())))
+}
+
+fn class_is_hidden(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    Ok(Some(Value::Int(thread.vm.is_hidden_class(&class.name) as i32)))
+}
+
+fn class_is_instance(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    let is_instance = args[1].as_reference().is_some_and(|object| object.class.is_assignable_to(&class));
+    Ok(Some(Value::Int(is_instance as i32)))
+}
+
+fn class_is_assignable_from(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    let other = Class::from_mirror(&reference_arg(&args, 1)?);
+    Ok(Some(Value::Int(other.is_assignable_to(&class) as i32)))
+}
+
+fn new_reflection_array(thread: &JavaThread, class_name: &str, objects: Vec<ObjectRef>) -> JvmResult<Option<Value>> {
+    let array_class = thread.vm.load_class(&format!("[L{};", class_name))?;
+    let array = Object::new_array(array_class, ArrayData::Reference(objects.into_iter().map(Some).collect()));
+    Ok(Some(Value::Reference(Some(array))))
+}
+
+/// Returns the methods of a class whose name matches, with their slot: their index in the methods of the class.
+fn declared_methods(class: &Class, public_only: bool, constructors: bool) -> Vec<(usize, Arc<Method>)> {
+    class.methods.iter().cloned().enumerate()
+        .filter(|(_, method)| (method.name == "<init>") == constructors && method.name != "<clinit>")
+        .filter(|(_, method)| !public_only || method.access_flags.contains(crate::class_loader::MethodFlags::ACC_PUBLIC))
+        .collect()
+}
+
+/// Returns the parameter types and checked exceptions of a method, as `Class[]` objects.
+fn method_signature_arrays(thread: &JavaThread, class: &Class, method: &Method) -> JvmResult<(Value, Value)> {
+    let parameter_types = classes_for_descriptors(thread, &argument_descriptors(&method.descriptor))?;
+    let info = class.info.as_ref().unwrap();
+    let mut exception_types = Vec::new();
+    for index in method.info(class).get_exceptions() {
+        let name = info.constant_pool.get_string(info.constant_pool.get_class_info(*index));
+        exception_types.push(thread.vm.load_class(name)?);
+    }
+    Ok((Value::Reference(Some(new_class_array(thread, &parameter_types)?)), Value::Reference(Some(new_class_array(thread, &exception_types)?))))
+}
+
+/// Creates the java.lang.reflect.Method object of a method, whose slot is its index in the methods of the class.
+fn new_method_object(thread: &JavaThread, mirror: &ObjectRef, class: &Class, slot: usize, method: &Method) -> JvmResult<ObjectRef> {
+    let (parameter_types, exception_types) = method_signature_arrays(thread, class, method)?;
+    let return_type = thread.vm.class_for_descriptor(method.return_descriptor())?.mirror(thread)?;
+    construct(thread, "java/lang/reflect/Method",
+        "(Ljava/lang/Class;Ljava/lang/String;[Ljava/lang/Class;Ljava/lang/Class;[Ljava/lang/Class;IILjava/lang/String;[B[B[B)V",
+        vec![
+            Value::Reference(Some(mirror.clone())),
+            Value::Reference(Some(thread.vm.intern_string(new_java_string(thread, &method.name)?))),
+            parameter_types,
+            Value::Reference(Some(return_type)),
+            exception_types,
+            Value::Int(method.access_flags.bits() as i32),
+            Value::Int(slot as i32),
+            Value::null(),
+            Value::null(),
+            Value::null(),
+            Value::null(),
+        ])
+}
+
+/// Creates the java.lang.reflect.Method objects of a class.
+fn class_get_declared_methods(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mirror = reference_arg(&args, 0)?;
+    let class = Class::from_mirror(&mirror);
+    let mut methods = Vec::new();
+    for (slot, method) in declared_methods(&class, args[1].as_int() != 0, false) {
+        methods.push(new_method_object(thread, &mirror, &class, slot, &method)?);
+    }
+    new_reflection_array(thread, "java/lang/reflect/Method", methods)
+}
+
+/// Returns the name and descriptor of the components in the Record attribute of a class, if it has one.
+fn record_components(class: &Class) -> Option<Vec<(String, String)>> {
+    let info = class.info.as_ref()?;
+    let constant_pool = &info.constant_pool;
+    let record = info.attributes.iter().find_map(|attribute| match attribute {
+        AttributeInfo::Unknown(unknown) if constant_pool.get_string(unknown.name_index) == "Record" => Some(&unknown.info),
+        other => None
+    })?;
+    let u2 = |position: usize| record.get(position..position + 2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+    let u4 = |position: usize| record.get(position..position + 4).map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    let mut components = Vec::new();
+    let mut position = 2;
+    for _ in 0..u2(0)? {
+        let name = constant_pool.get_string(u2(position)?).to_string();
+        let descriptor = constant_pool.get_string(u2(position + 2)?).to_string();
+        let attributes_count = u2(position + 4)?;
+        position += 6;
+        // Skips the Signature and annotations of the component
+        for _ in 0..attributes_count {
+            position += 6 + u4(position + 2)? as usize;
+        }
+        components.push((name, descriptor));
+    }
+    Some(components)
+}
+
+/// Tells whether a class is a record: a final class that extends java.lang.Record and has a Record attribute.
+fn class_is_record(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 0)?);
+    let is_record = class.info.as_ref().is_some_and(|info| info.access_flags.contains(ClassFlags::ACC_FINAL))
+        && class.super_class.as_ref().is_some_and(|super_class| super_class.name == "java/lang/Record")
+        && record_components(&class).is_some();
+    Ok(Some(Value::Int(is_record as i32)))
+}
+
+/// Creates the java.lang.reflect.RecordComponent objects of a record, which have no constructor to call.
+fn class_get_record_components(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mirror = reference_arg(&args, 0)?;
+    let class = Class::from_mirror(&mirror);
+    let Some(components) = record_components(&class) else {
+        return Ok(Some(Value::null()));
+    };
+    let component_class = thread.vm.load_class("java/lang/reflect/RecordComponent")?;
+    let mut objects = Vec::new();
+    for (name, descriptor) in components {
+        let accessor = match declared_methods(&class, false, false).into_iter()
+            .find(|(_, method)| method.name == name && method.descriptor == format!("(){}", descriptor)) {
+            Some((slot, method)) => Value::Reference(Some(new_method_object(thread, &mirror, &class, slot, &method)?)),
+            None => Value::null()
+        };
+        let name = Value::Reference(Some(thread.vm.intern_string(new_java_string(thread, &name)?)));
+        let component_type = Value::Reference(Some(thread.vm.class_for_descriptor(&descriptor)?.mirror(thread)?));
+        let object = Object::new_instance(component_class.clone());
+        {
+            let mut fields = object.fields();
+            fields[component_class.instance_field_slot("clazz", "Ljava/lang/Class;").unwrap()] = Value::Reference(Some(mirror.clone()));
+            fields[component_class.instance_field_slot("name", "Ljava/lang/String;").unwrap()] = name;
+            fields[component_class.instance_field_slot("type", "Ljava/lang/Class;").unwrap()] = component_type;
+            fields[component_class.instance_field_slot("accessor", "Ljava/lang/reflect/Method;").unwrap()] = accessor;
+        }
+        objects.push(object);
+    }
+    new_reflection_array(thread, "java/lang/reflect/RecordComponent", objects)
+}
+
+/// Creates the java.lang.reflect.Constructor objects of a class.
+fn class_get_declared_constructors(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mirror = reference_arg(&args, 0)?;
+    let class = Class::from_mirror(&mirror);
+    let mut constructors = Vec::new();
+    for (slot, method) in declared_methods(&class, args[1].as_int() != 0, true) {
+        let (parameter_types, exception_types) = method_signature_arrays(thread, &class, &method)?;
+        constructors.push(construct(thread, "java/lang/reflect/Constructor",
+            "(Ljava/lang/Class;[Ljava/lang/Class;[Ljava/lang/Class;IILjava/lang/String;[B[B)V",
+            vec![
+                Value::Reference(Some(mirror.clone())),
+                parameter_types,
+                exception_types,
+                Value::Int(method.access_flags.bits() as i32),
+                Value::Int(slot as i32),
+                Value::null(),
+                Value::null(),
+                Value::null(),
+            ])?);
+    }
+    new_reflection_array(thread, "java/lang/reflect/Constructor", constructors)
+}
+
+/// Creates the java.lang.reflect.Field objects of a class. Their slot is their index in the fields of the class.
+fn class_get_declared_fields(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mirror = reference_arg(&args, 0)?;
+    let class = Class::from_mirror(&mirror);
+    let public_only = args[1].as_int() != 0;
+    let field_class = thread.vm.load_class("java/lang/reflect/Field")?;
+    // JDK 9 and later tell whether the value of a final field can be trusted to never change
+    let has_trusted_final = field_class.find_declared_method("<init>", "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;IZILjava/lang/String;[B)V").is_some();
+    let mut fields = Vec::new();
+    for (slot, field) in class.fields.iter().enumerate() {
+        if public_only && !field.access_flags.contains(crate::class_loader::FieldFlags::ACC_PUBLIC) {
+            continue;
+        }
+        let mut field_args = vec![
+            Value::Reference(Some(mirror.clone())),
+            Value::Reference(Some(thread.vm.intern_string(new_java_string(thread, &field.name)?))),
+            Value::Reference(Some(thread.vm.class_for_descriptor(&field.descriptor)?.mirror(thread)?)),
+            Value::Int(field.access_flags.bits() as i32),
+        ];
+        if has_trusted_final {
+            field_args.push(Value::Int((field.is_static() && field.is_final()) as i32));
+        }
+        field_args.extend([Value::Int(slot as i32), Value::null(), Value::null()]);
+        let descriptor = if has_trusted_final {
+            "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;IZILjava/lang/String;[B)V"
+        } else {
+            "(Ljava/lang/Class;Ljava/lang/String;Ljava/lang/Class;IILjava/lang/String;[B)V"
+        };
+        fields.push(construct(thread, "java/lang/reflect/Field", descriptor, field_args)?);
+    }
+    new_reflection_array(thread, "java/lang/reflect/Field", fields)
+}
+
+fn int_field(object: &ObjectRef, name: &str) -> i32 {
+    object.fields()[object.class.instance_field_slot(name, "I").unwrap()].as_int()
+}
+
+/// Returns the declaring class and slot of a Method, Constructor or Field object.
+pub fn reflected_member(member: &ObjectRef) -> (Arc<Class>, usize) {
+    let clazz = member.fields()[member.class.instance_field_slot("clazz", "Ljava/lang/Class;").unwrap()].as_reference().cloned().unwrap();
+    (Class::from_mirror(&clazz), int_field(member, "slot") as usize)
+}
+
+/// Wraps a primitive value in an object of its wrapper class, like Integer for `I`.
+pub fn box_value(thread: &JavaThread, value: Value, descriptor: &str) -> JvmResult<Value> {
+    let wrapper_name = match WRAPPER_CLASSES.iter().find(|(primitive, _)| *primitive == descriptor) {
+        Some((_, wrapper_name)) => wrapper_name,
+        None => return Ok(value)
+    };
+    let wrapper = thread.vm.load_class(wrapper_name)?;
+    wrapper.initialize(thread)?;
+    let value_of_descriptor = format!("({})L{};", descriptor, wrapper_name);
+    // The stub library has some wrapper classes without boxes
+    let value_of = wrapper.find_declared_method("valueOf", &value_of_descriptor)
+        .ok_or_else(|| Throwable::new("java/lang/NoSuchMethodError", format!("{}.valueOf{}", wrapper_name.replace('/', "."), value_of_descriptor)))?;
+    Ok(Interpreter::invoke(thread, value_of, vec![value])?.unwrap())
+}
+
+/// Widens a primitive value to another primitive type, as method invocation conversion allows (JLS §5.1.2).
+pub fn widen(value: Value, from: &str, to: &str) -> Option<Value> {
+    if from == to {
+        return Some(value);
+    }
+    let widened = match (value, to) {
+        (Value::Int(value), "S") if from == "B" => Value::Int(value),
+        (Value::Int(value), "I") if from != "Z" => Value::Int(value),
+        (Value::Int(value), "J") if from != "Z" => Value::Long(value as i64),
+        (Value::Int(value), "F") if from != "Z" => Value::Float(value as f32),
+        (Value::Int(value), "D") if from != "Z" => Value::Double(value as f64),
+        (Value::Long(value), "F") => Value::Float(value as f32),
+        (Value::Long(value), "D") => Value::Double(value as f64),
+        (Value::Float(value), "D") => Value::Double(value as f64),
+        other => return None
+    };
+    Some(widened)
+}
+
+/// Converts an argument of Method.invoke to the type of a parameter, unboxing and widening primitives.
+fn unbox_argument(thread: &JavaThread, argument: Option<ObjectRef>, descriptor: &str) -> JvmResult<Value> {
+    let mismatch = || Throwable::new("java/lang/IllegalArgumentException", "argument type mismatch");
+    if matches!(descriptor.as_bytes()[0], b'L' | b'[') {
+        let parameter_class = thread.vm.class_for_descriptor(descriptor)?;
+        return match argument {
+            Some(object) if !object.class.is_assignable_to(&parameter_class) => Err(mismatch()),
+            other => Ok(Value::Reference(other))
+        };
+    }
+    let object = argument.ok_or_else(mismatch)?;
+    let (primitive, _) = WRAPPER_CLASSES.iter().find(|(_, wrapper_name)| *wrapper_name == object.class.name).ok_or_else(mismatch)?;
+    let value = object.fields()[object.class.instance_field_slot("value", primitive).unwrap()].clone();
+    widen(value, primitive, descriptor).ok_or_else(mismatch)
+}
+
+/// Unboxes the arguments of Method.invoke or Constructor.newInstance.
+fn unbox_arguments(thread: &JavaThread, method: &Method, arguments: Option<ObjectRef>) -> JvmResult<Vec<Value>> {
+    let arguments = match arguments {
+        Some(array) => match &*array.array() {
+            ArrayData::Reference(arguments) => arguments.clone(),
+            other => panic!("Arguments of a reflective call are not an Object[]")
+        },
+        None => Vec::new()
+    };
+    let parameter_types = argument_descriptors(&method.descriptor);
+    if arguments.len() != parameter_types.len() {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", "wrong number of arguments"));
+    }
+    arguments.into_iter().zip(parameter_types)
+        .map(|(argument, descriptor)| unbox_argument(thread, argument, descriptor))
+        .collect()
+}
+
+/// Runs a method for reflection, wrapping whatever it throws in an InvocationTargetException.
+fn invoke_wrapped(thread: &JavaThread, method: &Arc<Method>, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Interpreter::invoke(thread, method, args)
+        .map_err(|throwable| Throwable::caused_by("java/lang/reflect/InvocationTargetException", throwable))
+}
+
+/// Calls a method through Method.invoke. Instance methods are looked up in the class of the receiver,
+/// unless they can't be overridden.
+fn method_invoke(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (class, slot) = reflected_member(&reference_arg(&args, 0)?);
+    let mut method = class.methods[slot].clone();
+    let mut call_args = Vec::new();
+    if !method.is_static() {
+        let receiver = reference_arg(&args, 1)?;
+        if !receiver.class.is_assignable_to(&class) {
+            return Err(Throwable::new("java/lang/IllegalArgumentException", "object is not an instance of declaring class"));
+        }
+        let selected = receiver.class.vtable_index(&method.name, &method.descriptor).and_then(|index| receiver.class.vtable[index].clone());
+        if let Some(selected) = selected.filter(|_| !method.is_private()) {
+            method = selected;
+        }
+        call_args.push(Value::Reference(Some(receiver)));
+    } else {
+        class.initialize(thread)?;
+    }
+    call_args.extend(unbox_arguments(thread, &method, args[2].as_reference().cloned())?);
+    let result = invoke_wrapped(thread, &method, call_args)?;
+    match result {
+        Some(value) => Ok(Some(box_value(thread, value, method.return_descriptor())?)),
+        None => Ok(Some(Value::null()))
+    }
+}
+
+/// Creates an object through Constructor.newInstance.
+fn constructor_new_instance(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (class, slot) = reflected_member(&reference_arg(&args, 0)?);
+    if class.access_flags.contains(crate::class_loader::ClassFlags::ACC_ABSTRACT) {
+        return Err(Throwable::without_message("java/lang/InstantiationException"));
+    }
+    let constructor = class.methods[slot].clone();
+    let arguments = unbox_arguments(thread, &constructor, args[1].as_reference().cloned())?;
+    class.initialize(thread)?;
+    let object = Object::new_instance(class);
+    let mut call_args = vec![Value::Reference(Some(object.clone()))];
+    call_args.extend(arguments);
+    invoke_wrapped(thread, &constructor, call_args)?;
+    Ok(Some(Value::Reference(Some(object))))
+}
+
+fn array_new_array(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let component = Class::from_mirror(&reference_arg(&args, 0)?);
+    let length = args[1].as_int();
+    if length < 0 {
+        return Err(Throwable::new("java/lang/NegativeArraySizeException", length.to_string()));
+    }
+    if component.name == "void" {
+        return Err(Throwable::without_message("java/lang/IllegalArgumentException"));
+    }
+    let component_descriptor = component.descriptor();
+    let array_class = thread.vm.load_class(&format!("[{}", component_descriptor))?;
+    Ok(Some(Value::Reference(Some(Object::new_array(array_class, ArrayData::new(&component_descriptor, length as usize))))))
+}
+
+fn array_get_length(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let array = reference_arg(&args, 0)?;
+    if !array.is_array() {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", "Argument is not an array"));
+    }
+    Ok(Some(Value::Int(array.array().len() as i32)))
+}
+
+/// Returns the offset of a field for Unsafe: its slot, marked for static fields.
+fn unsafe_field_offset(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (class, slot) = reflected_member(&reference_arg(&args, 1)?);
+    let field = &class.fields[slot];
+    let offset = if field.is_static() { STATIC_FIELD_OFFSET | field.slot as i64 } else { field.slot as i64 };
+    Ok(Some(Value::Long(offset)))
+}
+
+/// Static fields are accessed through the Class object of their class.
+fn unsafe_static_field_base(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (class, _) = reflected_member(&reference_arg(&args, 1)?);
+    Ok(Some(Value::Reference(Some(class.mirror(thread)?))))
+}
+
+fn unsafe_ensure_class_initialized(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Class::from_mirror(&reference_arg(&args, 1)?).initialize(thread)?;
+    Ok(None)
+}
+
+fn unsafe_should_be_initialized(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(&reference_arg(&args, 1)?);
+    Ok(Some(Value::Int(!class.is_initialized() as i32)))
+}
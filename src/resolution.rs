@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use crate::{class_loader::{ConstantPoolItem, MethodFlags}, exceptions::{JvmResult, Throwable}, invokedynamic::CallSite, method_handles::is_signature_polymorphic, object::{ObjectRef, Value}, runtime_class::{Class, Field, Method}, strings::new_java_string_from_utf16, thread::JavaThread};
+
+/// The result of resolving a symbolic reference, cached per constant pool slot.
+pub enum ResolvedEntry {
+    Class(Arc<Class>),
+    Method(Arc<Method>),
+    Field(ResolvedField),
+    String(ObjectRef),
+    CallSite(Arc<CallSite>),
+    Constant(Value)
+}
+
+/// A field together with the class that declares it, which holds the value of static fields.
+#[derive(Clone)]
+pub struct ResolvedField {
+    pub class: Arc<Class>,
+    index: usize
+}
+
+impl ResolvedField {
+    pub fn field(&self) -> &Field {
+        &self.class.fields[self.index]
+    }
+}
+
+fn external_name(class_name: &str) -> String {
+    class_name.replace('/', ".")
+}
+
+/// Resolves a `CONSTANT_Class` entry of the constant pool of a class (JVMS §5.4.3.1).
+pub fn resolve_class(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<Arc<Class>> {
+    if let Some(ResolvedEntry::Class(class)) = current.cp_cache[index as usize].get() {
+        return Ok(class.clone());
+    }
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    let name = constant_pool.get_string(constant_pool.get_class_info(index));
+    let class = thread.vm.load_class(name)?;
+    Ok(cache_class(current, index, class))
+}
+
+/// Resolves a `CONSTANT_String` entry to an interned `java.lang.String` (JVMS §5.1).
+pub fn resolve_string(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<ObjectRef> {
+    if let Some(ResolvedEntry::String(string)) = current.cp_cache[index as usize].get() {
+        return Ok(string.clone());
+    }
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    // Decoded to UTF-16 from the constant, as a Rust string can't hold unpaired surrogates
+    let code_units = match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPStringInfo { string_index } => constant_pool.get_utf16(*string_index),
+        other => panic!("No string found in constantpool at index: {}", index)
+    };
+    let string = thread.vm.intern_string(new_java_string_from_utf16(thread, &code_units)?);
+    match current.cp_cache[index as usize].get_or_init(|| ResolvedEntry::String(string)) {
+        ResolvedEntry::String(string) => Ok(string.clone()),
+        other => panic!("Constant pool entry {} of {} resolved to something else than a string", index, current.name)
+    }
+}
+
+fn cache_class(current: &Class, index: u16, class: Arc<Class>) -> Arc<Class> {
+    match current.cp_cache[index as usize].get_or_init(|| ResolvedEntry::Class(class)) {
+        ResolvedEntry::Class(class) => class.clone(),
+        other => panic!("Constant pool entry {} of {} resolved to something else than a class", index, current.name)
+    }
+}
+
+fn cache_method(current: &Class, index: u16, method: Arc<Method>) -> Arc<Method> {
+    match current.cp_cache[index as usize].get_or_init(|| ResolvedEntry::Method(method)) {
+        ResolvedEntry::Method(method) => method.clone(),
+        other => panic!("Constant pool entry {} of {} resolved to something else than a method", index, current.name)
+    }
+}
+
+/// Returns the class, name and descriptor of a method or field reference.
+fn get_member_ref(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<(Arc<Class>, String, String)> {
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    let (class_index, name_and_type_index) = match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } |
+        ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } |
+        ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } => (*class_index, *name_and_type_index),
+        other => panic!("No member reference found in constantpool at index: {}", index)
+    };
+    let class = resolve_class(thread, current, class_index)?;
+    let (name_index, descriptor_index) = constant_pool.get_name_and_type(name_and_type_index);
+    let name = constant_pool.get_string(name_index).to_string();
+    let descriptor = constant_pool.get_string(descriptor_index).to_string();
+    Ok((class, name, descriptor))
+}
+
+/// Field resolution of JVMS §5.4.3.2, for a `CONSTANT_Fieldref` entry.
+pub fn resolve_field(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<ResolvedField> {
+    if let Some(ResolvedEntry::Field(field)) = current.cp_cache[index as usize].get() {
+        return Ok(field.clone());
+    }
+    let (class, name, descriptor) = get_member_ref(thread, current, index)?;
+    let (class, index_in_class) = class.find_field(&name, &descriptor)
+        .ok_or_else(|| Throwable::new("java/lang/NoSuchFieldError", name.clone()))?;
+    let field = ResolvedField { class, index: index_in_class };
+    match current.cp_cache[index as usize].get_or_init(|| ResolvedEntry::Field(field)) {
+        ResolvedEntry::Field(field) => Ok(field.clone()),
+        other => panic!("Constant pool entry {} of {} resolved to something else than a field", index, current.name)
+    }
+}
+
+/// Looks up a method in the maximally-specific superinterfaces, preferring the single non-abstract one,
+/// and otherwise taking any of them (steps 3 of JVMS §5.4.3.3 and 5 and 6 of §5.4.3.4).
+fn find_superinterface_method(class: &Class, name: &str, descriptor: &str) -> Option<Arc<Method>> {
+    let superinterfaces = class.all_superinterfaces();
+    let candidates = Class::maximally_specific_methods(&superinterfaces, name, descriptor);
+    let mut implementations = candidates.iter().filter(|method| !method.is_abstract());
+    if let (Some(implementation), None) = (implementations.next(), implementations.next()) {
+        return Some(implementation.clone());
+    }
+    superinterfaces.iter()
+        .filter_map(|interface| interface.find_declared_method(name, descriptor))
+        .find(|method| !method.is_private() && !method.is_static())
+        .cloned()
+}
+
+/// Looks up a method in a class, its superclasses and then its superinterfaces, like method resolution does.
+pub fn find_method(class: &Class, name: &str, descriptor: &str) -> Option<Arc<Method>> {
+    class.find_method_in_superclasses(name, descriptor)
+        .or_else(|| find_superinterface_method(class, name, descriptor))
+}
+
+/// Method resolution of JVMS §5.4.3.3, for a `CONSTANT_Methodref` entry.
+pub fn resolve_method(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<Arc<Method>> {
+    if let Some(ResolvedEntry::Method(method)) = current.cp_cache[index as usize].get() {
+        return Ok(method.clone());
+    }
+    let (class, name, descriptor) = get_member_ref(thread, current, index)?;
+    if class.is_interface() {
+        return Err(Throwable::new("java/lang/IncompatibleClassChangeError",
+            format!("Found interface {}, but class was expected", external_name(&class.name))));
+    }
+    let signature_polymorphic = || class.methods.iter()
+        .find(|method| method.name == name && is_signature_polymorphic(method))
+        .cloned();
+    let method = class.find_method_in_superclasses(&name, &descriptor)
+        .or_else(signature_polymorphic)
+        .or_else(|| find_superinterface_method(&class, &name, &descriptor))
+        .ok_or_else(|| no_such_method(&class, &name, &descriptor))?;
+    Ok(cache_method(current, index, method))
+}
+
+/// Returns the descriptor of a member reference as written at the call site, which for signature
+/// polymorphic methods differs from the descriptor of the resolved method.
+pub fn member_ref_descriptor(current: &Class, index: u16) -> &str {
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPMethodRef { name_and_type_index, .. } |
+        ConstantPoolItem::CPInterfaceMethodRef { name_and_type_index, .. } |
+        ConstantPoolItem::CPFieldRef { name_and_type_index, .. } => constant_pool.get_string(constant_pool.get_name_and_type(*name_and_type_index).1),
+        other => panic!("No member reference found in constantpool at index: {}", index)
+    }
+}
+
+/// Interface method resolution of JVMS §5.4.3.4, for a `CONSTANT_InterfaceMethodref` entry.
+pub fn resolve_interface_method(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<Arc<Method>> {
+    if let Some(ResolvedEntry::Method(method)) = current.cp_cache[index as usize].get() {
+        return Ok(method.clone());
+    }
+    let (class, name, descriptor) = get_member_ref(thread, current, index)?;
+    if !class.is_interface() {
+        return Err(Throwable::new("java/lang/IncompatibleClassChangeError",
+            format!("Found class {}, but interface was expected", external_name(&class.name))));
+    }
+    let object_method = || {
+        let object_class = class.super_class.as_ref()?;
+        object_class.find_declared_method(&name, &descriptor)
+            .filter(|method| method.access_flags.contains(MethodFlags::ACC_PUBLIC) && !method.is_static())
+            .cloned()
+    };
+    let method = class.find_declared_method(&name, &descriptor).cloned()
+        .or_else(object_method)
+        .or_else(|| find_superinterface_method(&class, &name, &descriptor))
+        .ok_or_else(|| no_such_method(&class, &name, &descriptor))?;
+    Ok(cache_method(current, index, method))
+}
+
+/// Resolves either kind of method reference, as both can be used by invokestatic and invokespecial.
+pub fn resolve_any_method(thread: &JavaThread, current: &Class, index: u16) -> JvmResult<Arc<Method>> {
+    let constant_pool = &current.info.as_ref().unwrap().constant_pool;
+    match &constant_pool.constants[index as usize] {
+        ConstantPoolItem::CPInterfaceMethodRef { .. } => resolve_interface_method(thread, current, index),
+        other => resolve_method(thread, current, index)
+    }
+}
+
+fn no_such_method(class: &Class, name: &str, descriptor: &str) -> Throwable {
+    Throwable::new("java/lang/NoSuchMethodError", format!("{}.{}{}", external_name(&class.name), name, descriptor))
+}
@@ -0,0 +1,644 @@
+use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, Arc, Condvar, Mutex, OnceLock, RwLock, Weak}, thread::{self, ThreadId}};
+
+use crate::{class_loader::{ATCode, AttributeInfo, ClassFlags, ClassInfo, ConstantPool, ConstantPoolItem, FieldFlags, FieldInfo, MethodFlags, MethodInfo}, exceptions::{JvmResult, Throwable}, interpreter::Interpreter, object::{Object, ObjectRef, Value, VmData}, resolution::{resolve_string, ResolvedEntry}, thread::JavaThread, verifier, vm::set_module};
+
+enum InitState {
+    Uninitialized,
+    Initializing(ThreadId),
+    Initialized,
+    Erroneous
+}
+
+/// A method of a loaded class, as used by the interpreter.
+pub struct Method {
+    pub class: Weak<Class>,
+    pub class_name: String,
+    // Position in the methods of the ClassInfo
+    pub index: usize,
+    pub name: String,
+    pub descriptor: String,
+    pub access_flags: MethodFlags,
+    // Number of arguments, not counting `this`
+    pub arg_count: usize,
+    pub vtable_index: Option<usize>,
+}
+
+impl Method {
+    pub fn class(&self) -> Arc<Class> {
+        self.class.upgrade().unwrap()
+    }
+
+    pub fn info<'a>(&self, class: &'a Class) -> &'a MethodInfo {
+        &class.info.as_ref().unwrap().methods[self.index]
+    }
+
+    pub fn code<'a>(&self, class: &'a Class) -> Option<&'a ATCode> {
+        self.info(class).get_code()
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access_flags.contains(MethodFlags::ACC_STATIC)
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.access_flags.contains(MethodFlags::ACC_PRIVATE)
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.access_flags.contains(MethodFlags::ACC_ABSTRACT)
+    }
+
+    pub fn is_native(&self) -> bool {
+        self.access_flags.contains(MethodFlags::ACC_NATIVE)
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        self.access_flags.contains(MethodFlags::ACC_SYNCHRONIZED)
+    }
+
+    /// Instance methods that can be overridden get a slot in the vtable.
+    fn is_virtual(&self) -> bool {
+        !self.is_static() && !self.is_private() && !self.name.starts_with('<')
+    }
+
+    /// Returns the return type part of the descriptor, like `V` or `Ljava/lang/String;`.
+    pub fn return_descriptor(&self) -> &str {
+        &self.descriptor[self.descriptor.find(')').unwrap() + 1..]
+    }
+}
+
+/// A field declared by a loaded class.
+pub struct Field {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+    pub access_flags: FieldFlags,
+    // Index into the fields of an object, or into the static values of the class for static fields
+    pub slot: usize,
+}
+
+impl Field {
+    pub fn is_static(&self) -> bool {
+        self.access_flags.contains(FieldFlags::ACC_STATIC)
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.access_flags.contains(FieldFlags::ACC_FINAL)
+    }
+}
+
+/// A class as loaded into the VM, with everything needed to run its code.
+///
+/// Array classes have no ClassInfo, their methods all come from `java/lang/Object`.
+pub struct Class {
+    pub name: String,
+    pub info: Option<ClassInfo>,
+    pub access_flags: ClassFlags,
+    pub super_class: Option<Arc<Class>>,
+    pub interfaces: Vec<Arc<Class>>,
+    // The element type of array classes, like `int` for `[I` or `[I` for `[[I`
+    pub component: Option<Arc<Class>>,
+    pub methods: Vec<Arc<Method>>,
+    pub fields: Vec<Field>,
+    // Zero values of all instance fields, those of the superclasses coming first
+    pub instance_field_defaults: Vec<Value>,
+    pub static_values: Mutex<Vec<Value>>,
+    // None marks a conflict between default methods, calling it is an IncompatibleClassChangeError
+    pub vtable: Vec<Option<Arc<Method>>>,
+    vtable_indices: HashMap<(String, String), usize>,
+    // For every implemented interface: the vtable index of each of its methods
+    itable: HashMap<String, Vec<Option<usize>>>,
+    pub(crate) cp_cache: Vec<OnceLock<ResolvedEntry>>,
+    init_state: Mutex<InitState>,
+    init_done: Condvar,
+    // The java.lang.Class object, created when Java code first asks for it
+    pub(crate) mirror: OnceLock<ObjectRef>,
+    // Results of earlier subtype checks against this class, by the name of the other class
+    subtype_cache: RwLock<HashMap<String, bool>>,
+    // Set for classes that don't come from the boot classpath, until their code passed the verifier
+    pub(crate) verification_pending: AtomicBool,
+}
+
+/// Gives static fields with a ConstantValue attribute their value, as part of preparation (JVMS §5.4.2).
+/// String constants need the heap, and are only set when the class gets initialized (see `set_string_constants`).
+fn constant_field_value(field_info: &FieldInfo, constant_pool: &ConstantPool) -> Option<Value> {
+    match &constant_pool.constants[field_info.get_constant_value()? as usize] {
+        ConstantPoolItem::CPIntegerInfo { value } => Some(Value::Int(*value)),
+        ConstantPoolItem::CPFloatInfo { value } => Some(Value::Float(*value)),
+        ConstantPoolItem::CPLongInfo { value } => Some(Value::Long(*value)),
+        ConstantPoolItem::CPDoubleInfo { value } => Some(Value::Double(*value)),
+        other => None
+    }
+}
+
+/// Returns the name of a primitive type from its descriptor, like `int` for `I`.
+pub fn primitive_name(descriptor: &str) -> &'static str {
+    match descriptor {
+        "Z" => "boolean",
+        "B" => "byte",
+        "C" => "char",
+        "S" => "short",
+        "I" => "int",
+        "J" => "long",
+        "F" => "float",
+        "D" => "double",
+        "V" => "void",
+        other => panic!("{} is not a primitive type", other)
+    }
+}
+
+fn package_name(class_name: &str) -> &str {
+    match class_name.rfind('/') {
+        Some(index) => &class_name[..index],
+        None => ""
+    }
+}
+
+impl Class {
+    /// Creates a class from its parsed class file. The superclass and interfaces need to be loaded already.
+    pub fn new(info: ClassInfo, super_class: Option<Arc<Class>>, interfaces: Vec<Arc<Class>>) -> Arc<Class> {
+        let name = info.constant_pool.get_string(info.constant_pool.get_class_info(info.this_class)).to_string();
+        let access_flags = info.access_flags;
+        let is_interface = access_flags.contains(ClassFlags::ACC_INTERFACE);
+        let cp_cache = (0..info.constant_pool.constants.len()).map(|_| OnceLock::new()).collect();
+        Arc::new_cyclic(|weak_class| {
+            let mut vtable = Vec::new();
+            let mut vtable_indices = HashMap::new();
+            if let (false, Some(super_class)) = (is_interface, &super_class) {
+                vtable = super_class.vtable.clone();
+                vtable_indices = super_class.vtable_indices.clone();
+            }
+
+            let mut methods = Vec::with_capacity(info.methods.len());
+            for (index, method_info) in info.methods.iter().enumerate() {
+                let mut method = Method {
+                    class: weak_class.clone(),
+                    class_name: name.clone(),
+                    index,
+                    name: method_info.get_name(&info.constant_pool).to_string(),
+                    descriptor: info.constant_pool.get_string(method_info.descriptor_index).to_string(),
+                    access_flags: method_info.access_flags,
+                    arg_count: method_info.args.len(),
+                    vtable_index: None,
+                };
+                if !is_interface && method.is_virtual() {
+                    let key = (method.name.clone(), method.descriptor.clone());
+                    let overridden = vtable_indices.get(&key).copied()
+                        .filter(|index| Self::can_override(&name, &vtable[*index]));
+                    let vtable_index = overridden.unwrap_or(vtable.len());
+                    if vtable_index == vtable.len() {
+                        vtable.push(None);
+                    }
+                    vtable_indices.insert(key, vtable_index);
+                    method.vtable_index = Some(vtable_index);
+                }
+                let method = Arc::new(method);
+                if let Some(vtable_index) = method.vtable_index {
+                    vtable[vtable_index] = Some(method.clone());
+                }
+                methods.push(method);
+            }
+
+            let mut instance_field_defaults = match &super_class {
+                Some(super_class) => super_class.instance_field_defaults.clone(),
+                None => Vec::new()
+            };
+            let mut static_values = Vec::new();
+            let mut fields = Vec::with_capacity(info.fields.len());
+            for field_info in &info.fields {
+                let descriptor = info.constant_pool.get_string(field_info.descriptor_index).to_string();
+                let default = Value::default_for(&descriptor);
+                let is_static = field_info.access_flags.contains(FieldFlags::ACC_STATIC);
+                let slot = if is_static {
+                    static_values.push(constant_field_value(field_info, &info.constant_pool).unwrap_or(default));
+                    static_values.len() - 1
+                } else {
+                    instance_field_defaults.push(default);
+                    instance_field_defaults.len() - 1
+                };
+                fields.push(Field {
+                    class_name: name.clone(),
+                    name: field_info.get_name(&info.constant_pool).to_string(),
+                    descriptor,
+                    access_flags: field_info.access_flags,
+                    slot
+                });
+            }
+
+            let mut class = Class {
+                name,
+                info: Some(info),
+                access_flags,
+                super_class,
+                interfaces,
+                component: None,
+                methods,
+                fields,
+                instance_field_defaults,
+                static_values: Mutex::new(static_values),
+                vtable,
+                vtable_indices,
+                itable: HashMap::new(),
+                cp_cache,
+                init_state: Mutex::new(InitState::Uninitialized),
+                init_done: Condvar::new(),
+                mirror: OnceLock::new(),
+                subtype_cache: RwLock::new(HashMap::new()),
+                verification_pending: AtomicBool::new(false),
+            };
+            if !is_interface {
+                class.link_interface_methods();
+            }
+            class
+        })
+    }
+
+    /// Creates an array class, like `[I` or `[Ljava/lang/String;`, from the class of its elements.
+    pub fn new_array(name: &str, component: Arc<Class>, object_class: Arc<Class>, interfaces: Vec<Arc<Class>>) -> Arc<Class> {
+        let mut class = Class {
+            name: String::from(name),
+            info: None,
+            access_flags: ClassFlags::ACC_PUBLIC | ClassFlags::ACC_FINAL | ClassFlags::ACC_ABSTRACT,
+            vtable: object_class.vtable.clone(),
+            vtable_indices: object_class.vtable_indices.clone(),
+            super_class: Some(object_class),
+            interfaces,
+            component: Some(component),
+            methods: Vec::new(),
+            fields: Vec::new(),
+            instance_field_defaults: Vec::new(),
+            static_values: Mutex::new(Vec::new()),
+            itable: HashMap::new(),
+            cp_cache: Vec::new(),
+            init_state: Mutex::new(InitState::Initialized),
+            init_done: Condvar::new(),
+            mirror: OnceLock::new(),
+            subtype_cache: RwLock::new(HashMap::new()),
+            verification_pending: AtomicBool::new(false),
+        };
+        class.link_interface_methods();
+        Arc::new(class)
+    }
+
+    /// Creates the class of a primitive type, like `int` or `void`. It has no superclass and no members.
+    pub fn new_primitive(name: &str) -> Arc<Class> {
+        Arc::new(Class {
+            name: String::from(name),
+            info: None,
+            access_flags: ClassFlags::ACC_PUBLIC | ClassFlags::ACC_FINAL | ClassFlags::ACC_ABSTRACT,
+            super_class: None,
+            interfaces: Vec::new(),
+            component: None,
+            methods: Vec::new(),
+            fields: Vec::new(),
+            instance_field_defaults: Vec::new(),
+            static_values: Mutex::new(Vec::new()),
+            vtable: Vec::new(),
+            vtable_indices: HashMap::new(),
+            itable: HashMap::new(),
+            cp_cache: Vec::new(),
+            init_state: Mutex::new(InitState::Initialized),
+            init_done: Condvar::new(),
+            mirror: OnceLock::new(),
+            subtype_cache: RwLock::new(HashMap::new()),
+            verification_pending: AtomicBool::new(false),
+        })
+    }
+
+    /// A method overrides another one when that one is accessible from the overriding class (JVMS §5.4.5).
+    fn can_override(class_name: &str, overridden: &Option<Arc<Method>>) -> bool {
+        match overridden {
+            Some(method) if method.access_flags.intersects(MethodFlags::ACC_PUBLIC | MethodFlags::ACC_PROTECTED) => true,
+            Some(method) => package_name(&method.class_name) == package_name(class_name),
+            None => true
+        }
+    }
+
+    /// Gives every method of the implemented interfaces a vtable slot, filled with the selected
+    /// default method when the class and its superclasses don't implement it. Then builds the itable.
+    fn link_interface_methods(&mut self) {
+        let superinterfaces = self.all_superinterfaces();
+        for interface in &superinterfaces {
+            for method in interface.methods.iter().filter(|method| method.is_virtual()) {
+                let key = (method.name.clone(), method.descriptor.clone());
+                let index = match self.vtable_indices.get(&key) {
+                    // Methods declared in classes always win over default methods.
+                    // Our own methods can't be upgraded to their class yet, but are class methods anyway.
+                    Some(index) if self.vtable[*index].as_ref().is_some_and(|method| {
+                        method.class_name == self.name || !method.class().is_interface()
+                    }) => continue,
+                    Some(index) => *index,
+                    None => {
+                        self.vtable.push(None);
+                        self.vtable_indices.insert(key, self.vtable.len() - 1);
+                        self.vtable.len() - 1
+                    }
+                };
+                let candidates = Self::maximally_specific_methods(&superinterfaces, &method.name, &method.descriptor);
+                let mut implementations = candidates.iter().filter(|method| !method.is_abstract());
+                self.vtable[index] = match (implementations.next(), implementations.next()) {
+                    (Some(implementation), None) => Some(implementation.clone()),
+                    (Some(_), Some(_)) => None,
+                    (None, _) => candidates.first().cloned()
+                };
+            }
+        }
+
+        for interface in &superinterfaces {
+            let slots = interface.methods.iter()
+                .map(|method| {
+                    if method.is_virtual() {
+                        self.vtable_indices.get(&(method.name.clone(), method.descriptor.clone())).copied()
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            self.itable.insert(interface.name.clone(), slots);
+        }
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.access_flags.contains(ClassFlags::ACC_INTERFACE)
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        self.info.is_none() && self.super_class.is_none()
+    }
+
+    /// Returns the field descriptor for this class, like `I`, `Ljava/lang/String;` or `[I`.
+    pub fn descriptor(&self) -> String {
+        match self.name.as_str() {
+            "boolean" => String::from("Z"),
+            "byte" => String::from("B"),
+            "char" => String::from("C"),
+            "short" => String::from("S"),
+            "int" => String::from("I"),
+            "long" => String::from("J"),
+            "float" => String::from("F"),
+            "double" => String::from("D"),
+            "void" => String::from("V"),
+            name if name.starts_with('[') => String::from(name),
+            name => format!("L{};", name)
+        }
+    }
+
+    /// The access flags as Class.getModifiers returns them, without ACC_SUPER.
+    pub fn modifiers(&self) -> i32 {
+        (self.access_flags - ClassFlags::ACC_SUPER).bits() as i32
+    }
+
+    /// The name that Class.getSimpleName gives: the inner name that the InnerClasses attribute records for a nested or
+    /// local class, or the name without its package.
+    pub fn simple_name(&self) -> &str {
+        let inner_name = self.info.as_ref().and_then(|info| {
+            let constant_pool = &info.constant_pool;
+            info.attributes.iter().find_map(|attribute| match attribute {
+                AttributeInfo::Unknown(unknown) if constant_pool.get_string(unknown.name_index) == "InnerClasses" => {
+                    let data = &unknown.info;
+                    let read_u16 = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]);
+                    let count = if data.len() >= 2 { read_u16(0) as usize } else { 0 };
+                    (0..count).map(|entry| 2 + 8 * entry).filter(|at| at + 8 <= data.len())
+                        .find(|at| constant_pool.get_string(constant_pool.get_class_info(read_u16(*at))) == self.name)
+                        // Anonymous classes have no inner name
+                        .map(|at| if read_u16(at + 4) == 0 { "" } else { constant_pool.get_string(read_u16(at + 4)) })
+                },
+                other => None
+            })
+        });
+        inner_name.unwrap_or_else(|| self.name.rsplit('/').next().unwrap())
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.name.starts_with('[')
+    }
+
+    pub fn package_name(&self) -> &str {
+        package_name(&self.name)
+    }
+
+    pub fn find_declared_method(&self, name: &str, descriptor: &str) -> Option<&Arc<Method>> {
+        self.methods.iter().find(|method| method.name == name && method.descriptor == descriptor)
+    }
+
+    /// Looks for a method in the class and its superclasses, not its interfaces.
+    pub fn find_method_in_superclasses(&self, name: &str, descriptor: &str) -> Option<Arc<Method>> {
+        match self.find_declared_method(name, descriptor) {
+            Some(method) => Some(method.clone()),
+            None => self.super_class.as_ref()?.find_method_in_superclasses(name, descriptor)
+        }
+    }
+
+    /// Field lookup of JVMS §5.4.3.2: the class itself, then its superinterfaces, then its superclasses.
+    /// Returns the class that declares the field, and the position of the field in that class.
+    pub fn find_field(self: &Arc<Self>, name: &str, descriptor: &str) -> Option<(Arc<Class>, usize)> {
+        if let Some(index) = self.fields.iter().position(|field| field.name == name && field.descriptor == descriptor) {
+            return Some((self.clone(), index));
+        }
+        self.interfaces.iter()
+            .find_map(|interface| interface.find_field(name, descriptor))
+            .or_else(|| self.super_class.as_ref()?.find_field(name, descriptor))
+    }
+
+    /// Returns the slot of an instance field by name, for code that knows the layout of a JDK class.
+    pub fn instance_field_slot(&self, name: &str, descriptor: &str) -> Option<usize> {
+        match self.fields.iter().find(|field| !field.is_static() && field.name == name && field.descriptor == descriptor) {
+            Some(field) => Some(field.slot),
+            None => self.super_class.as_ref()?.instance_field_slot(name, descriptor)
+        }
+    }
+
+    pub fn vtable_index(&self, name: &str, descriptor: &str) -> Option<usize> {
+        self.vtable_indices.get(&(name.to_string(), descriptor.to_string())).copied()
+    }
+
+    /// Returns the vtable slot that implements a method of an interface the class implements.
+    pub fn itable_slot(&self, interface_name: &str, method_index: usize) -> Option<Option<usize>> {
+        self.itable.get(interface_name).map(|slots| slots[method_index])
+    }
+
+    /// All interfaces implemented by this class, directly or through superclasses and superinterfaces.
+    pub fn all_superinterfaces(&self) -> Vec<Arc<Class>> {
+        let mut result: Vec<Arc<Class>> = Vec::new();
+        let mut pending: Vec<Arc<Class>> = self.interfaces.clone();
+        let mut super_class = self.super_class.clone();
+        while let Some(class) = super_class {
+            pending.extend(class.interfaces.iter().cloned());
+            super_class = class.super_class.clone();
+        }
+        while let Some(interface) = pending.pop() {
+            if result.iter().all(|known| known.name != interface.name) {
+                pending.extend(interface.interfaces.iter().cloned());
+                result.push(interface);
+            }
+        }
+        result
+    }
+
+    /// The maximally-specific superinterface methods of JVMS §5.4.3.3: the candidates for which no
+    /// other candidate is declared in a subinterface.
+    pub fn maximally_specific_methods(superinterfaces: &[Arc<Class>], name: &str, descriptor: &str) -> Vec<Arc<Method>> {
+        let candidates: Vec<(&Arc<Class>, &Arc<Method>)> = superinterfaces.iter()
+            .filter_map(|interface| {
+                interface.find_declared_method(name, descriptor)
+                    .filter(|method| !method.is_static() && !method.is_private())
+                    .map(|method| (interface, method))
+            })
+            .collect();
+        candidates.iter()
+            .filter(|(interface, method)| {
+                !candidates.iter().any(|(other, _)| other.name != interface.name && other.is_subclass_of(&interface.name))
+            })
+            .map(|(interface, method)| (*method).clone())
+            .collect()
+    }
+
+    /// Whether this class is the given class, or a subclass or implementation of it.
+    pub fn is_subclass_of(&self, class_name: &str) -> bool {
+        if self.name == class_name {
+            return true;
+        }
+        if self.interfaces.iter().any(|interface| interface.is_subclass_of(class_name)) {
+            return true;
+        }
+        self.super_class.as_ref().is_some_and(|super_class| super_class.is_subclass_of(class_name))
+    }
+
+    /// Whether a value of this class can be used where the other class is expected, following the rules of
+    /// checkcast (JVMS §6.5). Arrays are covariant, and primitive types are only assignable to themselves.
+    pub fn is_assignable_to(&self, other: &Class) -> bool {
+        if self.name == other.name {
+            return true;
+        }
+        if let Some(result) = self.subtype_cache.read().unwrap().get(&other.name) {
+            return *result;
+        }
+        let result = match (&self.component, &other.component) {
+            // Primitive components have no subtypes, so these have to be the same
+            (Some(component), Some(other_component)) if !component.is_primitive() && !other_component.is_primitive() => {
+                component.is_assignable_to(other_component)
+            },
+            (Some(_), Some(_)) => false,
+            // Arrays are Objects, Cloneable and Serializable, which are their superclass and interfaces
+            _ => self.is_subclass_of(&other.name)
+        };
+        self.subtype_cache.write().unwrap().insert(other.name.clone(), result);
+        result
+    }
+
+    /// Runs the class initialization procedure of JVMS §5.5.
+    pub fn initialize(self: &Arc<Self>, thread: &JavaThread) -> JvmResult<()> {
+        self.link(thread)?;
+        {
+            let mut state = self.init_state.lock().unwrap();
+            loop {
+                match *state {
+                    InitState::Initialized => return Ok(()),
+                    InitState::Initializing(thread_id) if thread_id == thread::current().id() => return Ok(()),
+                    InitState::Initializing(_) => state = self.init_done.wait(state).unwrap(),
+                    InitState::Erroneous => {
+                        return Err(Throwable::new("java/lang/NoClassDefFoundError", format!("Could not initialize class {}", self.name.replace('/', "."))));
+                    },
+                    InitState::Uninitialized => break
+                }
+            }
+            *state = InitState::Initializing(thread::current().id());
+        }
+
+        let result = self.run_initializers(thread);
+        let mut state = self.init_state.lock().unwrap();
+        *state = if result.is_ok() { InitState::Initialized } else { InitState::Erroneous };
+        self.init_done.notify_all();
+        result
+    }
+
+    /// Verifies the code of the class the first time it gets initialized, like HotSpot does when linking it.
+    /// A class that fails verification throws the VerifyError again on every later attempt.
+    fn link(&self, thread: &JavaThread) -> JvmResult<()> {
+        if self.verification_pending.load(Ordering::Acquire) {
+            verifier::verify_class(&thread.vm, self)?;
+            self.verification_pending.store(false, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    fn run_initializers(self: &Arc<Self>, thread: &JavaThread) -> JvmResult<()> {
+        if !self.is_interface() {
+            if let Some(super_class) = &self.super_class {
+                super_class.initialize(thread)?;
+            }
+            // Interfaces with default methods get initialized together with their implementations
+            for interface in &self.interfaces {
+                if interface.methods.iter().any(|method| method.is_virtual() && !method.is_abstract()) {
+                    interface.initialize(thread)?;
+                }
+            }
+        }
+        self.set_string_constants(thread)?;
+        match self.find_declared_method("<clinit>", "()V") {
+            Some(class_initializer) => {
+                Interpreter::invoke(thread, class_initializer, Vec::new())
+                    .map(|_| ())
+                    .map_err(|throwable| {
+                        if throwable.is_error(&thread.vm) {
+                            throwable
+                        } else {
+                            Throwable::caused_by("java/lang/ExceptionInInitializerError", throwable)
+                        }
+                    })
+            },
+            None => Ok(())
+        }
+    }
+
+    /// Gives static fields with a String ConstantValue attribute their value, before the class initializer runs.
+    fn set_string_constants(&self, thread: &JavaThread) -> JvmResult<()> {
+        // Array classes have no fields
+        let info = match &self.info {
+            Some(info) => info,
+            None => return Ok(())
+        };
+        for (field_info, field) in info.fields.iter().zip(&self.fields) {
+            let index = match field_info.get_constant_value() {
+                Some(index) if field.is_static() => index,
+                other => continue
+            };
+            if let ConstantPoolItem::CPStringInfo { .. } = info.constant_pool.constants[index as usize] {
+                let string = resolve_string(thread, self, index)?;
+                self.static_values.lock().unwrap()[field.slot] = Value::Reference(Some(string));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the `java.lang.Class` object of this class, creating it on first use.
+    pub fn mirror(self: &Arc<Self>, thread: &JavaThread) -> JvmResult<ObjectRef> {
+        if let Some(mirror) = self.mirror.get() {
+            return Ok(mirror.clone());
+        }
+        let mirror_class = thread.vm.load_class("java/lang/Class")?;
+        let mirror = Object::new_instance(mirror_class.clone());
+        *mirror.vm_data.lock().unwrap() = Some(VmData::Class(self.clone()));
+        // JDK 9 and later keep the component type of array classes in a field
+        if let (true, Some(slot)) = (self.is_array(), mirror_class.instance_field_slot("componentType", "Ljava/lang/Class;")) {
+            let component_mirror = thread.vm.class_for_descriptor(&self.name[1..])?.mirror(thread)?;
+            mirror.fields()[slot] = Value::Reference(Some(component_mirror));
+        }
+        if let Some(module) = thread.vm.unnamed_module.get() {
+            set_module(&mirror, module);
+        }
+        Ok(self.mirror.get_or_init(|| mirror).clone())
+    }
+
+    /// Returns the class that a `java.lang.Class` object stands for.
+    pub fn from_mirror(mirror: &ObjectRef) -> Arc<Class> {
+        match &*mirror.vm_data.lock().unwrap() {
+            Some(VmData::Class(class)) => class.clone(),
+            other => panic!("Object of class {} is not a class mirror", mirror.class.name)
+        }
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        matches!(*self.init_state.lock().unwrap(), InitState::Initialized)
+    }
+}
@@ -0,0 +1,63 @@
+use crate::{exceptions::JvmResult, object::{ArrayData, Object, ObjectRef, Value}, thread::JavaThread};
+
+// Values of String.coder on JDK 9 and later
+const LATIN1: i32 = 0;
+const UTF16: i32  = 1;
+
+/// Creates a `java.lang.String` from UTF-16 code units, in the layout of the JDK in use:
+/// a `char[]` on JDK 8, or a `byte[]` and a coder on JDK 9 and later (compact strings).
+pub fn new_java_string_from_utf16(thread: &JavaThread, chars: &[u16]) -> JvmResult<ObjectRef> {
+    let string_class = thread.vm.load_class("java/lang/String")?;
+    string_class.initialize(thread)?;
+    let string = Object::new_instance(string_class.clone());
+    let value_slot = string_class.instance_field_slot("value", "[C");
+    let (value, coder) = match value_slot {
+        Some(_) => (ArrayData::Char(chars.to_vec()), None),
+        None if chars.iter().all(|char| *char <= 0xFF) => {
+            (ArrayData::Byte(chars.iter().map(|char| *char as i8).collect()), Some(LATIN1))
+        },
+        // The byte order of UTF-16 strings is that of the platform (see StringUTF16.isBigEndian)
+        None => (ArrayData::Byte(chars.iter().flat_map(|char| char.to_ne_bytes()).map(|byte| byte as i8).collect()), Some(UTF16))
+    };
+    let array_class = thread.vm.load_class(if coder.is_some() { "[B" } else { "[C" })?;
+    let value = Object::new_array(array_class, value);
+    let mut fields = string.fields();
+    match coder {
+        Some(coder) => {
+            fields[string_class.instance_field_slot("value", "[B").unwrap()] = Value::Reference(Some(value));
+            fields[string_class.instance_field_slot("coder", "B").unwrap()] = Value::Int(coder);
+        },
+        None => fields[value_slot.unwrap()] = Value::Reference(Some(value))
+    }
+    drop(fields);
+    Ok(string)
+}
+
+/// Creates a `java.lang.String` with the same text as a Rust string.
+pub fn new_java_string(thread: &JavaThread, text: &str) -> JvmResult<ObjectRef> {
+    new_java_string_from_utf16(thread, &text.encode_utf16().collect::<Vec<u16>>())
+}
+
+/// Returns the UTF-16 code units of a `java.lang.String`.
+pub fn to_utf16(string: &ObjectRef) -> Vec<u16> {
+    let value_slot = string.class.instance_field_slot("value", "[C")
+        .or_else(|| string.class.instance_field_slot("value", "[B"))
+        .unwrap();
+    let fields = string.fields();
+    let value = fields[value_slot].as_reference().cloned().unwrap();
+    let coder = string.class.instance_field_slot("coder", "B").map(|slot| fields[slot].as_int());
+    drop(fields);
+    match (&*value.array(), coder) {
+        (ArrayData::Char(chars), None) => chars.clone(),
+        (ArrayData::Byte(bytes), Some(LATIN1)) => bytes.iter().map(|byte| *byte as u8 as u16).collect(),
+        (ArrayData::Byte(bytes), Some(UTF16)) => {
+            bytes.chunks_exact(2).map(|pair| u16::from_ne_bytes([pair[0] as u8, pair[1] as u8])).collect()
+        },
+        other => panic!("Unexpected layout of java.lang.String")
+    }
+}
+
+/// Converts a `java.lang.String` to a Rust string, replacing unpaired surrogates.
+pub fn to_rust_string(string: &ObjectRef) -> String {
+    String::from_utf16_lossy(&to_utf16(string))
+}
@@ -0,0 +1,477 @@
+use crate::{class_loader::{ClassFlags, FieldFlags, MethodFlags, MAGIC_NUMBER}, class_writer::ClassWriter, constant_pool_builder::ConstantPoolBuilder};
+
+/// Shown instead of a file path for the classes of the stub library.
+pub const STUB_LIBRARY_PATH: &str = "<stub library>";
+
+// Java 8, the oldest version that has everything the stub classes use
+const MAJOR_VERSION: u16 = 52;
+
+const NATIVE: MethodFlags = MethodFlags::ACC_PUBLIC.union(MethodFlags::ACC_NATIVE);
+const STATIC_NATIVE: MethodFlags = NATIVE.union(MethodFlags::ACC_STATIC);
+const PRIVATE_STATIC_NATIVE: MethodFlags = MethodFlags::ACC_PRIVATE.union(MethodFlags::ACC_STATIC).union(MethodFlags::ACC_NATIVE);
+const PROTECTED_NATIVE: MethodFlags = MethodFlags::ACC_PROTECTED.union(MethodFlags::ACC_NATIVE);
+const ABSTRACT: MethodFlags = MethodFlags::ACC_PUBLIC.union(MethodFlags::ACC_ABSTRACT);
+
+const PUBLIC_CLASS: ClassFlags = ClassFlags::ACC_PUBLIC.union(ClassFlags::ACC_SUPER);
+const FINAL_CLASS: ClassFlags = PUBLIC_CLASS.union(ClassFlags::ACC_FINAL);
+const ABSTRACT_CLASS: ClassFlags = PUBLIC_CLASS.union(ClassFlags::ACC_ABSTRACT);
+const INTERFACE: ClassFlags = ClassFlags::ACC_PUBLIC.union(ClassFlags::ACC_INTERFACE).union(ClassFlags::ACC_ABSTRACT);
+
+const PRIVATE_FIELD: FieldFlags = FieldFlags::ACC_PRIVATE;
+const STATIC_FINAL_FIELD: FieldFlags = FieldFlags::ACC_PUBLIC.union(FieldFlags::ACC_STATIC).union(FieldFlags::ACC_FINAL);
+const PRIVATE_STATIC_FIELD: FieldFlags = FieldFlags::ACC_PRIVATE.union(FieldFlags::ACC_STATIC);
+
+/// The throwables of the stub library with their superclass.
+/// All of them have the constructors of Throwable, and no other methods.
+pub const STUB_THROWABLES: [(&str, &str); 41] = [
+    ("java/lang/Exception",                       "java/lang/Throwable"),
+    ("java/lang/Error",                           "java/lang/Throwable"),
+    ("java/lang/RuntimeException",                "java/lang/Exception"),
+    ("java/lang/ArithmeticException",             "java/lang/RuntimeException"),
+    ("java/lang/ArrayStoreException",             "java/lang/RuntimeException"),
+    ("java/lang/ClassCastException",              "java/lang/RuntimeException"),
+    ("java/lang/IllegalArgumentException",        "java/lang/RuntimeException"),
+    ("java/lang/NumberFormatException",           "java/lang/IllegalArgumentException"),
+    ("java/lang/IllegalMonitorStateException",    "java/lang/RuntimeException"),
+    ("java/lang/IllegalStateException",           "java/lang/RuntimeException"),
+    ("java/lang/IllegalThreadStateException",     "java/lang/IllegalArgumentException"),
+    ("java/lang/IndexOutOfBoundsException",       "java/lang/RuntimeException"),
+    ("java/lang/ArrayIndexOutOfBoundsException",  "java/lang/IndexOutOfBoundsException"),
+    ("java/lang/StringIndexOutOfBoundsException", "java/lang/IndexOutOfBoundsException"),
+    ("java/lang/NegativeArraySizeException",      "java/lang/RuntimeException"),
+    ("java/lang/NullPointerException",            "java/lang/RuntimeException"),
+    ("java/lang/UnsupportedOperationException",   "java/lang/RuntimeException"),
+    ("java/lang/CloneNotSupportedException",      "java/lang/Exception"),
+    ("java/lang/InterruptedException",            "java/lang/Exception"),
+    ("java/lang/ReflectiveOperationException",    "java/lang/Exception"),
+    ("java/lang/ClassNotFoundException",          "java/lang/ReflectiveOperationException"),
+    ("java/io/IOException",                       "java/lang/Exception"),
+    ("java/lang/AssertionError",                  "java/lang/Error"),
+    ("java/lang/LinkageError",                    "java/lang/Error"),
+    ("java/lang/BootstrapMethodError",            "java/lang/LinkageError"),
+    ("java/lang/ClassFormatError",                "java/lang/LinkageError"),
+    ("java/lang/ExceptionInInitializerError",     "java/lang/LinkageError"),
+    ("java/lang/IncompatibleClassChangeError",    "java/lang/LinkageError"),
+    ("java/lang/AbstractMethodError",             "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/IllegalAccessError",              "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/InstantiationError",              "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/NoSuchFieldError",                "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/NoSuchMethodError",               "java/lang/IncompatibleClassChangeError"),
+    ("java/lang/NoClassDefFoundError",            "java/lang/LinkageError"),
+    ("java/lang/UnsatisfiedLinkError",            "java/lang/LinkageError"),
+    ("java/lang/VerifyError",                     "java/lang/LinkageError"),
+    ("java/lang/VirtualMachineError",             "java/lang/Error"),
+    ("java/lang/InternalError",                   "java/lang/VirtualMachineError"),
+    ("java/lang/OutOfMemoryError",                "java/lang/VirtualMachineError"),
+    ("java/lang/StackOverflowError",              "java/lang/VirtualMachineError"),
+    ("java/lang/UnknownError",                    "java/lang/VirtualMachineError"),
+];
+
+/// The constructors of every throwable. AssertionError has an extra one, for `assert` statements with a message.
+pub const THROWABLE_CONSTRUCTORS: [&str; 4] = [
+    "()V",
+    "(Ljava/lang/String;)V",
+    "(Ljava/lang/String;Ljava/lang/Throwable;)V",
+    "(Ljava/lang/Throwable;)V",
+];
+
+/// A class of the stub library. Its methods are native (see stub_natives) or abstract, so it has no code.
+/// The VM also builds the classes of lambdas this way.
+pub struct StubClass {
+    access_flags: ClassFlags,
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    fields: Vec<(FieldFlags, String, String)>,
+    methods: Vec<(MethodFlags, String, String)>
+}
+
+impl StubClass {
+    pub fn new(access_flags: ClassFlags, super_class: Option<&str>, interfaces: &[&str]) -> StubClass {
+        StubClass {
+            access_flags,
+            super_class: super_class.map(String::from),
+            interfaces: interfaces.iter().map(|interface| interface.to_string()).collect(),
+            fields: Vec::new(),
+            methods: Vec::new()
+        }
+    }
+
+    pub fn field(mut self, access_flags: FieldFlags, name: &str, descriptor: &str) -> StubClass {
+        self.fields.push((access_flags, name.to_string(), descriptor.to_string()));
+        self
+    }
+
+    /// Adds methods that share their access flags, given by name and descriptor.
+    pub fn methods(mut self, access_flags: MethodFlags, methods: &[(&str, &str)]) -> StubClass {
+        self.methods.extend(methods.iter().map(|(name, descriptor)| (access_flags, name.to_string(), descriptor.to_string())));
+        self
+    }
+
+    /// Encodes the class in the class file format, so it gets loaded like any other class.
+    /// Fails when its names don't fit in the constant pool.
+    pub fn to_class_file(&self, name: &str) -> Result<Vec<u8>, String> {
+        let mut constant_pool = ConstantPoolBuilder::new();
+        let this_class = constant_pool.add_class(name)?;
+        let super_class = match &self.super_class {
+            Some(super_class) => constant_pool.add_class(super_class)?,
+            None => 0
+        };
+        let mut body = Vec::new();
+        push_u16(&mut body, self.access_flags.bits());
+        push_u16(&mut body, this_class);
+        push_u16(&mut body, super_class);
+        push_u16(&mut body, self.interfaces.len() as u16);
+        for interface in &self.interfaces {
+            push_u16(&mut body, constant_pool.add_class(interface)?);
+        }
+        let members = self.fields.iter().map(|(access_flags, name, descriptor)| (access_flags.bits(), name, descriptor));
+        Self::write_members(&mut body, &mut constant_pool, members.collect())?;
+        let members = self.methods.iter().map(|(access_flags, name, descriptor)| (access_flags.bits(), name, descriptor));
+        Self::write_members(&mut body, &mut constant_pool, members.collect())?;
+        // No class attributes
+        push_u16(&mut body, 0);
+
+        let mut bytes = MAGIC_NUMBER.to_be_bytes().to_vec();
+        push_u16(&mut bytes, 0);
+        push_u16(&mut bytes, MAJOR_VERSION);
+        ClassWriter::write_constant_pool(&mut bytes, constant_pool.constant_pool());
+        bytes.extend(body);
+        Ok(bytes)
+    }
+
+    /// Writes fields or methods, which have the same layout. None of them has attributes.
+    fn write_members(bytes: &mut Vec<u8>, constant_pool: &mut ConstantPoolBuilder, members: Vec<(u16, &String, &String)>) -> Result<(), String> {
+        push_u16(bytes, members.len() as u16);
+        for (access_flags, name, descriptor) in members {
+            push_u16(bytes, access_flags);
+            push_u16(bytes, constant_pool.add_utf8(name)?);
+            push_u16(bytes, constant_pool.add_utf8(descriptor)?);
+            push_u16(bytes, 0);
+        }
+        Ok(())
+    }
+}
+
+fn push_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend(value.to_be_bytes());
+}
+
+fn throwable_class(super_class: &'static str) -> StubClass {
+    let constructors: Vec<(&str, &str)> = THROWABLE_CONSTRUCTORS.iter().map(|descriptor| ("<init>", *descriptor)).collect();
+    StubClass::new(PUBLIC_CLASS, Some(super_class), &[]).methods(NATIVE, &constructors)
+}
+
+fn find_stub_class(name: &str) -> Option<StubClass> {
+    let object = Some("java/lang/Object");
+    let class = match name {
+        "java/lang/Object" => StubClass::new(PUBLIC_CLASS, None, &[])
+            .methods(NATIVE, &[
+                ("<init>", "()V"),
+                ("equals", "(Ljava/lang/Object;)Z"),
+                ("hashCode", "()I"),
+                ("toString", "()Ljava/lang/String;"),
+                ("getClass", "()Ljava/lang/Class;"),
+                ("notify", "()V"),
+                ("notifyAll", "()V"),
+                ("wait", "()V"),
+                ("wait", "(J)V"),
+            ])
+            .methods(PROTECTED_NATIVE, &[("clone", "()Ljava/lang/Object;")]),
+        "java/lang/Cloneable" => StubClass::new(INTERFACE, object, &[]),
+        "java/io/Serializable" => StubClass::new(INTERFACE, object, &[]),
+        "java/lang/Runnable" => StubClass::new(INTERFACE, object, &[]).methods(ABSTRACT, &[("run", "()V")]),
+        "java/lang/Thread" => StubClass::new(PUBLIC_CLASS, object, &["java/lang/Runnable"])
+            .field(PRIVATE_FIELD, "target", "Ljava/lang/Runnable;")
+            .field(PRIVATE_FIELD, "name", "Ljava/lang/String;")
+            .field(PRIVATE_FIELD, "daemon", "Z")
+            .methods(NATIVE, &[
+                ("<init>", "()V"),
+                ("<init>", "(Ljava/lang/Runnable;)V"),
+                ("<init>", "(Ljava/lang/String;)V"),
+                ("<init>", "(Ljava/lang/Runnable;Ljava/lang/String;)V"),
+                ("start", "()V"),
+                ("run", "()V"),
+                ("join", "()V"),
+                ("join", "(J)V"),
+                ("isAlive", "()Z"),
+                ("interrupt", "()V"),
+                ("isInterrupted", "()Z"),
+                ("getName", "()Ljava/lang/String;"),
+                ("isDaemon", "()Z"),
+                ("setDaemon", "(Z)V"),
+            ])
+            .methods(STATIC_NATIVE, &[
+                ("currentThread", "()Ljava/lang/Thread;"),
+                ("sleep", "(J)V"),
+                ("yield", "()V"),
+                ("interrupted", "()Z"),
+                ("holdsLock", "(Ljava/lang/Object;)Z"),
+            ]),
+        "java/lang/Comparable" => StubClass::new(INTERFACE, object, &[]).methods(ABSTRACT, &[("compareTo", "(Ljava/lang/Object;)I")]),
+        "java/lang/CharSequence" => StubClass::new(INTERFACE, object, &[])
+            .methods(ABSTRACT, &[
+                ("length", "()I"),
+                ("charAt", "(I)C"),
+                ("subSequence", "(II)Ljava/lang/CharSequence;"),
+            ]),
+        "java/lang/Class" => StubClass::new(FINAL_CLASS, object, &["java/io/Serializable"])
+            .methods(NATIVE, &[
+                ("getName", "()Ljava/lang/String;"),
+                ("toString", "()Ljava/lang/String;"),
+                ("isPrimitive", "()Z"),
+                ("isArray", "()Z"),
+                ("isInterface", "()Z"),
+                ("desiredAssertionStatus", "()Z"),
+                ("getSuperclass", "()Ljava/lang/Class;"),
+                ("getComponentType", "()Ljava/lang/Class;"),
+                ("getModifiers", "()I"),
+                ("isInstance", "(Ljava/lang/Object;)Z"),
+                ("isAssignableFrom", "(Ljava/lang/Class;)Z"),
+            ])
+            .methods(STATIC_NATIVE, &[
+                ("forName", "(Ljava/lang/String;)Ljava/lang/Class;"),
+            ]),
+        "java/lang/String" => StubClass::new(FINAL_CLASS, object, &["java/io/Serializable", "java/lang/Comparable", "java/lang/CharSequence"])
+            .field(PRIVATE_FIELD, "value", "[C")
+            .methods(NATIVE, &[
+                ("<init>", "()V"),
+                ("<init>", "(Ljava/lang/String;)V"),
+                ("<init>", "([C)V"),
+                ("<init>", "([CII)V"),
+                ("length", "()I"),
+                ("isEmpty", "()Z"),
+                ("charAt", "(I)C"),
+                ("equals", "(Ljava/lang/Object;)Z"),
+                ("equalsIgnoreCase", "(Ljava/lang/String;)Z"),
+                ("hashCode", "()I"),
+                ("toString", "()Ljava/lang/String;"),
+                ("compareTo", "(Ljava/lang/String;)I"),
+                ("compareTo", "(Ljava/lang/Object;)I"),
+                ("concat", "(Ljava/lang/String;)Ljava/lang/String;"),
+                ("substring", "(I)Ljava/lang/String;"),
+                ("substring", "(II)Ljava/lang/String;"),
+                ("subSequence", "(II)Ljava/lang/CharSequence;"),
+                ("indexOf", "(I)I"),
+                ("indexOf", "(Ljava/lang/String;)I"),
+                ("lastIndexOf", "(I)I"),
+                ("lastIndexOf", "(Ljava/lang/String;)I"),
+                ("contains", "(Ljava/lang/CharSequence;)Z"),
+                ("startsWith", "(Ljava/lang/String;)Z"),
+                ("endsWith", "(Ljava/lang/String;)Z"),
+                ("replace", "(CC)Ljava/lang/String;"),
+                ("replace", "(Ljava/lang/CharSequence;Ljava/lang/CharSequence;)Ljava/lang/String;"),
+                ("toUpperCase", "()Ljava/lang/String;"),
+                ("toLowerCase", "()Ljava/lang/String;"),
+                ("trim", "()Ljava/lang/String;"),
+                ("repeat", "(I)Ljava/lang/String;"),
+                ("toCharArray", "()[C"),
+                ("intern", "()Ljava/lang/String;"),
+            ])
+            .methods(STATIC_NATIVE, &[
+                ("valueOf", "(Ljava/lang/Object;)Ljava/lang/String;"),
+                ("valueOf", "([C)Ljava/lang/String;"),
+                ("valueOf", "(Z)Ljava/lang/String;"),
+                ("valueOf", "(C)Ljava/lang/String;"),
+                ("valueOf", "(I)Ljava/lang/String;"),
+                ("valueOf", "(J)Ljava/lang/String;"),
+                ("valueOf", "(F)Ljava/lang/String;"),
+                ("valueOf", "(D)Ljava/lang/String;"),
+            ]),
+        "java/lang/StringBuilder" => StubClass::new(FINAL_CLASS, object, &["java/io/Serializable", "java/lang/CharSequence"])
+            // Holds exactly the characters of the builder, it grows in place
+            .field(PRIVATE_FIELD, "value", "[C")
+            .methods(NATIVE, &[
+                ("<init>", "()V"),
+                ("<init>", "(I)V"),
+                ("<init>", "(Ljava/lang/String;)V"),
+                ("<init>", "(Ljava/lang/CharSequence;)V"),
+                ("append", "(Ljava/lang/Object;)Ljava/lang/StringBuilder;"),
+                ("append", "(Ljava/lang/String;)Ljava/lang/StringBuilder;"),
+                ("append", "(Ljava/lang/CharSequence;)Ljava/lang/StringBuilder;"),
+                ("append", "([C)Ljava/lang/StringBuilder;"),
+                ("append", "(Z)Ljava/lang/StringBuilder;"),
+                ("append", "(C)Ljava/lang/StringBuilder;"),
+                ("append", "(I)Ljava/lang/StringBuilder;"),
+                ("append", "(J)Ljava/lang/StringBuilder;"),
+                ("append", "(F)Ljava/lang/StringBuilder;"),
+                ("append", "(D)Ljava/lang/StringBuilder;"),
+                ("insert", "(ILjava/lang/String;)Ljava/lang/StringBuilder;"),
+                ("insert", "(IC)Ljava/lang/StringBuilder;"),
+                ("insert", "(II)Ljava/lang/StringBuilder;"),
+                ("length", "()I"),
+                ("charAt", "(I)C"),
+                ("setCharAt", "(IC)V"),
+                ("setLength", "(I)V"),
+                ("deleteCharAt", "(I)Ljava/lang/StringBuilder;"),
+                ("delete", "(II)Ljava/lang/StringBuilder;"),
+                ("reverse", "()Ljava/lang/StringBuilder;"),
+                ("indexOf", "(Ljava/lang/String;)I"),
+                ("subSequence", "(II)Ljava/lang/CharSequence;"),
+                ("toString", "()Ljava/lang/String;"),
+            ]),
+        "java/lang/Number" => StubClass::new(ABSTRACT_CLASS, object, &["java/io/Serializable"])
+            .methods(NATIVE, &[("<init>", "()V")])
+            .methods(ABSTRACT, &[
+                ("intValue", "()I"),
+                ("longValue", "()J"),
+                ("floatValue", "()F"),
+                ("doubleValue", "()D"),
+            ]),
+        "java/lang/Integer" => StubClass::new(FINAL_CLASS, Some("java/lang/Number"), &["java/lang/Comparable"])
+            .field(PRIVATE_FIELD.union(FieldFlags::ACC_FINAL), "value", "I")
+            // The boxes of -128 to 127, which valueOf always returns the same object for
+            .field(PRIVATE_STATIC_FIELD, "cache", "[Ljava/lang/Integer;")
+            .methods(NATIVE, &[
+                ("<init>", "(I)V"),
+                ("<init>", "(Ljava/lang/String;)V"),
+                ("byteValue", "()B"),
+                ("shortValue", "()S"),
+                ("intValue", "()I"),
+                ("longValue", "()J"),
+                ("floatValue", "()F"),
+                ("doubleValue", "()D"),
+                ("toString", "()Ljava/lang/String;"),
+                ("hashCode", "()I"),
+                ("equals", "(Ljava/lang/Object;)Z"),
+                ("compareTo", "(Ljava/lang/Integer;)I"),
+                ("compareTo", "(Ljava/lang/Object;)I"),
+            ])
+            .methods(STATIC_NATIVE, &[
+                ("valueOf", "(I)Ljava/lang/Integer;"),
+                ("valueOf", "(Ljava/lang/String;)Ljava/lang/Integer;"),
+                ("parseInt", "(Ljava/lang/String;)I"),
+                ("parseInt", "(Ljava/lang/String;I)I"),
+                ("toString", "(I)Ljava/lang/String;"),
+                ("toString", "(II)Ljava/lang/String;"),
+                ("toHexString", "(I)Ljava/lang/String;"),
+                ("toOctalString", "(I)Ljava/lang/String;"),
+                ("toBinaryString", "(I)Ljava/lang/String;"),
+                ("hashCode", "(I)I"),
+                ("compare", "(II)I"),
+                ("signum", "(I)I"),
+                ("bitCount", "(I)I"),
+                ("max", "(II)I"),
+                ("min", "(II)I"),
+                ("sum", "(II)I"),
+            ]),
+        // Only the bit conversions, floats and doubles can't be boxed with the stub library
+        "java/lang/Float" => StubClass::new(FINAL_CLASS, object, &[])
+            .methods(STATIC_NATIVE, &[
+                ("floatToRawIntBits", "(F)I"),
+                ("intBitsToFloat", "(I)F"),
+            ]),
+        "java/lang/Double" => StubClass::new(FINAL_CLASS, object, &[])
+            .methods(STATIC_NATIVE, &[
+                ("doubleToRawLongBits", "(D)J"),
+                ("longBitsToDouble", "(J)D"),
+            ]),
+        "java/lang/Math" => StubClass::new(FINAL_CLASS, object, &[])
+            .methods(STATIC_NATIVE, &[
+                ("abs", "(I)I"),
+                ("abs", "(J)J"),
+                ("abs", "(F)F"),
+                ("abs", "(D)D"),
+                ("max", "(II)I"),
+                ("max", "(JJ)J"),
+                ("max", "(FF)F"),
+                ("max", "(DD)D"),
+                ("min", "(II)I"),
+                ("min", "(JJ)J"),
+                ("min", "(FF)F"),
+                ("min", "(DD)D"),
+                ("round", "(F)I"),
+                ("round", "(D)J"),
+                ("random", "()D"),
+                ("sqrt", "(D)D"),
+                ("cbrt", "(D)D"),
+                ("floor", "(D)D"),
+                ("ceil", "(D)D"),
+                ("rint", "(D)D"),
+                ("exp", "(D)D"),
+                ("log", "(D)D"),
+                ("log10", "(D)D"),
+                ("sin", "(D)D"),
+                ("cos", "(D)D"),
+                ("tan", "(D)D"),
+                ("asin", "(D)D"),
+                ("acos", "(D)D"),
+                ("atan", "(D)D"),
+                ("toRadians", "(D)D"),
+                ("toDegrees", "(D)D"),
+                ("pow", "(DD)D"),
+                ("atan2", "(DD)D"),
+                ("hypot", "(DD)D"),
+            ]),
+        "java/lang/System" => StubClass::new(FINAL_CLASS, object, &[])
+            .field(STATIC_FINAL_FIELD, "out", "Ljava/io/PrintStream;")
+            .field(STATIC_FINAL_FIELD, "err", "Ljava/io/PrintStream;")
+            .methods(STATIC_NATIVE, &[
+                ("arraycopy", "(Ljava/lang/Object;ILjava/lang/Object;II)V"),
+                ("currentTimeMillis", "()J"),
+                ("nanoTime", "()J"),
+                ("identityHashCode", "(Ljava/lang/Object;)I"),
+                ("exit", "(I)V"),
+                ("gc", "()V"),
+                ("getProperty", "(Ljava/lang/String;)Ljava/lang/String;"),
+                ("getProperty", "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;"),
+                ("getenv", "(Ljava/lang/String;)Ljava/lang/String;"),
+                ("lineSeparator", "()Ljava/lang/String;"),
+            ])
+            .methods(PRIVATE_STATIC_NATIVE, &[
+                ("setOut0", "(Ljava/io/PrintStream;)V"),
+                ("setErr0", "(Ljava/io/PrintStream;)V"),
+            ]),
+        "java/io/PrintStream" => StubClass::new(PUBLIC_CLASS, object, &[])
+            // The file descriptor written to, 1 or 2
+            .field(PRIVATE_FIELD, "fd", "I")
+            .methods(NATIVE, &[
+                ("print", "(Ljava/lang/String;)V"),
+                ("print", "(Ljava/lang/Object;)V"),
+                ("print", "([C)V"),
+                ("print", "(Z)V"),
+                ("print", "(C)V"),
+                ("print", "(I)V"),
+                ("print", "(J)V"),
+                ("print", "(F)V"),
+                ("print", "(D)V"),
+                ("println", "()V"),
+                ("println", "(Ljava/lang/String;)V"),
+                ("println", "(Ljava/lang/Object;)V"),
+                ("println", "([C)V"),
+                ("println", "(Z)V"),
+                ("println", "(C)V"),
+                ("println", "(I)V"),
+                ("println", "(J)V"),
+                ("println", "(F)V"),
+                ("println", "(D)V"),
+                ("write", "(I)V"),
+                ("flush", "()V"),
+            ]),
+        "java/lang/Throwable" => throwable_class("java/lang/Object")
+            .field(PRIVATE_FIELD, "detailMessage", "Ljava/lang/String;")
+            // Points to the throwable itself until the cause is set
+            .field(PRIVATE_FIELD, "cause", "Ljava/lang/Throwable;")
+            .methods(NATIVE, &[
+                ("getMessage", "()Ljava/lang/String;"),
+                ("getLocalizedMessage", "()Ljava/lang/String;"),
+                ("getCause", "()Ljava/lang/Throwable;"),
+                ("initCause", "(Ljava/lang/Throwable;)Ljava/lang/Throwable;"),
+                ("fillInStackTrace", "()Ljava/lang/Throwable;"),
+                ("printStackTrace", "()V"),
+                ("toString", "()Ljava/lang/String;"),
+            ]),
+        "java/lang/AssertionError" => throwable_class("java/lang/Error").methods(NATIVE, &[("<init>", "(Ljava/lang/Object;)V")]),
+        other => {
+            let (_, super_class) = STUB_THROWABLES.iter().find(|(throwable, _)| *throwable == name)?;
+            throwable_class(super_class)
+        }
+    };
+    Some(class)
+}
+
+/// Returns the class file of a class of the stub library, a tiny class library built into the VM,
+/// for running simple programs when there is no JDK around.
+pub fn read_class(name: &str) -> Option<Vec<u8>> {
+    // The stub classes have a few short names, which always fit
+    find_stub_class(name).map(|class| class.to_class_file(name).unwrap_or_else(|message| panic!("Stub class {}: {}", name, message)))
+}
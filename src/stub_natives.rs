@@ -0,0 +1,1130 @@
+use std::{env, io::{self, Write}, process, sync::{Arc, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use crate::{descriptor_parser::argument_descriptors, exceptions::{fill_in_stack_trace, format_stack_trace, JvmResult, Throwable}, interpreter::Interpreter, jdk_natives::write_to_fd, monitor::{enter_monitor, exit_monitor}, natives::{self, reference_arg, NativeMethod, NativeRegistry}, object::{ArrayData, Object, ObjectRef, Value}, runtime_class::Class, strings::{new_java_string, new_java_string_from_utf16, to_utf16}, stub_library::{STUB_THROWABLES, THROWABLE_CONSTRUCTORS}, thread::JavaThread, thread_natives};
+
+// The boxes that Integer.valueOf shares, like IntegerCache does
+const INTEGER_CACHE_LOW: i32 = -128;
+const INTEGER_CACHE_HIGH: i32 = 127;
+
+// State of the generator behind Math.random
+static RANDOM_SEED: AtomicU64 = AtomicU64::new(0);
+
+// Number in the name of the next thread created without one, like `Thread-0`
+static THREAD_NUMBER: AtomicU64 = AtomicU64::new(0);
+
+/// Registers the natives of the stub library, which implement all of its methods.
+/// Natives it shares with the JDK, like System.arraycopy, are registered by NativeRegistry itself.
+pub fn register(registry: &NativeRegistry) {
+    registry.register("java/lang/Object", "<init>", "()V", return_nothing);
+    registry.register("java/lang/Object", "equals", "(Ljava/lang/Object;)Z", object_equals);
+    registry.register("java/lang/Object", "toString", "()Ljava/lang/String;", object_to_string);
+    registry.register("java/lang/Object", "wait", "()V", object_wait);
+
+    // Thread natives the JDK has too, like sleep and isAlive, are registered by thread_natives
+    for descriptor in ["()V", "(Ljava/lang/Runnable;)V", "(Ljava/lang/String;)V", "(Ljava/lang/Runnable;Ljava/lang/String;)V"] {
+        registry.register("java/lang/Thread", "<init>", descriptor, thread_init);
+    }
+    registry.register("java/lang/Thread", "start", "()V", thread_start);
+    registry.register("java/lang/Thread", "run", "()V", thread_run);
+    registry.register("java/lang/Thread", "join", "()V", thread_join);
+    registry.register("java/lang/Thread", "join", "(J)V", thread_join);
+    registry.register("java/lang/Thread", "interrupt", "()V", thread_interrupt);
+    registry.register("java/lang/Thread", "isInterrupted", "()Z", thread_is_interrupted);
+    registry.register("java/lang/Thread", "interrupted", "()Z", thread_interrupted);
+    registry.register("java/lang/Thread", "getName", "()Ljava/lang/String;", thread_get_name);
+    registry.register("java/lang/Thread", "isDaemon", "()Z", thread_is_daemon);
+    registry.register("java/lang/Thread", "setDaemon", "(Z)V", thread_set_daemon);
+
+    registry.register("java/lang/Class", "getName", "()Ljava/lang/String;", class_get_name);
+    registry.register("java/lang/Class", "toString", "()Ljava/lang/String;", class_to_string);
+    registry.register("java/lang/Class", "desiredAssertionStatus", "()Z", return_false);
+    registry.register("java/lang/Class", "forName", "(Ljava/lang/String;)Ljava/lang/Class;", class_for_name);
+
+    let string_natives: [(&str, &str, NativeMethod); 31] = [
+        ("<init>", "()V", string_init),
+        ("<init>", "(Ljava/lang/String;)V", string_init),
+        ("<init>", "([C)V", string_init),
+        ("<init>", "([CII)V", string_init),
+        ("length", "()I", string_length),
+        ("isEmpty", "()Z", string_is_empty),
+        ("charAt", "(I)C", string_char_at),
+        ("equals", "(Ljava/lang/Object;)Z", string_equals),
+        ("equalsIgnoreCase", "(Ljava/lang/String;)Z", string_equals_ignore_case),
+        ("hashCode", "()I", string_hash_code),
+        ("toString", "()Ljava/lang/String;", return_this),
+        ("compareTo", "(Ljava/lang/String;)I", string_compare_to),
+        ("compareTo", "(Ljava/lang/Object;)I", string_compare_to),
+        ("concat", "(Ljava/lang/String;)Ljava/lang/String;", string_concat),
+        ("substring", "(I)Ljava/lang/String;", string_substring),
+        ("substring", "(II)Ljava/lang/String;", string_substring),
+        ("subSequence", "(II)Ljava/lang/CharSequence;", string_substring),
+        ("indexOf", "(I)I", string_index_of),
+        ("indexOf", "(Ljava/lang/String;)I", string_index_of),
+        ("lastIndexOf", "(I)I", string_last_index_of),
+        ("lastIndexOf", "(Ljava/lang/String;)I", string_last_index_of),
+        ("contains", "(Ljava/lang/CharSequence;)Z", string_contains),
+        ("startsWith", "(Ljava/lang/String;)Z", string_starts_with),
+        ("endsWith", "(Ljava/lang/String;)Z", string_ends_with),
+        ("replace", "(CC)Ljava/lang/String;", string_replace),
+        ("replace", "(Ljava/lang/CharSequence;Ljava/lang/CharSequence;)Ljava/lang/String;", string_replace),
+        ("toUpperCase", "()Ljava/lang/String;", string_to_upper_case),
+        ("toLowerCase", "()Ljava/lang/String;", string_to_lower_case),
+        ("trim", "()Ljava/lang/String;", string_trim),
+        ("repeat", "(I)Ljava/lang/String;", string_repeat),
+        ("toCharArray", "()[C", string_to_char_array),
+    ];
+    for (name, descriptor, native) in string_natives {
+        registry.register("java/lang/String", name, descriptor, native);
+    }
+    for argument in ["Ljava/lang/Object;", "[C", "Z", "C", "I", "J", "F", "D"] {
+        registry.register("java/lang/String", "valueOf", &format!("({})Ljava/lang/String;", argument), string_value_of);
+    }
+
+    for descriptor in ["()V", "(I)V", "(Ljava/lang/String;)V", "(Ljava/lang/CharSequence;)V"] {
+        registry.register("java/lang/StringBuilder", "<init>", descriptor, string_builder_init);
+    }
+    for argument in ["Ljava/lang/Object;", "Ljava/lang/String;", "Ljava/lang/CharSequence;", "[C", "Z", "C", "I", "J", "F", "D"] {
+        registry.register("java/lang/StringBuilder", "append", &format!("({})Ljava/lang/StringBuilder;", argument), string_builder_append);
+    }
+    for argument in ["Ljava/lang/String;", "C", "I"] {
+        registry.register("java/lang/StringBuilder", "insert", &format!("(I{})Ljava/lang/StringBuilder;", argument), string_builder_insert);
+    }
+    registry.register("java/lang/StringBuilder", "length", "()I", string_builder_length);
+    registry.register("java/lang/StringBuilder", "charAt", "(I)C", string_builder_char_at);
+    registry.register("java/lang/StringBuilder", "setCharAt", "(IC)V", string_builder_set_char_at);
+    registry.register("java/lang/StringBuilder", "setLength", "(I)V", string_builder_set_length);
+    registry.register("java/lang/StringBuilder", "deleteCharAt", "(I)Ljava/lang/StringBuilder;", string_builder_delete);
+    registry.register("java/lang/StringBuilder", "delete", "(II)Ljava/lang/StringBuilder;", string_builder_delete);
+    registry.register("java/lang/StringBuilder", "reverse", "()Ljava/lang/StringBuilder;", string_builder_reverse);
+    registry.register("java/lang/StringBuilder", "indexOf", "(Ljava/lang/String;)I", string_index_of);
+    registry.register("java/lang/StringBuilder", "subSequence", "(II)Ljava/lang/CharSequence;", string_substring);
+    registry.register("java/lang/StringBuilder", "toString", "()Ljava/lang/String;", string_builder_to_string);
+
+    registry.register("java/lang/Number", "<init>", "()V", return_nothing);
+    registry.register("java/lang/Integer", "<init>", "(I)V", integer_init);
+    registry.register("java/lang/Integer", "<init>", "(Ljava/lang/String;)V", integer_init);
+    for (name, descriptor) in [("byteValue", "()B"), ("shortValue", "()S"), ("intValue", "()I"), ("longValue", "()J"), ("floatValue", "()F"), ("doubleValue", "()D")] {
+        registry.register("java/lang/Integer", name, descriptor, integer_value);
+    }
+    registry.register("java/lang/Integer", "toString", "()Ljava/lang/String;", integer_to_string);
+    registry.register("java/lang/Integer", "hashCode", "()I", integer_value);
+    registry.register("java/lang/Integer", "equals", "(Ljava/lang/Object;)Z", integer_equals);
+    registry.register("java/lang/Integer", "compareTo", "(Ljava/lang/Integer;)I", integer_compare_to);
+    registry.register("java/lang/Integer", "compareTo", "(Ljava/lang/Object;)I", integer_compare_to);
+    registry.register("java/lang/Integer", "valueOf", "(I)Ljava/lang/Integer;", integer_value_of);
+    registry.register("java/lang/Integer", "valueOf", "(Ljava/lang/String;)Ljava/lang/Integer;", integer_value_of);
+    registry.register("java/lang/Integer", "parseInt", "(Ljava/lang/String;)I", integer_parse_int);
+    registry.register("java/lang/Integer", "parseInt", "(Ljava/lang/String;I)I", integer_parse_int);
+    registry.register("java/lang/Integer", "toString", "(I)Ljava/lang/String;", integer_to_string);
+    registry.register("java/lang/Integer", "toString", "(II)Ljava/lang/String;", integer_to_string);
+    registry.register("java/lang/Integer", "toHexString", "(I)Ljava/lang/String;", integer_to_unsigned_string);
+    registry.register("java/lang/Integer", "toOctalString", "(I)Ljava/lang/String;", integer_to_unsigned_string);
+    registry.register("java/lang/Integer", "toBinaryString", "(I)Ljava/lang/String;", integer_to_unsigned_string);
+    for name in ["hashCode", "signum", "bitCount"] {
+        registry.register("java/lang/Integer", name, "(I)I", integer_function);
+    }
+    for name in ["compare", "max", "min", "sum"] {
+        registry.register("java/lang/Integer", name, "(II)I", integer_function);
+    }
+
+    for (name, descriptors) in [("abs", ["(I)I", "(J)J", "(F)F", "(D)D"]), ("max", ["(II)I", "(JJ)J", "(FF)F", "(DD)D"]), ("min", ["(II)I", "(JJ)J", "(FF)F", "(DD)D"])] {
+        for descriptor in descriptors {
+            registry.register("java/lang/Math", name, descriptor, math_abs_max_min);
+        }
+    }
+    registry.register("java/lang/Math", "round", "(F)I", math_round);
+    registry.register("java/lang/Math", "round", "(D)J", math_round);
+    registry.register("java/lang/Math", "random", "()D", math_random);
+    for name in ["sqrt", "cbrt", "floor", "ceil", "rint", "exp", "log", "log10", "sin", "cos", "tan", "asin", "acos", "atan", "toRadians", "toDegrees"] {
+        registry.register("java/lang/Math", name, "(D)D", math_function);
+    }
+    for name in ["pow", "atan2", "hypot"] {
+        registry.register("java/lang/Math", name, "(DD)D", math_function);
+    }
+
+    registry.register("java/lang/System", "exit", "(I)V", system_exit);
+    registry.register("java/lang/System", "gc", "()V", return_nothing);
+    registry.register("java/lang/System", "getProperty", "(Ljava/lang/String;)Ljava/lang/String;", system_get_property);
+    registry.register("java/lang/System", "getProperty", "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;", system_get_property);
+    registry.register("java/lang/System", "getenv", "(Ljava/lang/String;)Ljava/lang/String;", system_getenv);
+    registry.register("java/lang/System", "lineSeparator", "()Ljava/lang/String;", system_line_separator);
+
+    for argument in ["Ljava/lang/String;", "Ljava/lang/Object;", "[C", "Z", "C", "I", "J", "F", "D"] {
+        registry.register("java/io/PrintStream", "print", &format!("({})V", argument), print_stream_print);
+        registry.register("java/io/PrintStream", "println", &format!("({})V", argument), print_stream_print);
+    }
+    registry.register("java/io/PrintStream", "println", "()V", print_stream_print);
+    registry.register("java/io/PrintStream", "write", "(I)V", print_stream_write);
+    registry.register("java/io/PrintStream", "flush", "()V", print_stream_flush);
+
+    for class_name in ["java/lang/Throwable"].into_iter().chain(STUB_THROWABLES.iter().map(|(class_name, _)| *class_name)) {
+        for descriptor in THROWABLE_CONSTRUCTORS {
+            registry.register(class_name, "<init>", descriptor, throwable_init);
+        }
+    }
+    registry.register("java/lang/AssertionError", "<init>", "(Ljava/lang/Object;)V", throwable_init);
+    registry.register("java/lang/Throwable", "getMessage", "()Ljava/lang/String;", throwable_get_message);
+    registry.register("java/lang/Throwable", "getLocalizedMessage", "()Ljava/lang/String;", throwable_get_message);
+    registry.register("java/lang/Throwable", "getCause", "()Ljava/lang/Throwable;", throwable_get_cause);
+    registry.register("java/lang/Throwable", "initCause", "(Ljava/lang/Throwable;)Ljava/lang/Throwable;", throwable_init_cause);
+    registry.register("java/lang/Throwable", "fillInStackTrace", "()Ljava/lang/Throwable;", throwable_fill_in_stack_trace);
+    registry.register("java/lang/Throwable", "printStackTrace", "()V", throwable_print_stack_trace);
+    registry.register("java/lang/Throwable", "toString", "()Ljava/lang/String;", throwable_to_string);
+}
+
+fn return_nothing(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(None)
+}
+
+fn return_false(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(0)))
+}
+
+fn return_this(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(args[0].clone()))
+}
+
+/// Returns the name and descriptor of the running native, for natives that implement several methods.
+fn current_method(thread: &JavaThread) -> (String, String) {
+    let stack = thread.stack.borrow();
+    let method = &stack.last().unwrap().method;
+    (method.name.clone(), method.descriptor.clone())
+}
+
+/// Returns the descriptor of the last argument of the running native, like `I` for `append(I)`.
+fn last_argument_type(thread: &JavaThread) -> String {
+    let (_, descriptor) = current_method(thread);
+    argument_descriptors(&descriptor).last().map_or(String::new(), |argument| argument.to_string())
+}
+
+fn string_result(thread: &JavaThread, chars: &[u16]) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Reference(Some(new_java_string_from_utf16(thread, chars)?))))
+}
+
+fn boolean_result(value: bool) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(value as i32)))
+}
+
+fn new_char_array(thread: &JavaThread, chars: Vec<u16>) -> JvmResult<ObjectRef> {
+    Ok(Object::new_array(thread.vm.load_class("[C")?, ArrayData::Char(chars)))
+}
+
+fn char_array(array: &ObjectRef) -> Vec<u16> {
+    match &*array.array() {
+        ArrayData::Char(chars) => chars.clone(),
+        other => panic!("Expected a char array, found {}", array.class.name)
+    }
+}
+
+/// Formats a double the way Double.toString does: at least one digit after the point,
+/// and scientific notation outside of 10^-3 to 10^7.
+fn floating_to_string(value: f64, plain: String, scientific: String) -> String {
+    if value.is_nan() {
+        return String::from("NaN");
+    }
+    if value.is_infinite() {
+        return String::from(if value > 0.0 { "Infinity" } else { "-Infinity" });
+    }
+    if value == 0.0 || (1e-3..1e7).contains(&value.abs()) {
+        return if plain.contains('.') { plain } else { format!("{}.0", plain) };
+    }
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    if mantissa.contains('.') {
+        format!("{}E{}", mantissa, exponent)
+    } else {
+        format!("{}.0E{}", mantissa, exponent)
+    }
+}
+
+/// Converts a value to text the way String.valueOf does, given the descriptor of its type.
+fn value_to_chars(thread: &JavaThread, value: &Value, descriptor: &str) -> JvmResult<Vec<u16>> {
+    let text = match descriptor {
+        "Z" => String::from(if value.as_int() != 0 { "true" } else { "false" }),
+        "C" => return Ok(vec![value.as_int() as u16]),
+        "B" | "S" | "I" => value.as_int().to_string(),
+        "J" => value.as_long().to_string(),
+        "F" => {
+            let float = value.as_float();
+            floating_to_string(float as f64, format!("{}", float), format!("{:e}", float))
+        },
+        "D" => {
+            let double = value.as_double();
+            floating_to_string(double, format!("{}", double), format!("{:e}", double))
+        },
+        "[C" => return Ok(char_array(&reference_arg(std::slice::from_ref(value), 0)?)),
+        other => match value.as_reference() {
+            None => String::from("null"),
+            Some(string) if string.class.name == "java/lang/String" => return Ok(to_utf16(string)),
+            Some(object) => match Interpreter::invoke_virtual_method(thread, object, "toString", "()Ljava/lang/String;", Vec::new())? {
+                Some(Value::Reference(Some(string))) => return Ok(to_utf16(&string)),
+                other => String::from("null")
+            }
+        }
+    };
+    Ok(text.encode_utf16().collect())
+}
+
+/// Returns the characters of a CharSequence, asking toString for those that are not strings.
+fn char_sequence_chars(thread: &JavaThread, args: &[Value], index: usize) -> JvmResult<Vec<u16>> {
+    reference_arg(args, index)?;
+    value_to_chars(thread, &args[index], "Ljava/lang/CharSequence;")
+}
+
+fn index_out_of_bounds(class_name: &str, index: i32, length: usize) -> Throwable {
+    Throwable::new(class_name, format!("Index {} out of bounds for length {}", index, length))
+}
+
+/// Finds a sequence of characters in another one, starting at an index.
+fn find_chars(chars: &[u16], target: &[u16], from: usize) -> Option<usize> {
+    if target.is_empty() {
+        return Some(from.min(chars.len()));
+    }
+    (from..chars.len()).find(|index| chars[*index..].starts_with(target))
+}
+
+/// Encodes a code point given to methods like indexOf(int), which can be a supplementary character.
+fn code_point_chars(code_point: i32) -> Vec<u16> {
+    match char::from_u32(code_point as u32) {
+        Some(char) => char.encode_utf16(&mut [0; 2]).to_vec(),
+        None => vec![code_point as u16]
+    }
+}
+
+fn object_equals(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    boolean_result(args[1].as_reference().is_some_and(|other| Arc::ptr_eq(this, other)))
+}
+
+/// Returns the class name and the hash code in hexadecimal, like `java.lang.Object@1b6d3586`.
+fn object_to_string(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let hash = Interpreter::invoke_virtual_method(thread, this, "hashCode", "()I", Vec::new())?.unwrap().as_int();
+    let text = format!("{}@{:x}", this.class.name.replace('/', "."), hash);
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &text)?))))
+}
+
+fn object_wait(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    thread_natives::object_wait(thread, vec![args[0].clone(), Value::Long(0)])
+}
+
+fn thread_field(thread_object: &ObjectRef, name: &str, descriptor: &str) -> usize {
+    thread_object.class.instance_field_slot(name, descriptor).unwrap()
+}
+
+/// Threads created without a name are called `Thread-` followed by a number.
+fn thread_init(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = reference_arg(&args, 0)?;
+    let (_, descriptor) = current_method(thread);
+    let (target, name) = match descriptor.as_str() {
+        "(Ljava/lang/Runnable;)V" => (args[1].clone(), None),
+        "(Ljava/lang/String;)V" => (Value::null(), Some(args[1].clone())),
+        "(Ljava/lang/Runnable;Ljava/lang/String;)V" => (args[1].clone(), Some(args[2].clone())),
+        other => (Value::null(), None)
+    };
+    let name = match name {
+        Some(Value::Reference(None)) => return Err(Throwable::new("java/lang/NullPointerException", "name cannot be null")),
+        Some(name) => name,
+        None => Value::Reference(Some(new_java_string(thread, &format!("Thread-{}", THREAD_NUMBER.fetch_add(1, Ordering::Relaxed)))?))
+    };
+    let mut fields = this.fields();
+    fields[thread_field(&this, "target", "Ljava/lang/Runnable;")] = target;
+    fields[thread_field(&this, "name", "Ljava/lang/String;")] = name;
+    Ok(None)
+}
+
+fn thread_start(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    thread_natives::thread_start(thread, args)
+}
+
+fn thread_run(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = reference_arg(&args, 0)?;
+    let target = this.fields()[thread_field(&this, "target", "Ljava/lang/Runnable;")].as_reference().cloned();
+    if let Some(target) = target {
+        Interpreter::invoke_virtual_method(thread, &target, "run", "()V", Vec::new())?;
+    }
+    Ok(None)
+}
+
+/// Waits on the Thread object until the thread ends, which notifies it. A timeout of zero waits forever.
+fn thread_join(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = reference_arg(&args, 0)?;
+    let millis = args.get(1).map_or(0, Value::as_long);
+    if millis < 0 {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", "timeout value is negative"));
+    }
+    let deadline = Instant::now() + Duration::from_millis(millis as u64);
+    enter_monitor(&this);
+    let joined = (|| {
+        while thread_natives::thread_is_alive(thread, vec![args[0].clone()])?.unwrap().as_int() != 0 {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if millis > 0 && remaining.is_zero() {
+                break;
+            }
+            // Waiting for less than a millisecond would be waiting forever
+            let wait_millis = if millis > 0 { remaining.as_millis().max(1) as i64 } else { 0 };
+            thread_natives::object_wait(thread, vec![args[0].clone(), Value::Long(wait_millis)])?;
+        }
+        Ok(None)
+    })();
+    exit_monitor(&this)?;
+    joined
+}
+
+fn thread_interrupt(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    thread_natives::thread_interrupt(thread, args)
+}
+
+fn thread_is_interrupted(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    thread_natives::thread_is_interrupted(thread, vec![args[0].clone(), Value::Int(0)])
+}
+
+fn thread_interrupted(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    boolean_result(thread.control.is_interrupted(true))
+}
+
+fn thread_get_name(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = reference_arg(&args, 0)?;
+    let name = this.fields()[thread_field(&this, "name", "Ljava/lang/String;")].clone();
+    Ok(Some(name))
+}
+
+fn thread_is_daemon(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = reference_arg(&args, 0)?;
+    let daemon = this.fields()[thread_field(&this, "daemon", "Z")].clone();
+    Ok(Some(daemon))
+}
+
+/// Only threads that have not been started can become daemons.
+fn thread_set_daemon(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = reference_arg(&args, 0)?;
+    if thread_natives::thread_is_alive(thread, vec![args[0].clone()])?.unwrap().as_int() != 0 {
+        return Err(Throwable::without_message("java/lang/IllegalThreadStateException"));
+    }
+    this.fields()[thread_field(&this, "daemon", "Z")] = args[1].clone();
+    Ok(None)
+}
+
+fn class_get_name(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(args[0].as_reference().unwrap());
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &class.name.replace('/', "."))?))))
+}
+
+/// Class.forName(String) loads and initializes the class, like forName0 does for the JDK.
+fn class_for_name(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    natives::class_for_name(thread, vec![args[0].clone(), Value::Int(1), Value::null(), Value::null()])
+}
+
+fn class_to_string(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let class = Class::from_mirror(args[0].as_reference().unwrap());
+    let text = match (class.is_primitive(), class.is_interface()) {
+        (true, _) => class.name.clone(),
+        (false, true) => format!("interface {}", class.name.replace('/', ".")),
+        (false, false) => format!("class {}", class.name.replace('/', "."))
+    };
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &text)?))))
+}
+
+/// Sets the characters of a String or StringBuilder, which both keep them in a `char[]` named `value`.
+fn set_value(thread: &JavaThread, object: &ObjectRef, chars: Vec<u16>) -> JvmResult<()> {
+    let array = new_char_array(thread, chars)?;
+    let slot = object.class.instance_field_slot("value", "[C").unwrap();
+    object.fields()[slot] = Value::Reference(Some(array));
+    Ok(())
+}
+
+fn value_array(object: &ObjectRef) -> ObjectRef {
+    let slot = object.class.instance_field_slot("value", "[C").unwrap();
+    object.fields()[slot].as_reference().cloned().unwrap()
+}
+
+/// Returns the characters of the receiver, a String or StringBuilder.
+fn this_chars(args: &[Value]) -> Vec<u16> {
+    char_array(&value_array(args[0].as_reference().unwrap()))
+}
+
+/// Checks a range of characters like `substring` does, throwing StringIndexOutOfBoundsException.
+fn check_range(begin: i32, end: i32, length: usize) -> JvmResult<(usize, usize)> {
+    if begin < 0 || begin > end || end as i64 > length as i64 {
+        return Err(Throwable::new("java/lang/StringIndexOutOfBoundsException", format!("begin {}, end {}, length {}", begin, end, length)));
+    }
+    Ok((begin as usize, end as usize))
+}
+
+fn string_init(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let chars = match args.len() {
+        1 => Vec::new(),
+        2 if args[1].as_reference().is_some_and(|argument| argument.is_array()) => char_array(&reference_arg(&args, 1)?),
+        2 => to_utf16(&reference_arg(&args, 1)?),
+        other => {
+            let chars = char_array(&reference_arg(&args, 1)?);
+            let (offset, count) = (args[2].as_int(), args[3].as_int());
+            if offset < 0 || count < 0 || offset as i64 + count as i64 > chars.len() as i64 {
+                return Err(Throwable::new("java/lang/StringIndexOutOfBoundsException",
+                    format!("offset {}, count {}, length {}", offset, count, chars.len())));
+            }
+            chars[offset as usize..(offset + count) as usize].to_vec()
+        }
+    };
+    set_value(thread, this, chars)?;
+    Ok(None)
+}
+
+fn string_length(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(this_chars(&args).len() as i32)))
+}
+
+fn string_is_empty(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    boolean_result(this_chars(&args).is_empty())
+}
+
+fn string_char_at(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let chars = this_chars(&args);
+    let index = args[1].as_int();
+    match chars.get(index as usize).filter(|_| index >= 0) {
+        Some(char) => Ok(Some(Value::Int(*char as i32))),
+        None => Err(index_out_of_bounds("java/lang/StringIndexOutOfBoundsException", index, chars.len()))
+    }
+}
+
+fn string_equals(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let equal = match args[1].as_reference() {
+        Some(other) if other.class.name == "java/lang/String" => this_chars(&args) == to_utf16(other),
+        other => false
+    };
+    boolean_result(equal)
+}
+
+fn string_equals_ignore_case(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let equal = match args[1].as_reference() {
+        Some(other) => {
+            let (chars, other_chars) = (this_chars(&args), to_utf16(other));
+            let fold = |char: &u16| char::from_u32(*char as u32).map_or(*char as u32, |char| char.to_lowercase().next().unwrap() as u32);
+            chars.len() == other_chars.len() && chars.iter().map(fold).eq(other_chars.iter().map(fold))
+        },
+        None => false
+    };
+    boolean_result(equal)
+}
+
+fn string_hash_code(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let hash = this_chars(&args).iter().fold(0i32, |hash, char| hash.wrapping_mul(31).wrapping_add(*char as i32));
+    Ok(Some(Value::Int(hash)))
+}
+
+fn string_compare_to(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let other = reference_arg(&args, 1)?;
+    if other.class.name != "java/lang/String" {
+        return Err(Throwable::new("java/lang/ClassCastException", format!("class {} cannot be cast to class java.lang.String", other.class.name.replace('/', "."))));
+    }
+    let (chars, other_chars) = (this_chars(&args), to_utf16(&other));
+    let difference = chars.iter().zip(other_chars.iter())
+        .find(|(char, other_char)| char != other_char)
+        .map(|(char, other_char)| *char as i32 - *other_char as i32)
+        .unwrap_or(chars.len() as i32 - other_chars.len() as i32);
+    Ok(Some(Value::Int(difference)))
+}
+
+fn string_concat(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mut chars = this_chars(&args);
+    chars.extend(to_utf16(&reference_arg(&args, 1)?));
+    string_result(thread, &chars)
+}
+
+/// Implements substring and subSequence, for both String and StringBuilder.
+fn string_substring(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let chars = this_chars(&args);
+    let end = args.get(2).map_or(chars.len() as i32, Value::as_int);
+    let (begin, end) = check_range(args[1].as_int(), end, chars.len())?;
+    string_result(thread, &chars[begin..end])
+}
+
+fn string_index_of(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let target = match &args[1] {
+        Value::Int(code_point) => code_point_chars(*code_point),
+        other => to_utf16(&reference_arg(&args, 1)?)
+    };
+    let index = find_chars(&this_chars(&args), &target, 0).map_or(-1, |index| index as i32);
+    Ok(Some(Value::Int(index)))
+}
+
+fn string_last_index_of(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let target = match &args[1] {
+        Value::Int(code_point) => code_point_chars(*code_point),
+        other => to_utf16(&reference_arg(&args, 1)?)
+    };
+    let chars = this_chars(&args);
+    let index = (0..=chars.len()).rev()
+        .find(|index| chars[*index..].starts_with(&target))
+        .map_or(-1, |index| index as i32);
+    Ok(Some(Value::Int(index)))
+}
+
+fn string_contains(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let target = char_sequence_chars(thread, &args, 1)?;
+    boolean_result(find_chars(&this_chars(&args), &target, 0).is_some())
+}
+
+fn string_starts_with(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    boolean_result(this_chars(&args).starts_with(&to_utf16(&reference_arg(&args, 1)?)))
+}
+
+fn string_ends_with(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    boolean_result(this_chars(&args).ends_with(&to_utf16(&reference_arg(&args, 1)?)))
+}
+
+fn string_replace(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (target, replacement) = match (&args[1], &args[2]) {
+        (Value::Int(old_char), Value::Int(new_char)) => (vec![*old_char as u16], vec![*new_char as u16]),
+        other => (char_sequence_chars(thread, &args, 1)?, char_sequence_chars(thread, &args, 2)?)
+    };
+    let chars = this_chars(&args);
+    let mut result = Vec::with_capacity(chars.len());
+    let mut index = 0;
+    while let Some(found) = find_chars(&chars, &target, index) {
+        result.extend_from_slice(&chars[index..found]);
+        result.extend_from_slice(&replacement);
+        if target.is_empty() {
+            // An empty target matches between all characters
+            if found == chars.len() {
+                index = found;
+                break;
+            }
+            result.push(chars[found]);
+            index = found + 1;
+        } else {
+            index = found + target.len();
+        }
+    }
+    result.extend_from_slice(&chars[index.min(chars.len())..]);
+    string_result(thread, &result)
+}
+
+fn string_to_upper_case(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let text = String::from_utf16_lossy(&this_chars(&args)).to_uppercase();
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &text)?))))
+}
+
+fn string_to_lower_case(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let text = String::from_utf16_lossy(&this_chars(&args)).to_lowercase();
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &text)?))))
+}
+
+/// Removes the characters up to and including space from both ends.
+fn string_trim(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let chars = this_chars(&args);
+    let start = chars.iter().position(|char| *char > b' ' as u16).unwrap_or(chars.len());
+    let end = chars.iter().rposition(|char| *char > b' ' as u16).map_or(start, |index| index + 1);
+    string_result(thread, &chars[start..end])
+}
+
+fn string_repeat(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let count = args[1].as_int();
+    if count < 0 {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", format!("count is negative: {}", count)));
+    }
+    string_result(thread, &this_chars(&args).repeat(count as usize))
+}
+
+fn string_to_char_array(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Reference(Some(new_char_array(thread, this_chars(&args))?))))
+}
+
+fn string_value_of(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let chars = value_to_chars(thread, &args[0], &last_argument_type(thread))?;
+    string_result(thread, &chars)
+}
+
+/// Runs a function on the characters of a StringBuilder, which can change them.
+fn with_builder_chars<R>(builder: &ObjectRef, function: impl FnOnce(&mut Vec<u16>) -> R) -> R {
+    let array = value_array(builder);
+    let mut data = array.array();
+    match &mut *data {
+        ArrayData::Char(chars) => function(chars),
+        other => panic!("StringBuilder.value is not a char array")
+    }
+}
+
+fn string_builder_init(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let chars = match args.get(1) {
+        None => Vec::new(),
+        Some(Value::Int(capacity)) if *capacity < 0 => return Err(Throwable::new("java/lang/NegativeArraySizeException", capacity.to_string())),
+        Some(Value::Int(capacity)) => Vec::with_capacity(*capacity as usize),
+        Some(other) => char_sequence_chars(thread, &args, 1)?
+    };
+    set_value(thread, this, chars)?;
+    Ok(None)
+}
+
+fn string_builder_append(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let appended = value_to_chars(thread, &args[1], &last_argument_type(thread))?;
+    with_builder_chars(args[0].as_reference().unwrap(), |chars| chars.extend(appended));
+    Ok(Some(args[0].clone()))
+}
+
+fn string_builder_insert(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let inserted = value_to_chars(thread, &args[2], &last_argument_type(thread))?;
+    let offset = args[1].as_int();
+    with_builder_chars(args[0].as_reference().unwrap(), |chars| {
+        if offset < 0 || offset as usize > chars.len() {
+            return Err(Throwable::new("java/lang/StringIndexOutOfBoundsException", format!("offset {}, length {}", offset, chars.len())));
+        }
+        chars.splice(offset as usize..offset as usize, inserted);
+        Ok(())
+    })?;
+    Ok(Some(args[0].clone()))
+}
+
+fn string_builder_length(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Int(with_builder_chars(args[0].as_reference().unwrap(), |chars| chars.len()) as i32)))
+}
+
+fn string_builder_char_at(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    string_char_at(thread, args)
+}
+
+fn string_builder_set_char_at(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let index = args[1].as_int();
+    with_builder_chars(args[0].as_reference().unwrap(), |chars| {
+        match chars.get_mut(index as usize).filter(|_| index >= 0) {
+            Some(char) => *char = args[2].as_int() as u16,
+            None => return Err(index_out_of_bounds("java/lang/StringIndexOutOfBoundsException", index, chars.len()))
+        }
+        Ok(None)
+    })
+}
+
+fn string_builder_set_length(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let length = args[1].as_int();
+    if length < 0 {
+        return Err(index_out_of_bounds("java/lang/StringIndexOutOfBoundsException", length, 0));
+    }
+    with_builder_chars(args[0].as_reference().unwrap(), |chars| chars.resize(length as usize, 0));
+    Ok(None)
+}
+
+/// Implements delete and deleteCharAt, which removes one character.
+fn string_builder_delete(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let start = args[1].as_int();
+    with_builder_chars(args[0].as_reference().unwrap(), |chars| {
+        let (start, end) = match args.get(2) {
+            Some(end) => check_range(start, end.as_int().min(chars.len() as i32), chars.len())?,
+            None if start >= 0 && (start as usize) < chars.len() => (start as usize, start as usize + 1),
+            None => return Err(index_out_of_bounds("java/lang/StringIndexOutOfBoundsException", start, chars.len()))
+        };
+        chars.drain(start..end);
+        Ok(())
+    })?;
+    Ok(Some(args[0].clone()))
+}
+
+/// Reverses the characters, keeping surrogate pairs in their order.
+fn string_builder_reverse(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    with_builder_chars(args[0].as_reference().unwrap(), |chars| {
+        chars.reverse();
+        let mut index = 0;
+        while index + 1 < chars.len() {
+            if (0xDC00..0xE000).contains(&chars[index]) && (0xD800..0xDC00).contains(&chars[index + 1]) {
+                chars.swap(index, index + 1);
+                index += 1;
+            }
+            index += 1;
+        }
+    });
+    Ok(Some(args[0].clone()))
+}
+
+fn string_builder_to_string(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    string_result(thread, &this_chars(&args))
+}
+
+/// Parses an int the way Integer.parseInt does, with an optional sign.
+fn parse_int(string: Option<&ObjectRef>, radix: i32) -> JvmResult<i32> {
+    let text = match string {
+        Some(string) => String::from_utf16_lossy(&to_utf16(string)),
+        None => return Err(Throwable::new("java/lang/NumberFormatException", "Cannot parse null string: null"))
+    };
+    if !(2..=36).contains(&radix) {
+        return Err(Throwable::new("java/lang/NumberFormatException", format!("radix {} out of range", radix)));
+    }
+    i32::from_str_radix(&text, radix as u32).map_err(|_| {
+        let under_radix = if radix == 10 { String::new() } else { format!(" under radix {}", radix) };
+        Throwable::new("java/lang/NumberFormatException", format!("For input string: \"{}\"{}", text, under_radix))
+    })
+}
+
+/// Returns the box of an int, sharing those of small values.
+fn box_integer(thread: &JavaThread, value: i32) -> JvmResult<ObjectRef> {
+    let class = thread.vm.load_class("java/lang/Integer")?;
+    class.initialize(thread)?;
+    let new_box = || {
+        let object = Object::new_instance(class.clone());
+        object.fields()[class.instance_field_slot("value", "I").unwrap()] = Value::Int(value);
+        object
+    };
+    if !(INTEGER_CACHE_LOW..=INTEGER_CACHE_HIGH).contains(&value) {
+        return Ok(new_box());
+    }
+    let cache_slot = class.fields.iter().find(|field| field.is_static() && field.name == "cache").unwrap().slot;
+    let cache = class.static_values.lock().unwrap()[cache_slot].as_reference().cloned();
+    let cache = match cache {
+        Some(cache) => cache,
+        None => {
+            let length = (INTEGER_CACHE_HIGH - INTEGER_CACHE_LOW + 1) as usize;
+            let cache = Object::new_array(thread.vm.load_class("[Ljava/lang/Integer;")?, ArrayData::Reference(vec![None; length]));
+            class.static_values.lock().unwrap()[cache_slot] = Value::Reference(Some(cache.clone()));
+            cache
+        }
+    };
+    let index = (value - INTEGER_CACHE_LOW) as usize;
+    let mut data = cache.array();
+    if let Value::Reference(Some(cached)) = data.get(index) {
+        return Ok(cached);
+    }
+    let boxed = new_box();
+    data.set(index, Value::Reference(Some(boxed.clone())));
+    Ok(boxed)
+}
+
+fn integer_of(object: &ObjectRef) -> i32 {
+    object.fields()[object.class.instance_field_slot("value", "I").unwrap()].as_int()
+}
+
+fn integer_init(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let value = match &args[1] {
+        Value::Int(value) => *value,
+        other => parse_int(other.as_reference(), 10)?
+    };
+    this.fields()[this.class.instance_field_slot("value", "I").unwrap()] = Value::Int(value);
+    Ok(None)
+}
+
+/// Implements the conversions of Number, picking the type from the descriptor of the running method.
+fn integer_value(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let value = integer_of(args[0].as_reference().unwrap());
+    let (_, descriptor) = current_method(thread);
+    let result = match descriptor.as_str() {
+        "()B" => Value::Int(value as i8 as i32),
+        "()S" => Value::Int(value as i16 as i32),
+        "()J" => Value::Long(value as i64),
+        "()F" => Value::Float(value as f32),
+        "()D" => Value::Double(value as f64),
+        other => Value::Int(value)
+    };
+    Ok(Some(result))
+}
+
+fn integer_equals(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let value = integer_of(args[0].as_reference().unwrap());
+    let equal = args[1].as_reference().is_some_and(|other| other.class.name == "java/lang/Integer" && integer_of(other) == value);
+    boolean_result(equal)
+}
+
+fn integer_compare_to(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let other = reference_arg(&args, 1)?;
+    if other.class.name != "java/lang/Integer" {
+        return Err(Throwable::new("java/lang/ClassCastException", format!("class {} cannot be cast to class java.lang.Integer", other.class.name.replace('/', "."))));
+    }
+    let ordering = integer_of(args[0].as_reference().unwrap()).cmp(&integer_of(&other));
+    Ok(Some(Value::Int(ordering as i32)))
+}
+
+fn integer_value_of(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let value = match &args[0] {
+        Value::Int(value) => *value,
+        other => parse_int(other.as_reference(), 10)?
+    };
+    Ok(Some(Value::Reference(Some(box_integer(thread, value)?))))
+}
+
+fn integer_parse_int(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let radix = args.get(1).map_or(10, Value::as_int);
+    Ok(Some(Value::Int(parse_int(args[0].as_reference(), radix)?)))
+}
+
+/// Implements both toString(), and the static toString(int) and toString(int, int) with a radix.
+fn integer_to_string(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let value = match &args[0] {
+        Value::Int(value) => *value,
+        other => integer_of(other.as_reference().unwrap())
+    };
+    // Radixes out of range are replaced by 10
+    let radix = args.get(1).map(Value::as_int).filter(|radix| (2..=36).contains(radix)).unwrap_or(10) as u32;
+    let mut digits = Vec::new();
+    let mut magnitude = (value as i64).unsigned_abs();
+    loop {
+        digits.push(char::from_digit((magnitude % radix as u64) as u32, radix).unwrap());
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if value < 0 {
+        digits.push('-');
+    }
+    let text: String = digits.iter().rev().collect();
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &text)?))))
+}
+
+fn integer_to_unsigned_string(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let value = args[0].as_int() as u32;
+    let text = match current_method(thread).0.as_str() {
+        "toHexString" => format!("{:x}", value),
+        "toOctalString" => format!("{:o}", value),
+        other => format!("{:b}", value)
+    };
+    Ok(Some(Value::Reference(Some(new_java_string(thread, &text)?))))
+}
+
+/// Implements the static int functions of Integer, by the name of the running method.
+fn integer_function(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let first = args[0].as_int();
+    let second = args.get(1).map(Value::as_int);
+    let result = match (current_method(thread).0.as_str(), second) {
+        ("signum", _) => first.signum(),
+        ("bitCount", _) => first.count_ones() as i32,
+        ("compare", Some(second)) => first.cmp(&second) as i32,
+        ("max", Some(second)) => first.max(second),
+        ("min", Some(second)) => first.min(second),
+        ("sum", Some(second)) => first.wrapping_add(second),
+        // hashCode(int)
+        other => first
+    };
+    Ok(Some(Value::Int(result)))
+}
+
+/// Math.max and Math.min for floating point values, where NaN wins and -0.0 is less than 0.0.
+fn floating_max_min(first: f64, second: f64, is_max: bool) -> f64 {
+    if first.is_nan() || second.is_nan() {
+        return f64::NAN;
+    }
+    if first == second {
+        // Only differs for zeros of opposite signs
+        return if first.is_sign_negative() == is_max { second } else { first };
+    }
+    if (first > second) == is_max { first } else { second }
+}
+
+/// Implements the overloads of Math.abs, Math.max and Math.min, for the type of the arguments.
+fn math_abs_max_min(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let name = current_method(thread).0;
+    let is_max = name == "max";
+    let result = match (&args[0], args.get(1)) {
+        (Value::Int(value), None) => Value::Int(value.wrapping_abs()),
+        (Value::Long(value), None) => Value::Long(value.wrapping_abs()),
+        (Value::Float(value), None) => Value::Float(value.abs()),
+        (Value::Double(value), None) => Value::Double(value.abs()),
+        (Value::Int(first), Some(second)) => Value::Int(if is_max { (*first).max(second.as_int()) } else { (*first).min(second.as_int()) }),
+        (Value::Long(first), Some(second)) => Value::Long(if is_max { (*first).max(second.as_long()) } else { (*first).min(second.as_long()) }),
+        (Value::Float(first), Some(second)) => Value::Float(floating_max_min(*first as f64, second.as_float() as f64, is_max) as f32),
+        (Value::Double(first), Some(second)) => Value::Double(floating_max_min(*first, second.as_double(), is_max)),
+        other => panic!("Unexpected arguments for Math.{}", name)
+    };
+    Ok(Some(result))
+}
+
+/// Rounds to the closest integer, with ties rounding up, saturating at the bounds of the result type.
+fn math_round(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let round = |value: f64| {
+        let floor = value.floor();
+        if value - floor >= 0.5 { floor + 1.0 } else { floor }
+    };
+    let result = match &args[0] {
+        Value::Float(value) => Value::Int(round(*value as f64) as i32),
+        other => Value::Long(round(other.as_double()) as i64)
+    };
+    Ok(Some(result))
+}
+
+/// A 48-bit linear congruential generator like java.util.Random, seeded with the time of the first call.
+fn math_random(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    const MULTIPLIER: u64 = 0x5DEECE66D;
+    const MASK: u64 = (1 << 48) - 1;
+    let next = |seed: u64| (seed.wrapping_mul(MULTIPLIER).wrapping_add(0xB)) & MASK;
+    let mut seed = RANDOM_SEED.load(Ordering::Relaxed);
+    if seed == 0 {
+        seed = (SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64 ^ MULTIPLIER) & MASK;
+    }
+    let first = next(seed);
+    let second = next(first);
+    RANDOM_SEED.store(second, Ordering::Relaxed);
+    // 53 random bits, 26 from the first number and 27 from the second, as Random.nextDouble takes them
+    let bits = ((first >> 22) << 27) + (second >> 21);
+    Ok(Some(Value::Double(bits as f64 / (1u64 << 53) as f64)))
+}
+
+/// Implements the double functions of Math, by the name of the running method.
+fn math_function(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let value = args[0].as_double();
+    let second = args.get(1).map(Value::as_double).unwrap_or(0.0);
+    let result = match current_method(thread).0.as_str() {
+        "sqrt" => value.sqrt(),
+        "cbrt" => value.cbrt(),
+        "floor" => value.floor(),
+        "ceil" => value.ceil(),
+        "rint" => value.round_ties_even(),
+        "exp" => value.exp(),
+        "log" => value.ln(),
+        "log10" => value.log10(),
+        "sin" => value.sin(),
+        "cos" => value.cos(),
+        "tan" => value.tan(),
+        "asin" => value.asin(),
+        "acos" => value.acos(),
+        "atan" => value.atan(),
+        "toRadians" => value.to_radians(),
+        "toDegrees" => value.to_degrees(),
+        "pow" => value.powf(second),
+        "atan2" => value.atan2(second),
+        "hypot" => value.hypot(second),
+        other => panic!("Unknown Math function {}", other)
+    };
+    Ok(Some(Value::Double(result)))
+}
+
+fn system_exit(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let _ = io::stdout().flush();
+    process::exit(args[0].as_int());
+}
+
+/// Looks a property up in the properties of the VM, there is no Properties object in the stub library.
+fn system_get_property(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let key = String::from_utf16_lossy(&to_utf16(&reference_arg(&args, 0)?));
+    let value = match thread.vm.system_properties.iter().find(|(name, _)| *name == key) {
+        Some((_, value)) => Some(new_java_string(thread, value)?),
+        None => args.get(1).and_then(|default| default.as_reference().cloned())
+    };
+    Ok(Some(Value::Reference(value)))
+}
+
+fn system_getenv(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let name = String::from_utf16_lossy(&to_utf16(&reference_arg(&args, 0)?));
+    let value = match env::var(name) {
+        Ok(value) => Some(new_java_string(thread, &value)?),
+        Err(_) => None
+    };
+    Ok(Some(Value::Reference(value)))
+}
+
+fn system_line_separator(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let separator = if cfg!(windows) { "\r\n" } else { "\n" };
+    Ok(Some(Value::Reference(Some(new_java_string(thread, separator)?))))
+}
+
+fn print_stream_fd(stream: &ObjectRef) -> i32 {
+    stream.fields()[stream.class.instance_field_slot("fd", "I").unwrap()].as_int()
+}
+
+/// Implements all overloads of print and println, converting the argument like String.valueOf.
+fn print_stream_print(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let mut chars = match args.get(1) {
+        Some(value) => value_to_chars(thread, value, &last_argument_type(thread))?,
+        None => Vec::new()
+    };
+    if current_method(thread).0 == "println" {
+        chars.push(b'\n' as u16);
+    }
+    let text = String::from_utf16_lossy(&chars);
+    write_to_fd(print_stream_fd(args[0].as_reference().unwrap()), text.as_bytes())?;
+    Ok(None)
+}
+
+fn print_stream_write(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    write_to_fd(print_stream_fd(args[0].as_reference().unwrap()), &[args[1].as_int() as u8])?;
+    Ok(None)
+}
+
+fn print_stream_flush(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let _ = io::stdout().flush();
+    Ok(None)
+}
+
+fn throwable_slot(throwable: &ObjectRef, name: &str, descriptor: &str) -> usize {
+    throwable.class.instance_field_slot(name, descriptor).unwrap()
+}
+
+/// Implements the constructors of all throwables, which take a message, a cause or both.
+fn throwable_init(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().cloned().unwrap();
+    let (_, descriptor) = current_method(thread);
+    let (message, cause) = match descriptor.as_str() {
+        "(Ljava/lang/String;)V" => (args[1].as_reference().cloned(), None),
+        "(Ljava/lang/String;Ljava/lang/Throwable;)V" => (args[1].as_reference().cloned(), Some(args[2].clone())),
+        "(Ljava/lang/Throwable;)V" => {
+            let message = match args[1].as_reference() {
+                Some(cause) => Some(new_java_string_from_utf16(thread, &value_to_chars(thread, &args[1], "Ljava/lang/Throwable;")?)?),
+                None => None
+            };
+            (message, Some(args[1].clone()))
+        },
+        // AssertionError(Object), whose message is the object as a string
+        "(Ljava/lang/Object;)V" => {
+            let message = new_java_string_from_utf16(thread, &value_to_chars(thread, &args[1], "Ljava/lang/Object;")?)?;
+            let cause = args[1].as_reference().filter(|detail| detail.class.is_subclass_of("java/lang/Throwable"));
+            (Some(message), cause.map(|cause| Value::Reference(Some(cause.clone()))))
+        },
+        other => (None, None)
+    };
+    {
+        let mut fields = this.fields();
+        fields[throwable_slot(&this, "detailMessage", "Ljava/lang/String;")] = Value::Reference(message);
+        fields[throwable_slot(&this, "cause", "Ljava/lang/Throwable;")] = cause.unwrap_or(Value::Reference(Some(this.clone())));
+    }
+    Interpreter::invoke_virtual_method(thread, &this, "fillInStackTrace", "()Ljava/lang/Throwable;", Vec::new())?;
+    Ok(None)
+}
+
+fn throwable_get_message(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let message = this.fields()[throwable_slot(this, "detailMessage", "Ljava/lang/String;")].clone();
+    Ok(Some(message))
+}
+
+fn throwable_get_cause(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let cause = this.fields()[throwable_slot(this, "cause", "Ljava/lang/Throwable;")].as_reference().cloned();
+    Ok(Some(Value::Reference(cause.filter(|cause| !Arc::ptr_eq(cause, this)))))
+}
+
+/// Sets the cause, which can only be done once and not by a constructor that took a cause.
+fn throwable_init_cause(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let slot = throwable_slot(this, "cause", "Ljava/lang/Throwable;");
+    let current = this.fields()[slot].as_reference().cloned();
+    if !current.as_ref().is_some_and(|current| Arc::ptr_eq(current, this)) {
+        let description = match args[1].as_reference() {
+            Some(_) => String::from_utf16_lossy(&value_to_chars(thread, &args[1], "Ljava/lang/Throwable;")?),
+            None => String::from("a null")
+        };
+        return Err(Throwable::new("java/lang/IllegalStateException", format!("Can't overwrite cause with {}", description)));
+    }
+    if args[1].as_reference().is_some_and(|cause| Arc::ptr_eq(cause, this)) {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", "Self-causation not permitted"));
+    }
+    this.fields()[slot] = args[1].clone();
+    Ok(Some(args[0].clone()))
+}
+
+fn throwable_fill_in_stack_trace(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    fill_in_stack_trace(thread, args[0].as_reference().unwrap());
+    Ok(Some(args[0].clone()))
+}
+
+fn throwable_print_stack_trace(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let _ = io::stdout().flush();
+    eprint!("{}", format_stack_trace(thread, args[0].as_reference().unwrap()));
+    Ok(None)
+}
+
+/// Returns the class name, followed by the localized message if there is one.
+fn throwable_to_string(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let this = args[0].as_reference().unwrap();
+    let mut chars: Vec<u16> = this.class.name.replace('/', ".").encode_utf16().collect();
+    if let Some(Value::Reference(Some(message))) = Interpreter::invoke_virtual_method(thread, this, "getLocalizedMessage", "()Ljava/lang/String;", Vec::new())? {
+        chars.extend(": ".encode_utf16());
+        chars.extend(to_utf16(&message));
+    }
+    string_result(thread, &chars)
+}
@@ -0,0 +1,138 @@
+use std::{cell::{Cell, RefCell}, sync::{Arc, Condvar, Mutex}, time::{Duration, Instant}};
+
+use crate::{object::ObjectRef, runtime_class::Method, vm::Vm};
+
+// Java code recurses a lot, and so does the interpreter for every call
+pub const THREAD_STACK_SIZE: usize = 512 * 1024 * 1024;
+
+pub struct StackFrame {
+    pub method: Arc<Method>,
+    pub pc: Cell<usize>
+}
+
+/// A thread running Java code. Each one is bound to a native thread, and has its own call stack.
+pub struct JavaThread {
+    pub vm: Arc<Vm>,
+    pub stack: RefCell<Vec<StackFrame>>,
+    // The java.lang.Thread object, that Thread.currentThread returns
+    pub object: RefCell<Option<ObjectRef>>,
+    pub control: Arc<ThreadControl>,
+    // Set while a StackOverflowError is created, which may use the frames kept in reserve for it
+    pub in_stack_reserve: Cell<bool>
+}
+
+impl JavaThread {
+    pub fn new(vm: Arc<Vm>) -> Self {
+        Self { vm, stack: RefCell::new(Vec::new()), object: RefCell::new(None), control: Arc::new(ThreadControl::new()), in_stack_reserve: Cell::new(false) }
+    }
+
+    pub fn push_frame(&self, method: Arc<Method>) {
+        self.stack.borrow_mut().push(StackFrame { method, pc: Cell::new(0) });
+    }
+
+    pub fn pop_frame(&self) {
+        self.stack.borrow_mut().pop();
+    }
+
+    /// Records the pc of the running method, so stack traces can point at the current instruction.
+    pub fn set_pc(&self, pc: usize) {
+        if let Some(frame) = self.stack.borrow().last() {
+            frame.pc.set(pc);
+        }
+    }
+}
+
+/// The part of a thread that other threads can reach through its java.lang.Thread object:
+/// its interrupt status and parking permit, and the wait set it is in.
+pub struct ThreadControl {
+    state: Mutex<ControlState>,
+    // Signalled when a sleeping or parked thread should look at its state again
+    wakeup: Condvar
+}
+
+struct ControlState {
+    alive: bool,
+    interrupted: bool,
+    // Given by LockSupport.unpark, and used up by the next park
+    permit: bool,
+    // The object whose wait set the thread is in
+    waiting_on: Option<ObjectRef>
+}
+
+impl ThreadControl {
+    pub fn new() -> ThreadControl {
+        let state = ControlState { alive: true, interrupted: false, permit: false, waiting_on: None };
+        ThreadControl { state: Mutex::new(state), wakeup: Condvar::new() }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.state.lock().unwrap().alive
+    }
+
+    pub fn terminate(&self) {
+        self.state.lock().unwrap().alive = false;
+    }
+
+    /// Returns the interrupt status, and clears it when asked to.
+    pub fn is_interrupted(&self, clear: bool) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let interrupted = state.interrupted;
+        if clear {
+            state.interrupted = false;
+        }
+        interrupted
+    }
+
+    /// Sets the interrupt status, and wakes up the thread when it is sleeping, parked or waiting.
+    pub fn interrupt(&self) {
+        let waiting_on = {
+            let mut state = self.state.lock().unwrap();
+            state.interrupted = true;
+            state.waiting_on.clone()
+        };
+        self.wakeup.notify_all();
+        // The thread checks its interrupt status with the monitor locked, so this can't get lost
+        if let Some(object) = waiting_on {
+            object.monitor.wake_waiters();
+        }
+    }
+
+    pub fn set_waiting_on(&self, object: Option<ObjectRef>) {
+        self.state.lock().unwrap().waiting_on = object;
+    }
+
+    /// Sleeps for a while, unless the thread gets interrupted. Returns whether it did, which clears the interrupt status.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.interrupted {
+                state.interrupted = false;
+                return true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self.wakeup.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// Blocks until the permit is given, the thread gets interrupted or the deadline passes (LockSupport.park).
+    pub fn park(&self, deadline: Option<Instant>) {
+        let mut state = self.state.lock().unwrap();
+        while !state.permit && !state.interrupted {
+            match deadline {
+                Some(deadline) if Instant::now() >= deadline => break,
+                Some(deadline) => state = self.wakeup.wait_timeout(state, deadline - Instant::now()).unwrap().0,
+                None => state = self.wakeup.wait(state).unwrap()
+            }
+        }
+        state.permit = false;
+    }
+
+    pub fn unpark(&self) {
+        self.state.lock().unwrap().permit = true;
+        self.wakeup.notify_all();
+    }
+}
@@ -0,0 +1,290 @@
+use std::{sync::Arc, thread, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use crate::{exceptions::{JvmResult, Throwable}, interpreter::Interpreter, launcher::report_uncaught, monitor::{enter_monitor, illegal_monitor_state, WaitOutcome}, natives::{reference_arg, NativeRegistry}, object::{ArrayData, ObjectRef, Value, VmData}, strings::to_rust_string, thread::{JavaThread, ThreadControl, THREAD_STACK_SIZE}};
+
+// Values of Thread.threadStatus, which are JVMTI thread states (see jdk.internal.misc.VM.toThreadState)
+pub const THREAD_STATUS_RUNNABLE: i32 = 0x0005;
+const THREAD_STATUS_TERMINATED: i32 = 0x0002;
+const THREAD_STATUS_SLEEPING: i32 = 0x00E1;
+const THREAD_STATUS_WAITING: i32 = 0x0191;
+const THREAD_STATUS_TIMED_WAITING: i32 = 0x01A1;
+const THREAD_STATUS_PARKED: i32 = 0x0291;
+const THREAD_STATUS_TIMED_PARKED: i32 = 0x02A1;
+
+/// Registers the natives of java.lang.Thread, the monitor methods of java.lang.Object and the parking of Unsafe.
+pub fn register(registry: &NativeRegistry) {
+    registry.register("java/lang/Object", "wait", "(J)V", object_wait);
+    registry.register("java/lang/Object", "notify", "()V", object_notify);
+    registry.register("java/lang/Object", "notifyAll", "()V", object_notify_all);
+
+    registry.register("java/lang/Thread", "currentThread", "()Ljava/lang/Thread;", thread_current_thread);
+    registry.register("java/lang/Thread", "start0", "()V", thread_start);
+    registry.register("java/lang/Thread", "isAlive", "()Z", thread_is_alive);
+    registry.register("java/lang/Thread", "sleep", "(J)V", thread_sleep);
+    registry.register("java/lang/Thread", "yield", "()V", thread_yield);
+    registry.register("java/lang/Thread", "holdsLock", "(Ljava/lang/Object;)Z", thread_holds_lock);
+    registry.register("java/lang/Thread", "interrupt0", "()V", thread_interrupt);
+    // JDK 9 and later keep the interrupt status in a field, and tell the VM when they clear it
+    registry.register("java/lang/Thread", "clearInterruptEvent", "()V", thread_clear_interrupt_event);
+    // JDK 8 asks the VM for it
+    registry.register("java/lang/Thread", "isInterrupted", "(Z)Z", thread_is_interrupted);
+    registry.register("java/lang/Thread", "setPriority0", "(I)V", no_op);
+    registry.register("java/lang/Thread", "setNativeName", "(Ljava/lang/String;)V", no_op);
+
+    for unsafe_class in ["jdk/internal/misc/Unsafe", "sun/misc/Unsafe"] {
+        registry.register(unsafe_class, "park", "(ZJ)V", unsafe_park);
+        registry.register(unsafe_class, "unpark", "(Ljava/lang/Object;)V", unsafe_unpark);
+    }
+}
+
+fn no_op(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(None)
+}
+
+/// Returns the running thread of a Thread object, if it has been started.
+fn thread_control(thread_object: &ObjectRef) -> Option<Arc<ThreadControl>> {
+    match &*thread_object.vm_data.lock().unwrap() {
+        Some(VmData::Thread(control)) => Some(control.clone()),
+        other => None
+    }
+}
+
+/// Sets the Thread.threadStatus of a thread, which Thread.getState reads. The stub library has no such field.
+fn set_thread_status(thread_object: &ObjectRef, status: i32) {
+    if let Some(slot) = thread_object.class.instance_field_slot("threadStatus", "I") {
+        thread_object.fields()[slot] = Value::Int(status);
+    }
+}
+
+/// Marks a thread as alive or not in Thread.eetop, which later JDK 17 updates check in Thread.isAlive.
+/// HotSpot keeps the address of its own thread structure there, anything but zero will do.
+pub fn set_eetop(thread_object: &ObjectRef, alive: bool) {
+    if let Some(slot) = thread_object.class.instance_field_slot("eetop", "J") {
+        thread_object.fields()[slot] = Value::Long(alive as i64);
+    }
+}
+
+fn set_current_thread_status(thread: &JavaThread, status: i32) {
+    if let Some(thread_object) = &*thread.object.borrow() {
+        set_thread_status(thread_object, status);
+    }
+}
+
+/// Clears the interrupt status, also in the Thread.interrupted field of JDK 9 and later.
+fn clear_interrupt(thread: &JavaThread) {
+    thread.control.is_interrupted(true);
+    let thread_object = thread.object.borrow();
+    if let Some((thread_object, slot)) = thread_object.as_ref()
+        .and_then(|thread_object| Some((thread_object, thread_object.class.instance_field_slot("interrupted", "Z")?))) {
+        thread_object.fields()[slot] = Value::Int(0);
+    }
+}
+
+/// The name of a thread, which JDK 8 keeps as a char array.
+fn thread_name(thread_object: &ObjectRef) -> String {
+    let class = &thread_object.class;
+    let slot = class.instance_field_slot("name", "Ljava/lang/String;").or_else(|| class.instance_field_slot("name", "[C"));
+    let name = slot.and_then(|slot| thread_object.fields()[slot].as_reference().cloned());
+    match name {
+        Some(name) if name.is_array() => match &*name.array() {
+            ArrayData::Char(chars) => String::from_utf16_lossy(chars),
+            other => panic!("Thread name is not a char array")
+        },
+        Some(name) => to_rust_string(&name),
+        None => String::from("Thread")
+    }
+}
+
+fn is_daemon(thread_object: &ObjectRef) -> bool {
+    thread_object.class.instance_field_slot("daemon", "Z")
+        .is_some_and(|slot| thread_object.fields()[slot].as_int() != 0)
+}
+
+pub fn object_wait(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let object = reference_arg(&args, 0)?;
+    let millis = args[1].as_long();
+    if millis < 0 {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", "timeout value is negative"));
+    }
+    if !object.monitor.is_owned_by_current_thread() {
+        return Err(illegal_monitor_state());
+    }
+    // A timeout of zero waits until the thread gets notified
+    let timeout = Some(Duration::from_millis(millis as u64)).filter(|_| millis > 0);
+    set_current_thread_status(thread, if timeout.is_some() { THREAD_STATUS_TIMED_WAITING } else { THREAD_STATUS_WAITING });
+    thread.control.set_waiting_on(Some(object.clone()));
+    let outcome = object.monitor.wait(timeout, || thread.control.is_interrupted(false));
+    thread.control.set_waiting_on(None);
+    set_current_thread_status(thread, THREAD_STATUS_RUNNABLE);
+    match outcome {
+        Some(WaitOutcome::Interrupted) => {
+            clear_interrupt(thread);
+            Err(Throwable::without_message("java/lang/InterruptedException"))
+        },
+        Some(other) => Ok(None),
+        None => Err(illegal_monitor_state())
+    }
+}
+
+fn object_notify(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    match reference_arg(&args, 0)?.monitor.notify() {
+        true => Ok(None),
+        false => Err(illegal_monitor_state())
+    }
+}
+
+fn object_notify_all(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    match reference_arg(&args, 0)?.monitor.notify_all() {
+        true => Ok(None),
+        false => Err(illegal_monitor_state())
+    }
+}
+
+pub fn thread_current_thread(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    Ok(Some(Value::Reference(thread.object.borrow().clone())))
+}
+
+/// Starts a thread on a native thread of its own, which runs the run method of the Thread object.
+pub fn thread_start(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let thread_object = reference_arg(&args, 0)?;
+    let control = Arc::new(ThreadControl::new());
+    {
+        let mut vm_data = thread_object.vm_data.lock().unwrap();
+        if vm_data.is_some() {
+            return Err(Throwable::without_message("java/lang/IllegalThreadStateException"));
+        }
+        *vm_data = Some(VmData::Thread(control.clone()));
+    }
+    set_thread_status(&thread_object, THREAD_STATUS_RUNNABLE);
+    set_eetop(&thread_object, true);
+    let daemon = is_daemon(&thread_object);
+    let vm = thread.vm.clone();
+    vm.thread_started(daemon);
+    let spawned = thread::Builder::new()
+        .name(thread_name(&thread_object))
+        .stack_size(THREAD_STACK_SIZE)
+        .spawn(move || {
+            let mut java_thread = JavaThread::new(vm);
+            java_thread.control = control;
+            *java_thread.object.borrow_mut() = Some(thread_object.clone());
+            run_thread(&java_thread, &thread_object);
+            java_thread.vm.thread_ended(daemon);
+        });
+    if let Err(error) = spawned {
+        thread.vm.thread_ended(daemon);
+        return Err(Throwable::new("java/lang/OutOfMemoryError", format!("unable to create native thread: {}", error)));
+    }
+    Ok(None)
+}
+
+/// Runs a started thread to its end. Like in HotSpot, the end of a thread notifies everybody
+/// waiting on its Thread object, which is how Thread.join works.
+fn run_thread(thread: &JavaThread, thread_object: &ObjectRef) {
+    if let Err(throwable) = Interpreter::invoke_virtual_method(thread, thread_object, "run", "()V", Vec::new()) {
+        dispatch_uncaught_exception(thread, thread_object, throwable);
+    }
+    // Thread.exit lets the thread group know
+    let exit = thread.vm.load_class("java/lang/Thread").ok()
+        .and_then(|class| class.find_declared_method("exit", "()V").cloned());
+    if let Some(exit) = exit {
+        let _ = Interpreter::invoke(thread, &exit, vec![Value::Reference(Some(thread_object.clone()))]);
+    }
+    set_thread_status(thread_object, THREAD_STATUS_TERMINATED);
+    enter_monitor(thread_object);
+    set_eetop(thread_object, false);
+    thread.control.terminate();
+    thread_object.monitor.notify_all();
+    thread_object.monitor.exit();
+}
+
+/// Hands an exception that ended a thread to its uncaught exception handler, or prints it without one.
+fn dispatch_uncaught_exception(thread: &JavaThread, thread_object: &ObjectRef, throwable: Throwable) {
+    let dispatch = thread_object.class.find_method_in_superclasses("dispatchUncaughtException", "(Ljava/lang/Throwable;)V");
+    let dispatched = match dispatch {
+        Some(dispatch) => throwable.into_object(thread).and_then(|exception| {
+            Interpreter::invoke(thread, &dispatch, vec![Value::Reference(Some(thread_object.clone())), Value::Reference(Some(exception))])
+        }),
+        None => {
+            report_uncaught(thread, &thread_name(thread_object), throwable);
+            return;
+        }
+    };
+    if let Err(throwable) = dispatched {
+        report_uncaught(thread, &thread_name(thread_object), throwable);
+    }
+}
+
+pub fn thread_is_alive(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let is_alive = thread_control(&reference_arg(&args, 0)?).is_some_and(|control| control.is_alive());
+    Ok(Some(Value::Int(is_alive as i32)))
+}
+
+pub fn thread_sleep(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let millis = args[0].as_long();
+    if millis < 0 {
+        return Err(Throwable::new("java/lang/IllegalArgumentException", "timeout value is negative"));
+    }
+    set_current_thread_status(thread, THREAD_STATUS_SLEEPING);
+    let interrupted = thread.control.sleep(Duration::from_millis(millis as u64));
+    set_current_thread_status(thread, THREAD_STATUS_RUNNABLE);
+    if interrupted {
+        clear_interrupt(thread);
+        return Err(Throwable::new("java/lang/InterruptedException", "sleep interrupted"));
+    }
+    Ok(None)
+}
+
+fn thread_yield(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    thread::yield_now();
+    Ok(None)
+}
+
+pub fn thread_holds_lock(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let holds_lock = reference_arg(&args, 0)?.monitor.is_owned_by_current_thread();
+    Ok(Some(Value::Int(holds_lock as i32)))
+}
+
+/// Interrupting a thread that has not been started does nothing.
+pub fn thread_interrupt(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    if let Some(control) = thread_control(&reference_arg(&args, 0)?) {
+        control.interrupt();
+    }
+    Ok(None)
+}
+
+fn thread_clear_interrupt_event(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    thread.control.is_interrupted(true);
+    Ok(None)
+}
+
+pub fn thread_is_interrupted(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let clear = args[1].as_int() != 0;
+    let interrupted = thread_control(&reference_arg(&args, 0)?).is_some_and(|control| control.is_interrupted(clear));
+    Ok(Some(Value::Int(interrupted as i32)))
+}
+
+/// Blocks the current thread for LockSupport.park. An absolute time is in milliseconds since the epoch,
+/// a relative one in nanoseconds, where zero means no timeout.
+fn unsafe_park(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    let (absolute, time) = (args[1].as_int() != 0, args[2].as_long());
+    let deadline = match (absolute, time) {
+        (true, millis) => {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+            Some(Instant::now() + Duration::from_millis(millis.saturating_sub(now).max(0) as u64))
+        },
+        (false, 0) => None,
+        (false, nanos) if nanos < 0 => return Ok(None),
+        (false, nanos) => Some(Instant::now() + Duration::from_nanos(nanos as u64))
+    };
+    set_current_thread_status(thread, if deadline.is_some() { THREAD_STATUS_TIMED_PARKED } else { THREAD_STATUS_PARKED });
+    thread.control.park(deadline);
+    set_current_thread_status(thread, THREAD_STATUS_RUNNABLE);
+    Ok(None)
+}
+
+fn unsafe_unpark(thread: &JavaThread, args: Vec<Value>) -> JvmResult<Option<Value>> {
+    if let Some(control) = args[1].as_reference().and_then(thread_control) {
+        control.unpark();
+    }
+    Ok(None)
+}
@@ -0,0 +1,1285 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{class_loader::{ATCode, ClassInfo, ConstantPoolItem, MethodFlags, MethodInfo, StackMapFrame, VerificationTypeInfo}, descriptor_parser::argument_descriptors, exceptions::{JvmResult, Throwable}, opcodes::*, runtime_class::Class, vm::Vm};
+
+/// A type as the verifier tracks it (JVMS §4.10.1.2). Booleans, bytes, chars and shorts are all ints.
+/// Longs and doubles take up two local variables, the second one holding Top, but a single operand stack entry.
+#[derive(Clone, PartialEq, Debug)]
+enum Type {
+    Top,
+    Int,
+    Float,
+    Long,
+    Double,
+    Null,
+    // `this` in a constructor, before another constructor was called on it
+    UninitializedThis,
+    // An object created by the `new` instruction at this offset, before a constructor was called on it
+    Uninitialized(u16),
+    // A class or interface by its internal name, or an array by its descriptor like `[I`
+    Reference(String),
+    // Pushed by jsr, for the subroutine starting at this offset
+    ReturnAddress(u16),
+}
+
+impl Type {
+    fn from_descriptor(descriptor: &str) -> Type {
+        match descriptor.as_bytes()[0] {
+            b'F' => Type::Float,
+            b'J' => Type::Long,
+            b'D' => Type::Double,
+            b'L' => Type::Reference(descriptor[1..descriptor.len() - 1].to_string()),
+            b'[' => Type::Reference(descriptor.to_string()),
+            other => Type::Int
+        }
+    }
+
+    fn object() -> Type {
+        Type::Reference(String::from("java/lang/Object"))
+    }
+
+    fn is_category2(&self) -> bool {
+        matches!(self, Type::Long | Type::Double)
+    }
+
+    fn size(&self) -> usize {
+        if self.is_category2() { 2 } else { 1 }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Type::Top => String::from("top"),
+            Type::Int => String::from("int"),
+            Type::Float => String::from("float"),
+            Type::Long => String::from("long"),
+            Type::Double => String::from("double"),
+            Type::Null => String::from("null"),
+            Type::UninitializedThis => String::from("uninitializedThis"),
+            Type::Uninitialized(offset) => format!("uninitialized({})", offset),
+            Type::Reference(name) => format!("'{}'", name.replace('/', ".")),
+            Type::ReturnAddress(subroutine) => format!("returnAddress({})", subroutine)
+        }
+    }
+}
+
+/// The types of the local variables and the operand stack at an instruction.
+#[derive(Clone, PartialEq)]
+struct Frame {
+    locals: Vec<Type>,
+    stack: Vec<Type>,
+    // Set in constructors until `this` is initialized
+    this_uninitialized: bool,
+    // For type inference: the locals that were stored to since entering the current subroutine
+    modified_locals: Vec<bool>,
+}
+
+/// Which classes get verified, like the `-Xverify` option of HotSpot.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Verification {
+    None,
+    // Only the classes that don't come from the boot classpath
+    Remote,
+    All
+}
+
+/// Verifies the code of the methods of a class, before the class gets initialized (JVMS §4.10).
+/// Classes of version 50 and later are type checked against the frames of their StackMapTable attributes.
+/// Older classes, and methods that lost their StackMapTable, get their types inferred instead.
+/// Only classes before version 51 may use jsr and ret, which the type checker doesn't support.
+pub fn verify_class(vm: &Vm, class: &Class) -> JvmResult<()> {
+    let info = match &class.info {
+        Some(info) => info,
+        None => return Ok(())
+    };
+    let allow_subroutines = info.major_version < 51;
+    for method in &info.methods {
+        if let Some(code) = method.get_code() {
+            if info.major_version >= 50 && code.has_stack_map_table() {
+                let result = MethodVerifier::new(vm, class, info, method, code, false, allow_subroutines).verify();
+                // Version 50 classes fall back to type inference when type checking fails, but not on a corrupt table
+                if result.is_ok() || info.major_version > 50 || code.get_stack_map_error().is_some() {
+                    result?;
+                    continue;
+                }
+            }
+            MethodVerifier::new(vm, class, info, method, code, true, allow_subroutines).verify()?;
+        }
+    }
+    Ok(())
+}
+
+/// Verifies a method in one of two modes, which share how each instruction changes the types:
+/// - the type checker of JVMS §4.10.1 goes through the instructions once, in order. Instructions that can't be
+///   reached by falling through need a stack map frame, which gives their types.
+/// - type inference (JVMS §4.10.2) computes the frames itself, by merging the frames of all the ways an instruction
+///   can be reached, until nothing changes anymore.
+///
+/// Protected member access is not checked.
+struct MethodVerifier<'a> {
+    vm: &'a Vm,
+    class: &'a Class,
+    info: &'a ClassInfo,
+    name: &'a str,
+    descriptor: &'a str,
+    is_static: bool,
+    code: &'a ATCode,
+    inference: bool,
+    allow_subroutines: bool,
+    // Whether an instruction starts at an offset
+    boundaries: Vec<bool>,
+    // The stack map frames when type checking, the frames inferred so far otherwise
+    frames: HashMap<usize, Frame>,
+    // For type inference: the instructions whose frame changed since they were last looked at
+    changed: BTreeSet<usize>,
+    // For type inference: the frames at the ret instructions of each subroutine, by where the subroutine starts
+    return_frames: HashMap<usize, Frame>,
+    pc: usize,
+    frame: Frame,
+}
+
+impl<'a> MethodVerifier<'a> {
+    fn new(vm: &'a Vm, class: &'a Class, info: &'a ClassInfo, method: &'a MethodInfo, code: &'a ATCode, inference: bool, allow_subroutines: bool) -> MethodVerifier<'a> {
+        MethodVerifier {
+            vm,
+            class,
+            info,
+            name: method.get_name(&info.constant_pool),
+            descriptor: info.constant_pool.get_string(method.descriptor_index),
+            is_static: method.access_flags.contains(MethodFlags::ACC_STATIC),
+            code,
+            inference,
+            allow_subroutines,
+            boundaries: vec![false; code.code.len()],
+            frames: HashMap::new(),
+            changed: BTreeSet::new(),
+            return_frames: HashMap::new(),
+            pc: 0,
+            frame: Frame { locals: Vec::new(), stack: Vec::new(), this_uninitialized: false, modified_locals: Vec::new() },
+        }
+    }
+
+    fn error(&self, message: impl AsRef<str>) -> Throwable {
+        self.exception("java/lang/VerifyError", message)
+    }
+
+    fn exception(&self, class_name: &str, message: impl AsRef<str>) -> Throwable {
+        Throwable::new(class_name, format!("{} in method {}.{}{} at pc {}",
+            message.as_ref(), self.class.name.replace('/', "."), self.name, self.descriptor, self.pc))
+    }
+
+    fn verify(mut self) -> JvmResult<()> {
+        let code = &self.code.code;
+        if code.is_empty() {
+            return Err(self.error("Code is empty"));
+        }
+        let mut pcs = Vec::new();
+        while self.pc < code.len() {
+            let length = instruction_length(code, self.pc).ok_or_else(|| self.error(format!("Bad instruction {:#04x}", code[self.pc])))?;
+            self.boundaries[self.pc] = true;
+            pcs.push(self.pc);
+            self.pc += length;
+        }
+        self.pc = 0;
+
+        let initial_frame = self.initial_frame()?;
+        if self.inference {
+            self.infer_types(initial_frame)
+        } else {
+            self.check_types(pcs, initial_frame)
+        }
+    }
+
+    fn check_types(&mut self, pcs: Vec<usize>, initial_frame: Frame) -> JvmResult<()> {
+        self.frames = self.read_stack_map(&initial_frame)?;
+        self.check_exception_table()?;
+
+        let mut current = Some(initial_frame);
+        for pc in pcs {
+            self.pc = pc;
+            if let Some(stack_map_frame) = self.frames.get(&pc) {
+                if let Some(frame) = &current && !self.is_frame_assignable(frame, stack_map_frame)? {
+                    return Err(self.error("Instruction type does not match stack map"));
+                }
+                current = Some(stack_map_frame.clone());
+            }
+            self.frame = current.take().ok_or_else(|| self.error("Expecting a stack map frame"))?;
+            self.check_handlers()?;
+            if self.execute()? {
+                current = Some(self.frame.clone());
+            }
+        }
+        if current.is_some() {
+            return Err(self.error("Falling off the end of the code"));
+        }
+        Ok(())
+    }
+
+    fn infer_types(&mut self, initial_frame: Frame) -> JvmResult<()> {
+        self.check_exception_table()?;
+        self.merge_into(0, initial_frame)?;
+        while let Some(pc) = self.changed.pop_first() {
+            self.pc = pc;
+            self.frame = self.frames[&pc].clone();
+            self.check_handlers()?;
+            let falls_through = self.execute()?;
+            // Handlers also see the locals an instruction stored to
+            self.check_handlers()?;
+            if falls_through {
+                let next = pc + instruction_length(&self.code.code, pc).unwrap();
+                if next >= self.code.code.len() {
+                    return Err(self.error("Falling off the end of the code"));
+                }
+                self.merge_into(next, self.frame.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges a frame into the frame inferred for an instruction so far, to look at it again when that changed.
+    fn merge_into(&mut self, pc: usize, frame: Frame) -> JvmResult<()> {
+        let merged = match self.frames.get(&pc) {
+            Some(existing) => self.merge_frames(existing, &frame)?,
+            None => frame
+        };
+        if self.frames.get(&pc) != Some(&merged) {
+            self.frames.insert(pc, merged);
+            self.changed.insert(pc);
+        }
+        Ok(())
+    }
+
+    fn merge_frames(&self, existing: &Frame, frame: &Frame) -> JvmResult<Frame> {
+        if existing.stack.len() != frame.stack.len() {
+            return Err(self.error(format!("Inconsistent stack height {} != {}", existing.stack.len(), frame.stack.len())));
+        }
+        let mut locals = Vec::with_capacity(existing.locals.len());
+        for (existing, local) in existing.locals.iter().zip(&frame.locals) {
+            // Locals that differ in a way that can't be merged just become unusable
+            locals.push(self.merge_types(existing, local)?.unwrap_or(Type::Top));
+        }
+        let mut stack = Vec::with_capacity(existing.stack.len());
+        for (existing, value) in existing.stack.iter().zip(&frame.stack) {
+            let merged = self.merge_types(existing, value)?;
+            stack.push(merged.ok_or_else(|| self.error(format!("Mismatched stack types: {} and {}", existing.describe(), value.describe())))?);
+        }
+        Ok(Frame {
+            locals,
+            stack,
+            this_uninitialized: existing.this_uninitialized || frame.this_uninitialized,
+            modified_locals: existing.modified_locals.iter().zip(&frame.modified_locals).map(|(existing, modified)| *existing || *modified).collect(),
+        })
+    }
+
+    fn merge_types(&self, first: &Type, second: &Type) -> JvmResult<Option<Type>> {
+        match (first, second) {
+            (first, second) if first == second => Ok(Some(first.clone())),
+            (Type::Null, Type::Reference(name)) | (Type::Reference(name), Type::Null) => Ok(Some(Type::Reference(name.clone()))),
+            (Type::Reference(first), Type::Reference(second)) => Ok(Some(Type::Reference(self.common_superclass(first, second)?))),
+            other => Ok(None)
+        }
+    }
+
+    /// The first common superclass of two classes or arrays. Interfaces are treated like Object.
+    fn common_superclass(&self, first: &str, second: &str) -> JvmResult<String> {
+        if first == second {
+            return Ok(first.to_string());
+        }
+        if let (Some(first_component), Some(second_component)) = (first.strip_prefix('['), second.strip_prefix('[')) {
+            if matches!(first_component.as_bytes()[0], b'L' | b'[') && matches!(second_component.as_bytes()[0], b'L' | b'[') {
+                let component = self.common_superclass(component_name(first_component), component_name(second_component))?;
+                return Ok(if component.starts_with('[') { format!("[{}", component) } else { format!("[L{};", component) });
+            }
+            return Ok(String::from("java/lang/Object"));
+        }
+        if first.starts_with('[') || second.starts_with('[') {
+            return Ok(String::from("java/lang/Object"));
+        }
+        let (first, second) = (self.vm.load_class(first)?, self.vm.load_class(second)?);
+        if first.is_interface() || second.is_interface() {
+            return Ok(String::from("java/lang/Object"));
+        }
+        let mut superclasses = Vec::new();
+        let mut class = Some(first);
+        while let Some(current) = class {
+            class = current.super_class.clone();
+            superclasses.push(current.name.clone());
+        }
+        let mut class = Some(second);
+        while let Some(current) = class {
+            if superclasses.contains(&current.name) {
+                return Ok(current.name.clone());
+            }
+            class = current.super_class.clone();
+        }
+        Ok(String::from("java/lang/Object"))
+    }
+
+    /// The types of `this` and the arguments, with `this` uninitialized in constructors other than that of Object.
+    fn initial_frame(&self) -> JvmResult<Frame> {
+        let mut locals = Vec::new();
+        let mut this_uninitialized = false;
+        if !self.is_static {
+            if self.name == "<init>" && self.class.name != "java/lang/Object" {
+                locals.push(Type::UninitializedThis);
+                this_uninitialized = true;
+            } else {
+                locals.push(Type::Reference(self.class.name.clone()));
+            }
+        }
+        for argument in argument_descriptors(self.descriptor) {
+            let argument = Type::from_descriptor(argument);
+            let is_category2 = argument.is_category2();
+            locals.push(argument);
+            if is_category2 {
+                locals.push(Type::Top);
+            }
+        }
+        if locals.len() > self.code.max_locals as usize {
+            return Err(self.error("Arguments can't fit into locals"));
+        }
+        locals.resize(self.code.max_locals as usize, Type::Top);
+        let modified_locals = if self.inference { vec![false; locals.len()] } else { Vec::new() };
+        Ok(Frame { locals, stack: Vec::new(), this_uninitialized, modified_locals })
+    }
+
+    fn stack_map_type(&self, verification_type: &VerificationTypeInfo) -> JvmResult<Type> {
+        let result = match verification_type {
+            VerificationTypeInfo::Top => Type::Top,
+            VerificationTypeInfo::Integer => Type::Int,
+            VerificationTypeInfo::Float => Type::Float,
+            VerificationTypeInfo::Long => Type::Long,
+            VerificationTypeInfo::Double => Type::Double,
+            VerificationTypeInfo::Null => Type::Null,
+            VerificationTypeInfo::UninitializedThis => Type::UninitializedThis,
+            VerificationTypeInfo::Object(index) => Type::Reference(self.class_name(*index)?.to_string()),
+            VerificationTypeInfo::Uninitialized(offset) => {
+                let offset = *offset as usize;
+                if offset >= self.boundaries.len() || !self.boundaries[offset] || self.code.code[offset] != NEW {
+                    return Err(self.error(format!("Uninitialized type in stack map frame does not point at a new instruction: {}", offset)));
+                }
+                Type::Uninitialized(offset as u16)
+            }
+        };
+        Ok(result)
+    }
+
+    fn stack_map_types(&self, verification_types: &[VerificationTypeInfo], expand: bool) -> JvmResult<Vec<Type>> {
+        let mut types = Vec::with_capacity(verification_types.len());
+        for verification_type in verification_types {
+            let stack_map_type = self.stack_map_type(verification_type)?;
+            let is_category2 = stack_map_type.is_category2();
+            types.push(stack_map_type);
+            if expand && is_category2 {
+                types.push(Type::Top);
+            }
+        }
+        Ok(types)
+    }
+
+    /// Turns the StackMapTable into a full frame for each offset that has one (JVMS §4.7.4).
+    fn read_stack_map(&mut self, initial_frame: &Frame) -> JvmResult<HashMap<usize, Frame>> {
+        let max_locals = self.code.max_locals as usize;
+        let mut frames = HashMap::new();
+        // The locals of the previous frame, without the Top that fills up the rest
+        let mut locals = initial_frame.locals[..self.arguments_size()].to_vec();
+        let mut offset: Option<usize> = None;
+        for stack_map_frame in self.code.get_stack_map_frames() {
+            let frame_offset = match offset {
+                Some(offset) => offset + stack_map_frame.offset_delta() as usize + 1,
+                None => stack_map_frame.offset_delta() as usize
+            };
+            offset = Some(frame_offset);
+            let stack = match stack_map_frame {
+                StackMapFrame::Same { .. } => Vec::new(),
+                StackMapFrame::SameLocals1StackItem { stack, .. } => self.stack_map_types(std::slice::from_ref(stack), false)?,
+                StackMapFrame::Chop { frame_type, .. } => {
+                    for _local in 0..251 - *frame_type {
+                        let removed = locals.pop();
+                        if removed == Some(Type::Top) && locals.last().is_some_and(Type::is_category2) {
+                            locals.pop();
+                        }
+                        if removed.is_none() {
+                            return Err(self.error("Chop frame removes more locals than there are"));
+                        }
+                    }
+                    Vec::new()
+                },
+                StackMapFrame::Append { locals: appended, .. } => {
+                    locals.extend(self.stack_map_types(appended, true)?);
+                    Vec::new()
+                },
+                StackMapFrame::Full { locals: full_locals, stack, .. } => {
+                    locals = self.stack_map_types(full_locals, true)?;
+                    self.stack_map_types(stack, false)?
+                }
+            };
+            if frame_offset >= self.boundaries.len() || !self.boundaries[frame_offset] {
+                return Err(self.error(format!("Stack map frame at offset {} is not at an instruction", frame_offset)));
+            }
+            if locals.len() > max_locals {
+                return Err(self.error(format!("Stack map frame at offset {} has more locals than max_locals", frame_offset)));
+            }
+            if stack.iter().map(Type::size).sum::<usize>() > self.code.max_stack as usize {
+                return Err(self.error(format!("Stack map frame at offset {} has a bigger stack than max_stack", frame_offset)));
+            }
+            let mut frame_locals = locals.clone();
+            frame_locals.resize(max_locals, Type::Top);
+            let this_uninitialized = frame_locals.contains(&Type::UninitializedThis) || stack.contains(&Type::UninitializedThis);
+            frames.insert(frame_offset, Frame { locals: frame_locals, stack, this_uninitialized, modified_locals: Vec::new() });
+        }
+        // A table that can't be read is a format error, which the JVM finds when it loads the class
+        if let Some(error) = self.code.get_stack_map_error() {
+            let message = match offset {
+                Some(offset) => {
+                    self.pc = offset;
+                    format!("{} after the stack map frame", error)
+                },
+                None => error.to_string()
+            };
+            return Err(self.exception("java/lang/ClassFormatError", message));
+        }
+        Ok(frames)
+    }
+
+    /// The number of local variables taken by `this` and the arguments.
+    fn arguments_size(&self) -> usize {
+        let arguments: usize = argument_descriptors(self.descriptor).iter().map(|argument| Type::from_descriptor(argument).size()).sum();
+        arguments + if self.is_static { 0 } else { 1 }
+    }
+
+    fn check_exception_table(&self) -> JvmResult<()> {
+        let code_length = self.code.code.len();
+        for entry in &self.code.exceptions {
+            let (start, end, handler) = (entry.start_pc as usize, entry.end_pc as usize, entry.handler_pc as usize);
+            let at_instruction = |pc: usize| pc < code_length && self.boundaries[pc];
+            if start >= end || !at_instruction(start) || !(end == code_length || at_instruction(end)) {
+                return Err(self.error(format!("Illegal exception table range {}-{}", start, end)));
+            }
+            if !at_instruction(handler) {
+                return Err(self.error(format!("Illegal exception table handler {}", handler)));
+            }
+            if !self.inference && !self.frames.contains_key(&handler) {
+                return Err(self.error(format!("Expecting a stack map frame at exception handler {}", handler)));
+            }
+            let catch_type = self.catch_type(entry.catch_type)?;
+            if !self.is_assignable(&catch_type, &Type::Reference(String::from("java/lang/Throwable")))? {
+                return Err(self.error(format!("Catch type is not a subclass of Throwable: {}", catch_type.describe())));
+            }
+        }
+        Ok(())
+    }
+
+    fn catch_type(&self, index: u16) -> JvmResult<Type> {
+        match index {
+            0 => Ok(Type::Reference(String::from("java/lang/Throwable"))),
+            index => Ok(Type::Reference(self.class_name(index)?.to_string()))
+        }
+    }
+
+    /// An exception can be thrown by any instruction in a try block, so its handler must accept the locals there.
+    fn check_handlers(&mut self) -> JvmResult<()> {
+        for entry in &self.code.exceptions {
+            if (entry.start_pc as usize..entry.end_pc as usize).contains(&self.pc) {
+                let handler_frame = Frame {
+                    locals: self.frame.locals.clone(),
+                    stack: vec![self.catch_type(entry.catch_type)?],
+                    this_uninitialized: self.frame.this_uninitialized,
+                    modified_locals: self.frame.modified_locals.clone(),
+                };
+                if self.inference {
+                    self.merge_into(entry.handler_pc as usize, handler_frame)?;
+                } else if !self.is_frame_assignable(&handler_frame, &self.frames[&(entry.handler_pc as usize)])? {
+                    return Err(self.error(format!("Stack map frame of exception handler {} does not match", entry.handler_pc)));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn is_frame_assignable(&self, from: &Frame, to: &Frame) -> JvmResult<bool> {
+        if from.stack.len() != to.stack.len() || (from.this_uninitialized && !to.this_uninitialized) {
+            return Ok(false);
+        }
+        for (from, to) in from.locals.iter().zip(&to.locals).chain(from.stack.iter().zip(&to.stack)) {
+            if !self.is_assignable(from, to)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// The subtyping of verification types (JVMS §4.10.1.2). Interfaces are treated like Object.
+    fn is_assignable(&self, from: &Type, to: &Type) -> JvmResult<bool> {
+        match (from, to) {
+            (from, to) if from == to => Ok(true),
+            (other, Type::Top) => Ok(true),
+            (Type::Null, Type::Reference(_)) => Ok(true),
+            (Type::Reference(from), Type::Reference(to)) => self.is_reference_assignable(from, to),
+            other => Ok(false)
+        }
+    }
+
+    fn is_reference_assignable(&self, from: &str, to: &str) -> JvmResult<bool> {
+        if from == to || to == "java/lang/Object" {
+            return Ok(true);
+        }
+        if let Some(to_component) = to.strip_prefix('[') {
+            let from_component = match from.strip_prefix('[') {
+                Some(from_component) => from_component,
+                None => return Ok(false)
+            };
+            return match (from_component.as_bytes()[0], to_component.as_bytes()[0]) {
+                (b'L' | b'[', b'L' | b'[') => self.is_reference_assignable(component_name(from_component), component_name(to_component)),
+                other => Ok(from_component == to_component)
+            };
+        }
+        if from.starts_with('[') {
+            return Ok(to == "java/lang/Cloneable" || to == "java/io/Serializable");
+        }
+        if self.vm.load_class(to)?.is_interface() {
+            return Ok(true);
+        }
+        let mut class = Some(self.vm.load_class(from)?);
+        while let Some(current) = class {
+            if current.name == to {
+                return Ok(true);
+            }
+            class = current.super_class.clone();
+        }
+        Ok(false)
+    }
+
+    fn constant(&self, index: u16) -> JvmResult<&'a ConstantPoolItem> {
+        self.info.constant_pool.constants.get(index as usize)
+            .filter(|_| index != 0)
+            .ok_or_else(|| self.error(format!("Illegal constant pool index {}", index)))
+    }
+
+    fn class_name(&self, index: u16) -> JvmResult<&'a str> {
+        match self.constant(index)? {
+            ConstantPoolItem::CPClassInfo { name_index } => Ok(self.info.constant_pool.get_string(*name_index)),
+            other => Err(self.error(format!("Illegal type at constant pool entry {}, expected a class", index)))
+        }
+    }
+
+    /// Returns the class, name and descriptor of a field or method reference.
+    fn member_ref(&self, index: u16, is_field: bool) -> JvmResult<(&'a str, &'a str, &'a str)> {
+        let constant_pool = &self.info.constant_pool;
+        let (class_index, name_and_type_index) = match self.constant(index)? {
+            ConstantPoolItem::CPFieldRef { class_index, name_and_type_index } if is_field => (*class_index, *name_and_type_index),
+            ConstantPoolItem::CPMethodRef { class_index, name_and_type_index } |
+            ConstantPoolItem::CPInterfaceMethodRef { class_index, name_and_type_index } if !is_field => (*class_index, *name_and_type_index),
+            other => return Err(self.error(format!("Illegal type at constant pool entry {}", index)))
+        };
+        let (name_index, descriptor_index) = constant_pool.get_name_and_type(name_and_type_index);
+        Ok((self.class_name(class_index)?, constant_pool.get_string(name_index), constant_pool.get_string(descriptor_index)))
+    }
+
+    fn read_u8(&self, offset: usize) -> u8 {
+        self.code.code[self.pc + offset]
+    }
+
+    fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_be_bytes([self.code.code[self.pc + offset], self.code.code[self.pc + offset + 1]])
+    }
+
+    fn read_i32(&self, offset: usize) -> i32 {
+        i32::from_be_bytes(self.code.code[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn push(&mut self, value: Type) -> JvmResult<()> {
+        let size = self.frame.stack.iter().map(Type::size).sum::<usize>() + value.size();
+        if size > self.code.max_stack as usize {
+            return Err(self.error("Operand stack overflow"));
+        }
+        self.frame.stack.push(value);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> JvmResult<Type> {
+        self.frame.stack.pop().ok_or_else(|| self.error("Attempt to pop empty stack"))
+    }
+
+    fn pop_category1(&mut self) -> JvmResult<Type> {
+        let value = self.pop()?;
+        if value.is_category2() {
+            return Err(self.error(format!("Bad type on operand stack: expected a category 1 value, found {}", value.describe())));
+        }
+        Ok(value)
+    }
+
+    fn pop_expecting(&mut self, expected: &Type) -> JvmResult<Type> {
+        let value = self.pop()?;
+        if !self.is_assignable(&value, expected)? {
+            return Err(self.error(format!("Bad type on operand stack: expected {}, found {}", expected.describe(), value.describe())));
+        }
+        Ok(value)
+    }
+
+    fn pop_reference(&mut self) -> JvmResult<Type> {
+        self.pop_expecting(&Type::object())
+    }
+
+    /// Pops an array, which should have one of the given component types unless it's null.
+    fn pop_array(&mut self, components: &[&str]) -> JvmResult<Type> {
+        let array = self.pop()?;
+        let is_array = match &array {
+            Type::Null => true,
+            Type::Reference(name) => name.starts_with('[') && (components.is_empty() || components.iter().any(|component| {
+                match *component {
+                    // Any array of references
+                    "L" => name.starts_with("[L") || name.starts_with("[["),
+                    component => &name[1..] == component
+                }
+            })),
+            other => false
+        };
+        if !is_array {
+            return Err(self.error(format!("Bad type on operand stack: expected an array of {}, found {}", components.join(" or "), array.describe())));
+        }
+        Ok(array)
+    }
+
+    fn local_index(&self, index: usize, size: usize) -> JvmResult<usize> {
+        if index + size > self.frame.locals.len() {
+            return Err(self.error(format!("Illegal local variable number {}", index)));
+        }
+        Ok(index)
+    }
+
+    fn load(&mut self, index: usize, expected: Type) -> JvmResult<()> {
+        let index = self.local_index(index, expected.size())?;
+        let value = self.frame.locals[index].clone();
+        let matches = match &expected {
+            // aload can also load objects that are not initialized yet
+            Type::Reference(_) => matches!(value, Type::UninitializedThis | Type::Uninitialized(_)) || self.is_assignable(&value, &expected)?,
+            expected => value == *expected
+        };
+        if !matches {
+            return Err(self.error(format!("Bad local variable type: expected {}, found {}", expected.describe(), value.describe())));
+        }
+        self.push(value)
+    }
+
+    fn check_int_local(&self, index: usize) -> JvmResult<()> {
+        let index = self.local_index(index, 1)?;
+        if self.frame.locals[index] != Type::Int {
+            return Err(self.error(format!("Bad local variable type: expected int, found {}", self.frame.locals[index].describe())));
+        }
+        Ok(())
+    }
+
+    fn store(&mut self, index: usize, value: Type) -> JvmResult<()> {
+        let index = self.local_index(index, value.size())?;
+        let mut changed = index..index + value.size();
+        // Overwriting the second half of a long or double makes the first half unusable
+        if index > 0 && self.frame.locals[index - 1].is_category2() {
+            self.frame.locals[index - 1] = Type::Top;
+            changed.start -= 1;
+        }
+        if value.is_category2() {
+            self.frame.locals[index + 1] = Type::Top;
+        }
+        self.frame.locals[index] = value;
+        if !self.frame.modified_locals.is_empty() {
+            self.frame.modified_locals[changed].fill(true);
+        }
+        Ok(())
+    }
+
+    fn store_popped(&mut self, index: usize, expected: Type) -> JvmResult<()> {
+        let value = match &expected {
+            // astore also takes objects that are not initialized yet, and the return addresses of subroutines
+            Type::Reference(_) => match self.pop()? {
+                value @ (Type::UninitializedThis | Type::Uninitialized(_) | Type::ReturnAddress(_)) => value,
+                value if self.is_assignable(&value, &expected)? => value,
+                value => return Err(self.error(format!("Bad type on operand stack: expected {}, found {}", expected.describe(), value.describe())))
+            },
+            expected => self.pop_expecting(expected)?
+        };
+        self.store(index, value)
+    }
+
+    fn branch_target(&self, offset: i32) -> JvmResult<usize> {
+        let target = self.pc as i64 + offset as i64;
+        if target < 0 || target as usize >= self.boundaries.len() || !self.boundaries[target as usize] {
+            return Err(self.error(format!("Illegal target of jump or branch: {}", target)));
+        }
+        Ok(target as usize)
+    }
+
+    /// Checks that the current frame matches the stack map frame of a jump target, or merges it into the target.
+    fn branch(&mut self, offset: i32) -> JvmResult<()> {
+        let target = self.branch_target(offset)?;
+        if self.inference {
+            return self.merge_into(target, self.frame.clone());
+        }
+        let stack_map_frame = self.frames.get(&target)
+            .ok_or_else(|| self.error(format!("Expecting a stack map frame at branch target {}", target)))?;
+        if !self.is_frame_assignable(&self.frame, stack_map_frame)? {
+            return Err(self.error(format!("Inconsistent stack map frame at branch target {}", target)));
+        }
+        Ok(())
+    }
+
+    /// Enters a subroutine. Its ret continues after each jsr that calls it, with the locals of that caller,
+    /// except for the ones the subroutine stored to (JVMS §4.10.2.4).
+    fn jump_to_subroutine(&mut self, offset: i32) -> JvmResult<()> {
+        let subroutine = self.branch_target(offset)?;
+        let caller = self.frame.clone();
+        self.push(Type::ReturnAddress(subroutine as u16))?;
+        let mut entry = self.frame.clone();
+        entry.modified_locals.fill(false);
+        self.merge_into(subroutine, entry)?;
+        if let Some(return_frame) = self.return_frames.get(&subroutine).cloned() {
+            self.continue_after_jsr(self.pc, &caller, &return_frame)?;
+        }
+        Ok(())
+    }
+
+    fn return_from_subroutine(&mut self, index: usize) -> JvmResult<()> {
+        let index = self.local_index(index, 1)?;
+        let subroutine = match &self.frame.locals[index] {
+            Type::ReturnAddress(subroutine) => *subroutine as usize,
+            other => return Err(self.error(format!("Bad local variable type: expected returnAddress, found {}", other.describe())))
+        };
+        let return_frame = match self.return_frames.get(&subroutine) {
+            Some(existing) => self.merge_frames(existing, &self.frame)?,
+            None => self.frame.clone()
+        };
+        self.return_frames.insert(subroutine, return_frame.clone());
+        let callers: Vec<usize> = self.frames.keys().copied().filter(|pc| self.subroutine_called_at(*pc) == Some(subroutine)).collect();
+        for caller in callers {
+            let caller_frame = self.frames[&caller].clone();
+            self.continue_after_jsr(caller, &caller_frame, &return_frame)?;
+        }
+        Ok(())
+    }
+
+    fn continue_after_jsr(&mut self, jsr_pc: usize, caller: &Frame, return_frame: &Frame) -> JvmResult<()> {
+        let next = jsr_pc + instruction_length(&self.code.code, jsr_pc).unwrap();
+        if next >= self.code.code.len() {
+            return Err(self.error("Falling off the end of the code"));
+        }
+        let locals = caller.locals.iter().zip(&return_frame.locals).zip(&return_frame.modified_locals)
+            .map(|((caller, returned), modified)| if *modified { returned.clone() } else { caller.clone() })
+            .collect();
+        let frame = Frame {
+            locals,
+            stack: return_frame.stack.clone(),
+            this_uninitialized: return_frame.this_uninitialized,
+            modified_locals: caller.modified_locals.iter().zip(&return_frame.modified_locals).map(|(caller, returned)| *caller || *returned).collect(),
+        };
+        self.merge_into(next, frame)
+    }
+
+    /// Returns where the subroutine starts if the instruction at this offset is a jsr.
+    fn subroutine_called_at(&self, pc: usize) -> Option<usize> {
+        let code = &self.code.code;
+        let offset = match code[pc] {
+            JSR => i16::from_be_bytes([code[pc + 1], code[pc + 2]]) as i64,
+            JSR_W => i32::from_be_bytes(code[pc + 1..pc + 5].try_into().unwrap()) as i64,
+            other => return None
+        };
+        Some((pc as i64 + offset) as usize)
+    }
+
+    fn subroutine_error(&self, opcode: u8) -> Throwable {
+        if self.allow_subroutines {
+            self.error(format!("{} is not supported by the type checker", opcode_name(opcode)))
+        } else {
+            self.error(format!("{} is not allowed in classes of version 51 and later", opcode_name(opcode)))
+        }
+    }
+
+    fn return_type(&self) -> &'a str {
+        &self.descriptor[self.descriptor.find(')').unwrap() + 1..]
+    }
+
+    fn check_return(&mut self, opcode: u8) -> JvmResult<()> {
+        let return_type = self.return_type();
+        let matches = match opcode {
+            IRETURN => matches!(return_type, "Z" | "B" | "C" | "S" | "I"),
+            LRETURN => return_type == "J",
+            FRETURN => return_type == "F",
+            DRETURN => return_type == "D",
+            ARETURN => return_type.starts_with('L') || return_type.starts_with('['),
+            other => return_type == "V"
+        };
+        if !matches {
+            return Err(self.error(format!("Method expects a return value of type {}", return_type)));
+        }
+        if opcode != RETURN {
+            self.pop_expecting(&Type::from_descriptor(return_type))?;
+        } else if self.frame.this_uninitialized {
+            return Err(self.error("Constructor must call super() or this() before return"));
+        }
+        Ok(())
+    }
+
+    /// Replaces every occurrence of an uninitialized type, after a constructor was called on it.
+    fn initialize_object(&mut self, uninitialized: &Type, initialized: Type) {
+        for value in self.frame.locals.iter_mut().chain(self.frame.stack.iter_mut()) {
+            if value == uninitialized {
+                *value = initialized.clone();
+            }
+        }
+        if *uninitialized == Type::UninitializedThis {
+            self.frame.this_uninitialized = false;
+        }
+    }
+
+    fn invoke(&mut self, opcode: u8) -> JvmResult<()> {
+        let index = self.read_u16(1);
+        let (class_name, name, descriptor) = match opcode {
+            INVOKEDYNAMIC => match self.constant(index)? {
+                ConstantPoolItem::CPInvokeDynamicInfo { name_and_type_index, .. } => {
+                    if self.read_u16(3) != 0 {
+                        return Err(self.error("The last bytes of invokedynamic must be zero"));
+                    }
+                    let (name_index, descriptor_index) = self.info.constant_pool.get_name_and_type(*name_and_type_index);
+                    ("java/lang/Object", self.info.constant_pool.get_string(name_index), self.info.constant_pool.get_string(descriptor_index))
+                },
+                other => return Err(self.error(format!("Illegal type at constant pool entry {}, expected an invokedynamic", index)))
+            },
+            other => self.member_ref(index, false)?
+        };
+        if name.starts_with('<') && !(opcode == INVOKESPECIAL && name == "<init>") {
+            return Err(self.error(format!("Illegal call to internal method {}", name)));
+        }
+        let arguments = argument_descriptors(descriptor);
+        if opcode == INVOKEINTERFACE {
+            let slots: usize = arguments.iter().map(|argument| Type::from_descriptor(argument).size()).sum();
+            if self.read_u8(3) as usize != slots + 1 || self.read_u8(4) != 0 {
+                return Err(self.error("Inconsistent args count operand in invokeinterface"));
+            }
+        }
+        for argument in arguments.iter().rev() {
+            self.pop_expecting(&Type::from_descriptor(argument))?;
+        }
+        if opcode == INVOKESPECIAL && name == "<init>" {
+            let receiver = self.pop()?;
+            let initialized = match &receiver {
+                Type::UninitializedThis => {
+                    let super_name = self.class.super_class.as_ref().map(|super_class| super_class.name.as_str());
+                    if class_name != self.class.name && Some(class_name) != super_name {
+                        return Err(self.error(format!("Bad <init> method call on uninitializedThis: {}", class_name.replace('/', "."))));
+                    }
+                    self.class.name.clone()
+                },
+                Type::Uninitialized(offset) => {
+                    let created = self.class_name(u16::from_be_bytes([self.code.code[*offset as usize + 1], self.code.code[*offset as usize + 2]]))?;
+                    if created != class_name {
+                        return Err(self.error(format!("Call to wrong <init> method: {} on {}", class_name.replace('/', "."), created.replace('/', "."))));
+                    }
+                    created.to_string()
+                },
+                other => return Err(self.error(format!("Bad type on operand stack: expected an uninitialized object, found {}", receiver.describe())))
+            };
+            self.initialize_object(&receiver, Type::Reference(initialized));
+        } else if opcode == INVOKESPECIAL {
+            self.pop_expecting(&Type::Reference(self.class.name.clone()))?;
+        } else if opcode == INVOKEVIRTUAL || opcode == INVOKEINTERFACE {
+            self.pop_expecting(&Type::Reference(class_name.to_string()))?;
+        }
+        let return_type = &descriptor[descriptor.find(')').unwrap() + 1..];
+        if return_type != "V" {
+            self.push(Type::from_descriptor(return_type))?;
+        }
+        Ok(())
+    }
+
+    fn field_access(&mut self, opcode: u8) -> JvmResult<()> {
+        let (class_name, name, descriptor) = self.member_ref(self.read_u16(1), true)?;
+        let field_type = Type::from_descriptor(descriptor);
+        let owner = Type::Reference(class_name.to_string());
+        match opcode {
+            GETSTATIC => self.push(field_type)?,
+            PUTSTATIC => {
+                self.pop_expecting(&field_type)?;
+            },
+            GETFIELD => {
+                self.pop_expecting(&owner)?;
+                self.push(field_type)?;
+            },
+            other => {
+                self.pop_expecting(&field_type)?;
+                // Constructors can set the fields of their class before calling super()
+                let declared_here = class_name == self.class.name && self.info.fields.iter()
+                    .any(|field| field.get_name(&self.info.constant_pool) == name && self.info.constant_pool.get_string(field.descriptor_index) == descriptor);
+                if self.frame.stack.last() == Some(&Type::UninitializedThis) && declared_here {
+                    self.pop()?;
+                } else {
+                    self.pop_expecting(&owner)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_constant(&mut self, index: u16, wide: bool) -> JvmResult<()> {
+        let value = match (self.constant(index)?, wide) {
+            (ConstantPoolItem::CPIntegerInfo { .. }, false) => Type::Int,
+            (ConstantPoolItem::CPFloatInfo { .. }, false) => Type::Float,
+            (ConstantPoolItem::CPStringInfo { .. }, false) => Type::Reference(String::from("java/lang/String")),
+            (ConstantPoolItem::CPClassInfo { .. }, false) => Type::Reference(String::from("java/lang/Class")),
+            (ConstantPoolItem::CPMethodTypeInfo { .. }, false) => Type::Reference(String::from("java/lang/invoke/MethodType")),
+            (ConstantPoolItem::CPMethodHandleInfo { .. }, false) => Type::Reference(String::from("java/lang/invoke/MethodHandle")),
+            (ConstantPoolItem::CPLongInfo { .. }, true) => Type::Long,
+            (ConstantPoolItem::CPDoubleInfo { .. }, true) => Type::Double,
+            (ConstantPoolItem::CPDynamicInfo { name_and_type_index, .. }, wide) => {
+                let (_, descriptor_index) = self.info.constant_pool.get_name_and_type(*name_and_type_index);
+                let value = Type::from_descriptor(self.info.constant_pool.get_string(descriptor_index));
+                if value.is_category2() != wide {
+                    return Err(self.error(format!("Illegal type of dynamic constant {} for ldc", index)));
+                }
+                value
+            },
+            other => return Err(self.error(format!("Illegal type at constant pool entry {} for ldc", index)))
+        };
+        self.push(value)
+    }
+
+    /// Checks an instruction and applies it to the current frame. Returns whether the next instruction can follow it.
+    fn execute(&mut self) -> JvmResult<bool> {
+        let opcode = self.read_u8(0);
+        match opcode {
+            NOP => {},
+            ACONST_NULL => self.push(Type::Null)?,
+            ICONST_M1..=ICONST_5 | BIPUSH | SIPUSH => self.push(Type::Int)?,
+            LCONST_0 | LCONST_1 => self.push(Type::Long)?,
+            FCONST_0..=FCONST_2 => self.push(Type::Float)?,
+            DCONST_0 | DCONST_1 => self.push(Type::Double)?,
+            LDC => self.load_constant(self.read_u8(1) as u16, false)?,
+            LDC_W => self.load_constant(self.read_u16(1), false)?,
+            LDC2_W => self.load_constant(self.read_u16(1), true)?,
+
+            ILOAD..=ALOAD => self.load(self.read_u8(1) as usize, load_store_type(opcode - ILOAD))?,
+            ILOAD_0..=ALOAD_3 => self.load(((opcode - ILOAD_0) % 4) as usize, load_store_type((opcode - ILOAD_0) / 4))?,
+            ISTORE..=ASTORE => self.store_popped(self.read_u8(1) as usize, load_store_type(opcode - ISTORE))?,
+            ISTORE_0..=ASTORE_3 => self.store_popped(((opcode - ISTORE_0) % 4) as usize, load_store_type((opcode - ISTORE_0) / 4))?,
+            WIDE => {
+                let index = self.read_u16(2) as usize;
+                match self.read_u8(1) {
+                    wide_opcode @ ILOAD..=ALOAD => self.load(index, load_store_type(wide_opcode - ILOAD))?,
+                    wide_opcode @ ISTORE..=ASTORE => self.store_popped(index, load_store_type(wide_opcode - ISTORE))?,
+                    IINC => self.check_int_local(index)?,
+                    RET if self.inference && self.allow_subroutines => {
+                        self.return_from_subroutine(index)?;
+                        return Ok(false);
+                    },
+                    RET => return Err(self.subroutine_error(RET)),
+                    other => return Err(self.error(format!("Bad wide instruction {:#04x}", other)))
+                }
+            },
+
+            IALOAD..=SALOAD => {
+                self.pop_expecting(&Type::Int)?;
+                let (components, value): (&[&str], Type) = match opcode {
+                    IALOAD => (&["I"], Type::Int),
+                    LALOAD => (&["J"], Type::Long),
+                    FALOAD => (&["F"], Type::Float),
+                    DALOAD => (&["D"], Type::Double),
+                    BALOAD => (&["B", "Z"], Type::Int),
+                    CALOAD => (&["C"], Type::Int),
+                    SALOAD => (&["S"], Type::Int),
+                    other => (&["L"], Type::Null)
+                };
+                let array = self.pop_array(components)?;
+                let value = match (opcode, array) {
+                    (AALOAD, Type::Reference(name)) => Type::from_descriptor(&name[1..]),
+                    other => value
+                };
+                self.push(value)?;
+            },
+            IASTORE..=SASTORE => {
+                let (components, value): (&[&str], Type) = match opcode {
+                    IASTORE => (&["I"], Type::Int),
+                    LASTORE => (&["J"], Type::Long),
+                    FASTORE => (&["F"], Type::Float),
+                    DASTORE => (&["D"], Type::Double),
+                    BASTORE => (&["B", "Z"], Type::Int),
+                    CASTORE => (&["C"], Type::Int),
+                    SASTORE => (&["S"], Type::Int),
+                    other => (&["L"], Type::object())
+                };
+                self.pop_expecting(&value)?;
+                self.pop_expecting(&Type::Int)?;
+                self.pop_array(components)?;
+            },
+
+            POP => {
+                self.pop_category1()?;
+            },
+            POP2 => {
+                if !self.pop()?.is_category2() {
+                    self.pop_category1()?;
+                }
+            },
+            DUP => {
+                let value = self.pop_category1()?;
+                self.push(value.clone())?;
+                self.push(value)?;
+            },
+            DUP_X1 => {
+                let (value1, value2) = (self.pop_category1()?, self.pop_category1()?);
+                for value in [value1.clone(), value2, value1] {
+                    self.push(value)?;
+                }
+            },
+            DUP_X2 => {
+                let (value1, value2) = (self.pop_category1()?, self.pop()?);
+                let below = if value2.is_category2() { vec![value2] } else { vec![self.pop_category1()?, value2] };
+                self.push(value1.clone())?;
+                for value in below {
+                    self.push(value)?;
+                }
+                self.push(value1)?;
+            },
+            DUP2 => {
+                let value1 = self.pop()?;
+                let values = if value1.is_category2() { vec![value1] } else { vec![self.pop_category1()?, value1] };
+                for value in values.iter().chain(&values) {
+                    self.push(value.clone())?;
+                }
+            },
+            DUP2_X1 => {
+                let value1 = self.pop()?;
+                let top = if value1.is_category2() { vec![value1] } else { vec![self.pop_category1()?, value1] };
+                let below = self.pop_category1()?;
+                for value in top.iter().chain([&below]).chain(&top) {
+                    self.push(value.clone())?;
+                }
+            },
+            DUP2_X2 => {
+                let value1 = self.pop()?;
+                let top = if value1.is_category2() { vec![value1] } else { vec![self.pop_category1()?, value1] };
+                let value3 = self.pop()?;
+                let below = if value3.is_category2() { vec![value3] } else { vec![self.pop_category1()?, value3] };
+                for value in top.iter().chain(&below).chain(&top) {
+                    self.push(value.clone())?;
+                }
+            },
+            SWAP => {
+                let (value1, value2) = (self.pop_category1()?, self.pop_category1()?);
+                self.push(value1)?;
+                self.push(value2)?;
+            },
+
+            IADD..=DREM | IAND..=LXOR => {
+                let value = binary_operand_type(opcode);
+                self.pop_expecting(&value)?;
+                self.pop_expecting(&value)?;
+                self.push(value)?;
+            },
+            INEG..=DNEG => {
+                let value = load_store_type(opcode - INEG);
+                self.pop_expecting(&value)?;
+                self.push(value)?;
+            },
+            ISHL..=LUSHR => {
+                let value = if opcode % 2 == ISHL % 2 { Type::Int } else { Type::Long };
+                self.pop_expecting(&Type::Int)?;
+                self.pop_expecting(&value)?;
+                self.push(value)?;
+            },
+            IINC => self.check_int_local(self.read_u8(1) as usize)?,
+            I2L..=I2S => {
+                let (from, to) = match opcode {
+                    I2L => (Type::Int, Type::Long),
+                    I2F => (Type::Int, Type::Float),
+                    I2D => (Type::Int, Type::Double),
+                    L2I => (Type::Long, Type::Int),
+                    L2F => (Type::Long, Type::Float),
+                    L2D => (Type::Long, Type::Double),
+                    F2I => (Type::Float, Type::Int),
+                    F2L => (Type::Float, Type::Long),
+                    F2D => (Type::Float, Type::Double),
+                    D2I => (Type::Double, Type::Int),
+                    D2L => (Type::Double, Type::Long),
+                    D2F => (Type::Double, Type::Float),
+                    other => (Type::Int, Type::Int)
+                };
+                self.pop_expecting(&from)?;
+                self.push(to)?;
+            },
+            LCMP..=DCMPG => {
+                let value = match opcode {
+                    LCMP => Type::Long,
+                    FCMPL | FCMPG => Type::Float,
+                    other => Type::Double
+                };
+                self.pop_expecting(&value)?;
+                self.pop_expecting(&value)?;
+                self.push(Type::Int)?;
+            },
+
+            IFEQ..=IFLE => {
+                self.pop_expecting(&Type::Int)?;
+                self.branch(self.read_u16(1) as i16 as i32)?;
+            },
+            IF_ICMPEQ..=IF_ICMPLE => {
+                self.pop_expecting(&Type::Int)?;
+                self.pop_expecting(&Type::Int)?;
+                self.branch(self.read_u16(1) as i16 as i32)?;
+            },
+            IF_ACMPEQ | IF_ACMPNE => {
+                self.pop_reference()?;
+                self.pop_reference()?;
+                self.branch(self.read_u16(1) as i16 as i32)?;
+            },
+            IFNULL | IFNONNULL => {
+                self.pop_reference()?;
+                self.branch(self.read_u16(1) as i16 as i32)?;
+            },
+            GOTO => {
+                self.branch(self.read_u16(1) as i16 as i32)?;
+                return Ok(false);
+            },
+            GOTO_W => {
+                self.branch(self.read_i32(self.pc + 1))?;
+                return Ok(false);
+            },
+            JSR | JSR_W | RET if !self.inference || !self.allow_subroutines => return Err(self.subroutine_error(opcode)),
+            JSR => {
+                self.jump_to_subroutine(self.read_u16(1) as i16 as i32)?;
+                return Ok(false);
+            },
+            JSR_W => {
+                self.jump_to_subroutine(self.read_i32(self.pc + 1))?;
+                return Ok(false);
+            },
+            RET => {
+                self.return_from_subroutine(self.read_u8(1) as usize)?;
+                return Ok(false);
+            },
+            TABLESWITCH | LOOKUPSWITCH => {
+                self.pop_expecting(&Type::Int)?;
+                let start = self.pc + 4 - self.pc % 4;
+                self.branch(self.read_i32(start))?;
+                if opcode == TABLESWITCH {
+                    let (low, high) = (self.read_i32(start + 4), self.read_i32(start + 8));
+                    for entry in 0..(high as i64 - low as i64 + 1) as usize {
+                        self.branch(self.read_i32(start + 12 + entry * 4))?;
+                    }
+                } else {
+                    let pairs_count = self.read_i32(start + 4) as usize;
+                    for pair in 0..pairs_count {
+                        if pair > 0 && self.read_i32(start + 8 + pair * 8) <= self.read_i32(start + 8 + (pair - 1) * 8) {
+                            return Err(self.error("Bad lookupswitch instruction, the keys are not sorted"));
+                        }
+                        self.branch(self.read_i32(start + 12 + pair * 8))?;
+                    }
+                }
+                return Ok(false);
+            },
+            IRETURN..=RETURN => {
+                self.check_return(opcode)?;
+                return Ok(false);
+            },
+
+            GETSTATIC..=PUTFIELD => self.field_access(opcode)?,
+            INVOKEVIRTUAL..=INVOKEDYNAMIC => self.invoke(opcode)?,
+            NEW => {
+                let class_name = self.class_name(self.read_u16(1))?;
+                if class_name.starts_with('[') {
+                    return Err(self.error(format!("Illegal use of new on the array class {}", class_name)));
+                }
+                let created = Type::Uninitialized(self.pc as u16);
+                if self.frame.stack.contains(&created) {
+                    return Err(self.error("The object of an earlier run of this new instruction is still on the operand stack"));
+                }
+                for local in self.frame.locals.iter_mut().filter(|local| **local == created) {
+                    *local = Type::Top;
+                }
+                self.push(created)?;
+            },
+            NEWARRAY => {
+                let component = match self.read_u8(1) {
+                    T_BOOLEAN => "Z",
+                    T_CHAR => "C",
+                    T_FLOAT => "F",
+                    T_DOUBLE => "D",
+                    T_BYTE => "B",
+                    T_SHORT => "S",
+                    T_INT => "I",
+                    T_LONG => "J",
+                    other => return Err(self.error(format!("Illegal newarray type {}", other)))
+                };
+                self.pop_expecting(&Type::Int)?;
+                self.push(Type::Reference(format!("[{}", component)))?;
+            },
+            ANEWARRAY => {
+                let class_name = self.class_name(self.read_u16(1))?;
+                if class_name.bytes().take_while(|byte| *byte == b'[').count() >= 255 {
+                    return Err(self.error("Array with too many dimensions"));
+                }
+                self.pop_expecting(&Type::Int)?;
+                let array = if class_name.starts_with('[') { format!("[{}", class_name) } else { format!("[L{};", class_name) };
+                self.push(Type::Reference(array))?;
+            },
+            MULTIANEWARRAY => {
+                let class_name = self.class_name(self.read_u16(1))?;
+                let dimensions = self.read_u8(3) as usize;
+                if dimensions == 0 || class_name.bytes().take_while(|byte| *byte == b'[').count() < dimensions {
+                    return Err(self.error(format!("Illegal dimensions {} for multianewarray of {}", dimensions, class_name)));
+                }
+                for _dimension in 0..dimensions {
+                    self.pop_expecting(&Type::Int)?;
+                }
+                self.push(Type::Reference(class_name.to_string()))?;
+            },
+            ARRAYLENGTH => {
+                self.pop_array(&[])?;
+                self.push(Type::Int)?;
+            },
+            ATHROW => {
+                self.pop_expecting(&Type::Reference(String::from("java/lang/Throwable")))?;
+                return Ok(false);
+            },
+            CHECKCAST => {
+                let class_name = self.class_name(self.read_u16(1))?;
+                self.pop_reference()?;
+                self.push(Type::Reference(class_name.to_string()))?;
+            },
+            INSTANCEOF => {
+                self.class_name(self.read_u16(1))?;
+                self.pop_reference()?;
+                self.push(Type::Int)?;
+            },
+            MONITORENTER | MONITOREXIT => {
+                self.pop_reference()?;
+            },
+            other => return Err(self.error(format!("Bad instruction {:#04x}", other)))
+        }
+        Ok(true)
+    }
+}
+
+/// The type of the load, store and negation instructions, which come in the order int, long, float, double, reference.
+fn load_store_type(kind: u8) -> Type {
+    match kind {
+        0 => Type::Int,
+        1 => Type::Long,
+        2 => Type::Float,
+        3 => Type::Double,
+        other => Type::object()
+    }
+}
+
+/// The arithmetic and bitwise instructions also come in the order int, long, float, double.
+fn binary_operand_type(opcode: u8) -> Type {
+    if opcode >= IAND {
+        load_store_type((opcode - IAND) % 2)
+    } else {
+        load_store_type((opcode - IADD) % 4)
+    }
+}
+
+/// Returns the class name of a component descriptor, like `java/lang/String` for `Ljava/lang/String;`.
+fn component_name(component: &str) -> &str {
+    match component.strip_prefix('L') {
+        Some(class_name) => class_name.trim_end_matches(';'),
+        None => component
+    }
+}
@@ -0,0 +1,196 @@
+use std::{collections::HashMap, sync::{atomic::Ordering, Arc, Condvar, Mutex, OnceLock, RwLock}, time::Instant};
+
+use crate::{class_loader::ClassInfo, class_path::ClassPath, exceptions::{JvmResult, Throwable}, format_checker, invokedynamic::Lambda, natives::NativeRegistry, object::{ObjectRef, Value}, runtime_class::{primitive_name, Class}, strings, verifier::Verification};
+
+/// The state shared by all threads of a running VM.
+pub struct Vm {
+    pub class_path: ClassPath,
+    pub natives: NativeRegistry,
+    // Properties the VM passes to System, like java.home and java.class.path
+    pub system_properties: Vec<(String, String)>,
+    // Origin of System.nanoTime
+    pub start_time: Instant,
+    pub verification: Verification,
+    classes: RwLock<HashMap<String, Arc<Class>>>,
+    // The classes the VM made for lambdas, by name, with the method that they call
+    lambdas: RwLock<HashMap<String, Arc<Lambda>>>,
+    // String literals and the results of String.intern, by their UTF-16 code units
+    interned_strings: Mutex<HashMap<Vec<u16>, ObjectRef>>,
+    // Started threads that are not daemons and have not ended, the VM keeps running until there are none
+    running_threads: Mutex<usize>,
+    threads_ended: Condvar,
+    // The unnamed module of the boot loader, which every class is in since there is only one loader
+    pub unnamed_module: OnceLock<ObjectRef>,
+}
+
+impl Vm {
+    pub fn new(class_path: ClassPath, system_properties: Vec<(String, String)>, verification: Verification) -> Arc<Vm> {
+        Arc::new(Vm {
+            class_path,
+            system_properties,
+            natives: NativeRegistry::new(),
+            start_time: Instant::now(),
+            verification,
+            classes: RwLock::new(HashMap::new()),
+            lambdas: RwLock::new(HashMap::new()),
+            interned_strings: Mutex::new(HashMap::new()),
+            running_threads: Mutex::new(0),
+            threads_ended: Condvar::new(),
+            unnamed_module: OnceLock::new(),
+        })
+    }
+
+    pub fn find_loaded_class(&self, name: &str) -> Option<Arc<Class>> {
+        self.classes.read().unwrap().get(name).cloned()
+    }
+
+    /// Loads and links a class by its internal name, like `java/lang/Object` or `[I`.
+    /// The class is not initialized yet.
+    pub fn load_class(&self, name: &str) -> JvmResult<Arc<Class>> {
+        if let Some(class) = self.find_loaded_class(name) {
+            return Ok(class);
+        }
+        let class = if name.starts_with('[') {
+            self.create_array_class(name)?
+        } else {
+            self.create_class(name)?
+        };
+        // Another thread could have been loading the same class, the first one wins
+        let mut classes = self.classes.write().unwrap();
+        Ok(classes.entry(name.to_string()).or_insert(class).clone())
+    }
+
+    /// Links a class that does not come from the classpath, like the class of a lambda.
+    pub fn define_class(&self, class_info: ClassInfo) -> JvmResult<Arc<Class>> {
+        let class = self.link_class(class_info)?;
+        let mut classes = self.classes.write().unwrap();
+        if classes.contains_key(&class.name) {
+            return Err(Throwable::new("java/lang/LinkageError", format!("duplicate class definition: {}", class.name)));
+        }
+        classes.insert(class.name.clone(), class.clone());
+        Ok(class)
+    }
+
+    /// Puts every class in the unnamed module, including those whose mirrors were made before it existed.
+    pub fn set_unnamed_module(&self, module: ObjectRef) {
+        let module = self.unnamed_module.get_or_init(|| module);
+        for class in self.classes.read().unwrap().values() {
+            if let Some(mirror) = class.mirror.get() {
+                set_module(mirror, module);
+            }
+        }
+    }
+
+    pub fn add_lambda(&self, class_name: &str, lambda: Arc<Lambda>) {
+        self.lambdas.write().unwrap().insert(class_name.to_string(), lambda);
+    }
+
+    pub fn find_lambda(&self, class_name: &str) -> Option<Arc<Lambda>> {
+        self.lambdas.read().unwrap().get(class_name).cloned()
+    }
+
+    /// Hidden classes, which are the classes of lambdas here, don't show up in stack traces.
+    pub fn is_hidden_class(&self, class_name: &str) -> bool {
+        self.lambdas.read().unwrap().contains_key(class_name)
+    }
+
+    /// Returns the class of a primitive type by its name, like `int`.
+    /// Those names are keywords, so they can be registered together with the other classes.
+    pub fn primitive_class(&self, name: &str) -> Arc<Class> {
+        if let Some(class) = self.find_loaded_class(name) {
+            return class;
+        }
+        let mut classes = self.classes.write().unwrap();
+        classes.entry(name.to_string()).or_insert_with(|| Class::new_primitive(name)).clone()
+    }
+
+    /// Returns the class of a field descriptor, like `I`, `Ljava/lang/String;` or `[I`, loading it if needed.
+    pub fn class_for_descriptor(&self, descriptor: &str) -> JvmResult<Arc<Class>> {
+        match descriptor.as_bytes()[0] {
+            b'L' => self.load_class(&descriptor[1..descriptor.len() - 1]),
+            b'[' => self.load_class(descriptor),
+            other => Ok(self.primitive_class(primitive_name(descriptor)))
+        }
+    }
+
+    /// Returns the interned String with the same characters, which is the given one if there was none yet.
+    pub fn intern_string(&self, string: ObjectRef) -> ObjectRef {
+        let key = strings::to_utf16(&string);
+        self.interned_strings.lock().unwrap().entry(key).or_insert(string).clone()
+    }
+
+    pub fn thread_started(&self, daemon: bool) {
+        if !daemon {
+            *self.running_threads.lock().unwrap() += 1;
+        }
+    }
+
+    pub fn thread_ended(&self, daemon: bool) {
+        if !daemon {
+            *self.running_threads.lock().unwrap() -= 1;
+            self.threads_ended.notify_all();
+        }
+    }
+
+    /// Blocks until all threads that are not daemons have ended, like DestroyJavaVM does after main returns.
+    pub fn wait_for_threads(&self) {
+        let mut running_threads = self.running_threads.lock().unwrap();
+        while *running_threads > 0 {
+            running_threads = self.threads_ended.wait(running_threads).unwrap();
+        }
+    }
+
+    fn create_class(&self, name: &str) -> JvmResult<Arc<Class>> {
+        let (class_info, from_boot_class_path) = self.class_path.find_class(name)
+            .ok_or_else(|| Throwable::new("java/lang/NoClassDefFoundError", name))?
+            .map_err(|message| Throwable::new("java/lang/ClassFormatError", format!("{} in class file {}", message, name)))?;
+        // By default only the classes of the application get checked and verified, the class library is trusted
+        let verify = match self.verification {
+            Verification::All => true,
+            Verification::Remote => !from_boot_class_path,
+            Verification::None => false
+        };
+        if verify && let Some(diagnostic) = format_checker::check_class(&class_info).first() {
+            return Err(Throwable::new("java/lang/ClassFormatError", format!("{} in class file {}", diagnostic, name)));
+        }
+        let class = self.link_class(class_info)?;
+        if verify {
+            class.verification_pending.store(true, Ordering::Release);
+        }
+        Ok(class)
+    }
+
+    /// Creates a class from its class file, loading its superclass and interfaces.
+    fn link_class(&self, class_info: ClassInfo) -> JvmResult<Arc<Class>> {
+        let constant_pool = &class_info.constant_pool;
+        let name = constant_pool.get_string(constant_pool.get_class_info(class_info.this_class));
+        let super_class = match class_info.super_class {
+            0 => None,
+            index => Some(self.load_class(constant_pool.get_string(constant_pool.get_class_info(index)))?)
+        };
+        let mut interfaces = Vec::with_capacity(class_info.interfaces.len());
+        for index in &class_info.interfaces {
+            let interface = self.load_class(constant_pool.get_string(constant_pool.get_class_info(*index)))?;
+            if !interface.is_interface() {
+                return Err(Throwable::new("java/lang/IncompatibleClassChangeError",
+                    format!("class {} can not implement {}, because it is not an interface", name, interface.name)));
+            }
+            interfaces.push(interface);
+        }
+        Ok(Class::new(class_info, super_class, interfaces))
+    }
+
+    fn create_array_class(&self, name: &str) -> JvmResult<Arc<Class>> {
+        let component = self.class_for_descriptor(&name[1..])?;
+        let object_class = self.load_class("java/lang/Object")?;
+        let interfaces = vec![self.load_class("java/lang/Cloneable")?, self.load_class("java/io/Serializable")?];
+        Ok(Class::new_array(name, component, object_class, interfaces))
+    }
+}
+
+/// Sets the `module` field of a class mirror.
+pub fn set_module(mirror: &ObjectRef, module: &ObjectRef) {
+    if let Some(slot) = mirror.class.instance_field_slot("module", "Ljava/lang/Module;") {
+        mirror.fields()[slot] = Value::Reference(Some(module.clone()));
+    }
+}
@@ -0,0 +1,18 @@
+package app;
+
+class Circle implements Shape {
+    private final double radius;
+
+    Circle(double radius) {
+        this.radius = radius;
+    }
+
+    public double area() {
+        return Math.PI * radius * radius;
+    }
+
+    @Override
+    public String toString() {
+        return "Circle";
+    }
+}
@@ -0,0 +1,39 @@
+package app;
+
+/**
+ * The classes that the `analysis` test runs `analyze` on. Plugin extends lib.Base, and main calls lib.Base.twice, but
+ * lib/Base.class is left out, so lib.Base is missing. isEven and isOdd call each other, and factorial calls itself
+ * without being called. Square.unused isn't called either, while Circle.toString is an entry point because the
+ * library can call it.
+ */
+public class Main {
+    public static void main(String[] args) {
+        Shape[] shapes = { new Circle(1), new Square(2) };
+        double total = 0;
+        for (Shape shape : shapes) {
+            total += shape.area();
+        }
+        System.out.println(total);
+        Runnable task = Main::report;
+        task.run();
+        System.out.println(isEven(10));
+        new Plugin().start();
+        System.out.println(lib.Base.twice(3));
+    }
+
+    static void report() {
+        System.out.println("report");
+    }
+
+    static boolean isEven(int n) {
+        return n == 0 || isOdd(n - 1);
+    }
+
+    static boolean isOdd(int n) {
+        return n != 0 && isEven(n - 1);
+    }
+
+    static int factorial(int n) {
+        return n <= 1 ? 1 : n * factorial(n - 1);
+    }
+}
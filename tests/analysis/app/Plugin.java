@@ -0,0 +1,12 @@
+package app;
+
+class Plugin extends lib.Base {
+    @Override
+    public void start() {
+        super.start();
+        helper();
+    }
+
+    private void helper() {
+    }
+}
@@ -0,0 +1,5 @@
+package app;
+
+interface Shape {
+    double area();
+}
@@ -0,0 +1,17 @@
+package app;
+
+class Square implements Shape {
+    private final double side;
+
+    Square(double side) {
+        this.side = side;
+    }
+
+    public double area() {
+        return side * side;
+    }
+
+    int unused() {
+        return 42;
+    }
+}
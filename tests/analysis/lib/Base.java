@@ -0,0 +1,10 @@
+package lib;
+
+public class Base {
+    public void start() {
+    }
+
+    public static int twice(int n) {
+        return 2 * n;
+    }
+}
@@ -0,0 +1,252 @@
+//! Runs the programs in tests/conformance with the stub library or the JDK, and compares what they print with the output of a
+//! reference JVM, which is stored next to them. The class files that javac can't produce are assembled from the .j
+//! files there. The tools are checked the same way, against the files they should print.
+
+use std::{fs, path::{Path, PathBuf}, process::Command};
+
+/// Returns a directory in tests.
+fn directory(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(name)
+}
+
+/// Runs Vigur with the arguments, checks that it exits with the exit code, and returns what it printed.
+fn vigur(args: &[&str], exit_code: i32) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_Vigur")).args(args).output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(exit_code), "{} failed: {}", args.join(" "), stderr);
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+/// Runs Vigur with the arguments, and compares what it prints with the expected file line by line.
+fn check_output(args: &[&str], expected_file: &Path, exit_code: i32) {
+    let actual = vigur(args, exit_code);
+    let expected = fs::read_to_string(expected_file).unwrap();
+    let name = expected_file.file_name().unwrap().to_string_lossy();
+    for (line, (expected, actual)) in expected.lines().zip(actual.lines()).enumerate() {
+        assert_eq!(expected, actual, "{} differs on line {}", name, line + 1);
+    }
+    assert_eq!(expected.lines().count(), actual.lines().count(), "{} has a different number of lines", name);
+}
+
+/// Runs a program in tests/conformance on the stub library or the JDK, and compares its output with `<class>.expected`.
+fn check_conformance(class_name: &str, library: &str) {
+    let directory = directory("conformance");
+    let expected_file = directory.join(format!("{}.expected", class_name));
+    check_output(&["run", "-cp", &directory.to_string_lossy(), "--library", library, class_name], &expected_file, 0);
+}
+
+#[test]
+fn boot_classes() {
+    check_conformance("BootClasses", "jdk");
+}
+
+#[test]
+fn invocation() {
+    check_conformance("Invocation", "stub");
+}
+
+#[test]
+fn fields() {
+    check_conformance("Fields", "stub");
+}
+
+#[test]
+fn exceptions() {
+    check_conformance("Exceptions", "jdk");
+}
+
+#[test]
+fn strings() {
+    check_conformance("Strings", "stub");
+    check_conformance("Strings", "jdk");
+}
+
+#[test]
+fn natives() {
+    check_conformance("Natives", "jdk");
+}
+
+#[test]
+fn system_init() {
+    check_conformance("SystemInit", "jdk");
+}
+
+#[test]
+fn stub_library() {
+    check_conformance("StubLibrary", "stub");
+}
+
+#[test]
+fn reflection() {
+    check_conformance("Reflection", "jdk");
+}
+
+#[test]
+fn type_checks() {
+    check_conformance("TypeChecks", "stub");
+}
+
+#[test]
+fn monitors() {
+    check_conformance("Monitors", "stub");
+}
+
+#[test]
+fn lambdas() {
+    check_conformance("Lambdas", "jdk");
+}
+
+#[test]
+fn handles() {
+    check_conformance("Handles", "jdk");
+}
+
+#[test]
+fn slots() {
+    check_conformance("Slots", "stub");
+}
+
+#[test]
+fn numerics() {
+    check_conformance("Numerics", "stub");
+}
+
+#[test]
+fn verification() {
+    check_conformance("Verification", "stub");
+    // The corrupt stack map frame has to be reported for its method, at the pc of the frame before it
+    let output = Command::new(env!("CARGO_BIN_EXE_Vigur"))
+        .args(["run", "-cp", &directory("conformance").to_string_lossy(), "--library", "stub", "Verification", "frames"])
+        .output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr.contains("java.lang.ClassFormatError: Unknown verification type 9 in StackMapTable after the stack map frame \
+        in method Verification$Frames.sign(I)Ljava/lang/String; at pc 9"), "{}", stderr);
+}
+
+#[test]
+fn subroutines() {
+    check_conformance("Subroutines", "stub");
+}
+
+#[test]
+fn stack_shapes() {
+    check_conformance("StackShapes", "stub");
+}
+
+#[test]
+fn format_check() {
+    check_conformance("FormatCheck", "stub");
+    // The check command reports the class files that can't be parsed like the other problems
+    let directory = directory("conformance");
+    let files: Vec<String> = ["BadTag", "Truncated", "WrongKind"].iter()
+        .map(|name| directory.join(format!("FormatCheck${}.class", name)).to_string_lossy().into_owned())
+        .collect();
+    let mut args = vec!["check"];
+    args.extend(files.iter().map(String::as_str));
+    let output = vigur(&args, 1);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines, [
+        format!("{}: Unknown constant pool tag 2 at index 1", files[0]),
+        format!("{}: Truncated class file, it ends at byte 260", files[1]),
+        format!("{}: method: Index 16 is not a Utf8 constant", files[2]),
+        String::from("3 classes checked, 3 problems found")
+    ]);
+}
+
+#[test]
+fn control_flow() {
+    check_conformance("ControlFlow", "stub");
+}
+
+/// Assembles the .j files, and checks that they give the class files next to them, and that disassembling those gives
+/// the .j files back without their comments.
+#[test]
+fn assembly() {
+    let directory = directory("conformance");
+    let output_directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("assembly");
+    for entry in fs::read_dir(&directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|extension| extension != "j") {
+            continue;
+        }
+        let class_name = path.file_stem().unwrap().to_string_lossy().to_string();
+        vigur(&["asm", "-d", &output_directory.to_string_lossy(), &path.to_string_lossy()], 0);
+        let class_file = format!("{}.class", class_name);
+        assert!(fs::read(output_directory.join(&class_file)).unwrap() == fs::read(directory.join(&class_file)).unwrap(),
+            "{} doesn't match {}", class_file, path.display());
+
+        let disassembled = vigur(&["disasm", &directory.join(&class_file).to_string_lossy()], 0);
+        let text = fs::read_to_string(&path).unwrap();
+        let expected: Vec<&str> = text.lines().filter(|line| !line.trim_start().starts_with(';')).collect();
+        assert_eq!(expected, disassembled.lines().collect::<Vec<_>>(), "{} disassembles differently", class_file);
+    }
+}
+
+/// Assembles the .j files in tests/assembly_errors, which have code that can't be verified, and checks that each fails
+/// with the file and line of the instruction, as listed in expected.txt.
+#[test]
+fn assembly_errors() {
+    let directory = directory("assembly_errors");
+    let output_directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("assembly_errors");
+    let expected = fs::read_to_string(directory.join("expected.txt")).unwrap();
+    for line in expected.lines() {
+        let (file_name, message) = line.split_once(':').unwrap();
+        let path = directory.join(file_name).to_string_lossy().to_string();
+        let output = Command::new(env!("CARGO_BIN_EXE_Vigur")).args(["asm", "-d", &output_directory.to_string_lossy(), &path]).output().unwrap();
+        assert_eq!(output.status.code(), Some(1), "{} was assembled", file_name);
+        assert_eq!(String::from_utf8_lossy(&output.stderr).trim_end(), format!("Error: {}:{}", path, message));
+    }
+}
+
+/// Checks the control flow graphs in DOT of a method with a try block and of one with a loop.
+#[test]
+fn control_flow_graph() {
+    let directory = directory("conformance");
+    for (class_name, method_name) in [("Subroutines", "main"), ("StackShapesCode", "sum")] {
+        let class_file = directory.join(format!("{}.class", class_name));
+        let expected_file = directory.join(format!("{}.{}.dot", class_name, method_name));
+        check_output(&["cfg", &class_file.to_string_lossy(), method_name], &expected_file, 0);
+    }
+}
+
+/// Checks the source that `decompile` rebuilds from a class compiled with its local variable names, and from one
+/// compiled without them.
+#[test]
+fn decompiler() {
+    let directory = directory("conformance");
+    for class_name in ["ControlFlow", "Slots"] {
+        let class_file = directory.join(format!("{}.class", class_name));
+        check_output(&["decompile", &class_file.to_string_lossy()], &directory.join(format!("{}.decompiled", class_name)), 0);
+    }
+}
+
+/// Analyzes the classes in tests/analysis, and compares the JSON report and both graphs with the files there.
+#[test]
+fn analysis() {
+    let directory = directory("analysis");
+    let class_path = directory.to_string_lossy();
+    for (options, file_name) in [(&[][..], "analysis.json"), (&["--dot", "calls"][..], "calls.dot"), (&["--dot", "hierarchy"][..], "hierarchy.dot")] {
+        let args = [&["analyze", "--library", "stub"][..], options, &[&class_path]].concat();
+        check_output(&args, &directory.join(file_name), 0);
+    }
+}
+
+/// Lists the dependencies of the module in tests/deps, where legacy.Tax isn't found.
+#[test]
+fn dependencies() {
+    let directory = directory("deps");
+    let class_path = directory.join("classes").to_string_lossy().to_string();
+    for (options, file_name) in [(&[][..], "dependencies.txt"), (&["--modules"][..], "modules.txt"), (&["--modules", "--dot"][..], "modules.dot")] {
+        let args = [&["deps", "--library", "stub"][..], options, &[&class_path]].concat();
+        check_output(&args, &directory.join(file_name), 0);
+    }
+}
+
+/// Compares the two versions of the library in tests/diff, which has to fail because some changes break binary
+/// compatibility.
+#[test]
+fn compatibility() {
+    let directory = directory("diff");
+    check_output(&["diff", &directory.join("old").to_string_lossy(), &directory.join("new").to_string_lossy()], &directory.join("expected.txt"), 1);
+}
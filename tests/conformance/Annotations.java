@@ -0,0 +1,42 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.Arrays;
+import java.util.List;
+
+/**
+ * Has annotations of every kind of element, which the disassembler writes as .attribute directives with the
+ * constants they use, and a local variable with a generic signature.
+ */
+public class Annotations {
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Info {
+        String name() default "none";
+        int level() default 1;
+        long big() default 5L;
+        double ratio() default 0.5;
+        char letter() default 'x';
+        Class<?> type() default Object.class;
+        RetentionPolicy policy() default RetentionPolicy.CLASS;
+        String[] tags() default {};
+        Deprecated nested() default @Deprecated;
+    }
+
+    @Retention(RetentionPolicy.RUNTIME)
+    @Target(ElementType.TYPE_USE)
+    @interface Checked {}
+
+    @Info(name = "field", tags = {"a", "b"})
+    public List<@Checked String> names;
+
+    @Info(name = "method", level = 3, big = 1L << 40, ratio = 1.5, letter = 'y', type = String.class, policy = RetentionPolicy.RUNTIME)
+    public static int sum(@Info(name = "first") int first, @Deprecated int second) {
+        List<Integer> values = Arrays.asList(first, second);
+        int total = 0;
+        for (Integer value : values) {
+            total += value;
+        }
+        return total;
+    }
+}
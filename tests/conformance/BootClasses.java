@@ -0,0 +1,31 @@
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.Collections;
+import java.util.List;
+import java.util.Map;
+import java.util.TreeMap;
+import java.util.concurrent.ConcurrentHashMap;
+
+/**
+ * Runs on the JDK, whose classes come from the jimage in lib/modules: collections from java.util and
+ * java.util.concurrent, and a class from java.sql, which is in another module than java.base.
+ */
+public class BootClasses {
+    public static void main(String[] args) throws Exception {
+        List<String> names = new ArrayList<>(Arrays.asList("pear", "apple", "fig"));
+        Collections.sort(names);
+        System.out.println(names);
+        Map<String, Integer> lengths = new TreeMap<>();
+        for (String name : names) {
+            lengths.put(name, name.length());
+        }
+        System.out.println(lengths);
+        ConcurrentHashMap<Integer, String> squares = new ConcurrentHashMap<>();
+        for (int i = 1; i <= 4; i++) {
+            squares.put(i, Integer.toString(i * i));
+        }
+        System.out.println(squares);
+        System.out.println(new java.sql.SQLException("no table", "42000").getSQLState());
+        System.out.println(Class.forName("java.sql.Timestamp").getName());
+    }
+}
@@ -0,0 +1,111 @@
+/**
+ * Runs the statements that the decompiler rebuilds from the control flow graph: if and else chains, conditions with
+ * && and ||, while, do-while and for loops with break and continue, switch, try and catch, and the conditional
+ * operator. It is compiled with -g, so that `decompile` gets the names of the local variables.
+ */
+public class ControlFlow {
+    static int counter;
+
+    static String classify(int n) {
+        if (n < 0) {
+            return "negative";
+        } else if (n == 0) {
+            return "zero";
+        } else if (n % 2 == 0 && n > 100) {
+            return "large even";
+        }
+        return n % 2 == 0 ? "even" : "odd";
+    }
+
+    static int sumOfDigits(int n) {
+        int sum = 0;
+        while (n != 0) {
+            sum += n % 10;
+            n /= 10;
+        }
+        return sum;
+    }
+
+    static int firstMultiple(int[] values, int divisor) {
+        for (int i = 0; i < values.length; i++) {
+            if (values[i] == 0) {
+                continue;
+            }
+            if (values[i] % divisor == 0) {
+                return i;
+            }
+        }
+        return -1;
+    }
+
+    static int collatz(long n) {
+        int steps = 0;
+        do {
+            n = n % 2 == 0 ? n / 2 : 3 * n + 1;
+            steps++;
+        } while (n != 1);
+        return steps;
+    }
+
+    static String weekday(int day) {
+        String name;
+        switch (day) {
+            case 1:
+                name = "Monday";
+                break;
+            case 2:
+                name = "Tuesday";
+                break;
+            case 6:
+            case 7:
+                name = "weekend";
+                break;
+            default:
+                name = "other";
+        }
+        return name;
+    }
+
+    static int parse(String text) {
+        try {
+            return Integer.parseInt(text);
+        } catch (NumberFormatException e) {
+            counter++;
+            return -1;
+        }
+    }
+
+    static boolean isPrime(int n) {
+        if (n < 2) {
+            return false;
+        }
+        for (int divisor = 2; divisor * divisor <= n; divisor++) {
+            if (n % divisor == 0) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    public static void main(String[] args) {
+        for (int n : new int[] { -5, 0, 7, 8, 102 }) {
+            System.out.println(classify(n));
+        }
+        System.out.println(sumOfDigits(90210));
+        System.out.println(firstMultiple(new int[] { 0, 5, 9, 12 }, 3));
+        System.out.println(firstMultiple(new int[] { 1, 2 }, 7));
+        System.out.println(collatz(27));
+        for (int day = 1; day <= 7; day++) {
+            System.out.println(weekday(day));
+        }
+        System.out.println(parse("123") + parse("twelve") + parse("-7"));
+        System.out.println(counter);
+        StringBuilder primes = new StringBuilder();
+        for (int n = 0; n < 30; n++) {
+            if (isPrime(n)) {
+                primes.append(n).append(' ');
+            }
+        }
+        System.out.println(primes.toString().trim());
+    }
+}
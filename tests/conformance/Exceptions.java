@@ -0,0 +1,104 @@
+/**
+ * Throws exceptions with athrow and from the VM, and catches them by the handler whose type matches, in the method
+ * that threw them or in a caller. It also catches the StackOverflowError of unbounded recursion, twice, and reads
+ * line numbers from stack traces.
+ */
+public class Exceptions {
+    static class AppException extends Exception {
+        AppException(String message) {
+            super(message);
+        }
+    }
+
+    static class DetailedException extends AppException {
+        DetailedException(String message) {
+            super("detailed " + message);
+        }
+    }
+
+    static int depth;
+
+    static void recurse() {
+        depth++;
+        recurse();
+    }
+
+    static void fail(int kind) throws AppException {
+        switch (kind) {
+            case 0:
+                throw new AppException("plain");
+            case 1:
+                throw new DetailedException("kind 1");
+            case 2:
+                Object text = "text";
+                System.out.println((Integer) text);
+                break;
+            case 3:
+                int[] values = new int[2];
+                values[kind] = 1;
+                break;
+            case 4:
+                String nothing = null;
+                System.out.println(nothing.length());
+                break;
+            case 5:
+                System.out.println(kind / (kind - 5));
+                break;
+            case 6:
+                System.out.println(new int[kind - 7].length);
+                break;
+            default:
+                Object[] objects = new String[1];
+                objects[0] = kind;
+        }
+    }
+
+    static String cleanUp(int kind) {
+        StringBuilder steps = new StringBuilder();
+        try {
+            try {
+                steps.append("try ");
+                fail(kind);
+            } finally {
+                steps.append("finally ");
+            }
+        } catch (DetailedException e) {
+            steps.append("detailed ");
+        } catch (AppException e) {
+            steps.append("app ");
+        } catch (RuntimeException e) {
+            steps.append("runtime ");
+        }
+        return steps.toString();
+    }
+
+    static int lineOf(Throwable throwable) {
+        return throwable.getStackTrace()[0].getLineNumber();
+    }
+
+    public static void main(String[] args) {
+        for (int kind = 0; kind <= 7; kind++) {
+            try {
+                fail(kind);
+            } catch (AppException e) {
+                System.out.println(kind + ": " + e.getClass().getName() + " " + e.getMessage() + " at line " + lineOf(e));
+            } catch (RuntimeException e) {
+                System.out.println(kind + ": " + e.getClass().getName() + " at line " + lineOf(e));
+            }
+            System.out.println(cleanUp(kind));
+        }
+        for (int attempt = 0; attempt < 2; attempt++) {
+            depth = 0;
+            try {
+                recurse();
+            } catch (StackOverflowError e) {
+                System.out.println("StackOverflowError after more than 1000 calls: " + (depth > 1000) + " at line " + lineOf(e));
+            }
+        }
+        try {
+            throw new Error("rethrown", new AppException("cause"));
+        } catch (Error e) {
+            System.out.println(e.getMessage() + " caused by " + e.getCause().getMessage());
+        }
+    }
+}
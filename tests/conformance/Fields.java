@@ -0,0 +1,87 @@
+/**
+ * Reads and writes static and instance fields: fields inherited from superclasses and superinterfaces, hidden
+ * fields, constants from ConstantValue attributes, every primitive type, and the order of static initializers.
+ */
+public class Fields {
+    interface Limits {
+        int MAX = 10;
+        String UNIT = "cm";
+        long START = System.nanoTime() > 0 ? 5 : 0;
+    }
+
+    static class Base implements Limits {
+        static int created;
+        protected String name = "base";
+        int size = 1;
+
+        Base() {
+            created++;
+        }
+    }
+
+    static class Derived extends Base {
+        String name = "derived";
+        static final double RATIO = 1.5;
+        static final String LABEL;
+
+        static {
+            System.out.println("initializing Derived");
+            LABEL = "label " + RATIO;
+        }
+
+        String names() {
+            return name + " " + super.name + " " + ((Base) this).name;
+        }
+    }
+
+    static boolean flag;
+    static byte tiny = -128;
+    static char letter = 'a';
+    static short small = 32767;
+    static long big = Long.MIN_VALUE;
+    static float ratio = 0.1f;
+    static double precise = 1e300;
+
+    byte b;
+    char c;
+    short s;
+    int i;
+    long l;
+    float f;
+    double d;
+    boolean z;
+    Object o;
+
+    public static void main(String[] args) {
+        System.out.println(flag + " " + tiny + " " + letter + " " + small + " " + big + " " + ratio + " " + precise);
+        flag = !flag;
+        tiny--;
+        letter++;
+        small++;
+        big--;
+        ratio *= 3;
+        precise *= 1e10;
+        System.out.println(flag + " " + tiny + " " + letter + " " + small + " " + big + " " + ratio + " " + precise);
+
+        Fields fields = new Fields();
+        System.out.println(fields.b + " " + (int) fields.c + " " + fields.s + " " + fields.i + " " + fields.l + " "
+            + fields.f + " " + fields.d + " " + fields.z + " " + fields.o);
+        fields.b = (byte) 200;
+        fields.c = 'z';
+        fields.s = (short) 70000;
+        fields.i = Integer.MAX_VALUE;
+        fields.l = 1L << 40;
+        fields.f = -0.0f;
+        fields.d = Double.NaN;
+        fields.z = true;
+        fields.o = "object";
+        System.out.println(fields.b + " " + fields.c + " " + fields.s + " " + fields.i + " " + fields.l + " "
+            + fields.f + " " + fields.d + " " + fields.z + " " + fields.o);
+
+        System.out.println("before Derived");
+        Derived derived = new Derived();
+        new Base();
+        System.out.println(derived.names() + " " + derived.size + " " + Base.created + " " + Derived.created);
+        System.out.println(Derived.MAX + " " + Derived.UNIT + " " + Derived.START + " " + Derived.LABEL);
+    }
+}
@@ -0,0 +1,56 @@
+public class FormatCheck {
+    static class Duplicates {
+        // The class file in this directory has the name of two renamed to one, which makes it a duplicate
+        static int one() {
+            return 1;
+        }
+
+        static int two() {
+            return 2;
+        }
+    }
+
+    static class Truncated {
+        // The class file in this directory ends in the middle of its methods
+        static int one() {
+            return 1;
+        }
+    }
+
+    static class BadTag {
+        // The class file in this directory has a constant with the unknown tag 2
+        static int one() {
+            return 1;
+        }
+    }
+
+    static class WrongKind {
+        // The class file in this directory has the descriptor of one pointing at a Class constant
+        static int one() {
+            return 1;
+        }
+    }
+
+    public static void main(String[] args) {
+        try {
+            System.out.println(Duplicates.one());
+        } catch (ClassFormatError e) {
+            System.out.println("ClassFormatError");
+        }
+        try {
+            System.out.println(Truncated.one());
+        } catch (ClassFormatError e) {
+            System.out.println("ClassFormatError");
+        }
+        try {
+            System.out.println(BadTag.one());
+        } catch (ClassFormatError e) {
+            System.out.println("ClassFormatError");
+        }
+        try {
+            System.out.println(WrongKind.one());
+        } catch (ClassFormatError e) {
+            System.out.println("ClassFormatError");
+        }
+    }
+}
@@ -0,0 +1,55 @@
+import java.lang.invoke.MethodHandle;
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodType;
+import java.lang.invoke.WrongMethodTypeException;
+
+public class Handles {
+    static String greeting = "hello";
+
+    private final String name;
+
+    Handles(String name) {
+        this.name = name;
+    }
+
+    static int twice(int value) {
+        return value * 2;
+    }
+
+    String greet(String other) {
+        return name + " greets " + other;
+    }
+
+    public static void main(String[] args) throws Throwable {
+        MethodHandles.Lookup lookup = MethodHandles.lookup();
+        MethodHandle twice = lookup.findStatic(Handles.class, "twice", MethodType.methodType(int.class, int.class));
+        System.out.println((int) twice.invokeExact(21));
+        System.out.println(twice.invoke(Integer.valueOf(4)));
+        System.out.println(twice.type());
+
+        MethodHandle greet = lookup.findVirtual(Handles.class, "greet", MethodType.methodType(String.class, String.class));
+        System.out.println((String) greet.invokeExact(new Handles("Ann"), "Bob"));
+        MethodHandle constructor = lookup.findConstructor(Handles.class, MethodType.methodType(void.class, String.class));
+        Handles made = (Handles) constructor.invokeExact("Cy");
+        System.out.println(made.name);
+
+        // Virtual and interface handles call the method of the receiver's class
+        MethodHandle toString = lookup.findVirtual(Object.class, "toString", MethodType.methodType(String.class));
+        System.out.println((String) toString.invokeExact((Object) Integer.valueOf(7)));
+        MethodHandle length = lookup.findVirtual(CharSequence.class, "length", MethodType.methodType(int.class));
+        System.out.println((int) length.invokeExact((CharSequence) "four"));
+
+        try {
+            System.out.println((long) twice.invokeExact(1));
+        } catch (WrongMethodTypeException e) {
+            System.out.println(e.getMessage());
+        }
+
+        // Constants that javac doesn't load with ldc
+        System.out.println((int) HandleConstants.twice().invokeExact(50));
+        System.out.println(HandleConstants.type());
+        System.out.println((String) HandleConstants.greeting().invokeExact());
+        System.out.println(HandleConstants.answer());
+        System.out.println(HandleConstants.nothing());
+    }
+}
@@ -0,0 +1,108 @@
+/**
+ * Calls methods with invokestatic, invokespecial, invokevirtual and invokeinterface: overriding, super calls, default
+ * methods with the maximally specific one winning, and overloads. InvocationLibrary was changed after this class was
+ * compiled against it, so calling it throws NoSuchMethodError and IncompatibleClassChangeError, and AbstractMethodError
+ * for Square, which doesn't have the method that InvocationLibrary.Shape got.
+ */
+public class Invocation {
+    interface Named {
+        default String name() {
+            return "named";
+        }
+    }
+
+    interface Titled extends Named {
+        @Override
+        default String name() {
+            return "titled " + Named.super.name();
+        }
+    }
+
+    interface Sized {
+        int size();
+
+        static int twice(Sized sized) {
+            return 2 * sized.size();
+        }
+    }
+
+    static abstract class Animal implements Named, Sized {
+        abstract String sound();
+
+        String describe() {
+            return name() + " says " + sound() + " at size " + size();
+        }
+    }
+
+    static class Dog extends Animal implements Titled {
+        String sound() {
+            return "woof";
+        }
+
+        public int size() {
+            return 3;
+        }
+    }
+
+    static class Puppy extends Dog {
+        @Override
+        String sound() {
+            return "yip, not " + super.sound();
+        }
+
+        @Override
+        public int size() {
+            return secret() + super.size();
+        }
+
+        private int secret() {
+            return -2;
+        }
+    }
+
+    static class Square implements InvocationLibrary.Shape {
+        public int area() {
+            return 4;
+        }
+    }
+
+    static String pick(Object value) {
+        return "object";
+    }
+
+    static String pick(String value) {
+        return "string";
+    }
+
+    static String pick(int value) {
+        return "int";
+    }
+
+    public static void main(String[] args) {
+        Animal[] animals = { new Dog(), new Puppy() };
+        for (Animal animal : animals) {
+            System.out.println(animal.describe());
+            System.out.println(Sized.twice(animal));
+        }
+        Named named = new Named() {};
+        System.out.println(named.name());
+        Object text = "text";
+        System.out.println(pick(text) + " " + pick("text") + " " + pick(7));
+
+        try {
+            InvocationLibrary.removed();
+        } catch (NoSuchMethodError e) {
+            System.out.println("NoSuchMethodError");
+        }
+        try {
+            InvocationLibrary.becameInstance();
+        } catch (IncompatibleClassChangeError e) {
+            System.out.println("IncompatibleClassChangeError");
+        }
+        try {
+            System.out.println(InvocationLibrary.perimeter(new Square()));
+        } catch (AbstractMethodError e) {
+            System.out.println("AbstractMethodError");
+        }
+    }
+}
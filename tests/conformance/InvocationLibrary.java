@@ -0,0 +1,18 @@
+/**
+ * The version of the class that Invocation runs against. It was compiled against one where removed and
+ * becameInstance were static methods, and Shape only had area.
+ */
+public class InvocationLibrary {
+    public interface Shape {
+        int area();
+
+        int perimeter();
+    }
+
+    public static int perimeter(Shape shape) {
+        return shape.perimeter();
+    }
+
+    public void becameInstance() {
+    }
+}
@@ -0,0 +1,67 @@
+import java.util.function.BiFunction;
+import java.util.function.IntBinaryOperator;
+import java.util.function.Supplier;
+
+public class Lambdas {
+    record Pair(String name, long count, double ratio, boolean flag) {
+    }
+
+    interface Greeter {
+        String greet(String name);
+    }
+
+    private final String greeting;
+
+    Lambdas(String greeting) {
+        this.greeting = greeting;
+    }
+
+    String greet(String name) {
+        return greeting + ", " + name;
+    }
+
+    static int add(int a, int b) {
+        return a + b;
+    }
+
+    public static void main(String[] args) {
+        IntBinaryOperator multiply = (a, b) -> a * b;
+        IntBinaryOperator adder = Lambdas::add;
+        System.out.println(multiply.applyAsInt(6, 7) + " " + adder.applyAsInt(2, 3));
+
+        int offset = 100;
+        IntBinaryOperator capturing = (a, b) -> a + b + offset;
+        System.out.println(capturing.applyAsInt(1, 2));
+
+        Greeter greeter = new Lambdas("Hello")::greet;
+        System.out.println(greeter.greet("world"));
+        Supplier<Lambdas> constructor = () -> new Lambdas("Hi");
+        System.out.println(constructor.get().greet("there"));
+        BiFunction<String, Integer, String> repeat = (text, times) -> {
+            StringBuilder builder = new StringBuilder();
+            for (int i = 0; i < times; i++) {
+                builder.append(text);
+            }
+            return builder.toString();
+        };
+        System.out.println(repeat.apply("ab", 3));
+
+        char c = 'x';
+        long big = 1L << 40;
+        Object nothing = null;
+        System.out.println("c=" + c + " big=" + big + " half=" + 0.5f + " nothing=" + nothing + " " + true);
+
+        record Point(int x, int y) {
+        }
+        Point point = new Point(1, 2);
+        System.out.println(point);
+        System.out.println(point.equals(new Point(1, 2)) + " " + point.equals(new Point(2, 1)));
+        System.out.println(point.hashCode() == new Point(1, 2).hashCode());
+
+        Pair pair = new Pair("a", 3L, 0.25, true);
+        System.out.println(pair);
+        System.out.println(new Pair(null, -1L, Double.NaN, false));
+        System.out.println(pair.equals(new Pair("a", 3L, 0.25, true)));
+        System.out.println(new Pair("a", 0, Double.NaN, false).equals(new Pair("a", 0, Double.NaN, false)));
+    }
+}
@@ -0,0 +1,152 @@
+public class Monitors {
+    static final int ITEMS = 200;
+
+    // A buffer of one item, which the producer fills and the consumer empties
+    static class Slot {
+        private int value;
+        private boolean full;
+
+        synchronized void put(int value) throws InterruptedException {
+            while (full) {
+                wait();
+            }
+            this.value = value;
+            full = true;
+            notifyAll();
+        }
+
+        synchronized int take() throws InterruptedException {
+            while (!full) {
+                wait();
+            }
+            full = false;
+            notifyAll();
+            return value;
+        }
+    }
+
+    static class Counter {
+        private int count;
+
+        synchronized void increment() {
+            count++;
+        }
+
+        int get() {
+            synchronized (this) {
+                return count;
+            }
+        }
+    }
+
+    static long total;
+
+    public static void main(String[] args) throws Exception {
+        final Slot slot = new Slot();
+        Thread producer = new Thread() {
+            public void run() {
+                try {
+                    for (int i = 1; i <= ITEMS; i++) {
+                        slot.put(i);
+                    }
+                    slot.put(-1);
+                } catch (InterruptedException e) {
+                    System.out.println("producer interrupted");
+                }
+            }
+        };
+        Thread consumer = new Thread() {
+            public void run() {
+                try {
+                    int value;
+                    while ((value = slot.take()) != -1) {
+                        total += value;
+                    }
+                } catch (InterruptedException e) {
+                    System.out.println("consumer interrupted");
+                }
+            }
+        };
+        producer.start();
+        consumer.start();
+        producer.join();
+        consumer.join();
+        System.out.println("total " + total);
+
+        final Counter counter = new Counter();
+        Thread[] threads = new Thread[4];
+        for (int i = 0; i < threads.length; i++) {
+            threads[i] = new Thread() {
+                public void run() {
+                    for (int j = 0; j < 1000; j++) {
+                        counter.increment();
+                    }
+                }
+            };
+            threads[i].start();
+        }
+        for (Thread thread : threads) {
+            thread.join();
+        }
+        System.out.println("count " + counter.get());
+
+        Object lock = new Object();
+        try {
+            lock.wait();
+        } catch (IllegalMonitorStateException e) {
+            System.out.println("wait: IllegalMonitorStateException");
+        }
+        try {
+            lock.notify();
+        } catch (IllegalMonitorStateException e) {
+            System.out.println("notify: IllegalMonitorStateException");
+        }
+        synchronized (lock) {
+            System.out.println("holds lock " + Thread.holdsLock(lock));
+            synchronized (lock) {
+                System.out.println("reentered " + Thread.holdsLock(lock));
+            }
+            lock.notifyAll();
+        }
+        System.out.println("holds lock " + Thread.holdsLock(lock));
+
+        final Object waitLock = new Object();
+        Thread waiter = new Thread() {
+            public void run() {
+                synchronized (waitLock) {
+                    try {
+                        waitLock.wait();
+                        System.out.println("woken up");
+                    } catch (InterruptedException e) {
+                        System.out.println("wait interrupted, interrupted flag " + isInterrupted());
+                    }
+                }
+            }
+        };
+        waiter.start();
+        Thread.sleep(50);
+        waiter.interrupt();
+        waiter.join();
+
+        Thread sleeper = new Thread() {
+            public void run() {
+                try {
+                    Thread.sleep(10000);
+                    System.out.println("slept");
+                } catch (InterruptedException e) {
+                    System.out.println("sleep interrupted");
+                }
+            }
+        };
+        sleeper.start();
+        sleeper.interrupt();
+        sleeper.join();
+
+        Thread.currentThread().interrupt();
+        System.out.println("interrupted " + Thread.interrupted());
+        System.out.println("interrupted " + Thread.interrupted());
+        long start = System.currentTimeMillis();
+        Thread.sleep(20);
+        System.out.println("slept at least 20 ms " + (System.currentTimeMillis() - start >= 20));
+    }
+}
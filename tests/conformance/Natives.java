@@ -0,0 +1,57 @@
+/**
+ * Calls the natives of the VM that the JDK uses: identity hash codes, getClass, clone and arraycopy, the raw bits of
+ * floating point numbers, primitive classes, the clocks and the functions of StrictMath. The message of a NullPointerException comes from a native
+ * on JDK 14 and later, and a native method without an implementation throws UnsatisfiedLinkError. The expected output
+ * is from a JVM run with -XX:-ShowCodeDetailsInExceptionMessages, as the VM doesn't generate helpful messages.
+ */
+public class Natives {
+    static native void missing();
+
+    public static void main(String[] args) {
+        Object object = new Object();
+        System.out.println(object.hashCode() == System.identityHashCode(object));
+        System.out.println(object.hashCode() == object.hashCode());
+        System.out.println(object.getClass().getName() + " " + "text".getClass().getName() + " " + new int[0].getClass().getName());
+        System.out.println(int.class.getName() + " " + void.class.getName() + " " + int.class.isPrimitive());
+
+        int[] values = { 1, 2, 3, 4, 5 };
+        int[] copy = values.clone();
+        System.arraycopy(values, 0, values, 1, 4);
+        System.out.println(java.util.Arrays.toString(values) + " " + java.util.Arrays.toString(copy) + " " + (copy != values));
+        try {
+            System.arraycopy(values, 3, copy, 0, 3);
+        } catch (IndexOutOfBoundsException e) {
+            System.out.println(e.getClass().getName());
+        }
+        try {
+            System.arraycopy(new Object[] { "text", 1 }, 0, new String[2], 0, 2);
+        } catch (ArrayStoreException e) {
+            System.out.println(e.getClass().getName());
+        }
+
+        System.out.println(Integer.toHexString(Float.floatToRawIntBits(-0.0f)) + " " + Float.intBitsToFloat(0x7f800000));
+        System.out.println(Long.toHexString(Double.doubleToRawLongBits(1.0)) + " " + Double.longBitsToDouble(0x8000000000000001L));
+        long start = System.nanoTime();
+        System.out.println(System.currentTimeMillis() > 1_500_000_000_000L && System.nanoTime() >= start);
+
+        System.out.println(Math.sqrt(2.0) + " " + Math.cbrt(27.0) + " " + Math.pow(2.0, 10.0) + " " + Math.hypot(3.0, 4.0));
+        System.out.println(StrictMath.sin(1.0) + " " + StrictMath.cos(1.0) + " " + StrictMath.tan(0.5));
+        System.out.println(StrictMath.asin(0.5) + " " + StrictMath.acos(0.5) + " " + StrictMath.atan(1.0) + " " + StrictMath.atan2(1.0, 2.0));
+        System.out.println(StrictMath.exp(1.0) + " " + StrictMath.log(10.0) + " " + StrictMath.log10(1000.0));
+        System.out.println(StrictMath.sinh(1.0) + " " + StrictMath.cosh(1.0) + " " + StrictMath.tanh(0.5));
+        System.out.println(StrictMath.expm1(1e-10) + " " + StrictMath.log1p(1e-10));
+        System.out.println(StrictMath.IEEEremainder(10.0, 3.0) + " " + StrictMath.IEEEremainder(11.0, 3.0) + " " + StrictMath.IEEEremainder(-7.5, 5.0));
+
+        try {
+            Object nothing = null;
+            nothing.hashCode();
+        } catch (NullPointerException e) {
+            System.out.println("NullPointerException " + e.getMessage());
+        }
+        try {
+            missing();
+        } catch (UnsatisfiedLinkError e) {
+            System.out.println(e.getClass().getName());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Runs the arithmetic, conversion and comparison opcodes on edge cases and prints the results.
+// Floats and doubles are printed as their bits, and all NaNs as NaN, as the bits of a NaN may differ.
+//
+// The expected output comes from a reference JVM:
+//     javac --release 8 Numerics.java && java Numerics > Numerics.expected
+public class Numerics {
+    static final int[] INTS = { 0, 1, -1, 2, 7, -7, 31, 32, 33, -33, 0x12345678, Integer.MAX_VALUE, Integer.MIN_VALUE };
+    static final long[] LONGS = { 0L, 1L, -1L, 7L, -7L, 63L, 64L, 65L, 0x123456789ABCDEFL, Long.MAX_VALUE, Long.MIN_VALUE };
+    static final float[] FLOATS = { 0.0f, -0.0f, 1.0f, -1.0f, 1.5f, -2.5f, 0.1f, 7.0f, 3.0e9f, -3.0e9f, 1.0e19f,
+        Float.MAX_VALUE, Float.MIN_VALUE, Float.POSITIVE_INFINITY, Float.NEGATIVE_INFINITY, Float.NaN };
+    static final double[] DOUBLES = { 0.0, -0.0, 1.0, -1.0, 1.5, -2.5, 0.1, 7.0, 3.0e9, -3.0e9, 9.3e18, 1.0e19,
+        Double.MAX_VALUE, Double.MIN_VALUE, Double.POSITIVE_INFINITY, Double.NEGATIVE_INFINITY, Double.NaN };
+
+    static String f(float value) {
+        return value != value ? "NaN" : Integer.toHexString(Float.floatToRawIntBits(value));
+    }
+
+    static String d(double value) {
+        if (value != value) {
+            return "NaN";
+        }
+        long bits = Double.doubleToRawLongBits(value);
+        return Integer.toHexString((int) (bits >>> 32)) + ":" + Integer.toHexString((int) bits);
+    }
+
+    static int iadd(int a, int b) { return a + b; }
+    static int isub(int a, int b) { return a - b; }
+    static int imul(int a, int b) { return a * b; }
+    static int idiv(int a, int b) { return a / b; }
+    static int irem(int a, int b) { return a % b; }
+    static int ineg(int a) { return -a; }
+    static int ishl(int a, int b) { return a << b; }
+    static int ishr(int a, int b) { return a >> b; }
+    static int iushr(int a, int b) { return a >>> b; }
+    static int iand(int a, int b) { return a & b; }
+    static int ior(int a, int b) { return a | b; }
+    static int ixor(int a, int b) { return a ^ b; }
+
+    static long ladd(long a, long b) { return a + b; }
+    static long lsub(long a, long b) { return a - b; }
+    static long lmul(long a, long b) { return a * b; }
+    static long ldiv(long a, long b) { return a / b; }
+    static long lrem(long a, long b) { return a % b; }
+    static long lneg(long a) { return -a; }
+    static long lshl(long a, int b) { return a << b; }
+    static long lshr(long a, int b) { return a >> b; }
+    static long lushr(long a, int b) { return a >>> b; }
+    static long land(long a, long b) { return a & b; }
+    static long lor(long a, long b) { return a | b; }
+    static long lxor(long a, long b) { return a ^ b; }
+
+    static String intResults(int a, int b) {
+        String division;
+        try {
+            division = idiv(a, b) + " " + irem(a, b);
+        } catch (ArithmeticException e) {
+            division = "ArithmeticException: " + e.getMessage();
+        }
+        return iadd(a, b) + " " + isub(a, b) + " " + imul(a, b) + " " + division + " " + ishl(a, b) + " " + ishr(a, b)
+            + " " + iushr(a, b) + " " + iand(a, b) + " " + ior(a, b) + " " + ixor(a, b);
+    }
+
+    static String longResults(long a, long b) {
+        String division;
+        try {
+            division = ldiv(a, b) + " " + lrem(a, b);
+        } catch (ArithmeticException e) {
+            division = "ArithmeticException: " + e.getMessage();
+        }
+        int shift = (int) b;
+        return ladd(a, b) + " " + lsub(a, b) + " " + lmul(a, b) + " " + division + " " + lshl(a, shift) + " "
+            + lshr(a, shift) + " " + lushr(a, shift) + " " + land(a, b) + " " + lor(a, b) + " " + lxor(a, b);
+    }
+
+    // javac compiles < and <= to fcmpg, and > , >= and == to fcmpl
+    static String floatResults(float a, float b) {
+        return f(a + b) + " " + f(a - b) + " " + f(a * b) + " " + f(a / b) + " " + f(a % b) + " "
+            + (a < b) + " " + (a <= b) + " " + (a > b) + " " + (a >= b) + " " + (a == b);
+    }
+
+    static String doubleResults(double a, double b) {
+        return d(a + b) + " " + d(a - b) + " " + d(a * b) + " " + d(a / b) + " " + d(a % b) + " "
+            + (a < b) + " " + (a <= b) + " " + (a > b) + " " + (a >= b) + " " + (a == b);
+    }
+
+    static int lcmp(long a, long b) {
+        return a < b ? -1 : a > b ? 1 : 0;
+    }
+
+    static int iinc(int a) {
+        a += 100;
+        a -= 7;
+        a += 1000;
+        return a;
+    }
+
+    public static void main(String[] args) {
+        for (int a : INTS) {
+            System.out.println("int " + a + ": " + ineg(a) + " " + iinc(a) + " " + (long) a + " " + f((float) a) + " "
+                + d((double) a) + " " + (byte) a + " " + (int) (char) a + " " + (short) a);
+            for (int b : INTS) {
+                System.out.println("int " + a + " " + b + ": " + intResults(a, b));
+            }
+        }
+        for (long a : LONGS) {
+            System.out.println("long " + a + ": " + lneg(a) + " " + (int) a + " " + f((float) a) + " " + d((double) a));
+            for (long b : LONGS) {
+                System.out.println("long " + a + " " + b + ": " + longResults(a, b) + " " + lcmp(a, b));
+            }
+        }
+        for (float a : FLOATS) {
+            System.out.println("float " + f(a) + ": " + f(-a) + " " + (int) a + " " + (long) a + " " + d((double) a));
+            for (float b : FLOATS) {
+                System.out.println("float " + f(a) + " " + f(b) + ": " + floatResults(a, b));
+            }
+        }
+        for (double a : DOUBLES) {
+            System.out.println("double " + d(a) + ": " + d(-a) + " " + (int) a + " " + (long) a + " " + f((float) a));
+            for (double b : DOUBLES) {
+                System.out.println("double " + d(a) + " " + d(b) + ": " + doubleResults(a, b));
+            }
+        }
+    }
+}
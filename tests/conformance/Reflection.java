@@ -0,0 +1,64 @@
+import java.lang.reflect.Field;
+import java.lang.reflect.Method;
+import java.lang.reflect.RecordComponent;
+import java.util.Arrays;
+
+public class Reflection {
+    record Point(int x, int y) {
+        int sum() {
+            return x + y;
+        }
+    }
+
+    static class Base {
+        public int value = 7;
+        protected String label = "base";
+
+        public int twice(int n) {
+            return 2 * n;
+        }
+    }
+
+    static final class Derived extends Base {
+        private long count = 42L;
+
+        public String describe(String prefix) {
+            return prefix + ":" + label;
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        Class<?> derived = Class.forName("Reflection$Derived");
+        System.out.println(derived.getName());
+        System.out.println(derived.getSuperclass().getName());
+        System.out.println(derived.isInstance(new Derived()));
+        System.out.println(derived.isInstance(new Base()));
+        System.out.println(Base.class.isAssignableFrom(derived));
+        System.out.println(derived.isAssignableFrom(Base.class));
+
+        String[] methods = Arrays.stream(derived.getDeclaredMethods()).map(Method::getName).sorted().toArray(String[]::new);
+        System.out.println(String.join(",", methods));
+        String[] fields = Arrays.stream(Base.class.getDeclaredFields()).map(Field::getName).sorted().toArray(String[]::new);
+        System.out.println(String.join(",", fields));
+
+        Method twice = derived.getMethod("twice", int.class);
+        System.out.println(twice.invoke(new Derived(), 21));
+        Method describe = derived.getDeclaredMethod("describe", String.class);
+        System.out.println(describe.invoke(new Derived(), "derived"));
+        Field count = derived.getDeclaredField("count");
+        count.setAccessible(true);
+        System.out.println(count.get(new Derived()));
+        System.out.println(Base.class.getField("value").get(new Derived()));
+
+        System.out.println(Point.class.isRecord());
+        System.out.println(derived.isRecord());
+        System.out.println(Record.class.isRecord());
+        System.out.println(derived.getRecordComponents() == null);
+        Point point = new Point(3, 4);
+        for (RecordComponent component : Point.class.getRecordComponents()) {
+            System.out.println(component.getName() + " " + component.getType() + " " + component.getAccessor().invoke(point));
+        }
+        System.out.println(point);
+        System.out.println(point.sum());
+    }
+}
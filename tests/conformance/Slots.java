@@ -0,0 +1,42 @@
+/**
+ * Reuses local variable slots for values of other types, in blocks and in the handlers of try statements. It is
+ * compiled with -g:none, so the decompiler has to name the variables after their slots.
+ */
+public class Slots {
+    static int parse(String text) {
+        try {
+            return Integer.parseInt(text);
+        } catch (NumberFormatException e) {
+            return -1;
+        }
+    }
+
+    public static void main(String[] args) {
+        {
+            String text = "12";
+            System.out.println(parse(text));
+        }
+        {
+            StringBuilder builder = new StringBuilder("ab");
+            builder.append('c');
+            System.out.println(builder);
+        }
+        try {
+            Object value = args.length > 0 ? args[0] : null;
+            System.out.println(value.hashCode());
+        } catch (NullPointerException e) {
+            System.out.println("no value");
+        } catch (RuntimeException e) {
+            System.out.println(e.getMessage());
+        }
+        for (int i = 0; i < 2; i++) {
+            String line = "line " + i;
+            System.out.println(line);
+        }
+        try {
+            throw new IllegalStateException("state");
+        } catch (IllegalStateException exception) {
+            System.out.println(exception.getMessage());
+        }
+    }
+}
@@ -0,0 +1,17 @@
+/**
+ * Calls into StackShapesCode, which is assembled from StackShapesCode.j because javac doesn't leave values on the
+ * operand stack across branches:
+ * - sum(n) keeps the running sum on the stack through the loop.
+ * - pick(b) has a String or an Integer on the stack where the branches join.
+ * - subtract(l, i) moves a long under an int with dup2_x1 and pop2.
+ */
+public class StackShapes {
+    public static void main(String[] args) {
+        System.out.println(StackShapesCode.sum(10));
+        System.out.println(StackShapesCode.sum(0));
+        System.out.println(StackShapesCode.pick(true));
+        System.out.println(StackShapesCode.pick(false));
+        System.out.println(StackShapesCode.subtract(100, 1));
+        System.out.println(StackShapesCode.subtract(Long.MIN_VALUE, 1));
+    }
+}
@@ -0,0 +1,36 @@
+/**
+ * Creates strings from constants with ldc: Latin-1 and UTF-16 text, which the JDK stores in different layouts,
+ * supplementary characters, an unpaired surrogate that only modified UTF-8 can encode, and interned literals that are
+ * the same object in every class.
+ */
+public class Strings {
+    static class Other {
+        static final String GREETING = "hello";
+
+        static String greeting() {
+            return "hello";
+        }
+    }
+
+    static String codeUnits(String text) {
+        StringBuilder units = new StringBuilder();
+        for (int i = 0; i < text.length(); i++) {
+            units.append((int) text.charAt(i)).append(' ');
+        }
+        return units.toString().trim();
+    }
+
+    public static void main(String[] args) {
+        String[] texts = { "", "plain", "caf\u00e9 \u00ff", "\u20ac \u4e2d\u6587", "\ud83d\ude00", "x\ud800y", "\udc00", "nul\u0000" };
+        for (String text : texts) {
+            System.out.println(text.length() + ": " + codeUnits(text));
+        }
+        System.out.println("x\ud800y".charAt(1) == 0xD800);
+        System.out.println("hello" == Other.greeting());
+        System.out.println("hello" == Other.GREETING);
+        String built = new StringBuilder("hel").append("lo").toString();
+        System.out.println(built == "hello");
+        System.out.println(built.intern() == "hello");
+        System.out.println(built.equals("hello") + " " + built.hashCode() + " " + "x\ud800y".hashCode());
+    }
+}
@@ -0,0 +1,38 @@
+/**
+ * Runs on the stub library, whose classes are implemented in Rust: String, StringBuilder, Integer, Math, System.out
+ * and the exceptions that Integer.parseInt and String.charAt throw.
+ */
+public class StubLibrary {
+    public static void main(String[] args) {
+        String text = "  Stub Library  ".trim();
+        System.out.println(text + " " + text.length() + " " + text.toUpperCase() + " " + text.toLowerCase());
+        System.out.println(text.substring(5) + " " + text.indexOf('L') + " " + text.lastIndexOf("b") + " " + text.contains("Lib"));
+        System.out.println(text.replace('b', 'p') + " " + text.replace("Stub", "Tiny"));
+        System.out.println(text.startsWith("Stub") + " " + text.endsWith("ary") + " " + text.equalsIgnoreCase("STUB LIBRARY"));
+        System.out.println("apple".compareTo("banana") + " " + "b".compareTo("a") + " " + "same".hashCode());
+        System.out.println(String.valueOf(new char[] { 'o', 'k' }) + " " + String.valueOf(true) + " " + String.valueOf(1.5));
+
+        StringBuilder builder = new StringBuilder("built");
+        builder.insert(0, "re").append('-').append(7).append(8L).append(true).append(2.5f).append(0.25);
+        System.out.println(builder + " " + builder.length() + " " + builder.indexOf("-"));
+        builder.setLength(7);
+        builder.setCharAt(0, 'R');
+        System.out.println(builder.reverse() + " " + builder.deleteCharAt(0).delete(0, 2));
+
+        System.out.println(Integer.parseInt("-123") + Integer.parseInt("ff", 16) + " " + (Integer.valueOf(127) == Integer.valueOf(127)));
+        System.out.println(Integer.toHexString(-1) + " " + Integer.toBinaryString(10) + " " + Integer.toOctalString(8) + " " + Integer.toString(35, 36));
+        System.out.println(Integer.MAX_VALUE + 1 + " " + Integer.bitCount(255) + " " + Integer.signum(-9) + " " + Integer.compare(3, 4));
+        System.out.println(Math.max(3, 9) + " " + Math.min(-0.0, 0.0) + " " + Math.abs(-7L) + " " + Math.round(2.5) + " " + Math.round(-2.5f));
+        System.out.println(Math.sqrt(2) + " " + Math.pow(2, 10) + " " + Math.floor(-1.5) + " " + Math.ceil(-1.5) + " " + Math.hypot(3, 4));
+        try {
+            Integer.parseInt("twelve");
+        } catch (NumberFormatException e) {
+            System.out.println("NumberFormatException " + e.getMessage());
+        }
+        try {
+            text.charAt(40);
+        } catch (StringIndexOutOfBoundsException e) {
+            System.out.println("StringIndexOutOfBoundsException");
+        }
+    }
+}
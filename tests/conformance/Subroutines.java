@@ -0,0 +1,18 @@
+/**
+ * Calls into two class files of version 49, which javac can't produce anymore, assembled from the .j files next to
+ * this one. Both have a method
+ * `static int run(int)` that stores its argument in local 1, and calls a subroutine with jsr before returning local 1:
+ * - SubroutinesValid: the subroutine keeps its return address in local 2, adds 10 to local 1 and returns with ret 2.
+ * - SubroutinesBroken: the subroutine returns with ret 1, through the int in local 1.
+ */
+public class Subroutines {
+    public static void main(String[] args) {
+        System.out.println(SubroutinesValid.run(5));
+        System.out.println(SubroutinesValid.run(-10));
+        try {
+            System.out.println(SubroutinesBroken.run(5));
+        } catch (VerifyError e) {
+            System.out.println("VerifyError");
+        }
+    }
+}
@@ -0,0 +1,23 @@
+/**
+ * Prints through System.out of the JDK, which only works after System.initPhase1 set up the properties and the
+ * streams on top of FileOutputStream, and reads the properties it set.
+ */
+public class SystemInit {
+    public static void main(String[] args) {
+        System.out.println("Hello, World");
+        System.out.print("no newline, ");
+        System.out.println(42);
+        System.out.printf("%s and %s%n", "formatted", "printed");
+        // Numbers are formatted with the symbols of the CLDR locale provider
+        System.out.println(String.format("%d", 1));
+        System.out.printf("%,d items at %.2f, %08.3f, %x%n", 12345, 3.14159, -2.5, 255);
+        System.out.println(System.getProperty("file.separator") + " " + System.getProperty("path.separator"));
+        System.out.println(System.lineSeparator().equals("\n"));
+        System.out.println(System.getProperty("java.home") != null);
+        System.out.println(System.getProperty("no.such.property", "default"));
+        System.setProperty("custom.property", "set");
+        System.out.println(System.getProperty("custom.property"));
+        System.out.println(System.out.getClass().getName() + " " + (System.in != null) + " " + (System.err != null));
+        System.out.flush();
+    }
+}
@@ -0,0 +1,128 @@
+public class TypeChecks {
+    interface Shape {
+        int sides();
+    }
+
+    interface Polygon extends Shape {
+    }
+
+    static class Triangle implements Polygon {
+        public int sides() {
+            return 3;
+        }
+    }
+
+    static class Square implements Polygon {
+        public int sides() {
+            return 4;
+        }
+    }
+
+    static class Circle implements Shape {
+        public int sides() {
+            return 0;
+        }
+    }
+
+    static class Failure extends RuntimeException {
+    }
+
+    static String cast(Object object) {
+        try {
+            Polygon polygon = (Polygon) object;
+            return polygon == null ? "null polygon" : "polygon " + polygon.sides();
+        } catch (ClassCastException e) {
+            return "not a polygon";
+        }
+    }
+
+    static String kind(Object object) {
+        if (object instanceof Triangle) {
+            return "triangle";
+        } else if (object instanceof Polygon) {
+            return "polygon";
+        } else if (object instanceof Shape) {
+            return "shape";
+        } else if (object instanceof Object[]) {
+            return "object array";
+        } else if (object instanceof int[]) {
+            return "int array";
+        } else if (object instanceof String) {
+            return "string";
+        }
+        return "other";
+    }
+
+    static void store(Object[] array, Object value) {
+        try {
+            array[0] = value;
+            System.out.println("stored " + kind(value));
+        } catch (ArrayStoreException e) {
+            System.out.println("ArrayStoreException " + kind(value));
+        }
+    }
+
+    static void throwing(int which) {
+        try {
+            if (which == 0) {
+                throw new Failure();
+            } else if (which == 1) {
+                throw new IllegalStateException();
+            }
+            throw new Error();
+        } catch (Failure e) {
+            System.out.println("caught Failure");
+        } catch (RuntimeException e) {
+            System.out.println("caught RuntimeException");
+        } catch (Throwable e) {
+            System.out.println("caught Throwable");
+        }
+    }
+
+    public static void main(String[] args) {
+        Object[] objects = {new Triangle(), new Square(), new Circle(), "text", new int[1], new Shape[1], null};
+        // Repeats the checks with the classes varying, so that a cached result for a class isn't reused for another
+        for (int round = 0; round < 3; round++) {
+            for (Object object : objects) {
+                System.out.println(cast(object) + " / " + kind(object) + " / " + (object instanceof Shape));
+            }
+        }
+
+        Object triangles = new Triangle[2][3];
+        System.out.println(triangles instanceof Object[]);
+        System.out.println(triangles instanceof Object[][]);
+        System.out.println(triangles instanceof Shape[][]);
+        System.out.println(triangles instanceof Polygon[][]);
+        System.out.println(triangles instanceof Square[][]);
+        System.out.println(triangles instanceof Cloneable[]);
+        System.out.println(triangles instanceof java.io.Serializable);
+        Object matrix = new int[2][2];
+        System.out.println(matrix instanceof Object[]);
+        System.out.println(matrix instanceof int[][]);
+        System.out.println(matrix instanceof long[][]);
+        System.out.println(((Object[]) matrix)[0] instanceof int[]);
+        try {
+            Shape[][] shapes = (Shape[][]) triangles;
+            System.out.println("cast to Shape[][] " + shapes.length + " " + shapes[0].length);
+            Object[][][] deeper = (Object[][][]) triangles;
+            System.out.println("cast to Object[][][]");
+        } catch (ClassCastException e) {
+            System.out.println("ClassCastException");
+        }
+
+        Shape[] shapes = new Polygon[1];
+        store(shapes, new Triangle());
+        store(shapes, new Square());
+        store(shapes, new Circle());
+        store(new Object[1], "text");
+        store(new String[1], "text");
+        store(new String[1], new Triangle());
+        store(new Object[1][], new int[2][]);
+        store(new int[1][], new long[1]);
+        store(new Object[1], null);
+
+        for (int which = 0; which < 3; which++) {
+            throwing(which);
+        }
+    }
+}
@@ -0,0 +1,45 @@
+public class Verification {
+    static class Loads {
+        // The class file in this directory has the iload_0 of this method replaced by aload_0
+        static int next(int value) {
+            return value + 1;
+        }
+    }
+
+    static class Frames {
+        // The class file in this directory has the type of the stack map frame of this method changed to the unknown 9
+        static String sign(int value) {
+            return value < 0 ? "negative" : "positive";
+        }
+    }
+
+    static class Valid {
+        static String describe(Object value) {
+            if (value == null) {
+                return "null";
+            }
+            return value instanceof Integer ? "int " + value : "object";
+        }
+    }
+
+    public static void main(String[] args) {
+        // With an argument the error is left uncaught, to see its message
+        if (args.length > 0) {
+            System.out.println(Frames.sign(1));
+        }
+        System.out.println(Valid.describe(null));
+        System.out.println(Valid.describe(42));
+        for (int attempt = 0; attempt < 2; attempt++) {
+            try {
+                System.out.println(Loads.next(41));
+            } catch (VerifyError e) {
+                System.out.println("VerifyError on attempt " + attempt);
+            }
+        }
+        try {
+            System.out.println(Frames.sign(1));
+        } catch (VerifyError | ClassFormatError e) {
+            System.out.println(e.getClass().getName());
+        }
+    }
+}
@@ -0,0 +1,18 @@
+package shop;
+
+import shop.model.Item;
+import shop.model.Order;
+import shop.service.Checkout;
+
+/**
+ * The classes that the `dependencies` test runs `deps` on. They make up the module shop, where shop.service uses
+ * legacy.Tax, but legacy/Tax.class is left out, so it isn't found.
+ */
+public class Main {
+    public static void main(String[] args) {
+        Order order = new Order(2);
+        order.add(new Item("pen", 3));
+        order.add(new Item("book", 12));
+        System.out.println(new Checkout().receipt(order));
+    }
+}
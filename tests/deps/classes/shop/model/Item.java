@@ -0,0 +1,19 @@
+package shop.model;
+
+public class Item {
+    private final String name;
+    private final int price;
+
+    public Item(String name, int price) {
+        this.name = name;
+        this.price = price;
+    }
+
+    public String getName() {
+        return name;
+    }
+
+    public int getPrice() {
+        return price;
+    }
+}
@@ -0,0 +1,21 @@
+package shop.model;
+
+public class Order {
+    private final Item[] items;
+    private int count;
+
+    public Order(int capacity) {
+        items = new Item[capacity];
+    }
+
+    public void add(Item item) {
+        if (count == items.length) {
+            throw new IllegalStateException("order is full");
+        }
+        items[count++] = item;
+    }
+
+    public Item[] getItems() {
+        return items.clone();
+    }
+}
@@ -0,0 +1,18 @@
+package shop.service;
+
+import legacy.Tax;
+import shop.model.Item;
+import shop.model.Order;
+
+public class Checkout {
+    public String receipt(Order order) {
+        StringBuilder receipt = new StringBuilder();
+        int total = 0;
+        for (Item item : order.getItems()) {
+            receipt.append(item.getName()).append(' ').append(item.getPrice()).append('\n');
+            total += item.getPrice();
+        }
+        total += Tax.on(total, Tax.rate);
+        return receipt.append("total ").append(Math.max(total, 0)).toString();
+    }
+}
@@ -0,0 +1,9 @@
+package legacy;
+
+public class Tax {
+    public static int rate = 21;
+
+    public static int on(int amount, int rate) {
+        return amount * rate / 100;
+    }
+}
@@ -0,0 +1,11 @@
+package api;
+
+/**
+ * The new version of the library that the `compatibility` test compares with the one in tests/diff/old. name moved
+ * here from Shapes.
+ */
+public class Base {
+    public String name() {
+        return "base";
+    }
+}
@@ -0,0 +1,5 @@
+package api;
+
+public abstract class Callback {
+    public abstract void call();
+}
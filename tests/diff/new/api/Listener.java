@@ -0,0 +1,7 @@
+package api;
+
+public interface Listener {
+    void changed();
+
+    void closed();
+}
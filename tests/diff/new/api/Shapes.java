@@ -0,0 +1,22 @@
+package api;
+
+public class Shapes extends Base {
+    public static final int VERSION = 2;
+    public final int count = 0;
+    public int total;
+
+    protected void resize(int factor) {
+    }
+
+    public void hook() {
+    }
+
+    public final void paint() {
+    }
+
+    public static final void helper() {
+    }
+
+    public void fill() {
+    }
+}
@@ -0,0 +1,6 @@
+package api;
+
+public final class Widget extends Base {
+    public void changed() {
+    }
+}
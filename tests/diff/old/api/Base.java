@@ -0,0 +1,8 @@
+package api;
+
+/**
+ * The old version of the library that the `compatibility` test compares with the one in tests/diff/new. Each class
+ * has changes of one kind, and the expected report is in tests/diff/expected.txt.
+ */
+public class Base {
+}
@@ -0,0 +1,5 @@
+package api;
+
+public interface Callback {
+    void call();
+}
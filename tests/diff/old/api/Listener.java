@@ -0,0 +1,5 @@
+package api;
+
+public interface Listener {
+    void changed();
+}
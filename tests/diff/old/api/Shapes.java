@@ -0,0 +1,29 @@
+package api;
+
+public class Shapes extends Base {
+    public static final int VERSION = 1;
+    public int count;
+    public static int total;
+
+    public void draw() {
+    }
+
+    public void resize(int factor) {
+    }
+
+    protected void hook() {
+    }
+
+    public void paint() {
+    }
+
+    public static void helper() {
+    }
+
+    void internal() {
+    }
+
+    public String name() {
+        return "shapes";
+    }
+}
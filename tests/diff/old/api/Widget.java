@@ -0,0 +1,6 @@
+package api;
+
+public class Widget extends Base implements Listener {
+    public void changed() {
+    }
+}